@@ -0,0 +1,292 @@
+use crate::config::{parse_var_or, parse_var_with};
+use crate::utils::normalize_user_id;
+use anyhow::{bail, Result};
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tonic::body::BoxBody;
+use tonic::Status;
+use tower::{Layer, Service};
+
+/// Header clients send their API key in, `authorization: Bearer <key>` is accepted as well
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// What an API key may do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Scope {
+    /// Market data, and the orders, trades and balances of users
+    Read,
+    /// Placing and canceling orders
+    Trade,
+    /// Requesting withdrawals, kept apart from `trade` so a trading bot's key cannot move funds
+    /// out
+    Withdraw,
+    /// Markets, maintenance, treasury and back-office wallet operations. Grants every other
+    /// scope too.
+    Admin,
+}
+
+impl FromStr for Scope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "read" => Ok(Scope::Read),
+            "trade" => Ok(Scope::Trade),
+            "withdraw" => Ok(Scope::Withdraw),
+            "admin" => Ok(Scope::Admin),
+            other => Err(format!("Unknown scope {}", other)),
+        }
+    }
+}
+
+/// Who a request was authenticated as. [`Auth`] puts it in the extensions of every request it
+/// lets through, for handlers to check the user a request acts for with [`authorize_user`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Principal {
+    /// An admin key, which acts for every user
+    Admin,
+    /// A key bound to the one user it acts for
+    User(String),
+    /// A read key bound to no user, which reads market data but no user's orders or balances
+    MarketData,
+}
+
+impl Principal {
+    /// Checks the key may act for `user_id`.
+    #[allow(clippy::result_large_err)]
+    pub fn authorize_user(&self, user_id: &str) -> Result<(), Status> {
+        match self {
+            Principal::Admin => Ok(()),
+            Principal::User(bound) if normalize_user_id(user_id).is_ok_and(|id| id == *bound) => {
+                Ok(())
+            }
+            Principal::User(bound) => Err(Status::permission_denied(format!(
+                "API key acts for user {} only",
+                bound
+            ))),
+            Principal::MarketData => {
+                Err(Status::permission_denied("API key is not bound to a user"))
+            }
+        }
+    }
+}
+
+/// Checks the key `request` was authenticated with may act for `user_id`: an admin key for
+/// every user, any other key only for the user it is bound to. Requests that went through no
+/// auth carry no principal and are let through, as auth is off for them.
+#[allow(clippy::result_large_err)]
+pub fn authorize_user<T>(request: &tonic::Request<T>, user_id: &str) -> Result<(), Status> {
+    authorize_principal(request.extensions().get(), user_id)
+}
+
+/// Like [`authorize_user`], for the principal of a request that is already taken apart, as when
+/// the user is only known once the order or trade the request names is read.
+#[allow(clippy::result_large_err)]
+pub fn authorize_principal(principal: Option<&Principal>, user_id: &str) -> Result<(), Status> {
+    principal.map_or(Ok(()), |principal| principal.authorize_user(user_id))
+}
+
+#[derive(Debug, Clone)]
+struct ApiKey {
+    scopes: HashSet<Scope>,
+    /// User the key acts for, `None` for admin and market data keys
+    user_id: Option<String>,
+}
+
+/// API keys a server accepts, each with its scopes and the user it acts for
+#[derive(Debug, Clone, Default)]
+pub struct ApiKeys {
+    keys: HashMap<String, ApiKey>,
+}
+
+impl ApiKeys {
+    /// Keys from `API_KEYS`, checked by [`ApiKeys::require`] against `AUTH_DISABLED`.
+    pub fn load() -> Result<Self> {
+        parse_var_with("API_KEYS", Self::parse)?
+            .unwrap_or_default()
+            .require(parse_var_or("AUTH_DISABLED", false)?)
+    }
+
+    /// A server without any key would take every request unauthenticated, so that is refused
+    /// unless `auth_disabled` asks for it. Both at once is refused too, as one of them is a
+    /// mistake.
+    pub fn require(self, auth_disabled: bool) -> Result<Self> {
+        match (self.is_empty(), auth_disabled) {
+            (true, false) => {
+                bail!("API_KEYS is not set, set AUTH_DISABLED=true to serve without auth")
+            }
+            (false, true) => bail!("AUTH_DISABLED=true cannot be set along with API_KEYS"),
+            _ => Ok(self),
+        }
+    }
+
+    /// Parses `key:scope,scope:user_id;key:scope`, e.g. `k1:read,trade:42;k2:admin`. Every
+    /// key but admin and read-only ones must name the user it acts for.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut keys = HashMap::new();
+        for entry in spec.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+            let mut parts = entry.splitn(3, ':');
+            let key = parts.next().unwrap_or_default().trim();
+            let scopes = parts.next().ok_or_else(|| {
+                "An API key needs its scopes, as key:scope,scope:user_id".to_string()
+            })?;
+            let scopes = scopes
+                .split(',')
+                .map(Scope::from_str)
+                .collect::<Result<HashSet<_>, _>>()?;
+            if key.is_empty() {
+                return Err("An API key cannot be empty".to_string());
+            }
+            let user_id = parts
+                .next()
+                .map(|user_id| {
+                    normalize_user_id(user_id)
+                        .map_err(|e| format!("Invalid user of API key {}: {}", key, e))
+                })
+                .transpose()?;
+            let user_id = match (scopes.contains(&Scope::Admin), user_id) {
+                (true, Some(_)) => {
+                    return Err(format!("Admin API key {} acts for every user", key))
+                }
+                (false, None) if scopes.iter().any(|scope| *scope != Scope::Read) => {
+                    return Err(format!(
+                        "API key {} needs the user it acts for, as key:scope,scope:user_id",
+                        key
+                    ))
+                }
+                (_, user_id) => user_id,
+            };
+            keys.insert(key.to_string(), ApiKey { scopes, user_id });
+        }
+        Ok(Self { keys })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Checks that `key` exists and holds `scope`, returning who it authenticates.
+    #[allow(clippy::result_large_err)]
+    pub fn authorize(&self, key: Option<&str>, scope: Scope) -> Result<Principal, Status> {
+        let key = key.ok_or_else(|| Status::unauthenticated("Missing API key"))?;
+        let key = self
+            .keys
+            .get(key)
+            .ok_or_else(|| Status::unauthenticated("Invalid API key"))?;
+        if !key.scopes.contains(&scope) && !key.scopes.contains(&Scope::Admin) {
+            return Err(Status::permission_denied(format!(
+                "API key lacks the {:?} scope",
+                scope
+            )));
+        }
+        Ok(match (&key.user_id, key.scopes.contains(&Scope::Admin)) {
+            (_, true) => Principal::Admin,
+            (Some(user_id), false) => Principal::User(user_id.clone()),
+            (None, false) => Principal::MarketData,
+        })
+    }
+}
+
+/// Name of the method a gRPC request calls, the part of `/package.Service/Method` after the
+/// last slash
+pub fn grpc_method(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// API key a request carries, in the [`API_KEY_HEADER`] or as a bearer token
+pub fn request_api_key(headers: &http::HeaderMap) -> Option<&str> {
+    headers
+        .get(API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .or_else(|| {
+            headers
+                .get(http::header::AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix("Bearer "))
+        })
+}
+
+/// Authenticates every request of a gRPC server by API key and checks it holds the scope of
+/// the method it calls. `method_scope` gives the scope of a method name, `None` for methods
+/// anybody may call. Requests it lets through carry the [`Principal`] they were authenticated
+/// as. Without any key configured, which [`ApiKeys::load`] only allows with `AUTH_DISABLED`,
+/// auth is off and every request goes through.
+///
+/// A layer rather than a tonic interceptor, since interceptors are not told the method.
+#[derive(Clone)]
+pub struct AuthLayer {
+    keys: Arc<ApiKeys>,
+    method_scope: fn(&str) -> Option<Scope>,
+}
+
+impl AuthLayer {
+    pub fn new(keys: ApiKeys, method_scope: fn(&str) -> Option<Scope>) -> Self {
+        Self {
+            keys: Arc::new(keys),
+            method_scope,
+        }
+    }
+}
+
+impl<S> Layer<S> for AuthLayer {
+    type Service = Auth<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Auth {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Auth<S> {
+    inner: S,
+    layer: AuthLayer,
+}
+
+impl<S> Auth<S> {
+    #[allow(clippy::result_large_err)]
+    fn check<B>(&self, request: &http::Request<B>) -> Result<Option<Principal>, Status> {
+        if self.layer.keys.is_empty() {
+            return Ok(None);
+        }
+        let Some(scope) = (self.layer.method_scope)(grpc_method(request.uri().path())) else {
+            return Ok(None);
+        };
+        self.layer
+            .keys
+            .authorize(request_api_key(request.headers()), scope)
+            .map(Some)
+    }
+}
+
+impl<S, B> Service<http::Request<B>> for Auth<S>
+where
+    S: Service<http::Request<B>, Response = http::Response<BoxBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: http::Request<B>) -> Self::Future {
+        match self.check(&request) {
+            Ok(principal) => {
+                if let Some(principal) = principal {
+                    request.extensions_mut().insert(principal);
+                }
+                Box::pin(self.inner.call(request))
+            }
+            Err(status) => Box::pin(async move { Ok(status.into_http()) }),
+        }
+    }
+}
@@ -0,0 +1,145 @@
+//! Settings of the services, each read from its environment variable when set, else from the
+//! config file given with `--config`, else the service's default.
+//!
+//! The config file is TOML with the environment variable names as keys, in lower case and
+//! optionally grouped in tables: `[rate_limit]` with `orders_per_sec = 50` sets what
+//! `RATE_LIMIT_ORDERS_PER_SEC` would. Arrays are joined the way their variable lists values,
+//! see [`list_separator`].
+
+use anyhow::{anyhow, Context, Result};
+use config::{Config, File, Map, Value, ValueKind};
+use std::collections::HashMap;
+use std::env;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::Duration;
+use structopt::StructOpt;
+
+static FILE_SETTINGS: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+/// Separator of the values a list setting holds: `;` between the entries of `API_KEYS`,
+/// whose own scopes are comma separated, and `,` for any other list
+pub fn list_separator(name: &str) -> &'static str {
+    match name {
+        "API_KEYS" => ";",
+        _ => ",",
+    }
+}
+
+/// Command line options every service takes
+#[derive(Debug, Default, StructOpt)]
+pub struct ConfigOptions {
+    /// TOML file of settings, environment variables take precedence over it
+    #[structopt(long, parse(from_os_str))]
+    pub config: Option<PathBuf>,
+}
+
+impl ConfigOptions {
+    /// Loads the config file, if one was given
+    pub fn load(&self) -> Result<()> {
+        match &self.config {
+            Some(path) => load_config_file(path),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Makes the settings of the config file at `path` the fallback of every setting read from
+/// then on. Only one file can be loaded.
+pub fn load_config_file(path: &Path) -> Result<()> {
+    let settings = read_config_file(path)?;
+    FILE_SETTINGS
+        .set(settings)
+        .map_err(|_| anyhow!("A config file is already loaded"))
+}
+
+/// The settings of the config file at `path`, by environment variable name
+pub fn read_config_file(path: &Path) -> Result<HashMap<String, String>> {
+    let table = Config::builder()
+        .add_source(File::from(path))
+        .build()
+        .and_then(|config| config.try_deserialize::<Map<String, Value>>())
+        .with_context(|| format!("Failed to read config file {}", path.display()))?;
+    let mut settings = HashMap::new();
+    flatten("", table, &mut settings)?;
+    Ok(settings)
+}
+
+fn flatten(
+    prefix: &str,
+    table: Map<String, Value>,
+    settings: &mut HashMap<String, String>,
+) -> Result<()> {
+    for (key, value) in table {
+        let name = match prefix {
+            "" => key.to_uppercase(),
+            _ => format!("{}_{}", prefix, key.to_uppercase()),
+        };
+        match value.kind {
+            ValueKind::Table(table) => flatten(&name, table, settings)?,
+            ValueKind::Array(values) => {
+                let values = values
+                    .into_iter()
+                    .map(Value::into_string)
+                    .collect::<Result<Vec<_>, _>>()
+                    .with_context(|| format!("Invalid {} in config file", name))?;
+                let values = values.join(list_separator(&name));
+                settings.insert(name, values);
+            }
+            ValueKind::Nil => {}
+            kind => {
+                let value = Value::new(None, kind)
+                    .into_string()
+                    .with_context(|| format!("Invalid {} in config file", name))?;
+                settings.insert(name, value);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The value of setting `name`, from its environment variable or else the config file
+pub fn var(name: &str) -> Option<String> {
+    env::var(name)
+        .ok()
+        .or_else(|| FILE_SETTINGS.get()?.get(name).cloned())
+}
+
+/// Parses setting `name`, `None` when it is set nowhere. A value that does not parse is an
+/// error naming the setting, rather than the default taking its place.
+pub fn parse_var<T>(name: &str) -> Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    parse_var_with(name, str::parse::<T>)
+}
+
+/// Same as [`parse_var`], with `parse` turning the value into a `T`
+pub fn parse_var_with<T, E: Display>(
+    name: &str,
+    parse: impl FnOnce(&str) -> Result<T, E>,
+) -> Result<Option<T>> {
+    var(name)
+        .map(|value| parse(&value).map_err(|e| anyhow!("Invalid {} `{}`: {}", name, value, e)))
+        .transpose()
+}
+
+/// Same as [`parse_var`], with `default` for a setting set nowhere
+pub fn parse_var_or<T>(name: &str, default: T) -> Result<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    Ok(parse_var(name)?.unwrap_or(default))
+}
+
+/// A duration setting in milliseconds, which has to be positive
+pub fn parse_interval_ms(name: &str, default_ms: u64) -> Result<Duration> {
+    match parse_var_or(name, default_ms)? {
+        0 => Err(anyhow!("{} must be positive", name)),
+        interval_ms => Ok(Duration::from_millis(interval_ms)),
+    }
+}
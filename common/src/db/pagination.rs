@@ -4,6 +4,8 @@ pub struct Pagination {
     pub offset: Option<i64>,
     pub order_by: Option<String>, // Allow ordering by different fields
     pub order_direction: Option<String>, // "asc" or "desc"
+    /// Continues after a cursor instead of skipping `offset` rows, where the listing supports it
+    pub cursor: Option<Cursor>,
 }
 
 impl Pagination {
@@ -13,13 +15,69 @@ impl Pagination {
             offset: Some(0),  // Default offset
             order_by: Some("created_at".to_string()),
             order_direction: Some("desc".to_string()),
+            cursor: None,
         }
     }
+
+    /// Column to sort by, `default` when none is given
+    pub fn sort_field<'a>(&'a self, default: &'a str) -> &'a str {
+        self.order_by
+            .as_deref()
+            .filter(|field| !field.is_empty())
+            .unwrap_or(default)
+    }
+
+    /// Whether to sort descending, the default, or ascending. `None` for any other direction.
+    pub fn is_descending(&self) -> Option<bool> {
+        let direction = self.order_direction.as_deref().map(str::to_lowercase);
+        match direction.as_deref() {
+            None | Some("") | Some("desc") => Some(true),
+            Some("asc") => Some(false),
+            Some(_) => None,
+        }
+    }
+}
+
+/// Position in a listing sorted newest first, as the time and id of the last item seen. The
+/// next page holds the items sorted after it, which the database finds through an index
+/// however deep the page is, where an offset has it walk every row before.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cursor {
+    pub timestamp: i64,
+    pub id: String,
+}
+
+impl Cursor {
+    pub fn new(timestamp: i64, id: impl Into<String>) -> Self {
+        Self {
+            timestamp,
+            id: id.into(),
+        }
+    }
+
+    /// The cursor as clients see it, `<timestamp>:<id>`. They are not meant to build one.
+    pub fn encode(&self) -> String {
+        format!("{}:{}", self.timestamp, self.id)
+    }
+
+    pub fn decode(cursor: &str) -> Result<Self, String> {
+        let (timestamp, id) = cursor
+            .split_once(':')
+            .filter(|(_, id)| !id.is_empty())
+            .ok_or_else(|| format!("Invalid cursor {}", cursor))?;
+        let timestamp = timestamp
+            .parse()
+            .map_err(|_| format!("Invalid cursor {}", cursor))?;
+        Ok(Self::new(timestamp, id))
+    }
 }
 
+#[derive(Debug)]
 pub struct Paginated<T> {
     pub items: Vec<T>,
     pub total_count: i64,
     pub next_offset: Option<i64>,
     pub has_more: bool,
+    /// Cursor of the last item, set when there is more to list and the listing supports cursors
+    pub next_cursor: Option<Cursor>,
 }
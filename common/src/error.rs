@@ -0,0 +1,135 @@
+use bigdecimal::BigDecimal;
+use prost::Message;
+use tonic::metadata::MetadataValue;
+use tonic::{Code, Status};
+use tracing::error;
+
+/// Metadata key every status made from a [`BitradeError`] carries its code under
+pub const ERROR_CODE_METADATA: &str = "x-error-code";
+
+/// Failures the services report to clients. Each maps to a gRPC code and carries a
+/// machine-readable code, so clients need not parse messages to tell them apart.
+///
+/// `Internal` is everything else. Its message stays in the server log and the client is only
+/// told something went wrong, since it may hold SQL or other details of the backend.
+#[derive(Debug, thiserror::Error)]
+pub enum BitradeError {
+    #[error("Insufficient {asset} balance: {required} required, {available} available")]
+    InsufficientBalance {
+        asset: String,
+        required: BigDecimal,
+        available: BigDecimal,
+    },
+    #[error("Market {0} not found")]
+    MarketNotFound(String),
+    #[error("Order {0} not found")]
+    OrderNotFound(String),
+    #[error("{field} ({value}) is below the market minimum ({minimum})")]
+    BelowMinimum {
+        field: String,
+        value: BigDecimal,
+        minimum: BigDecimal,
+    },
+    #[error("{field} ({value}) has more than {precision} decimal places")]
+    InvalidPrecision {
+        field: String,
+        value: BigDecimal,
+        precision: i32,
+    },
+    #[error("{0}")]
+    RiskLimitExceeded(String),
+    #[error("{0}")]
+    InvalidArgument(String),
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    AlreadyExists(String),
+    #[error("{0}")]
+    PermissionDenied(String),
+    #[error("{0}")]
+    FailedPrecondition(String),
+    #[error("{0}")]
+    Unavailable(String),
+    #[error("Internal error")]
+    Internal(#[source] anyhow::Error),
+}
+
+impl BitradeError {
+    /// The machine-readable code, stable across releases
+    pub fn code(&self) -> &'static str {
+        match self {
+            BitradeError::InsufficientBalance { .. } => "INSUFFICIENT_FUNDS",
+            BitradeError::MarketNotFound(_) => "MARKET_NOT_FOUND",
+            BitradeError::OrderNotFound(_) => "ORDER_NOT_FOUND",
+            BitradeError::BelowMinimum { .. } => "BELOW_MINIMUM",
+            BitradeError::InvalidPrecision { .. } => "INVALID_PRECISION",
+            BitradeError::RiskLimitExceeded(_) => "RISK_LIMIT_EXCEEDED",
+            BitradeError::InvalidArgument(_) => "INVALID_ARGUMENT",
+            BitradeError::NotFound(_) => "NOT_FOUND",
+            BitradeError::AlreadyExists(_) => "ALREADY_EXISTS",
+            BitradeError::PermissionDenied(_) => "PERMISSION_DENIED",
+            BitradeError::FailedPrecondition(_) => "FAILED_PRECONDITION",
+            BitradeError::Unavailable(_) => "UNAVAILABLE",
+            BitradeError::Internal(_) => "INTERNAL",
+        }
+    }
+
+    pub fn grpc_code(&self) -> Code {
+        match self {
+            BitradeError::InsufficientBalance { .. } | BitradeError::FailedPrecondition(_) => {
+                Code::FailedPrecondition
+            }
+            BitradeError::MarketNotFound(_)
+            | BitradeError::OrderNotFound(_)
+            | BitradeError::NotFound(_) => Code::NotFound,
+            BitradeError::BelowMinimum { .. }
+            | BitradeError::InvalidPrecision { .. }
+            | BitradeError::InvalidArgument(_) => Code::InvalidArgument,
+            BitradeError::RiskLimitExceeded(_) => Code::ResourceExhausted,
+            BitradeError::AlreadyExists(_) => Code::AlreadyExists,
+            BitradeError::PermissionDenied(_) => Code::PermissionDenied,
+            BitradeError::Unavailable(_) => Code::Unavailable,
+            BitradeError::Internal(_) => Code::Internal,
+        }
+    }
+
+    /// The error itself when `e` is one, `Internal` otherwise
+    pub fn from_anyhow(e: anyhow::Error) -> Self {
+        e.downcast::<BitradeError>()
+            .unwrap_or_else(BitradeError::Internal)
+    }
+}
+
+/// Details of a status made from a [`BitradeError`], encoded in its `details`
+#[derive(Clone, PartialEq, Message)]
+pub struct ErrorDetail {
+    #[prost(string, tag = "1")]
+    pub code: String,
+}
+
+impl From<BitradeError> for Status {
+    fn from(e: BitradeError) -> Self {
+        if let BitradeError::Internal(source) = &e {
+            error!("Internal error: {:?}", source);
+        }
+        let details = ErrorDetail {
+            code: e.code().to_string(),
+        }
+        .encode_to_vec();
+        let mut status = Status::with_details(e.grpc_code(), e.to_string(), details.into());
+        with_error_code(&mut status, e.code());
+        status
+    }
+}
+
+/// Adds `code` to the metadata of a status that carries other details
+pub fn with_error_code(status: &mut Status, code: &'static str) {
+    status
+        .metadata_mut()
+        .insert(ERROR_CODE_METADATA, MetadataValue::from_static(code));
+}
+
+/// Status for a failure no handler told apart, hiding it unless it is a [`BitradeError`]
+pub fn internal_status(e: anyhow::Error) -> Status {
+    BitradeError::from_anyhow(e).into()
+}
@@ -1,2 +1,10 @@
+pub mod auth;
+pub mod config;
 pub mod db;
+pub mod error;
+pub mod logging;
+pub mod maintenance;
+pub mod metrics;
+pub mod rate_limit;
+pub mod rounding;
 pub mod utils;
@@ -0,0 +1,24 @@
+use tracing_subscriber::EnvFilter;
+
+use crate::config;
+
+/// Installs the process-wide tracing subscriber, after the config file is loaded for it to
+/// take its settings from.
+///
+/// Levels come from `RUST_LOG` (`info` when unset), e.g. `RUST_LOG=info,bitrade=debug`.
+/// `LOG_FORMAT=json` writes one JSON object per event, with the fields of the spans it
+/// happened in, instead of text lines. Records of crates still using `log` are forwarded.
+pub fn init_tracing() {
+    let filter = config::var("RUST_LOG")
+        .and_then(|directives| EnvFilter::try_new(directives).ok())
+        .unwrap_or_else(|| EnvFilter::new("info"));
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+    match config::var("LOG_FORMAT").as_deref() {
+        Some("json") => subscriber
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .init(),
+        _ => subscriber.init(),
+    }
+}
@@ -0,0 +1,61 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tonic::metadata::MetadataValue;
+use tonic::Status;
+
+/// Seconds clients are asked to wait before retrying while maintenance is on.
+pub const DEFAULT_RETRY_AFTER_SECS: u64 = 30;
+
+/// Shared maintenance switch for gRPC services.
+///
+/// Clones share the same flag, so a single instance can be handed to every service of a
+/// server and toggled from its admin endpoint.
+#[derive(Debug, Clone)]
+pub struct MaintenanceMode {
+    enabled: Arc<AtomicBool>,
+    retry_after_secs: u64,
+}
+
+impl Default for MaintenanceMode {
+    fn default() -> Self {
+        Self::new(DEFAULT_RETRY_AFTER_SECS)
+    }
+}
+
+impl MaintenanceMode {
+    pub fn new(retry_after_secs: u64) -> Self {
+        Self {
+            enabled: Arc::new(AtomicBool::new(false)),
+            retry_after_secs,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::SeqCst)
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::SeqCst);
+    }
+
+    pub fn retry_after_secs(&self) -> u64 {
+        self.retry_after_secs
+    }
+
+    /// Returns `Status::unavailable` with a `retry-after` hint while maintenance is on.
+    #[allow(clippy::result_large_err)]
+    pub fn check(&self) -> Result<(), Status> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+
+        let mut status = Status::unavailable(format!(
+            "Service is under maintenance, retry after {} seconds",
+            self.retry_after_secs
+        ));
+        status
+            .metadata_mut()
+            .insert("retry-after", MetadataValue::from(self.retry_after_secs));
+        Err(status)
+    }
+}
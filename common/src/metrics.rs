@@ -0,0 +1,119 @@
+use axum::http::header::CONTENT_TYPE;
+use axum::routing::get;
+use axum::Router;
+use prometheus::{
+    register_histogram_vec, register_int_gauge_vec, Encoder, HistogramVec, IntGaugeVec, TextEncoder,
+};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, LazyLock};
+use std::task::{Context, Poll};
+use std::time::Instant;
+use tower::{Layer, Service};
+
+/// Time to the response headers of each gRPC method. A streaming call is timed up to its
+/// first message, not for as long as it stays open.
+pub static GRPC_REQUEST_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "bitrade_grpc_request_duration_seconds",
+        "Time to answer a gRPC request, by method",
+        &["method"]
+    )
+    .unwrap()
+});
+
+/// Connections of the database pool: open ones, idle ones and the most it opens
+pub static DB_POOL_CONNECTIONS: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!(
+        "bitrade_db_pool_connections",
+        "Connections of the database pool, by state",
+        &["state"]
+    )
+    .unwrap()
+});
+
+/// Records the state of a database pool, see [`DB_POOL_CONNECTIONS`].
+pub fn observe_db_pool(connections: u32, idle: u32, max: u32) {
+    DB_POOL_CONNECTIONS
+        .with_label_values(&["active"])
+        .set(i64::from(connections - idle.min(connections)));
+    DB_POOL_CONNECTIONS
+        .with_label_values(&["idle"])
+        .set(i64::from(idle));
+    DB_POOL_CONNECTIONS
+        .with_label_values(&["max"])
+        .set(i64::from(max));
+}
+
+/// Every registered metric in the Prometheus text format
+pub fn encode_metrics() -> String {
+    let mut buffer = Vec::new();
+    // Writing to a Vec only fails on a metric that is invalid to begin with
+    let _ = TextEncoder::new().encode(&prometheus::gather(), &mut buffer);
+    String::from_utf8(buffer).unwrap_or_default()
+}
+
+/// Serves `/metrics` on `address` until the server fails. `on_scrape` runs before each
+/// scrape, to sample what is not recorded as it happens.
+pub async fn serve_metrics(
+    address: String,
+    on_scrape: impl Fn() + Send + Sync + 'static,
+) -> std::io::Result<()> {
+    let on_scrape = Arc::new(on_scrape);
+    let router = Router::new().route(
+        "/metrics",
+        get(move || {
+            let on_scrape = on_scrape.clone();
+            async move {
+                on_scrape();
+                ([(CONTENT_TYPE, prometheus::TEXT_FORMAT)], encode_metrics())
+            }
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind(&address).await?;
+    axum::serve(listener, router).await
+}
+
+/// Times the requests of a gRPC server into [`GRPC_REQUEST_DURATION`]
+#[derive(Debug, Clone, Default)]
+pub struct GrpcMetricsLayer;
+
+impl<S> Layer<S> for GrpcMetricsLayer {
+    type Service = GrpcMetrics<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        GrpcMetrics { inner }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GrpcMetrics<S> {
+    inner: S,
+}
+
+impl<S, B> Service<http::Request<B>> for GrpcMetrics<S>
+where
+    S: Service<http::Request<B>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        let method = request.uri().path().to_string();
+        let started = Instant::now();
+        let response = self.inner.call(request);
+        Box::pin(async move {
+            let response = response.await;
+            GRPC_REQUEST_DURATION
+                .with_label_values(&[&method])
+                .observe(started.elapsed().as_secs_f64());
+            response
+        })
+    }
+}
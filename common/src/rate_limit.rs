@@ -0,0 +1,243 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tonic::body::BoxBody;
+use tonic::metadata::MetadataValue;
+use tonic::transport::server::TcpConnectInfo;
+use tonic::Status;
+use tower::{Layer, Service};
+
+use crate::auth::{grpc_method, request_api_key};
+use crate::config::parse_var_or;
+
+/// Buckets kept before the full ones, of clients gone quiet, are dropped
+const MAX_IDLE_BUCKETS: usize = 10_000;
+
+/// What a rate limited request counts against
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RateClass {
+    Order,
+    Cancel,
+    Query,
+}
+
+/// Requests per second each client may make of each class, 0 for no limit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RateLimits {
+    pub orders_per_sec: u32,
+    pub cancels_per_sec: u32,
+    pub queries_per_sec: u32,
+}
+
+impl RateLimits {
+    /// Reads `RATE_LIMIT_ORDERS_PER_SEC`, `RATE_LIMIT_CANCELS_PER_SEC` and
+    /// `RATE_LIMIT_QUERIES_PER_SEC`, each unlimited when unset
+    pub fn load() -> Result<Self> {
+        Ok(Self {
+            orders_per_sec: parse_var_or("RATE_LIMIT_ORDERS_PER_SEC", 0)?,
+            cancels_per_sec: parse_var_or("RATE_LIMIT_CANCELS_PER_SEC", 0)?,
+            queries_per_sec: parse_var_or("RATE_LIMIT_QUERIES_PER_SEC", 0)?,
+        })
+    }
+
+    pub fn per_sec(&self, class: RateClass) -> u32 {
+        match class {
+            RateClass::Order => self.orders_per_sec,
+            RateClass::Cancel => self.cancels_per_sec,
+            RateClass::Query => self.queries_per_sec,
+        }
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.orders_per_sec == 0 && self.cancels_per_sec == 0 && self.queries_per_sec == 0
+    }
+}
+
+/// Holds up to a second's worth of requests and refills continuously
+#[derive(Debug, Clone)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn full(rate: f64, now: Instant) -> Self {
+        Self {
+            tokens: rate,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, rate: f64, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(rate);
+        self.updated = now;
+    }
+
+    /// Takes a token, or says how long until one is there
+    fn take(&mut self, rate: f64, now: Instant) -> Result<(), Duration> {
+        self.refill(rate, now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / rate))
+        }
+    }
+}
+
+/// Token buckets of every client, one per class of request
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    limits: RateLimits,
+    buckets: Mutex<HashMap<(String, RateClass), TokenBucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(limits: RateLimits) -> Self {
+        Self {
+            limits,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn limits(&self) -> RateLimits {
+        self.limits
+    }
+
+    /// Counts a request of `client` against its `class` bucket. A client over its limit gets
+    /// `RESOURCE_EXHAUSTED`, with `retry-after` in whole seconds and `retry-after-ms` saying
+    /// when the next request goes through.
+    #[allow(clippy::result_large_err)]
+    pub fn check(&self, client: &str, class: RateClass, now: Instant) -> Result<(), Status> {
+        let per_sec = self.limits.per_sec(class);
+        if per_sec == 0 {
+            return Ok(());
+        }
+        let rate = f64::from(per_sec);
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_IDLE_BUCKETS {
+            let limits = self.limits;
+            buckets.retain(|(_, class), bucket| {
+                let rate = f64::from(limits.per_sec(*class));
+                bucket.refill(rate, now);
+                bucket.tokens < rate
+            });
+        }
+        let wait = match buckets.get_mut(&(client.to_string(), class)) {
+            Some(bucket) => bucket.take(rate, now),
+            None => {
+                let mut bucket = TokenBucket::full(rate, now);
+                let taken = bucket.take(rate, now);
+                buckets.insert((client.to_string(), class), bucket);
+                taken
+            }
+        };
+        drop(buckets);
+
+        wait.map_err(|wait| {
+            let retry_after_ms = wait.as_millis().max(1) as u64;
+            let mut status = Status::resource_exhausted(format!(
+                "Rate limit of {} {:?} requests per second exceeded, retry after {} ms",
+                per_sec, class, retry_after_ms
+            ));
+            let metadata = status.metadata_mut();
+            metadata.insert(
+                "retry-after",
+                MetadataValue::from(retry_after_ms.div_ceil(1000)),
+            );
+            metadata.insert("retry-after-ms", MetadataValue::from(retry_after_ms));
+            status
+        })
+    }
+}
+
+/// Who a request is rate limited as: its API key when it carries one, its peer address
+/// otherwise
+pub fn request_client<B>(request: &http::Request<B>) -> String {
+    if let Some(key) = request_api_key(request.headers()) {
+        return format!("key:{}", key);
+    }
+    request
+        .extensions()
+        .get::<TcpConnectInfo>()
+        .and_then(TcpConnectInfo::remote_addr)
+        .map(|address| format!("ip:{}", address.ip()))
+        .unwrap_or_else(|| "ip:unknown".to_string())
+}
+
+/// Rate limits every request of a gRPC server by client, see [`request_client`].
+/// `method_class` gives the class of a method name, `None` for methods that are never
+/// limited. A request opening a stream counts once, and so does a batch.
+#[derive(Clone)]
+pub struct RateLimitLayer {
+    limiter: Arc<RateLimiter>,
+    method_class: fn(&str) -> Option<RateClass>,
+}
+
+impl RateLimitLayer {
+    pub fn new(limiter: Arc<RateLimiter>, method_class: fn(&str) -> Option<RateClass>) -> Self {
+        Self {
+            limiter,
+            method_class,
+        }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimit {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimit<S> {
+    inner: S,
+    layer: RateLimitLayer,
+}
+
+impl<S> RateLimit<S> {
+    #[allow(clippy::result_large_err)]
+    fn check<B>(&self, request: &http::Request<B>) -> Result<(), Status> {
+        if self.layer.limiter.limits().is_unlimited() {
+            return Ok(());
+        }
+        let method = grpc_method(request.uri().path());
+        let Some(class) = (self.layer.method_class)(method) else {
+            return Ok(());
+        };
+        self.layer
+            .limiter
+            .check(&request_client(request), class, Instant::now())
+    }
+}
+
+impl<S, B> Service<http::Request<B>> for RateLimit<S>
+where
+    S: Service<http::Request<B>, Response = http::Response<BoxBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        match self.check(&request) {
+            Ok(()) => Box::pin(self.inner.call(request)),
+            Err(status) => Box::pin(async move { Ok(status.into_http()) }),
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use anyhow::{anyhow, Result};
+use bigdecimal::{BigDecimal, RoundingMode};
+use std::sync::RwLock;
+
+/// Decimal places amounts are stored with, matching the `DECIMAL(30, 8)` columns
+pub const DEFAULT_ROUNDING_SCALE: i64 = 8;
+
+/// How a decimal is brought down to a fixed number of decimal places
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rounding {
+    /// Drops the extra digits, rounding toward zero
+    Truncate,
+    /// Rounds ties away from zero
+    HalfUp,
+    /// Rounds ties to the even neighbour (banker's rounding)
+    HalfEven,
+}
+
+impl Rounding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Rounding::Truncate => "TRUNCATE",
+            Rounding::HalfUp => "HALF_UP",
+            Rounding::HalfEven => "HALF_EVEN",
+        }
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "TRUNCATE" => Ok(Rounding::Truncate),
+            "HALF_UP" => Ok(Rounding::HalfUp),
+            "HALF_EVEN" => Ok(Rounding::HalfEven),
+            _ => Err(anyhow!("Invalid rounding mode: {}", s)),
+        }
+    }
+
+    fn mode(&self) -> RoundingMode {
+        match self {
+            Rounding::Truncate => RoundingMode::Down,
+            Rounding::HalfUp => RoundingMode::HalfUp,
+            Rounding::HalfEven => RoundingMode::HalfEven,
+        }
+    }
+}
+
+/// Rounding applied to order amounts, fees and settlements across the crates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RoundingConfig {
+    pub mode: Rounding,
+    /// Decimal places used when the caller does not ask for a scale of its own
+    pub default_scale: i64,
+}
+
+impl RoundingConfig {
+    pub const DEFAULT: RoundingConfig = RoundingConfig {
+        mode: Rounding::HalfUp,
+        default_scale: DEFAULT_ROUNDING_SCALE,
+    };
+
+    /// Rounds `value` to the default scale.
+    pub fn round(&self, value: &BigDecimal) -> BigDecimal {
+        self.round_to_scale(value, self.default_scale)
+    }
+
+    /// Rounds `value` to `scale` decimal places. Values that already fit are left untouched.
+    pub fn round_to_scale(&self, value: &BigDecimal, scale: i64) -> BigDecimal {
+        value.with_scale_round(scale, self.mode.mode())
+    }
+}
+
+impl Default for RoundingConfig {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+static ROUNDING_CONFIG: RwLock<RoundingConfig> = RwLock::new(RoundingConfig::DEFAULT);
+
+/// Returns the rounding the process currently uses.
+pub fn rounding_config() -> RoundingConfig {
+    *ROUNDING_CONFIG.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Sets the rounding used by every helper in [`crate::utils`]. Meant to be called once at
+/// startup, before any order is processed.
+pub fn set_rounding_config(config: RoundingConfig) {
+    *ROUNDING_CONFIG.write().unwrap_or_else(|e| e.into_inner()) = config;
+}
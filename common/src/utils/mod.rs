@@ -1,4 +1,5 @@
-use anyhow::{anyhow, Context, Result};
+use crate::rounding::rounding_config;
+use anyhow::{anyhow, bail, Context, Result};
 use bigdecimal::BigDecimal;
 use chrono::Utc;
 use std::str::FromStr;
@@ -16,18 +17,42 @@ pub fn get_uuid_string() -> String {
 }
 
 pub fn is_zero(value: &BigDecimal) -> bool {
-    value.with_prec(8) == BigDecimal::from(0)
+    value.with_prec(8) == 0
 }
 
 pub fn is_zero_with_precision(value: &BigDecimal, precision: u64) -> bool {
-    value.with_prec(precision) == BigDecimal::from(0)
+    value.with_prec(precision) == 0
+}
+
+/// Rounds `value` to `precision` decimal places with the configured rounding mode.
+pub fn round_to_precision(value: &BigDecimal, precision: i32) -> BigDecimal {
+    rounding_config().round_to_scale(value, precision as i64)
+}
+
+/// Rounds an amount to the configured default scale with the configured rounding mode.
+pub fn round_amount(value: &BigDecimal) -> BigDecimal {
+    rounding_config().round(value)
+}
+
+/// Renders `value` rounded to `scale` decimal places, in plain notation and without trailing
+/// zeros: `1.50000000` becomes `1.5`, `1E-7` becomes `0.0000001` and `100` stays `100`.
+pub fn format_decimal(value: &BigDecimal, scale: i64) -> String {
+    let rounded = rounding_config().round_to_scale(value, scale).normalized();
+    if rounded == 0 {
+        return "0".to_string();
+    }
+    rounded.to_plain_string()
+}
+
+/// Renders an amount for API responses, trimmed to the configured default scale.
+pub fn format_amount(value: &BigDecimal) -> String {
+    format_decimal(value, rounding_config().default_scale)
 }
 
 pub fn validate_positive_decimal(value: &str, field_name: &str) -> Result<BigDecimal> {
-    let decimal = BigDecimal::from_str(value)
-        .context(format!("Failed to parse {} as decimal", field_name))?;
+    let decimal = parse_decimal(value, field_name, false)?;
 
-    if decimal <= BigDecimal::from(0) {
+    if decimal <= 0 {
         return Err(anyhow!("{} must be greater than zero", field_name));
     }
 
@@ -35,8 +60,83 @@ pub fn validate_positive_decimal(value: &str, field_name: &str) -> Result<BigDec
 }
 
 pub fn bigdecimal_from_str(value: &str, field_name: &str) -> Result<BigDecimal> {
-    let decimal = BigDecimal::from_str(value)
-        .context(format!("Failed to parse {} as decimal", field_name))?;
+    parse_decimal(value, field_name, false)
+}
 
-    Ok(decimal)
+/// Digit group separators clients commonly send, as in `1_000` or `1,000`
+pub const DIGIT_SEPARATORS: [char; 2] = ['_', ','];
+
+/// Parses a decimal, telling apart input that is only malformed by digit separators.
+///
+/// With `strip_separators` the separators are dropped before parsing. Without it, and for any
+/// other character that cannot appear in a decimal, the error names the offending characters.
+pub fn parse_decimal(value: &str, field_name: &str, strip_separators: bool) -> Result<BigDecimal> {
+    let mut offending: Vec<char> = Vec::new();
+    for c in value.chars() {
+        let allowed = c.is_ascii_digit()
+            || matches!(c, '.' | '-' | '+' | 'e' | 'E')
+            || (strip_separators && DIGIT_SEPARATORS.contains(&c));
+        if !allowed && !offending.contains(&c) {
+            offending.push(c);
+        }
+    }
+
+    if !offending.is_empty() {
+        let offending = offending
+            .iter()
+            .map(|c| format!("'{}'", c))
+            .collect::<Vec<_>>()
+            .join(", ");
+        bail!(
+            "Invalid number format for {}: unexpected character(s) {} in \"{}\"",
+            field_name,
+            offending,
+            value
+        );
+    }
+
+    let cleaned: String = value
+        .chars()
+        .filter(|c| !DIGIT_SEPARATORS.contains(c))
+        .collect();
+    BigDecimal::from_str(&cleaned).context(format!("Failed to parse {} as decimal", field_name))
+}
+
+/// Longest user id the database accepts
+pub const MAX_USER_ID_LEN: usize = 36;
+
+/// Returns the canonical string form of a user id given either as a number or as a string.
+///
+/// Numeric ids drop leading zeros, so `"007"` and `7` name the same user. Other ids may only
+/// contain ASCII letters, digits, `-` and `_`.
+pub fn normalize_user_id(user_id: impl ToString) -> Result<String> {
+    let user_id = user_id.to_string();
+    let user_id = user_id.trim();
+
+    if user_id.is_empty() {
+        bail!("User ID cannot be empty");
+    }
+
+    if user_id.bytes().all(|b| b.is_ascii_digit()) {
+        let numeric_id = user_id
+            .parse::<u64>()
+            .context(format!("Numeric user ID {} is out of range", user_id))?;
+        return Ok(numeric_id.to_string());
+    }
+
+    if user_id.len() > MAX_USER_ID_LEN {
+        bail!(
+            "User ID cannot be longer than {} characters",
+            MAX_USER_ID_LEN
+        );
+    }
+
+    if !user_id
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        bail!("User ID {} contains invalid characters", user_id);
+    }
+
+    Ok(user_id.to_string())
 }
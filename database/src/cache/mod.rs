@@ -0,0 +1,37 @@
+#[cfg(feature = "redis-cache")]
+pub mod redis_cache;
+
+use bigdecimal::BigDecimal;
+use serde::{Deserialize, Serialize};
+
+/// Best levels of a market's book as the engine last mirrored them, best price first
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CachedDepth {
+    pub market_id: String,
+    pub bids: Vec<(BigDecimal, BigDecimal)>,
+    pub asks: Vec<(BigDecimal, BigDecimal)>,
+    /// Sequence of the book once the depth applied
+    pub sequence: u64,
+    pub update_time: i64,
+}
+
+/// Price of the last trade of a market
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CachedPrice {
+    pub market_id: String,
+    pub price: BigDecimal,
+    /// Execution time of the trade, in seconds like trade timestamps
+    pub trade_time: i64,
+}
+
+pub fn depth_key(market_id: &str) -> String {
+    format!("bitrade:depth:{}", market_id)
+}
+
+pub fn market_stats_key(market_id: &str) -> String {
+    format!("bitrade:stats:{}", market_id)
+}
+
+pub fn last_price_key(market_id: &str) -> String {
+    format!("bitrade:price:{}", market_id)
+}
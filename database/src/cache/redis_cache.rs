@@ -0,0 +1,75 @@
+use anyhow::{Context, Result};
+use redis::AsyncCommands;
+use redis::aio::MultiplexedConnection;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use super::{CachedDepth, CachedPrice, depth_key, last_price_key, market_stats_key};
+use crate::models::models::MarketStat;
+
+/// Market data the engine mirrors into Redis, read back by the services serving it.
+///
+/// Every value is stored as JSON under its own key. The cache holds what was last written
+/// and nothing more: a reader has to fall back to the database on a miss.
+#[derive(Clone)]
+pub struct RedisMarketCache {
+    connection: MultiplexedConnection,
+}
+
+impl RedisMarketCache {
+    /// Connects to the Redis server at `url`. The connection is shared by clones of the cache.
+    pub async fn connect(url: &str) -> Result<Self> {
+        let client =
+            redis::Client::open(url).with_context(|| format!("Invalid Redis url {}", url))?;
+        let connection = client
+            .get_multiplexed_tokio_connection()
+            .await
+            .with_context(|| format!("Failed to connect to Redis at {}", url))?;
+        Ok(Self { connection })
+    }
+
+    pub async fn set_depth(&self, depth: &CachedDepth) -> Result<()> {
+        self.set(depth_key(&depth.market_id), depth).await
+    }
+
+    pub async fn get_depth(&self, market_id: &str) -> Result<Option<CachedDepth>> {
+        self.get(depth_key(market_id)).await
+    }
+
+    pub async fn set_market_stats(&self, stats: &MarketStat) -> Result<()> {
+        self.set(market_stats_key(&stats.market_id), stats).await
+    }
+
+    pub async fn get_market_stats(&self, market_id: &str) -> Result<Option<MarketStat>> {
+        self.get(market_stats_key(market_id)).await
+    }
+
+    pub async fn set_last_price(&self, price: &CachedPrice) -> Result<()> {
+        self.set(last_price_key(&price.market_id), price).await
+    }
+
+    pub async fn get_last_price(&self, market_id: &str) -> Result<Option<CachedPrice>> {
+        self.get(last_price_key(market_id)).await
+    }
+
+    async fn set<T: Serialize>(&self, key: String, value: &T) -> Result<()> {
+        let payload = serde_json::to_string(value).context("Failed to encode cached value")?;
+        let mut connection = self.connection.clone();
+        connection
+            .set::<_, _, ()>(&key, payload)
+            .await
+            .with_context(|| format!("Failed to write {} to Redis", key))
+    }
+
+    async fn get<T: DeserializeOwned>(&self, key: String) -> Result<Option<T>> {
+        let mut connection = self.connection.clone();
+        let payload: Option<String> = connection
+            .get(&key)
+            .await
+            .with_context(|| format!("Failed to read {} from Redis", key))?;
+        payload
+            .map(|payload| serde_json::from_str(&payload))
+            .transpose()
+            .with_context(|| format!("Invalid cached value under {}", key))
+    }
+}
@@ -1,4 +1,4 @@
-#[derive(Default, Clone)]
+#[derive(Debug, Default, Clone)]
 pub struct OrderFilter {
     pub user_id: Option<String>,
     pub market_id: Option<String>,
@@ -6,6 +6,7 @@ pub struct OrderFilter {
     pub side: Option<String>,
     pub status: Option<String>,
     pub order_type: Option<String>,
+    pub client_order_id: Option<String>,
 }
 
 impl OrderFilter {
@@ -42,9 +43,14 @@ impl OrderFilter {
         self.order_type = order_type;
         self
     }
+
+    pub fn client_order_id(mut self, client_order_id: Option<String>) -> Self {
+        self.client_order_id = client_order_id;
+        self
+    }
 }
 
-#[derive(Default, Clone)]
+#[derive(Debug, Default, Clone)]
 pub struct TradeFilter {
     pub market_id: Option<String>,
     pub buyer_order_id: Option<String>,
@@ -108,7 +114,7 @@ impl TradeFilter {
     }
 }
 
-#[derive(Default, Clone)]
+#[derive(Debug, Default, Clone)]
 pub struct MarketFilter {
     pub market_id: Option<String>,
     pub market_name: Option<String>,
@@ -136,7 +142,7 @@ impl MarketFilter {
     }
 }
 
-#[derive(Default, Clone)]
+#[derive(Debug, Default, Clone)]
 pub struct WalletFilter {
     pub user_id: Option<String>,
     pub asset: Option<String>,
@@ -158,7 +164,7 @@ impl WalletFilter {
     }
 }
 
-#[derive(Default, Clone)]
+#[derive(Debug, Default, Clone)]
 pub struct FeeTreasuryFilter {
     pub market_id: Option<String>,
     pub asset: Option<String>,
@@ -180,7 +186,7 @@ impl FeeTreasuryFilter {
     }
 }
 
-#[derive(Default, Clone)]
+#[derive(Debug, Default, Clone)]
 pub struct MarketStatFilter {
     pub market_id: Option<String>,
     pub start_time: Option<i64>,
@@ -207,3 +213,49 @@ impl MarketStatFilter {
         self
     }
 }
+
+#[derive(Debug, Default, Clone)]
+pub struct LedgerFilter {
+    pub owner_id: Option<String>,
+    pub asset: Option<String>,
+    pub kind: Option<String>,
+    pub reference_id: Option<String>,
+    pub start_time: Option<i64>,
+    pub end_time: Option<i64>,
+}
+
+impl LedgerFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn owner_id(mut self, owner_id: Option<String>) -> Self {
+        self.owner_id = owner_id;
+        self
+    }
+
+    pub fn asset(mut self, asset: Option<String>) -> Self {
+        self.asset = asset;
+        self
+    }
+
+    pub fn kind(mut self, kind: Option<String>) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    pub fn reference_id(mut self, reference_id: Option<String>) -> Self {
+        self.reference_id = reference_id;
+        self
+    }
+
+    pub fn start_time(mut self, start_time: Option<i64>) -> Self {
+        self.start_time = start_time;
+        self
+    }
+
+    pub fn end_time(mut self, end_time: Option<i64>) -> Self {
+        self.end_time = end_time;
+        self
+    }
+}
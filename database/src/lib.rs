@@ -1,9 +1,16 @@
 #![recursion_limit = "512"]
 
+pub mod cache;
 pub mod filters;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
 pub mod models;
 pub mod provider;
 pub mod repository;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod tests;
 
 use diesel::pg::PgConnection;
 use diesel::r2d2::{self, ConnectionManager};
@@ -21,6 +28,28 @@ pub fn establish_connection_pool(database_url: String, pool_size: u32) -> DbPool
         .expect("Failed to create connection pool")
 }
 
+/// Tells whether `e` came from the database being out of reach rather than refusing the write:
+/// no connection to be had from the pool, a connection lost meanwhile, or a transaction that
+/// lost a serialization race. Trying again may get past these, not past the others.
+pub fn is_transient_error(e: &anyhow::Error) -> bool {
+    use diesel::result::{DatabaseErrorKind, Error};
+
+    e.chain().any(|cause| {
+        cause.is::<r2d2::PoolError>()
+            || cause.is::<diesel::ConnectionError>()
+            || matches!(
+                cause.downcast_ref::<Error>(),
+                Some(
+                    Error::DatabaseError(
+                        DatabaseErrorKind::SerializationFailure
+                            | DatabaseErrorKind::ClosedConnection,
+                        _
+                    ) | Error::BrokenTransactionManager
+                )
+            )
+    })
+}
+
 /// Get a connection from the pool
 pub fn get_connection(pool: &DbPool) -> DbConnection {
     pool.get()
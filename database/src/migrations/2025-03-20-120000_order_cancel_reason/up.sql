@@ -0,0 +1,2 @@
+-- Why an order was canceled: USER_CANCELED, UNFILLED, ADMIN_CANCEL, MARKET_CLOSED
+ALTER TABLE orders ADD COLUMN cancel_reason VARCHAR(20);
@@ -0,0 +1,23 @@
+-- Append-only log of order create and cancel requests, written before they are processed
+CREATE TABLE order_audit (
+    id BIGSERIAL PRIMARY KEY,
+    action VARCHAR(20) NOT NULL, -- 'CREATE_ORDER' or 'CANCEL_ORDER'
+    user_id VARCHAR(36), -- user named in the request, as sent
+    remote_addr VARCHAR(64), -- peer address the request came from
+    market_id VARCHAR(36) NOT NULL,
+    order_id VARCHAR(36),
+    request TEXT NOT NULL,
+    create_time BIGINT NOT NULL
+);
+
+CREATE INDEX idx_order_audit_user_id ON order_audit(user_id);
+
+CREATE OR REPLACE FUNCTION reject_order_audit_change() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'order_audit is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER order_audit_append_only
+BEFORE UPDATE OR DELETE ON order_audit
+FOR EACH ROW EXECUTE FUNCTION reject_order_audit_change();
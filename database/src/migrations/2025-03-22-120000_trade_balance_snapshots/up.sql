@@ -0,0 +1,13 @@
+-- Wallet balances of both counterparties around a trade, recorded when snapshots are enabled
+CREATE TABLE trade_balance_snapshots (
+    trade_id VARCHAR(36) NOT NULL,
+    user_id VARCHAR(36) NOT NULL,
+    asset VARCHAR(20) NOT NULL,
+    available_before DECIMAL(30, 8) NOT NULL,
+    locked_before DECIMAL(30, 8) NOT NULL,
+    available_after DECIMAL(30, 8) NOT NULL,
+    locked_after DECIMAL(30, 8) NOT NULL,
+
+    PRIMARY KEY (trade_id, user_id, asset),
+    CONSTRAINT fk_snapshot_trade FOREIGN KEY (trade_id) REFERENCES trades(id)
+);
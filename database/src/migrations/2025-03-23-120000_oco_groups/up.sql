@@ -0,0 +1,19 @@
+-- Pairs of orders where filling or canceling one leg cancels the other
+CREATE TABLE oco_groups (
+    id VARCHAR(36) PRIMARY KEY,
+    market_id VARCHAR(36) NOT NULL,
+    user_id VARCHAR(36) NOT NULL,
+    first_order_id VARCHAR(36) NOT NULL,
+    second_order_id VARCHAR(36) NOT NULL,
+    create_time BIGINT NOT NULL,
+
+    CONSTRAINT fk_market_oco FOREIGN KEY (market_id) REFERENCES markets(id),
+    CONSTRAINT fk_oco_first_order FOREIGN KEY (first_order_id) REFERENCES orders(id),
+    CONSTRAINT fk_oco_second_order FOREIGN KEY (second_order_id) REFERENCES orders(id),
+    CONSTRAINT distinct_oco_legs CHECK (first_order_id <> second_order_id)
+);
+
+-- An order belongs to one group at most
+CREATE UNIQUE INDEX idx_oco_groups_first_order ON oco_groups(first_order_id);
+CREATE UNIQUE INDEX idx_oco_groups_second_order ON oco_groups(second_order_id);
+CREATE INDEX idx_oco_groups_market_id ON oco_groups(market_id);
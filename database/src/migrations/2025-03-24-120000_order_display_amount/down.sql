@@ -0,0 +1,2 @@
+ALTER TABLE orders DROP CONSTRAINT IF EXISTS valid_display_amount;
+ALTER TABLE orders DROP COLUMN IF EXISTS display_amount;
@@ -0,0 +1,5 @@
+-- Iceberg orders show only this much of their remaining base amount in the book
+ALTER TABLE orders ADD COLUMN display_amount DECIMAL(30, 8) DEFAULT NULL;
+ALTER TABLE orders ADD CONSTRAINT valid_display_amount CHECK (
+    display_amount IS NULL OR (display_amount > 0 AND display_amount <= base_amount)
+);
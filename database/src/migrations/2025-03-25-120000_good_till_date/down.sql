@@ -0,0 +1,10 @@
+DROP INDEX IF EXISTS idx_orders_expires_at;
+
+ALTER TABLE orders DROP CONSTRAINT valid_expires_at;
+ALTER TABLE orders ADD CONSTRAINT valid_expires_at CHECK (
+    (time_in_force = 'GTC' AND expires_at IS NULL) OR
+    (time_in_force IN ('IOC', 'FOK') AND expires_at IS NOT NULL)
+);
+
+ALTER TABLE orders DROP CONSTRAINT valid_time_in_force;
+ALTER TABLE orders ADD CONSTRAINT valid_time_in_force CHECK (time_in_force IN ('GTC', 'IOC', 'FOK'));
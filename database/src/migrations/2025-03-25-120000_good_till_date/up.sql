@@ -0,0 +1,13 @@
+-- GTD orders rest like GTC ones until their expires_at
+ALTER TABLE orders DROP CONSTRAINT valid_time_in_force;
+ALTER TABLE orders ADD CONSTRAINT valid_time_in_force CHECK (time_in_force IN ('GTC', 'IOC', 'FOK', 'GTD'));
+
+ALTER TABLE orders DROP CONSTRAINT valid_expires_at;
+ALTER TABLE orders ADD CONSTRAINT valid_expires_at CHECK (
+    (time_in_force = 'GTC' AND expires_at IS NULL) OR
+    (time_in_force IN ('IOC', 'FOK', 'GTD') AND expires_at IS NOT NULL)
+);
+
+-- The expiry sweeper looks up resting orders past their expiry
+CREATE INDEX idx_orders_expires_at ON orders(market_id, expires_at)
+    WHERE expires_at IS NOT NULL AND status IN ('OPEN', 'PARTIALLY_FILLED');
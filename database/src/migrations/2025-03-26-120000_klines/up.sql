@@ -0,0 +1,37 @@
+-- OHLCV candles per market and interval, folded in as trades settle
+CREATE TABLE klines (
+    market_id VARCHAR(36) NOT NULL,
+    interval VARCHAR(3) NOT NULL,
+    -- Unix timestamp in seconds, like trades.timestamp
+    open_time BIGINT NOT NULL,
+    open DECIMAL(30, 8) NOT NULL,
+    high DECIMAL(30, 8) NOT NULL,
+    low DECIMAL(30, 8) NOT NULL,
+    close DECIMAL(30, 8) NOT NULL,
+    volume DECIMAL(30, 8) NOT NULL,
+    quote_volume DECIMAL(30, 8) NOT NULL,
+    trade_count BIGINT NOT NULL,
+
+    PRIMARY KEY (market_id, interval, open_time),
+    CONSTRAINT fk_kline_market FOREIGN KEY (market_id) REFERENCES markets(id),
+    CONSTRAINT valid_kline_interval CHECK (interval IN ('1m', '5m', '1h', '1d'))
+);
+
+-- Candles of the trades settled before this migration
+INSERT INTO klines (
+    market_id, interval, open_time, open, high, low, close, volume, quote_volume, trade_count
+)
+SELECT
+    t.market_id,
+    i.name,
+    t.timestamp - t.timestamp % i.seconds AS open_time,
+    (array_agg(t.price ORDER BY t.timestamp))[1],
+    MAX(t.price),
+    MIN(t.price),
+    (array_agg(t.price ORDER BY t.timestamp DESC))[1],
+    SUM(t.base_amount),
+    SUM(t.quote_amount),
+    COUNT(*)
+FROM trades t
+CROSS JOIN (VALUES ('1m', 60), ('5m', 300), ('1h', 3600), ('1d', 86400)) AS i(name, seconds)
+GROUP BY t.market_id, i.name, open_time;
@@ -0,0 +1,10 @@
+-- Top of book of each market as last stored by the engine, for tickers
+CREATE TABLE market_quotes (
+    market_id VARCHAR(36) PRIMARY KEY,
+    -- NULL while that side of the book is empty
+    best_bid DECIMAL(30, 8),
+    best_ask DECIMAL(30, 8),
+    update_time BIGINT NOT NULL,
+
+    CONSTRAINT fk_quote_market FOREIGN KEY (market_id) REFERENCES markets(id)
+);
@@ -0,0 +1,33 @@
+-- Double-entry log of every balance movement. Each transfer writes two rows under one
+-- transaction_id: a debit on the account the amount leaves and a credit on the one it enters,
+-- so the entries of a transaction always net to zero per asset.
+CREATE TABLE ledger_entries (
+    id BIGSERIAL PRIMARY KEY,
+    transaction_id VARCHAR(36) NOT NULL,
+    kind VARCHAR(20) NOT NULL, -- 'DEPOSIT', 'WITHDRAWAL', 'LOCK', 'UNLOCK', 'TRADE' or 'FEE'
+    owner_id VARCHAR(36) NOT NULL, -- user id, or market id for the fee treasury
+    account VARCHAR(20) NOT NULL, -- 'AVAILABLE', 'LOCKED', 'EXTERNAL' or 'FEE_TREASURY'
+    asset VARCHAR(20) NOT NULL,
+    debit DECIMAL(30, 8) NOT NULL DEFAULT 0,
+    credit DECIMAL(30, 8) NOT NULL DEFAULT 0,
+    reference_id VARCHAR(36), -- order or trade the movement belongs to
+    create_time BIGINT NOT NULL,
+
+    CONSTRAINT chk_ledger_kind CHECK (kind IN ('DEPOSIT', 'WITHDRAWAL', 'LOCK', 'UNLOCK', 'TRADE', 'FEE')),
+    CONSTRAINT chk_ledger_account CHECK (account IN ('AVAILABLE', 'LOCKED', 'EXTERNAL', 'FEE_TREASURY')),
+    CONSTRAINT chk_ledger_one_side CHECK ((debit > 0 AND credit = 0) OR (debit = 0 AND credit > 0))
+);
+
+CREATE INDEX idx_ledger_entries_owner ON ledger_entries(owner_id, asset, id);
+CREATE INDEX idx_ledger_entries_transaction ON ledger_entries(transaction_id);
+CREATE INDEX idx_ledger_entries_reference ON ledger_entries(reference_id);
+
+CREATE OR REPLACE FUNCTION reject_ledger_entry_change() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'ledger_entries is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER ledger_entries_append_only
+BEFORE UPDATE OR DELETE ON ledger_entries
+FOR EACH ROW EXECUTE FUNCTION reject_ledger_entry_change();
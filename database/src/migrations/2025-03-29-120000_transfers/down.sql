@@ -0,0 +1,5 @@
+ALTER TABLE ledger_entries DROP CONSTRAINT chk_ledger_account;
+ALTER TABLE ledger_entries ADD CONSTRAINT chk_ledger_account
+    CHECK (account IN ('AVAILABLE', 'LOCKED', 'EXTERNAL', 'FEE_TREASURY')) NOT VALID;
+
+DROP TABLE IF EXISTS transfers;
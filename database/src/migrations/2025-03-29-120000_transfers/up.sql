@@ -0,0 +1,28 @@
+-- Deposits and withdrawals moving funds in and out of the exchange. A withdrawal reserves its
+-- amount when requested and only leaves the wallet once completed.
+CREATE TABLE transfers (
+    id VARCHAR(36) PRIMARY KEY,
+    user_id VARCHAR(36) NOT NULL,
+    asset VARCHAR(20) NOT NULL,
+    kind VARCHAR(20) NOT NULL, -- 'DEPOSIT' or 'WITHDRAWAL'
+    amount DECIMAL(30, 8) NOT NULL,
+    status VARCHAR(20) NOT NULL, -- 'PENDING', 'APPROVED', 'COMPLETED' or 'REJECTED'
+    address VARCHAR(128), -- destination of a withdrawal
+    external_id VARCHAR(128), -- id of the transfer outside the exchange, e.g. a transaction hash
+    reject_reason VARCHAR(255),
+    create_time BIGINT NOT NULL,
+    update_time BIGINT NOT NULL,
+
+    CONSTRAINT chk_transfer_kind CHECK (kind IN ('DEPOSIT', 'WITHDRAWAL')),
+    CONSTRAINT chk_transfer_status CHECK (status IN ('PENDING', 'APPROVED', 'COMPLETED', 'REJECTED')),
+    CONSTRAINT chk_transfer_amount CHECK (amount > 0)
+);
+
+CREATE INDEX idx_transfers_user ON transfers(user_id, create_time);
+-- A deposit seen outside the exchange is credited once
+CREATE UNIQUE INDEX idx_transfers_external_id ON transfers(kind, asset, external_id);
+
+-- Funds of requested withdrawals sit in the reserved balance
+ALTER TABLE ledger_entries DROP CONSTRAINT chk_ledger_account;
+ALTER TABLE ledger_entries ADD CONSTRAINT chk_ledger_account
+    CHECK (account IN ('AVAILABLE', 'LOCKED', 'RESERVED', 'EXTERNAL', 'FEE_TREASURY'));
@@ -0,0 +1,16 @@
+-- Volume based maker/taker rates of a market. An order pays the rates of the tier with the
+-- highest min_volume its user's 30-day traded volume in the market reaches.
+CREATE TABLE fee_tiers (
+    market_id VARCHAR(36) NOT NULL,
+    min_volume DECIMAL(30, 8) NOT NULL, -- in the market's quote asset
+    maker_fee DECIMAL(10, 8) NOT NULL,
+    taker_fee DECIMAL(10, 8) NOT NULL,
+    create_time BIGINT NOT NULL,
+    update_time BIGINT NOT NULL,
+
+    PRIMARY KEY (market_id, min_volume),
+    CONSTRAINT fk_fee_tier_market FOREIGN KEY (market_id) REFERENCES markets(id),
+    CONSTRAINT chk_fee_tier_min_volume CHECK (min_volume >= 0),
+    CONSTRAINT chk_fee_tier_maker_fee CHECK (maker_fee >= 0),
+    CONSTRAINT chk_fee_tier_taker_fee CHECK (taker_fee >= 0)
+);
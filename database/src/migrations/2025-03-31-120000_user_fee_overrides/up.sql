@@ -0,0 +1,12 @@
+-- Maker/taker rates set by operators for particular users, e.g. market makers. They replace
+-- the fees of the user's orders on every market, ahead of any fee tier.
+CREATE TABLE user_fee_overrides (
+    user_id VARCHAR(36) PRIMARY KEY,
+    maker_fee DECIMAL(10, 8) NOT NULL,
+    taker_fee DECIMAL(10, 8) NOT NULL,
+    create_time BIGINT NOT NULL,
+    update_time BIGINT NOT NULL,
+
+    CONSTRAINT chk_user_fee_override_maker_fee CHECK (maker_fee >= 0),
+    CONSTRAINT chk_user_fee_override_taker_fee CHECK (taker_fee >= 0)
+);
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS fee_treasury_withdrawals;
+ALTER TABLE fee_treasury DROP COLUMN total_withdrawn;
@@ -0,0 +1,18 @@
+-- Collected fees swept out of a market's treasury
+ALTER TABLE fee_treasury ADD COLUMN total_withdrawn DECIMAL(30, 8) NOT NULL DEFAULT 0;
+
+CREATE TABLE fee_treasury_withdrawals (
+    id VARCHAR(36) PRIMARY KEY,
+    market_id VARCHAR(36) NOT NULL,
+    asset VARCHAR(20) NOT NULL,
+    amount DECIMAL(30, 8) NOT NULL,
+    destination VARCHAR(128) NOT NULL, -- address or account the fees were sent to
+    create_time BIGINT NOT NULL,
+
+    CONSTRAINT fk_treasury_withdrawal_treasury FOREIGN KEY (market_id, asset)
+        REFERENCES fee_treasury(market_id, asset),
+    CONSTRAINT chk_treasury_withdrawal_amount CHECK (amount > 0)
+);
+
+CREATE INDEX idx_fee_treasury_withdrawals_treasury
+    ON fee_treasury_withdrawals(market_id, asset, create_time);
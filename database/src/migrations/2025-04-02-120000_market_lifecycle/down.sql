@@ -0,0 +1,3 @@
+UPDATE markets SET status = 'SUSPENDED' WHERE status <> 'ACTIVE';
+ALTER TABLE markets DROP CONSTRAINT valid_status;
+ALTER TABLE markets ADD CONSTRAINT valid_status CHECK (status IN ('ACTIVE', 'INACTIVE', 'SUSPENDED'));
@@ -0,0 +1,5 @@
+-- Trading phases a market goes through. Markets in a status the engine never set are closed.
+UPDATE markets SET status = 'CLOSED' WHERE status NOT IN ('ACTIVE');
+ALTER TABLE markets DROP CONSTRAINT valid_status;
+ALTER TABLE markets ADD CONSTRAINT valid_status
+    CHECK (status IN ('ACTIVE', 'POST_ONLY', 'HALTED_MATCHING', 'CANCEL_ONLY', 'CLOSED'));
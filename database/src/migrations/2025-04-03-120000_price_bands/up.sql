@@ -0,0 +1,18 @@
+-- Price protection of a market. Limit orders priced more than band_percent away from the last
+-- trade are refused, and a move of more than halt_percent within halt_window_ms halts matching
+-- for halt_duration_ms. A NULL percent turns that check off.
+CREATE TABLE price_bands (
+    market_id VARCHAR(36) PRIMARY KEY,
+    band_percent DECIMAL(10, 4),
+    halt_percent DECIMAL(10, 4),
+    halt_window_ms BIGINT NOT NULL,
+    halt_duration_ms BIGINT NOT NULL,
+    create_time BIGINT NOT NULL,
+    update_time BIGINT NOT NULL,
+
+    CONSTRAINT fk_price_band_market FOREIGN KEY (market_id) REFERENCES markets(id),
+    CONSTRAINT chk_price_band_band_percent CHECK (band_percent > 0),
+    CONSTRAINT chk_price_band_halt_percent CHECK (halt_percent > 0),
+    CONSTRAINT chk_price_band_halt_window CHECK (halt_window_ms >= 0),
+    CONSTRAINT chk_price_band_halt_duration CHECK (halt_duration_ms >= 0)
+);
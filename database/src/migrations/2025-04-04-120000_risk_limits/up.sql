@@ -0,0 +1,13 @@
+-- Limits every user of a market trades under: how many orders they may keep open and how much
+-- quote value those orders may hold. A NULL limit is not enforced.
+CREATE TABLE risk_limits (
+    market_id VARCHAR(36) PRIMARY KEY,
+    max_open_orders INTEGER,
+    max_locked_notional DECIMAL(30, 8),
+    create_time BIGINT NOT NULL,
+    update_time BIGINT NOT NULL,
+
+    CONSTRAINT fk_risk_limit_market FOREIGN KEY (market_id) REFERENCES markets(id),
+    CONSTRAINT chk_risk_limit_max_open_orders CHECK (max_open_orders > 0),
+    CONSTRAINT chk_risk_limit_max_locked_notional CHECK (max_locked_notional > 0)
+);
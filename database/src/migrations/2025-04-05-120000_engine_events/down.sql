@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS engine_checkpoints;
+DROP TABLE IF EXISTS engine_events;
+DROP FUNCTION IF EXISTS reject_engine_event_change();
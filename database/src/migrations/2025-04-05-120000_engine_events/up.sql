@@ -0,0 +1,37 @@
+-- Write-ahead journal of the engine. Every change an order book makes is written here before it
+-- is applied; the payload holds what is needed to apply it again.
+CREATE TABLE engine_events (
+    sequence BIGSERIAL PRIMARY KEY,
+    market_id VARCHAR(36) NOT NULL,
+    event_type VARCHAR(20) NOT NULL,
+    order_id VARCHAR(36),
+    payload TEXT NOT NULL,
+    create_time BIGINT NOT NULL,
+
+    CONSTRAINT fk_engine_event_market FOREIGN KEY (market_id) REFERENCES markets(id),
+    CONSTRAINT chk_engine_event_type CHECK (event_type IN (
+        'ORDER_ACCEPTED', 'TRADE_EXECUTED', 'ORDER_CANCELED', 'ORDERS_CANCELED', 'ORDER_AMENDED'
+    ))
+);
+
+CREATE INDEX idx_engine_events_market_sequence ON engine_events(market_id, sequence);
+
+CREATE OR REPLACE FUNCTION reject_engine_event_change() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'engine_events is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER engine_events_append_only
+BEFORE UPDATE OR DELETE ON engine_events
+FOR EACH ROW EXECUTE FUNCTION reject_engine_event_change();
+
+-- Last journal entry of each market whose effects were applied. Entries after it were written
+-- but the engine stopped before applying them, and are replayed when the market is loaded.
+CREATE TABLE engine_checkpoints (
+    market_id VARCHAR(36) PRIMARY KEY,
+    applied_sequence BIGINT NOT NULL,
+    update_time BIGINT NOT NULL,
+
+    CONSTRAINT fk_engine_checkpoint_market FOREIGN KEY (market_id) REFERENCES markets(id)
+);
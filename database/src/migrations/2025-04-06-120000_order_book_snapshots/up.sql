@@ -0,0 +1,11 @@
+-- Latest serialized order book of each market, so a restart restores resting orders in queue
+-- order instead of matching every open order again. applied_sequence is the last engine_events
+-- entry the snapshot reflects.
+CREATE TABLE order_book_snapshots (
+    market_id VARCHAR(36) PRIMARY KEY,
+    applied_sequence BIGINT NOT NULL,
+    payload TEXT NOT NULL,
+    create_time BIGINT NOT NULL,
+
+    CONSTRAINT fk_order_book_snapshot_market FOREIGN KEY (market_id) REFERENCES markets(id)
+);
@@ -0,0 +1,16 @@
+-- Outbox of the events other services consume. Rows are written in the transaction that makes
+-- the change they describe, and published to the message broker afterwards by the engine's
+-- relay, which sets published_time once the broker has acknowledged them.
+CREATE TABLE events (
+    id BIGSERIAL PRIMARY KEY,
+    topic VARCHAR(20) NOT NULL,
+    market_id VARCHAR(36) NOT NULL,
+    payload TEXT NOT NULL,
+    create_time BIGINT NOT NULL,
+    published_time BIGINT,
+
+    CONSTRAINT fk_event_market FOREIGN KEY (market_id) REFERENCES markets(id),
+    CONSTRAINT chk_event_topic CHECK (topic IN ('TRADE', 'ORDER', 'WALLET'))
+);
+
+CREATE INDEX idx_events_unpublished ON events(id) WHERE published_time IS NULL;
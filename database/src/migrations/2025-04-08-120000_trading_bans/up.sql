@@ -0,0 +1,7 @@
+-- Users an operator barred from placing and amending orders. They can still cancel the orders
+-- they have open, and deposit or withdraw.
+CREATE TABLE trading_bans (
+    user_id VARCHAR(36) PRIMARY KEY,
+    reason TEXT NOT NULL,
+    create_time BIGINT NOT NULL
+);
@@ -0,0 +1,10 @@
+CREATE TABLE trading_bans (
+    user_id VARCHAR(36) PRIMARY KEY,
+    reason TEXT NOT NULL,
+    create_time BIGINT NOT NULL
+);
+
+INSERT INTO trading_bans (user_id, reason, create_time)
+SELECT user_id, reason, update_time FROM user_restrictions;
+
+DROP TABLE IF EXISTS user_restrictions;
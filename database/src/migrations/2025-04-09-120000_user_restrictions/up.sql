@@ -0,0 +1,13 @@
+-- What users may do, for those an operator restricted: CANCEL_ONLY users can only cancel the
+-- orders they have open, BANNED users had them canceled too. Users without a row are ACTIVE.
+CREATE TABLE user_restrictions (
+    user_id VARCHAR(36) PRIMARY KEY,
+    status VARCHAR(20) NOT NULL,
+    reason TEXT NOT NULL,
+    update_time BIGINT NOT NULL
+);
+
+INSERT INTO user_restrictions (user_id, status, reason, update_time)
+SELECT user_id, 'BANNED', reason, create_time FROM trading_bans;
+
+DROP TABLE trading_bans;
@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_trades_cursor;
+DROP INDEX IF EXISTS idx_orders_cursor;
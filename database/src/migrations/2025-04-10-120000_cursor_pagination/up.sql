@@ -0,0 +1,4 @@
+-- Keyset pages of orders and trades continue from the (time, id) of the last row of the page
+-- before, newest first
+CREATE INDEX idx_orders_cursor ON orders(create_time, id);
+CREATE INDEX idx_trades_cursor ON trades(timestamp, id);
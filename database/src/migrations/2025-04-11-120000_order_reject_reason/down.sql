@@ -0,0 +1,4 @@
+DELETE FROM orders WHERE status = 'REJECTED';
+DROP INDEX idx_user_client_order_id;
+CREATE UNIQUE INDEX idx_user_client_order_id ON orders(user_id, client_order_id) WHERE client_order_id IS NOT NULL;
+ALTER TABLE orders DROP COLUMN IF EXISTS reject_reason;
@@ -0,0 +1,7 @@
+-- Why an order was REJECTED before it reached the book: INSUFFICIENT_FUNDS, RISK_LIMIT, ...
+ALTER TABLE orders ADD COLUMN reject_reason VARCHAR(30);
+
+-- A rejected order does not take its client order id, so the user can retry under it
+DROP INDEX idx_user_client_order_id;
+CREATE UNIQUE INDEX idx_user_client_order_id ON orders(user_id, client_order_id)
+    WHERE client_order_id IS NOT NULL AND status <> 'REJECTED';
@@ -0,0 +1,11 @@
+-- Place of an order in the queue of its price level, lower first. The engine numbers orders
+-- per market as they join the book, and again when an amendment sends one to the back, so a
+-- recovered book queues them as the running one did. Existing orders queue by creation time.
+ALTER TABLE orders ADD COLUMN priority BIGINT NOT NULL DEFAULT 0;
+UPDATE orders SET priority = ranked.priority
+FROM (
+    SELECT id, ROW_NUMBER() OVER (PARTITION BY market_id ORDER BY create_time, id) AS priority
+    FROM orders
+) ranked
+WHERE orders.id = ranked.id;
+ALTER TABLE orders ALTER COLUMN priority DROP DEFAULT;
@@ -0,0 +1,15 @@
+-- Settlements the engine matched in memory but could not store. A batch the database refuses
+-- is not tried again: it is kept here with the reason, and its market halted, so an operator
+-- can settle it by hand once the cause is fixed.
+CREATE TABLE settlement_dead_letters (
+    id BIGSERIAL PRIMARY KEY,
+    market_id VARCHAR(36) NOT NULL,
+    sequence BIGINT NOT NULL,
+    fills TEXT NOT NULL,
+    error TEXT NOT NULL,
+    create_time BIGINT NOT NULL,
+
+    CONSTRAINT fk_settlement_dead_letter_market FOREIGN KEY (market_id) REFERENCES markets(id)
+);
+
+CREATE INDEX idx_settlement_dead_letters_market ON settlement_dead_letters(market_id, id);
@@ -0,0 +1,1345 @@
+use crate::filters::{LedgerFilter, OrderFilter, TradeFilter, WalletFilter};
+use crate::models::models::*;
+use crate::provider::*;
+use anyhow::{Context, Result, anyhow};
+use bigdecimal::BigDecimal;
+use common::db::pagination::*;
+use common::error::BitradeError;
+use common::utils::round_amount;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, MutexGuard};
+
+/// In-memory stand-in for the database, so order books run without Postgres. It keeps
+/// orders, trades, OCO groups, the engine journal and order book snapshots, and numbers trades
+/// and journal entries in the order they come: the same orders always give the same trades.
+///
+/// Funds are tracked for the markets created in it, with the wallet and fee treasury math of
+/// the repository: orders lock what they may spend, trades settle from the locked balances and
+/// cancels unlock the rest. Orders of any other market are taken as funded and settle without
+/// moving balances. What an order book never calls is not simulated and returns an
+/// error.
+#[derive(Debug, Default)]
+pub struct MockPersister {
+    state: Mutex<MockState>,
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    orders: BTreeMap<String, Order>,
+    trades: Vec<NewTrade>,
+    oco_groups: Vec<OcoGroup>,
+    engine_events: Vec<EngineEvent>,
+    applied_sequences: HashMap<String, i64>,
+    dead_letters: Vec<SettlementDeadLetter>,
+    snapshots: HashMap<String, OrderBookSnapshot>,
+    markets: BTreeMap<String, Market>,
+    wallets: BTreeMap<(String, String), Wallet>,
+    fee_treasuries: BTreeMap<(String, String), FeeTreasury>,
+}
+
+fn is_active(order: &Order) -> bool {
+    order.status == OrderStatus::Open.as_str()
+        || order.status == OrderStatus::PartiallyFilled.as_str()
+}
+
+fn is_buy(order: &Order) -> bool {
+    order.side == OrderSide::Buy.as_str()
+}
+
+fn empty_wallet(user_id: &str, asset: &str) -> Wallet {
+    Wallet {
+        user_id: user_id.to_string(),
+        asset: asset.to_string(),
+        available: BigDecimal::from(0),
+        locked: BigDecimal::from(0),
+        update_time: 0,
+        reserved: BigDecimal::from(0),
+        total_deposited: BigDecimal::from(0),
+        total_withdrawn: BigDecimal::from(0),
+    }
+}
+
+/// Returns the wallet of `user_id` and `asset` among those a settlement loaded.
+fn loaded_wallet<'w>(
+    wallets: &'w mut BTreeMap<(String, String), Wallet>,
+    user_id: &str,
+    asset: &str,
+) -> Result<&'w mut Wallet> {
+    wallets
+        .get_mut(&(user_id.to_string(), asset.to_string()))
+        .with_context(|| format!("{} wallet of {} is not loaded", asset, user_id))
+}
+
+/// Applies `fill` and the order's `fee` to order `order_id`, the way the orders table keeps
+/// its amounts.
+fn fill_order<'o>(
+    orders: &'o mut BTreeMap<String, Order>,
+    order_id: &str,
+    fill: &TradeFill,
+    fee: &BigDecimal,
+) -> Result<&'o Order> {
+    let order = orders
+        .get_mut(order_id)
+        .with_context(|| format!("Failed to fetch open order {}", order_id))?;
+    order.filled_base =
+        round_amount(&(round_amount(&order.filled_base) + round_amount(&fill.base_amount)));
+    order.filled_quote =
+        round_amount(&(round_amount(&order.filled_quote) + round_amount(&fill.quote_amount)));
+    order.filled_fee = round_amount(&(round_amount(&order.filled_fee) + fee));
+    order.remained_base =
+        round_amount(&(round_amount(&order.remained_base) - round_amount(&fill.base_amount)));
+    order.remained_quote = if is_buy(order) {
+        round_amount(&(round_amount(&order.remained_quote) - round_amount(&fill.quote_amount)))
+    } else {
+        round_amount(&(&order.remained_base * &order.price))
+    };
+    order.status = if order.filled_base >= round_amount(&order.base_amount) {
+        OrderStatus::Filled.as_str().to_string()
+    } else {
+        OrderStatus::PartiallyFilled.as_str().to_string()
+    };
+    Ok(order)
+}
+
+impl MockPersister {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every order stored so far, by id
+    pub fn orders(&self) -> Vec<Order> {
+        self.state().orders.values().cloned().collect()
+    }
+
+    /// Every trade settled so far, oldest first
+    pub fn trades(&self) -> Vec<NewTrade> {
+        self.state().trades.clone()
+    }
+
+    /// Deletes `market_id` as an operator would from the database, which has no call for it
+    pub fn remove_market(&self, market_id: &str) -> Option<Market> {
+        self.state().markets.remove(market_id)
+    }
+
+    fn state(&self) -> MutexGuard<'_, MockState> {
+        self.state.lock().unwrap()
+    }
+}
+
+impl MockState {
+    fn order_mut(&mut self, order_id: &str) -> Result<&mut Order> {
+        self.orders
+            .get_mut(order_id)
+            .ok_or_else(|| BitradeError::OrderNotFound(order_id.to_string()).into())
+    }
+
+    fn active_orders(&self, filter: impl Fn(&Order) -> bool) -> Vec<Order> {
+        self.orders
+            .values()
+            .filter(|order| is_active(order) && filter(order))
+            .cloned()
+            .collect()
+    }
+
+    /// The asset an order of `market_id` locks on `side`, unless funds of the market are not
+    /// tracked
+    fn locked_asset(&self, market_id: &str, side: &str) -> Option<String> {
+        let market = self.markets.get(market_id)?;
+        Some(match side == OrderSide::Buy.as_str() {
+            true => market.quote_asset.clone(),
+            false => market.base_asset.clone(),
+        })
+    }
+
+    /// Changes the balances of a wallet, refusing to take either below zero
+    fn move_funds(
+        &mut self,
+        user_id: &str,
+        asset: &str,
+        available_delta: &BigDecimal,
+        locked_delta: &BigDecimal,
+    ) -> Result<()> {
+        let key = (user_id.to_string(), asset.to_string());
+        let mut wallet = self
+            .wallets
+            .get(&key)
+            .cloned()
+            .unwrap_or_else(|| empty_wallet(user_id, asset));
+        let available = &wallet.available + available_delta;
+        if available < 0 {
+            return Err(BitradeError::InsufficientBalance {
+                asset: asset.to_string(),
+                required: -available_delta,
+                available: wallet.available.clone(),
+            }
+            .into());
+        }
+        let locked = &wallet.locked + locked_delta;
+        if locked < 0 {
+            return Err(anyhow::anyhow!("Insufficient locked balance"));
+        }
+        wallet.available = available;
+        wallet.locked = locked;
+        self.wallets.insert(key, wallet);
+        Ok(())
+    }
+
+    /// Moves `amount` from the available to the locked balance of the wallet order `order`
+    /// spends from, or back for a negative `amount`
+    fn lock_for(&mut self, order: &Order, amount: &BigDecimal) -> Result<()> {
+        match self.locked_asset(&order.market_id, &order.side) {
+            Some(asset) => self.move_funds(&order.user_id, &asset, &-amount, amount),
+            None => Ok(()),
+        }
+    }
+
+    fn oco_group(&self, order_id: &str) -> Option<&OcoGroup> {
+        self.oco_groups
+            .iter()
+            .find(|group| group.first_order_id == order_id || group.second_order_id == order_id)
+    }
+
+    /// Stores `order_data`, refusing what the unique indexes of the orders table would.
+    fn insert_order(
+        &mut self,
+        order_data: NewOrder,
+        reject_reason: Option<RejectReason>,
+    ) -> Result<Order> {
+        if self.orders.contains_key(&order_data.id) {
+            return Err(anyhow::anyhow!("Order {} already exists", order_data.id));
+        }
+        let holds_client_order_id = |order: &Order| {
+            order.user_id == order_data.user_id
+                && order.client_order_id == order_data.client_order_id
+                && order.status != OrderStatus::Rejected.as_str()
+        };
+        if reject_reason.is_none()
+            && order_data.client_order_id.is_some()
+            && self.orders.values().any(holds_client_order_id)
+        {
+            return Err(anyhow::anyhow!(
+                "Client order ID is already used by another order of the user"
+            ));
+        }
+
+        let order = Order {
+            reject_reason: reject_reason.map(|reason| reason.as_str().to_string()),
+            ..order_data.into()
+        };
+        if order.reject_reason.is_none() {
+            // Buys lock the quote they may spend, sells the base
+            let amount = match is_buy(&order) {
+                true => order.quote_amount.clone(),
+                false => order.base_amount.clone(),
+            };
+            self.lock_for(&order, &amount)?;
+        }
+        self.orders.insert(order.id.clone(), order.clone());
+        Ok(order)
+    }
+
+    /// Takes back an order [`Self::insert_order`] just stored, with the funds it locked
+    fn remove_order(&mut self, order_id: &str) -> Result<()> {
+        let order = self.orders.remove(order_id).context("No order to remove")?;
+        let amount = match is_buy(&order) {
+            true => order.quote_amount.clone(),
+            false => order.base_amount.clone(),
+        };
+        self.lock_for(&order, &-amount)
+    }
+
+    /// Cancels an order that is still on the book and unlocks what it has not spent.
+    fn cancel(&mut self, order_id: &str, reason: CancelReason) -> Result<Order> {
+        let order = self.order_mut(order_id)?;
+        if !is_active(order) {
+            return Err(anyhow::anyhow!("Order already in final state"));
+        }
+        order.status = OrderStatus::Canceled.as_str().to_string();
+        order.cancel_reason = Some(reason.as_str().to_string());
+        let order = order.clone();
+        let unlocked = match is_buy(&order) {
+            true => order.remained_quote.clone(),
+            false => order.remained_base.clone(),
+        };
+        self.lock_for(&order, &-unlocked)?;
+        Ok(order)
+    }
+
+    /// Filling or canceling either leg of an OCO group cancels the other
+    fn cancel_oco_sibling(&mut self, order_id: &str) {
+        let Some(sibling_id) = self
+            .oco_group(order_id)
+            .map(|group| group.sibling_of(order_id).to_string())
+        else {
+            return;
+        };
+        if self.orders.get(&sibling_id).is_some_and(is_active) {
+            let _ = self.cancel(&sibling_id, CancelReason::OneCancelsOther);
+        }
+    }
+
+    /// Settles `fills` the way the repository does, all of them or none. The orders and
+    /// wallets they touch are worked on apart and written back once every fill went through.
+    fn settle(&mut self, market_id: &str, fills: &[TradeFill]) -> Result<Vec<NewTrade>> {
+        if fills
+            .iter()
+            .any(|fill| fill.buyer_user_id == fill.seller_user_id)
+        {
+            return Err(anyhow::anyhow!("Buyer and seller cannot be the same user"));
+        }
+        let assets = self
+            .markets
+            .get(market_id)
+            .map(|market| (market.base_asset.clone(), market.quote_asset.clone()));
+
+        let mut orders = BTreeMap::new();
+        let mut wallets = BTreeMap::new();
+        for fill in fills {
+            for order_id in [&fill.buyer_order_id, &fill.seller_order_id] {
+                let order = self
+                    .orders
+                    .get(order_id)
+                    .filter(|order| is_active(order))
+                    .with_context(|| format!("Failed to fetch open order {}", order_id))?;
+                orders.insert(order_id.clone(), order.clone());
+            }
+            if let Some((base_asset, quote_asset)) = &assets {
+                for user_id in [&fill.buyer_user_id, &fill.seller_user_id] {
+                    for asset in [base_asset, quote_asset] {
+                        let key = (user_id.clone(), asset.clone());
+                        let wallet = self.wallets.get(&key).cloned();
+                        wallets.entry(key).or_insert_with(|| {
+                            wallet.unwrap_or_else(|| empty_wallet(user_id, asset))
+                        });
+                    }
+                }
+            }
+        }
+
+        let mut trades = Vec::with_capacity(fills.len());
+        let mut buyer_fees = BigDecimal::from(0);
+        let mut seller_fees = BigDecimal::from(0);
+        for fill in fills {
+            let buyer_fee = round_amount(&(&fill.buyer_fee_rate * &fill.base_amount));
+            let seller_fee = round_amount(&(&fill.seller_fee_rate * &fill.quote_amount));
+            buyer_fees += &buyer_fee;
+            seller_fees += &seller_fee;
+
+            fill_order(&mut orders, &fill.seller_order_id, fill, &seller_fee)?;
+            let buyer_order = fill_order(&mut orders, &fill.buyer_order_id, fill, &buyer_fee)?;
+            // Quote a filled buy locked but never spent goes back to the buyer whole
+            let buyer_quote_residue = if buyer_order.status == OrderStatus::Filled.as_str() {
+                buyer_order.remained_quote.clone()
+            } else {
+                BigDecimal::from(0)
+            };
+
+            if let Some((base_asset, quote_asset)) = &assets {
+                let seller_base = loaded_wallet(&mut wallets, &fill.seller_user_id, base_asset)?;
+                if seller_base.locked < fill.base_amount {
+                    return Err(anyhow::anyhow!(
+                        "Insufficient frozen balance: seller {} has {} {} frozen but needs {}",
+                        fill.seller_user_id,
+                        seller_base.locked,
+                        base_asset,
+                        fill.base_amount
+                    ));
+                }
+                seller_base.locked =
+                    round_amount(&seller_base.locked) - round_amount(&fill.base_amount);
+
+                let buyer_quote = loaded_wallet(&mut wallets, &fill.buyer_user_id, quote_asset)?;
+                if buyer_quote.locked < fill.quote_amount {
+                    return Err(anyhow::anyhow!(
+                        "Insufficient frozen balance: buyer {} has {} {} frozen but needs {}",
+                        fill.buyer_user_id,
+                        buyer_quote.locked,
+                        quote_asset,
+                        fill.quote_amount
+                    ));
+                }
+                buyer_quote.locked = round_amount(&buyer_quote.locked)
+                    - round_amount(&fill.quote_amount)
+                    - round_amount(&buyer_quote_residue);
+                buyer_quote.available =
+                    round_amount(&buyer_quote.available) + round_amount(&buyer_quote_residue);
+
+                // The seller gets the quote and the buyer the base, less fees
+                loaded_wallet(&mut wallets, &fill.seller_user_id, quote_asset)?.available +=
+                    round_amount(&(&fill.quote_amount - &seller_fee));
+                loaded_wallet(&mut wallets, &fill.buyer_user_id, base_asset)?.available +=
+                    round_amount(&(&fill.base_amount - &buyer_fee));
+            }
+
+            let taker_order_id = match fill.is_buyer_taker {
+                true => &fill.buyer_order_id,
+                false => &fill.seller_order_id,
+            };
+            trades.push(NewTrade {
+                id: fill
+                    .trade_id
+                    .clone()
+                    .unwrap_or_else(|| format!("trade-{}", self.trades.len() + trades.len() + 1)),
+                // Seconds like the trades table, of the incoming order so runs are repeatable
+                timestamp: fill
+                    .timestamp
+                    .unwrap_or(orders[taker_order_id].create_time / 1000),
+                market_id: market_id.to_string(),
+                price: fill.price.clone(),
+                base_amount: fill.base_amount.clone(),
+                quote_amount: fill.quote_amount.clone(),
+                buyer_user_id: fill.buyer_user_id.clone(),
+                buyer_order_id: fill.buyer_order_id.clone(),
+                buyer_fee,
+                seller_user_id: fill.seller_user_id.clone(),
+                seller_order_id: fill.seller_order_id.clone(),
+                seller_fee,
+                taker_side: match fill.is_buyer_taker {
+                    true => OrderSide::Buy.as_str().to_string(),
+                    false => OrderSide::Sell.as_str().to_string(),
+                },
+                is_liquidation: None,
+            });
+        }
+
+        self.orders.extend(orders);
+        self.wallets.extend(wallets);
+        if let Some((base_asset, quote_asset)) = assets {
+            // Like the UPDATE of the repository, fees of a treasury never created are lost
+            for (asset, collected) in [(quote_asset, seller_fees), (base_asset, buyer_fees)] {
+                if let Some(treasury) = self.fee_treasuries.get_mut(&(market_id.to_string(), asset))
+                {
+                    treasury.collected_amount += collected;
+                }
+            }
+        }
+        for fill in fills {
+            self.cancel_oco_sibling(&fill.buyer_order_id);
+            self.cancel_oco_sibling(&fill.seller_order_id);
+        }
+        self.trades.extend(trades.iter().cloned());
+        Ok(trades)
+    }
+}
+
+impl OrderDatabaseReader for MockPersister {
+    fn get_order(&self, order_id: &str) -> Result<Option<Order>> {
+        let state = self.state();
+        let order = state
+            .orders
+            .get(order_id)
+            .cloned()
+            .ok_or_else(|| BitradeError::OrderNotFound(order_id.to_string()))?;
+        Ok(Some(order))
+    }
+
+    fn get_order_by_client_id(
+        &self,
+        user_id: &str,
+        client_order_id: &str,
+    ) -> Result<Option<Order>> {
+        let state = self.state();
+        let mut orders: Vec<&Order> = state
+            .orders
+            .values()
+            .filter(|order| {
+                order.user_id == user_id
+                    && order.client_order_id.as_deref() == Some(client_order_id)
+            })
+            .collect();
+        // Rejected orders don't hold the id, any other order is the one that does
+        orders.sort_by_key(|order| {
+            (
+                order.status == OrderStatus::Rejected.as_str(),
+                -order.create_time,
+            )
+        });
+        Ok(orders.first().map(|order| (*order).clone()))
+    }
+
+    fn get_active_orders(&self, market_id: &str) -> Result<Vec<Order>> {
+        let mut active = self
+            .state()
+            .active_orders(|order| order.market_id == market_id);
+        active.sort_by_key(|order| (order.priority, order.create_time));
+        Ok(active)
+    }
+
+    fn get_expired_orders(&self, market_id: &str, now: i64) -> Result<Vec<Order>> {
+        let mut expired = self.state().active_orders(|order| {
+            order.market_id == market_id
+                && order.time_in_force.as_deref() == Some(TimeInForce::GTD.as_str())
+                && order.expires_at.is_some_and(|expires_at| expires_at <= now)
+        });
+        expired.sort_by_key(|order| order.expires_at);
+        Ok(expired)
+    }
+
+    fn list_orders(
+        &self,
+        _filter: OrderFilter,
+        _pagination: Option<Pagination>,
+    ) -> Result<Paginated<Order>> {
+        Err(anyhow!("list_orders is not simulated"))
+    }
+
+    fn get_open_order_stats(&self) -> Result<Vec<OpenOrderStat>> {
+        Err(anyhow!("get_open_order_stats is not simulated"))
+    }
+
+    fn get_book_levels(&self, _market_id: &str) -> Result<Vec<BookLevel>> {
+        Err(anyhow!("get_book_levels is not simulated"))
+    }
+
+    fn get_user_order_counts(
+        &self,
+        _user_id: &str,
+        _market_id: Option<&str>,
+    ) -> Result<Vec<OrderStatusCount>> {
+        Err(anyhow!("get_user_order_counts is not simulated"))
+    }
+
+    fn get_user_active_orders_count(&self, user_id: &str, market_id: &str) -> Result<i64> {
+        let active = self
+            .state()
+            .active_orders(|order| order.user_id == user_id && order.market_id == market_id);
+        Ok(active.len() as i64)
+    }
+
+    fn get_user_locked_notional(&self, user_id: &str, market_id: &str) -> Result<BigDecimal> {
+        let active = self
+            .state()
+            .active_orders(|order| order.user_id == user_id && order.market_id == market_id);
+        Ok(active
+            .into_iter()
+            .map(|order| match order.side == OrderSide::Buy.as_str() {
+                true => order.remained_quote,
+                false => order.remained_base * order.price,
+            })
+            .sum())
+    }
+
+    fn get_user_active_orders(&self, user_id: &str) -> Result<Vec<Order>> {
+        let mut active = self.state().active_orders(|order| order.user_id == user_id);
+        active.sort_by_key(|order| order.create_time);
+        Ok(active)
+    }
+}
+
+impl OrderDatabaseWriter for MockPersister {
+    fn create_order(&self, order_data: NewOrder) -> Result<Order> {
+        self.state().insert_order(order_data, None)
+    }
+
+    fn reject_order(&self, order_data: NewOrder, reason: RejectReason) -> Result<Order> {
+        let rejected = NewOrder {
+            remained_base: BigDecimal::from(0),
+            remained_quote: BigDecimal::from(0),
+            status: OrderStatus::Rejected.as_str().to_string(),
+            ..order_data
+        };
+        self.state().insert_order(rejected, Some(reason))
+    }
+
+    fn cancel_order(&self, order_id: &str, reason: CancelReason) -> Result<Order> {
+        let mut state = self.state();
+        let canceled = state.cancel(order_id, reason)?;
+        state.cancel_oco_sibling(order_id);
+        Ok(canceled)
+    }
+
+    fn cancel_all_orders(&self, market_id: &str, reason: CancelReason) -> Result<Vec<Order>> {
+        let mut state = self.state();
+        let active = state.active_orders(|order| order.market_id == market_id);
+        active
+            .iter()
+            .map(|order| state.cancel(&order.id, reason.clone()))
+            .collect()
+    }
+
+    fn cancel_all_global_orders(&self, reason: CancelReason) -> Result<Vec<Order>> {
+        let mut state = self.state();
+        let active = state.active_orders(|_| true);
+        active
+            .iter()
+            .map(|order| state.cancel(&order.id, reason.clone()))
+            .collect()
+    }
+
+    fn update_order_status(&self, order_id: &str, status: OrderStatus) -> Result<Order> {
+        let mut state = self.state();
+        let order = state.order_mut(order_id)?;
+        order.status = status.as_str().to_string();
+        Ok(order.clone())
+    }
+
+    fn amend_order(
+        &self,
+        order_id: &str,
+        price: BigDecimal,
+        remained_base: BigDecimal,
+        priority: i64,
+    ) -> Result<Order> {
+        if price <= 0 || remained_base <= 0 {
+            return Err(anyhow::anyhow!(
+                "Amended price and remaining amount must be greater than 0"
+            ));
+        }
+        let mut state = self.state();
+        let order = state.order_mut(order_id)?;
+        if !is_active(order) {
+            return Err(anyhow::anyhow!("Only open orders can be amended"));
+        }
+        if order.order_type != OrderType::Limit.as_str() {
+            return Err(anyhow::anyhow!("Only limit orders can be amended"));
+        }
+        // Buys hold the quote of what is left, sells the base
+        let remained_quote = round_amount(&(&price * &remained_base));
+        let delta = match is_buy(order) {
+            true => &remained_quote - &order.remained_quote,
+            false => &remained_base - &order.remained_base,
+        };
+        let order = order.clone();
+        state.lock_for(&order, &delta)?;
+
+        let order = state.order_mut(order_id)?;
+        order.base_amount = &order.filled_base + &remained_base;
+        order.quote_amount = &order.filled_quote + &remained_quote;
+        // An iceberg never shows more than is left of it
+        order.display_amount = order
+            .display_amount
+            .take()
+            .map(|display_amount| display_amount.min(order.base_amount.clone()));
+        order.remained_quote = remained_quote;
+        order.price = price;
+        order.remained_base = remained_base;
+        order.priority = priority;
+        Ok(order.clone())
+    }
+}
+
+impl WalletDatabaseReader for MockPersister {
+    fn get_wallet(&self, user_id: &str, asset: &str) -> Result<Option<Wallet>> {
+        let key = (user_id.to_string(), asset.to_string());
+        Ok(self.state().wallets.get(&key).cloned())
+    }
+
+    fn get_user_wallets(&self, user_id: &str) -> Result<Vec<Wallet>> {
+        Ok(self
+            .state()
+            .wallets
+            .values()
+            .filter(|wallet| wallet.user_id == user_id)
+            .cloned()
+            .collect())
+    }
+
+    fn list_wallets(
+        &self,
+        _filter: WalletFilter,
+        _pagination: Option<Pagination>,
+    ) -> Result<Paginated<Wallet>> {
+        Err(anyhow!("list_wallets is not simulated"))
+    }
+
+    fn get_wallet_changes(&self, _user_id: &str, _since_time: i64) -> Result<Vec<Wallet>> {
+        Err(anyhow!("get_wallet_changes is not simulated"))
+    }
+}
+
+impl WalletDatabaseWriter for MockPersister {
+    fn deposit_balance(&self, user_id: &str, asset: &str, amount: BigDecimal) -> Result<Wallet> {
+        let mut state = self.state();
+        let wallet = state
+            .wallets
+            .entry((user_id.to_string(), asset.to_string()))
+            .or_insert_with(|| empty_wallet(user_id, asset));
+        wallet.available += &amount;
+        wallet.total_deposited += amount;
+        Ok(wallet.clone())
+    }
+
+    fn withdraw_balance(&self, user_id: &str, asset: &str, amount: BigDecimal) -> Result<Wallet> {
+        let mut state = self.state();
+        let key = (user_id.to_string(), asset.to_string());
+        let available = state
+            .wallets
+            .get(&key)
+            .map(|wallet| wallet.available.clone())
+            .unwrap_or_else(|| BigDecimal::from(0));
+        if available < amount {
+            return Err(BitradeError::InsufficientBalance {
+                asset: asset.to_string(),
+                required: amount,
+                available,
+            }
+            .into());
+        }
+        let wallet = state.wallets.get_mut(&key).unwrap();
+        wallet.available -= &amount;
+        wallet.total_withdrawn += amount;
+        Ok(wallet.clone())
+    }
+
+    fn lock_balance(&self, user_id: &str, asset: &str, amount: BigDecimal) -> Result<Wallet> {
+        let mut state = self.state();
+        state.move_funds(user_id, asset, &-&amount, &amount)?;
+        Ok(state.wallets[&(user_id.to_string(), asset.to_string())].clone())
+    }
+
+    fn unlock_balance(&self, user_id: &str, asset: &str, amount: BigDecimal) -> Result<Wallet> {
+        let mut state = self.state();
+        state.move_funds(user_id, asset, &amount, &-&amount)?;
+        Ok(state.wallets[&(user_id.to_string(), asset.to_string())].clone())
+    }
+
+    fn release_orphaned_locks(&self, user_id: &str) -> Result<Vec<Wallet>> {
+        let mut state = self.state();
+        // What the open orders of the user still hold, by asset
+        let mut backed_amounts: HashMap<String, BigDecimal> = HashMap::new();
+        for order in state.active_orders(|order| order.user_id == user_id) {
+            if let Some(asset) = state.locked_asset(&order.market_id, &order.side) {
+                let amount = match is_buy(&order) {
+                    true => order.remained_quote,
+                    false => order.remained_base,
+                };
+                *backed_amounts
+                    .entry(asset)
+                    .or_insert_with(|| BigDecimal::from(0)) += amount;
+            }
+        }
+
+        let mut released_wallets = Vec::new();
+        for wallet in state.wallets.values_mut() {
+            if wallet.user_id != user_id {
+                continue;
+            }
+            let backed_amount = backed_amounts
+                .remove(&wallet.asset)
+                .unwrap_or_else(|| BigDecimal::from(0));
+            let orphaned_amount = &wallet.locked - &backed_amount;
+            if orphaned_amount > 0 {
+                wallet.available += orphaned_amount;
+                wallet.locked = backed_amount;
+                released_wallets.push(wallet.clone());
+            }
+        }
+        Ok(released_wallets)
+    }
+}
+
+impl TradeDatabaseReader for MockPersister {
+    fn list_trades(
+        &self,
+        _filter: TradeFilter,
+        _pagination: Option<Pagination>,
+    ) -> Result<Paginated<Trade>> {
+        Err(anyhow!("list_trades is not simulated"))
+    }
+
+    fn export_trades(
+        &self,
+        _filter: TradeFilter,
+        _after: Option<&Cursor>,
+        _limit: i64,
+    ) -> Result<Vec<Trade>> {
+        Err(anyhow!("export_trades is not simulated"))
+    }
+
+    fn get_order_fills(&self, _order_id: &str) -> Result<Vec<OrderFill>> {
+        Err(anyhow!("get_order_fills is not simulated"))
+    }
+
+    fn get_trade_detail(&self, _trade_id: &str) -> Result<Option<TradeDetail>> {
+        Err(anyhow!("get_trade_detail is not simulated"))
+    }
+
+    fn get_user_fees_paid(
+        &self,
+        _user_id: &str,
+        _start_time: Option<i64>,
+        _end_time: Option<i64>,
+    ) -> Result<Vec<UserFeePaid>> {
+        Err(anyhow!("get_user_fees_paid is not simulated"))
+    }
+
+    fn get_user_traded_volume(
+        &self,
+        _user_id: &str,
+        _market_id: &str,
+        _start_time: i64,
+    ) -> Result<BigDecimal> {
+        Err(anyhow!("get_user_traded_volume is not simulated"))
+    }
+
+    fn get_user_traded_volumes(
+        &self,
+        _user_id: &str,
+        _start_time: i64,
+    ) -> Result<Vec<UserTradedVolume>> {
+        Err(anyhow!("get_user_traded_volumes is not simulated"))
+    }
+}
+
+impl TradeDatabaseWriter for MockPersister {
+    fn execute_limit_trade(
+        &self,
+        is_buyer_taker: bool,
+        market_id: String,
+        base_asset: String,
+        quote_asset: String,
+        buyer_user_id: String,
+        seller_user_id: String,
+        buyer_order_id: String,
+        seller_order_id: String,
+        price: BigDecimal,
+        base_amount: BigDecimal,
+        quote_amount: BigDecimal,
+        buyer_fee_rate: BigDecimal,
+        seller_fee_rate: BigDecimal,
+    ) -> Result<NewTrade> {
+        let fill = TradeFill {
+            is_buyer_taker,
+            buyer_user_id,
+            seller_user_id,
+            buyer_order_id,
+            seller_order_id,
+            price,
+            base_amount,
+            quote_amount,
+            buyer_fee_rate,
+            seller_fee_rate,
+            trade_id: None,
+            timestamp: None,
+        };
+        self.execute_limit_trades(&market_id, &base_asset, &quote_asset, &[fill])?
+            .pop()
+            .context("Settlement returned no trade")
+    }
+
+    fn execute_limit_trades(
+        &self,
+        market_id: &str,
+        _base_asset: &str,
+        _quote_asset: &str,
+        fills: &[TradeFill],
+    ) -> Result<Vec<NewTrade>> {
+        self.state().settle(market_id, fills)
+    }
+
+    fn execute_journaled_trades(
+        &self,
+        market_id: &str,
+        _base_asset: &str,
+        _quote_asset: &str,
+        fills: &[TradeFill],
+        sequence: i64,
+    ) -> Result<Vec<NewTrade>> {
+        let mut state = self.state();
+        let trades = state.settle(market_id, fills)?;
+        state
+            .applied_sequences
+            .insert(market_id.to_string(), sequence);
+        Ok(trades)
+    }
+
+    fn create_order_with_trades(
+        &self,
+        order_data: NewOrder,
+        _base_asset: &str,
+        _quote_asset: &str,
+        fills: &[TradeFill],
+    ) -> Result<(Order, Vec<NewTrade>)> {
+        let mut state = self.state();
+        // Settling refuses before it changes anything, only the order has to be taken back
+        let order = state.insert_order(order_data, None)?;
+        match state.settle(&order.market_id, fills) {
+            Ok(trades) => Ok((state.orders[&order.id].clone(), trades)),
+            Err(e) => {
+                state.remove_order(&order.id)?;
+                Err(e)
+            }
+        }
+    }
+}
+
+impl MarketDatabaseReader for MockPersister {
+    fn get_market(&self, market_id: &str) -> Result<Option<Market>> {
+        Ok(self.state().markets.get(market_id).cloned())
+    }
+
+    fn list_markets(&self) -> Result<Vec<Market>> {
+        Ok(self.state().markets.values().cloned().collect())
+    }
+}
+
+impl MarketDatabaseWriter for MockPersister {
+    fn create_market(&self, market_data: NewMarket) -> Result<Market> {
+        let mut state = self.state();
+        let market = state
+            .markets
+            .entry(market_data.id.clone())
+            .or_insert_with(|| Market {
+                id: market_data.id,
+                base_asset: market_data.base_asset,
+                quote_asset: market_data.quote_asset,
+                default_maker_fee: market_data.default_maker_fee,
+                default_taker_fee: market_data.default_taker_fee,
+                create_time: market_data.create_time,
+                update_time: market_data.update_time,
+                status: market_data.status,
+                min_base_amount: market_data.min_base_amount,
+                min_quote_amount: market_data.min_quote_amount,
+                price_precision: market_data.price_precision,
+                amount_precision: market_data.amount_precision,
+            });
+        Ok(market.clone())
+    }
+
+    fn update_market_status(&self, market_id: &str, status: MarketStatus) -> Result<Market> {
+        let mut state = self.state();
+        let market = state
+            .markets
+            .get_mut(market_id)
+            .ok_or_else(|| BitradeError::MarketNotFound(market_id.to_string()))?;
+        market.status = status.as_str().to_string();
+        Ok(market.clone())
+    }
+
+    fn update_market(&self, _market_id: &str, _changes: MarketUpdate) -> Result<Market> {
+        Err(anyhow!("update_market is not simulated"))
+    }
+}
+
+impl MarketStatDatabaseReader for MockPersister {
+    fn get_market_stats(&self, _market_id: &str) -> Result<Option<MarketStat>> {
+        Err(anyhow!("get_market_stats is not simulated"))
+    }
+
+    fn list_tickers(&self) -> Result<Vec<Ticker>> {
+        Err(anyhow!("list_tickers is not simulated"))
+    }
+}
+
+impl MarketStatDatabaseWriter for MockPersister {
+    fn upsert_market_stats(
+        &self,
+        _market_id: &str,
+        _high_24h: BigDecimal,
+        _low_24h: BigDecimal,
+        _volume_24h: BigDecimal,
+        _price_change_24h: BigDecimal,
+        _last_price: BigDecimal,
+    ) -> Result<MarketStat> {
+        Err(anyhow!("upsert_market_stats is not simulated"))
+    }
+
+    fn refresh_market_stats(&self, _market_id: &str, _now: i64) -> Result<Option<MarketStat>> {
+        Err(anyhow!("refresh_market_stats is not simulated"))
+    }
+
+    fn upsert_market_quote(
+        &self,
+        _market_id: &str,
+        _best_bid: Option<BigDecimal>,
+        _best_ask: Option<BigDecimal>,
+    ) -> Result<MarketQuote> {
+        Err(anyhow!("upsert_market_quote is not simulated"))
+    }
+}
+
+impl KlineDatabaseReader for MockPersister {
+    fn list_klines(
+        &self,
+        _market_id: &str,
+        _interval: KlineInterval,
+        _start_time: Option<i64>,
+        _end_time: Option<i64>,
+        _limit: i64,
+    ) -> Result<Vec<Kline>> {
+        Err(anyhow!("list_klines is not simulated"))
+    }
+}
+
+impl LedgerDatabaseReader for MockPersister {
+    fn list_ledger_entries(
+        &self,
+        _filter: LedgerFilter,
+        _pagination: Option<Pagination>,
+    ) -> Result<Paginated<LedgerEntry>> {
+        Err(anyhow!("list_ledger_entries is not simulated"))
+    }
+}
+
+impl ReconciliationDatabaseReader for MockPersister {
+    fn get_balance_sheet(&self) -> Result<BalanceSheet> {
+        Err(anyhow!("get_balance_sheet is not simulated"))
+    }
+}
+
+impl TransferDatabaseReader for MockPersister {
+    fn get_transfer(&self, _transfer_id: &str) -> Result<Option<Transfer>> {
+        Err(anyhow!("get_transfer is not simulated"))
+    }
+
+    fn get_user_transfers(&self, _user_id: &str) -> Result<Vec<Transfer>> {
+        Err(anyhow!("get_user_transfers is not simulated"))
+    }
+}
+
+impl TransferDatabaseWriter for MockPersister {
+    fn complete_deposit(
+        &self,
+        _user_id: &str,
+        _asset: &str,
+        _amount: BigDecimal,
+        _external_id: &str,
+    ) -> Result<Transfer> {
+        Err(anyhow!("complete_deposit is not simulated"))
+    }
+
+    fn request_withdrawal(
+        &self,
+        _user_id: &str,
+        _asset: &str,
+        _amount: BigDecimal,
+        _address: &str,
+    ) -> Result<Transfer> {
+        Err(anyhow!("request_withdrawal is not simulated"))
+    }
+
+    fn approve_withdrawal(&self, _transfer_id: &str) -> Result<Transfer> {
+        Err(anyhow!("approve_withdrawal is not simulated"))
+    }
+
+    fn reject_withdrawal(&self, _transfer_id: &str, _reason: &str) -> Result<Transfer> {
+        Err(anyhow!("reject_withdrawal is not simulated"))
+    }
+
+    fn complete_withdrawal(&self, _transfer_id: &str, _external_id: &str) -> Result<Transfer> {
+        Err(anyhow!("complete_withdrawal is not simulated"))
+    }
+}
+
+impl FeeTreasuryDatabaseReader for MockPersister {
+    fn get_fee_treasury(&self, market_id: &str, asset: &str) -> Result<Option<FeeTreasury>> {
+        let key = (market_id.to_string(), asset.to_string());
+        Ok(self.state().fee_treasuries.get(&key).cloned())
+    }
+
+    fn list_fee_treasuries(&self, market_id: Option<&str>) -> Result<Vec<FeeTreasury>> {
+        Ok(self
+            .state()
+            .fee_treasuries
+            .values()
+            .filter(|treasury| market_id.is_none_or(|market_id| treasury.market_id == market_id))
+            .cloned()
+            .collect())
+    }
+}
+
+impl FeeTreasuryDatabaseWriter for MockPersister {
+    fn create_fee_treasury(&self, fee_treasury_data: NewFeeTreasury) -> Result<FeeTreasury> {
+        let key = (
+            fee_treasury_data.market_id.clone(),
+            fee_treasury_data.asset.clone(),
+        );
+        let mut state = self.state();
+        if state.fee_treasuries.contains_key(&key) {
+            return Err(anyhow::anyhow!(
+                "{} fee treasury of market {} already exists",
+                key.1,
+                key.0
+            ));
+        }
+        let treasury = FeeTreasury {
+            market_id: fee_treasury_data.market_id,
+            asset: fee_treasury_data.asset,
+            treasury_address: fee_treasury_data.treasury_address,
+            collected_amount: fee_treasury_data.collected_amount,
+            last_update_time: fee_treasury_data.last_update_time,
+            total_withdrawn: BigDecimal::from(0),
+        };
+        state.fee_treasuries.insert(key, treasury.clone());
+        Ok(treasury)
+    }
+
+    fn transfer_to_fee_treasury(&self, _fee_amount: BigDecimal) -> Result<FeeTreasury> {
+        Err(anyhow!("transfer_to_fee_treasury is not simulated"))
+    }
+
+    fn withdraw_from_fee_treasury(
+        &self,
+        _market_id: &str,
+        _asset: &str,
+        _amount: BigDecimal,
+        _destination: &str,
+    ) -> Result<FeeTreasuryWithdrawal> {
+        Err(anyhow!("withdraw_from_fee_treasury is not simulated"))
+    }
+}
+
+impl FeeTierDatabaseReader for MockPersister {
+    fn get_fee_tiers(&self, _market_id: &str) -> Result<Vec<FeeTier>> {
+        Err(anyhow!("get_fee_tiers is not simulated"))
+    }
+}
+
+impl FeeTierDatabaseWriter for MockPersister {
+    fn set_fee_tier(
+        &self,
+        _market_id: &str,
+        _min_volume: BigDecimal,
+        _maker_fee: BigDecimal,
+        _taker_fee: BigDecimal,
+    ) -> Result<FeeTier> {
+        Err(anyhow!("set_fee_tier is not simulated"))
+    }
+
+    fn delete_fee_tier(&self, _market_id: &str, _min_volume: &BigDecimal) -> Result<bool> {
+        Err(anyhow!("delete_fee_tier is not simulated"))
+    }
+}
+
+impl PriceBandDatabaseReader for MockPersister {
+    fn get_price_band(&self, _market_id: &str) -> Result<Option<PriceBand>> {
+        Ok(None)
+    }
+}
+
+impl PriceBandDatabaseWriter for MockPersister {
+    fn set_price_band(
+        &self,
+        _market_id: &str,
+        _band_percent: Option<BigDecimal>,
+        _halt_percent: Option<BigDecimal>,
+        _halt_window_ms: i64,
+        _halt_duration_ms: i64,
+    ) -> Result<PriceBand> {
+        Err(anyhow!("set_price_band is not simulated"))
+    }
+
+    fn delete_price_band(&self, _market_id: &str) -> Result<bool> {
+        Err(anyhow!("delete_price_band is not simulated"))
+    }
+}
+
+impl RiskLimitDatabaseReader for MockPersister {
+    fn get_risk_limit(&self, _market_id: &str) -> Result<Option<RiskLimit>> {
+        Ok(None)
+    }
+}
+
+impl RiskLimitDatabaseWriter for MockPersister {
+    fn set_risk_limit(
+        &self,
+        _market_id: &str,
+        _max_open_orders: Option<i32>,
+        _max_locked_notional: Option<BigDecimal>,
+    ) -> Result<RiskLimit> {
+        Err(anyhow!("set_risk_limit is not simulated"))
+    }
+
+    fn delete_risk_limit(&self, _market_id: &str) -> Result<bool> {
+        Err(anyhow!("delete_risk_limit is not simulated"))
+    }
+}
+
+impl UserFeeOverrideDatabaseReader for MockPersister {
+    fn get_user_fee_override(&self, _user_id: &str) -> Result<Option<UserFeeOverride>> {
+        Err(anyhow!("get_user_fee_override is not simulated"))
+    }
+}
+
+impl UserFeeOverrideDatabaseWriter for MockPersister {
+    fn set_user_fee_override(
+        &self,
+        _user_id: &str,
+        _maker_fee: BigDecimal,
+        _taker_fee: BigDecimal,
+    ) -> Result<UserFeeOverride> {
+        Err(anyhow!("set_user_fee_override is not simulated"))
+    }
+
+    fn delete_user_fee_override(&self, _user_id: &str) -> Result<bool> {
+        Err(anyhow!("delete_user_fee_override is not simulated"))
+    }
+}
+
+impl UserRestrictionDatabaseReader for MockPersister {
+    fn get_user_restriction(&self, _user_id: &str) -> Result<Option<UserRestriction>> {
+        Ok(None)
+    }
+
+    fn list_user_restrictions(&self) -> Result<Vec<UserRestriction>> {
+        Ok(Vec::new())
+    }
+}
+
+impl UserRestrictionDatabaseWriter for MockPersister {
+    fn set_user_restriction(
+        &self,
+        _user_id: &str,
+        _status: UserStatus,
+        _reason: &str,
+    ) -> Result<UserRestriction> {
+        Err(anyhow!("set_user_restriction is not simulated"))
+    }
+
+    fn delete_user_restriction(&self, _user_id: &str) -> Result<bool> {
+        Err(anyhow!("delete_user_restriction is not simulated"))
+    }
+}
+
+impl OcoGroupDatabaseReader for MockPersister {
+    fn get_oco_group_by_order(&self, order_id: &str) -> Result<Option<OcoGroup>> {
+        Ok(self.state().oco_group(order_id).cloned())
+    }
+
+    fn get_active_oco_groups(&self, market_id: &str) -> Result<Vec<OcoGroup>> {
+        let state = self.state();
+        let is_open = |order_id: &str| state.orders.get(order_id).is_some_and(is_active);
+        Ok(state
+            .oco_groups
+            .iter()
+            .filter(|group| group.market_id == market_id)
+            .filter(|group| is_open(&group.first_order_id) && is_open(&group.second_order_id))
+            .cloned()
+            .collect())
+    }
+}
+
+impl OcoGroupDatabaseWriter for MockPersister {
+    fn create_oco_group(&self, group: NewOcoGroup) -> Result<OcoGroup> {
+        let group = OcoGroup {
+            id: group.id,
+            market_id: group.market_id,
+            user_id: group.user_id,
+            first_order_id: group.first_order_id,
+            second_order_id: group.second_order_id,
+            create_time: group.create_time,
+        };
+        self.state().oco_groups.push(group.clone());
+        Ok(group)
+    }
+}
+
+impl AuditDatabaseReader for MockPersister {
+    fn get_user_order_audit(&self, _user_id: &str) -> Result<Vec<OrderAudit>> {
+        Err(anyhow!("get_user_order_audit is not simulated"))
+    }
+}
+
+impl AuditDatabaseWriter for MockPersister {
+    fn record_audit(&self, _entry: NewOrderAudit) -> Result<OrderAudit> {
+        Err(anyhow!("record_audit is not simulated"))
+    }
+}
+
+impl EngineEventDatabaseReader for MockPersister {
+    fn get_engine_events(&self, market_id: &str, after_sequence: i64) -> Result<Vec<EngineEvent>> {
+        Ok(self
+            .state()
+            .engine_events
+            .iter()
+            .filter(|event| event.market_id == market_id && event.sequence > after_sequence)
+            .cloned()
+            .collect())
+    }
+
+    fn get_applied_sequence(&self, market_id: &str) -> Result<i64> {
+        Ok(self
+            .state()
+            .applied_sequences
+            .get(market_id)
+            .copied()
+            .unwrap_or(0))
+    }
+
+    fn get_settlement_dead_letters(&self, market_id: &str) -> Result<Vec<SettlementDeadLetter>> {
+        Ok(self
+            .state()
+            .dead_letters
+            .iter()
+            .filter(|letter| letter.market_id == market_id)
+            .cloned()
+            .collect())
+    }
+}
+
+impl EngineEventDatabaseWriter for MockPersister {
+    fn append_engine_event(&self, event: NewEngineEvent) -> Result<EngineEvent> {
+        self.append_engine_events(vec![event])?
+            .pop()
+            .context("No engine event appended")
+    }
+
+    fn append_engine_events(&self, events: Vec<NewEngineEvent>) -> Result<Vec<EngineEvent>> {
+        let mut state = self.state();
+        let appended: Vec<EngineEvent> = events
+            .into_iter()
+            .enumerate()
+            .map(|(i, event)| EngineEvent {
+                sequence: state.engine_events.len() as i64 + i as i64 + 1,
+                market_id: event.market_id,
+                event_type: event.event_type,
+                order_id: event.order_id,
+                payload: event.payload,
+                create_time: event.create_time,
+            })
+            .collect();
+        state.engine_events.extend(appended.iter().cloned());
+        Ok(appended)
+    }
+
+    fn set_applied_sequence(&self, market_id: &str, sequence: i64) -> Result<()> {
+        self.state()
+            .applied_sequences
+            .insert(market_id.to_string(), sequence);
+        Ok(())
+    }
+
+    fn store_settlement_dead_letter(
+        &self,
+        letter: NewSettlementDeadLetter,
+    ) -> Result<SettlementDeadLetter> {
+        let mut state = self.state();
+        let letter = SettlementDeadLetter {
+            id: state.dead_letters.len() as i64 + 1,
+            market_id: letter.market_id,
+            sequence: letter.sequence,
+            fills: letter.fills,
+            error: letter.error,
+            create_time: letter.create_time,
+        };
+        state.dead_letters.push(letter.clone());
+        Ok(letter)
+    }
+}
+
+impl OrderBookSnapshotDatabaseReader for MockPersister {
+    fn get_order_book_snapshot(&self, market_id: &str) -> Result<Option<OrderBookSnapshot>> {
+        Ok(self.state().snapshots.get(market_id).cloned())
+    }
+}
+
+impl OrderBookSnapshotDatabaseWriter for MockPersister {
+    fn store_order_book_snapshot(&self, snapshot: OrderBookSnapshot) -> Result<()> {
+        self.state()
+            .snapshots
+            .insert(snapshot.market_id.clone(), snapshot);
+        Ok(())
+    }
+}
+
+impl OutboxDatabaseReader for MockPersister {
+    fn get_unpublished_events(&self, _limit: i64) -> Result<Vec<OutboxEvent>> {
+        Err(anyhow!("get_unpublished_events is not simulated"))
+    }
+}
+
+impl OutboxDatabaseWriter for MockPersister {
+    fn mark_events_published(&self, _ids: &[i64]) -> Result<usize> {
+        Err(anyhow!("mark_events_published is not simulated"))
+    }
+}
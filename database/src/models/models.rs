@@ -1,4 +1,4 @@
-use bigdecimal::BigDecimal;
+use bigdecimal::{BigDecimal, Zero};
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 
@@ -18,6 +18,7 @@ impl OrderType {
         }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Result<Self, String> {
         match s.to_uppercase().as_str() {
             "LIMIT" => Ok(OrderType::Limit),
@@ -42,6 +43,7 @@ impl OrderSide {
         }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Result<Self, String> {
         match s.to_uppercase().as_str() {
             "BUY" => Ok(OrderSide::Buy),
@@ -66,6 +68,7 @@ impl MarketRole {
         }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Result<Self, String> {
         match s.to_uppercase().as_str() {
             "MAKER" => Ok(MarketRole::Maker),
@@ -95,6 +98,7 @@ impl OrderStatus {
         }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Result<Self, String> {
         match s.to_uppercase().as_str() {
             "OPEN" => Ok(OrderStatus::Open),
@@ -121,6 +125,7 @@ pub enum TimeInForce {
     GTC, // Good Till Cancelled
     IOC, // Immediate Or Cancel
     FOK, // Fill Or Kill
+    GTD, // Good Till Date, rests until expires_at
 }
 
 impl TimeInForce {
@@ -129,19 +134,183 @@ impl TimeInForce {
             TimeInForce::GTC => "GTC",
             TimeInForce::IOC => "IOC",
             TimeInForce::FOK => "FOK",
+            TimeInForce::GTD => "GTD",
         }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Result<Self, String> {
         match s.to_uppercase().as_str() {
             "GTC" => Ok(TimeInForce::GTC),
             "IOC" => Ok(TimeInForce::IOC),
             "FOK" => Ok(TimeInForce::FOK),
+            "GTD" => Ok(TimeInForce::GTD),
             _ => Err(format!("Unknown time in force: {}", s)),
         }
     }
 }
 
+// Why an order ended up CANCELED
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum CancelReason {
+    UserCanceled,    // Canceled on the owner's request
+    Unfilled,        // Market, IOC or FOK remainder that could not be matched
+    AdminCancel,     // Mass cancel of a market by an operator
+    MarketClosed,    // Canceled because the engine shut the market down
+    PriceCollar,     // Would have traded too far from the last traded price
+    PostOnly,        // Post-only order that would have taken liquidity
+    OneCancelsOther, // The other leg of its OCO group was filled or canceled
+    Expired,         // GTD order that reached its expires_at
+    CircuitBreaker,  // Remainder of the order whose fill halted matching
+    UserBanned,      // Open order of a user an operator banned
+    Disconnected,    // Open order of a user whose last session closed with cancel-on-disconnect
+}
+
+impl CancelReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CancelReason::UserCanceled => "USER_CANCELED",
+            CancelReason::Unfilled => "UNFILLED",
+            CancelReason::AdminCancel => "ADMIN_CANCEL",
+            CancelReason::MarketClosed => "MARKET_CLOSED",
+            CancelReason::PriceCollar => "PRICE_COLLAR",
+            CancelReason::PostOnly => "POST_ONLY",
+            CancelReason::OneCancelsOther => "ONE_CANCELS_OTHER",
+            CancelReason::Expired => "EXPIRED",
+            CancelReason::CircuitBreaker => "CIRCUIT_BREAKER",
+            CancelReason::UserBanned => "USER_BANNED",
+            CancelReason::Disconnected => "DISCONNECTED",
+        }
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_uppercase().as_str() {
+            "USER_CANCELED" => Ok(CancelReason::UserCanceled),
+            "UNFILLED" => Ok(CancelReason::Unfilled),
+            "ADMIN_CANCEL" => Ok(CancelReason::AdminCancel),
+            "MARKET_CLOSED" => Ok(CancelReason::MarketClosed),
+            "PRICE_COLLAR" => Ok(CancelReason::PriceCollar),
+            "POST_ONLY" => Ok(CancelReason::PostOnly),
+            "ONE_CANCELS_OTHER" => Ok(CancelReason::OneCancelsOther),
+            "EXPIRED" => Ok(CancelReason::Expired),
+            "CIRCUIT_BREAKER" => Ok(CancelReason::CircuitBreaker),
+            "USER_BANNED" => Ok(CancelReason::UserBanned),
+            "DISCONNECTED" => Ok(CancelReason::Disconnected),
+            _ => Err(format!("Unknown cancel reason: {}", s)),
+        }
+    }
+}
+
+// Why an order ended up REJECTED, refused before it reached the book
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum RejectReason {
+    InsufficientFunds,      // Available balance could not cover what the order locks
+    UserRestricted,         // Its user is restricted to cancels or banned
+    MarketUnavailable,      // Market stopped or not accepting new orders
+    MarketConstraint,       // Below the market minimum or too many decimals
+    PriceBand,              // Priced too far from the last traded price
+    CircuitBreaker,         // Matching was halted
+    RiskLimit,              // Over the open order or notional limit of the user
+    DuplicateClientOrderId, // Client order id already taken by another order of the user
+}
+
+impl RejectReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RejectReason::InsufficientFunds => "INSUFFICIENT_FUNDS",
+            RejectReason::UserRestricted => "USER_RESTRICTED",
+            RejectReason::MarketUnavailable => "MARKET_UNAVAILABLE",
+            RejectReason::MarketConstraint => "MARKET_CONSTRAINT",
+            RejectReason::PriceBand => "PRICE_BAND",
+            RejectReason::CircuitBreaker => "CIRCUIT_BREAKER",
+            RejectReason::RiskLimit => "RISK_LIMIT",
+            RejectReason::DuplicateClientOrderId => "DUPLICATE_CLIENT_ORDER_ID",
+        }
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_uppercase().as_str() {
+            "INSUFFICIENT_FUNDS" => Ok(RejectReason::InsufficientFunds),
+            "USER_RESTRICTED" => Ok(RejectReason::UserRestricted),
+            "MARKET_UNAVAILABLE" => Ok(RejectReason::MarketUnavailable),
+            "MARKET_CONSTRAINT" => Ok(RejectReason::MarketConstraint),
+            "PRICE_BAND" => Ok(RejectReason::PriceBand),
+            "CIRCUIT_BREAKER" => Ok(RejectReason::CircuitBreaker),
+            "RISK_LIMIT" => Ok(RejectReason::RiskLimit),
+            "DUPLICATE_CLIENT_ORDER_ID" => Ok(RejectReason::DuplicateClientOrderId),
+            _ => Err(format!("Unknown reject reason: {}", s)),
+        }
+    }
+}
+
+// Order request recorded in the audit log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum AuditAction {
+    CreateOrder,
+    CancelOrder,
+    CreateOcoOrder,
+    AmendOrder,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::CreateOrder => "CREATE_ORDER",
+            AuditAction::CancelOrder => "CANCEL_ORDER",
+            AuditAction::CreateOcoOrder => "CREATE_OCO_ORDER",
+            AuditAction::AmendOrder => "AMEND_ORDER",
+        }
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_uppercase().as_str() {
+            "CREATE_ORDER" => Ok(AuditAction::CreateOrder),
+            "CANCEL_ORDER" => Ok(AuditAction::CancelOrder),
+            "CREATE_OCO_ORDER" => Ok(AuditAction::CreateOcoOrder),
+            "AMEND_ORDER" => Ok(AuditAction::AmendOrder),
+            _ => Err(format!("Unknown audit action: {}", s)),
+        }
+    }
+}
+
+// Kind of change recorded in the engine journal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EngineEventType {
+    OrderAccepted,
+    TradeExecuted,
+    OrderCanceled,
+    /// Every open order of the market canceled at once
+    OrdersCanceled,
+    OrderAmended,
+}
+
+impl EngineEventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EngineEventType::OrderAccepted => "ORDER_ACCEPTED",
+            EngineEventType::TradeExecuted => "TRADE_EXECUTED",
+            EngineEventType::OrderCanceled => "ORDER_CANCELED",
+            EngineEventType::OrdersCanceled => "ORDERS_CANCELED",
+            EngineEventType::OrderAmended => "ORDER_AMENDED",
+        }
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_uppercase().as_str() {
+            "ORDER_ACCEPTED" => Ok(EngineEventType::OrderAccepted),
+            "TRADE_EXECUTED" => Ok(EngineEventType::TradeExecuted),
+            "ORDER_CANCELED" => Ok(EngineEventType::OrderCanceled),
+            "ORDERS_CANCELED" => Ok(EngineEventType::OrdersCanceled),
+            "ORDER_AMENDED" => Ok(EngineEventType::OrderAmended),
+            _ => Err(format!("Unknown engine event type: {}", s)),
+        }
+    }
+}
+
 // Market model
 #[derive(Debug, Clone, Queryable, Selectable, Serialize, Deserialize)]
 #[diesel(table_name = markets)]
@@ -162,11 +331,7 @@ pub struct Market {
 
 impl Market {
     pub fn get_status(&self) -> Result<MarketStatus, String> {
-        match self.status.as_str() {
-            "ACTIVE" => Ok(MarketStatus::Active),
-            "CLOSED" => Ok(MarketStatus::Closed),
-            _ => Err(format!("Unknown market status: {}", self.status)),
-        }
+        MarketStatus::from_str(&self.status)
     }
 }
 
@@ -188,6 +353,18 @@ pub struct NewMarket {
     pub amount_precision: i32,
 }
 
+// Trading parameters of a market to change, fields left None keep their value
+#[derive(Debug, Clone, Default, AsChangeset)]
+#[diesel(table_name = markets)]
+pub struct MarketUpdate {
+    pub default_maker_fee: Option<BigDecimal>,
+    pub default_taker_fee: Option<BigDecimal>,
+    pub min_base_amount: Option<BigDecimal>,
+    pub min_quote_amount: Option<BigDecimal>,
+    pub price_precision: Option<i32>,
+    pub amount_precision: Option<i32>,
+}
+
 // Order model
 #[derive(Debug, Clone, Queryable, Selectable, Serialize, Deserialize)]
 #[diesel(belongs_to(Market))]
@@ -205,6 +382,7 @@ pub struct Order {
     pub taker_fee: BigDecimal,
     pub create_time: i64,
     pub remained_base: BigDecimal,
+    // Buys: quote budget still locked. Sells: remained_base valued at the order price
     pub remained_quote: BigDecimal,
     pub filled_base: BigDecimal,
     pub filled_quote: BigDecimal,
@@ -215,6 +393,10 @@ pub struct Order {
     pub post_only: Option<bool>,
     pub time_in_force: Option<String>,
     pub expires_at: Option<i64>,
+    pub cancel_reason: Option<String>, // Will be converted to/from CancelReason enum
+    pub display_amount: Option<BigDecimal>, // Iceberg orders: base amount shown in the book
+    pub reject_reason: Option<String>, // Will be converted to/from RejectReason enum
+    pub priority: i64,                 // Place in the queue of its price level, lower trades first
 }
 
 // Helper methods to work with enums
@@ -230,6 +412,26 @@ impl Order {
     pub fn get_status(&self) -> Result<OrderStatus, String> {
         OrderStatus::from_str(&self.status)
     }
+
+    pub fn get_cancel_reason(&self) -> Result<Option<CancelReason>, String> {
+        self.cancel_reason
+            .as_deref()
+            .map(CancelReason::from_str)
+            .transpose()
+    }
+
+    pub fn get_reject_reason(&self) -> Result<Option<RejectReason>, String> {
+        self.reject_reason
+            .as_deref()
+            .map(RejectReason::from_str)
+            .transpose()
+    }
+
+    /// Price the order filled at on average, `filled_quote / filled_base`, which is the
+    /// volume weighted price of its trades. `None` until something filled.
+    pub fn average_fill_price(&self) -> Option<BigDecimal> {
+        (!self.filled_base.is_zero()).then(|| &self.filled_quote / &self.filled_base)
+    }
 }
 
 // New Order for insertion
@@ -259,6 +461,42 @@ pub struct NewOrder {
     pub post_only: Option<bool>,
     pub time_in_force: Option<String>,
     pub expires_at: Option<i64>,
+    pub display_amount: Option<BigDecimal>,
+    pub priority: i64,
+}
+
+/// The order as the orders table has it right after the insert
+impl From<NewOrder> for Order {
+    fn from(order: NewOrder) -> Self {
+        Self {
+            id: order.id,
+            market_id: order.market_id,
+            user_id: order.user_id,
+            order_type: order.order_type,
+            side: order.side,
+            price: order.price,
+            base_amount: order.base_amount,
+            quote_amount: order.quote_amount,
+            maker_fee: order.maker_fee,
+            taker_fee: order.taker_fee,
+            create_time: order.create_time,
+            remained_base: order.remained_base,
+            remained_quote: order.remained_quote,
+            filled_base: order.filled_base,
+            filled_quote: order.filled_quote,
+            filled_fee: order.filled_fee,
+            update_time: order.update_time,
+            status: order.status,
+            client_order_id: order.client_order_id,
+            post_only: order.post_only,
+            time_in_force: order.time_in_force,
+            expires_at: order.expires_at,
+            cancel_reason: None,
+            display_amount: order.display_amount,
+            reject_reason: None,
+            priority: order.priority,
+        }
+    }
 }
 
 // Trade model
@@ -303,19 +541,128 @@ pub struct NewTrade {
     pub is_liquidation: Option<bool>,
 }
 
+// A fill the engine matched, to be settled with the other fills of the same incoming order
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TradeFill {
+    pub is_buyer_taker: bool,
+    pub buyer_user_id: String,
+    pub seller_user_id: String,
+    pub buyer_order_id: String,
+    pub seller_order_id: String,
+    pub price: BigDecimal,
+    pub base_amount: BigDecimal,
+    pub quote_amount: BigDecimal,
+    pub buyer_fee_rate: BigDecimal,
+    pub seller_fee_rate: BigDecimal,
+    /// Id the engine gave the trade ahead of settlement, one is generated when absent
+    pub trade_id: Option<String>,
+    /// Time of the trade in seconds, given with `trade_id`
+    pub timestamp: Option<i64>,
+}
+
+// Balance of one wallet touched by a trade, before and after its settlement
+#[derive(Debug, Clone, Queryable, Selectable, Insertable, Serialize, Deserialize)]
+#[diesel(primary_key(trade_id, user_id, asset))]
+#[diesel(table_name = trade_balance_snapshots)]
+pub struct TradeBalanceSnapshot {
+    pub trade_id: String,
+    pub user_id: String,
+    pub asset: String,
+    pub available_before: BigDecimal,
+    pub locked_before: BigDecimal,
+    pub available_after: BigDecimal,
+    pub locked_after: BigDecimal,
+}
+
+// Trade with the balance snapshots recorded at settlement, empty when snapshots were off
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TradeDetail {
+    pub trade: Trade,
+    pub balance_snapshots: Vec<TradeBalanceSnapshot>,
+}
+
+// A trade as one order in it took part: the side and role of the order, the fee it paid and
+// how much of it was filled once the trade settled
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderFill {
+    pub trade: Trade,
+    pub side: OrderSide,
+    pub role: MarketRole,
+    pub fee: BigDecimal,
+    pub filled_base: BigDecimal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum MarketStatus {
     Active,
-    Closed, // Market is closed and no longer accepting orders
+    PostOnly,       // Only post-only limit orders are accepted, so nothing trades
+    HaltedMatching, // Neither orders nor cancels are accepted, the book is frozen
+    CancelOnly,     // Orders can be canceled but no new ones placed
+    Closed,         // Market is closed and no longer accepting orders
 }
 
 impl MarketStatus {
     pub fn as_str(&self) -> &'static str {
         match self {
             MarketStatus::Active => "ACTIVE",
+            MarketStatus::PostOnly => "POST_ONLY",
+            MarketStatus::HaltedMatching => "HALTED_MATCHING",
+            MarketStatus::CancelOnly => "CANCEL_ONLY",
             MarketStatus::Closed => "CLOSED",
         }
     }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_uppercase().as_str() {
+            "ACTIVE" => Ok(MarketStatus::Active),
+            "POST_ONLY" => Ok(MarketStatus::PostOnly),
+            "HALTED_MATCHING" => Ok(MarketStatus::HaltedMatching),
+            "CANCEL_ONLY" => Ok(MarketStatus::CancelOnly),
+            "CLOSED" => Ok(MarketStatus::Closed),
+            _ => Err(format!("Unknown market status: {}", s)),
+        }
+    }
+
+    /// Whether users may cancel their orders
+    pub fn accepts_cancels(&self) -> bool {
+        matches!(
+            self,
+            MarketStatus::Active | MarketStatus::PostOnly | MarketStatus::CancelOnly
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UserStatus {
+    Active,     // Trades freely, users without a restriction are active
+    CancelOnly, // Orders can be canceled but no new ones placed or amended
+    Banned,     // Like cancel only, with the open orders canceled when banned
+}
+
+impl UserStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UserStatus::Active => "ACTIVE",
+            UserStatus::CancelOnly => "CANCEL_ONLY",
+            UserStatus::Banned => "BANNED",
+        }
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_uppercase().as_str() {
+            "ACTIVE" => Ok(UserStatus::Active),
+            "CANCEL_ONLY" => Ok(UserStatus::CancelOnly),
+            "BANNED" => Ok(UserStatus::Banned),
+            _ => Err(format!("Unknown user status: {}", s)),
+        }
+    }
+
+    /// Whether the user may place and amend orders
+    pub fn accepts_orders(&self) -> bool {
+        matches!(self, UserStatus::Active)
+    }
 }
 
 // Balance model
@@ -372,6 +719,130 @@ pub struct NewMarketStat {
     pub last_price: BigDecimal,
     pub last_update_time: i64,
 }
+// Best bid and ask of a market as last stored by the engine
+#[derive(Debug, Clone, Queryable, Selectable, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = market_quotes)]
+pub struct MarketQuote {
+    pub market_id: String,
+    pub best_bid: Option<BigDecimal>,
+    pub best_ask: Option<BigDecimal>,
+    pub update_time: i64,
+}
+
+// Everything known about a market's current price, absent parts left as None
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ticker {
+    pub market_id: String,
+    pub stats: Option<MarketStat>,
+    pub quote: Option<MarketQuote>,
+}
+
+// Width of the candles trades are aggregated into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KlineInterval {
+    OneMinute,
+    FiveMinutes,
+    OneHour,
+    OneDay,
+}
+
+impl KlineInterval {
+    pub const ALL: [KlineInterval; 4] = [
+        KlineInterval::OneMinute,
+        KlineInterval::FiveMinutes,
+        KlineInterval::OneHour,
+        KlineInterval::OneDay,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KlineInterval::OneMinute => "1m",
+            KlineInterval::FiveMinutes => "5m",
+            KlineInterval::OneHour => "1h",
+            KlineInterval::OneDay => "1d",
+        }
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "1m" => Ok(KlineInterval::OneMinute),
+            "5m" => Ok(KlineInterval::FiveMinutes),
+            "1h" => Ok(KlineInterval::OneHour),
+            "1d" => Ok(KlineInterval::OneDay),
+            _ => Err(format!("Unknown kline interval: {}", s)),
+        }
+    }
+
+    pub fn seconds(&self) -> i64 {
+        match self {
+            KlineInterval::OneMinute => 60,
+            KlineInterval::FiveMinutes => 5 * 60,
+            KlineInterval::OneHour => 60 * 60,
+            KlineInterval::OneDay => 24 * 60 * 60,
+        }
+    }
+
+    /// Start of the candle a `timestamp` in seconds falls in
+    pub fn open_time(&self, timestamp: i64) -> i64 {
+        timestamp - timestamp.rem_euclid(self.seconds())
+    }
+}
+
+// One candle of a market, covering `open_time` up to the next candle of its interval
+#[derive(Debug, Clone, Queryable, Selectable, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = klines)]
+pub struct Kline {
+    pub market_id: String,
+    pub interval: String,
+    pub open_time: i64,
+    pub open: BigDecimal,
+    pub high: BigDecimal,
+    pub low: BigDecimal,
+    pub close: BigDecimal,
+    pub volume: BigDecimal,
+    pub quote_volume: BigDecimal,
+    pub trade_count: i64,
+}
+
+// Open orders resting on one side of the books, with their remaining base amount
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OpenOrderStat {
+    pub side: String,
+    pub order_count: i64,
+    pub remained_base: BigDecimal,
+}
+
+// Base amount resting at one price of one side of a book, counting only the visible slice of
+// icebergs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BookLevel {
+    pub side: String,
+    pub price: BigDecimal,
+    pub amount: BigDecimal,
+}
+
+// Orders of a user in one status
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrderStatusCount {
+    pub status: String,
+    pub order_count: i64,
+}
+
+// Fees a user paid in one asset, summed over their trades
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserFeePaid {
+    pub asset: String,
+    pub amount: BigDecimal,
+}
+
+// Quote volume a user traded in one asset, summed over the markets quoted in it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserTradedVolume {
+    pub asset: String,
+    pub volume: BigDecimal,
+}
+
 // Fee Treasury model
 #[derive(Debug, Clone, Queryable, Selectable, Serialize, Deserialize)]
 #[diesel(belongs_to(Market))]
@@ -383,6 +854,7 @@ pub struct FeeTreasury {
     pub treasury_address: String,
     pub collected_amount: BigDecimal,
     pub last_update_time: i64,
+    pub total_withdrawn: BigDecimal,
 }
 
 #[derive(Debug, Clone, Insertable, Serialize, Deserialize)]
@@ -394,3 +866,508 @@ pub struct NewFeeTreasury {
     pub collected_amount: BigDecimal,
     pub last_update_time: i64,
 }
+
+// Fees moved out of a market's treasury
+#[derive(Debug, Clone, PartialEq, Queryable, Selectable, Insertable, Serialize, Deserialize)]
+#[diesel(belongs_to(Market))]
+#[diesel(table_name = fee_treasury_withdrawals)]
+pub struct FeeTreasuryWithdrawal {
+    pub id: String,
+    pub market_id: String,
+    pub asset: String,
+    pub amount: BigDecimal,
+    pub destination: String,
+    pub create_time: i64,
+}
+
+// Maker/taker rates a market charges from a 30-day traded volume on
+#[derive(Debug, Clone, PartialEq, Queryable, Selectable, Insertable, Serialize, Deserialize)]
+#[diesel(belongs_to(Market))]
+#[diesel(primary_key(market_id, min_volume))]
+#[diesel(table_name = fee_tiers)]
+pub struct FeeTier {
+    pub market_id: String,
+    pub min_volume: BigDecimal,
+    pub maker_fee: BigDecimal,
+    pub taker_fee: BigDecimal,
+    pub create_time: i64,
+    pub update_time: i64,
+}
+
+// Maker/taker rates an operator set for one user, charged instead of the market's
+#[derive(Debug, Clone, PartialEq, Queryable, Selectable, Insertable, Serialize, Deserialize)]
+#[diesel(primary_key(user_id))]
+#[diesel(table_name = user_fee_overrides)]
+pub struct UserFeeOverride {
+    pub user_id: String,
+    pub maker_fee: BigDecimal,
+    pub taker_fee: BigDecimal,
+    pub create_time: i64,
+    pub update_time: i64,
+}
+
+// What an operator restricted a user to, see UserStatus
+#[derive(Debug, Clone, PartialEq, Queryable, Selectable, Insertable, Serialize, Deserialize)]
+#[diesel(primary_key(user_id))]
+#[diesel(table_name = user_restrictions)]
+pub struct UserRestriction {
+    pub user_id: String,
+    pub status: String,
+    pub reason: String,
+    pub update_time: i64,
+}
+
+// Price band and circuit breaker settings of a market
+#[derive(Debug, Clone, PartialEq, Queryable, Selectable, Insertable, Serialize, Deserialize)]
+#[diesel(belongs_to(Market))]
+#[diesel(primary_key(market_id))]
+#[diesel(table_name = price_bands)]
+pub struct PriceBand {
+    pub market_id: String,
+    pub band_percent: Option<BigDecimal>,
+    pub halt_percent: Option<BigDecimal>,
+    pub halt_window_ms: i64,
+    pub halt_duration_ms: i64,
+    pub create_time: i64,
+    pub update_time: i64,
+}
+
+// Per-user order limits of a market
+#[derive(Debug, Clone, PartialEq, Queryable, Selectable, Insertable, Serialize, Deserialize)]
+#[diesel(belongs_to(Market))]
+#[diesel(primary_key(market_id))]
+#[diesel(table_name = risk_limits)]
+pub struct RiskLimit {
+    pub market_id: String,
+    pub max_open_orders: Option<i32>,
+    pub max_locked_notional: Option<BigDecimal>,
+    pub create_time: i64,
+    pub update_time: i64,
+}
+
+// OCO group: two orders of a user where filling or canceling one cancels the other
+#[derive(Debug, Clone, Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(belongs_to(Market))]
+#[diesel(table_name = oco_groups)]
+pub struct OcoGroup {
+    pub id: String,
+    pub market_id: String,
+    pub user_id: String,
+    pub first_order_id: String,
+    pub second_order_id: String,
+    pub create_time: i64,
+}
+
+impl OcoGroup {
+    /// The leg paired with `order_id`
+    pub fn sibling_of(&self, order_id: &str) -> &str {
+        if self.first_order_id == order_id {
+            &self.second_order_id
+        } else {
+            &self.first_order_id
+        }
+    }
+}
+
+#[derive(Debug, Clone, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = oco_groups)]
+pub struct NewOcoGroup {
+    pub id: String,
+    pub market_id: String,
+    pub user_id: String,
+    pub first_order_id: String,
+    pub second_order_id: String,
+    pub create_time: i64,
+}
+
+// Order audit entry, append-only
+#[derive(Debug, Clone, Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(table_name = order_audit)]
+pub struct OrderAudit {
+    pub id: i64,
+    pub action: String,
+    pub user_id: Option<String>,
+    pub remote_addr: Option<String>,
+    pub market_id: String,
+    pub order_id: Option<String>,
+    pub request: String,
+    pub create_time: i64,
+}
+
+#[derive(Debug, Clone, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = order_audit)]
+pub struct NewOrderAudit {
+    pub action: String,
+    pub user_id: Option<String>,
+    pub remote_addr: Option<String>,
+    pub market_id: String,
+    pub order_id: Option<String>,
+    pub request: String,
+    pub create_time: i64,
+}
+
+// Engine journal entry, append-only
+#[derive(Debug, Clone, Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(belongs_to(Market))]
+#[diesel(table_name = engine_events)]
+pub struct EngineEvent {
+    pub sequence: i64,
+    pub market_id: String,
+    pub event_type: String,
+    pub order_id: Option<String>,
+    pub payload: String,
+    pub create_time: i64,
+}
+
+#[derive(Debug, Clone, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = engine_events)]
+pub struct NewEngineEvent {
+    pub market_id: String,
+    pub event_type: String,
+    pub order_id: Option<String>,
+    pub payload: String,
+    pub create_time: i64,
+}
+
+// Settlement the engine could not store, set aside with what the database refused it for
+#[derive(Debug, Clone, Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(belongs_to(Market))]
+#[diesel(table_name = settlement_dead_letters)]
+pub struct SettlementDeadLetter {
+    pub id: i64,
+    pub market_id: String,
+    /// Journal entry the settlement was journaled up to
+    pub sequence: i64,
+    /// The fills, JSON encoded
+    pub fills: String,
+    pub error: String,
+    pub create_time: i64,
+}
+
+#[derive(Debug, Clone, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = settlement_dead_letters)]
+pub struct NewSettlementDeadLetter {
+    pub market_id: String,
+    pub sequence: i64,
+    pub fills: String,
+    pub error: String,
+    pub create_time: i64,
+}
+
+// Serialized order book of a market, replaced by each new snapshot
+#[derive(Debug, Clone, Queryable, Selectable, Insertable, Serialize, Deserialize)]
+#[diesel(belongs_to(Market))]
+#[diesel(primary_key(market_id))]
+#[diesel(table_name = order_book_snapshots)]
+pub struct OrderBookSnapshot {
+    pub market_id: String,
+    pub applied_sequence: i64,
+    pub payload: String,
+    pub create_time: i64,
+}
+
+// What an outbox event describes, and the subject it is published under
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OutboxTopic {
+    Trade,
+    Order,
+    Wallet,
+}
+
+impl OutboxTopic {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OutboxTopic::Trade => "TRADE",
+            OutboxTopic::Order => "ORDER",
+            OutboxTopic::Wallet => "WALLET",
+        }
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_uppercase().as_str() {
+            "TRADE" => Ok(OutboxTopic::Trade),
+            "ORDER" => Ok(OutboxTopic::Order),
+            "WALLET" => Ok(OutboxTopic::Wallet),
+            _ => Err(format!("Unknown outbox topic: {}", s)),
+        }
+    }
+}
+
+// Event waiting in the outbox to be published, or already published
+#[derive(Debug, Clone, Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(belongs_to(Market))]
+#[diesel(table_name = events)]
+pub struct OutboxEvent {
+    pub id: i64,
+    pub topic: String,
+    pub market_id: String,
+    pub payload: String,
+    pub create_time: i64,
+    pub published_time: Option<i64>,
+}
+
+impl OutboxEvent {
+    pub fn get_topic(&self) -> Result<OutboxTopic, String> {
+        OutboxTopic::from_str(&self.topic)
+    }
+}
+
+#[derive(Debug, Clone, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = events)]
+pub struct NewOutboxEvent {
+    pub topic: String,
+    pub market_id: String,
+    pub payload: String,
+    pub create_time: i64,
+}
+
+// Why funds moved, recorded on every ledger entry of the movement
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LedgerEntryKind {
+    Deposit,
+    Withdrawal,
+    Lock,
+    Unlock,
+    Trade,
+    Fee,
+}
+
+impl LedgerEntryKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LedgerEntryKind::Deposit => "DEPOSIT",
+            LedgerEntryKind::Withdrawal => "WITHDRAWAL",
+            LedgerEntryKind::Lock => "LOCK",
+            LedgerEntryKind::Unlock => "UNLOCK",
+            LedgerEntryKind::Trade => "TRADE",
+            LedgerEntryKind::Fee => "FEE",
+        }
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_uppercase().as_str() {
+            "DEPOSIT" => Ok(LedgerEntryKind::Deposit),
+            "WITHDRAWAL" => Ok(LedgerEntryKind::Withdrawal),
+            "LOCK" => Ok(LedgerEntryKind::Lock),
+            "UNLOCK" => Ok(LedgerEntryKind::Unlock),
+            "TRADE" => Ok(LedgerEntryKind::Trade),
+            "FEE" => Ok(LedgerEntryKind::Fee),
+            _ => Err(format!("Unknown ledger entry kind: {}", s)),
+        }
+    }
+}
+
+// Balance of an owner a ledger entry moves funds in or out of. `Reserved` holds requested
+// withdrawals, `External` stands for funds outside the exchange and `FeeTreasury` for a
+// market's collected fees.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LedgerAccount {
+    Available,
+    Locked,
+    Reserved,
+    External,
+    FeeTreasury,
+}
+
+impl LedgerAccount {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LedgerAccount::Available => "AVAILABLE",
+            LedgerAccount::Locked => "LOCKED",
+            LedgerAccount::Reserved => "RESERVED",
+            LedgerAccount::External => "EXTERNAL",
+            LedgerAccount::FeeTreasury => "FEE_TREASURY",
+        }
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_uppercase().as_str() {
+            "AVAILABLE" => Ok(LedgerAccount::Available),
+            "LOCKED" => Ok(LedgerAccount::Locked),
+            "RESERVED" => Ok(LedgerAccount::Reserved),
+            "EXTERNAL" => Ok(LedgerAccount::External),
+            "FEE_TREASURY" => Ok(LedgerAccount::FeeTreasury),
+            _ => Err(format!("Unknown ledger account: {}", s)),
+        }
+    }
+}
+
+// Ledger entry, append-only. `debit` is what left the account, `credit` what entered it.
+#[derive(Debug, Clone, Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(table_name = ledger_entries)]
+pub struct LedgerEntry {
+    pub id: i64,
+    pub transaction_id: String,
+    pub kind: String,
+    pub owner_id: String,
+    pub account: String,
+    pub asset: String,
+    pub debit: BigDecimal,
+    pub credit: BigDecimal,
+    pub reference_id: Option<String>,
+    pub create_time: i64,
+}
+
+#[derive(Debug, Clone, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = ledger_entries)]
+pub struct NewLedgerEntry {
+    pub transaction_id: String,
+    pub kind: String,
+    pub owner_id: String,
+    pub account: String,
+    pub asset: String,
+    pub debit: BigDecimal,
+    pub credit: BigDecimal,
+    pub reference_id: Option<String>,
+    pub create_time: i64,
+}
+
+// Movement of `amount` of `asset` from one owner's account to another's, written to the
+// ledger as a debit and a credit entry
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LedgerTransfer {
+    pub kind: LedgerEntryKind,
+    pub asset: String,
+    pub from: (String, LedgerAccount),
+    pub to: (String, LedgerAccount),
+    pub amount: BigDecimal,
+}
+
+impl LedgerTransfer {
+    pub fn new(
+        kind: LedgerEntryKind,
+        asset: &str,
+        from: (&str, LedgerAccount),
+        to: (&str, LedgerAccount),
+        amount: BigDecimal,
+    ) -> Self {
+        LedgerTransfer {
+            kind,
+            asset: asset.to_string(),
+            from: (from.0.to_string(), from.1),
+            to: (to.0.to_string(), to.1),
+            amount,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransferKind {
+    Deposit,
+    Withdrawal,
+}
+
+impl TransferKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransferKind::Deposit => "DEPOSIT",
+            TransferKind::Withdrawal => "WITHDRAWAL",
+        }
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_uppercase().as_str() {
+            "DEPOSIT" => Ok(TransferKind::Deposit),
+            "WITHDRAWAL" => Ok(TransferKind::Withdrawal),
+            _ => Err(format!("Unknown transfer kind: {}", s)),
+        }
+    }
+}
+
+// Where a transfer is in its workflow. Withdrawals go from pending to approved to completed, or
+// to rejected from either of the first two; deposits are recorded once completed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransferStatus {
+    Pending,
+    Approved,
+    Completed,
+    Rejected,
+}
+
+impl TransferStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransferStatus::Pending => "PENDING",
+            TransferStatus::Approved => "APPROVED",
+            TransferStatus::Completed => "COMPLETED",
+            TransferStatus::Rejected => "REJECTED",
+        }
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_uppercase().as_str() {
+            "PENDING" => Ok(TransferStatus::Pending),
+            "APPROVED" => Ok(TransferStatus::Approved),
+            "COMPLETED" => Ok(TransferStatus::Completed),
+            "REJECTED" => Ok(TransferStatus::Rejected),
+            _ => Err(format!("Unknown transfer status: {}", s)),
+        }
+    }
+}
+
+// Deposit into or withdrawal out of a user's wallet
+#[derive(Debug, Clone, Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(table_name = transfers)]
+pub struct Transfer {
+    pub id: String,
+    pub user_id: String,
+    pub asset: String,
+    pub kind: String,
+    pub amount: BigDecimal,
+    pub status: String,
+    pub address: Option<String>,
+    pub external_id: Option<String>,
+    pub reject_reason: Option<String>,
+    pub create_time: i64,
+    pub update_time: i64,
+}
+
+#[derive(Debug, Clone, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = transfers)]
+pub struct NewTransfer {
+    pub id: String,
+    pub user_id: String,
+    pub asset: String,
+    pub kind: String,
+    pub amount: BigDecimal,
+    pub status: String,
+    pub address: Option<String>,
+    pub external_id: Option<String>,
+    pub reject_reason: Option<String>,
+    pub create_time: i64,
+    pub update_time: i64,
+}
+
+// Balances of one asset summed over all wallets and fee treasuries, next to what was deposited
+// and withdrawn, fees swept out of treasuries included
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AssetBalanceTotals {
+    pub asset: String,
+    pub available: BigDecimal,
+    pub locked: BigDecimal,
+    pub reserved: BigDecimal,
+    pub fee_treasury: BigDecimal,
+    pub total_deposited: BigDecimal,
+    pub total_withdrawn: BigDecimal,
+}
+
+// Locked balance of a wallet next to what its user's open orders hold in that asset
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LockedBalance {
+    pub user_id: String,
+    pub asset: String,
+    pub locked: BigDecimal,
+    pub order_locked: BigDecimal,
+}
+
+// Balance totals and locks read from one snapshot of the database
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BalanceSheet {
+    pub assets: Vec<AssetBalanceTotals>,
+    pub locks: Vec<LockedBalance>,
+}
@@ -1,5 +1,53 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    engine_checkpoints (market_id) {
+        #[max_length = 36]
+        market_id -> Varchar,
+        applied_sequence -> Int8,
+        update_time -> Int8,
+    }
+}
+
+diesel::table! {
+    engine_events (sequence) {
+        sequence -> Int8,
+        #[max_length = 36]
+        market_id -> Varchar,
+        #[max_length = 20]
+        event_type -> Varchar,
+        #[max_length = 36]
+        order_id -> Nullable<Varchar>,
+        payload -> Text,
+        create_time -> Int8,
+    }
+}
+
+diesel::table! {
+    events (id) {
+        id -> Int8,
+        #[max_length = 20]
+        topic -> Varchar,
+        #[max_length = 36]
+        market_id -> Varchar,
+        payload -> Text,
+        create_time -> Int8,
+        published_time -> Nullable<Int8>,
+    }
+}
+
+diesel::table! {
+    fee_tiers (market_id, min_volume) {
+        #[max_length = 36]
+        market_id -> Varchar,
+        min_volume -> Numeric,
+        maker_fee -> Numeric,
+        taker_fee -> Numeric,
+        create_time -> Int8,
+        update_time -> Int8,
+    }
+}
+
 diesel::table! {
     fee_treasury (market_id, asset) {
         #[max_length = 36]
@@ -10,6 +58,70 @@ diesel::table! {
         treasury_address -> Varchar,
         collected_amount -> Numeric,
         last_update_time -> Int8,
+        total_withdrawn -> Numeric,
+    }
+}
+
+diesel::table! {
+    fee_treasury_withdrawals (id) {
+        #[max_length = 36]
+        id -> Varchar,
+        #[max_length = 36]
+        market_id -> Varchar,
+        #[max_length = 20]
+        asset -> Varchar,
+        amount -> Numeric,
+        #[max_length = 128]
+        destination -> Varchar,
+        create_time -> Int8,
+    }
+}
+
+diesel::table! {
+    klines (market_id, interval, open_time) {
+        #[max_length = 36]
+        market_id -> Varchar,
+        #[max_length = 3]
+        interval -> Varchar,
+        open_time -> Int8,
+        open -> Numeric,
+        high -> Numeric,
+        low -> Numeric,
+        close -> Numeric,
+        volume -> Numeric,
+        quote_volume -> Numeric,
+        trade_count -> Int8,
+    }
+}
+
+diesel::table! {
+    ledger_entries (id) {
+        id -> Int8,
+        #[max_length = 36]
+        transaction_id -> Varchar,
+        #[max_length = 20]
+        kind -> Varchar,
+        #[max_length = 36]
+        owner_id -> Varchar,
+        #[max_length = 20]
+        account -> Varchar,
+        #[max_length = 20]
+        asset -> Varchar,
+        debit -> Numeric,
+        credit -> Numeric,
+        #[max_length = 36]
+        reference_id -> Nullable<Varchar>,
+        create_time -> Int8,
+    }
+}
+
+diesel::table! {
+    market_quotes (market_id) {
+        #[max_length = 36]
+        market_id -> Varchar,
+        best_bid -> Nullable<Numeric>,
+        best_ask -> Nullable<Numeric>,
+        update_time -> Int8,
     }
 }
 
@@ -47,6 +159,50 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    oco_groups (id) {
+        #[max_length = 36]
+        id -> Varchar,
+        #[max_length = 36]
+        market_id -> Varchar,
+        #[max_length = 36]
+        user_id -> Varchar,
+        #[max_length = 36]
+        first_order_id -> Varchar,
+        #[max_length = 36]
+        second_order_id -> Varchar,
+        create_time -> Int8,
+    }
+}
+
+diesel::table! {
+    order_audit (id) {
+        id -> Int8,
+        #[max_length = 20]
+        action -> Varchar,
+        #[max_length = 36]
+        user_id -> Nullable<Varchar>,
+        #[max_length = 64]
+        remote_addr -> Nullable<Varchar>,
+        #[max_length = 36]
+        market_id -> Varchar,
+        #[max_length = 36]
+        order_id -> Nullable<Varchar>,
+        request -> Text,
+        create_time -> Int8,
+    }
+}
+
+diesel::table! {
+    order_book_snapshots (market_id) {
+        #[max_length = 36]
+        market_id -> Varchar,
+        applied_sequence -> Int8,
+        payload -> Text,
+        create_time -> Int8,
+    }
+}
+
 diesel::table! {
     orders (id) {
         #[max_length = 36]
@@ -79,6 +235,63 @@ diesel::table! {
         #[max_length = 10]
         time_in_force -> Nullable<Varchar>,
         expires_at -> Nullable<Int8>,
+        #[max_length = 20]
+        cancel_reason -> Nullable<Varchar>,
+        display_amount -> Nullable<Numeric>,
+        #[max_length = 30]
+        reject_reason -> Nullable<Varchar>,
+        priority -> Int8,
+    }
+}
+
+diesel::table! {
+    price_bands (market_id) {
+        #[max_length = 36]
+        market_id -> Varchar,
+        band_percent -> Nullable<Numeric>,
+        halt_percent -> Nullable<Numeric>,
+        halt_window_ms -> Int8,
+        halt_duration_ms -> Int8,
+        create_time -> Int8,
+        update_time -> Int8,
+    }
+}
+
+diesel::table! {
+    risk_limits (market_id) {
+        #[max_length = 36]
+        market_id -> Varchar,
+        max_open_orders -> Nullable<Int4>,
+        max_locked_notional -> Nullable<Numeric>,
+        create_time -> Int8,
+        update_time -> Int8,
+    }
+}
+
+diesel::table! {
+    settlement_dead_letters (id) {
+        id -> Int8,
+        #[max_length = 36]
+        market_id -> Varchar,
+        sequence -> Int8,
+        fills -> Text,
+        error -> Text,
+        create_time -> Int8,
+    }
+}
+
+diesel::table! {
+    trade_balance_snapshots (trade_id, user_id, asset) {
+        #[max_length = 36]
+        trade_id -> Varchar,
+        #[max_length = 36]
+        user_id -> Varchar,
+        #[max_length = 20]
+        asset -> Varchar,
+        available_before -> Numeric,
+        locked_before -> Numeric,
+        available_after -> Numeric,
+        locked_after -> Numeric,
     }
 }
 
@@ -108,6 +321,52 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    transfers (id) {
+        #[max_length = 36]
+        id -> Varchar,
+        #[max_length = 36]
+        user_id -> Varchar,
+        #[max_length = 20]
+        asset -> Varchar,
+        #[max_length = 20]
+        kind -> Varchar,
+        amount -> Numeric,
+        #[max_length = 20]
+        status -> Varchar,
+        #[max_length = 128]
+        address -> Nullable<Varchar>,
+        #[max_length = 128]
+        external_id -> Nullable<Varchar>,
+        #[max_length = 255]
+        reject_reason -> Nullable<Varchar>,
+        create_time -> Int8,
+        update_time -> Int8,
+    }
+}
+
+diesel::table! {
+    user_fee_overrides (user_id) {
+        #[max_length = 36]
+        user_id -> Varchar,
+        maker_fee -> Numeric,
+        taker_fee -> Numeric,
+        create_time -> Int8,
+        update_time -> Int8,
+    }
+}
+
+diesel::table! {
+    user_restrictions (user_id) {
+        #[max_length = 36]
+        user_id -> Varchar,
+        #[max_length = 20]
+        status -> Varchar,
+        reason -> Text,
+        update_time -> Int8,
+    }
+}
+
 diesel::table! {
     wallets (user_id, asset) {
         #[max_length = 36]
@@ -123,16 +382,47 @@ diesel::table! {
     }
 }
 
+diesel::joinable!(engine_checkpoints -> markets (market_id));
+diesel::joinable!(engine_events -> markets (market_id));
+diesel::joinable!(events -> markets (market_id));
+diesel::joinable!(fee_tiers -> markets (market_id));
 diesel::joinable!(fee_treasury -> markets (market_id));
+diesel::joinable!(fee_treasury_withdrawals -> markets (market_id));
+diesel::joinable!(klines -> markets (market_id));
+diesel::joinable!(market_quotes -> markets (market_id));
 diesel::joinable!(market_stats -> markets (market_id));
+diesel::joinable!(oco_groups -> markets (market_id));
+diesel::joinable!(order_book_snapshots -> markets (market_id));
 diesel::joinable!(orders -> markets (market_id));
+diesel::joinable!(price_bands -> markets (market_id));
+diesel::joinable!(risk_limits -> markets (market_id));
+diesel::joinable!(settlement_dead_letters -> markets (market_id));
+diesel::joinable!(trade_balance_snapshots -> trades (trade_id));
 diesel::joinable!(trades -> markets (market_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    engine_checkpoints,
+    engine_events,
+    events,
+    fee_tiers,
     fee_treasury,
+    fee_treasury_withdrawals,
+    klines,
+    ledger_entries,
+    market_quotes,
     market_stats,
     markets,
+    oco_groups,
+    order_audit,
+    order_book_snapshots,
     orders,
+    price_bands,
+    risk_limits,
+    settlement_dead_letters,
+    trade_balance_snapshots,
     trades,
+    transfers,
+    user_fee_overrides,
+    user_restrictions,
     wallets,
 );
@@ -1,3 +1,4 @@
+use crate::filters::LedgerFilter;
 use crate::filters::OrderFilter;
 use crate::filters::WalletFilter;
 use crate::{filters::TradeFilter, models::models::*};
@@ -7,29 +8,71 @@ use common::db::pagination::*;
 
 pub trait OrderDatabaseReader {
     fn get_order(&self, order_id: &str) -> Result<Option<Order>>;
+    /// The order `user_id` placed under `client_order_id`, unique per user across markets.
+    /// Rejected orders don't hold their id: one is returned only while no other order has it.
+    fn get_order_by_client_id(&self, user_id: &str, client_order_id: &str)
+    -> Result<Option<Order>>;
+    /// Open orders of `market_id` in the sequence they queue, lowest priority first
     fn get_active_orders(&self, market_id: &str) -> Result<Vec<Order>>;
+    /// Open GTD orders of `market_id` whose `expires_at` is at or before `now`
+    fn get_expired_orders(&self, market_id: &str, now: i64) -> Result<Vec<Order>>;
     fn list_orders(
         &self,
         filter: OrderFilter,
         pagination: Option<Pagination>,
     ) -> Result<Paginated<Order>>;
+    /// Counts open and partially filled orders across all markets, grouped by side.
+    fn get_open_order_stats(&self) -> Result<Vec<OpenOrderStat>>;
+    /// Price levels of the open and partially filled orders of `market_id`, as the book of
+    /// the engine would show them, unsorted.
+    fn get_book_levels(&self, market_id: &str) -> Result<Vec<BookLevel>>;
+    /// Counts a user's orders per status, in one market or across all of them.
+    fn get_user_order_counts(
+        &self,
+        user_id: &str,
+        market_id: Option<&str>,
+    ) -> Result<Vec<OrderStatusCount>>;
+    /// Counts the open and partially filled orders of `user_id` in `market_id`.
+    fn get_user_active_orders_count(&self, user_id: &str, market_id: &str) -> Result<i64>;
+    /// Quote value still held by the user's active orders in `market_id`: what is left of
+    /// their buys, and what is left of their sells at the order price.
+    fn get_user_locked_notional(&self, user_id: &str, market_id: &str) -> Result<BigDecimal>;
+    /// Open and partially filled orders of `user_id` in every market
+    fn get_user_active_orders(&self, user_id: &str) -> Result<Vec<Order>>;
 }
 
 pub trait OrderDatabaseWriter {
     fn create_order(&self, order_data: NewOrder) -> Result<Order>;
-    fn cancel_order(&self, order_id: &str) -> Result<Order>;
-    fn cancel_all_orders(&self, market_id: &str) -> Result<Vec<Order>>;
-    fn cancel_all_global_orders(&self) -> Result<Vec<Order>>;
+    /// Stores an order refused before it reached the book as REJECTED, with nothing left to
+    /// fill and no funds locked.
+    fn reject_order(&self, order_data: NewOrder, reason: RejectReason) -> Result<Order>;
+    fn cancel_order(&self, order_id: &str, reason: CancelReason) -> Result<Order>;
+    fn cancel_all_orders(&self, market_id: &str, reason: CancelReason) -> Result<Vec<Order>>;
+    fn cancel_all_global_orders(&self, reason: CancelReason) -> Result<Vec<Order>>;
     fn update_order_status(&self, order_id: &str, status: OrderStatus) -> Result<Order>;
+    /// Moves an open limit order to `price` with `remained_base` left to fill, queued at
+    /// `priority`, locking or unlocking the difference in funds it holds in the same
+    /// transaction.
+    fn amend_order(
+        &self,
+        order_id: &str,
+        price: BigDecimal,
+        remained_base: BigDecimal,
+        priority: i64,
+    ) -> Result<Order>;
 }
 
 pub trait WalletDatabaseReader {
     fn get_wallet(&self, user_id: &str, asset: &str) -> Result<Option<Wallet>>;
+    /// Every wallet of `user_id`, by asset
+    fn get_user_wallets(&self, user_id: &str) -> Result<Vec<Wallet>>;
     fn list_wallets(
         &self,
         filter: WalletFilter,
         pagination: Option<Pagination>,
     ) -> Result<Paginated<Wallet>>;
+    /// Returns the user's wallets updated after `since_time` (milliseconds), oldest change first.
+    fn get_wallet_changes(&self, user_id: &str, since_time: i64) -> Result<Vec<Wallet>>;
 }
 
 pub trait WalletDatabaseWriter {
@@ -37,6 +80,9 @@ pub trait WalletDatabaseWriter {
     fn withdraw_balance(&self, user_id: &str, asset: &str, amount: BigDecimal) -> Result<Wallet>;
     fn lock_balance(&self, user_id: &str, asset: &str, amount: BigDecimal) -> Result<Wallet>;
     fn unlock_balance(&self, user_id: &str, asset: &str, amount: BigDecimal) -> Result<Wallet>;
+    /// Moves locked balance that is not backed by any open order back to available.
+    /// Returns the wallets that were adjusted.
+    fn release_orphaned_locks(&self, user_id: &str) -> Result<Vec<Wallet>>;
 }
 
 pub trait TradeDatabaseReader {
@@ -45,9 +91,45 @@ pub trait TradeDatabaseReader {
         filter: TradeFilter,
         pagination: Option<Pagination>,
     ) -> Result<Paginated<Trade>>;
+    /// Up to `limit` trades matching `filter`, oldest first, starting after `after`. Unlike
+    /// `list_trades` it counts nothing, so that walking every trade in pages stays cheap.
+    fn export_trades(
+        &self,
+        filter: TradeFilter,
+        after: Option<&Cursor>,
+        limit: i64,
+    ) -> Result<Vec<Trade>>;
+    /// Every trade `order_id` took either side of, oldest first, as the order saw it.
+    fn get_order_fills(&self, order_id: &str) -> Result<Vec<OrderFill>>;
+    /// Fetches a trade with the wallet balances of its counterparties around settlement.
+    fn get_trade_detail(&self, trade_id: &str) -> Result<Option<TradeDetail>>;
+    /// Sums the fees a user paid per asset: `buyer_fee` in the base asset of trades where
+    /// they bought and `seller_fee` in the quote asset of trades where they sold.
+    fn get_user_fees_paid(
+        &self,
+        user_id: &str,
+        start_time: Option<i64>,
+        end_time: Option<i64>,
+    ) -> Result<Vec<UserFeePaid>>;
+    /// Quote volume of the trades `user_id` took either side of in `market_id` since
+    /// `start_time`, in seconds like trade timestamps.
+    fn get_user_traded_volume(
+        &self,
+        user_id: &str,
+        market_id: &str,
+        start_time: i64,
+    ) -> Result<BigDecimal>;
+    /// Quote volume of the trades `user_id` took either side of since `start_time`, in
+    /// seconds, summed per quote asset across markets.
+    fn get_user_traded_volumes(
+        &self,
+        user_id: &str,
+        start_time: i64,
+    ) -> Result<Vec<UserTradedVolume>>;
 }
 
 pub trait TradeDatabaseWriter {
+    #[allow(clippy::too_many_arguments)]
     fn execute_limit_trade(
         &self,
         is_buyer_taker: bool,
@@ -64,6 +146,40 @@ pub trait TradeDatabaseWriter {
         buyer_fee_rate: BigDecimal,
         seller_fee_rate: BigDecimal,
     ) -> Result<NewTrade>;
+
+    /// Settles `fills` in order within one transaction, so they all trade or none does, and
+    /// returns their trades. Each order and wallet the fills share is written once, with what
+    /// the last of them leaves; both legs of an OCO group cannot fill in the same call.
+    fn execute_limit_trades(
+        &self,
+        market_id: &str,
+        base_asset: &str,
+        quote_asset: &str,
+        fills: &[TradeFill],
+    ) -> Result<Vec<NewTrade>>;
+
+    /// Settles `fills` like [`Self::execute_limit_trades`] and moves the engine checkpoint of
+    /// `market_id` to `sequence` in the same transaction, so the journal entries of the fills
+    /// count as applied exactly when their trades are stored.
+    fn execute_journaled_trades(
+        &self,
+        market_id: &str,
+        base_asset: &str,
+        quote_asset: &str,
+        fills: &[TradeFill],
+        sequence: i64,
+    ) -> Result<Vec<NewTrade>>;
+
+    /// Creates `order_data`, locking its funds, and settles `fills` in the same transaction,
+    /// so the order is never stored without the fills it matched. Without fills it is only
+    /// created.
+    fn create_order_with_trades(
+        &self,
+        order_data: NewOrder,
+        base_asset: &str,
+        quote_asset: &str,
+        fills: &[TradeFill],
+    ) -> Result<(Order, Vec<NewTrade>)>;
 }
 
 pub trait MarketDatabaseReader {
@@ -73,10 +189,15 @@ pub trait MarketDatabaseReader {
 
 pub trait MarketDatabaseWriter {
     fn create_market(&self, market_data: NewMarket) -> Result<Market>;
+    fn update_market_status(&self, market_id: &str, status: MarketStatus) -> Result<Market>;
+    /// Changes the trading parameters set in `changes`, leaving the others as they are
+    fn update_market(&self, market_id: &str, changes: MarketUpdate) -> Result<Market>;
 }
 
 pub trait MarketStatDatabaseReader {
     fn get_market_stats(&self, market_id: &str) -> Result<Option<MarketStat>>;
+    /// Stats and top of book of every market, ordered by market id
+    fn list_tickers(&self) -> Result<Vec<Ticker>>;
 }
 
 pub trait MarketStatDatabaseWriter {
@@ -89,16 +210,266 @@ pub trait MarketStatDatabaseWriter {
         price_change_24h: BigDecimal,
         last_price: BigDecimal,
     ) -> Result<MarketStat>;
+    /// Recomputes the stats of `market_id` over the 24h of trades up to `now` (seconds, like
+    /// trade timestamps). `None` when the market never traded.
+    fn refresh_market_stats(&self, market_id: &str, now: i64) -> Result<Option<MarketStat>>;
+    fn upsert_market_quote(
+        &self,
+        market_id: &str,
+        best_bid: Option<BigDecimal>,
+        best_ask: Option<BigDecimal>,
+    ) -> Result<MarketQuote>;
+}
+
+pub trait KlineDatabaseReader {
+    /// Candles of `market_id` opening within `start_time..=end_time` (seconds), oldest first.
+    /// Intervals without trades have no candle.
+    fn list_klines(
+        &self,
+        market_id: &str,
+        interval: KlineInterval,
+        start_time: Option<i64>,
+        end_time: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<Kline>>;
+}
+
+pub trait LedgerDatabaseReader {
+    /// Ledger entries matching `filter`, newest first
+    fn list_ledger_entries(
+        &self,
+        filter: LedgerFilter,
+        pagination: Option<Pagination>,
+    ) -> Result<Paginated<LedgerEntry>>;
+}
+
+/// Implemented by the connection a balance mutation runs its transaction on, so the entries
+/// commit or roll back together with the balances they describe.
+pub trait LedgerDatabaseWriter {
+    /// Writes a debit and a credit entry per transfer, all under one new transaction id.
+    /// Zero amounts move nothing and are skipped.
+    fn record_ledger_transfers(
+        &mut self,
+        reference_id: Option<&str>,
+        transfers: &[LedgerTransfer],
+    ) -> Result<Vec<LedgerEntry>>;
+
+    /// Like [`Self::record_ledger_transfers`] for several transactions at once, each under a
+    /// new id of its own, in a single insert.
+    fn record_ledger_transactions(
+        &mut self,
+        transactions: &[(Option<&str>, &[LedgerTransfer])],
+    ) -> Result<Vec<LedgerEntry>>;
+}
+
+pub trait ReconciliationDatabaseReader {
+    /// Per-asset balance totals and every wallet's lock next to its open orders, read in a
+    /// single repeatable-read transaction so trades settling meanwhile cannot skew them.
+    /// Wallets that lock nothing and back no open order are left out.
+    fn get_balance_sheet(&self) -> Result<BalanceSheet>;
+}
+
+pub trait TransferDatabaseReader {
+    fn get_transfer(&self, transfer_id: &str) -> Result<Option<Transfer>>;
+    /// Deposits and withdrawals of `user_id`, newest first
+    fn get_user_transfers(&self, user_id: &str) -> Result<Vec<Transfer>>;
+}
+
+pub trait TransferDatabaseWriter {
+    /// Credits a deposit received outside the exchange as `external_id`. A deposit already
+    /// recorded under that id is returned as it is instead of being credited again.
+    fn complete_deposit(
+        &self,
+        user_id: &str,
+        asset: &str,
+        amount: BigDecimal,
+        external_id: &str,
+    ) -> Result<Transfer>;
+    /// Moves `amount` from the available to the reserved balance under a pending withdrawal.
+    fn request_withdrawal(
+        &self,
+        user_id: &str,
+        asset: &str,
+        amount: BigDecimal,
+        address: &str,
+    ) -> Result<Transfer>;
+    fn approve_withdrawal(&self, transfer_id: &str) -> Result<Transfer>;
+    /// Rejects a pending or approved withdrawal, giving its reserved funds back.
+    fn reject_withdrawal(&self, transfer_id: &str, reason: &str) -> Result<Transfer>;
+    /// Settles an approved withdrawal sent out as `external_id`; its funds leave the wallet.
+    fn complete_withdrawal(&self, transfer_id: &str, external_id: &str) -> Result<Transfer>;
 }
 
 pub trait FeeTreasuryDatabaseReader {
-    fn get_fee_treasury(&self, market_id: &str) -> Result<Option<FeeTreasury>>;
-    fn list_fee_treasuries(&self) -> Result<Vec<FeeTreasury>>;
+    fn get_fee_treasury(&self, market_id: &str, asset: &str) -> Result<Option<FeeTreasury>>;
+    /// Treasuries of `market_id`, or of every market when `None`, one per asset, ordered by
+    /// market and asset
+    fn list_fee_treasuries(&self, market_id: Option<&str>) -> Result<Vec<FeeTreasury>>;
 }
 
 pub trait FeeTreasuryDatabaseWriter {
     fn create_fee_treasury(&self, fee_treasury_data: NewFeeTreasury) -> Result<FeeTreasury>;
     fn transfer_to_fee_treasury(&self, fee_amount: BigDecimal) -> Result<FeeTreasury>;
+    /// Sends `amount` of the fees collected in a treasury to `destination`, recording the
+    /// withdrawal and its ledger entries in the same transaction.
+    fn withdraw_from_fee_treasury(
+        &self,
+        market_id: &str,
+        asset: &str,
+        amount: BigDecimal,
+        destination: &str,
+    ) -> Result<FeeTreasuryWithdrawal>;
+}
+
+pub trait FeeTierDatabaseReader {
+    /// Fee tiers of `market_id`, lowest `min_volume` first
+    fn get_fee_tiers(&self, market_id: &str) -> Result<Vec<FeeTier>>;
+}
+
+pub trait FeeTierDatabaseWriter {
+    /// Creates the tier of `market_id` starting at `min_volume`, or replaces its rates.
+    fn set_fee_tier(
+        &self,
+        market_id: &str,
+        min_volume: BigDecimal,
+        maker_fee: BigDecimal,
+        taker_fee: BigDecimal,
+    ) -> Result<FeeTier>;
+    /// Returns whether there was a tier to delete.
+    fn delete_fee_tier(&self, market_id: &str, min_volume: &BigDecimal) -> Result<bool>;
+}
+
+pub trait PriceBandDatabaseReader {
+    fn get_price_band(&self, market_id: &str) -> Result<Option<PriceBand>>;
+}
+
+pub trait PriceBandDatabaseWriter {
+    /// Sets the price protection of `market_id`, replacing earlier settings. Markets read it
+    /// when their order book is created.
+    fn set_price_band(
+        &self,
+        market_id: &str,
+        band_percent: Option<BigDecimal>,
+        halt_percent: Option<BigDecimal>,
+        halt_window_ms: i64,
+        halt_duration_ms: i64,
+    ) -> Result<PriceBand>;
+    /// Returns whether the market had settings to delete.
+    fn delete_price_band(&self, market_id: &str) -> Result<bool>;
+}
+
+pub trait RiskLimitDatabaseReader {
+    fn get_risk_limit(&self, market_id: &str) -> Result<Option<RiskLimit>>;
+}
+
+pub trait RiskLimitDatabaseWriter {
+    /// Sets the limits each user of `market_id` trades under, replacing earlier ones.
+    fn set_risk_limit(
+        &self,
+        market_id: &str,
+        max_open_orders: Option<i32>,
+        max_locked_notional: Option<BigDecimal>,
+    ) -> Result<RiskLimit>;
+    /// Returns whether the market had limits to delete.
+    fn delete_risk_limit(&self, market_id: &str) -> Result<bool>;
+}
+
+pub trait UserFeeOverrideDatabaseReader {
+    fn get_user_fee_override(&self, user_id: &str) -> Result<Option<UserFeeOverride>>;
+}
+
+pub trait UserFeeOverrideDatabaseWriter {
+    /// Sets the rates `user_id` pays on every market, zero included, replacing earlier ones.
+    fn set_user_fee_override(
+        &self,
+        user_id: &str,
+        maker_fee: BigDecimal,
+        taker_fee: BigDecimal,
+    ) -> Result<UserFeeOverride>;
+    /// Puts the user back on market and tier rates. Returns whether they had an override.
+    fn delete_user_fee_override(&self, user_id: &str) -> Result<bool>;
+}
+
+pub trait UserRestrictionDatabaseReader {
+    /// The user's restriction, `None` for an active user
+    fn get_user_restriction(&self, user_id: &str) -> Result<Option<UserRestriction>>;
+    /// Every restricted user, most recently restricted first
+    fn list_user_restrictions(&self) -> Result<Vec<UserRestriction>>;
+}
+
+pub trait UserRestrictionDatabaseWriter {
+    /// Restricts `user_id` to `status`, replacing the status and reason of an earlier
+    /// restriction.
+    fn set_user_restriction(
+        &self,
+        user_id: &str,
+        status: UserStatus,
+        reason: &str,
+    ) -> Result<UserRestriction>;
+    /// Makes the user active again. Returns whether they were restricted.
+    fn delete_user_restriction(&self, user_id: &str) -> Result<bool>;
+}
+
+pub trait OcoGroupDatabaseReader {
+    /// The group `order_id` is a leg of, if any
+    fn get_oco_group_by_order(&self, order_id: &str) -> Result<Option<OcoGroup>>;
+    /// Groups of `market_id` whose legs are both still open
+    fn get_active_oco_groups(&self, market_id: &str) -> Result<Vec<OcoGroup>>;
+}
+
+pub trait OcoGroupDatabaseWriter {
+    fn create_oco_group(&self, group: NewOcoGroup) -> Result<OcoGroup>;
+}
+
+pub trait AuditDatabaseReader {
+    /// Audit entries naming `user_id`, oldest first
+    fn get_user_order_audit(&self, user_id: &str) -> Result<Vec<OrderAudit>>;
+}
+
+pub trait AuditDatabaseWriter {
+    fn record_audit(&self, entry: NewOrderAudit) -> Result<OrderAudit>;
+}
+
+pub trait EngineEventDatabaseReader {
+    /// Journal entries of `market_id` after `after_sequence`, oldest first
+    fn get_engine_events(&self, market_id: &str, after_sequence: i64) -> Result<Vec<EngineEvent>>;
+    /// Sequence of the last journal entry of `market_id` whose effects were applied, 0 before
+    /// the first one.
+    fn get_applied_sequence(&self, market_id: &str) -> Result<i64>;
+    /// Settlements of `market_id` that could not be stored, oldest first
+    fn get_settlement_dead_letters(&self, market_id: &str) -> Result<Vec<SettlementDeadLetter>>;
+}
+
+pub trait EngineEventDatabaseWriter {
+    fn append_engine_event(&self, event: NewEngineEvent) -> Result<EngineEvent>;
+    /// Appends `events` in one insert, returning them in journal order.
+    fn append_engine_events(&self, events: Vec<NewEngineEvent>) -> Result<Vec<EngineEvent>>;
+    /// Records that the journal entries of `market_id` up to `sequence` have been applied.
+    fn set_applied_sequence(&self, market_id: &str, sequence: i64) -> Result<()>;
+    /// Sets aside a settlement that could not be stored.
+    fn store_settlement_dead_letter(
+        &self,
+        letter: NewSettlementDeadLetter,
+    ) -> Result<SettlementDeadLetter>;
+}
+
+pub trait OrderBookSnapshotDatabaseReader {
+    fn get_order_book_snapshot(&self, market_id: &str) -> Result<Option<OrderBookSnapshot>>;
+}
+
+pub trait OrderBookSnapshotDatabaseWriter {
+    /// Stores `snapshot` as the latest of its market, replacing the previous one.
+    fn store_order_book_snapshot(&self, snapshot: OrderBookSnapshot) -> Result<()>;
+}
+
+pub trait OutboxDatabaseReader {
+    /// Up to `limit` outbox events not published yet, oldest first
+    fn get_unpublished_events(&self, limit: i64) -> Result<Vec<OutboxEvent>>;
+}
+
+pub trait OutboxDatabaseWriter {
+    /// Records that the broker acknowledged the outbox events `ids`.
+    fn mark_events_published(&self, ids: &[i64]) -> Result<usize>;
 }
 
 pub trait ReadDatabaseProvider:
@@ -109,7 +480,21 @@ pub trait ReadDatabaseProvider:
     + TradeDatabaseReader
     + MarketDatabaseReader
     + MarketStatDatabaseReader
+    + KlineDatabaseReader
+    + LedgerDatabaseReader
+    + TransferDatabaseReader
+    + ReconciliationDatabaseReader
     + FeeTreasuryDatabaseReader
+    + FeeTierDatabaseReader
+    + UserFeeOverrideDatabaseReader
+    + PriceBandDatabaseReader
+    + RiskLimitDatabaseReader
+    + UserRestrictionDatabaseReader
+    + AuditDatabaseReader
+    + OcoGroupDatabaseReader
+    + EngineEventDatabaseReader
+    + OrderBookSnapshotDatabaseReader
+    + OutboxDatabaseReader
 {
 }
 
@@ -121,7 +506,18 @@ pub trait WriteDatabaseProvider:
     + TradeDatabaseWriter
     + MarketDatabaseWriter
     + MarketStatDatabaseWriter
+    + TransferDatabaseWriter
     + FeeTreasuryDatabaseWriter
+    + FeeTierDatabaseWriter
+    + UserFeeOverrideDatabaseWriter
+    + PriceBandDatabaseWriter
+    + RiskLimitDatabaseWriter
+    + UserRestrictionDatabaseWriter
+    + AuditDatabaseWriter
+    + OcoGroupDatabaseWriter
+    + EngineEventDatabaseWriter
+    + OrderBookSnapshotDatabaseWriter
+    + OutboxDatabaseWriter
 {
 }
 
@@ -133,7 +529,21 @@ impl<
         + TradeDatabaseReader
         + MarketDatabaseReader
         + MarketStatDatabaseReader
-        + FeeTreasuryDatabaseReader,
+        + KlineDatabaseReader
+        + LedgerDatabaseReader
+        + TransferDatabaseReader
+        + ReconciliationDatabaseReader
+        + FeeTreasuryDatabaseReader
+        + FeeTierDatabaseReader
+        + UserFeeOverrideDatabaseReader
+        + PriceBandDatabaseReader
+        + RiskLimitDatabaseReader
+        + UserRestrictionDatabaseReader
+        + AuditDatabaseReader
+        + OcoGroupDatabaseReader
+        + EngineEventDatabaseReader
+        + OrderBookSnapshotDatabaseReader
+        + OutboxDatabaseReader,
 > ReadDatabaseProvider for T
 {
 }
@@ -146,7 +556,18 @@ impl<
         + TradeDatabaseWriter
         + MarketDatabaseWriter
         + MarketStatDatabaseWriter
-        + FeeTreasuryDatabaseWriter,
+        + TransferDatabaseWriter
+        + FeeTreasuryDatabaseWriter
+        + FeeTierDatabaseWriter
+        + UserFeeOverrideDatabaseWriter
+        + PriceBandDatabaseWriter
+        + RiskLimitDatabaseWriter
+        + UserRestrictionDatabaseWriter
+        + AuditDatabaseWriter
+        + OcoGroupDatabaseWriter
+        + EngineEventDatabaseWriter
+        + OrderBookSnapshotDatabaseWriter
+        + OutboxDatabaseWriter,
 > WriteDatabaseProvider for T
 {
 }
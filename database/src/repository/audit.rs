@@ -0,0 +1,34 @@
+use super::Repository;
+use crate::models::models::*;
+
+use crate::models::schema::*;
+use crate::provider::{AuditDatabaseReader, AuditDatabaseWriter};
+
+use anyhow::Result;
+
+use diesel::prelude::*;
+
+impl AuditDatabaseReader for Repository {
+    fn get_user_order_audit(&self, user_id: &str) -> Result<Vec<OrderAudit>> {
+        let conn = &mut self.get_conn()?;
+
+        let result = order_audit::table
+            .filter(order_audit::user_id.eq(user_id))
+            .order(order_audit::id.asc())
+            .load(conn)?;
+
+        Ok(result)
+    }
+}
+
+impl AuditDatabaseWriter for Repository {
+    fn record_audit(&self, entry: NewOrderAudit) -> Result<OrderAudit> {
+        let conn = &mut self.get_conn()?;
+
+        let result = diesel::insert_into(order_audit::table)
+            .values(&entry)
+            .get_result(conn)?;
+
+        Ok(result)
+    }
+}
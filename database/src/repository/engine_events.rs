@@ -0,0 +1,109 @@
+use super::Repository;
+use crate::models::models::*;
+use crate::models::schema::*;
+use crate::provider::{EngineEventDatabaseReader, EngineEventDatabaseWriter};
+use anyhow::{Context, Result};
+use common::utils::get_utc_now_millis;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+
+impl EngineEventDatabaseReader for Repository {
+    fn get_engine_events(&self, market_id: &str, after_sequence: i64) -> Result<Vec<EngineEvent>> {
+        let conn = &mut self.get_conn()?;
+
+        engine_events::table
+            .filter(engine_events::market_id.eq(market_id))
+            .filter(engine_events::sequence.gt(after_sequence))
+            .order(engine_events::sequence.asc())
+            .load(conn)
+            .context("Failed to fetch engine events")
+    }
+
+    fn get_applied_sequence(&self, market_id: &str) -> Result<i64> {
+        let conn = &mut self.get_conn()?;
+
+        let applied = engine_checkpoints::table
+            .find(market_id)
+            .select(engine_checkpoints::applied_sequence)
+            .first(conn)
+            .optional()
+            .context("Failed to fetch engine checkpoint")?;
+
+        Ok(applied.unwrap_or(0))
+    }
+
+    fn get_settlement_dead_letters(&self, market_id: &str) -> Result<Vec<SettlementDeadLetter>> {
+        let conn = &mut self.get_conn()?;
+
+        settlement_dead_letters::table
+            .filter(settlement_dead_letters::market_id.eq(market_id))
+            .order(settlement_dead_letters::id.asc())
+            .load(conn)
+            .context("Failed to fetch settlement dead letters")
+    }
+}
+
+impl EngineEventDatabaseWriter for Repository {
+    fn append_engine_event(&self, event: NewEngineEvent) -> Result<EngineEvent> {
+        let conn = &mut self.get_conn()?;
+
+        diesel::insert_into(engine_events::table)
+            .values(&event)
+            .get_result(conn)
+            .context("Failed to append engine event")
+    }
+
+    fn append_engine_events(&self, events: Vec<NewEngineEvent>) -> Result<Vec<EngineEvent>> {
+        let conn = &mut self.get_conn()?;
+
+        let mut appended: Vec<EngineEvent> = diesel::insert_into(engine_events::table)
+            .values(&events)
+            .get_results(conn)
+            .context("Failed to append engine events")?;
+        appended.sort_by_key(|event| event.sequence);
+        Ok(appended)
+    }
+
+    fn set_applied_sequence(&self, market_id: &str, sequence: i64) -> Result<()> {
+        let conn = &mut self.get_conn()?;
+        store_applied_sequence(conn, market_id, sequence)
+    }
+
+    fn store_settlement_dead_letter(
+        &self,
+        letter: NewSettlementDeadLetter,
+    ) -> Result<SettlementDeadLetter> {
+        let conn = &mut self.get_conn()?;
+
+        diesel::insert_into(settlement_dead_letters::table)
+            .values(&letter)
+            .get_result(conn)
+            .context("Failed to store settlement dead letter")
+    }
+}
+
+/// Moves the checkpoint of `market_id` to `sequence` on `conn`, so it can be part of the
+/// transaction applying the entries up to it
+pub(super) fn store_applied_sequence(
+    conn: &mut PgConnection,
+    market_id: &str,
+    sequence: i64,
+) -> Result<()> {
+    let now = get_utc_now_millis();
+    diesel::insert_into(engine_checkpoints::table)
+        .values((
+            engine_checkpoints::market_id.eq(market_id),
+            engine_checkpoints::applied_sequence.eq(sequence),
+            engine_checkpoints::update_time.eq(now),
+        ))
+        .on_conflict(engine_checkpoints::market_id)
+        .do_update()
+        .set((
+            engine_checkpoints::applied_sequence.eq(sequence),
+            engine_checkpoints::update_time.eq(now),
+        ))
+        .execute(conn)
+        .context("Failed to store engine checkpoint")?;
+
+    Ok(())
+}
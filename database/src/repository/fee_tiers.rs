@@ -0,0 +1,63 @@
+use super::Repository;
+use crate::models::models::*;
+use crate::models::schema::*;
+use crate::provider::{FeeTierDatabaseReader, FeeTierDatabaseWriter};
+use anyhow::{Context, Result};
+use bigdecimal::BigDecimal;
+use common::utils::get_utc_now_millis;
+use diesel::prelude::*;
+
+impl FeeTierDatabaseReader for Repository {
+    fn get_fee_tiers(&self, market_id: &str) -> Result<Vec<FeeTier>> {
+        let conn = &mut self.get_conn()?;
+
+        fee_tiers::table
+            .filter(fee_tiers::market_id.eq(market_id))
+            .order(fee_tiers::min_volume.asc())
+            .load(conn)
+            .context("Failed to fetch fee tiers")
+    }
+}
+
+impl FeeTierDatabaseWriter for Repository {
+    fn set_fee_tier(
+        &self,
+        market_id: &str,
+        min_volume: BigDecimal,
+        maker_fee: BigDecimal,
+        taker_fee: BigDecimal,
+    ) -> Result<FeeTier> {
+        let conn = &mut self.get_conn()?;
+
+        let now = get_utc_now_millis();
+        let tier = FeeTier {
+            market_id: market_id.to_string(),
+            min_volume,
+            maker_fee,
+            taker_fee,
+            create_time: now,
+            update_time: now,
+        };
+        diesel::insert_into(fee_tiers::table)
+            .values(&tier)
+            .on_conflict((fee_tiers::market_id, fee_tiers::min_volume))
+            .do_update()
+            .set((
+                fee_tiers::maker_fee.eq(&tier.maker_fee),
+                fee_tiers::taker_fee.eq(&tier.taker_fee),
+                fee_tiers::update_time.eq(now),
+            ))
+            .get_result(conn)
+            .context("Failed to store fee tier")
+    }
+
+    fn delete_fee_tier(&self, market_id: &str, min_volume: &BigDecimal) -> Result<bool> {
+        let conn = &mut self.get_conn()?;
+
+        let deleted = diesel::delete(fee_tiers::table.find((market_id, min_volume)))
+            .execute(conn)
+            .context("Failed to delete fee tier")?;
+
+        Ok(deleted > 0)
+    }
+}
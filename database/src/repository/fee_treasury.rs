@@ -1,29 +1,36 @@
-use super::Repository;
+use super::{Repository, TreasuryError};
 use crate::models::models::*;
 
 use crate::models::schema::*;
-use crate::provider::{FeeTreasuryDatabaseReader, FeeTreasuryDatabaseWriter};
+use crate::provider::{FeeTreasuryDatabaseReader, FeeTreasuryDatabaseWriter, LedgerDatabaseWriter};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use bigdecimal::BigDecimal;
+use common::utils::{get_utc_now_millis, get_uuid_string};
 
 use diesel::prelude::*;
 
 impl FeeTreasuryDatabaseReader for Repository {
-    fn get_fee_treasury(&self, market_id: &str) -> Result<Option<FeeTreasury>> {
+    fn get_fee_treasury(&self, market_id: &str, asset: &str) -> Result<Option<FeeTreasury>> {
         let conn = &mut self.get_conn()?;
 
         let result = fee_treasury::table
-            .filter(fee_treasury::market_id.eq(market_id))
+            .find((market_id, asset))
             .first(conn)
             .optional()?;
 
         Ok(result)
     }
-    fn list_fee_treasuries(&self) -> Result<Vec<FeeTreasury>> {
+    fn list_fee_treasuries(&self, market_id: Option<&str>) -> Result<Vec<FeeTreasury>> {
         let conn = &mut self.get_conn()?;
 
-        let result = fee_treasury::table.load(conn)?;
+        let mut query = fee_treasury::table.into_boxed();
+        if let Some(market_id) = market_id {
+            query = query.filter(fee_treasury::market_id.eq(market_id));
+        }
+        let result = query
+            .order((fee_treasury::market_id.asc(), fee_treasury::asset.asc()))
+            .load(conn)?;
 
         Ok(result)
     }
@@ -43,6 +50,69 @@ impl FeeTreasuryDatabaseWriter for Repository {
         Ok(result)
     }
 
+    fn withdraw_from_fee_treasury(
+        &self,
+        market_id: &str,
+        asset: &str,
+        amount: BigDecimal,
+        destination: &str,
+    ) -> Result<FeeTreasuryWithdrawal> {
+        let conn = &mut self.get_conn()?;
+        conn.transaction(|conn| {
+            let treasury = fee_treasury::table
+                .find((market_id, asset))
+                .for_update()
+                .first::<FeeTreasury>(conn)
+                .optional()
+                .context("Failed to fetch fee treasury")?
+                .ok_or_else(|| TreasuryError::NotFound {
+                    market_id: market_id.to_string(),
+                    asset: asset.to_string(),
+                })?;
+            if treasury.collected_amount < amount {
+                return Err(TreasuryError::InsufficientFees {
+                    asset: asset.to_string(),
+                    collected: treasury.collected_amount,
+                }
+                .into());
+            }
+
+            let now = get_utc_now_millis();
+            diesel::update(fee_treasury::table.find((market_id, asset)))
+                .set((
+                    fee_treasury::collected_amount.eq(fee_treasury::collected_amount - &amount),
+                    fee_treasury::total_withdrawn.eq(fee_treasury::total_withdrawn + &amount),
+                    fee_treasury::last_update_time.eq(now),
+                ))
+                .execute(conn)
+                .context("Failed to debit fee treasury")?;
+
+            let withdrawal = diesel::insert_into(fee_treasury_withdrawals::table)
+                .values(&FeeTreasuryWithdrawal {
+                    id: get_uuid_string(),
+                    market_id: market_id.to_string(),
+                    asset: asset.to_string(),
+                    amount: amount.clone(),
+                    destination: destination.to_string(),
+                    create_time: now,
+                })
+                .get_result::<FeeTreasuryWithdrawal>(conn)
+                .context("Failed to record fee treasury withdrawal")?;
+
+            conn.record_ledger_transfers(
+                Some(&withdrawal.id),
+                &[LedgerTransfer::new(
+                    LedgerEntryKind::Withdrawal,
+                    asset,
+                    (market_id, LedgerAccount::FeeTreasury),
+                    (market_id, LedgerAccount::External),
+                    amount,
+                )],
+            )?;
+            Ok(withdrawal)
+        })
+    }
+
     fn transfer_to_fee_treasury(&self, fee_amount: BigDecimal) -> Result<FeeTreasury> {
         let conn = &mut self.get_conn()?;
 
@@ -0,0 +1,111 @@
+use crate::{models::models::*, provider::KlineDatabaseReader};
+
+use super::Repository;
+use crate::models::schema::*;
+use anyhow::{Context, Result};
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::sql_types::Numeric;
+use diesel::upsert::excluded;
+use std::collections::BTreeMap;
+
+define_sql_function!(fn greatest(a: Numeric, b: Numeric) -> Numeric);
+define_sql_function!(fn least(a: Numeric, b: Numeric) -> Numeric);
+
+impl KlineDatabaseReader for Repository {
+    fn list_klines(
+        &self,
+        market_id: &str,
+        interval: KlineInterval,
+        start_time: Option<i64>,
+        end_time: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<Kline>> {
+        let conn = &mut self.get_conn()?;
+
+        let mut query = klines::table
+            .filter(klines::market_id.eq(market_id))
+            .filter(klines::interval.eq(interval.as_str()))
+            .into_boxed();
+        if let Some(start_time) = start_time {
+            query = query.filter(klines::open_time.ge(start_time));
+        }
+        if let Some(end_time) = end_time {
+            query = query.filter(klines::open_time.le(end_time));
+        }
+
+        let klines = query
+            .order(klines::open_time.asc())
+            .limit(limit)
+            .load(conn)
+            .context("Failed to list klines")?;
+        Ok(klines)
+    }
+}
+
+/// Folds `trades`, in the order they settled, into one candle per interval and open time
+/// they fall in.
+pub(crate) fn fold_kline_trades(trades: &[NewTrade]) -> Vec<Kline> {
+    let mut candles: BTreeMap<(&str, &str, i64), Kline> = BTreeMap::new();
+    for trade in trades {
+        for interval in KlineInterval::ALL {
+            let open_time = interval.open_time(trade.timestamp);
+            let key = (trade.market_id.as_str(), interval.as_str(), open_time);
+            match candles.get_mut(&key) {
+                Some(candle) => {
+                    candle.high = candle.high.clone().max(trade.price.clone());
+                    candle.low = candle.low.clone().min(trade.price.clone());
+                    candle.close = trade.price.clone();
+                    candle.volume += &trade.base_amount;
+                    candle.quote_volume += &trade.quote_amount;
+                    candle.trade_count += 1;
+                }
+                None => {
+                    candles.insert(
+                        key,
+                        Kline {
+                            market_id: trade.market_id.clone(),
+                            interval: interval.as_str().to_string(),
+                            open_time,
+                            open: trade.price.clone(),
+                            high: trade.price.clone(),
+                            low: trade.price.clone(),
+                            close: trade.price.clone(),
+                            volume: trade.base_amount.clone(),
+                            quote_volume: trade.quote_amount.clone(),
+                            trade_count: 1,
+                        },
+                    );
+                }
+            }
+        }
+    }
+    candles.into_values().collect()
+}
+
+/// Folds `trades`, in the order they settled, into their candles of every interval. Runs on
+/// the caller's connection so the candles commit with the trades.
+pub(crate) fn record_kline_trades(conn: &mut PgConnection, trades: &[NewTrade]) -> Result<()> {
+    // An upsert cannot touch a row twice, so trades sharing a candle are folded here first
+    let candles = fold_kline_trades(trades);
+    if candles.is_empty() {
+        return Ok(());
+    }
+
+    // Trades of a market settle in order, so the latest ones close the candle
+    diesel::insert_into(klines::table)
+        .values(&candles)
+        .on_conflict((klines::market_id, klines::interval, klines::open_time))
+        .do_update()
+        .set((
+            klines::high.eq(greatest(klines::high, excluded(klines::high))),
+            klines::low.eq(least(klines::low, excluded(klines::low))),
+            klines::close.eq(excluded(klines::close)),
+            klines::volume.eq(klines::volume + excluded(klines::volume)),
+            klines::quote_volume.eq(klines::quote_volume + excluded(klines::quote_volume)),
+            klines::trade_count.eq(klines::trade_count + excluded(klines::trade_count)),
+        ))
+        .execute(conn)
+        .context("Failed to update klines")?;
+    Ok(())
+}
@@ -0,0 +1,139 @@
+use super::Repository;
+use crate::filters::LedgerFilter;
+use crate::models::models::*;
+use crate::models::schema::*;
+use crate::provider::{LedgerDatabaseReader, LedgerDatabaseWriter};
+use anyhow::{Context, Result};
+use bigdecimal::BigDecimal;
+use common::db::pagination::{Paginated, Pagination};
+use common::utils::{get_utc_now_millis, get_uuid_string};
+use diesel::pg::{Pg, PgConnection};
+use diesel::prelude::*;
+
+fn filtered_entries(filter: LedgerFilter) -> ledger_entries::BoxedQuery<'static, Pg> {
+    let mut query = ledger_entries::table.into_boxed();
+
+    if let Some(owner_id) = filter.owner_id {
+        query = query.filter(ledger_entries::owner_id.eq(owner_id));
+    }
+    if let Some(asset) = filter.asset {
+        query = query.filter(ledger_entries::asset.eq(asset));
+    }
+    if let Some(kind) = filter.kind {
+        query = query.filter(ledger_entries::kind.eq(kind));
+    }
+    if let Some(reference_id) = filter.reference_id {
+        query = query.filter(ledger_entries::reference_id.eq(reference_id));
+    }
+    if let Some(start_time) = filter.start_time {
+        query = query.filter(ledger_entries::create_time.ge(start_time));
+    }
+    if let Some(end_time) = filter.end_time {
+        query = query.filter(ledger_entries::create_time.le(end_time));
+    }
+
+    query
+}
+
+impl LedgerDatabaseReader for Repository {
+    fn list_ledger_entries(
+        &self,
+        filter: LedgerFilter,
+        pagination: Option<Pagination>,
+    ) -> Result<Paginated<LedgerEntry>> {
+        let conn = &mut self.get_conn()?;
+        let pagination = pagination.unwrap_or_default();
+        let limit = pagination.limit.unwrap_or(10).min(100);
+        let offset = pagination.offset.unwrap_or(0);
+
+        let total_count: i64 = filtered_entries(filter.clone())
+            .select(diesel::dsl::count_star())
+            .first(conn)
+            .context("Failed to count ledger entries")?;
+
+        let entries = filtered_entries(filter)
+            .order(ledger_entries::id.desc())
+            .limit(limit)
+            .offset(offset)
+            .load::<LedgerEntry>(conn)
+            .context("Failed to fetch ledger entries")?;
+
+        let has_more = offset + (entries.len() as i64) < total_count;
+        let next_offset = if has_more {
+            Some(offset + entries.len() as i64)
+        } else {
+            None
+        };
+
+        Ok(Paginated {
+            items: entries,
+            total_count,
+            next_offset,
+            has_more,
+            next_cursor: None,
+        })
+    }
+}
+
+/// The debit and credit entry of every transfer of `transactions`, each transaction under a
+/// new id. Zero amounts move nothing and get no entries.
+pub(crate) fn ledger_entries(
+    transactions: &[(Option<&str>, &[LedgerTransfer])],
+) -> Vec<NewLedgerEntry> {
+    let create_time = get_utc_now_millis();
+    let zero = BigDecimal::from(0);
+
+    transactions
+        .iter()
+        .flat_map(|(reference_id, transfers)| {
+            let transaction_id = get_uuid_string();
+            transfers
+                .iter()
+                .map(move |transfer| (transaction_id.clone(), *reference_id, transfer))
+        })
+        .filter(|(_, _, transfer)| transfer.amount != zero)
+        .flat_map(|(transaction_id, reference_id, transfer)| {
+            let entry =
+                |(owner_id, account): &(String, LedgerAccount), debit, credit| NewLedgerEntry {
+                    transaction_id: transaction_id.clone(),
+                    kind: transfer.kind.as_str().to_string(),
+                    owner_id: owner_id.clone(),
+                    account: account.as_str().to_string(),
+                    asset: transfer.asset.clone(),
+                    debit,
+                    credit,
+                    reference_id: reference_id.map(str::to_string),
+                    create_time,
+                };
+            [
+                entry(&transfer.from, transfer.amount.clone(), zero.clone()),
+                entry(&transfer.to, zero.clone(), transfer.amount.clone()),
+            ]
+        })
+        .collect()
+}
+
+impl LedgerDatabaseWriter for PgConnection {
+    fn record_ledger_transfers(
+        &mut self,
+        reference_id: Option<&str>,
+        transfers: &[LedgerTransfer],
+    ) -> Result<Vec<LedgerEntry>> {
+        self.record_ledger_transactions(&[(reference_id, transfers)])
+    }
+
+    fn record_ledger_transactions(
+        &mut self,
+        transactions: &[(Option<&str>, &[LedgerTransfer])],
+    ) -> Result<Vec<LedgerEntry>> {
+        let entries = ledger_entries(transactions);
+        if entries.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        diesel::insert_into(ledger_entries::table)
+            .values(&entries)
+            .get_results(self)
+            .context("Failed to record ledger entries")
+    }
+}
@@ -5,11 +5,15 @@ use crate::{
 
 use super::Repository;
 use crate::models::schema::*;
-use anyhow::Result;
+use anyhow::{Context, Result};
 use bigdecimal::BigDecimal;
 use common::utils;
+use diesel::dsl::sum;
 use diesel::prelude::*;
 
+/// Window the high, low, volume and price change of market stats cover
+const MARKET_STATS_WINDOW_SECS: i64 = 24 * 60 * 60;
+
 impl MarketStatDatabaseReader for Repository {
     fn get_market_stats(&self, market_id: &str) -> Result<Option<MarketStat>> {
         let conn = &mut self.get_conn()?;
@@ -18,9 +22,131 @@ impl MarketStatDatabaseReader for Repository {
 
         Ok(result)
     }
+
+    fn list_tickers(&self) -> Result<Vec<Ticker>> {
+        let conn = &mut self.get_conn()?;
+
+        let rows = markets::table
+            .left_join(market_stats::table)
+            .left_join(market_quotes::table)
+            .select((
+                markets::id,
+                market_stats::all_columns.nullable(),
+                market_quotes::all_columns.nullable(),
+            ))
+            .order(markets::id.asc())
+            .load::<(String, Option<MarketStat>, Option<MarketQuote>)>(conn)
+            .context("Failed to list tickers")?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(market_id, stats, quote)| Ticker {
+                market_id,
+                stats,
+                quote,
+            })
+            .collect())
+    }
 }
 
 impl MarketStatDatabaseWriter for Repository {
+    fn upsert_market_quote(
+        &self,
+        market_id: &str,
+        best_bid: Option<BigDecimal>,
+        best_ask: Option<BigDecimal>,
+    ) -> Result<MarketQuote> {
+        let conn = &mut self.get_conn()?;
+
+        let quote = MarketQuote {
+            market_id: market_id.to_string(),
+            best_bid,
+            best_ask,
+            update_time: utils::get_utc_now_millis(),
+        };
+        let result = diesel::insert_into(market_quotes::table)
+            .values(&quote)
+            .on_conflict(market_quotes::market_id)
+            .do_update()
+            .set((
+                market_quotes::best_bid.eq(&quote.best_bid),
+                market_quotes::best_ask.eq(&quote.best_ask),
+                market_quotes::update_time.eq(quote.update_time),
+            ))
+            .get_result(conn)
+            .context("Failed to store market quote")?;
+
+        Ok(result)
+    }
+
+    fn refresh_market_stats(&self, market_id: &str, now: i64) -> Result<Option<MarketStat>> {
+        let window_start = now - MARKET_STATS_WINDOW_SECS;
+        let (last_price, high, low, volume, opening_price) = {
+            let conn = &mut self.get_conn()?;
+            let market_trades = || trades::table.filter(trades::market_id.eq(market_id));
+
+            let Some(last_price) = market_trades()
+                .filter(trades::timestamp.le(now))
+                .order(trades::timestamp.desc())
+                .select(trades::price)
+                .first::<BigDecimal>(conn)
+                .optional()?
+            else {
+                return Ok(None);
+            };
+
+            let in_window = || {
+                market_trades()
+                    .filter(trades::timestamp.gt(window_start))
+                    .filter(trades::timestamp.le(now))
+            };
+            // Diesel has no MAX or MIN over numeric columns, so the extremes are ordered for
+            let high = in_window()
+                .order(trades::price.desc())
+                .select(trades::price)
+                .first::<BigDecimal>(conn)
+                .optional()?;
+            let low = in_window()
+                .order(trades::price.asc())
+                .select(trades::price)
+                .first::<BigDecimal>(conn)
+                .optional()?;
+            let volume = in_window()
+                .select(sum(trades::base_amount))
+                .first::<Option<BigDecimal>>(conn)
+                .context("Failed to sum the traded volume")?;
+
+            // The price the window opened at: the last trade before it, or else its first one
+            let opening_price = match market_trades()
+                .filter(trades::timestamp.le(window_start))
+                .order(trades::timestamp.desc())
+                .select(trades::price)
+                .first::<BigDecimal>(conn)
+                .optional()?
+            {
+                Some(price) => Some(price),
+                None => in_window()
+                    .order(trades::timestamp.asc())
+                    .select(trades::price)
+                    .first::<BigDecimal>(conn)
+                    .optional()?,
+            };
+            (last_price, high, low, volume, opening_price)
+        };
+
+        // Without trades in the window the market sat at its last price the whole time
+        let opening_price = opening_price.unwrap_or_else(|| last_price.clone());
+        let stats = self.upsert_market_stats(
+            market_id,
+            high.unwrap_or_else(|| last_price.clone()),
+            low.unwrap_or_else(|| last_price.clone()),
+            volume.unwrap_or_else(|| BigDecimal::from(0)),
+            &last_price - &opening_price,
+            last_price,
+        )?;
+        Ok(Some(stats))
+    }
+
     fn upsert_market_stats(
         &self,
         market_id: &str,
@@ -34,13 +160,25 @@ impl MarketStatDatabaseWriter for Repository {
 
         let current_time = utils::get_utc_now_millis();
 
+        // Store stats at the market's precision so they compare across markets
+        let market = markets::table
+            .find(market_id)
+            .first::<Market>(conn)
+            .context("Failed to fetch market")?;
+        let price_precision = market.price_precision;
+        let high_24h = utils::round_to_precision(&high_24h, price_precision);
+        let low_24h = utils::round_to_precision(&low_24h, price_precision);
+        let price_change_24h = utils::round_to_precision(&price_change_24h, price_precision);
+        let last_price = utils::round_to_precision(&last_price, price_precision);
+        let volume_24h = utils::round_to_precision(&volume_24h, market.amount_precision);
+
         // Check if stats exist
         let stats_option = market_stats::table
             .find(market_id)
             .first::<MarketStat>(conn)
             .optional()?;
 
-        if let Some(_) = stats_option {
+        if stats_option.is_some() {
             // Update existing stats
             let result = diesel::update(market_stats::table.find(market_id))
                 .set((
@@ -2,7 +2,9 @@ use super::Repository;
 use crate::models::models::*;
 use crate::models::schema::*;
 use crate::provider::{MarketDatabaseReader, MarketDatabaseWriter};
-use anyhow::Result;
+use anyhow::{Context, Result};
+use common::error::BitradeError;
+use common::utils::get_utc_now_millis;
 use diesel::prelude::*;
 
 impl MarketDatabaseReader for Repository {
@@ -24,12 +26,49 @@ impl MarketDatabaseReader for Repository {
 }
 
 impl MarketDatabaseWriter for Repository {
+    /// Creates the market, or returns the existing one if a market with the same id was
+    /// already created (e.g. by a concurrent call).
     fn create_market(&self, market_data: NewMarket) -> Result<Market> {
         let conn = &mut self.get_conn()?;
-        let result = diesel::insert_into(markets::table)
-            .values(market_data)
-            .get_result(conn)?;
+        let inserted = diesel::insert_into(markets::table)
+            .values(&market_data)
+            .on_conflict(markets::id)
+            .do_nothing()
+            .get_result::<Market>(conn)
+            .optional()?;
 
-        Ok(result)
+        match inserted {
+            Some(market) => Ok(market),
+            None => markets::table
+                .find(&market_data.id)
+                .first::<Market>(conn)
+                .context("Failed to fetch existing market"),
+        }
+    }
+
+    fn update_market_status(&self, market_id: &str, status: MarketStatus) -> Result<Market> {
+        let conn = &mut self.get_conn()?;
+
+        diesel::update(markets::table.find(market_id))
+            .set((
+                markets::status.eq(status.as_str()),
+                markets::update_time.eq(get_utc_now_millis()),
+            ))
+            .get_result(conn)
+            .optional()
+            .context("Failed to update market status")?
+            .ok_or_else(|| BitradeError::MarketNotFound(market_id.to_string()).into())
+    }
+
+    fn update_market(&self, market_id: &str, changes: MarketUpdate) -> Result<Market> {
+        let conn = &mut self.get_conn()?;
+
+        // Setting update_time as well keeps the changeset valid when nothing else changes
+        diesel::update(markets::table.find(market_id))
+            .set((&changes, markets::update_time.eq(get_utc_now_millis())))
+            .get_result(conn)
+            .optional()
+            .context("Failed to update market")?
+            .ok_or_else(|| BitradeError::MarketNotFound(market_id.to_string()).into())
     }
 }
@@ -1,22 +1,165 @@
+mod audit;
+mod engine_events;
+mod fee_tiers;
 mod fee_treasury;
+mod klines;
+mod ledger;
 mod market_stats;
 mod markets;
+mod oco_groups;
+mod order_book_snapshots;
 mod orders;
+mod outbox;
+mod price_bands;
+mod reconciliation;
+mod risk_limits;
 mod trades;
+mod transfers;
+mod user_fee_overrides;
+mod user_restrictions;
 mod wallets;
 
+pub(crate) use klines::record_kline_trades;
+pub(crate) use outbox::{record_outbox_events, trade_events};
+// What the SQLite backend shares with this one, so both store the same rows
+#[cfg(feature = "sqlite")]
+pub(crate) use klines::fold_kline_trades;
+#[cfg(feature = "sqlite")]
+pub(crate) use ledger::ledger_entries;
+#[cfg(feature = "sqlite")]
+pub(crate) use trades::{apply_fills, check_fills};
+
 use crate::DbConnection;
 use crate::DbPool;
 use anyhow::Result;
+use bigdecimal::BigDecimal;
+
+/// What trade settlement does when a counterparty has no wallet for the asset it receives
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MissingWalletPolicy {
+    /// Create the wallet with a zero balance and credit it
+    #[default]
+    CreateEmpty,
+    /// Abort the trade with [`SettlementError::WalletMissing`]
+    Fail,
+}
+
+impl MissingWalletPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MissingWalletPolicy::CreateEmpty => "CREATE_EMPTY",
+            MissingWalletPolicy::Fail => "FAIL",
+        }
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_uppercase().as_str() {
+            "CREATE_EMPTY" => Ok(MissingWalletPolicy::CreateEmpty),
+            "FAIL" => Ok(MissingWalletPolicy::Fail),
+            _ => Err(format!("Unknown missing wallet policy: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SettlementError {
+    #[error("Wallet of user {user_id} for asset {asset} is missing")]
+    WalletMissing { user_id: String, asset: String },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TransferError {
+    #[error("Transfer {0} not found")]
+    NotFound(String),
+    #[error("Transfer {transfer_id} is {status}, expected {expected}")]
+    InvalidStatus {
+        transfer_id: String,
+        status: String,
+        expected: &'static str,
+    },
+    #[error("Insufficient balance: {required} {asset} required, {available} available")]
+    InsufficientBalance {
+        asset: String,
+        required: BigDecimal,
+        available: BigDecimal,
+    },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TreasuryError {
+    #[error("Fee treasury of market {market_id} for asset {asset} not found")]
+    NotFound { market_id: String, asset: String },
+    #[error("Insufficient fees: {collected} {asset} collected")]
+    InsufficientFees {
+        asset: String,
+        collected: BigDecimal,
+    },
+}
+
+/// A sort or cursor a listing cannot page by
+#[derive(Debug, thiserror::Error)]
+pub enum PaginationError {
+    #[error("{listing} cannot be sorted by {field}")]
+    UnsortableField {
+        listing: &'static str,
+        field: String,
+    },
+    #[error("Invalid sort direction {0}")]
+    InvalidDirection(String),
+    #[error("A cursor pages {listing} sorted by {field} only")]
+    CursorNeedsTimeSort {
+        listing: &'static str,
+        field: &'static str,
+    },
+}
 
 #[derive(Debug, Clone)]
 pub struct Repository {
     pool: DbPool,
+    missing_wallet_policy: MissingWalletPolicy,
+    /// When set, canceling an already canceled order returns it instead of failing
+    idempotent_cancel: bool,
+    /// When set, settlement records the wallet balances it changes before and after a trade
+    balance_snapshots: bool,
+    /// When set, settlement writes the events of every trade to the outbox for the relay
+    outbox: bool,
 }
 impl Repository {
     pub fn new(pool: DbPool) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            missing_wallet_policy: MissingWalletPolicy::default(),
+            idempotent_cancel: false,
+            balance_snapshots: false,
+            outbox: false,
+        }
+    }
+
+    pub fn with_missing_wallet_policy(mut self, policy: MissingWalletPolicy) -> Self {
+        self.missing_wallet_policy = policy;
+        self
+    }
+
+    /// Lets clients retry a cancel safely. Filled and rejected orders still refuse to cancel.
+    pub fn with_idempotent_cancel(mut self, enabled: bool) -> Self {
+        self.idempotent_cancel = enabled;
+        self
+    }
+
+    /// Records buyer and seller balances around every trade, for settlement verification.
+    pub fn with_balance_snapshots(mut self, enabled: bool) -> Self {
+        self.balance_snapshots = enabled;
+        self
     }
+
+    /// Writes trade, order and wallet events to the `events` outbox with every trade. Only
+    /// enable it with a relay running, or the outbox grows without bound.
+    pub fn with_outbox(mut self, enabled: bool) -> Self {
+        self.outbox = enabled;
+        self
+    }
+
     pub fn get_conn(&self) -> Result<DbConnection> {
         Ok(self.pool.get()?)
     }
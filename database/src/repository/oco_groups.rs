@@ -0,0 +1,126 @@
+use super::Repository;
+use super::orders::cancel_open_order;
+use crate::models::models::*;
+
+use crate::models::schema::*;
+use crate::provider::{OcoGroupDatabaseReader, OcoGroupDatabaseWriter};
+
+use anyhow::Context;
+use anyhow::Result;
+
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+
+fn find_oco_group(conn: &mut PgConnection, order_id: &str) -> Result<Option<OcoGroup>> {
+    let group = oco_groups::table
+        .filter(
+            oco_groups::first_order_id
+                .eq(order_id)
+                .or(oco_groups::second_order_id.eq(order_id)),
+        )
+        .first::<OcoGroup>(conn)
+        .optional()
+        .context("Failed to fetch OCO group")?;
+    Ok(group)
+}
+
+/// Cancels the other leg of the OCO group of `order_id` if that leg is still
+/// open. Runs on the caller's connection so it commits with the caller's
+/// transaction.
+pub(super) fn cancel_oco_sibling(conn: &mut PgConnection, order_id: &str) -> Result<Option<Order>> {
+    let Some(group) = find_oco_group(conn, order_id)? else {
+        return Ok(None);
+    };
+    cancel_open_sibling(conn, &group, order_id)
+}
+
+/// Like [`cancel_oco_sibling`] for each of `order_ids`, reading their groups at once. Both legs
+/// of a group being among `order_ids` is refused, as one of them would have to be canceled.
+pub(super) fn cancel_oco_siblings(conn: &mut PgConnection, order_ids: &[&str]) -> Result<()> {
+    let groups = oco_groups::table
+        .filter(
+            oco_groups::first_order_id
+                .eq_any(order_ids)
+                .or(oco_groups::second_order_id.eq_any(order_ids)),
+        )
+        .load::<OcoGroup>(conn)
+        .context("Failed to fetch OCO groups")?;
+
+    for group in groups {
+        let order_id = match (
+            order_ids.contains(&group.first_order_id.as_str()),
+            order_ids.contains(&group.second_order_id.as_str()),
+        ) {
+            (true, true) => {
+                return Err(anyhow::anyhow!(
+                    "Both legs of OCO group {} cannot fill together",
+                    group.id
+                ));
+            }
+            (true, false) => group.first_order_id.clone(),
+            _ => group.second_order_id.clone(),
+        };
+        cancel_open_sibling(conn, &group, &order_id)?;
+    }
+    Ok(())
+}
+
+fn cancel_open_sibling(
+    conn: &mut PgConnection,
+    group: &OcoGroup,
+    order_id: &str,
+) -> Result<Option<Order>> {
+    let sibling = orders::table
+        .find(group.sibling_of(order_id))
+        .for_update()
+        .first::<Order>(conn)
+        .context("OCO sibling order not found")?;
+
+    let status = OrderStatus::from_str(&sibling.status)
+        .map_err(|e| anyhow::anyhow!("Failed to parse order status: {}", e))?;
+    if !matches!(status, OrderStatus::Open | OrderStatus::PartiallyFilled) {
+        return Ok(None);
+    }
+
+    cancel_open_order(conn, &sibling, CancelReason::OneCancelsOther).map(Some)
+}
+
+impl OcoGroupDatabaseReader for Repository {
+    fn get_oco_group_by_order(&self, order_id: &str) -> Result<Option<OcoGroup>> {
+        let conn = &mut self.get_conn()?;
+        find_oco_group(conn, order_id)
+    }
+
+    fn get_active_oco_groups(&self, market_id: &str) -> Result<Vec<OcoGroup>> {
+        let conn = &mut self.get_conn()?;
+        let active = [
+            OrderStatus::Open.as_str(),
+            OrderStatus::PartiallyFilled.as_str(),
+        ];
+
+        let (first, second) = diesel::alias!(orders as first, orders as second);
+        let result = oco_groups::table
+            .inner_join(first.on(first.field(orders::id).eq(oco_groups::first_order_id)))
+            .inner_join(second.on(second.field(orders::id).eq(oco_groups::second_order_id)))
+            .filter(oco_groups::market_id.eq(market_id))
+            .filter(first.field(orders::status).eq_any(active))
+            .filter(second.field(orders::status).eq_any(active))
+            .select(OcoGroup::as_select())
+            .load(conn)?;
+
+        Ok(result)
+    }
+}
+
+impl OcoGroupDatabaseWriter for Repository {
+    fn create_oco_group(&self, group: NewOcoGroup) -> Result<OcoGroup> {
+        let conn = &mut self.get_conn()?;
+
+        let result = diesel::insert_into(oco_groups::table)
+            .values(&group)
+            .get_result(conn)
+            .context("Failed to create OCO group")?;
+
+        Ok(result)
+    }
+}
@@ -0,0 +1,38 @@
+use super::Repository;
+use crate::models::models::*;
+use crate::models::schema::*;
+use crate::provider::{OrderBookSnapshotDatabaseReader, OrderBookSnapshotDatabaseWriter};
+use anyhow::{Context, Result};
+use diesel::prelude::*;
+
+impl OrderBookSnapshotDatabaseReader for Repository {
+    fn get_order_book_snapshot(&self, market_id: &str) -> Result<Option<OrderBookSnapshot>> {
+        let conn = &mut self.get_conn()?;
+
+        order_book_snapshots::table
+            .find(market_id)
+            .first(conn)
+            .optional()
+            .context("Failed to fetch order book snapshot")
+    }
+}
+
+impl OrderBookSnapshotDatabaseWriter for Repository {
+    fn store_order_book_snapshot(&self, snapshot: OrderBookSnapshot) -> Result<()> {
+        let conn = &mut self.get_conn()?;
+
+        diesel::insert_into(order_book_snapshots::table)
+            .values(&snapshot)
+            .on_conflict(order_book_snapshots::market_id)
+            .do_update()
+            .set((
+                order_book_snapshots::applied_sequence.eq(snapshot.applied_sequence),
+                order_book_snapshots::payload.eq(&snapshot.payload),
+                order_book_snapshots::create_time.eq(snapshot.create_time),
+            ))
+            .execute(conn)
+            .context("Failed to store order book snapshot")?;
+
+        Ok(())
+    }
+}
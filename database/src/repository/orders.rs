@@ -1,13 +1,121 @@
-use super::Repository;
+use super::oco_groups::cancel_oco_sibling;
+use super::wallets::{lock_funds, record_unlock};
+use super::{PaginationError, Repository};
 use crate::filters::OrderFilter;
 use crate::models::models::*;
 use crate::models::schema::*;
 use crate::provider::*;
 use anyhow::Context;
 use anyhow::Result;
+use bigdecimal::BigDecimal;
 use common::db::pagination::*;
+use common::error::BitradeError;
 use common::utils;
+use diesel::dsl::{count_star, sql, sum};
+use diesel::pg::PgConnection;
 use diesel::prelude::*;
+use diesel::sql_types::{Nullable, Numeric};
+
+/// Locks the funds of a new order and inserts it, within the transaction of the caller.
+pub(super) fn insert_order(conn: &mut PgConnection, order_data: &NewOrder) -> Result<Order> {
+    // Get market details first
+    let market = markets::table
+        .find(&order_data.market_id)
+        .first::<Market>(conn)
+        .context("Failed to fetch market")?;
+
+    // Calculate required amount based on order side
+    let order_side = OrderSide::from_str(&order_data.side)
+        .map_err(|e| anyhow::anyhow!("Invalid order side: {}", e))?;
+
+    match order_side {
+        OrderSide::Buy => {
+            // For buy orders, we need to lock quote_asset (price * amount)
+            let quote_amount = order_data.quote_amount.clone();
+
+            // Decrease available and increase frozen (freezing the funds)
+            lock_funds(
+                conn,
+                &order_data.user_id,
+                &market.quote_asset,
+                &quote_amount,
+                Some(&order_data.id),
+            )
+            .context("Failed to update buyer balance")?;
+        }
+        OrderSide::Sell => {
+            // For sell orders, we need to lock base_asset
+            // Decrease available and increase frozen (freezing the funds)
+            lock_funds(
+                conn,
+                &order_data.user_id,
+                &market.base_asset,
+                &order_data.base_amount,
+                Some(&order_data.id),
+            )
+            .context("Failed to update seller balance")?;
+        }
+    }
+
+    // Create the order
+    diesel::insert_into(orders::table)
+        .values(order_data)
+        .get_result(conn)
+        .context("Failed to insert order")
+}
+
+/// Cancels an order that is still on the book and unlocks what it has not spent.
+pub(super) fn cancel_open_order(
+    conn: &mut PgConnection,
+    order: &Order,
+    reason: CancelReason,
+) -> Result<Order> {
+    // Parse the order side
+    let order_side = OrderSide::from_str(&order.side)
+        .map_err(|e| anyhow::anyhow!("Failed to parse order side: {}", e))?;
+
+    // Fetch the market to determine assets
+    let market = markets::table
+        .filter(markets::id.eq(&order.market_id))
+        .first::<Market>(conn)
+        .context("Market not found")?;
+
+    // Calculate remaining amount to unfreeze
+    let (asset, unlock_amount) = match order_side {
+        OrderSide::Buy => (market.quote_asset.clone(), order.remained_quote.clone()),
+        OrderSide::Sell => (market.base_asset.clone(), order.remained_base.clone()),
+    };
+
+    // Update order status to CANCELED
+    let updated_order = diesel::update(orders::table.find(&order.id))
+        .set((
+            orders::status.eq(OrderStatus::Canceled.as_str()),
+            orders::cancel_reason.eq(reason.as_str()),
+            orders::update_time.eq(utils::get_utc_now_millis()),
+        ))
+        .get_result::<Order>(conn)
+        .context("Failed to update order status")?;
+
+    // Unlock the balance, a refund of unspent funds that carries no fee
+    diesel::update(wallets::table)
+        .filter(wallets::user_id.eq(&order.user_id))
+        .filter(wallets::asset.eq(&asset))
+        .set((
+            wallets::available.eq(wallets::available + unlock_amount.clone()),
+            wallets::locked.eq(wallets::locked - unlock_amount.clone()),
+        ))
+        .execute(conn)
+        .context("Failed to unlock balance")?;
+    record_unlock(
+        conn,
+        &order.user_id,
+        &asset,
+        &unlock_amount,
+        Some(&order.id),
+    )?;
+
+    Ok(updated_order)
+}
 
 impl Repository {
     fn get_order_total_count(&self, filter: OrderFilter) -> Result<i64> {
@@ -31,6 +139,9 @@ impl Repository {
         if let Some(order_type) = filter.order_type {
             count_query = count_query.filter(orders::order_type.eq(order_type));
         }
+        if let Some(client_order_id) = filter.client_order_id {
+            count_query = count_query.filter(orders::client_order_id.eq(client_order_id));
+        }
 
         // Get total count
         let total_count: i64 = count_query.select(diesel::dsl::count_star()).first(conn)?;
@@ -44,18 +155,58 @@ impl OrderDatabaseReader for Repository {
         let order = orders::table
             .find(order_id)
             .first::<Order>(conn)
-            .context("Order not found")?;
+            .optional()?
+            .ok_or_else(|| BitradeError::OrderNotFound(order_id.to_string()))?;
         Ok(Some(order))
     }
-    fn get_active_orders(&self, _market_id: &str) -> Result<Vec<Order>> {
-        use crate::models::schema::orders::dsl::*;
+
+    fn get_order_by_client_id(
+        &self,
+        user_id: &str,
+        client_order_id: &str,
+    ) -> Result<Option<Order>> {
+        let conn = &mut self.get_conn()?;
+        orders::table
+            .filter(orders::user_id.eq(user_id))
+            .filter(orders::client_order_id.eq(client_order_id))
+            // Rejected orders don't hold the id, any other order is the one that does
+            .order((
+                orders::status.eq(OrderStatus::Rejected.as_str()),
+                orders::create_time.desc(),
+            ))
+            .first::<Order>(conn)
+            .optional()
+            .context("Failed to get order by client order id")
+    }
+
+    fn get_active_orders(&self, market_id: &str) -> Result<Vec<Order>> {
         let conn = &mut self.get_conn()?;
-        orders
-            .filter(status.eq(OrderStatus::Open.as_str()))
+        orders::table
+            .filter(orders::market_id.eq(market_id))
+            .filter(orders::status.eq_any(&[
+                OrderStatus::Open.as_str(),
+                OrderStatus::PartiallyFilled.as_str(),
+            ]))
+            .order((orders::priority.asc(), orders::create_time.asc()))
             .load::<Order>(conn)
             .map_err(|e| anyhow::anyhow!("Failed to get active orders: {}", e))
     }
 
+    fn get_expired_orders(&self, market_id: &str, now: i64) -> Result<Vec<Order>> {
+        let conn = &mut self.get_conn()?;
+        orders::table
+            .filter(orders::market_id.eq(market_id))
+            .filter(orders::status.eq_any(&[
+                OrderStatus::Open.as_str(),
+                OrderStatus::PartiallyFilled.as_str(),
+            ]))
+            .filter(orders::time_in_force.eq(TimeInForce::GTD.as_str()))
+            .filter(orders::expires_at.le(now))
+            .order(orders::expires_at.asc())
+            .load::<Order>(conn)
+            .context("Failed to get expired orders")
+    }
+
     fn list_orders(
         &self,
         filter: OrderFilter,
@@ -87,13 +238,66 @@ impl OrderDatabaseReader for Repository {
         if let Some(order_type) = filter.order_type {
             query = query.filter(orders::order_type.eq(order_type));
         }
+        if let Some(client_order_id) = filter.client_order_id {
+            query = query.filter(orders::client_order_id.eq(client_order_id));
+        }
 
         let limit = pagination.limit.unwrap_or(10);
         let offset = pagination.offset.unwrap_or(0);
         let total_count = self.get_order_total_count(cloned_filter)?;
+        let sort_field = pagination.sort_field("create_time");
+        let descending = pagination.is_descending().ok_or_else(|| {
+            PaginationError::InvalidDirection(
+                pagination.order_direction.clone().unwrap_or_default(),
+            )
+        })?;
+        // The id breaks ties, so that a page ends between two orders
+        query = match (sort_field, descending) {
+            ("create_time", true) => query.order((orders::create_time.desc(), orders::id.desc())),
+            ("create_time", false) => query.order((orders::create_time.asc(), orders::id.asc())),
+            ("price", true) => query.order((orders::price.desc(), orders::id.desc())),
+            ("price", false) => query.order((orders::price.asc(), orders::id.asc())),
+            ("filled_base", true) => query.order((orders::filled_base.desc(), orders::id.desc())),
+            ("filled_base", false) => query.order((orders::filled_base.asc(), orders::id.asc())),
+            (field, _) => {
+                return Err(PaginationError::UnsortableField {
+                    listing: "orders",
+                    field: field.to_string(),
+                }
+                .into());
+            }
+        };
+        let by_time = sort_field == "create_time";
+        match &pagination.cursor {
+            Some(_) if !by_time => {
+                return Err(PaginationError::CursorNeedsTimeSort {
+                    listing: "orders",
+                    field: "create_time",
+                }
+                .into());
+            }
+            Some(cursor) if descending => {
+                query = query.filter(
+                    orders::create_time
+                        .lt(cursor.timestamp)
+                        .or(orders::create_time
+                            .eq(cursor.timestamp)
+                            .and(orders::id.lt(cursor.id.clone()))),
+                )
+            }
+            Some(cursor) => {
+                query = query.filter(
+                    orders::create_time
+                        .gt(cursor.timestamp)
+                        .or(orders::create_time
+                            .eq(cursor.timestamp)
+                            .and(orders::id.gt(cursor.id.clone()))),
+                )
+            }
+            None => query = query.offset(offset),
+        }
         let mut orders = query
             .limit(limit + 1)
-            .offset(offset)
             .load::<Order>(conn)
             .context("Failed to retrieve orders")?;
 
@@ -103,75 +307,200 @@ impl OrderDatabaseReader for Repository {
             orders.pop(); // Remove the extra item we fetched
         }
 
-        let next_offset = if has_more { Some(offset + limit) } else { None };
+        let next_offset = if has_more && pagination.cursor.is_none() {
+            Some(offset + limit)
+        } else {
+            None
+        };
+        let next_cursor = orders
+            .last()
+            .filter(|_| has_more && by_time)
+            .map(|order| Cursor::new(order.create_time, order.id.clone()));
 
         Ok(Paginated {
             items: orders,
             total_count,
             next_offset,
             has_more,
+            next_cursor,
         })
     }
-}
 
-impl OrderDatabaseWriter for Repository {
-    fn create_order(&self, order_data: NewOrder) -> Result<Order> {
+    fn get_open_order_stats(&self) -> Result<Vec<OpenOrderStat>> {
         let conn = &mut self.get_conn()?;
+        let stats = orders::table
+            .filter(orders::status.eq_any(&[
+                OrderStatus::Open.as_str(),
+                OrderStatus::PartiallyFilled.as_str(),
+            ]))
+            .group_by(orders::side)
+            .select((orders::side, count_star(), sum(orders::remained_base)))
+            .load::<(String, i64, Option<BigDecimal>)>(conn)
+            .context("Failed to count open orders")?;
+
+        Ok(stats
+            .into_iter()
+            .map(|(side, order_count, remained_base)| OpenOrderStat {
+                side,
+                order_count,
+                remained_base: remained_base.unwrap_or_default(),
+            })
+            .collect())
+    }
 
-        conn.transaction::<Order, anyhow::Error, _>(|conn| {
-            // Get market details first
-            let market = markets::table
-                .find(&order_data.market_id)
-                .first::<Market>(conn)
-                .context("Failed to fetch market")?;
+    fn get_user_order_counts(
+        &self,
+        user_id: &str,
+        market_id: Option<&str>,
+    ) -> Result<Vec<OrderStatusCount>> {
+        let conn = &mut self.get_conn()?;
+        let mut query = orders::table
+            .filter(orders::user_id.eq(user_id))
+            .group_by(orders::status)
+            .select((orders::status, count_star()))
+            .order_by(orders::status)
+            .into_boxed();
+        if let Some(market_id) = market_id {
+            query = query.filter(orders::market_id.eq(market_id));
+        }
 
-            // Calculate required amount based on order side
-            let order_side = OrderSide::from_str(&order_data.side)
-                .map_err(|e| anyhow::anyhow!("Invalid order side: {}", e))?;
+        Ok(query
+            .load::<(String, i64)>(conn)
+            .context("Failed to count user orders")?
+            .into_iter()
+            .map(|(status, order_count)| OrderStatusCount {
+                status,
+                order_count,
+            })
+            .collect())
+    }
+
+    fn get_book_levels(&self, market_id: &str) -> Result<Vec<BookLevel>> {
+        let conn = &mut self.get_conn()?;
+        let levels = orders::table
+            .filter(orders::market_id.eq(market_id))
+            .filter(orders::status.eq_any(&[
+                OrderStatus::Open.as_str(),
+                OrderStatus::PartiallyFilled.as_str(),
+            ]))
+            .filter(orders::order_type.eq(OrderType::Limit.as_str()))
+            .group_by((orders::side, orders::price))
+            .select((
+                orders::side,
+                orders::price,
+                sql::<Nullable<Numeric>>(
+                    "SUM(LEAST(remained_base, COALESCE(display_amount, remained_base)))",
+                ),
+            ))
+            .load::<(String, BigDecimal, Option<BigDecimal>)>(conn)
+            .context("Failed to sum book levels")?;
+
+        Ok(levels
+            .into_iter()
+            .map(|(side, price, amount)| BookLevel {
+                side,
+                price,
+                amount: amount.unwrap_or_default(),
+            })
+            .collect())
+    }
 
-            match order_side {
-                OrderSide::Buy => {
-                    // For buy orders, we need to lock quote_asset (price * amount)
-                    let quote_amount = order_data.quote_amount.clone();
+    fn get_user_active_orders_count(&self, user_id: &str, market_id: &str) -> Result<i64> {
+        let conn = &mut self.get_conn()?;
+        orders::table
+            .filter(orders::user_id.eq(user_id))
+            .filter(orders::market_id.eq(market_id))
+            .filter(orders::status.eq_any(&[
+                OrderStatus::Open.as_str(),
+                OrderStatus::PartiallyFilled.as_str(),
+            ]))
+            .select(count_star())
+            .first(conn)
+            .context("Failed to count active user orders")
+    }
 
-                    // Decrease available and increase frozen (freezing the funds)
-                    self.lock_balance(&order_data.user_id, &market.quote_asset, quote_amount)
-                        .context("Failed to update buyer balance")?;
-                }
-                OrderSide::Sell => {
-                    // For sell orders, we need to lock base_asset
-                    // Decrease available and increase frozen (freezing the funds)
-                    self.lock_balance(
-                        &order_data.user_id,
-                        &market.base_asset,
-                        order_data.base_amount.clone(),
-                    )
-                    .context("Failed to update seller balance")?;
+    fn get_user_locked_notional(&self, user_id: &str, market_id: &str) -> Result<BigDecimal> {
+        let conn = &mut self.get_conn()?;
+        let open_orders = orders::table
+            .filter(orders::user_id.eq(user_id))
+            .filter(orders::market_id.eq(market_id))
+            .filter(orders::status.eq_any(&[
+                OrderStatus::Open.as_str(),
+                OrderStatus::PartiallyFilled.as_str(),
+            ]))
+            .select((
+                orders::side,
+                orders::price,
+                orders::remained_base,
+                orders::remained_quote,
+            ))
+            .load::<(String, BigDecimal, BigDecimal, BigDecimal)>(conn)
+            .context("Failed to fetch active user orders")?;
+
+        Ok(open_orders
+            .into_iter()
+            .map(|(side, price, remained_base, remained_quote)| {
+                if side == OrderSide::Buy.as_str() {
+                    remained_quote
+                } else {
+                    remained_base * price
                 }
-            }
+            })
+            .sum())
+    }
 
-            // Create the order
-            let result = diesel::insert_into(orders::table)
-                .values(&order_data)
-                .get_result(conn)
-                .unwrap();
+    fn get_user_active_orders(&self, user_id: &str) -> Result<Vec<Order>> {
+        let conn = &mut self.get_conn()?;
+        orders::table
+            .filter(orders::user_id.eq(user_id))
+            .filter(orders::status.eq_any(&[
+                OrderStatus::Open.as_str(),
+                OrderStatus::PartiallyFilled.as_str(),
+            ]))
+            .order(orders::create_time.asc())
+            .load(conn)
+            .context("Failed to fetch active user orders")
+    }
+}
 
-            Ok(result)
-        })
+impl OrderDatabaseWriter for Repository {
+    fn create_order(&self, order_data: NewOrder) -> Result<Order> {
+        let conn = &mut self.get_conn()?;
+        conn.transaction(|conn| insert_order(conn, &order_data))
+    }
+
+    fn reject_order(&self, order_data: NewOrder, reason: RejectReason) -> Result<Order> {
+        let conn = &mut self.get_conn()?;
+        let rejected = NewOrder {
+            remained_base: BigDecimal::from(0),
+            remained_quote: BigDecimal::from(0),
+            status: OrderStatus::Rejected.as_str().to_string(),
+            ..order_data
+        };
+        diesel::insert_into(orders::table)
+            .values((&rejected, orders::reject_reason.eq(reason.as_str())))
+            .get_result(conn)
+            .context("Failed to insert rejected order")
     }
 
-    fn cancel_order(&self, order_id: &str) -> Result<Order> {
+    fn cancel_order(&self, order_id: &str, reason: CancelReason) -> Result<Order> {
+        let idempotent_cancel = self.idempotent_cancel;
         let conn = &mut self.get_conn()?;
         conn.transaction::<Order, anyhow::Error, _>(|conn| {
             // Fetch the order first
             let order = orders::table
                 .filter(orders::id.eq(order_id))
                 .first::<Order>(conn)
-                .context("Order not found")?;
+                .optional()?
+                .ok_or_else(|| BitradeError::OrderNotFound(order_id.to_string()))?;
 
             // Check if order is already in a final state
             let current_status = OrderStatus::from_str(&order.status)
                 .map_err(|e| anyhow::anyhow!("Failed to parse order status: {}", e))?;
+            if idempotent_cancel && current_status == OrderStatus::Canceled {
+                // A retried cancel gets the order back as the first cancel left it
+                return Ok(order);
+            }
             if matches!(
                 current_status,
                 OrderStatus::Filled | OrderStatus::Canceled | OrderStatus::Rejected
@@ -179,48 +508,17 @@ impl OrderDatabaseWriter for Repository {
                 return Err(anyhow::anyhow!("Order already in final state"));
             }
 
-            // Parse the order side
-            let order_side = OrderSide::from_str(&order.side)
-                .map_err(|e| anyhow::anyhow!("Failed to parse order side: {}", e))?;
-
-            // Fetch the market to determine assets
-            let market = markets::table
-                .filter(markets::id.eq(&order.market_id))
-                .first::<Market>(conn)
-                .context("Market not found")?;
-
-            // Calculate remaining amount to unfreeze
-            let (asset, unlock_amount) = match order_side {
-                OrderSide::Buy => (market.quote_asset.clone(), order.remained_quote.clone()),
-                OrderSide::Sell => (market.base_asset.clone(), order.remained_base.clone()),
-            };
-
-            // Update order status to CANCELED
-            let updated_order = diesel::update(orders::table.find(order_id))
-                .set((
-                    orders::status.eq(OrderStatus::Canceled.as_str()),
-                    orders::update_time.eq(utils::get_utc_now_millis()),
-                ))
-                .get_result::<Order>(conn)
-                .context("Failed to update order status")?;
+            let updated_order = cancel_open_order(conn, &order, reason)?;
 
-            // Unlock the balance
-            diesel::update(wallets::table)
-                .filter(wallets::user_id.eq(&order.user_id))
-                .filter(wallets::asset.eq(&asset))
-                .set((
-                    wallets::available.eq(wallets::available + unlock_amount.clone()),
-                    wallets::locked.eq(wallets::locked - unlock_amount),
-                ))
-                .execute(conn)
-                .context("Failed to unlock balance")?;
+            // Filling or canceling either leg of an OCO group cancels the other
+            cancel_oco_sibling(conn, order_id)?;
 
             Ok(updated_order)
         })
     }
 
     /// Cancel all active orders for a specific market
-    fn cancel_all_orders(&self, market_id: &str) -> Result<Vec<Order>> {
+    fn cancel_all_orders(&self, market_id: &str, reason: CancelReason) -> Result<Vec<Order>> {
         let conn = &mut self.get_conn()?;
         conn.transaction::<Vec<Order>, anyhow::Error, _>(|conn| {
             // Fetch all active orders for the market
@@ -239,7 +537,8 @@ impl OrderDatabaseWriter for Repository {
             let market = markets::table
                 .filter(markets::id.eq(market_id))
                 .first::<Market>(conn)
-                .context("Market not found")?;
+                .optional()?
+                .ok_or_else(|| BitradeError::MarketNotFound(market_id.to_string()))?;
 
             for order in active_orders {
                 // Parse the order side
@@ -256,6 +555,7 @@ impl OrderDatabaseWriter for Repository {
                 let canceled_order = diesel::update(orders::table.find(&order.id))
                     .set((
                         orders::status.eq(OrderStatus::Canceled.as_str()),
+                        orders::cancel_reason.eq(reason.as_str()),
                         orders::update_time.eq(utils::get_utc_now_millis()),
                     ))
                     .get_result::<Order>(conn)
@@ -267,10 +567,17 @@ impl OrderDatabaseWriter for Repository {
                     .filter(wallets::asset.eq(&asset))
                     .set((
                         wallets::available.eq(wallets::available + unlock_amount.clone()),
-                        wallets::locked.eq(wallets::locked - unlock_amount),
+                        wallets::locked.eq(wallets::locked - unlock_amount.clone()),
                     ))
                     .execute(conn)
                     .context("Failed to unlock balance")?;
+                record_unlock(
+                    conn,
+                    &order.user_id,
+                    &asset,
+                    &unlock_amount,
+                    Some(&order.id),
+                )?;
 
                 canceled_orders.push(canceled_order);
             }
@@ -280,7 +587,7 @@ impl OrderDatabaseWriter for Repository {
     }
 
     /// Cancel all active orders globally
-    fn cancel_all_global_orders(&self) -> Result<Vec<Order>> {
+    fn cancel_all_global_orders(&self, reason: CancelReason) -> Result<Vec<Order>> {
         let conn = &mut self.get_conn()?;
         conn.transaction::<Vec<Order>, anyhow::Error, _>(|conn| {
             // Fetch all active orders across all markets
@@ -315,6 +622,7 @@ impl OrderDatabaseWriter for Repository {
                 let canceled_order = diesel::update(orders::table.find(&order.id))
                     .set((
                         orders::status.eq(OrderStatus::Canceled.as_str()),
+                        orders::cancel_reason.eq(reason.as_str()),
                         orders::update_time.eq(utils::get_utc_now_millis()),
                     ))
                     .get_result::<Order>(conn)
@@ -326,10 +634,17 @@ impl OrderDatabaseWriter for Repository {
                     .filter(wallets::asset.eq(&asset))
                     .set((
                         wallets::available.eq(wallets::available + unlock_amount.clone()),
-                        wallets::locked.eq(wallets::locked - unlock_amount),
+                        wallets::locked.eq(wallets::locked - unlock_amount.clone()),
                     ))
                     .execute(conn)
                     .context("Failed to unlock balance")?;
+                record_unlock(
+                    conn,
+                    &order.user_id,
+                    &asset,
+                    &unlock_amount,
+                    Some(&order.id),
+                )?;
 
                 canceled_orders.push(canceled_order);
             }
@@ -347,4 +662,120 @@ impl OrderDatabaseWriter for Repository {
 
         Ok(updated_order)
     }
+
+    fn amend_order(
+        &self,
+        order_id: &str,
+        price: BigDecimal,
+        remained_base: BigDecimal,
+        priority: i64,
+    ) -> Result<Order> {
+        if price <= 0 || remained_base <= 0 {
+            return Err(anyhow::anyhow!(
+                "Amended price and remaining amount must be greater than 0"
+            ));
+        }
+
+        let conn = &mut self.get_conn()?;
+        conn.transaction::<Order, anyhow::Error, _>(|conn| {
+            let order = orders::table
+                .find(order_id)
+                .for_update()
+                .first::<Order>(conn)
+                .optional()?
+                .ok_or_else(|| BitradeError::OrderNotFound(order_id.to_string()))?;
+
+            let status = OrderStatus::from_str(&order.status)
+                .map_err(|e| anyhow::anyhow!("Failed to parse order status: {}", e))?;
+            if !matches!(status, OrderStatus::Open | OrderStatus::PartiallyFilled) {
+                return Err(anyhow::anyhow!("Only open orders can be amended"));
+            }
+            if OrderType::from_str(&order.order_type)
+                .map_err(|e| anyhow::anyhow!("Failed to parse order type: {}", e))?
+                != OrderType::Limit
+            {
+                return Err(anyhow::anyhow!("Only limit orders can be amended"));
+            }
+
+            let market = markets::table
+                .find(&order.market_id)
+                .first::<Market>(conn)
+                .context("Market not found")?;
+
+            // Buys hold the quote of what is left, sells the base
+            let remained_quote = utils::round_amount(&(&price * &remained_base));
+            let (asset, delta) = match OrderSide::from_str(&order.side)
+                .map_err(|e| anyhow::anyhow!("Failed to parse order side: {}", e))?
+            {
+                OrderSide::Buy => (&market.quote_asset, &remained_quote - &order.remained_quote),
+                OrderSide::Sell => (&market.base_asset, &remained_base - &order.remained_base),
+            };
+
+            let wallet = wallets::table
+                .find((&order.user_id, asset))
+                .for_update()
+                .first::<Wallet>(conn)
+                .context("Wallet not found")?;
+            if wallet.available < delta {
+                return Err(BitradeError::InsufficientBalance {
+                    asset: asset.clone(),
+                    required: delta,
+                    available: wallet.available,
+                }
+                .into());
+            }
+            diesel::update(wallets::table.find((&order.user_id, asset)))
+                .set((
+                    wallets::available.eq(wallets::available - delta.clone()),
+                    wallets::locked.eq(wallets::locked + delta.clone()),
+                    wallets::update_time.eq(utils::get_utc_now_millis()),
+                ))
+                .execute(conn)
+                .context("Failed to relock balance")?;
+            // A growing order locks the difference, a shrinking one releases it
+            let (kind, from, to) = if delta >= 0 {
+                (
+                    LedgerEntryKind::Lock,
+                    LedgerAccount::Available,
+                    LedgerAccount::Locked,
+                )
+            } else {
+                (
+                    LedgerEntryKind::Unlock,
+                    LedgerAccount::Locked,
+                    LedgerAccount::Available,
+                )
+            };
+            conn.record_ledger_transfers(
+                Some(order_id),
+                &[LedgerTransfer::new(
+                    kind,
+                    asset,
+                    (&order.user_id, from),
+                    (&order.user_id, to),
+                    delta.abs(),
+                )],
+            )?;
+
+            let base_amount = &order.filled_base + &remained_base;
+            let amended = diesel::update(orders::table.find(order_id))
+                .set((
+                    orders::price.eq(&price),
+                    orders::base_amount.eq(&base_amount),
+                    orders::quote_amount.eq(&order.filled_quote + &remained_quote),
+                    orders::remained_base.eq(&remained_base),
+                    orders::remained_quote.eq(&remained_quote),
+                    // An iceberg never shows more than is left of it
+                    orders::display_amount.eq(order
+                        .display_amount
+                        .map(|display_amount| display_amount.min(base_amount.clone()))),
+                    orders::priority.eq(priority),
+                    orders::update_time.eq(utils::get_utc_now_millis()),
+                ))
+                .get_result::<Order>(conn)
+                .context("Failed to amend order")?;
+
+            Ok(amended)
+        })
+    }
 }
@@ -0,0 +1,80 @@
+use super::Repository;
+use crate::models::models::*;
+use crate::models::schema::*;
+use crate::provider::{OutboxDatabaseReader, OutboxDatabaseWriter};
+use anyhow::{Context, Result};
+use common::utils::get_utc_now_millis;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use serde::Serialize;
+
+fn outbox_event(
+    topic: OutboxTopic,
+    market_id: &str,
+    payload: &impl Serialize,
+) -> Result<NewOutboxEvent> {
+    Ok(NewOutboxEvent {
+        topic: topic.as_str().to_string(),
+        market_id: market_id.to_string(),
+        payload: serde_json::to_string(payload).context("Failed to encode outbox event")?,
+        create_time: get_utc_now_millis(),
+    })
+}
+
+/// The outbox events of `trade`: the trade, then its buy and sell order and the buyer's and
+/// seller's base and quote wallets, each as it stood right after the trade settled.
+pub(crate) fn trade_events(
+    trade: &NewTrade,
+    orders: [&Order; 2],
+    wallets: [&Wallet; 4],
+) -> Result<Vec<NewOutboxEvent>> {
+    let mut outbox = vec![outbox_event(OutboxTopic::Trade, &trade.market_id, trade)?];
+    for order in orders {
+        outbox.push(outbox_event(OutboxTopic::Order, &trade.market_id, order)?);
+    }
+    for wallet in wallets {
+        outbox.push(outbox_event(OutboxTopic::Wallet, &trade.market_id, wallet)?);
+    }
+    Ok(outbox)
+}
+
+/// Writes `outbox` on the caller's connection, so the events commit with what they describe.
+pub(crate) fn record_outbox_events(
+    conn: &mut PgConnection,
+    outbox: &[NewOutboxEvent],
+) -> Result<()> {
+    if outbox.is_empty() {
+        return Ok(());
+    }
+    diesel::insert_into(events::table)
+        .values(outbox)
+        .execute(conn)
+        .context("Failed to record outbox events")?;
+    Ok(())
+}
+
+impl OutboxDatabaseReader for Repository {
+    fn get_unpublished_events(&self, limit: i64) -> Result<Vec<OutboxEvent>> {
+        let conn = &mut self.get_conn()?;
+
+        events::table
+            .filter(events::published_time.is_null())
+            .order(events::id.asc())
+            .limit(limit)
+            .load(conn)
+            .context("Failed to fetch unpublished outbox events")
+    }
+}
+
+impl OutboxDatabaseWriter for Repository {
+    fn mark_events_published(&self, ids: &[i64]) -> Result<usize> {
+        let conn = &mut self.get_conn()?;
+
+        diesel::update(events::table)
+            .filter(events::id.eq_any(ids))
+            .filter(events::published_time.is_null())
+            .set(events::published_time.eq(get_utc_now_millis()))
+            .execute(conn)
+            .context("Failed to mark outbox events published")
+    }
+}
@@ -0,0 +1,67 @@
+use super::Repository;
+use crate::models::models::*;
+use crate::models::schema::*;
+use crate::provider::{PriceBandDatabaseReader, PriceBandDatabaseWriter};
+use anyhow::{Context, Result};
+use bigdecimal::BigDecimal;
+use common::utils::get_utc_now_millis;
+use diesel::prelude::*;
+
+impl PriceBandDatabaseReader for Repository {
+    fn get_price_band(&self, market_id: &str) -> Result<Option<PriceBand>> {
+        let conn = &mut self.get_conn()?;
+
+        price_bands::table
+            .find(market_id)
+            .first(conn)
+            .optional()
+            .context("Failed to fetch price band")
+    }
+}
+
+impl PriceBandDatabaseWriter for Repository {
+    fn set_price_band(
+        &self,
+        market_id: &str,
+        band_percent: Option<BigDecimal>,
+        halt_percent: Option<BigDecimal>,
+        halt_window_ms: i64,
+        halt_duration_ms: i64,
+    ) -> Result<PriceBand> {
+        let conn = &mut self.get_conn()?;
+
+        let now = get_utc_now_millis();
+        let band = PriceBand {
+            market_id: market_id.to_string(),
+            band_percent,
+            halt_percent,
+            halt_window_ms,
+            halt_duration_ms,
+            create_time: now,
+            update_time: now,
+        };
+        diesel::insert_into(price_bands::table)
+            .values(&band)
+            .on_conflict(price_bands::market_id)
+            .do_update()
+            .set((
+                price_bands::band_percent.eq(&band.band_percent),
+                price_bands::halt_percent.eq(&band.halt_percent),
+                price_bands::halt_window_ms.eq(halt_window_ms),
+                price_bands::halt_duration_ms.eq(halt_duration_ms),
+                price_bands::update_time.eq(now),
+            ))
+            .get_result(conn)
+            .context("Failed to store price band")
+    }
+
+    fn delete_price_band(&self, market_id: &str) -> Result<bool> {
+        let conn = &mut self.get_conn()?;
+
+        let deleted = diesel::delete(price_bands::table.find(market_id))
+            .execute(conn)
+            .context("Failed to delete price band")?;
+
+        Ok(deleted > 0)
+    }
+}
@@ -0,0 +1,168 @@
+use super::Repository;
+use crate::models::models::*;
+use crate::models::schema::*;
+use crate::provider::ReconciliationDatabaseReader;
+use anyhow::{Context, Result};
+use bigdecimal::BigDecimal;
+use diesel::dsl::sum;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use std::collections::BTreeMap;
+
+type SummedWallets = (
+    String,
+    Option<BigDecimal>,
+    Option<BigDecimal>,
+    Option<BigDecimal>,
+    Option<BigDecimal>,
+    Option<BigDecimal>,
+);
+
+fn asset_balance_totals(conn: &mut PgConnection) -> Result<Vec<AssetBalanceTotals>> {
+    let wallet_sums = wallets::table
+        .group_by(wallets::asset)
+        .select((
+            wallets::asset,
+            sum(wallets::available),
+            sum(wallets::locked),
+            sum(wallets::reserved),
+            sum(wallets::total_deposited),
+            sum(wallets::total_withdrawn),
+        ))
+        .load::<SummedWallets>(conn)
+        .context("Failed to sum wallet balances")?;
+    // Fees swept out of a treasury left the exchange like a withdrawal
+    let mut fee_sums: BTreeMap<String, (BigDecimal, BigDecimal)> = fee_treasury::table
+        .group_by(fee_treasury::asset)
+        .select((
+            fee_treasury::asset,
+            sum(fee_treasury::collected_amount),
+            sum(fee_treasury::total_withdrawn),
+        ))
+        .load::<(String, Option<BigDecimal>, Option<BigDecimal>)>(conn)
+        .context("Failed to sum fee treasuries")?
+        .into_iter()
+        .map(|(asset, collected, withdrawn)| {
+            (
+                asset,
+                (collected.unwrap_or_default(), withdrawn.unwrap_or_default()),
+            )
+        })
+        .collect();
+
+    let mut totals: Vec<AssetBalanceTotals> = wallet_sums
+        .into_iter()
+        .map(
+            |(asset, available, locked, reserved, deposited, withdrawn)| {
+                let (fee_treasury, fees_withdrawn) = fee_sums.remove(&asset).unwrap_or_default();
+                AssetBalanceTotals {
+                    asset,
+                    available: available.unwrap_or_default(),
+                    locked: locked.unwrap_or_default(),
+                    reserved: reserved.unwrap_or_default(),
+                    fee_treasury,
+                    total_deposited: deposited.unwrap_or_default(),
+                    total_withdrawn: withdrawn.unwrap_or_default() + fees_withdrawn,
+                }
+            },
+        )
+        .collect();
+    // Fees of an asset no wallet holds anymore still count
+    totals.extend(
+        fee_sums.into_iter().map(
+            |(asset, (fee_treasury, fees_withdrawn))| AssetBalanceTotals {
+                asset,
+                available: BigDecimal::from(0),
+                locked: BigDecimal::from(0),
+                reserved: BigDecimal::from(0),
+                fee_treasury,
+                total_deposited: BigDecimal::from(0),
+                total_withdrawn: fees_withdrawn,
+            },
+        ),
+    );
+    totals.sort_by(|a, b| a.asset.cmp(&b.asset));
+    Ok(totals)
+}
+
+fn locked_balances(conn: &mut PgConnection) -> Result<Vec<LockedBalance>> {
+    let active_orders = orders::table
+        .inner_join(markets::table)
+        .filter(orders::status.eq_any(&[
+            OrderStatus::Open.as_str(),
+            OrderStatus::PartiallyFilled.as_str(),
+        ]))
+        .select((
+            orders::user_id,
+            orders::side,
+            orders::remained_base,
+            orders::remained_quote,
+            markets::base_asset,
+            markets::quote_asset,
+        ))
+        .load::<(String, String, BigDecimal, BigDecimal, String, String)>(conn)
+        .context("Failed to fetch active orders")?;
+
+    // Buys hold the quote of what is left of them, sells the base
+    let mut order_locked: BTreeMap<(String, String), BigDecimal> = BTreeMap::new();
+    for (user_id, side, remained_base, remained_quote, base_asset, quote_asset) in active_orders {
+        let (asset, amount) = match OrderSide::from_str(&side)
+            .map_err(|e| anyhow::anyhow!("Failed to parse order side: {}", e))?
+        {
+            OrderSide::Buy => (quote_asset, remained_quote),
+            OrderSide::Sell => (base_asset, remained_base),
+        };
+        *order_locked.entry((user_id, asset)).or_default() += amount;
+    }
+
+    let locked_wallets = wallets::table
+        .filter(wallets::locked.ne(BigDecimal::from(0)))
+        .select((wallets::user_id, wallets::asset, wallets::locked))
+        .load::<(String, String, BigDecimal)>(conn)
+        .context("Failed to fetch locked wallets")?;
+
+    let mut balances: BTreeMap<(String, String), LockedBalance> = BTreeMap::new();
+    for (user_id, asset, locked) in locked_wallets {
+        let order_locked = order_locked
+            .remove(&(user_id.clone(), asset.clone()))
+            .unwrap_or_default();
+        balances.insert(
+            (user_id.clone(), asset.clone()),
+            LockedBalance {
+                user_id,
+                asset,
+                locked,
+                order_locked,
+            },
+        );
+    }
+    // Open orders whose wallet has nothing locked
+    for ((user_id, asset), order_locked) in order_locked {
+        balances.insert(
+            (user_id.clone(), asset.clone()),
+            LockedBalance {
+                user_id,
+                asset,
+                locked: BigDecimal::from(0),
+                order_locked,
+            },
+        );
+    }
+
+    Ok(balances.into_values().collect())
+}
+
+impl ReconciliationDatabaseReader for Repository {
+    fn get_balance_sheet(&self) -> Result<BalanceSheet> {
+        let conn = &mut self.get_conn()?;
+        conn.build_transaction()
+            .repeatable_read()
+            .read_only()
+            .run(|conn| {
+                Ok(BalanceSheet {
+                    assets: asset_balance_totals(conn)?,
+                    locks: locked_balances(conn)?,
+                })
+            })
+    }
+}
@@ -1,60 +1,587 @@
-use super::Repository;
+use super::engine_events::store_applied_sequence;
+use super::oco_groups::cancel_oco_siblings;
+use super::orders::insert_order;
+use super::{MissingWalletPolicy, PaginationError, Repository, SettlementError};
+use super::{record_kline_trades, record_outbox_events, trade_events};
 use crate::filters::TradeFilter;
 use crate::models::models::*;
 
 use crate::models::schema::*;
-use crate::provider::{TradeDatabaseReader, TradeDatabaseWriter};
+use crate::provider::{LedgerDatabaseWriter, TradeDatabaseReader, TradeDatabaseWriter};
 use anyhow::Context;
 use anyhow::Result;
 use bigdecimal::BigDecimal;
 use chrono::Utc;
-use common::db::pagination::Paginated;
-use common::db::pagination::Pagination;
+use common::db::pagination::{Cursor, Paginated, Pagination};
+use common::utils::{get_utc_now_millis, round_amount};
+use diesel::dsl::sum;
+use diesel::pg::{Pg, PgConnection};
 use diesel::prelude::*;
+use diesel::sql_query;
+use diesel::sql_types::Text;
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet};
 use uuid::Uuid;
 
-impl Repository {
-    fn get_trade_total_count(&self, filter: TradeFilter) -> Result<i64> {
-        let conn = &mut self.get_conn()?;
-        let mut query = trades::table.into_boxed();
-
-        if let Some(market_id) = filter.market_id {
-            query = query.filter(trades::market_id.eq(market_id));
+/// Fetches a wallet for update, handling a missing row according to `policy`.
+fn lock_wallet(
+    conn: &mut PgConnection,
+    user_id: &str,
+    asset: &str,
+    policy: MissingWalletPolicy,
+) -> Result<Wallet> {
+    let wallet = wallets::table
+        .find((user_id, asset))
+        .for_update()
+        .first::<Wallet>(conn)
+        .optional()
+        .context(format!("Failed to fetch {} wallet of {}", asset, user_id))?;
+
+    match (wallet, policy) {
+        (Some(wallet), _) => Ok(wallet),
+        (None, MissingWalletPolicy::CreateEmpty) => diesel::insert_into(wallets::table)
+            .values(&NewWallet {
+                user_id: user_id.to_string(),
+                asset: asset.to_string(),
+                available: BigDecimal::from(0),
+                locked: BigDecimal::from(0),
+                reserved: BigDecimal::from(0),
+                total_deposited: BigDecimal::from(0),
+                total_withdrawn: BigDecimal::from(0),
+                update_time: get_utc_now_millis(),
+            })
+            .get_result(conn)
+            .context(format!("Failed to create {} wallet of {}", asset, user_id)),
+        (None, MissingWalletPolicy::Fail) => Err(SettlementError::WalletMissing {
+            user_id: user_id.to_string(),
+            asset: asset.to_string(),
         }
+        .into()),
+    }
+}
 
-        if let Some(buyer_order_id) = filter.buyer_order_id {
-            query = query.filter(trades::buyer_order_id.eq(buyer_order_id));
-        }
+/// Returns the wallet of `key` among those a settlement locked.
+fn locked_wallet<'w, 'k>(
+    wallets: &'w mut BTreeMap<(&'k str, &'k str), Wallet>,
+    key: (&'k str, &'k str),
+) -> Result<&'w mut Wallet> {
+    wallets
+        .get_mut(&key)
+        .with_context(|| format!("{} wallet of {} is not locked", key.1, key.0))
+}
 
-        if let Some(seller_order_id) = filter.seller_order_id {
-            query = query.filter(trades::seller_order_id.eq(seller_order_id));
-        }
+/// Applies `fill` and the order's `fee` to order `order_id`, the way the orders table keeps
+/// its amounts.
+fn fill_order<'o>(
+    orders: &'o mut BTreeMap<String, Order>,
+    order_id: &str,
+    fill: &TradeFill,
+    fee: &BigDecimal,
+) -> Result<&'o Order> {
+    let order = orders
+        .get_mut(order_id)
+        .with_context(|| format!("Failed to fetch open order {}", order_id))?;
+
+    order.filled_base =
+        round_amount(&(round_amount(&order.filled_base) + round_amount(&fill.base_amount)));
+    order.filled_quote =
+        round_amount(&(round_amount(&order.filled_quote) + round_amount(&fill.quote_amount)));
+    order.filled_fee = round_amount(&(round_amount(&order.filled_fee) + fee));
+    order.remained_base =
+        round_amount(&(round_amount(&order.remained_base) - round_amount(&fill.base_amount)));
+    order.remained_quote = if order.side == OrderSide::Sell.as_str() {
+        // A sell order's remaining quote is what its unfilled base is worth at the order's
+        // own price. Trades may fill it at better prices, so it is not derived from them.
+        round_amount(&(&order.remained_base * &order.price))
+    } else {
+        round_amount(&(round_amount(&order.remained_quote) - round_amount(&fill.quote_amount)))
+    };
+    order.status = if order.filled_base >= round_amount(&order.base_amount) {
+        OrderStatus::Filled.as_str().to_string()
+    } else {
+        OrderStatus::PartiallyFilled.as_str().to_string()
+    };
+    Ok(order)
+}
 
-        if let Some(buyer_user_id) = filter.buyer_user_id {
-            query = query.filter(trades::buyer_user_id.eq(buyer_user_id));
-        }
+/// Sets `columns` of many rows of `table` in a single UPDATE, joined on `keys` to a VALUES
+/// list. Each row holds the values of the keys, then those of the columns, bound as text and
+/// cast to the SQL type paired with the column.
+fn update_rows(
+    conn: &mut PgConnection,
+    table: &str,
+    keys: &[&str],
+    columns: &[(&str, &str)],
+    rows: Vec<Vec<String>>,
+) -> QueryResult<usize> {
+    let names: Vec<&str> = keys
+        .iter()
+        .copied()
+        .chain(columns.iter().map(|(name, _)| *name))
+        .collect();
+    let types: Vec<&str> = keys
+        .iter()
+        .map(|_| "text")
+        .chain(columns.iter().map(|(_, sql_type)| *sql_type))
+        .collect();
+    let values: Vec<String> = (0..rows.len())
+        .map(|row| {
+            let params: Vec<String> = types
+                .iter()
+                .enumerate()
+                .map(|(column, sql_type)| {
+                    format!("${}::{}", row * types.len() + column + 1, sql_type)
+                })
+                .collect();
+            format!("({})", params.join(", "))
+        })
+        .collect();
+    let assignments: Vec<String> = columns
+        .iter()
+        .map(|(name, _)| format!("{} = v.{}", name, name))
+        .collect();
+    let joins: Vec<String> = keys
+        .iter()
+        .map(|key| format!("t.{} = v.{}", key, key))
+        .collect();
+    let sql = format!(
+        "UPDATE {} AS t SET {} FROM (VALUES {}) AS v ({}) WHERE {}",
+        table,
+        assignments.join(", "),
+        values.join(", "),
+        names.join(", "),
+        joins.join(" AND ")
+    );
+
+    let mut query = sql_query(sql).into_boxed::<Pg>();
+    for value in rows.into_iter().flatten() {
+        query = query.bind::<Text, _>(value);
+    }
+    query.execute(conn)
+}
+
+/// Refuses fills a user would trade against themself in.
+pub(crate) fn check_fills(fills: &[TradeFill]) -> Result<()> {
+    // Ensure buyer and seller are not the same user
+    if fills
+        .iter()
+        .any(|fill| fill.buyer_user_id == fill.seller_user_id)
+    {
+        return Err(anyhow::anyhow!("Buyer and seller cannot be the same user"));
+    }
+    Ok(())
+}
 
-        if let Some(seller_user_id) = filter.seller_user_id {
-            query = query.filter(trades::seller_user_id.eq(seller_user_id));
+/// What settling fills changes besides the orders and wallets: the trades with their ledger
+/// transfers, balance snapshots and outbox events, and the fees collected on either side.
+pub(crate) struct Settlement {
+    pub trades: Vec<NewTrade>,
+    pub transfers: Vec<[LedgerTransfer; 5]>,
+    pub snapshots: Vec<TradeBalanceSnapshot>,
+    pub outbox: Vec<NewOutboxEvent>,
+    pub buyer_fees: BigDecimal,
+    pub seller_fees: BigDecimal,
+}
+
+impl Settlement {
+    /// The ledger transactions of the trades, one per trade referencing it
+    pub fn transactions(&self) -> Vec<(Option<&str>, &[LedgerTransfer])> {
+        self.trades
+            .iter()
+            .zip(&self.transfers)
+            .map(|(trade, transfers)| (Some(trade.id.as_str()), transfers.as_slice()))
+            .collect()
+    }
+}
+
+/// Settles `fills` one after the other on the `wallets` and `orders` a settlement locked,
+/// leaving them as the last fill does. Nothing is written, so every backend settles the same
+/// way.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn apply_fills<'k>(
+    market_id: &str,
+    base_asset: &'k str,
+    quote_asset: &'k str,
+    fills: &'k [TradeFill],
+    wallets: &mut BTreeMap<(&'k str, &'k str), Wallet>,
+    orders: &mut BTreeMap<String, Order>,
+    balance_snapshots: bool,
+    outbox: bool,
+) -> Result<Settlement> {
+    let mut trades = Vec::with_capacity(fills.len());
+    let mut transfers = Vec::with_capacity(fills.len());
+    let mut snapshots = Vec::new();
+    let mut events = Vec::new();
+    let mut buyer_fees = BigDecimal::from(0);
+    let mut seller_fees = BigDecimal::from(0);
+    for fill in fills {
+        let buyer_base_key = (fill.buyer_user_id.as_str(), base_asset);
+        let buyer_quote_key = (fill.buyer_user_id.as_str(), quote_asset);
+        let seller_base_key = (fill.seller_user_id.as_str(), base_asset);
+        let seller_quote_key = (fill.seller_user_id.as_str(), quote_asset);
+        let keys = [
+            buyer_base_key,
+            buyer_quote_key,
+            seller_base_key,
+            seller_quote_key,
+        ];
+        let before: Vec<Wallet> = match balance_snapshots {
+            true => keys.iter().map(|key| wallets[key].clone()).collect(),
+            false => Vec::new(),
+        };
+
+        // 🔹 Ensure the seller and the buyer have enough frozen balance
+        let seller_locked = &wallets[&seller_base_key].locked;
+        if *seller_locked < fill.base_amount {
+            return Err(anyhow::anyhow!(
+                "Insufficient frozen balance: seller {} has {} {} frozen but needs {}",
+                fill.seller_user_id,
+                seller_locked,
+                base_asset,
+                fill.base_amount
+            ));
+        }
+        let buyer_locked = &wallets[&buyer_quote_key].locked;
+        if *buyer_locked < fill.quote_amount {
+            return Err(anyhow::anyhow!(
+                "Insufficient frozen balance: buyer {} has {} {} frozen but needs {}",
+                fill.buyer_user_id,
+                buyer_locked,
+                quote_asset,
+                fill.quote_amount
+            ));
         }
 
-        if let Some(taker_side) = filter.taker_side {
-            query = query.filter(trades::taker_side.eq(taker_side));
+        // 🔹 Calculate fees
+        // buyer fee is calculated on the base amount (received amount). Every fill is
+        // charged on its own, so a market buy pays it on what each price level delivered
+        let buyer_fee = round_amount(&(&fill.buyer_fee_rate * &fill.base_amount));
+        // seller fee is calculated on the quote amount (received amount)
+        let seller_fee = round_amount(&(&fill.seller_fee_rate * &fill.quote_amount));
+        buyer_fees += &buyer_fee;
+        seller_fees += &seller_fee;
+
+        fill_order(orders, &fill.seller_order_id, fill, &seller_fee)?;
+        let buyer_order = fill_order(orders, &fill.buyer_order_id, fill, &buyer_fee)?;
+
+        // 🔹 Calculate buyer's quote asset residue
+        // It is quote the filled order locked but never spent, so it goes back to the
+        // buyer whole. Fees apply only to traded amounts.
+        let buyer_quote_residue = if buyer_order.status == OrderStatus::Filled.as_str() {
+            buyer_order.remained_quote.clone()
+        } else {
+            BigDecimal::from(0)
+        };
+
+        // 🔹 Deduct base asset from seller's and quote asset from buyer's frozen balance
+        let wallet = locked_wallet(wallets, seller_base_key)?;
+        wallet.locked = round_amount(&wallet.locked) - round_amount(&fill.base_amount);
+        let wallet = locked_wallet(wallets, buyer_quote_key)?;
+        wallet.locked = round_amount(&wallet.locked)
+            - round_amount(&fill.quote_amount)
+            - round_amount(&buyer_quote_residue);
+        wallet.available = round_amount(&wallet.available) + round_amount(&buyer_quote_residue);
+
+        // 🔹 Credit the seller with the quote and the buyer with the base, less fees
+        let seller_receives = round_amount(&(&fill.quote_amount - &seller_fee));
+        locked_wallet(wallets, seller_quote_key)?.available += &seller_receives;
+        let buyer_receives = round_amount(&(&fill.base_amount - &buyer_fee));
+        locked_wallet(wallets, buyer_base_key)?.available += &buyer_receives;
+
+        let traded = |kind, asset: &str, from: (&str, LedgerAccount), to, amount: &BigDecimal| {
+            LedgerTransfer::new(kind, asset, from, to, round_amount(amount))
+        };
+        let seller_locked = (fill.seller_user_id.as_str(), LedgerAccount::Locked);
+        let buyer_locked = (fill.buyer_user_id.as_str(), LedgerAccount::Locked);
+        let treasury = (market_id, LedgerAccount::FeeTreasury);
+        transfers.push([
+            traded(
+                LedgerEntryKind::Trade,
+                base_asset,
+                seller_locked,
+                (&fill.buyer_user_id, LedgerAccount::Available),
+                &buyer_receives,
+            ),
+            traded(
+                LedgerEntryKind::Fee,
+                base_asset,
+                seller_locked,
+                treasury,
+                &buyer_fee,
+            ),
+            traded(
+                LedgerEntryKind::Trade,
+                quote_asset,
+                buyer_locked,
+                (&fill.seller_user_id, LedgerAccount::Available),
+                &seller_receives,
+            ),
+            traded(
+                LedgerEntryKind::Fee,
+                quote_asset,
+                buyer_locked,
+                treasury,
+                &seller_fee,
+            ),
+            traded(
+                LedgerEntryKind::Unlock,
+                quote_asset,
+                buyer_locked,
+                (&fill.buyer_user_id, LedgerAccount::Available),
+                &buyer_quote_residue,
+            ),
+        ]);
+
+        let new_trade = NewTrade {
+            id: fill
+                .trade_id
+                .clone()
+                .unwrap_or_else(|| Uuid::new_v4().to_string()),
+            timestamp: fill.timestamp.unwrap_or_else(|| Utc::now().timestamp()),
+            market_id: market_id.to_string(),
+            price: fill.price.clone(),
+            base_amount: fill.base_amount.clone(),
+            quote_amount: fill.quote_amount.clone(),
+            buyer_user_id: fill.buyer_user_id.clone(),
+            buyer_order_id: fill.buyer_order_id.clone(),
+            buyer_fee,
+            seller_user_id: fill.seller_user_id.clone(),
+            seller_order_id: fill.seller_order_id.clone(),
+            seller_fee,
+            taker_side: if fill.is_buyer_taker {
+                "BUY".to_string()
+            } else {
+                "SELL".to_string()
+            },
+            is_liquidation: None,
+        };
+
+        for (before, key) in before.into_iter().zip(&keys) {
+            let after = &wallets[key];
+            snapshots.push(TradeBalanceSnapshot {
+                trade_id: new_trade.id.clone(),
+                user_id: before.user_id,
+                asset: before.asset,
+                available_before: before.available,
+                locked_before: before.locked,
+                available_after: after.available.clone(),
+                locked_after: after.locked.clone(),
+            });
+        }
+        if outbox {
+            events.extend(trade_events(
+                &new_trade,
+                [
+                    &orders[&fill.buyer_order_id],
+                    &orders[&fill.seller_order_id],
+                ],
+                keys.map(|key| &wallets[&key]),
+            )?);
         }
+        trades.push(new_trade);
+    }
+
+    Ok(Settlement {
+        trades,
+        transfers,
+        snapshots,
+        outbox: events,
+        buyer_fees,
+        seller_fees,
+    })
+}
+
+fn filtered_trades(filter: TradeFilter) -> trades::BoxedQuery<'static, Pg> {
+    let mut query = trades::table.into_boxed();
+
+    if let Some(market_id) = filter.market_id {
+        query = query.filter(trades::market_id.eq(market_id));
+    }
+    if let Some(buyer_order_id) = filter.buyer_order_id {
+        query = query.filter(trades::buyer_order_id.eq(buyer_order_id));
+    }
+    if let Some(seller_order_id) = filter.seller_order_id {
+        query = query.filter(trades::seller_order_id.eq(seller_order_id));
+    }
+    if let Some(buyer_user_id) = filter.buyer_user_id {
+        query = query.filter(trades::buyer_user_id.eq(buyer_user_id));
+    }
+    if let Some(seller_user_id) = filter.seller_user_id {
+        query = query.filter(trades::seller_user_id.eq(seller_user_id));
+    }
+    if let Some(taker_side) = filter.taker_side {
+        query = query.filter(trades::taker_side.eq(taker_side));
+    }
+    if let Some(is_liquidation) = filter.is_liquidation {
+        query = query.filter(trades::is_liquidation.eq(is_liquidation));
+    }
+    if let Some(start_time) = filter.start_time {
+        query = query.filter(trades::timestamp.ge(start_time));
+    }
+    if let Some(end_time) = filter.end_time {
+        query = query.filter(trades::timestamp.le(end_time));
+    }
 
-        if let Some(is_liquidation) = filter.is_liquidation {
-            query = query.filter(trades::is_liquidation.eq(is_liquidation));
+    query
+}
+
+impl Repository {
+    /// Settles `fills` on `conn`, within the transaction of the caller.
+    fn settle_fills(
+        &self,
+        conn: &mut PgConnection,
+        market_id: &str,
+        base_asset: &str,
+        quote_asset: &str,
+        fills: &[TradeFill],
+    ) -> Result<Vec<NewTrade>> {
+        // 🔹 Fetch & Lock every wallet the fills touch, once each. The funds being traded
+        // are locked in the sellers' base and buyers' quote wallets, those can never be
+        // created here
+        let mut wallets = BTreeMap::new();
+        for fill in fills {
+            for (user_id, asset, policy) in [
+                (&fill.seller_user_id, base_asset, MissingWalletPolicy::Fail),
+                (&fill.buyer_user_id, quote_asset, MissingWalletPolicy::Fail),
+                (
+                    &fill.seller_user_id,
+                    quote_asset,
+                    self.missing_wallet_policy,
+                ),
+                (&fill.buyer_user_id, base_asset, self.missing_wallet_policy),
+            ] {
+                let key = (user_id.as_str(), asset);
+                if let Entry::Vacant(entry) = wallets.entry(key) {
+                    entry.insert(lock_wallet(conn, user_id, asset, policy)?);
+                }
+            }
         }
 
-        if let Some(start_time) = filter.start_time {
-            query = query.filter(trades::timestamp.ge(start_time));
+        // 🔹 Fetch & Lock the orders, all of which must still be open
+        let order_ids: Vec<&str> = fills
+            .iter()
+            .flat_map(|fill| [fill.buyer_order_id.as_str(), fill.seller_order_id.as_str()])
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        let mut orders: BTreeMap<String, Order> = orders::table
+            .filter(orders::id.eq_any(&order_ids))
+            .filter(orders::status.eq_any(&[
+                OrderStatus::Open.as_str(),
+                OrderStatus::PartiallyFilled.as_str(),
+            ]))
+            .order(orders::id.asc())
+            .for_update()
+            .load::<Order>(conn)
+            .context("Failed to fetch orders")?
+            .into_iter()
+            .map(|order| (order.id.clone(), order))
+            .collect();
+
+        // 🔹 Settle the fills one after the other on the locked rows
+        let settlement = apply_fills(
+            market_id,
+            base_asset,
+            quote_asset,
+            fills,
+            &mut wallets,
+            &mut orders,
+            self.balance_snapshots,
+            self.outbox,
+        )?;
+
+        // 🔹 Write every order and wallet back once, as the last fill left it
+        update_rows(
+            conn,
+            "orders",
+            &["id"],
+            &[
+                ("filled_base", "numeric"),
+                ("filled_quote", "numeric"),
+                ("filled_fee", "numeric"),
+                ("remained_base", "numeric"),
+                ("remained_quote", "numeric"),
+                ("status", "text"),
+            ],
+            orders
+                .values()
+                .map(|order| {
+                    vec![
+                        order.id.clone(),
+                        order.filled_base.to_string(),
+                        order.filled_quote.to_string(),
+                        order.filled_fee.to_string(),
+                        order.remained_base.to_string(),
+                        order.remained_quote.to_string(),
+                        order.status.clone(),
+                    ]
+                })
+                .collect(),
+        )
+        .context("Failed to update orders")?;
+        update_rows(
+            conn,
+            "wallets",
+            &["user_id", "asset"],
+            &[("available", "numeric"), ("locked", "numeric")],
+            wallets
+                .values()
+                .map(|wallet| {
+                    vec![
+                        wallet.user_id.clone(),
+                        wallet.asset.clone(),
+                        wallet.available.to_string(),
+                        wallet.locked.to_string(),
+                    ]
+                })
+                .collect(),
+        )
+        .context("Failed to update wallets")?;
+
+        // 🔹 Update fee treasury for quote asset (seller fees) and base asset (buyer fees)
+        for (asset, collected) in [
+            (quote_asset, &settlement.seller_fees),
+            (base_asset, &settlement.buyer_fees),
+        ] {
+            diesel::update(fee_treasury::table)
+                .filter(fee_treasury::market_id.eq(market_id))
+                .filter(fee_treasury::asset.eq(asset))
+                .set(fee_treasury::collected_amount.eq(fee_treasury::collected_amount + collected))
+                .execute(conn)
+                .context(format!("Failed to update {} fee treasury", asset))?;
         }
 
-        if let Some(end_time) = filter.end_time {
-            query = query.filter(trades::timestamp.le(end_time));
+        // 🔹 Record the trades with their ledger transactions and candles
+        conn.record_ledger_transactions(&settlement.transactions())?;
+
+        diesel::insert_into(trades::table)
+            .values(&settlement.trades)
+            .execute(conn)
+            .context("Failed to record trades")?;
+
+        record_kline_trades(conn, &settlement.trades)?;
+
+        if !settlement.snapshots.is_empty() {
+            diesel::insert_into(trade_balance_snapshots::table)
+                .values(&settlement.snapshots)
+                .execute(conn)
+                .context("Failed to record trade balance snapshots")?;
         }
 
-        let total_count: i64 = query.select(diesel::dsl::count_star()).first(conn)?;
+        // A fill of any order cancels the other leg of its OCO group. The cancel unlocks
+        // funds of wallets written above, so it runs once they are.
+        cancel_oco_siblings(conn, &order_ids)?;
+
+        record_outbox_events(conn, &settlement.outbox)?;
+
+        Ok(settlement.trades)
+    }
+
+    fn get_trade_total_count(&self, filter: TradeFilter) -> Result<i64> {
+        let conn = &mut self.get_conn()?;
+        let total_count: i64 = filtered_trades(filter)
+            .select(diesel::dsl::count_star())
+            .first(conn)?;
         Ok(total_count)
     }
 }
@@ -67,62 +594,272 @@ impl TradeDatabaseReader for Repository {
     ) -> Result<Paginated<Trade>> {
         let conn = &mut self.get_conn()?;
         let pagination = pagination.unwrap_or_default();
-        let mut query = trades::table.into_boxed();
         let total_count = self.get_trade_total_count(filter.clone())?;
-        if let Some(market_id) = filter.market_id {
-            query = query.filter(trades::market_id.eq(market_id));
-        }
+        let mut query = filtered_trades(filter);
 
-        if let Some(buyer_order_id) = filter.buyer_order_id {
-            query = query.filter(trades::buyer_order_id.eq(buyer_order_id));
-        }
+        let limit = pagination.limit.unwrap_or(10);
+        let offset = pagination.offset.unwrap_or(0);
 
-        if let Some(seller_order_id) = filter.seller_order_id {
-            query = query.filter(trades::seller_order_id.eq(seller_order_id));
+        let sort_field = pagination.sort_field("timestamp");
+        let descending = pagination.is_descending().ok_or_else(|| {
+            PaginationError::InvalidDirection(
+                pagination.order_direction.clone().unwrap_or_default(),
+            )
+        })?;
+        // The id breaks ties, so that a page ends between two trades
+        query = match (sort_field, descending) {
+            ("timestamp", true) => query.order((trades::timestamp.desc(), trades::id.desc())),
+            ("timestamp", false) => query.order((trades::timestamp.asc(), trades::id.asc())),
+            ("price", true) => query.order((trades::price.desc(), trades::id.desc())),
+            ("price", false) => query.order((trades::price.asc(), trades::id.asc())),
+            (field, _) => {
+                return Err(PaginationError::UnsortableField {
+                    listing: "trades",
+                    field: field.to_string(),
+                }
+                .into());
+            }
+        };
+        let by_time = sort_field == "timestamp";
+        match &pagination.cursor {
+            Some(_) if !by_time => {
+                return Err(PaginationError::CursorNeedsTimeSort {
+                    listing: "trades",
+                    field: "timestamp",
+                }
+                .into());
+            }
+            Some(cursor) if descending => {
+                query = query.filter(
+                    trades::timestamp.lt(cursor.timestamp).or(trades::timestamp
+                        .eq(cursor.timestamp)
+                        .and(trades::id.lt(cursor.id.clone()))),
+                )
+            }
+            Some(cursor) => {
+                query = query.filter(
+                    trades::timestamp.gt(cursor.timestamp).or(trades::timestamp
+                        .eq(cursor.timestamp)
+                        .and(trades::id.gt(cursor.id.clone()))),
+                )
+            }
+            None => query = query.offset(offset),
         }
+        let mut trades = query.limit(limit + 1).load::<Trade>(conn)?;
 
-        if let Some(buyer_user_id) = filter.buyer_user_id {
-            query = query.filter(trades::buyer_user_id.eq(buyer_user_id));
+        let has_more = trades.len() > limit as usize;
+        if has_more {
+            trades.pop();
         }
+        let next_offset = if has_more && pagination.cursor.is_none() {
+            Some(offset + limit)
+        } else {
+            None
+        };
+        let next_cursor = trades
+            .last()
+            .filter(|_| has_more && by_time)
+            .map(|trade| Cursor::new(trade.timestamp, trade.id.clone()));
 
-        if let Some(seller_user_id) = filter.seller_user_id {
-            query = query.filter(trades::seller_user_id.eq(seller_user_id));
+        Ok(Paginated {
+            items: trades,
+            total_count,
+            next_offset,
+            has_more,
+            next_cursor,
+        })
+    }
+    fn export_trades(
+        &self,
+        filter: TradeFilter,
+        after: Option<&Cursor>,
+        limit: i64,
+    ) -> Result<Vec<Trade>> {
+        let conn = &mut self.get_conn()?;
+        let mut query = filtered_trades(filter);
+        if let Some(after) = after {
+            query = query.filter(
+                trades::timestamp.gt(after.timestamp).or(trades::timestamp
+                    .eq(after.timestamp)
+                    .and(trades::id.gt(after.id.clone()))),
+            );
         }
+        Ok(query
+            .order((trades::timestamp.asc(), trades::id.asc()))
+            .limit(limit)
+            .load::<Trade>(conn)?)
+    }
 
-        if let Some(taker_side) = filter.taker_side {
-            query = query.filter(trades::taker_side.eq(taker_side));
-        }
+    fn get_order_fills(&self, order_id: &str) -> Result<Vec<OrderFill>> {
+        let conn = &mut self.get_conn()?;
+
+        // Trades of one second have no order among them but their id
+        let trades = trades::table
+            .filter(
+                trades::buyer_order_id
+                    .eq(order_id)
+                    .or(trades::seller_order_id.eq(order_id)),
+            )
+            .order((trades::timestamp.asc(), trades::id.asc()))
+            .load::<Trade>(conn)
+            .context("Failed to load order fills")?;
+
+        let mut filled_base = BigDecimal::from(0);
+        Ok(trades
+            .into_iter()
+            .map(|trade| {
+                let (side, fee) = if trade.buyer_order_id == order_id {
+                    (OrderSide::Buy, trade.buyer_fee.clone())
+                } else {
+                    (OrderSide::Sell, trade.seller_fee.clone())
+                };
+                let role = if trade.taker_side == side.as_str() {
+                    MarketRole::Taker
+                } else {
+                    MarketRole::Maker
+                };
+                filled_base += &trade.base_amount;
+                OrderFill {
+                    trade,
+                    side,
+                    role,
+                    fee,
+                    filled_base: filled_base.clone(),
+                }
+            })
+            .collect())
+    }
+
+    fn get_trade_detail(&self, trade_id: &str) -> Result<Option<TradeDetail>> {
+        let conn = &mut self.get_conn()?;
+
+        let Some(trade) = trades::table
+            .find(trade_id)
+            .first::<Trade>(conn)
+            .optional()?
+        else {
+            return Ok(None);
+        };
+        let balance_snapshots = trade_balance_snapshots::table
+            .filter(trade_balance_snapshots::trade_id.eq(trade_id))
+            .order((
+                trade_balance_snapshots::user_id,
+                trade_balance_snapshots::asset,
+            ))
+            .load(conn)?;
+
+        Ok(Some(TradeDetail {
+            trade,
+            balance_snapshots,
+        }))
+    }
 
-        if let Some(is_liquidation) = filter.is_liquidation {
-            query = query.filter(trades::is_liquidation.eq(is_liquidation));
+    fn get_user_fees_paid(
+        &self,
+        user_id: &str,
+        start_time: Option<i64>,
+        end_time: Option<i64>,
+    ) -> Result<Vec<UserFeePaid>> {
+        let conn = &mut self.get_conn()?;
+
+        // Buyers pay their fee in the base asset
+        let mut buyer_query = trades::table
+            .inner_join(markets::table)
+            .filter(trades::buyer_user_id.eq(user_id))
+            .group_by(markets::base_asset)
+            .select((markets::base_asset, sum(trades::buyer_fee)))
+            .into_boxed();
+
+        // Sellers pay their fee in the quote asset
+        let mut seller_query = trades::table
+            .inner_join(markets::table)
+            .filter(trades::seller_user_id.eq(user_id))
+            .group_by(markets::quote_asset)
+            .select((markets::quote_asset, sum(trades::seller_fee)))
+            .into_boxed();
+
+        if let Some(start_time) = start_time {
+            buyer_query = buyer_query.filter(trades::timestamp.ge(start_time));
+            seller_query = seller_query.filter(trades::timestamp.ge(start_time));
         }
 
-        if let Some(start_time) = filter.start_time {
-            query = query.filter(trades::timestamp.ge(start_time));
+        if let Some(end_time) = end_time {
+            buyer_query = buyer_query.filter(trades::timestamp.le(end_time));
+            seller_query = seller_query.filter(trades::timestamp.le(end_time));
         }
 
-        if let Some(end_time) = filter.end_time {
-            query = query.filter(trades::timestamp.le(end_time));
+        let buyer_fees = buyer_query
+            .load::<(String, Option<BigDecimal>)>(conn)
+            .context("Failed to sum buyer fees")?;
+        let seller_fees = seller_query
+            .load::<(String, Option<BigDecimal>)>(conn)
+            .context("Failed to sum seller fees")?;
+
+        // An asset can be paid both as buyer and as seller across markets
+        let mut fees_by_asset: BTreeMap<String, BigDecimal> = BTreeMap::new();
+        for (asset, fee) in buyer_fees.into_iter().chain(seller_fees) {
+            *fees_by_asset
+                .entry(asset)
+                .or_insert_with(|| BigDecimal::from(0)) += fee.unwrap_or_default();
         }
 
-        let limit = pagination.limit.unwrap_or(10);
-        let offset = pagination.offset.unwrap_or(0);
+        Ok(fees_by_asset
+            .into_iter()
+            .map(|(asset, amount)| UserFeePaid { asset, amount })
+            .collect())
+    }
 
-        let trades = query
-            .order(trades::timestamp.desc())
-            .limit(limit)
-            .offset(offset)
-            .load::<Trade>(conn)?;
+    fn get_user_traded_volume(
+        &self,
+        user_id: &str,
+        market_id: &str,
+        start_time: i64,
+    ) -> Result<BigDecimal> {
+        let conn = &mut self.get_conn()?;
 
-        let has_more = trades.len() > limit as usize;
-        let next_offset = if has_more { Some(offset + limit) } else { None };
+        let volume = trades::table
+            .filter(trades::market_id.eq(market_id))
+            .filter(
+                trades::buyer_user_id
+                    .eq(user_id)
+                    .or(trades::seller_user_id.eq(user_id)),
+            )
+            .filter(trades::timestamp.ge(start_time))
+            .select(sum(trades::quote_amount))
+            .first::<Option<BigDecimal>>(conn)
+            .context("Failed to sum traded volume")?;
+
+        Ok(volume.unwrap_or_default())
+    }
 
-        Ok(Paginated {
-            items: trades,
-            total_count,
-            next_offset,
-            has_more,
-        })
+    fn get_user_traded_volumes(
+        &self,
+        user_id: &str,
+        start_time: i64,
+    ) -> Result<Vec<UserTradedVolume>> {
+        let conn = &mut self.get_conn()?;
+
+        let volumes = trades::table
+            .inner_join(markets::table)
+            .filter(
+                trades::buyer_user_id
+                    .eq(user_id)
+                    .or(trades::seller_user_id.eq(user_id)),
+            )
+            .filter(trades::timestamp.ge(start_time))
+            .group_by(markets::quote_asset)
+            .select((markets::quote_asset, sum(trades::quote_amount)))
+            .order_by(markets::quote_asset)
+            .load::<(String, Option<BigDecimal>)>(conn)
+            .context("Failed to sum traded volumes")?;
+
+        Ok(volumes
+            .into_iter()
+            .map(|(asset, volume)| UserTradedVolume {
+                asset,
+                volume: volume.unwrap_or_default(),
+            })
+            .collect())
     }
 }
 
@@ -143,290 +880,81 @@ impl TradeDatabaseWriter for Repository {
         buyer_fee_rate: BigDecimal,
         seller_fee_rate: BigDecimal,
     ) -> Result<NewTrade> {
-        // Ensure buyer and seller are not the same user
-        if buyer_user_id == seller_user_id {
-            return Err(anyhow::anyhow!("Buyer and seller cannot be the same user"));
+        let fill = TradeFill {
+            is_buyer_taker,
+            buyer_user_id,
+            seller_user_id,
+            buyer_order_id,
+            seller_order_id,
+            price,
+            base_amount,
+            quote_amount,
+            buyer_fee_rate,
+            seller_fee_rate,
+            trade_id: None,
+            timestamp: None,
+        };
+        self.execute_limit_trades(&market_id, &base_asset, &quote_asset, &[fill])?
+            .pop()
+            .context("Settlement returned no trade")
+    }
+
+    fn execute_limit_trades(
+        &self,
+        market_id: &str,
+        base_asset: &str,
+        quote_asset: &str,
+        fills: &[TradeFill],
+    ) -> Result<Vec<NewTrade>> {
+        check_fills(fills)?;
+        if fills.is_empty() {
+            return Ok(Vec::new());
         }
 
         let conn = &mut self.get_conn()?;
-        conn.transaction::<_, anyhow::Error, _>(|conn| {
-            // 🔹 Fetch & Lock Seller's Balance
-            let seller_base_balance: Wallet = wallets::table
-                .filter(wallets::user_id.eq(&seller_user_id))
-                .filter(wallets::asset.eq(&base_asset))
-                .for_update()
-                .first(conn)
-                .context("Failed to fetch seller balance")?;
-
-            let buyer_quote_balance: Wallet = wallets::table
-                .filter(wallets::user_id.eq(&buyer_user_id))
-                .filter(wallets::asset.eq(&quote_asset))
-                .for_update()
-                .first(conn)
-                .context("Failed to fetch buyer balance")?;
-
-            // 🔹 Ensure the seller has enough frozen balance
-            if seller_base_balance.locked < base_amount {
-                return Err(anyhow::anyhow!(
-                    "Insufficient frozen balance: seller {} has {} {} frozen but needs {}",
-                    seller_user_id,
-                    seller_base_balance.locked,
-                    base_asset,
-                    base_amount
-                ));
-            }
-
-            // 🔹 Ensure the buyer has enough frozen balance
-            if buyer_quote_balance.locked < quote_amount {
-                return Err(anyhow::anyhow!(
-                    "Insufficient frozen balance: buyer {} has {} {} frozen but needs {}",
-                    buyer_user_id,
-                    buyer_quote_balance.locked,
-                    quote_asset,
-                    quote_amount
-                ));
-            }
-            // 🔹 Calculate fees
-            // buyer fee is calculated on the base amount (spent amount)
-            let buyer_fee = (buyer_fee_rate * &base_amount).with_prec(8);
-            // seller fee is calculated on the quote amount (received amount)
-            let seller_fee = (seller_fee_rate * &quote_amount).with_prec(8);
-            // 🔹 Fetch & Lock Seller Order
-            let seller_order: Order = orders::table
-                .filter(orders::id.eq(&seller_order_id))
-                .filter(orders::status.eq_any(&[
-                    OrderStatus::Open.as_str(),
-                    OrderStatus::PartiallyFilled.as_str(),
-                ]))
-                .for_update()
-                .first(conn)
-                .context("Failed to fetch seller order")?;
-            println!("seller_order.remained_base: {}", seller_order.remained_base);
-            let new_seller_filled_base =
-                &seller_order.filled_base.with_prec(8) + &base_amount.with_prec(8);
-            let new_seller_filled_quote =
-                &seller_order.filled_quote.with_prec(8) + &quote_amount.with_prec(8);
-            let new_seller_filled_fee =
-                (&seller_order.filled_fee.with_prec(8) + &seller_fee).with_prec(8);
-            let new_seller_remained_base =
-                &seller_order.remained_base.with_prec(8) - &base_amount.with_prec(8);
-            // remained quote is not needed for the seller order
-            // let new_seller_remained_quote =
-            //     &seller_order.remained_quote.with_prec(8) - &quote_amount.with_prec(8);
-            let seller_status =
-                if new_seller_filled_base.with_prec(8) >= seller_order.base_amount.with_prec(8) {
-                    OrderStatus::Filled.as_str()
-                } else {
-                    OrderStatus::PartiallyFilled.as_str()
-                };
-
-            // Debug printing for seller order calculations
-            println!("Seller Order Update Values:");
-            println!("  - Order ID: {}", seller_order_id);
-            println!("  - Original filled_base: {}", seller_order.filled_base);
-            println!("  - New filled_base: {}", new_seller_filled_base);
-            println!("  - Original filled_quote: {}", seller_order.filled_quote);
-            println!("  - New filled_quote: {}", new_seller_filled_quote);
-            println!("  - Original filled_fee: {}", seller_order.filled_fee);
-            println!("  - New filled_fee: {}", new_seller_filled_fee);
-            println!("  - Original remained_base: {}", seller_order.remained_base);
-            println!("  - New remained_base: {}", new_seller_remained_base);
-            println!(
-                "  - Original remained_quote: {}",
-                seller_order.remained_quote
-            );
-
-            println!(
-                "  - amount being traded: base={}, quote={}",
-                base_amount, quote_amount
-            );
-            println!("  - fee: {}", seller_fee);
-            println!("  - new status: {}", seller_status);
-
-            diesel::update(orders::table)
-                .filter(orders::id.eq(&seller_order_id))
-                .set((
-                    orders::filled_base.eq(new_seller_filled_base.with_prec(8)),
-                    orders::filled_quote.eq(new_seller_filled_quote.with_prec(8)),
-                    orders::filled_fee.eq(new_seller_filled_fee.with_prec(8)),
-                    orders::remained_base.eq(new_seller_remained_base.with_prec(8)),
-                    orders::status.eq(seller_status),
-                ))
-                .execute(conn)
-                .context("Failed to update seller order")?;
-
-            // 🔹 Fetch & Lock Buyer Order
-            let buyer_order: Order = orders::table
-                .filter(orders::id.eq(&buyer_order_id))
-                .filter(orders::status.eq_any(&[
-                    OrderStatus::Open.as_str(),
-                    OrderStatus::PartiallyFilled.as_str(),
-                ]))
-                .for_update()
-                .first(conn)
-                .context("Failed to fetch buyer order")?;
-
-            let new_buyer_filled_base =
-                &buyer_order.filled_base.with_prec(8) + &base_amount.with_prec(8);
-            let new_buyer_filled_quote =
-                &buyer_order.filled_quote.with_prec(8) + &quote_amount.with_prec(8);
-            let new_buyer_filled_fee =
-                (&buyer_order.filled_fee.with_prec(8) + &buyer_fee).with_prec(8);
-            let new_buyer_remained_base =
-                &buyer_order.remained_base.with_prec(8) - &base_amount.with_prec(8);
-            let new_buyer_remained_quote =
-                &buyer_order.remained_quote.with_prec(8) - &quote_amount.with_prec(8);
-
-            // Debug printing for buyer order calculations
-            println!("Buyer Order Update Values:");
-            println!("  - Order ID: {}", buyer_order_id);
-            println!("  - Original filled_base: {}", buyer_order.filled_base);
-            println!("  - New filled_base: {}", new_buyer_filled_base);
-            println!("  - Original filled_quote: {}", buyer_order.filled_quote);
-            println!("  - New filled_quote: {}", new_buyer_filled_quote);
-            println!("  - Original filled_fee: {}", buyer_order.filled_fee);
-            println!("  - New filled_fee: {}", new_buyer_filled_fee);
-            println!("  - Original remained_base: {}", buyer_order.remained_base);
-            println!("  - New remained_base: {}", new_buyer_remained_base);
-            println!(
-                "  - Original remained_quote: {}",
-                buyer_order.remained_quote
-            );
-            println!("  - New remained_quote: {}", new_buyer_remained_quote);
-            println!(
-                "  - amount being traded: base={}, quote={}",
-                base_amount, quote_amount
-            );
-            println!("  - fee : {}", buyer_fee);
-
-            let buyer_status =
-                if new_buyer_filled_base.with_prec(8) >= buyer_order.base_amount.with_prec(8) {
-                    OrderStatus::Filled.as_str()
-                } else {
-                    OrderStatus::PartiallyFilled.as_str()
-                };
+        conn.transaction(|conn| self.settle_fills(conn, market_id, base_asset, quote_asset, fills))
+    }
 
-            diesel::update(orders::table)
-                .filter(orders::id.eq(&buyer_order_id))
-                .set((
-                    orders::filled_base.eq(&new_buyer_filled_base.with_prec(8)),
-                    orders::filled_quote.eq(&new_buyer_filled_quote.with_prec(8)),
-                    orders::filled_fee.eq(&new_buyer_filled_fee.with_prec(8)),
-                    orders::remained_base.eq(&new_buyer_remained_base.with_prec(8)),
-                    orders::remained_quote.eq(&new_buyer_remained_quote.with_prec(8)),
-                    orders::status.eq(buyer_status),
-                ))
-                .execute(conn)
-                .context("Failed to update buyer order")?;
+    fn execute_journaled_trades(
+        &self,
+        market_id: &str,
+        base_asset: &str,
+        quote_asset: &str,
+        fills: &[TradeFill],
+        sequence: i64,
+    ) -> Result<Vec<NewTrade>> {
+        check_fills(fills)?;
 
-            // 🔹 Calculate buyer's quote asset residue
-            let buyer_quote_residue = if buyer_status == OrderStatus::Filled.as_str() {
-                new_buyer_remained_quote
-            } else {
-                BigDecimal::from(0)
+        let conn = &mut self.get_conn()?;
+        conn.transaction(|conn| {
+            let trades = match fills.is_empty() {
+                true => Vec::new(),
+                false => self.settle_fills(conn, market_id, base_asset, quote_asset, fills)?,
             };
+            store_applied_sequence(conn, market_id, sequence)?;
+            Ok(trades)
+        })
+    }
 
-            // 🔹 Deduct base asset from seller's frozen balance
-            diesel::update(wallets::table)
-                .filter(wallets::user_id.eq(&seller_user_id))
-                .filter(wallets::asset.eq(&base_asset))
-                .set((wallets::locked
-                    .eq(seller_base_balance.locked.with_prec(8) - &base_amount.with_prec(8)),))
-                .execute(conn)
-                .context("Failed to update seller base balance")?;
-
-            // 🔹 Deduct quote asset from buyer's frozen balance
-            diesel::update(wallets::table)
-                .filter(wallets::user_id.eq(&buyer_user_id))
-                .filter(wallets::asset.eq(&quote_asset))
-                .set((
-                    wallets::locked.eq(buyer_quote_balance.locked.with_prec(8)
-                        - &quote_amount.with_prec(8)
-                        - &buyer_quote_residue.with_prec(8)),
-                    wallets::available.eq(buyer_quote_balance.available.with_prec(8)
-                        + &buyer_quote_residue.with_prec(8)),
-                ))
-                .execute(conn)
-                .context("Failed to update buyer quote balance")?;
-
-            // 🔹 Fetch seller's quote balance to credit with quote amount
-            let seller_quote_balance: Wallet = wallets::table
-                .filter(wallets::user_id.eq(&seller_user_id))
-                .filter(wallets::asset.eq(&quote_asset))
-                .for_update()
-                .first(conn)
-                .context("Failed to fetch seller quote balance")?;
-
-            // 🔹 Fetch buyer's base balance to credit with base amount
-            let buyer_base_balance: Wallet = wallets::table
-                .filter(wallets::user_id.eq(&buyer_user_id))
-                .filter(wallets::asset.eq(&base_asset))
-                .for_update()
-                .first(conn)
-                .context("Failed to fetch buyer base balance")?;
-
-            let seller_receives = (&quote_amount - &seller_fee).with_prec(8);
-            diesel::update(wallets::table)
-                .filter(wallets::user_id.eq(&seller_user_id))
-                .filter(wallets::asset.eq(&quote_asset))
-                .set(wallets::available.eq(seller_quote_balance.available + seller_receives))
-                .execute(conn)
-                .context("Failed to update seller quote balance")?;
-
-            let buyer_receives = (&base_amount - &buyer_fee).with_prec(8);
-            diesel::update(wallets::table)
-                .filter(wallets::user_id.eq(&buyer_user_id))
-                .filter(wallets::asset.eq(&base_asset))
-                .set(wallets::available.eq(buyer_base_balance.available + buyer_receives))
-                .execute(conn)
-                .context("Failed to update buyer base balance")?;
-            // 🔹 Determine taker and maker for the trade record
-
-            // 🔹 Update fee treasury for quote asset (seller fee)
-            diesel::update(fee_treasury::table)
-                .filter(fee_treasury::market_id.eq(&market_id))
-                .filter(fee_treasury::asset.eq(&quote_asset))
-                .set(
-                    fee_treasury::collected_amount.eq(fee_treasury::collected_amount + &seller_fee),
-                )
-                .execute(conn)
-                .context("Failed to update quote asset fee treasury")?;
+    fn create_order_with_trades(
+        &self,
+        order_data: NewOrder,
+        base_asset: &str,
+        quote_asset: &str,
+        fills: &[TradeFill],
+    ) -> Result<(Order, Vec<NewTrade>)> {
+        check_fills(fills)?;
 
-            // 🔹 Update fee treasury for base asset (buyer fee)
-            diesel::update(fee_treasury::table)
-                .filter(fee_treasury::market_id.eq(&market_id))
-                .filter(fee_treasury::asset.eq(&base_asset))
-                .set(fee_treasury::collected_amount.eq(fee_treasury::collected_amount + &buyer_fee))
-                .execute(conn)
-                .context("Failed to update base asset fee treasury")?;
-            // 🔹 Create and insert the trade record
-            let new_trade = NewTrade {
-                id: Uuid::new_v4().to_string(),
-                timestamp: Utc::now().timestamp(),
-                market_id,
-                price,
-                base_amount,
-                quote_amount,
-                buyer_user_id,
-                buyer_order_id,
-                buyer_fee,
-                seller_user_id,
-                seller_order_id,
-                seller_fee,
-                taker_side: if is_buyer_taker {
-                    "BUY".to_string()
-                } else {
-                    "SELL".to_string()
-                },
-                is_liquidation: None,
+        let conn = &mut self.get_conn()?;
+        conn.transaction(|conn| {
+            let order = insert_order(conn, &order_data)?;
+            let trades = match fills.is_empty() {
+                true => Vec::new(),
+                false => {
+                    self.settle_fills(conn, &order_data.market_id, base_asset, quote_asset, fills)?
+                }
             };
-
-            diesel::insert_into(trades::table)
-                .values(&new_trade)
-                .execute(conn)
-                .unwrap();
-
-            Ok(new_trade)
+            Ok((order, trades))
         })
     }
 }
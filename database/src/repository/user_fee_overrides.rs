@@ -0,0 +1,62 @@
+use super::Repository;
+use crate::models::models::*;
+use crate::models::schema::*;
+use crate::provider::{UserFeeOverrideDatabaseReader, UserFeeOverrideDatabaseWriter};
+use anyhow::{Context, Result};
+use bigdecimal::BigDecimal;
+use common::utils::get_utc_now_millis;
+use diesel::prelude::*;
+
+impl UserFeeOverrideDatabaseReader for Repository {
+    fn get_user_fee_override(&self, user_id: &str) -> Result<Option<UserFeeOverride>> {
+        let conn = &mut self.get_conn()?;
+
+        let result = user_fee_overrides::table
+            .find(user_id)
+            .first(conn)
+            .optional()?;
+
+        Ok(result)
+    }
+}
+
+impl UserFeeOverrideDatabaseWriter for Repository {
+    fn set_user_fee_override(
+        &self,
+        user_id: &str,
+        maker_fee: BigDecimal,
+        taker_fee: BigDecimal,
+    ) -> Result<UserFeeOverride> {
+        let conn = &mut self.get_conn()?;
+
+        let now = get_utc_now_millis();
+        let fee_override = UserFeeOverride {
+            user_id: user_id.to_string(),
+            maker_fee,
+            taker_fee,
+            create_time: now,
+            update_time: now,
+        };
+        diesel::insert_into(user_fee_overrides::table)
+            .values(&fee_override)
+            .on_conflict(user_fee_overrides::user_id)
+            .do_update()
+            .set((
+                user_fee_overrides::maker_fee.eq(&fee_override.maker_fee),
+                user_fee_overrides::taker_fee.eq(&fee_override.taker_fee),
+                user_fee_overrides::update_time.eq(now),
+            ))
+            .get_result(conn)
+            .context("Failed to store user fee override")
+    }
+
+    fn delete_user_fee_override(&self, user_id: &str) -> Result<bool> {
+        let conn = &mut self.get_conn()?;
+
+        let deleted = diesel::delete(user_fee_overrides::table.find(user_id))
+            .execute(conn)
+            .context("Failed to delete user fee override")?;
+
+        Ok(deleted > 0)
+    }
+}
@@ -0,0 +1,67 @@
+use super::Repository;
+use crate::models::models::*;
+use crate::models::schema::*;
+use crate::provider::{UserRestrictionDatabaseReader, UserRestrictionDatabaseWriter};
+use anyhow::{Context, Result};
+use common::utils::get_utc_now_millis;
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+
+impl UserRestrictionDatabaseReader for Repository {
+    fn get_user_restriction(&self, user_id: &str) -> Result<Option<UserRestriction>> {
+        let conn = &mut self.get_conn()?;
+
+        user_restrictions::table
+            .find(user_id)
+            .first(conn)
+            .optional()
+            .context("Failed to fetch user restriction")
+    }
+
+    fn list_user_restrictions(&self) -> Result<Vec<UserRestriction>> {
+        let conn = &mut self.get_conn()?;
+
+        user_restrictions::table
+            .order(user_restrictions::update_time.desc())
+            .load(conn)
+            .context("Failed to list user restrictions")
+    }
+}
+
+impl UserRestrictionDatabaseWriter for Repository {
+    fn set_user_restriction(
+        &self,
+        user_id: &str,
+        status: UserStatus,
+        reason: &str,
+    ) -> Result<UserRestriction> {
+        let conn = &mut self.get_conn()?;
+
+        diesel::insert_into(user_restrictions::table)
+            .values(&UserRestriction {
+                user_id: user_id.to_string(),
+                status: status.as_str().to_string(),
+                reason: reason.to_string(),
+                update_time: get_utc_now_millis(),
+            })
+            .on_conflict(user_restrictions::user_id)
+            .do_update()
+            .set((
+                user_restrictions::status.eq(excluded(user_restrictions::status)),
+                user_restrictions::reason.eq(excluded(user_restrictions::reason)),
+                user_restrictions::update_time.eq(excluded(user_restrictions::update_time)),
+            ))
+            .get_result(conn)
+            .context("Failed to store user restriction")
+    }
+
+    fn delete_user_restriction(&self, user_id: &str) -> Result<bool> {
+        let conn = &mut self.get_conn()?;
+
+        let deleted = diesel::delete(user_restrictions::table.find(user_id))
+            .execute(conn)
+            .context("Failed to delete user restriction")?;
+
+        Ok(deleted > 0)
+    }
+}
@@ -3,11 +3,14 @@ use crate::models::models::*;
 
 use super::Repository;
 use crate::models::schema::*;
-use crate::provider::{WalletDatabaseReader, WalletDatabaseWriter};
-use anyhow::{Result, bail};
+use crate::provider::{LedgerDatabaseWriter, WalletDatabaseReader, WalletDatabaseWriter};
+use anyhow::{Context, Result, bail};
 use bigdecimal::BigDecimal;
 use common::db::pagination::{Paginated, Pagination};
+use common::error::BitradeError;
+use diesel::pg::PgConnection;
 use diesel::prelude::*;
+use std::collections::HashMap;
 
 impl Repository {
     fn get_wallet_total_count(&self, filter: WalletFilter) -> Result<i64> {
@@ -24,67 +27,213 @@ impl Repository {
         let total_count: i64 = count_query.select(diesel::dsl::count_star()).first(conn)?;
         Ok(total_count)
     }
+}
 
-    fn update_or_create_balance(
-        &self,
-        user_id: &str,
-        asset: &str,
-        available_delta: BigDecimal,
-        locked_delta: BigDecimal,
-    ) -> Result<Wallet> {
-        let conn = &mut self.get_conn()?;
-        let current_time = common::utils::get_utc_now_millis();
-
-        let wallet_option = wallets::table
-            .find((user_id, asset))
-            .first::<Wallet>(conn)
-            .optional()?;
-
-        match wallet_option {
-            Some(wallet) => {
-                let new_available = wallet.available + available_delta.clone();
-                let new_locked = wallet.locked + locked_delta.clone();
-
-                if new_available < BigDecimal::from(0) || new_locked < BigDecimal::from(0) {
-                    bail!("Insufficient balance");
+fn update_or_create_balance(
+    conn: &mut PgConnection,
+    user_id: &str,
+    asset: &str,
+    available_delta: BigDecimal,
+    locked_delta: BigDecimal,
+) -> Result<Wallet> {
+    let current_time = common::utils::get_utc_now_millis();
+
+    let wallet_option = wallets::table
+        .find((user_id, asset))
+        .for_update()
+        .first::<Wallet>(conn)
+        .optional()?;
+
+    match wallet_option {
+        Some(wallet) => {
+            let new_available = &wallet.available + available_delta.clone();
+            let new_locked = wallet.locked + locked_delta.clone();
+
+            if new_available < 0 {
+                return Err(BitradeError::InsufficientBalance {
+                    asset: asset.to_string(),
+                    required: -available_delta,
+                    available: wallet.available,
                 }
+                .into());
+            }
+            if new_locked < 0 {
+                bail!("Insufficient locked balance");
+            }
 
-                let result = diesel::update(wallets::table.find((user_id, asset)))
-                    .set((
-                        wallets::available.eq(new_available),
-                        wallets::locked.eq(new_locked),
-                        wallets::update_time.eq(current_time),
-                    ))
-                    .get_result(conn)?;
+            let result = diesel::update(wallets::table.find((user_id, asset)))
+                .set((
+                    wallets::available.eq(new_available),
+                    wallets::locked.eq(new_locked),
+                    wallets::update_time.eq(current_time),
+                ))
+                .get_result(conn)?;
 
-                Ok(result)
-            }
-            None => {
-                if available_delta < BigDecimal::from(0) || locked_delta < BigDecimal::from(0) {
-                    bail!("Insufficient balance");
+            Ok(result)
+        }
+        None => {
+            if available_delta < 0 {
+                return Err(BitradeError::InsufficientBalance {
+                    asset: asset.to_string(),
+                    required: -available_delta,
+                    available: BigDecimal::from(0),
                 }
+                .into());
+            }
+            if locked_delta < 0 {
+                bail!("Insufficient locked balance");
+            }
 
-                let new_wallet = NewWallet {
-                    user_id: user_id.to_string(),
-                    asset: asset.to_string(),
-                    available: available_delta,
-                    locked: locked_delta,
-                    reserved: BigDecimal::from(0),
-                    total_deposited: BigDecimal::from(0),
-                    total_withdrawn: BigDecimal::from(0),
-                    update_time: current_time,
-                };
+            let new_wallet = NewWallet {
+                user_id: user_id.to_string(),
+                asset: asset.to_string(),
+                available: available_delta,
+                locked: locked_delta,
+                reserved: BigDecimal::from(0),
+                total_deposited: BigDecimal::from(0),
+                total_withdrawn: BigDecimal::from(0),
+                update_time: current_time,
+            };
+
+            let result = diesel::insert_into(wallets::table)
+                .values(&new_wallet)
+                .get_result(conn)?;
+
+            Ok(result)
+        }
+    }
+}
 
-                let result = diesel::insert_into(wallets::table)
-                    .values(&new_wallet)
-                    .get_result(conn)?;
+/// Credits `amount` to the available balance and counts it as deposited.
+pub(super) fn deposit_funds(
+    conn: &mut PgConnection,
+    user_id: &str,
+    asset: &str,
+    amount: &BigDecimal,
+) -> Result<Wallet> {
+    let current_time = common::utils::get_utc_now_millis();
+
+    let wallet = wallets::table
+        .find((user_id, asset))
+        .for_update()
+        .first::<Wallet>(conn)
+        .optional()?;
+
+    match wallet {
+        Some(wallet) => {
+            let new_wallet = diesel::update(wallets::table.find((user_id, asset)))
+                .set((
+                    wallets::available.eq(wallet.available + amount.clone()),
+                    wallets::total_deposited.eq(wallet.total_deposited + amount.clone()),
+                    wallets::update_time.eq(current_time),
+                ))
+                .get_result(conn)?;
+
+            Ok(new_wallet)
+        }
+        None => {
+            let new_wallet = NewWallet {
+                user_id: user_id.to_string(),
+                asset: asset.to_string(),
+                available: amount.clone(),
+                locked: BigDecimal::from(0),
+                reserved: BigDecimal::from(0),
+                total_deposited: amount.clone(),
+                total_withdrawn: BigDecimal::from(0),
+                update_time: current_time,
+            };
+
+            let result = diesel::insert_into(wallets::table)
+                .values(&new_wallet)
+                .get_result(conn)?;
+
+            Ok(result)
+        }
+    }
+}
 
-                Ok(result)
+fn withdraw_funds(
+    conn: &mut PgConnection,
+    user_id: &str,
+    asset: &str,
+    amount: &BigDecimal,
+) -> Result<Wallet> {
+    let current_time = common::utils::get_utc_now_millis();
+
+    let balance = wallets::table
+        .find((user_id, asset))
+        .for_update()
+        .first::<Wallet>(conn)
+        .optional()?;
+
+    let insufficient = |available: BigDecimal| BitradeError::InsufficientBalance {
+        asset: asset.to_string(),
+        required: amount.clone(),
+        available,
+    };
+    match balance {
+        Some(balance) => {
+            if balance.available < *amount {
+                return Err(insufficient(balance.available).into());
             }
+
+            let new_balance = diesel::update(wallets::table.find((user_id, asset)))
+                .set((
+                    wallets::available.eq(balance.available - amount.clone()),
+                    wallets::total_withdrawn.eq(balance.total_withdrawn + amount.clone()),
+                    wallets::update_time.eq(current_time),
+                ))
+                .get_result(conn)?;
+
+            Ok(new_balance)
         }
+        None => Err(insufficient(BigDecimal::from(0)).into()),
     }
 }
 
+/// Moves `amount` from the available to the locked balance of a wallet.
+pub(super) fn lock_funds(
+    conn: &mut PgConnection,
+    user_id: &str,
+    asset: &str,
+    amount: &BigDecimal,
+    reference_id: Option<&str>,
+) -> Result<Wallet> {
+    let wallet = update_or_create_balance(conn, user_id, asset, -amount.clone(), amount.clone())?;
+    conn.record_ledger_transfers(
+        reference_id,
+        &[LedgerTransfer::new(
+            LedgerEntryKind::Lock,
+            asset,
+            (user_id, LedgerAccount::Available),
+            (user_id, LedgerAccount::Locked),
+            amount.clone(),
+        )],
+    )?;
+    Ok(wallet)
+}
+
+/// Records `amount` of `asset` going back from the locked to the available balance of a user.
+pub(super) fn record_unlock(
+    conn: &mut PgConnection,
+    user_id: &str,
+    asset: &str,
+    amount: &BigDecimal,
+    reference_id: Option<&str>,
+) -> Result<()> {
+    conn.record_ledger_transfers(
+        reference_id,
+        &[LedgerTransfer::new(
+            LedgerEntryKind::Unlock,
+            asset,
+            (user_id, LedgerAccount::Locked),
+            (user_id, LedgerAccount::Available),
+            amount.clone(),
+        )],
+    )?;
+    Ok(())
+}
+
 impl WalletDatabaseReader for Repository {
     fn get_wallet(&self, user_id: &str, asset: &str) -> Result<Option<Wallet>> {
         let conn = &mut self.get_conn()?;
@@ -97,6 +246,27 @@ impl WalletDatabaseReader for Repository {
         Ok(result)
     }
 
+    fn get_user_wallets(&self, user_id: &str) -> Result<Vec<Wallet>> {
+        let conn = &mut self.get_conn()?;
+
+        wallets::table
+            .filter(wallets::user_id.eq(user_id))
+            .order(wallets::asset.asc())
+            .load(conn)
+            .context("Failed to load user wallets")
+    }
+
+    fn get_wallet_changes(&self, user_id: &str, since_time: i64) -> Result<Vec<Wallet>> {
+        let conn = &mut self.get_conn()?;
+
+        wallets::table
+            .filter(wallets::user_id.eq(user_id))
+            .filter(wallets::update_time.gt(since_time))
+            .order((wallets::update_time.asc(), wallets::asset.asc()))
+            .load::<Wallet>(conn)
+            .context("Failed to fetch wallet changes")
+    }
+
     fn list_wallets(
         &self,
         filter: WalletFilter,
@@ -150,81 +320,131 @@ impl WalletDatabaseReader for Repository {
             total_count: total,
             next_offset: None,
             has_more: false,
+            next_cursor: None,
         })
     }
 }
 
 impl WalletDatabaseWriter for Repository {
     fn lock_balance(&self, user_id: &str, asset: &str, amount: BigDecimal) -> Result<Wallet> {
-        self.update_or_create_balance(user_id, asset, -amount.clone(), amount)
+        let conn = &mut self.get_conn()?;
+        conn.transaction(|conn| lock_funds(conn, user_id, asset, &amount, None))
     }
 
     fn unlock_balance(&self, user_id: &str, asset: &str, amount: BigDecimal) -> Result<Wallet> {
-        self.update_or_create_balance(user_id, asset, amount.clone(), amount)
+        let conn = &mut self.get_conn()?;
+        conn.transaction(|conn| {
+            let wallet =
+                update_or_create_balance(conn, user_id, asset, amount.clone(), -amount.clone())?;
+            record_unlock(conn, user_id, asset, &amount, None)?;
+            Ok(wallet)
+        })
     }
 
     fn deposit_balance(&self, user_id: &str, asset: &str, amount: BigDecimal) -> Result<Wallet> {
         let conn = &mut self.get_conn()?;
-        let current_time = common::utils::get_utc_now_millis();
-
-        let wallet = self.get_wallet(user_id, asset)?;
-
-        match wallet {
-            Some(wallet) => {
-                let new_wallet = diesel::update(wallets::table.find((user_id, asset)))
-                    .set((
-                        wallets::available.eq(wallet.available + amount.clone()),
-                        wallets::total_deposited.eq(wallet.total_deposited + amount.clone()),
-                        wallets::update_time.eq(current_time),
-                    ))
-                    .get_result(conn)?;
-
-                Ok(new_wallet)
-            }
-            None => {
-                let new_wallet = NewWallet {
-                    user_id: user_id.to_string(),
-                    asset: asset.to_string(),
-                    available: amount.clone(),
-                    locked: BigDecimal::from(0),
-                    reserved: BigDecimal::from(0),
-                    total_deposited: amount.clone(),
-                    total_withdrawn: BigDecimal::from(0),
-                    update_time: current_time,
-                };
-
-                let result = diesel::insert_into(wallets::table)
-                    .values(&new_wallet)
-                    .get_result(conn)?;
-
-                Ok(result)
-            }
-        }
+        conn.transaction(|conn| {
+            let wallet = deposit_funds(conn, user_id, asset, &amount)?;
+            conn.record_ledger_transfers(
+                None,
+                &[LedgerTransfer::new(
+                    LedgerEntryKind::Deposit,
+                    asset,
+                    (user_id, LedgerAccount::External),
+                    (user_id, LedgerAccount::Available),
+                    amount.clone(),
+                )],
+            )?;
+            Ok(wallet)
+        })
     }
 
     fn withdraw_balance(&self, user_id: &str, asset: &str, amount: BigDecimal) -> Result<Wallet> {
         let conn = &mut self.get_conn()?;
-        let current_time = common::utils::get_utc_now_millis();
+        conn.transaction(|conn| {
+            let wallet = withdraw_funds(conn, user_id, asset, &amount)?;
+            conn.record_ledger_transfers(
+                None,
+                &[LedgerTransfer::new(
+                    LedgerEntryKind::Withdrawal,
+                    asset,
+                    (user_id, LedgerAccount::Available),
+                    (user_id, LedgerAccount::External),
+                    amount.clone(),
+                )],
+            )?;
+            Ok(wallet)
+        })
+    }
 
-        let balance = self.get_wallet(user_id, asset)?;
+    /// Recovery tool for balances left locked after a crash. Only the amount exceeding what the
+    /// user's open orders still hold is released.
+    fn release_orphaned_locks(&self, user_id: &str) -> Result<Vec<Wallet>> {
+        let conn = &mut self.get_conn()?;
+        conn.transaction::<Vec<Wallet>, anyhow::Error, _>(|conn| {
+            // Lock the user's wallets so no trade settles against them while we compare
+            let user_wallets = wallets::table
+                .filter(wallets::user_id.eq(user_id))
+                .for_update()
+                .load::<Wallet>(conn)
+                .context("Failed to fetch wallets")?;
+
+            let active_orders = orders::table
+                .inner_join(markets::table)
+                .filter(orders::user_id.eq(user_id))
+                .filter(orders::status.eq_any(&[
+                    OrderStatus::Open.as_str(),
+                    OrderStatus::PartiallyFilled.as_str(),
+                ]))
+                .select((
+                    orders::side,
+                    orders::remained_base,
+                    orders::remained_quote,
+                    markets::base_asset,
+                    markets::quote_asset,
+                ))
+                .load::<(String, BigDecimal, BigDecimal, String, String)>(conn)
+                .context("Failed to fetch active orders")?;
+
+            // Sum the amount each asset is expected to have locked by open orders
+            let mut backed_amounts: HashMap<String, BigDecimal> = HashMap::new();
+            for (side, remained_base, remained_quote, base_asset, quote_asset) in active_orders {
+                let order_side = OrderSide::from_str(&side)
+                    .map_err(|e| anyhow::anyhow!("Failed to parse order side: {}", e))?;
+                let (asset, amount) = match order_side {
+                    OrderSide::Buy => (quote_asset, remained_quote),
+                    OrderSide::Sell => (base_asset, remained_base),
+                };
+                *backed_amounts
+                    .entry(asset)
+                    .or_insert_with(|| BigDecimal::from(0)) += amount;
+            }
 
-        match balance {
-            Some(balance) => {
-                if balance.available < amount {
-                    bail!("Insufficient balance");
+            let current_time = common::utils::get_utc_now_millis();
+            let mut released_wallets = Vec::new();
+            for wallet in user_wallets {
+                let backed_amount = backed_amounts
+                    .remove(&wallet.asset)
+                    .unwrap_or_else(|| BigDecimal::from(0));
+                let orphaned_amount = &wallet.locked - &backed_amount;
+                if orphaned_amount <= 0 {
+                    continue;
                 }
 
-                let new_balance = diesel::update(wallets::table.find((user_id, asset)))
+                let released = diesel::update(wallets::table.find((user_id, &wallet.asset)))
                     .set((
-                        wallets::available.eq(balance.available - amount.clone()),
-                        wallets::total_withdrawn.eq(balance.total_withdrawn + amount.clone()),
+                        wallets::available.eq(&wallet.available + &orphaned_amount),
+                        wallets::locked.eq(backed_amount),
                         wallets::update_time.eq(current_time),
                     ))
-                    .get_result(conn)?;
+                    .get_result::<Wallet>(conn)
+                    .context("Failed to release orphaned lock")?;
+                record_unlock(conn, user_id, &wallet.asset, &orphaned_amount, None)?;
 
-                Ok(new_balance)
+                released_wallets.push(released);
             }
-            None => bail!("Balance not found"),
-        }
+
+            Ok(released_wallets)
+        })
     }
 }
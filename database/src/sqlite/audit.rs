@@ -0,0 +1,39 @@
+use super::SqliteRepository;
+use super::schema::*;
+use crate::models::models::*;
+use crate::provider::{AuditDatabaseReader, AuditDatabaseWriter};
+use anyhow::Result;
+use diesel::prelude::*;
+
+impl AuditDatabaseReader for SqliteRepository {
+    fn get_user_order_audit(&self, user_id: &str) -> Result<Vec<OrderAudit>> {
+        let conn = &mut *self.get_conn()?;
+
+        let result = order_audit::table
+            .filter(order_audit::user_id.eq(user_id))
+            .order(order_audit::id.asc())
+            .load(conn)?;
+
+        Ok(result)
+    }
+}
+
+impl AuditDatabaseWriter for SqliteRepository {
+    fn record_audit(&self, entry: NewOrderAudit) -> Result<OrderAudit> {
+        let conn = &mut *self.get_conn()?;
+
+        let result = diesel::insert_into(order_audit::table)
+            .values((
+                order_audit::action.eq(&entry.action),
+                order_audit::user_id.eq(&entry.user_id),
+                order_audit::remote_addr.eq(&entry.remote_addr),
+                order_audit::market_id.eq(&entry.market_id),
+                order_audit::order_id.eq(&entry.order_id),
+                order_audit::request.eq(&entry.request),
+                order_audit::create_time.eq(entry.create_time),
+            ))
+            .get_result(conn)?;
+
+        Ok(result)
+    }
+}
@@ -0,0 +1,134 @@
+use super::SqliteRepository;
+use super::schema::*;
+use crate::models::models::*;
+use crate::provider::{EngineEventDatabaseReader, EngineEventDatabaseWriter};
+use anyhow::{Context, Result};
+use common::utils::get_utc_now_millis;
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+
+impl EngineEventDatabaseReader for SqliteRepository {
+    fn get_engine_events(&self, market_id: &str, after_sequence: i64) -> Result<Vec<EngineEvent>> {
+        let conn = &mut *self.get_conn()?;
+
+        engine_events::table
+            .filter(engine_events::market_id.eq(market_id))
+            .filter(engine_events::sequence.gt(after_sequence))
+            .order(engine_events::sequence.asc())
+            .load(conn)
+            .context("Failed to fetch engine events")
+    }
+
+    fn get_applied_sequence(&self, market_id: &str) -> Result<i64> {
+        let conn = &mut *self.get_conn()?;
+
+        let applied = engine_checkpoints::table
+            .find(market_id)
+            .select(engine_checkpoints::applied_sequence)
+            .first(conn)
+            .optional()
+            .context("Failed to fetch engine checkpoint")?;
+
+        Ok(applied.unwrap_or(0))
+    }
+
+    fn get_settlement_dead_letters(&self, market_id: &str) -> Result<Vec<SettlementDeadLetter>> {
+        let conn = &mut *self.get_conn()?;
+
+        settlement_dead_letters::table
+            .filter(settlement_dead_letters::market_id.eq(market_id))
+            .order(settlement_dead_letters::id.asc())
+            .load(conn)
+            .context("Failed to fetch settlement dead letters")
+    }
+}
+
+impl EngineEventDatabaseWriter for SqliteRepository {
+    fn append_engine_event(&self, event: NewEngineEvent) -> Result<EngineEvent> {
+        let conn = &mut *self.get_conn()?;
+
+        diesel::insert_into(engine_events::table)
+            .values(engine_event_values(&event))
+            .get_result(conn)
+            .context("Failed to append engine event")
+    }
+
+    fn append_engine_events(&self, events: Vec<NewEngineEvent>) -> Result<Vec<EngineEvent>> {
+        let conn = &mut *self.get_conn()?;
+
+        let mut appended: Vec<EngineEvent> = diesel::insert_into(engine_events::table)
+            .values(events.iter().map(engine_event_values).collect::<Vec<_>>())
+            .get_results(conn)
+            .context("Failed to append engine events")?;
+        appended.sort_by_key(|event| event.sequence);
+        Ok(appended)
+    }
+
+    fn set_applied_sequence(&self, market_id: &str, sequence: i64) -> Result<()> {
+        let conn = &mut *self.get_conn()?;
+        store_applied_sequence(conn, market_id, sequence)
+    }
+
+    fn store_settlement_dead_letter(
+        &self,
+        letter: NewSettlementDeadLetter,
+    ) -> Result<SettlementDeadLetter> {
+        let conn = &mut *self.get_conn()?;
+
+        diesel::insert_into(settlement_dead_letters::table)
+            .values((
+                settlement_dead_letters::market_id.eq(&letter.market_id),
+                settlement_dead_letters::sequence.eq(letter.sequence),
+                settlement_dead_letters::fills.eq(&letter.fills),
+                settlement_dead_letters::error.eq(&letter.error),
+                settlement_dead_letters::create_time.eq(letter.create_time),
+            ))
+            .get_result(conn)
+            .context("Failed to store settlement dead letter")
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn engine_event_values(
+    event: &NewEngineEvent,
+) -> (
+    diesel::dsl::Eq<engine_events::market_id, &String>,
+    diesel::dsl::Eq<engine_events::event_type, &String>,
+    diesel::dsl::Eq<engine_events::order_id, &Option<String>>,
+    diesel::dsl::Eq<engine_events::payload, &String>,
+    diesel::dsl::Eq<engine_events::create_time, i64>,
+) {
+    (
+        engine_events::market_id.eq(&event.market_id),
+        engine_events::event_type.eq(&event.event_type),
+        engine_events::order_id.eq(&event.order_id),
+        engine_events::payload.eq(&event.payload),
+        engine_events::create_time.eq(event.create_time),
+    )
+}
+
+/// Moves the checkpoint of `market_id` to `sequence` on `conn`, so it can be part of the
+/// transaction applying the entries up to it
+pub(super) fn store_applied_sequence(
+    conn: &mut SqliteConnection,
+    market_id: &str,
+    sequence: i64,
+) -> Result<()> {
+    let now = get_utc_now_millis();
+    diesel::insert_into(engine_checkpoints::table)
+        .values((
+            engine_checkpoints::market_id.eq(market_id),
+            engine_checkpoints::applied_sequence.eq(sequence),
+            engine_checkpoints::update_time.eq(now),
+        ))
+        .on_conflict(engine_checkpoints::market_id)
+        .do_update()
+        .set((
+            engine_checkpoints::applied_sequence.eq(sequence),
+            engine_checkpoints::update_time.eq(now),
+        ))
+        .execute(conn)
+        .context("Failed to store engine checkpoint")?;
+
+    Ok(())
+}
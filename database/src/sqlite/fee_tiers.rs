@@ -0,0 +1,68 @@
+use super::SqliteRepository;
+use super::schema::*;
+use super::types::dec;
+use crate::models::models::*;
+use crate::provider::{FeeTierDatabaseReader, FeeTierDatabaseWriter};
+use anyhow::{Context, Result};
+use bigdecimal::BigDecimal;
+use common::utils::get_utc_now_millis;
+use diesel::prelude::*;
+
+impl FeeTierDatabaseReader for SqliteRepository {
+    fn get_fee_tiers(&self, market_id: &str) -> Result<Vec<FeeTier>> {
+        let conn = &mut *self.get_conn()?;
+
+        let mut tiers: Vec<FeeTier> = fee_tiers::table
+            .filter(fee_tiers::market_id.eq(market_id))
+            .load(conn)
+            .context("Failed to fetch fee tiers")?;
+        tiers.sort_by(|a, b| a.min_volume.cmp(&b.min_volume));
+        Ok(tiers)
+    }
+}
+
+impl FeeTierDatabaseWriter for SqliteRepository {
+    fn set_fee_tier(
+        &self,
+        market_id: &str,
+        min_volume: BigDecimal,
+        maker_fee: BigDecimal,
+        taker_fee: BigDecimal,
+    ) -> Result<FeeTier> {
+        let conn = &mut *self.get_conn()?;
+
+        let now = get_utc_now_millis();
+        diesel::insert_into(fee_tiers::table)
+            .values((
+                fee_tiers::market_id.eq(market_id),
+                fee_tiers::min_volume.eq(dec(&min_volume)),
+                fee_tiers::maker_fee.eq(dec(&maker_fee)),
+                fee_tiers::taker_fee.eq(dec(&taker_fee)),
+                fee_tiers::create_time.eq(now),
+                fee_tiers::update_time.eq(now),
+            ))
+            .on_conflict((fee_tiers::market_id, fee_tiers::min_volume))
+            .do_update()
+            .set((
+                fee_tiers::maker_fee.eq(dec(&maker_fee)),
+                fee_tiers::taker_fee.eq(dec(&taker_fee)),
+                fee_tiers::update_time.eq(now),
+            ))
+            .get_result(conn)
+            .context("Failed to store fee tier")
+    }
+
+    fn delete_fee_tier(&self, market_id: &str, min_volume: &BigDecimal) -> Result<bool> {
+        let conn = &mut *self.get_conn()?;
+
+        let deleted = diesel::delete(
+            fee_tiers::table
+                .filter(fee_tiers::market_id.eq(market_id))
+                .filter(fee_tiers::min_volume.eq(dec(min_volume))),
+        )
+        .execute(conn)
+        .context("Failed to delete fee tier")?;
+
+        Ok(deleted > 0)
+    }
+}
@@ -0,0 +1,130 @@
+use super::SqliteRepository;
+use super::schema::*;
+use super::types::dec;
+use crate::models::models::*;
+use crate::provider::{FeeTreasuryDatabaseReader, FeeTreasuryDatabaseWriter, LedgerDatabaseWriter};
+use crate::repository::TreasuryError;
+use anyhow::{Context, Result};
+use bigdecimal::BigDecimal;
+use common::utils::{get_utc_now_millis, get_uuid_string};
+use diesel::prelude::*;
+
+impl FeeTreasuryDatabaseReader for SqliteRepository {
+    fn get_fee_treasury(&self, market_id: &str, asset: &str) -> Result<Option<FeeTreasury>> {
+        let conn = &mut *self.get_conn()?;
+
+        let result = fee_treasury::table
+            .find((market_id, asset))
+            .first(conn)
+            .optional()?;
+
+        Ok(result)
+    }
+
+    fn list_fee_treasuries(&self, market_id: Option<&str>) -> Result<Vec<FeeTreasury>> {
+        let conn = &mut *self.get_conn()?;
+
+        let mut query = fee_treasury::table.into_boxed();
+        if let Some(market_id) = market_id {
+            query = query.filter(fee_treasury::market_id.eq(market_id));
+        }
+        let result = query
+            .order((fee_treasury::market_id.asc(), fee_treasury::asset.asc()))
+            .load(conn)?;
+
+        Ok(result)
+    }
+}
+
+impl FeeTreasuryDatabaseWriter for SqliteRepository {
+    fn create_fee_treasury(&self, fee_treasury_data: NewFeeTreasury) -> Result<FeeTreasury> {
+        let conn = &mut *self.get_conn()?;
+
+        let result = diesel::insert_into(fee_treasury::table)
+            .values((
+                fee_treasury::market_id.eq(&fee_treasury_data.market_id),
+                fee_treasury::asset.eq(&fee_treasury_data.asset),
+                fee_treasury::treasury_address.eq(&fee_treasury_data.treasury_address),
+                fee_treasury::collected_amount.eq(dec(&fee_treasury_data.collected_amount)),
+                fee_treasury::last_update_time.eq(fee_treasury_data.last_update_time),
+            ))
+            .get_result(conn)?;
+
+        Ok(result)
+    }
+
+    fn withdraw_from_fee_treasury(
+        &self,
+        market_id: &str,
+        asset: &str,
+        amount: BigDecimal,
+        destination: &str,
+    ) -> Result<FeeTreasuryWithdrawal> {
+        let conn = &mut *self.get_conn()?;
+        conn.transaction(|conn| {
+            let treasury = fee_treasury::table
+                .find((market_id, asset))
+                .first::<FeeTreasury>(conn)
+                .optional()
+                .context("Failed to fetch fee treasury")?
+                .ok_or_else(|| TreasuryError::NotFound {
+                    market_id: market_id.to_string(),
+                    asset: asset.to_string(),
+                })?;
+            if treasury.collected_amount < amount {
+                return Err(TreasuryError::InsufficientFees {
+                    asset: asset.to_string(),
+                    collected: treasury.collected_amount,
+                }
+                .into());
+            }
+
+            let now = get_utc_now_millis();
+            diesel::update(fee_treasury::table.find((market_id, asset)))
+                .set((
+                    fee_treasury::collected_amount.eq(dec(&(&treasury.collected_amount - &amount))),
+                    fee_treasury::total_withdrawn.eq(dec(&(&treasury.total_withdrawn + &amount))),
+                    fee_treasury::last_update_time.eq(now),
+                ))
+                .execute(conn)
+                .context("Failed to debit fee treasury")?;
+
+            let withdrawal = diesel::insert_into(fee_treasury_withdrawals::table)
+                .values((
+                    fee_treasury_withdrawals::id.eq(get_uuid_string()),
+                    fee_treasury_withdrawals::market_id.eq(market_id),
+                    fee_treasury_withdrawals::asset.eq(asset),
+                    fee_treasury_withdrawals::amount.eq(dec(&amount)),
+                    fee_treasury_withdrawals::destination.eq(destination),
+                    fee_treasury_withdrawals::create_time.eq(now),
+                ))
+                .get_result::<FeeTreasuryWithdrawal>(conn)
+                .context("Failed to record fee treasury withdrawal")?;
+
+            conn.record_ledger_transfers(
+                Some(&withdrawal.id),
+                &[LedgerTransfer::new(
+                    LedgerEntryKind::Withdrawal,
+                    asset,
+                    (market_id, LedgerAccount::FeeTreasury),
+                    (market_id, LedgerAccount::External),
+                    amount,
+                )],
+            )?;
+            Ok(withdrawal)
+        })
+    }
+
+    fn transfer_to_fee_treasury(&self, fee_amount: BigDecimal) -> Result<FeeTreasury> {
+        let conn = &mut *self.get_conn()?;
+
+        let result = diesel::update(fee_treasury::table)
+            .set((
+                fee_treasury::collected_amount.eq(dec(&fee_amount)),
+                fee_treasury::last_update_time.eq(get_utc_now_millis()),
+            ))
+            .get_result(conn)?;
+
+        Ok(result)
+    }
+}
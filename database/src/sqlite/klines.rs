@@ -0,0 +1,83 @@
+use super::SqliteRepository;
+use super::schema::*;
+use super::types::dec;
+use crate::models::models::*;
+use crate::provider::KlineDatabaseReader;
+use crate::repository::fold_kline_trades;
+use anyhow::{Context, Result};
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+
+impl KlineDatabaseReader for SqliteRepository {
+    fn list_klines(
+        &self,
+        market_id: &str,
+        interval: KlineInterval,
+        start_time: Option<i64>,
+        end_time: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<Kline>> {
+        let conn = &mut *self.get_conn()?;
+
+        let mut query = klines::table
+            .filter(klines::market_id.eq(market_id))
+            .filter(klines::interval.eq(interval.as_str()))
+            .into_boxed();
+        if let Some(start_time) = start_time {
+            query = query.filter(klines::open_time.ge(start_time));
+        }
+        if let Some(end_time) = end_time {
+            query = query.filter(klines::open_time.le(end_time));
+        }
+
+        let klines = query
+            .order(klines::open_time.asc())
+            .limit(limit)
+            .load(conn)
+            .context("Failed to list klines")?;
+        Ok(klines)
+    }
+}
+
+/// Folds `trades`, in the order they settled, into their candles of every interval. Runs on
+/// the caller's connection so the candles commit with the trades.
+pub(super) fn record_kline_trades(conn: &mut SqliteConnection, trades: &[NewTrade]) -> Result<()> {
+    for candle in fold_kline_trades(trades) {
+        let stored = klines::table
+            .find((&candle.market_id, &candle.interval, candle.open_time))
+            .first::<Kline>(conn)
+            .optional()
+            .context("Failed to fetch kline")?;
+
+        // Trades of a market settle in order, so the latest ones close the candle
+        let candle = match stored {
+            Some(stored) => Kline {
+                open: stored.open,
+                high: stored.high.max(candle.high),
+                low: stored.low.min(candle.low),
+                volume: stored.volume + candle.volume,
+                quote_volume: stored.quote_volume + candle.quote_volume,
+                trade_count: stored.trade_count + candle.trade_count,
+                ..candle
+            },
+            None => candle,
+        };
+
+        diesel::replace_into(klines::table)
+            .values((
+                klines::market_id.eq(&candle.market_id),
+                klines::interval.eq(&candle.interval),
+                klines::open_time.eq(candle.open_time),
+                klines::open.eq(dec(&candle.open)),
+                klines::high.eq(dec(&candle.high)),
+                klines::low.eq(dec(&candle.low)),
+                klines::close.eq(dec(&candle.close)),
+                klines::volume.eq(dec(&candle.volume)),
+                klines::quote_volume.eq(dec(&candle.quote_volume)),
+                klines::trade_count.eq(candle.trade_count),
+            ))
+            .execute(conn)
+            .context("Failed to update klines")?;
+    }
+    Ok(())
+}
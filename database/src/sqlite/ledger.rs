@@ -0,0 +1,117 @@
+use super::SqliteRepository;
+use super::schema::*;
+use super::types::dec;
+use crate::filters::LedgerFilter;
+use crate::models::models::*;
+use crate::provider::{LedgerDatabaseReader, LedgerDatabaseWriter};
+use crate::repository::ledger_entries;
+use anyhow::{Context, Result};
+use common::db::pagination::{Paginated, Pagination};
+use diesel::prelude::*;
+use diesel::sqlite::{Sqlite, SqliteConnection};
+
+fn filtered_entries(filter: LedgerFilter) -> ledger_entries::BoxedQuery<'static, Sqlite> {
+    let mut query = ledger_entries::table.into_boxed();
+
+    if let Some(owner_id) = filter.owner_id {
+        query = query.filter(ledger_entries::owner_id.eq(owner_id));
+    }
+    if let Some(asset) = filter.asset {
+        query = query.filter(ledger_entries::asset.eq(asset));
+    }
+    if let Some(kind) = filter.kind {
+        query = query.filter(ledger_entries::kind.eq(kind));
+    }
+    if let Some(reference_id) = filter.reference_id {
+        query = query.filter(ledger_entries::reference_id.eq(reference_id));
+    }
+    if let Some(start_time) = filter.start_time {
+        query = query.filter(ledger_entries::create_time.ge(start_time));
+    }
+    if let Some(end_time) = filter.end_time {
+        query = query.filter(ledger_entries::create_time.le(end_time));
+    }
+
+    query
+}
+
+impl LedgerDatabaseReader for SqliteRepository {
+    fn list_ledger_entries(
+        &self,
+        filter: LedgerFilter,
+        pagination: Option<Pagination>,
+    ) -> Result<Paginated<LedgerEntry>> {
+        let conn = &mut *self.get_conn()?;
+        let pagination = pagination.unwrap_or_default();
+        let limit = pagination.limit.unwrap_or(10).min(100);
+        let offset = pagination.offset.unwrap_or(0);
+
+        let total_count: i64 = filtered_entries(filter.clone())
+            .select(diesel::dsl::count_star())
+            .first(conn)
+            .context("Failed to count ledger entries")?;
+
+        let entries = filtered_entries(filter)
+            .order(ledger_entries::id.desc())
+            .limit(limit)
+            .offset(offset)
+            .load::<LedgerEntry>(conn)
+            .context("Failed to fetch ledger entries")?;
+
+        let has_more = offset + (entries.len() as i64) < total_count;
+        let next_offset = if has_more {
+            Some(offset + entries.len() as i64)
+        } else {
+            None
+        };
+
+        Ok(Paginated {
+            items: entries,
+            total_count,
+            next_offset,
+            has_more,
+            next_cursor: None,
+        })
+    }
+}
+
+impl LedgerDatabaseWriter for SqliteConnection {
+    fn record_ledger_transfers(
+        &mut self,
+        reference_id: Option<&str>,
+        transfers: &[LedgerTransfer],
+    ) -> Result<Vec<LedgerEntry>> {
+        self.record_ledger_transactions(&[(reference_id, transfers)])
+    }
+
+    fn record_ledger_transactions(
+        &mut self,
+        transactions: &[(Option<&str>, &[LedgerTransfer])],
+    ) -> Result<Vec<LedgerEntry>> {
+        let entries = ledger_entries(transactions);
+        if entries.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let values: Vec<_> = entries
+            .iter()
+            .map(|entry| {
+                (
+                    ledger_entries::transaction_id.eq(&entry.transaction_id),
+                    ledger_entries::kind.eq(&entry.kind),
+                    ledger_entries::owner_id.eq(&entry.owner_id),
+                    ledger_entries::account.eq(&entry.account),
+                    ledger_entries::asset.eq(&entry.asset),
+                    ledger_entries::debit.eq(dec(&entry.debit)),
+                    ledger_entries::credit.eq(dec(&entry.credit)),
+                    ledger_entries::reference_id.eq(&entry.reference_id),
+                    ledger_entries::create_time.eq(entry.create_time),
+                )
+            })
+            .collect();
+        diesel::insert_into(ledger_entries::table)
+            .values(values)
+            .get_results(self)
+            .context("Failed to record ledger entries")
+    }
+}
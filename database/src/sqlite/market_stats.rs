@@ -0,0 +1,169 @@
+use super::SqliteRepository;
+use super::schema::*;
+use super::types::{dec, dec_opt};
+use crate::models::models::*;
+use crate::provider::{MarketStatDatabaseReader, MarketStatDatabaseWriter};
+use anyhow::{Context, Result};
+use bigdecimal::BigDecimal;
+use common::utils;
+use diesel::prelude::*;
+
+/// Window the high, low, volume and price change of market stats cover
+const MARKET_STATS_WINDOW_SECS: i64 = 24 * 60 * 60;
+
+impl MarketStatDatabaseReader for SqliteRepository {
+    fn get_market_stats(&self, market_id: &str) -> Result<Option<MarketStat>> {
+        let conn = &mut *self.get_conn()?;
+
+        let result = market_stats::table.find(market_id).first(conn).optional()?;
+
+        Ok(result)
+    }
+
+    fn list_tickers(&self) -> Result<Vec<Ticker>> {
+        let conn = &mut *self.get_conn()?;
+
+        let rows = markets::table
+            .left_join(market_stats::table)
+            .left_join(market_quotes::table)
+            .select((
+                markets::id,
+                market_stats::all_columns.nullable(),
+                market_quotes::all_columns.nullable(),
+            ))
+            .order(markets::id.asc())
+            .load::<(String, Option<MarketStat>, Option<MarketQuote>)>(conn)
+            .context("Failed to list tickers")?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(market_id, stats, quote)| Ticker {
+                market_id,
+                stats,
+                quote,
+            })
+            .collect())
+    }
+}
+
+impl MarketStatDatabaseWriter for SqliteRepository {
+    fn upsert_market_quote(
+        &self,
+        market_id: &str,
+        best_bid: Option<BigDecimal>,
+        best_ask: Option<BigDecimal>,
+    ) -> Result<MarketQuote> {
+        let conn = &mut *self.get_conn()?;
+
+        let update_time = utils::get_utc_now_millis();
+        let result = diesel::insert_into(market_quotes::table)
+            .values((
+                market_quotes::market_id.eq(market_id),
+                market_quotes::best_bid.eq(dec_opt(&best_bid)),
+                market_quotes::best_ask.eq(dec_opt(&best_ask)),
+                market_quotes::update_time.eq(update_time),
+            ))
+            .on_conflict(market_quotes::market_id)
+            .do_update()
+            .set((
+                market_quotes::best_bid.eq(dec_opt(&best_bid)),
+                market_quotes::best_ask.eq(dec_opt(&best_ask)),
+                market_quotes::update_time.eq(update_time),
+            ))
+            .get_result(conn)
+            .context("Failed to store market quote")?;
+
+        Ok(result)
+    }
+
+    fn refresh_market_stats(&self, market_id: &str, now: i64) -> Result<Option<MarketStat>> {
+        let window_start = now - MARKET_STATS_WINDOW_SECS;
+        // Prices are text here, so the trades up to `now` are read oldest first and folded
+        let prices = {
+            let conn = &mut *self.get_conn()?;
+            trades::table
+                .filter(trades::market_id.eq(market_id))
+                .filter(trades::timestamp.le(now))
+                .order((trades::timestamp.asc(), trades::id.asc()))
+                .select((trades::timestamp, trades::price, trades::base_amount))
+                .load::<(i64, BigDecimal, BigDecimal)>(conn)?
+        };
+        let Some((_, last_price, _)) = prices.last() else {
+            return Ok(None);
+        };
+        let last_price = last_price.clone();
+
+        let (before, in_window): (Vec<_>, Vec<_>) = prices
+            .iter()
+            .partition(|(timestamp, _, _)| *timestamp <= window_start);
+        let high = in_window.iter().map(|(_, price, _)| price).max();
+        let low = in_window.iter().map(|(_, price, _)| price).min();
+        let volume: BigDecimal = in_window.iter().map(|(_, _, amount)| amount).sum();
+
+        // The price the window opened at: the last trade before it, or else its first one.
+        // Without trades in the window the market sat at its last price the whole time.
+        let opening_price = before
+            .last()
+            .or(in_window.first())
+            .map_or(&last_price, |(_, price, _)| price);
+        let stats = self.upsert_market_stats(
+            market_id,
+            high.unwrap_or(&last_price).clone(),
+            low.unwrap_or(&last_price).clone(),
+            volume,
+            &last_price - opening_price,
+            last_price.clone(),
+        )?;
+        Ok(Some(stats))
+    }
+
+    fn upsert_market_stats(
+        &self,
+        market_id: &str,
+        high_24h: BigDecimal,
+        low_24h: BigDecimal,
+        volume_24h: BigDecimal,
+        price_change_24h: BigDecimal,
+        last_price: BigDecimal,
+    ) -> Result<MarketStat> {
+        let conn = &mut *self.get_conn()?;
+
+        let current_time = utils::get_utc_now_millis();
+
+        // Store stats at the market's precision so they compare across markets
+        let market = markets::table
+            .find(market_id)
+            .first::<Market>(conn)
+            .context("Failed to fetch market")?;
+        let price_precision = market.price_precision;
+        let high_24h = utils::round_to_precision(&high_24h, price_precision);
+        let low_24h = utils::round_to_precision(&low_24h, price_precision);
+        let price_change_24h = utils::round_to_precision(&price_change_24h, price_precision);
+        let last_price = utils::round_to_precision(&last_price, price_precision);
+        let volume_24h = utils::round_to_precision(&volume_24h, market.amount_precision);
+
+        let result = diesel::insert_into(market_stats::table)
+            .values((
+                market_stats::market_id.eq(market_id),
+                market_stats::high_24h.eq(dec(&high_24h)),
+                market_stats::low_24h.eq(dec(&low_24h)),
+                market_stats::volume_24h.eq(dec(&volume_24h)),
+                market_stats::price_change_24h.eq(dec(&price_change_24h)),
+                market_stats::last_price.eq(dec(&last_price)),
+                market_stats::last_update_time.eq(current_time),
+            ))
+            .on_conflict(market_stats::market_id)
+            .do_update()
+            .set((
+                market_stats::high_24h.eq(dec(&high_24h)),
+                market_stats::low_24h.eq(dec(&low_24h)),
+                market_stats::volume_24h.eq(dec(&volume_24h)),
+                market_stats::price_change_24h.eq(dec(&price_change_24h)),
+                market_stats::last_price.eq(dec(&last_price)),
+                market_stats::last_update_time.eq(current_time),
+            ))
+            .get_result(conn)?;
+
+        Ok(result)
+    }
+}
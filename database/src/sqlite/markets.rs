@@ -0,0 +1,117 @@
+use super::SqliteRepository;
+use super::schema::*;
+use super::types::dec;
+use crate::models::models::*;
+use crate::provider::{MarketDatabaseReader, MarketDatabaseWriter};
+use anyhow::{Context, Result};
+use common::error::BitradeError;
+use common::utils::get_utc_now_millis;
+use diesel::prelude::*;
+
+impl MarketDatabaseReader for SqliteRepository {
+    fn get_market(&self, market_id: &str) -> Result<Option<Market>> {
+        let conn = &mut *self.get_conn()?;
+
+        let result = markets::table.find(market_id).first(conn).optional()?;
+
+        Ok(result)
+    }
+
+    fn list_markets(&self) -> Result<Vec<Market>> {
+        let conn = &mut *self.get_conn()?;
+
+        let result = markets::table.load(conn)?;
+
+        Ok(result)
+    }
+}
+
+impl MarketDatabaseWriter for SqliteRepository {
+    /// Creates the market, or returns the existing one if a market with the same id was
+    /// already created.
+    fn create_market(&self, market_data: NewMarket) -> Result<Market> {
+        let conn = &mut *self.get_conn()?;
+        let inserted = diesel::insert_into(markets::table)
+            .values((
+                markets::id.eq(&market_data.id),
+                markets::base_asset.eq(&market_data.base_asset),
+                markets::quote_asset.eq(&market_data.quote_asset),
+                markets::default_maker_fee.eq(dec(&market_data.default_maker_fee)),
+                markets::default_taker_fee.eq(dec(&market_data.default_taker_fee)),
+                markets::create_time.eq(market_data.create_time),
+                markets::update_time.eq(market_data.update_time),
+                markets::status.eq(&market_data.status),
+                markets::min_base_amount.eq(dec(&market_data.min_base_amount)),
+                markets::min_quote_amount.eq(dec(&market_data.min_quote_amount)),
+                markets::price_precision.eq(market_data.price_precision),
+                markets::amount_precision.eq(market_data.amount_precision),
+            ))
+            .on_conflict(markets::id)
+            .do_nothing()
+            .get_result::<Market>(conn)
+            .optional()?;
+
+        match inserted {
+            Some(market) => Ok(market),
+            None => markets::table
+                .find(&market_data.id)
+                .first::<Market>(conn)
+                .context("Failed to fetch existing market"),
+        }
+    }
+
+    fn update_market_status(&self, market_id: &str, status: MarketStatus) -> Result<Market> {
+        let conn = &mut *self.get_conn()?;
+
+        diesel::update(markets::table.find(market_id))
+            .set((
+                markets::status.eq(status.as_str()),
+                markets::update_time.eq(get_utc_now_millis()),
+            ))
+            .get_result(conn)
+            .optional()
+            .context("Failed to update market status")?
+            .ok_or_else(|| BitradeError::MarketNotFound(market_id.to_string()).into())
+    }
+
+    fn update_market(&self, market_id: &str, changes: MarketUpdate) -> Result<Market> {
+        let conn = &mut *self.get_conn()?;
+
+        conn.transaction(|conn| {
+            let market = markets::table
+                .find(market_id)
+                .first::<Market>(conn)
+                .optional()
+                .context("Failed to fetch market")?
+                .ok_or_else(|| BitradeError::MarketNotFound(market_id.to_string()))?;
+
+            // The changeset of `MarketUpdate` is bound to Postgres, so it is merged here
+            diesel::update(markets::table.find(market_id))
+                .set((
+                    markets::default_maker_fee.eq(dec(changes
+                        .default_maker_fee
+                        .as_ref()
+                        .unwrap_or(&market.default_maker_fee))),
+                    markets::default_taker_fee.eq(dec(changes
+                        .default_taker_fee
+                        .as_ref()
+                        .unwrap_or(&market.default_taker_fee))),
+                    markets::min_base_amount.eq(dec(changes
+                        .min_base_amount
+                        .as_ref()
+                        .unwrap_or(&market.min_base_amount))),
+                    markets::min_quote_amount.eq(dec(changes
+                        .min_quote_amount
+                        .as_ref()
+                        .unwrap_or(&market.min_quote_amount))),
+                    markets::price_precision
+                        .eq(changes.price_precision.unwrap_or(market.price_precision)),
+                    markets::amount_precision
+                        .eq(changes.amount_precision.unwrap_or(market.amount_precision)),
+                    markets::update_time.eq(get_utc_now_millis()),
+                ))
+                .get_result(conn)
+                .context("Failed to update market")
+        })
+    }
+}
@@ -0,0 +1,23 @@
+DROP TABLE events;
+DROP TABLE order_book_snapshots;
+DROP TABLE engine_checkpoints;
+DROP TABLE engine_events;
+DROP TABLE transfers;
+DROP TABLE ledger_entries;
+DROP TABLE order_audit;
+DROP TABLE oco_groups;
+DROP TABLE risk_limits;
+DROP TABLE price_bands;
+DROP TABLE user_restrictions;
+DROP TABLE user_fee_overrides;
+DROP TABLE fee_tiers;
+DROP TABLE fee_treasury_withdrawals;
+DROP TABLE fee_treasury;
+DROP TABLE klines;
+DROP TABLE market_quotes;
+DROP TABLE market_stats;
+DROP TABLE trade_balance_snapshots;
+DROP TABLE wallets;
+DROP TABLE trades;
+DROP TABLE orders;
+DROP TABLE markets;
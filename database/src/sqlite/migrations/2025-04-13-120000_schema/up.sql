@@ -0,0 +1,437 @@
+-- The Postgres schema as of its last migration, for SQLite. Decimals are stored as text so
+-- they keep every digit, and their checks compare them as numbers through a cast.
+
+CREATE TABLE markets (
+    id VARCHAR(36) PRIMARY KEY,
+    base_asset VARCHAR(20) NOT NULL,
+    quote_asset VARCHAR(20) NOT NULL,
+    default_maker_fee TEXT NOT NULL,
+    default_taker_fee TEXT NOT NULL,
+    create_time BIGINT NOT NULL,
+    update_time BIGINT NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'ACTIVE',
+    min_base_amount TEXT NOT NULL DEFAULT '0',
+    min_quote_amount TEXT NOT NULL DEFAULT '0',
+    price_precision INTEGER NOT NULL DEFAULT 8,
+    amount_precision INTEGER NOT NULL DEFAULT 8,
+
+    UNIQUE (base_asset, quote_asset),
+    CONSTRAINT positive_maker_fee CHECK (CAST(default_maker_fee AS REAL) >= 0),
+    CONSTRAINT positive_taker_fee CHECK (CAST(default_taker_fee AS REAL) >= 0),
+    CONSTRAINT valid_precision CHECK (price_precision BETWEEN 0 AND 18),
+    CONSTRAINT valid_amount_precision CHECK (amount_precision BETWEEN 0 AND 18),
+    CONSTRAINT valid_status
+        CHECK (status IN ('ACTIVE', 'POST_ONLY', 'HALTED_MATCHING', 'CANCEL_ONLY', 'CLOSED')),
+    CONSTRAINT valid_min_amounts
+        CHECK (CAST(min_base_amount AS REAL) >= 0 AND CAST(min_quote_amount AS REAL) >= 0)
+);
+
+CREATE TABLE orders (
+    id VARCHAR(36) PRIMARY KEY,
+    market_id VARCHAR(36) NOT NULL,
+    user_id VARCHAR(36) NOT NULL,
+    order_type VARCHAR(20) NOT NULL,
+    side VARCHAR(10) NOT NULL,
+    price TEXT NOT NULL,
+    base_amount TEXT NOT NULL,
+    quote_amount TEXT NOT NULL,
+    maker_fee TEXT NOT NULL,
+    taker_fee TEXT NOT NULL,
+    create_time BIGINT NOT NULL,
+    remained_base TEXT NOT NULL,
+    remained_quote TEXT NOT NULL,
+    filled_base TEXT NOT NULL DEFAULT '0',
+    filled_quote TEXT NOT NULL DEFAULT '0',
+    filled_fee TEXT NOT NULL DEFAULT '0',
+    update_time BIGINT NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'OPEN',
+    client_order_id VARCHAR(50),
+    post_only BOOLEAN DEFAULT FALSE,
+    time_in_force VARCHAR(10) DEFAULT 'GTC',
+    expires_at BIGINT DEFAULT NULL,
+    cancel_reason VARCHAR(20),
+    display_amount TEXT DEFAULT NULL,
+    reject_reason VARCHAR(30),
+    priority BIGINT NOT NULL,
+
+    CONSTRAINT fk_market FOREIGN KEY (market_id) REFERENCES markets(id),
+    CONSTRAINT positive_base_amount CHECK (CAST(base_amount AS REAL) > 0),
+    CONSTRAINT non_negative_quote_amount CHECK (CAST(quote_amount AS REAL) >= 0),
+    CONSTRAINT non_negative_remained_base CHECK (CAST(remained_base AS REAL) >= 0),
+    CONSTRAINT non_negative_remained_quote CHECK (CAST(remained_quote AS REAL) >= 0),
+    CONSTRAINT non_negative_price CHECK (CAST(price AS REAL) >= 0),
+    CONSTRAINT non_negative_filled_base CHECK (CAST(filled_base AS REAL) >= 0),
+    CONSTRAINT non_negative_filled_quote CHECK (CAST(filled_quote AS REAL) >= 0),
+    CONSTRAINT non_negative_filled_fee CHECK (CAST(filled_fee AS REAL) >= 0),
+    CONSTRAINT valid_order_type
+        CHECK (order_type IN ('LIMIT', 'MARKET', 'STOP_LIMIT', 'STOP_MARKET')),
+    CONSTRAINT valid_side CHECK (side IN ('BUY', 'SELL')),
+    CONSTRAINT valid_order_status
+        CHECK (status IN ('OPEN', 'FILLED', 'CANCELED', 'REJECTED', 'PARTIALLY_FILLED')),
+    CONSTRAINT valid_time_in_force CHECK (time_in_force IN ('GTC', 'IOC', 'FOK', 'GTD')),
+    CONSTRAINT valid_expires_at CHECK (
+        (time_in_force = 'GTC' AND expires_at IS NULL) OR
+        (time_in_force IN ('IOC', 'FOK', 'GTD') AND expires_at IS NOT NULL)
+    ),
+    CONSTRAINT valid_display_amount CHECK (display_amount IS NULL OR CAST(display_amount AS REAL) > 0)
+);
+
+CREATE INDEX idx_open_orders ON orders(market_id, side, price) WHERE status = 'OPEN';
+CREATE INDEX idx_user_orders ON orders(user_id, create_time);
+CREATE UNIQUE INDEX idx_user_client_order_id ON orders(user_id, client_order_id)
+    WHERE client_order_id IS NOT NULL AND status <> 'REJECTED';
+CREATE INDEX idx_orders_expires_at ON orders(market_id, expires_at)
+    WHERE expires_at IS NOT NULL AND status IN ('OPEN', 'PARTIALLY_FILLED');
+CREATE INDEX idx_orders_cursor ON orders(create_time, id);
+
+CREATE TABLE trades (
+    id VARCHAR(36) PRIMARY KEY,
+    timestamp BIGINT NOT NULL,
+    market_id VARCHAR(36) NOT NULL,
+    price TEXT NOT NULL,
+    base_amount TEXT NOT NULL,
+    quote_amount TEXT NOT NULL,
+    buyer_user_id VARCHAR(36) NOT NULL,
+    buyer_order_id VARCHAR(36) NOT NULL,
+    buyer_fee TEXT NOT NULL,
+    seller_user_id VARCHAR(36) NOT NULL,
+    seller_order_id VARCHAR(36) NOT NULL,
+    seller_fee TEXT NOT NULL,
+    taker_side VARCHAR(10) NOT NULL,
+    is_liquidation BOOLEAN DEFAULT FALSE,
+
+    CONSTRAINT fk_market_trade FOREIGN KEY (market_id) REFERENCES markets(id),
+    CONSTRAINT fk_buyer_order FOREIGN KEY (buyer_order_id) REFERENCES orders(id),
+    CONSTRAINT fk_seller_order FOREIGN KEY (seller_order_id) REFERENCES orders(id),
+    CONSTRAINT positive_trade_price CHECK (CAST(price AS REAL) > 0),
+    CONSTRAINT positive_trade_base_amount CHECK (CAST(base_amount AS REAL) > 0),
+    CONSTRAINT positive_trade_quote_amount CHECK (CAST(quote_amount AS REAL) > 0),
+    CONSTRAINT non_negative_buyer_fee CHECK (CAST(buyer_fee AS REAL) >= 0),
+    CONSTRAINT non_negative_seller_fee CHECK (CAST(seller_fee AS REAL) >= 0),
+    CONSTRAINT valid_taker_side CHECK (taker_side IN ('BUY', 'SELL')),
+    CONSTRAINT different_users CHECK (buyer_user_id != seller_user_id)
+);
+
+CREATE INDEX idx_market_trades ON trades(market_id, timestamp);
+CREATE INDEX idx_buyer_trades ON trades(buyer_user_id, timestamp);
+CREATE INDEX idx_seller_trades ON trades(seller_user_id, timestamp);
+CREATE INDEX idx_buyer_order_trades ON trades(buyer_order_id);
+CREATE INDEX idx_seller_order_trades ON trades(seller_order_id);
+CREATE INDEX idx_trades_cursor ON trades(timestamp, id);
+
+CREATE TABLE wallets (
+    user_id VARCHAR(36) NOT NULL,
+    asset VARCHAR(20) NOT NULL,
+    available TEXT NOT NULL DEFAULT '0',
+    locked TEXT NOT NULL DEFAULT '0',
+    update_time BIGINT NOT NULL,
+    reserved TEXT NOT NULL DEFAULT '0',
+    total_deposited TEXT NOT NULL DEFAULT '0',
+    total_withdrawn TEXT NOT NULL DEFAULT '0',
+
+    PRIMARY KEY (user_id, asset),
+    CONSTRAINT non_negative_available CHECK (CAST(available AS REAL) >= 0),
+    CONSTRAINT non_negative_locked CHECK (CAST(locked AS REAL) >= 0),
+    CONSTRAINT non_negative_reserved CHECK (CAST(reserved AS REAL) >= 0),
+    CONSTRAINT non_negative_deposited CHECK (CAST(total_deposited AS REAL) >= 0),
+    CONSTRAINT non_negative_withdrawn CHECK (CAST(total_withdrawn AS REAL) >= 0)
+);
+
+CREATE TABLE trade_balance_snapshots (
+    trade_id VARCHAR(36) NOT NULL,
+    user_id VARCHAR(36) NOT NULL,
+    asset VARCHAR(20) NOT NULL,
+    available_before TEXT NOT NULL,
+    locked_before TEXT NOT NULL,
+    available_after TEXT NOT NULL,
+    locked_after TEXT NOT NULL,
+
+    PRIMARY KEY (trade_id, user_id, asset),
+    CONSTRAINT fk_snapshot_trade FOREIGN KEY (trade_id) REFERENCES trades(id)
+);
+
+CREATE TABLE market_stats (
+    market_id VARCHAR(36) PRIMARY KEY,
+    high_24h TEXT NOT NULL DEFAULT '0',
+    low_24h TEXT NOT NULL DEFAULT '0',
+    volume_24h TEXT NOT NULL DEFAULT '0',
+    price_change_24h TEXT NOT NULL DEFAULT '0',
+    last_price TEXT NOT NULL DEFAULT '0',
+    last_update_time BIGINT NOT NULL,
+
+    CONSTRAINT fk_market_stats FOREIGN KEY (market_id) REFERENCES markets(id)
+);
+
+CREATE TABLE market_quotes (
+    market_id VARCHAR(36) PRIMARY KEY,
+    best_bid TEXT,
+    best_ask TEXT,
+    update_time BIGINT NOT NULL,
+
+    CONSTRAINT fk_quote_market FOREIGN KEY (market_id) REFERENCES markets(id)
+);
+
+CREATE TABLE klines (
+    market_id VARCHAR(36) NOT NULL,
+    interval VARCHAR(3) NOT NULL,
+    open_time BIGINT NOT NULL,
+    open TEXT NOT NULL,
+    high TEXT NOT NULL,
+    low TEXT NOT NULL,
+    close TEXT NOT NULL,
+    volume TEXT NOT NULL,
+    quote_volume TEXT NOT NULL,
+    trade_count BIGINT NOT NULL,
+
+    PRIMARY KEY (market_id, interval, open_time),
+    CONSTRAINT fk_kline_market FOREIGN KEY (market_id) REFERENCES markets(id),
+    CONSTRAINT valid_kline_interval CHECK (interval IN ('1m', '5m', '1h', '1d'))
+);
+
+CREATE TABLE fee_treasury (
+    market_id VARCHAR(36) NOT NULL,
+    asset VARCHAR(20) NOT NULL,
+    treasury_address VARCHAR(100) NOT NULL,
+    collected_amount TEXT NOT NULL DEFAULT '0',
+    last_update_time BIGINT NOT NULL,
+    total_withdrawn TEXT NOT NULL DEFAULT '0',
+
+    PRIMARY KEY (market_id, asset),
+    CONSTRAINT fk_market_treasury FOREIGN KEY (market_id) REFERENCES markets(id),
+    CONSTRAINT non_negative_collected_fees CHECK (CAST(collected_amount AS REAL) >= 0)
+);
+
+CREATE INDEX idx_fee_treasury_address ON fee_treasury(treasury_address);
+
+CREATE TABLE fee_treasury_withdrawals (
+    id VARCHAR(36) PRIMARY KEY,
+    market_id VARCHAR(36) NOT NULL,
+    asset VARCHAR(20) NOT NULL,
+    amount TEXT NOT NULL,
+    destination VARCHAR(128) NOT NULL,
+    create_time BIGINT NOT NULL,
+
+    CONSTRAINT fk_treasury_withdrawal_treasury FOREIGN KEY (market_id, asset)
+        REFERENCES fee_treasury(market_id, asset),
+    CONSTRAINT chk_treasury_withdrawal_amount CHECK (CAST(amount AS REAL) > 0)
+);
+
+CREATE INDEX idx_fee_treasury_withdrawals_treasury
+    ON fee_treasury_withdrawals(market_id, asset, create_time);
+
+CREATE TABLE fee_tiers (
+    market_id VARCHAR(36) NOT NULL,
+    min_volume TEXT NOT NULL,
+    maker_fee TEXT NOT NULL,
+    taker_fee TEXT NOT NULL,
+    create_time BIGINT NOT NULL,
+    update_time BIGINT NOT NULL,
+
+    PRIMARY KEY (market_id, min_volume),
+    CONSTRAINT fk_fee_tier_market FOREIGN KEY (market_id) REFERENCES markets(id),
+    CONSTRAINT chk_fee_tier_min_volume CHECK (CAST(min_volume AS REAL) >= 0),
+    CONSTRAINT chk_fee_tier_maker_fee CHECK (CAST(maker_fee AS REAL) >= 0),
+    CONSTRAINT chk_fee_tier_taker_fee CHECK (CAST(taker_fee AS REAL) >= 0)
+);
+
+CREATE TABLE user_fee_overrides (
+    user_id VARCHAR(36) PRIMARY KEY,
+    maker_fee TEXT NOT NULL,
+    taker_fee TEXT NOT NULL,
+    create_time BIGINT NOT NULL,
+    update_time BIGINT NOT NULL,
+
+    CONSTRAINT chk_user_fee_override_maker_fee CHECK (CAST(maker_fee AS REAL) >= 0),
+    CONSTRAINT chk_user_fee_override_taker_fee CHECK (CAST(taker_fee AS REAL) >= 0)
+);
+
+CREATE TABLE user_restrictions (
+    user_id VARCHAR(36) PRIMARY KEY,
+    status VARCHAR(20) NOT NULL,
+    reason TEXT NOT NULL,
+    update_time BIGINT NOT NULL
+);
+
+CREATE TABLE price_bands (
+    market_id VARCHAR(36) PRIMARY KEY,
+    band_percent TEXT,
+    halt_percent TEXT,
+    halt_window_ms BIGINT NOT NULL,
+    halt_duration_ms BIGINT NOT NULL,
+    create_time BIGINT NOT NULL,
+    update_time BIGINT NOT NULL,
+
+    CONSTRAINT fk_price_band_market FOREIGN KEY (market_id) REFERENCES markets(id),
+    CONSTRAINT chk_price_band_band_percent CHECK (CAST(band_percent AS REAL) > 0),
+    CONSTRAINT chk_price_band_halt_percent CHECK (CAST(halt_percent AS REAL) > 0),
+    CONSTRAINT chk_price_band_halt_window CHECK (halt_window_ms >= 0),
+    CONSTRAINT chk_price_band_halt_duration CHECK (halt_duration_ms >= 0)
+);
+
+CREATE TABLE risk_limits (
+    market_id VARCHAR(36) PRIMARY KEY,
+    max_open_orders INTEGER,
+    max_locked_notional TEXT,
+    create_time BIGINT NOT NULL,
+    update_time BIGINT NOT NULL,
+
+    CONSTRAINT fk_risk_limit_market FOREIGN KEY (market_id) REFERENCES markets(id),
+    CONSTRAINT chk_risk_limit_max_open_orders CHECK (max_open_orders > 0),
+    CONSTRAINT chk_risk_limit_max_locked_notional CHECK (CAST(max_locked_notional AS REAL) > 0)
+);
+
+CREATE TABLE oco_groups (
+    id VARCHAR(36) PRIMARY KEY,
+    market_id VARCHAR(36) NOT NULL,
+    user_id VARCHAR(36) NOT NULL,
+    first_order_id VARCHAR(36) NOT NULL,
+    second_order_id VARCHAR(36) NOT NULL,
+    create_time BIGINT NOT NULL,
+
+    CONSTRAINT fk_market_oco FOREIGN KEY (market_id) REFERENCES markets(id),
+    CONSTRAINT fk_oco_first_order FOREIGN KEY (first_order_id) REFERENCES orders(id),
+    CONSTRAINT fk_oco_second_order FOREIGN KEY (second_order_id) REFERENCES orders(id),
+    CONSTRAINT distinct_oco_legs CHECK (first_order_id <> second_order_id)
+);
+
+CREATE UNIQUE INDEX idx_oco_groups_first_order ON oco_groups(first_order_id);
+CREATE UNIQUE INDEX idx_oco_groups_second_order ON oco_groups(second_order_id);
+CREATE INDEX idx_oco_groups_market_id ON oco_groups(market_id);
+
+CREATE TABLE order_audit (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    action VARCHAR(20) NOT NULL,
+    user_id VARCHAR(36),
+    remote_addr VARCHAR(64),
+    market_id VARCHAR(36) NOT NULL,
+    order_id VARCHAR(36),
+    request TEXT NOT NULL,
+    create_time BIGINT NOT NULL
+);
+
+CREATE INDEX idx_order_audit_user_id ON order_audit(user_id);
+
+CREATE TRIGGER order_audit_no_update BEFORE UPDATE ON order_audit
+BEGIN
+    SELECT RAISE(ABORT, 'order_audit is append-only');
+END;
+
+CREATE TRIGGER order_audit_no_delete BEFORE DELETE ON order_audit
+BEGIN
+    SELECT RAISE(ABORT, 'order_audit is append-only');
+END;
+
+CREATE TABLE ledger_entries (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    transaction_id VARCHAR(36) NOT NULL,
+    kind VARCHAR(20) NOT NULL,
+    owner_id VARCHAR(36) NOT NULL,
+    account VARCHAR(20) NOT NULL,
+    asset VARCHAR(20) NOT NULL,
+    debit TEXT NOT NULL DEFAULT '0',
+    credit TEXT NOT NULL DEFAULT '0',
+    reference_id VARCHAR(36),
+    create_time BIGINT NOT NULL,
+
+    CONSTRAINT chk_ledger_kind
+        CHECK (kind IN ('DEPOSIT', 'WITHDRAWAL', 'LOCK', 'UNLOCK', 'TRADE', 'FEE')),
+    CONSTRAINT chk_ledger_account
+        CHECK (account IN ('AVAILABLE', 'LOCKED', 'RESERVED', 'EXTERNAL', 'FEE_TREASURY')),
+    CONSTRAINT chk_ledger_one_side CHECK (
+        (CAST(debit AS REAL) > 0 AND CAST(credit AS REAL) = 0) OR
+        (CAST(debit AS REAL) = 0 AND CAST(credit AS REAL) > 0)
+    )
+);
+
+CREATE INDEX idx_ledger_entries_owner ON ledger_entries(owner_id, asset, id);
+CREATE INDEX idx_ledger_entries_transaction ON ledger_entries(transaction_id);
+CREATE INDEX idx_ledger_entries_reference ON ledger_entries(reference_id);
+
+CREATE TRIGGER ledger_entries_no_update BEFORE UPDATE ON ledger_entries
+BEGIN
+    SELECT RAISE(ABORT, 'ledger_entries is append-only');
+END;
+
+CREATE TRIGGER ledger_entries_no_delete BEFORE DELETE ON ledger_entries
+BEGIN
+    SELECT RAISE(ABORT, 'ledger_entries is append-only');
+END;
+
+CREATE TABLE transfers (
+    id VARCHAR(36) PRIMARY KEY,
+    user_id VARCHAR(36) NOT NULL,
+    asset VARCHAR(20) NOT NULL,
+    kind VARCHAR(20) NOT NULL,
+    amount TEXT NOT NULL,
+    status VARCHAR(20) NOT NULL,
+    address VARCHAR(128),
+    external_id VARCHAR(128),
+    reject_reason VARCHAR(255),
+    create_time BIGINT NOT NULL,
+    update_time BIGINT NOT NULL,
+
+    CONSTRAINT chk_transfer_kind CHECK (kind IN ('DEPOSIT', 'WITHDRAWAL')),
+    CONSTRAINT chk_transfer_status
+        CHECK (status IN ('PENDING', 'APPROVED', 'COMPLETED', 'REJECTED')),
+    CONSTRAINT chk_transfer_amount CHECK (CAST(amount AS REAL) > 0)
+);
+
+CREATE INDEX idx_transfers_user ON transfers(user_id, create_time);
+CREATE UNIQUE INDEX idx_transfers_external_id ON transfers(kind, asset, external_id);
+
+CREATE TABLE engine_events (
+    sequence INTEGER PRIMARY KEY AUTOINCREMENT,
+    market_id VARCHAR(36) NOT NULL,
+    event_type VARCHAR(20) NOT NULL,
+    order_id VARCHAR(36),
+    payload TEXT NOT NULL,
+    create_time BIGINT NOT NULL,
+
+    CONSTRAINT fk_engine_event_market FOREIGN KEY (market_id) REFERENCES markets(id),
+    CONSTRAINT chk_engine_event_type CHECK (event_type IN (
+        'ORDER_ACCEPTED', 'TRADE_EXECUTED', 'ORDER_CANCELED', 'ORDERS_CANCELED', 'ORDER_AMENDED'
+    ))
+);
+
+CREATE INDEX idx_engine_events_market_sequence ON engine_events(market_id, sequence);
+
+CREATE TRIGGER engine_events_no_update BEFORE UPDATE ON engine_events
+BEGIN
+    SELECT RAISE(ABORT, 'engine_events is append-only');
+END;
+
+CREATE TRIGGER engine_events_no_delete BEFORE DELETE ON engine_events
+BEGIN
+    SELECT RAISE(ABORT, 'engine_events is append-only');
+END;
+
+CREATE TABLE engine_checkpoints (
+    market_id VARCHAR(36) PRIMARY KEY,
+    applied_sequence BIGINT NOT NULL,
+    update_time BIGINT NOT NULL,
+
+    CONSTRAINT fk_engine_checkpoint_market FOREIGN KEY (market_id) REFERENCES markets(id)
+);
+
+CREATE TABLE order_book_snapshots (
+    market_id VARCHAR(36) PRIMARY KEY,
+    applied_sequence BIGINT NOT NULL,
+    payload TEXT NOT NULL,
+    create_time BIGINT NOT NULL,
+
+    CONSTRAINT fk_order_book_snapshot_market FOREIGN KEY (market_id) REFERENCES markets(id)
+);
+
+CREATE TABLE events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    topic VARCHAR(20) NOT NULL,
+    market_id VARCHAR(36) NOT NULL,
+    payload TEXT NOT NULL,
+    create_time BIGINT NOT NULL,
+    published_time BIGINT,
+
+    CONSTRAINT fk_event_market FOREIGN KEY (market_id) REFERENCES markets(id),
+    CONSTRAINT chk_event_topic CHECK (topic IN ('TRADE', 'ORDER', 'WALLET'))
+);
+
+CREATE INDEX idx_events_unpublished ON events(id) WHERE published_time IS NULL;
@@ -0,0 +1,12 @@
+CREATE TABLE settlement_dead_letters (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    market_id VARCHAR(36) NOT NULL,
+    sequence BIGINT NOT NULL,
+    fills TEXT NOT NULL,
+    error TEXT NOT NULL,
+    create_time BIGINT NOT NULL,
+
+    CONSTRAINT fk_settlement_dead_letter_market FOREIGN KEY (market_id) REFERENCES markets(id)
+);
+
+CREATE INDEX idx_settlement_dead_letters_market ON settlement_dead_letters(market_id, id);
@@ -0,0 +1,117 @@
+//! [`SqliteRepository`], the repository on an embedded SQLite database, for tests and tools
+//! that cannot reach a Postgres server.
+
+mod audit;
+mod engine_events;
+mod fee_tiers;
+mod fee_treasury;
+mod klines;
+mod ledger;
+mod market_stats;
+mod markets;
+mod oco_groups;
+mod order_book_snapshots;
+mod orders;
+mod outbox;
+mod price_bands;
+mod reconciliation;
+mod risk_limits;
+pub mod schema;
+mod trades;
+mod transfers;
+pub mod types;
+mod user_fee_overrides;
+mod user_restrictions;
+mod wallets;
+
+use crate::repository::MissingWalletPolicy;
+use anyhow::{Context, Result};
+use diesel::prelude::*;
+use diesel::sql_query;
+use diesel::sqlite::SqliteConnection;
+use diesel_migrations::{EmbeddedMigrations, MigrationHarness, embed_migrations};
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("src/sqlite/migrations");
+
+/// The database provider on one SQLite connection, shared by its clones.
+///
+/// It stores what [`crate::repository::Repository`] does, with the same errors, so tests of
+/// order and settlement logic run without a Postgres server. Calls take turns on the
+/// connection, which suits tests but not a matching engine under load.
+#[derive(Clone)]
+pub struct SqliteRepository {
+    conn: Arc<Mutex<SqliteConnection>>,
+    missing_wallet_policy: MissingWalletPolicy,
+    /// When set, canceling an already canceled order returns it instead of failing
+    idempotent_cancel: bool,
+    /// When set, settlement records the wallet balances it changes before and after a trade
+    balance_snapshots: bool,
+    /// When set, settlement writes the events of every trade to the outbox
+    outbox: bool,
+}
+
+impl SqliteRepository {
+    /// Opens a database of its own in memory, gone once the last clone is dropped.
+    pub fn in_memory() -> Result<Self> {
+        Self::open(":memory:")
+    }
+
+    /// Opens the database file at `path`, creating it when missing, and migrates it.
+    pub fn open(path: &str) -> Result<Self> {
+        let mut conn = SqliteConnection::establish(path)
+            .with_context(|| format!("Failed to open SQLite database {}", path))?;
+        sql_query("PRAGMA foreign_keys = ON").execute(&mut conn)?;
+        conn.run_pending_migrations(MIGRATIONS)
+            .map_err(|e| anyhow::anyhow!("Failed to run migrations: {}", e))?;
+
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+            missing_wallet_policy: MissingWalletPolicy::default(),
+            idempotent_cancel: false,
+            balance_snapshots: false,
+            outbox: false,
+        })
+    }
+
+    pub fn with_missing_wallet_policy(mut self, policy: MissingWalletPolicy) -> Self {
+        self.missing_wallet_policy = policy;
+        self
+    }
+
+    /// Lets clients retry a cancel safely. Filled and rejected orders still refuse to cancel.
+    pub fn with_idempotent_cancel(mut self, enabled: bool) -> Self {
+        self.idempotent_cancel = enabled;
+        self
+    }
+
+    /// Records buyer and seller balances around every trade, for settlement verification.
+    pub fn with_balance_snapshots(mut self, enabled: bool) -> Self {
+        self.balance_snapshots = enabled;
+        self
+    }
+
+    /// Writes trade, order and wallet events to the `events` outbox with every trade.
+    pub fn with_outbox(mut self, enabled: bool) -> Self {
+        self.outbox = enabled;
+        self
+    }
+
+    /// The connection, held until the guard is dropped. Calls holding it must not take it
+    /// again.
+    pub fn get_conn(&self) -> Result<MutexGuard<'_, SqliteConnection>> {
+        Ok(self.conn.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+impl fmt::Debug for SqliteRepository {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SqliteRepository")
+            .field("missing_wallet_policy", &self.missing_wallet_policy)
+            .field("idempotent_cancel", &self.idempotent_cancel)
+            .field("balance_snapshots", &self.balance_snapshots)
+            .field("outbox", &self.outbox)
+            .finish_non_exhaustive()
+    }
+}
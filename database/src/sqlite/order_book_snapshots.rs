@@ -0,0 +1,43 @@
+use super::SqliteRepository;
+use super::schema::*;
+use crate::models::models::*;
+use crate::provider::{OrderBookSnapshotDatabaseReader, OrderBookSnapshotDatabaseWriter};
+use anyhow::{Context, Result};
+use diesel::prelude::*;
+
+impl OrderBookSnapshotDatabaseReader for SqliteRepository {
+    fn get_order_book_snapshot(&self, market_id: &str) -> Result<Option<OrderBookSnapshot>> {
+        let conn = &mut *self.get_conn()?;
+
+        order_book_snapshots::table
+            .find(market_id)
+            .first(conn)
+            .optional()
+            .context("Failed to fetch order book snapshot")
+    }
+}
+
+impl OrderBookSnapshotDatabaseWriter for SqliteRepository {
+    fn store_order_book_snapshot(&self, snapshot: OrderBookSnapshot) -> Result<()> {
+        let conn = &mut *self.get_conn()?;
+
+        diesel::insert_into(order_book_snapshots::table)
+            .values((
+                order_book_snapshots::market_id.eq(&snapshot.market_id),
+                order_book_snapshots::applied_sequence.eq(snapshot.applied_sequence),
+                order_book_snapshots::payload.eq(&snapshot.payload),
+                order_book_snapshots::create_time.eq(snapshot.create_time),
+            ))
+            .on_conflict(order_book_snapshots::market_id)
+            .do_update()
+            .set((
+                order_book_snapshots::applied_sequence.eq(snapshot.applied_sequence),
+                order_book_snapshots::payload.eq(&snapshot.payload),
+                order_book_snapshots::create_time.eq(snapshot.create_time),
+            ))
+            .execute(conn)
+            .context("Failed to store order book snapshot")?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,685 @@
+use super::SqliteRepository;
+use super::oco_groups::cancel_oco_sibling;
+use super::schema::*;
+use super::types::{dec, dec_opt};
+use super::wallets::{lock_funds, record_unlock};
+use crate::filters::OrderFilter;
+use crate::models::models::*;
+use crate::provider::*;
+use crate::repository::PaginationError;
+use anyhow::Context;
+use anyhow::Result;
+use bigdecimal::BigDecimal;
+use common::db::pagination::*;
+use common::error::BitradeError;
+use common::utils;
+use diesel::dsl::count_star;
+use diesel::prelude::*;
+use diesel::sqlite::{Sqlite, SqliteConnection};
+use std::collections::BTreeMap;
+
+fn filtered_orders(filter: OrderFilter) -> orders::BoxedQuery<'static, Sqlite> {
+    let mut query = orders::table.into_boxed();
+
+    if let Some(order_id) = filter.order_id {
+        query = query.filter(orders::id.eq(order_id));
+    }
+    if let Some(market_id) = filter.market_id {
+        query = query.filter(orders::market_id.eq(market_id));
+    }
+    if let Some(user_id) = filter.user_id {
+        query = query.filter(orders::user_id.eq(user_id));
+    }
+    if let Some(status) = filter.status {
+        query = query.filter(orders::status.eq(status));
+    }
+    if let Some(side) = filter.side {
+        query = query.filter(orders::side.eq(side));
+    }
+    if let Some(order_type) = filter.order_type {
+        query = query.filter(orders::order_type.eq(order_type));
+    }
+    if let Some(client_order_id) = filter.client_order_id {
+        query = query.filter(orders::client_order_id.eq(client_order_id));
+    }
+
+    query
+}
+
+/// Inserts `order` as it is, with `reject_reason` when it was refused.
+fn insert_order_row(
+    conn: &mut SqliteConnection,
+    order: &NewOrder,
+    reject_reason: Option<&str>,
+) -> Result<Order> {
+    let result = diesel::insert_into(orders::table)
+        .values((
+            (
+                orders::id.eq(&order.id),
+                orders::market_id.eq(&order.market_id),
+                orders::user_id.eq(&order.user_id),
+                orders::order_type.eq(&order.order_type),
+                orders::side.eq(&order.side),
+                orders::price.eq(dec(&order.price)),
+                orders::base_amount.eq(dec(&order.base_amount)),
+                orders::quote_amount.eq(dec(&order.quote_amount)),
+                orders::maker_fee.eq(dec(&order.maker_fee)),
+                orders::taker_fee.eq(dec(&order.taker_fee)),
+                orders::create_time.eq(order.create_time),
+                orders::remained_base.eq(dec(&order.remained_base)),
+            ),
+            (
+                orders::remained_quote.eq(dec(&order.remained_quote)),
+                orders::filled_base.eq(dec(&order.filled_base)),
+                orders::filled_quote.eq(dec(&order.filled_quote)),
+                orders::filled_fee.eq(dec(&order.filled_fee)),
+                orders::update_time.eq(order.update_time),
+                orders::status.eq(&order.status),
+                orders::client_order_id.eq(&order.client_order_id),
+                orders::post_only.eq(order.post_only),
+                orders::time_in_force.eq(&order.time_in_force),
+                orders::expires_at.eq(order.expires_at),
+                orders::display_amount.eq(dec_opt(&order.display_amount)),
+                orders::priority.eq(order.priority),
+                orders::reject_reason.eq(reject_reason),
+            ),
+        ))
+        .get_result(conn)?;
+    Ok(result)
+}
+
+/// Locks the funds of a new order and inserts it, within the transaction of the caller.
+pub(super) fn insert_order(conn: &mut SqliteConnection, order_data: &NewOrder) -> Result<Order> {
+    let market = markets::table
+        .find(&order_data.market_id)
+        .first::<Market>(conn)
+        .context("Failed to fetch market")?;
+
+    let order_side = OrderSide::from_str(&order_data.side)
+        .map_err(|e| anyhow::anyhow!("Invalid order side: {}", e))?;
+
+    match order_side {
+        // Buys lock the quote they may spend, sells the base they offer
+        OrderSide::Buy => lock_funds(
+            conn,
+            &order_data.user_id,
+            &market.quote_asset,
+            &order_data.quote_amount,
+            Some(&order_data.id),
+        )
+        .context("Failed to update buyer balance")?,
+        OrderSide::Sell => lock_funds(
+            conn,
+            &order_data.user_id,
+            &market.base_asset,
+            &order_data.base_amount,
+            Some(&order_data.id),
+        )
+        .context("Failed to update seller balance")?,
+    };
+
+    insert_order_row(conn, order_data, None).context("Failed to insert order")
+}
+
+/// Cancels an order that is still on the book and unlocks what it has not spent.
+pub(super) fn cancel_open_order(
+    conn: &mut SqliteConnection,
+    order: &Order,
+    reason: CancelReason,
+) -> Result<Order> {
+    let order_side = OrderSide::from_str(&order.side)
+        .map_err(|e| anyhow::anyhow!("Failed to parse order side: {}", e))?;
+
+    let market = markets::table
+        .filter(markets::id.eq(&order.market_id))
+        .first::<Market>(conn)
+        .context("Market not found")?;
+
+    let (asset, unlock_amount) = match order_side {
+        OrderSide::Buy => (market.quote_asset.clone(), order.remained_quote.clone()),
+        OrderSide::Sell => (market.base_asset.clone(), order.remained_base.clone()),
+    };
+
+    let updated_order = diesel::update(orders::table.find(&order.id))
+        .set((
+            orders::status.eq(OrderStatus::Canceled.as_str()),
+            orders::cancel_reason.eq(reason.as_str()),
+            orders::update_time.eq(utils::get_utc_now_millis()),
+        ))
+        .get_result::<Order>(conn)
+        .context("Failed to update order status")?;
+
+    // Unlock the balance, a refund of unspent funds that carries no fee
+    let wallet = wallets::table
+        .find((&order.user_id, &asset))
+        .first::<Wallet>(conn)
+        .optional()
+        .context("Failed to fetch wallet")?;
+    if let Some(wallet) = wallet {
+        diesel::update(wallets::table.find((&order.user_id, &asset)))
+            .set((
+                wallets::available.eq(dec(&(&wallet.available + &unlock_amount))),
+                wallets::locked.eq(dec(&(&wallet.locked - &unlock_amount))),
+            ))
+            .execute(conn)
+            .context("Failed to unlock balance")?;
+    }
+    record_unlock(
+        conn,
+        &order.user_id,
+        &asset,
+        &unlock_amount,
+        Some(&order.id),
+    )?;
+
+    Ok(updated_order)
+}
+
+fn active_orders_of<'a>(query: orders::BoxedQuery<'a, Sqlite>) -> orders::BoxedQuery<'a, Sqlite> {
+    query.filter(orders::status.eq_any([
+        OrderStatus::Open.as_str(),
+        OrderStatus::PartiallyFilled.as_str(),
+    ]))
+}
+
+impl OrderDatabaseReader for SqliteRepository {
+    fn get_order(&self, order_id: &str) -> Result<Option<Order>> {
+        let conn = &mut *self.get_conn()?;
+        let order = orders::table
+            .find(order_id)
+            .first::<Order>(conn)
+            .optional()?
+            .ok_or_else(|| BitradeError::OrderNotFound(order_id.to_string()))?;
+        Ok(Some(order))
+    }
+
+    fn get_order_by_client_id(
+        &self,
+        user_id: &str,
+        client_order_id: &str,
+    ) -> Result<Option<Order>> {
+        let conn = &mut *self.get_conn()?;
+        orders::table
+            .filter(orders::user_id.eq(user_id))
+            .filter(orders::client_order_id.eq(client_order_id))
+            // Rejected orders don't hold the id, any other order is the one that does
+            .order((
+                orders::status.eq(OrderStatus::Rejected.as_str()),
+                orders::create_time.desc(),
+            ))
+            .first::<Order>(conn)
+            .optional()
+            .context("Failed to get order by client order id")
+    }
+
+    fn get_active_orders(&self, market_id: &str) -> Result<Vec<Order>> {
+        let conn = &mut *self.get_conn()?;
+        active_orders_of(orders::table.into_boxed())
+            .filter(orders::market_id.eq(market_id))
+            .order((orders::priority.asc(), orders::create_time.asc()))
+            .load::<Order>(conn)
+            .map_err(|e| anyhow::anyhow!("Failed to get active orders: {}", e))
+    }
+
+    fn get_expired_orders(&self, market_id: &str, now: i64) -> Result<Vec<Order>> {
+        let conn = &mut *self.get_conn()?;
+        active_orders_of(orders::table.into_boxed())
+            .filter(orders::market_id.eq(market_id))
+            .filter(orders::time_in_force.eq(TimeInForce::GTD.as_str()))
+            .filter(orders::expires_at.le(now))
+            .order(orders::expires_at.asc())
+            .load::<Order>(conn)
+            .context("Failed to get expired orders")
+    }
+
+    fn list_orders(
+        &self,
+        filter: OrderFilter,
+        pagination: Option<Pagination>,
+    ) -> Result<Paginated<Order>> {
+        let conn = &mut *self.get_conn()?;
+        let pagination = pagination.unwrap_or_default();
+
+        let limit = pagination.limit.unwrap_or(10);
+        let offset = pagination.offset.unwrap_or(0);
+        let total_count: i64 = filtered_orders(filter.clone())
+            .select(count_star())
+            .first(conn)?;
+        let sort_field = pagination.sort_field("create_time");
+        let descending = pagination.is_descending().ok_or_else(|| {
+            PaginationError::InvalidDirection(
+                pagination.order_direction.clone().unwrap_or_default(),
+            )
+        })?;
+        if !matches!(sort_field, "create_time" | "price" | "filled_base") {
+            return Err(PaginationError::UnsortableField {
+                listing: "orders",
+                field: sort_field.to_string(),
+            }
+            .into());
+        }
+        let by_time = sort_field == "create_time";
+
+        let mut query = filtered_orders(filter);
+        let mut orders = if by_time {
+            // The id breaks ties, so that a page ends between two orders
+            query = if descending {
+                query.order((orders::create_time.desc(), orders::id.desc()))
+            } else {
+                query.order((orders::create_time.asc(), orders::id.asc()))
+            };
+            match &pagination.cursor {
+                Some(cursor) if descending => {
+                    query = query.filter(
+                        orders::create_time
+                            .lt(cursor.timestamp)
+                            .or(orders::create_time
+                                .eq(cursor.timestamp)
+                                .and(orders::id.lt(cursor.id.clone()))),
+                    )
+                }
+                Some(cursor) => {
+                    query = query.filter(
+                        orders::create_time
+                            .gt(cursor.timestamp)
+                            .or(orders::create_time
+                                .eq(cursor.timestamp)
+                                .and(orders::id.gt(cursor.id.clone()))),
+                    )
+                }
+                None => query = query.offset(offset),
+            }
+            query
+                .limit(limit + 1)
+                .load::<Order>(conn)
+                .context("Failed to retrieve orders")?
+        } else {
+            if pagination.cursor.is_some() {
+                return Err(PaginationError::CursorNeedsTimeSort {
+                    listing: "orders",
+                    field: "create_time",
+                }
+                .into());
+            }
+            // Amounts are text here, so the page is cut once they are sorted as numbers
+            let mut orders = query
+                .load::<Order>(conn)
+                .context("Failed to retrieve orders")?;
+            orders.sort_by(|a, b| {
+                let (a_key, b_key) = if sort_field == "price" {
+                    (&a.price, &b.price)
+                } else {
+                    (&a.filled_base, &b.filled_base)
+                };
+                a_key.cmp(b_key).then_with(|| a.id.cmp(&b.id))
+            });
+            if descending {
+                orders.reverse();
+            }
+            orders
+                .into_iter()
+                .skip(offset as usize)
+                .take(limit as usize + 1)
+                .collect()
+        };
+
+        let has_more = orders.len() > limit as usize;
+        if has_more {
+            orders.pop();
+        }
+
+        let next_offset = if has_more && pagination.cursor.is_none() {
+            Some(offset + limit)
+        } else {
+            None
+        };
+        let next_cursor = orders
+            .last()
+            .filter(|_| has_more && by_time)
+            .map(|order| Cursor::new(order.create_time, order.id.clone()));
+
+        Ok(Paginated {
+            items: orders,
+            total_count,
+            next_offset,
+            has_more,
+            next_cursor,
+        })
+    }
+
+    fn get_open_order_stats(&self) -> Result<Vec<OpenOrderStat>> {
+        let conn = &mut *self.get_conn()?;
+        let open_orders = active_orders_of(orders::table.into_boxed())
+            .select((orders::side, orders::remained_base))
+            .load::<(String, BigDecimal)>(conn)
+            .context("Failed to count open orders")?;
+
+        let mut stats: BTreeMap<String, OpenOrderStat> = BTreeMap::new();
+        for (side, remained_base) in open_orders {
+            let stat = stats.entry(side.clone()).or_insert_with(|| OpenOrderStat {
+                side,
+                order_count: 0,
+                remained_base: BigDecimal::from(0),
+            });
+            stat.order_count += 1;
+            stat.remained_base += remained_base;
+        }
+        Ok(stats.into_values().collect())
+    }
+
+    fn get_user_order_counts(
+        &self,
+        user_id: &str,
+        market_id: Option<&str>,
+    ) -> Result<Vec<OrderStatusCount>> {
+        let conn = &mut *self.get_conn()?;
+        let mut query = orders::table
+            .filter(orders::user_id.eq(user_id))
+            .group_by(orders::status)
+            .select((orders::status, count_star()))
+            .order_by(orders::status)
+            .into_boxed();
+        if let Some(market_id) = market_id {
+            query = query.filter(orders::market_id.eq(market_id));
+        }
+
+        Ok(query
+            .load::<(String, i64)>(conn)
+            .context("Failed to count user orders")?
+            .into_iter()
+            .map(|(status, order_count)| OrderStatusCount {
+                status,
+                order_count,
+            })
+            .collect())
+    }
+
+    fn get_book_levels(&self, market_id: &str) -> Result<Vec<BookLevel>> {
+        let conn = &mut *self.get_conn()?;
+        let resting = active_orders_of(orders::table.into_boxed())
+            .filter(orders::market_id.eq(market_id))
+            .filter(orders::order_type.eq(OrderType::Limit.as_str()))
+            .select((
+                orders::side,
+                orders::price,
+                orders::remained_base,
+                orders::display_amount,
+            ))
+            .load::<(String, BigDecimal, BigDecimal, Option<BigDecimal>)>(conn)
+            .context("Failed to sum book levels")?;
+
+        // An iceberg counts with what it shows of itself
+        let mut levels: BTreeMap<(String, BigDecimal), BigDecimal> = BTreeMap::new();
+        for (side, price, remained_base, display_amount) in resting {
+            let shown = match display_amount {
+                Some(display_amount) => display_amount.min(remained_base),
+                None => remained_base,
+            };
+            *levels.entry((side, price)).or_default() += shown;
+        }
+        Ok(levels
+            .into_iter()
+            .map(|((side, price), amount)| BookLevel {
+                side,
+                price,
+                amount,
+            })
+            .collect())
+    }
+
+    fn get_user_active_orders_count(&self, user_id: &str, market_id: &str) -> Result<i64> {
+        let conn = &mut *self.get_conn()?;
+        active_orders_of(orders::table.into_boxed())
+            .filter(orders::user_id.eq(user_id))
+            .filter(orders::market_id.eq(market_id))
+            .select(count_star())
+            .first(conn)
+            .context("Failed to count active user orders")
+    }
+
+    fn get_user_locked_notional(&self, user_id: &str, market_id: &str) -> Result<BigDecimal> {
+        let conn = &mut *self.get_conn()?;
+        let open_orders = active_orders_of(orders::table.into_boxed())
+            .filter(orders::user_id.eq(user_id))
+            .filter(orders::market_id.eq(market_id))
+            .select((
+                orders::side,
+                orders::price,
+                orders::remained_base,
+                orders::remained_quote,
+            ))
+            .load::<(String, BigDecimal, BigDecimal, BigDecimal)>(conn)
+            .context("Failed to fetch active user orders")?;
+
+        Ok(open_orders
+            .into_iter()
+            .map(|(side, price, remained_base, remained_quote)| {
+                if side == OrderSide::Buy.as_str() {
+                    remained_quote
+                } else {
+                    remained_base * price
+                }
+            })
+            .sum())
+    }
+
+    fn get_user_active_orders(&self, user_id: &str) -> Result<Vec<Order>> {
+        let conn = &mut *self.get_conn()?;
+        active_orders_of(orders::table.into_boxed())
+            .filter(orders::user_id.eq(user_id))
+            .order(orders::create_time.asc())
+            .load(conn)
+            .context("Failed to fetch active user orders")
+    }
+}
+
+impl OrderDatabaseWriter for SqliteRepository {
+    fn create_order(&self, order_data: NewOrder) -> Result<Order> {
+        let conn = &mut *self.get_conn()?;
+        conn.transaction(|conn| insert_order(conn, &order_data))
+    }
+
+    fn reject_order(&self, order_data: NewOrder, reason: RejectReason) -> Result<Order> {
+        let conn = &mut *self.get_conn()?;
+        let rejected = NewOrder {
+            remained_base: BigDecimal::from(0),
+            remained_quote: BigDecimal::from(0),
+            status: OrderStatus::Rejected.as_str().to_string(),
+            ..order_data
+        };
+        insert_order_row(conn, &rejected, Some(reason.as_str()))
+            .context("Failed to insert rejected order")
+    }
+
+    fn cancel_order(&self, order_id: &str, reason: CancelReason) -> Result<Order> {
+        let idempotent_cancel = self.idempotent_cancel;
+        let conn = &mut *self.get_conn()?;
+        conn.transaction::<Order, anyhow::Error, _>(|conn| {
+            let order = orders::table
+                .find(order_id)
+                .first::<Order>(conn)
+                .optional()?
+                .ok_or_else(|| BitradeError::OrderNotFound(order_id.to_string()))?;
+
+            let current_status = OrderStatus::from_str(&order.status)
+                .map_err(|e| anyhow::anyhow!("Failed to parse order status: {}", e))?;
+            if idempotent_cancel && current_status == OrderStatus::Canceled {
+                // A retried cancel gets the order back as the first cancel left it
+                return Ok(order);
+            }
+            if matches!(
+                current_status,
+                OrderStatus::Filled | OrderStatus::Canceled | OrderStatus::Rejected
+            ) {
+                return Err(anyhow::anyhow!("Order already in final state"));
+            }
+
+            let updated_order = cancel_open_order(conn, &order, reason)?;
+
+            // Filling or canceling either leg of an OCO group cancels the other
+            cancel_oco_sibling(conn, order_id)?;
+
+            Ok(updated_order)
+        })
+    }
+
+    fn cancel_all_orders(&self, market_id: &str, reason: CancelReason) -> Result<Vec<Order>> {
+        let conn = &mut *self.get_conn()?;
+        conn.transaction::<Vec<Order>, anyhow::Error, _>(|conn| {
+            markets::table
+                .find(market_id)
+                .first::<Market>(conn)
+                .optional()?
+                .ok_or_else(|| BitradeError::MarketNotFound(market_id.to_string()))?;
+
+            let active_orders = active_orders_of(orders::table.into_boxed())
+                .filter(orders::market_id.eq(market_id))
+                .load::<Order>(conn)
+                .context("Failed to fetch active orders")?;
+
+            active_orders
+                .iter()
+                .map(|order| cancel_open_order(conn, order, reason.clone()))
+                .collect()
+        })
+    }
+
+    fn cancel_all_global_orders(&self, reason: CancelReason) -> Result<Vec<Order>> {
+        let conn = &mut *self.get_conn()?;
+        conn.transaction::<Vec<Order>, anyhow::Error, _>(|conn| {
+            let active_orders = active_orders_of(orders::table.into_boxed())
+                .load::<Order>(conn)
+                .context("Failed to fetch active orders")?;
+
+            active_orders
+                .iter()
+                .map(|order| cancel_open_order(conn, order, reason.clone()))
+                .collect()
+        })
+    }
+
+    fn update_order_status(&self, order_id: &str, status: OrderStatus) -> Result<Order> {
+        let conn = &mut *self.get_conn()?;
+        let updated_order = diesel::update(orders::table.find(order_id))
+            .set(orders::status.eq(status.as_str()))
+            .get_result::<Order>(conn)
+            .context("Failed to update order status")?;
+
+        Ok(updated_order)
+    }
+
+    fn amend_order(
+        &self,
+        order_id: &str,
+        price: BigDecimal,
+        remained_base: BigDecimal,
+        priority: i64,
+    ) -> Result<Order> {
+        if price <= 0 || remained_base <= 0 {
+            return Err(anyhow::anyhow!(
+                "Amended price and remaining amount must be greater than 0"
+            ));
+        }
+
+        let conn = &mut *self.get_conn()?;
+        conn.transaction::<Order, anyhow::Error, _>(|conn| {
+            let order = orders::table
+                .find(order_id)
+                .first::<Order>(conn)
+                .optional()?
+                .ok_or_else(|| BitradeError::OrderNotFound(order_id.to_string()))?;
+
+            let status = OrderStatus::from_str(&order.status)
+                .map_err(|e| anyhow::anyhow!("Failed to parse order status: {}", e))?;
+            if !matches!(status, OrderStatus::Open | OrderStatus::PartiallyFilled) {
+                return Err(anyhow::anyhow!("Only open orders can be amended"));
+            }
+            if OrderType::from_str(&order.order_type)
+                .map_err(|e| anyhow::anyhow!("Failed to parse order type: {}", e))?
+                != OrderType::Limit
+            {
+                return Err(anyhow::anyhow!("Only limit orders can be amended"));
+            }
+
+            let market = markets::table
+                .find(&order.market_id)
+                .first::<Market>(conn)
+                .context("Market not found")?;
+
+            // Buys hold the quote of what is left, sells the base
+            let remained_quote = utils::round_amount(&(&price * &remained_base));
+            let (asset, delta) = match OrderSide::from_str(&order.side)
+                .map_err(|e| anyhow::anyhow!("Failed to parse order side: {}", e))?
+            {
+                OrderSide::Buy => (&market.quote_asset, &remained_quote - &order.remained_quote),
+                OrderSide::Sell => (&market.base_asset, &remained_base - &order.remained_base),
+            };
+
+            let wallet = wallets::table
+                .find((&order.user_id, asset))
+                .first::<Wallet>(conn)
+                .context("Wallet not found")?;
+            if wallet.available < delta {
+                return Err(BitradeError::InsufficientBalance {
+                    asset: asset.clone(),
+                    required: delta,
+                    available: wallet.available,
+                }
+                .into());
+            }
+            diesel::update(wallets::table.find((&order.user_id, asset)))
+                .set((
+                    wallets::available.eq(dec(&(&wallet.available - &delta))),
+                    wallets::locked.eq(dec(&(&wallet.locked + &delta))),
+                    wallets::update_time.eq(utils::get_utc_now_millis()),
+                ))
+                .execute(conn)
+                .context("Failed to relock balance")?;
+            // A growing order locks the difference, a shrinking one releases it
+            let (kind, from, to) = if delta >= 0 {
+                (
+                    LedgerEntryKind::Lock,
+                    LedgerAccount::Available,
+                    LedgerAccount::Locked,
+                )
+            } else {
+                (
+                    LedgerEntryKind::Unlock,
+                    LedgerAccount::Locked,
+                    LedgerAccount::Available,
+                )
+            };
+            conn.record_ledger_transfers(
+                Some(order_id),
+                &[LedgerTransfer::new(
+                    kind,
+                    asset,
+                    (&order.user_id, from),
+                    (&order.user_id, to),
+                    delta.abs(),
+                )],
+            )?;
+
+            let base_amount = &order.filled_base + &remained_base;
+            // An iceberg never shows more than is left of it
+            let display_amount = order
+                .display_amount
+                .map(|display_amount| display_amount.min(base_amount.clone()));
+            let amended = diesel::update(orders::table.find(order_id))
+                .set((
+                    orders::price.eq(dec(&price)),
+                    orders::base_amount.eq(dec(&base_amount)),
+                    orders::quote_amount.eq(dec(&(&order.filled_quote + &remained_quote))),
+                    orders::remained_base.eq(dec(&remained_base)),
+                    orders::remained_quote.eq(dec(&remained_quote)),
+                    orders::display_amount.eq(dec_opt(&display_amount)),
+                    orders::priority.eq(priority),
+                    orders::update_time.eq(utils::get_utc_now_millis()),
+                ))
+                .get_result::<Order>(conn)
+                .context("Failed to amend order")?;
+
+            Ok(amended)
+        })
+    }
+}
@@ -0,0 +1,60 @@
+use super::SqliteRepository;
+use super::schema::*;
+use crate::models::models::*;
+use crate::provider::{OutboxDatabaseReader, OutboxDatabaseWriter};
+use anyhow::{Context, Result};
+use common::utils::get_utc_now_millis;
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+
+/// Writes `outbox` on the caller's connection, so the events commit with what they describe.
+pub(super) fn record_outbox_events(
+    conn: &mut SqliteConnection,
+    outbox: &[NewOutboxEvent],
+) -> Result<()> {
+    if outbox.is_empty() {
+        return Ok(());
+    }
+    let values: Vec<_> = outbox
+        .iter()
+        .map(|event| {
+            (
+                events::topic.eq(&event.topic),
+                events::market_id.eq(&event.market_id),
+                events::payload.eq(&event.payload),
+                events::create_time.eq(event.create_time),
+            )
+        })
+        .collect();
+    diesel::insert_into(events::table)
+        .values(values)
+        .execute(conn)
+        .context("Failed to record outbox events")?;
+    Ok(())
+}
+
+impl OutboxDatabaseReader for SqliteRepository {
+    fn get_unpublished_events(&self, limit: i64) -> Result<Vec<OutboxEvent>> {
+        let conn = &mut *self.get_conn()?;
+
+        events::table
+            .filter(events::published_time.is_null())
+            .order(events::id.asc())
+            .limit(limit)
+            .load(conn)
+            .context("Failed to fetch unpublished outbox events")
+    }
+}
+
+impl OutboxDatabaseWriter for SqliteRepository {
+    fn mark_events_published(&self, ids: &[i64]) -> Result<usize> {
+        let conn = &mut *self.get_conn()?;
+
+        diesel::update(events::table)
+            .filter(events::id.eq_any(ids))
+            .filter(events::published_time.is_null())
+            .set(events::published_time.eq(get_utc_now_millis()))
+            .execute(conn)
+            .context("Failed to mark outbox events published")
+    }
+}
@@ -0,0 +1,67 @@
+use super::SqliteRepository;
+use super::schema::*;
+use super::types::dec_opt;
+use crate::models::models::*;
+use crate::provider::{PriceBandDatabaseReader, PriceBandDatabaseWriter};
+use anyhow::{Context, Result};
+use bigdecimal::BigDecimal;
+use common::utils::get_utc_now_millis;
+use diesel::prelude::*;
+
+impl PriceBandDatabaseReader for SqliteRepository {
+    fn get_price_band(&self, market_id: &str) -> Result<Option<PriceBand>> {
+        let conn = &mut *self.get_conn()?;
+
+        price_bands::table
+            .find(market_id)
+            .first(conn)
+            .optional()
+            .context("Failed to fetch price band")
+    }
+}
+
+impl PriceBandDatabaseWriter for SqliteRepository {
+    fn set_price_band(
+        &self,
+        market_id: &str,
+        band_percent: Option<BigDecimal>,
+        halt_percent: Option<BigDecimal>,
+        halt_window_ms: i64,
+        halt_duration_ms: i64,
+    ) -> Result<PriceBand> {
+        let conn = &mut *self.get_conn()?;
+
+        let now = get_utc_now_millis();
+        diesel::insert_into(price_bands::table)
+            .values((
+                price_bands::market_id.eq(market_id),
+                price_bands::band_percent.eq(dec_opt(&band_percent)),
+                price_bands::halt_percent.eq(dec_opt(&halt_percent)),
+                price_bands::halt_window_ms.eq(halt_window_ms),
+                price_bands::halt_duration_ms.eq(halt_duration_ms),
+                price_bands::create_time.eq(now),
+                price_bands::update_time.eq(now),
+            ))
+            .on_conflict(price_bands::market_id)
+            .do_update()
+            .set((
+                price_bands::band_percent.eq(dec_opt(&band_percent)),
+                price_bands::halt_percent.eq(dec_opt(&halt_percent)),
+                price_bands::halt_window_ms.eq(halt_window_ms),
+                price_bands::halt_duration_ms.eq(halt_duration_ms),
+                price_bands::update_time.eq(now),
+            ))
+            .get_result(conn)
+            .context("Failed to store price band")
+    }
+
+    fn delete_price_band(&self, market_id: &str) -> Result<bool> {
+        let conn = &mut *self.get_conn()?;
+
+        let deleted = diesel::delete(price_bands::table.find(market_id))
+            .execute(conn)
+            .context("Failed to delete price band")?;
+
+        Ok(deleted > 0)
+    }
+}
@@ -0,0 +1,135 @@
+use super::SqliteRepository;
+use super::schema::*;
+use crate::models::models::*;
+use crate::provider::ReconciliationDatabaseReader;
+use anyhow::{Context, Result};
+use bigdecimal::BigDecimal;
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use std::collections::BTreeMap;
+
+fn totals_of<'a>(
+    totals: &'a mut BTreeMap<String, AssetBalanceTotals>,
+    asset: &str,
+) -> &'a mut AssetBalanceTotals {
+    totals
+        .entry(asset.to_string())
+        .or_insert_with(|| AssetBalanceTotals {
+            asset: asset.to_string(),
+            available: BigDecimal::from(0),
+            locked: BigDecimal::from(0),
+            reserved: BigDecimal::from(0),
+            fee_treasury: BigDecimal::from(0),
+            total_deposited: BigDecimal::from(0),
+            total_withdrawn: BigDecimal::from(0),
+        })
+}
+
+/// Balances are text here, so they are summed per asset once loaded rather than in SQL
+fn asset_balance_totals(conn: &mut SqliteConnection) -> Result<Vec<AssetBalanceTotals>> {
+    let wallets = wallets::table
+        .load::<Wallet>(conn)
+        .context("Failed to sum wallet balances")?;
+    let treasuries = fee_treasury::table
+        .load::<FeeTreasury>(conn)
+        .context("Failed to sum fee treasuries")?;
+
+    let mut totals = BTreeMap::new();
+    for wallet in wallets {
+        let totals = totals_of(&mut totals, &wallet.asset);
+        totals.available += wallet.available;
+        totals.locked += wallet.locked;
+        totals.reserved += wallet.reserved;
+        totals.total_deposited += wallet.total_deposited;
+        totals.total_withdrawn += wallet.total_withdrawn;
+    }
+    // Fees swept out of a treasury left the exchange like a withdrawal
+    for treasury in treasuries {
+        let totals = totals_of(&mut totals, &treasury.asset);
+        totals.fee_treasury += treasury.collected_amount;
+        totals.total_withdrawn += treasury.total_withdrawn;
+    }
+
+    Ok(totals.into_values().collect())
+}
+
+fn locked_balances(conn: &mut SqliteConnection) -> Result<Vec<LockedBalance>> {
+    let active_orders = orders::table
+        .inner_join(markets::table)
+        .filter(orders::status.eq_any(&[
+            OrderStatus::Open.as_str(),
+            OrderStatus::PartiallyFilled.as_str(),
+        ]))
+        .select((
+            orders::user_id,
+            orders::side,
+            orders::remained_base,
+            orders::remained_quote,
+            markets::base_asset,
+            markets::quote_asset,
+        ))
+        .load::<(String, String, BigDecimal, BigDecimal, String, String)>(conn)
+        .context("Failed to fetch active orders")?;
+
+    // Buys hold the quote of what is left of them, sells the base
+    let mut order_locked: BTreeMap<(String, String), BigDecimal> = BTreeMap::new();
+    for (user_id, side, remained_base, remained_quote, base_asset, quote_asset) in active_orders {
+        let (asset, amount) = match OrderSide::from_str(&side)
+            .map_err(|e| anyhow::anyhow!("Failed to parse order side: {}", e))?
+        {
+            OrderSide::Buy => (quote_asset, remained_quote),
+            OrderSide::Sell => (base_asset, remained_base),
+        };
+        *order_locked.entry((user_id, asset)).or_default() += amount;
+    }
+
+    let locked_wallets = wallets::table
+        .select((wallets::user_id, wallets::asset, wallets::locked))
+        .load::<(String, String, BigDecimal)>(conn)
+        .context("Failed to fetch locked wallets")?;
+
+    let mut balances: BTreeMap<(String, String), LockedBalance> = BTreeMap::new();
+    for (user_id, asset, locked) in locked_wallets {
+        if locked == 0 {
+            continue;
+        }
+        let order_locked = order_locked
+            .remove(&(user_id.clone(), asset.clone()))
+            .unwrap_or_default();
+        balances.insert(
+            (user_id.clone(), asset.clone()),
+            LockedBalance {
+                user_id,
+                asset,
+                locked,
+                order_locked,
+            },
+        );
+    }
+    // Open orders whose wallet has nothing locked
+    for ((user_id, asset), order_locked) in order_locked {
+        balances.insert(
+            (user_id.clone(), asset.clone()),
+            LockedBalance {
+                user_id,
+                asset,
+                locked: BigDecimal::from(0),
+                order_locked,
+            },
+        );
+    }
+
+    Ok(balances.into_values().collect())
+}
+
+impl ReconciliationDatabaseReader for SqliteRepository {
+    fn get_balance_sheet(&self) -> Result<BalanceSheet> {
+        let conn = &mut *self.get_conn()?;
+        conn.transaction(|conn| {
+            Ok(BalanceSheet {
+                assets: asset_balance_totals(conn)?,
+                locks: locked_balances(conn)?,
+            })
+        })
+    }
+}
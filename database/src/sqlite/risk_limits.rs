@@ -0,0 +1,61 @@
+use super::SqliteRepository;
+use super::schema::*;
+use super::types::dec_opt;
+use crate::models::models::*;
+use crate::provider::{RiskLimitDatabaseReader, RiskLimitDatabaseWriter};
+use anyhow::{Context, Result};
+use bigdecimal::BigDecimal;
+use common::utils::get_utc_now_millis;
+use diesel::prelude::*;
+
+impl RiskLimitDatabaseReader for SqliteRepository {
+    fn get_risk_limit(&self, market_id: &str) -> Result<Option<RiskLimit>> {
+        let conn = &mut *self.get_conn()?;
+
+        risk_limits::table
+            .find(market_id)
+            .first(conn)
+            .optional()
+            .context("Failed to fetch risk limit")
+    }
+}
+
+impl RiskLimitDatabaseWriter for SqliteRepository {
+    fn set_risk_limit(
+        &self,
+        market_id: &str,
+        max_open_orders: Option<i32>,
+        max_locked_notional: Option<BigDecimal>,
+    ) -> Result<RiskLimit> {
+        let conn = &mut *self.get_conn()?;
+
+        let now = get_utc_now_millis();
+        diesel::insert_into(risk_limits::table)
+            .values((
+                risk_limits::market_id.eq(market_id),
+                risk_limits::max_open_orders.eq(max_open_orders),
+                risk_limits::max_locked_notional.eq(dec_opt(&max_locked_notional)),
+                risk_limits::create_time.eq(now),
+                risk_limits::update_time.eq(now),
+            ))
+            .on_conflict(risk_limits::market_id)
+            .do_update()
+            .set((
+                risk_limits::max_open_orders.eq(max_open_orders),
+                risk_limits::max_locked_notional.eq(dec_opt(&max_locked_notional)),
+                risk_limits::update_time.eq(now),
+            ))
+            .get_result(conn)
+            .context("Failed to store risk limit")
+    }
+
+    fn delete_risk_limit(&self, market_id: &str) -> Result<bool> {
+        let conn = &mut *self.get_conn()?;
+
+        let deleted = diesel::delete(risk_limits::table.find(market_id))
+            .execute(conn)
+            .context("Failed to delete risk limit")?;
+
+        Ok(deleted > 0)
+    }
+}
@@ -0,0 +1,497 @@
+//! The tables of [`crate::models::schema`] on SQLite, their decimal columns stored as text.
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::sqlite::types::TextDecimal;
+
+    engine_checkpoints (market_id) {
+        #[max_length = 36]
+        market_id -> Varchar,
+        applied_sequence -> Int8,
+        update_time -> Int8,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::sqlite::types::TextDecimal;
+
+    engine_events (sequence) {
+        sequence -> Int8,
+        #[max_length = 36]
+        market_id -> Varchar,
+        #[max_length = 20]
+        event_type -> Varchar,
+        #[max_length = 36]
+        order_id -> Nullable<Varchar>,
+        payload -> Text,
+        create_time -> Int8,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::sqlite::types::TextDecimal;
+
+    events (id) {
+        id -> Int8,
+        #[max_length = 20]
+        topic -> Varchar,
+        #[max_length = 36]
+        market_id -> Varchar,
+        payload -> Text,
+        create_time -> Int8,
+        published_time -> Nullable<Int8>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::sqlite::types::TextDecimal;
+
+    fee_tiers (market_id, min_volume) {
+        #[max_length = 36]
+        market_id -> Varchar,
+        min_volume -> TextDecimal,
+        maker_fee -> TextDecimal,
+        taker_fee -> TextDecimal,
+        create_time -> Int8,
+        update_time -> Int8,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::sqlite::types::TextDecimal;
+
+    fee_treasury (market_id, asset) {
+        #[max_length = 36]
+        market_id -> Varchar,
+        #[max_length = 20]
+        asset -> Varchar,
+        #[max_length = 100]
+        treasury_address -> Varchar,
+        collected_amount -> TextDecimal,
+        last_update_time -> Int8,
+        total_withdrawn -> TextDecimal,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::sqlite::types::TextDecimal;
+
+    fee_treasury_withdrawals (id) {
+        #[max_length = 36]
+        id -> Varchar,
+        #[max_length = 36]
+        market_id -> Varchar,
+        #[max_length = 20]
+        asset -> Varchar,
+        amount -> TextDecimal,
+        #[max_length = 128]
+        destination -> Varchar,
+        create_time -> Int8,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::sqlite::types::TextDecimal;
+
+    klines (market_id, interval, open_time) {
+        #[max_length = 36]
+        market_id -> Varchar,
+        #[max_length = 3]
+        interval -> Varchar,
+        open_time -> Int8,
+        open -> TextDecimal,
+        high -> TextDecimal,
+        low -> TextDecimal,
+        close -> TextDecimal,
+        volume -> TextDecimal,
+        quote_volume -> TextDecimal,
+        trade_count -> Int8,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::sqlite::types::TextDecimal;
+
+    ledger_entries (id) {
+        id -> Int8,
+        #[max_length = 36]
+        transaction_id -> Varchar,
+        #[max_length = 20]
+        kind -> Varchar,
+        #[max_length = 36]
+        owner_id -> Varchar,
+        #[max_length = 20]
+        account -> Varchar,
+        #[max_length = 20]
+        asset -> Varchar,
+        debit -> TextDecimal,
+        credit -> TextDecimal,
+        #[max_length = 36]
+        reference_id -> Nullable<Varchar>,
+        create_time -> Int8,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::sqlite::types::TextDecimal;
+
+    market_quotes (market_id) {
+        #[max_length = 36]
+        market_id -> Varchar,
+        best_bid -> Nullable<TextDecimal>,
+        best_ask -> Nullable<TextDecimal>,
+        update_time -> Int8,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::sqlite::types::TextDecimal;
+
+    market_stats (market_id) {
+        #[max_length = 36]
+        market_id -> Varchar,
+        high_24h -> TextDecimal,
+        low_24h -> TextDecimal,
+        volume_24h -> TextDecimal,
+        price_change_24h -> TextDecimal,
+        last_price -> TextDecimal,
+        last_update_time -> Int8,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::sqlite::types::TextDecimal;
+
+    markets (id) {
+        #[max_length = 36]
+        id -> Varchar,
+        #[max_length = 20]
+        base_asset -> Varchar,
+        #[max_length = 20]
+        quote_asset -> Varchar,
+        default_maker_fee -> TextDecimal,
+        default_taker_fee -> TextDecimal,
+        create_time -> Int8,
+        update_time -> Int8,
+        #[max_length = 20]
+        status -> Varchar,
+        min_base_amount -> TextDecimal,
+        min_quote_amount -> TextDecimal,
+        price_precision -> Int4,
+        amount_precision -> Int4,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::sqlite::types::TextDecimal;
+
+    oco_groups (id) {
+        #[max_length = 36]
+        id -> Varchar,
+        #[max_length = 36]
+        market_id -> Varchar,
+        #[max_length = 36]
+        user_id -> Varchar,
+        #[max_length = 36]
+        first_order_id -> Varchar,
+        #[max_length = 36]
+        second_order_id -> Varchar,
+        create_time -> Int8,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::sqlite::types::TextDecimal;
+
+    order_audit (id) {
+        id -> Int8,
+        #[max_length = 20]
+        action -> Varchar,
+        #[max_length = 36]
+        user_id -> Nullable<Varchar>,
+        #[max_length = 64]
+        remote_addr -> Nullable<Varchar>,
+        #[max_length = 36]
+        market_id -> Varchar,
+        #[max_length = 36]
+        order_id -> Nullable<Varchar>,
+        request -> Text,
+        create_time -> Int8,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::sqlite::types::TextDecimal;
+
+    order_book_snapshots (market_id) {
+        #[max_length = 36]
+        market_id -> Varchar,
+        applied_sequence -> Int8,
+        payload -> Text,
+        create_time -> Int8,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::sqlite::types::TextDecimal;
+
+    orders (id) {
+        #[max_length = 36]
+        id -> Varchar,
+        #[max_length = 36]
+        market_id -> Varchar,
+        #[max_length = 36]
+        user_id -> Varchar,
+        #[max_length = 20]
+        order_type -> Varchar,
+        #[max_length = 10]
+        side -> Varchar,
+        price -> TextDecimal,
+        base_amount -> TextDecimal,
+        quote_amount -> TextDecimal,
+        maker_fee -> TextDecimal,
+        taker_fee -> TextDecimal,
+        create_time -> Int8,
+        remained_base -> TextDecimal,
+        remained_quote -> TextDecimal,
+        filled_base -> TextDecimal,
+        filled_quote -> TextDecimal,
+        filled_fee -> TextDecimal,
+        update_time -> Int8,
+        #[max_length = 20]
+        status -> Varchar,
+        #[max_length = 50]
+        client_order_id -> Nullable<Varchar>,
+        post_only -> Nullable<Bool>,
+        #[max_length = 10]
+        time_in_force -> Nullable<Varchar>,
+        expires_at -> Nullable<Int8>,
+        #[max_length = 20]
+        cancel_reason -> Nullable<Varchar>,
+        display_amount -> Nullable<TextDecimal>,
+        #[max_length = 30]
+        reject_reason -> Nullable<Varchar>,
+        priority -> Int8,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::sqlite::types::TextDecimal;
+
+    price_bands (market_id) {
+        #[max_length = 36]
+        market_id -> Varchar,
+        band_percent -> Nullable<TextDecimal>,
+        halt_percent -> Nullable<TextDecimal>,
+        halt_window_ms -> Int8,
+        halt_duration_ms -> Int8,
+        create_time -> Int8,
+        update_time -> Int8,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::sqlite::types::TextDecimal;
+
+    risk_limits (market_id) {
+        #[max_length = 36]
+        market_id -> Varchar,
+        max_open_orders -> Nullable<Int4>,
+        max_locked_notional -> Nullable<TextDecimal>,
+        create_time -> Int8,
+        update_time -> Int8,
+    }
+}
+
+diesel::table! {
+    settlement_dead_letters (id) {
+        id -> Int8,
+        #[max_length = 36]
+        market_id -> Varchar,
+        sequence -> Int8,
+        fills -> Text,
+        error -> Text,
+        create_time -> Int8,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::sqlite::types::TextDecimal;
+
+    trade_balance_snapshots (trade_id, user_id, asset) {
+        #[max_length = 36]
+        trade_id -> Varchar,
+        #[max_length = 36]
+        user_id -> Varchar,
+        #[max_length = 20]
+        asset -> Varchar,
+        available_before -> TextDecimal,
+        locked_before -> TextDecimal,
+        available_after -> TextDecimal,
+        locked_after -> TextDecimal,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::sqlite::types::TextDecimal;
+
+    trades (id) {
+        #[max_length = 36]
+        id -> Varchar,
+        timestamp -> Int8,
+        #[max_length = 36]
+        market_id -> Varchar,
+        price -> TextDecimal,
+        base_amount -> TextDecimal,
+        quote_amount -> TextDecimal,
+        #[max_length = 36]
+        buyer_user_id -> Varchar,
+        #[max_length = 36]
+        buyer_order_id -> Varchar,
+        buyer_fee -> TextDecimal,
+        #[max_length = 36]
+        seller_user_id -> Varchar,
+        #[max_length = 36]
+        seller_order_id -> Varchar,
+        seller_fee -> TextDecimal,
+        #[max_length = 10]
+        taker_side -> Varchar,
+        is_liquidation -> Nullable<Bool>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::sqlite::types::TextDecimal;
+
+    transfers (id) {
+        #[max_length = 36]
+        id -> Varchar,
+        #[max_length = 36]
+        user_id -> Varchar,
+        #[max_length = 20]
+        asset -> Varchar,
+        #[max_length = 20]
+        kind -> Varchar,
+        amount -> TextDecimal,
+        #[max_length = 20]
+        status -> Varchar,
+        #[max_length = 128]
+        address -> Nullable<Varchar>,
+        #[max_length = 128]
+        external_id -> Nullable<Varchar>,
+        #[max_length = 255]
+        reject_reason -> Nullable<Varchar>,
+        create_time -> Int8,
+        update_time -> Int8,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::sqlite::types::TextDecimal;
+
+    user_fee_overrides (user_id) {
+        #[max_length = 36]
+        user_id -> Varchar,
+        maker_fee -> TextDecimal,
+        taker_fee -> TextDecimal,
+        create_time -> Int8,
+        update_time -> Int8,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::sqlite::types::TextDecimal;
+
+    user_restrictions (user_id) {
+        #[max_length = 36]
+        user_id -> Varchar,
+        #[max_length = 20]
+        status -> Varchar,
+        reason -> Text,
+        update_time -> Int8,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::sqlite::types::TextDecimal;
+
+    wallets (user_id, asset) {
+        #[max_length = 36]
+        user_id -> Varchar,
+        #[max_length = 20]
+        asset -> Varchar,
+        available -> TextDecimal,
+        locked -> TextDecimal,
+        update_time -> Int8,
+        reserved -> TextDecimal,
+        total_deposited -> TextDecimal,
+        total_withdrawn -> TextDecimal,
+    }
+}
+
+diesel::joinable!(engine_checkpoints -> markets (market_id));
+diesel::joinable!(engine_events -> markets (market_id));
+diesel::joinable!(events -> markets (market_id));
+diesel::joinable!(fee_tiers -> markets (market_id));
+diesel::joinable!(fee_treasury -> markets (market_id));
+diesel::joinable!(fee_treasury_withdrawals -> markets (market_id));
+diesel::joinable!(klines -> markets (market_id));
+diesel::joinable!(market_quotes -> markets (market_id));
+diesel::joinable!(market_stats -> markets (market_id));
+diesel::joinable!(oco_groups -> markets (market_id));
+diesel::joinable!(order_book_snapshots -> markets (market_id));
+diesel::joinable!(orders -> markets (market_id));
+diesel::joinable!(price_bands -> markets (market_id));
+diesel::joinable!(risk_limits -> markets (market_id));
+diesel::joinable!(settlement_dead_letters -> markets (market_id));
+diesel::joinable!(trade_balance_snapshots -> trades (trade_id));
+diesel::joinable!(trades -> markets (market_id));
+
+diesel::allow_tables_to_appear_in_same_query!(
+    engine_checkpoints,
+    engine_events,
+    events,
+    fee_tiers,
+    fee_treasury,
+    fee_treasury_withdrawals,
+    klines,
+    ledger_entries,
+    market_quotes,
+    market_stats,
+    markets,
+    oco_groups,
+    order_audit,
+    order_book_snapshots,
+    orders,
+    price_bands,
+    risk_limits,
+    settlement_dead_letters,
+    trade_balance_snapshots,
+    trades,
+    transfers,
+    user_fee_overrides,
+    user_restrictions,
+    wallets,
+);
@@ -0,0 +1,640 @@
+use super::SqliteRepository;
+use super::engine_events::store_applied_sequence;
+use super::klines::record_kline_trades;
+use super::oco_groups::cancel_oco_siblings;
+use super::orders::insert_order;
+use super::outbox::record_outbox_events;
+use super::schema::*;
+use super::types::dec;
+use super::wallets::insert_wallet;
+use crate::filters::TradeFilter;
+use crate::models::models::*;
+use crate::provider::{LedgerDatabaseWriter, TradeDatabaseReader, TradeDatabaseWriter};
+use crate::repository::{
+    MissingWalletPolicy, PaginationError, SettlementError, apply_fills, check_fills,
+};
+use anyhow::Context;
+use anyhow::Result;
+use bigdecimal::BigDecimal;
+use common::db::pagination::{Cursor, Paginated, Pagination};
+use diesel::prelude::*;
+use diesel::sqlite::{Sqlite, SqliteConnection};
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Fetches a wallet a settlement changes, handling a missing row according to `policy`.
+fn settled_wallet(
+    conn: &mut SqliteConnection,
+    user_id: &str,
+    asset: &str,
+    policy: MissingWalletPolicy,
+) -> Result<Wallet> {
+    let wallet = wallets::table
+        .find((user_id, asset))
+        .first::<Wallet>(conn)
+        .optional()
+        .context(format!("Failed to fetch {} wallet of {}", asset, user_id))?;
+
+    let zero = BigDecimal::from(0);
+    match (wallet, policy) {
+        (Some(wallet), _) => Ok(wallet),
+        (None, MissingWalletPolicy::CreateEmpty) => {
+            insert_wallet(conn, user_id, asset, &zero, &zero, &zero)
+                .context(format!("Failed to create {} wallet of {}", asset, user_id))
+        }
+        (None, MissingWalletPolicy::Fail) => Err(SettlementError::WalletMissing {
+            user_id: user_id.to_string(),
+            asset: asset.to_string(),
+        }
+        .into()),
+    }
+}
+
+fn filtered_trades(filter: TradeFilter) -> trades::BoxedQuery<'static, Sqlite> {
+    let mut query = trades::table.into_boxed();
+
+    if let Some(market_id) = filter.market_id {
+        query = query.filter(trades::market_id.eq(market_id));
+    }
+    if let Some(buyer_order_id) = filter.buyer_order_id {
+        query = query.filter(trades::buyer_order_id.eq(buyer_order_id));
+    }
+    if let Some(seller_order_id) = filter.seller_order_id {
+        query = query.filter(trades::seller_order_id.eq(seller_order_id));
+    }
+    if let Some(buyer_user_id) = filter.buyer_user_id {
+        query = query.filter(trades::buyer_user_id.eq(buyer_user_id));
+    }
+    if let Some(seller_user_id) = filter.seller_user_id {
+        query = query.filter(trades::seller_user_id.eq(seller_user_id));
+    }
+    if let Some(taker_side) = filter.taker_side {
+        query = query.filter(trades::taker_side.eq(taker_side));
+    }
+    if let Some(is_liquidation) = filter.is_liquidation {
+        query = query.filter(trades::is_liquidation.eq(is_liquidation));
+    }
+    if let Some(start_time) = filter.start_time {
+        query = query.filter(trades::timestamp.ge(start_time));
+    }
+    if let Some(end_time) = filter.end_time {
+        query = query.filter(trades::timestamp.le(end_time));
+    }
+
+    query
+}
+
+impl SqliteRepository {
+    /// Settles `fills` on `conn`, within the transaction of the caller.
+    fn settle_fills(
+        &self,
+        conn: &mut SqliteConnection,
+        market_id: &str,
+        base_asset: &str,
+        quote_asset: &str,
+        fills: &[TradeFill],
+    ) -> Result<Vec<NewTrade>> {
+        // The funds being traded are held in the sellers' base and buyers' quote wallets,
+        // those can never be created here
+        let mut wallets = BTreeMap::new();
+        for fill in fills {
+            for (user_id, asset, policy) in [
+                (&fill.seller_user_id, base_asset, MissingWalletPolicy::Fail),
+                (&fill.buyer_user_id, quote_asset, MissingWalletPolicy::Fail),
+                (
+                    &fill.seller_user_id,
+                    quote_asset,
+                    self.missing_wallet_policy,
+                ),
+                (&fill.buyer_user_id, base_asset, self.missing_wallet_policy),
+            ] {
+                let key = (user_id.as_str(), asset);
+                if let Entry::Vacant(entry) = wallets.entry(key) {
+                    entry.insert(settled_wallet(conn, user_id, asset, policy)?);
+                }
+            }
+        }
+
+        let order_ids: Vec<&str> = fills
+            .iter()
+            .flat_map(|fill| [fill.buyer_order_id.as_str(), fill.seller_order_id.as_str()])
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        let mut orders: BTreeMap<String, Order> = orders::table
+            .filter(orders::id.eq_any(&order_ids))
+            .filter(orders::status.eq_any([
+                OrderStatus::Open.as_str(),
+                OrderStatus::PartiallyFilled.as_str(),
+            ]))
+            .load::<Order>(conn)
+            .context("Failed to fetch orders")?
+            .into_iter()
+            .map(|order| (order.id.clone(), order))
+            .collect();
+
+        let settlement = apply_fills(
+            market_id,
+            base_asset,
+            quote_asset,
+            fills,
+            &mut wallets,
+            &mut orders,
+            self.balance_snapshots,
+            self.outbox,
+        )?;
+
+        // Write every order and wallet back once, as the last fill left it
+        for order in orders.values() {
+            diesel::update(orders::table.find(&order.id))
+                .set((
+                    orders::filled_base.eq(dec(&order.filled_base)),
+                    orders::filled_quote.eq(dec(&order.filled_quote)),
+                    orders::filled_fee.eq(dec(&order.filled_fee)),
+                    orders::remained_base.eq(dec(&order.remained_base)),
+                    orders::remained_quote.eq(dec(&order.remained_quote)),
+                    orders::status.eq(&order.status),
+                ))
+                .execute(conn)
+                .context("Failed to update orders")?;
+        }
+        for wallet in wallets.values() {
+            diesel::update(wallets::table.find((&wallet.user_id, &wallet.asset)))
+                .set((
+                    wallets::available.eq(dec(&wallet.available)),
+                    wallets::locked.eq(dec(&wallet.locked)),
+                ))
+                .execute(conn)
+                .context("Failed to update wallets")?;
+        }
+
+        // Sellers pay their fees in the quote asset, buyers in the base asset
+        for (asset, collected) in [
+            (quote_asset, &settlement.seller_fees),
+            (base_asset, &settlement.buyer_fees),
+        ] {
+            let treasury = fee_treasury::table
+                .find((market_id, asset))
+                .first::<FeeTreasury>(conn)
+                .optional()
+                .context(format!("Failed to fetch {} fee treasury", asset))?;
+            if let Some(treasury) = treasury {
+                diesel::update(fee_treasury::table.find((market_id, asset)))
+                    .set(
+                        fee_treasury::collected_amount
+                            .eq(dec(&(treasury.collected_amount + collected))),
+                    )
+                    .execute(conn)
+                    .context(format!("Failed to update {} fee treasury", asset))?;
+            }
+        }
+
+        conn.record_ledger_transactions(&settlement.transactions())?;
+
+        let trade_values: Vec<_> = settlement
+            .trades
+            .iter()
+            .map(|trade| {
+                (
+                    trades::id.eq(&trade.id),
+                    trades::timestamp.eq(trade.timestamp),
+                    trades::market_id.eq(&trade.market_id),
+                    trades::price.eq(dec(&trade.price)),
+                    trades::base_amount.eq(dec(&trade.base_amount)),
+                    trades::quote_amount.eq(dec(&trade.quote_amount)),
+                    trades::buyer_user_id.eq(&trade.buyer_user_id),
+                    trades::buyer_order_id.eq(&trade.buyer_order_id),
+                    trades::buyer_fee.eq(dec(&trade.buyer_fee)),
+                    trades::seller_user_id.eq(&trade.seller_user_id),
+                    trades::seller_order_id.eq(&trade.seller_order_id),
+                    trades::seller_fee.eq(dec(&trade.seller_fee)),
+                    trades::taker_side.eq(&trade.taker_side),
+                    trades::is_liquidation.eq(trade.is_liquidation),
+                )
+            })
+            .collect();
+        diesel::insert_into(trades::table)
+            .values(trade_values)
+            .execute(conn)
+            .context("Failed to record trades")?;
+
+        record_kline_trades(conn, &settlement.trades)?;
+
+        if !settlement.snapshots.is_empty() {
+            let snapshot_values: Vec<_> = settlement
+                .snapshots
+                .iter()
+                .map(|snapshot| {
+                    (
+                        trade_balance_snapshots::trade_id.eq(&snapshot.trade_id),
+                        trade_balance_snapshots::user_id.eq(&snapshot.user_id),
+                        trade_balance_snapshots::asset.eq(&snapshot.asset),
+                        trade_balance_snapshots::available_before
+                            .eq(dec(&snapshot.available_before)),
+                        trade_balance_snapshots::locked_before.eq(dec(&snapshot.locked_before)),
+                        trade_balance_snapshots::available_after.eq(dec(&snapshot.available_after)),
+                        trade_balance_snapshots::locked_after.eq(dec(&snapshot.locked_after)),
+                    )
+                })
+                .collect();
+            diesel::insert_into(trade_balance_snapshots::table)
+                .values(snapshot_values)
+                .execute(conn)
+                .context("Failed to record trade balance snapshots")?;
+        }
+
+        // A fill of any order cancels the other leg of its OCO group. The cancel unlocks
+        // funds of wallets written above, so it runs once they are.
+        cancel_oco_siblings(conn, &order_ids)?;
+
+        record_outbox_events(conn, &settlement.outbox)?;
+
+        Ok(settlement.trades)
+    }
+}
+
+impl TradeDatabaseReader for SqliteRepository {
+    fn list_trades(
+        &self,
+        filter: TradeFilter,
+        pagination: Option<Pagination>,
+    ) -> Result<Paginated<Trade>> {
+        let conn = &mut *self.get_conn()?;
+        let pagination = pagination.unwrap_or_default();
+        let total_count: i64 = filtered_trades(filter.clone())
+            .select(diesel::dsl::count_star())
+            .first(conn)?;
+        let mut query = filtered_trades(filter);
+
+        let limit = pagination.limit.unwrap_or(10);
+        let offset = pagination.offset.unwrap_or(0);
+
+        let sort_field = pagination.sort_field("timestamp");
+        let descending = pagination.is_descending().ok_or_else(|| {
+            PaginationError::InvalidDirection(
+                pagination.order_direction.clone().unwrap_or_default(),
+            )
+        })?;
+        if !matches!(sort_field, "timestamp" | "price") {
+            return Err(PaginationError::UnsortableField {
+                listing: "trades",
+                field: sort_field.to_string(),
+            }
+            .into());
+        }
+        let by_time = sort_field == "timestamp";
+
+        let mut trades = if by_time {
+            // The id breaks ties, so that a page ends between two trades
+            query = if descending {
+                query.order((trades::timestamp.desc(), trades::id.desc()))
+            } else {
+                query.order((trades::timestamp.asc(), trades::id.asc()))
+            };
+            match &pagination.cursor {
+                Some(cursor) if descending => {
+                    query = query.filter(
+                        trades::timestamp.lt(cursor.timestamp).or(trades::timestamp
+                            .eq(cursor.timestamp)
+                            .and(trades::id.lt(cursor.id.clone()))),
+                    )
+                }
+                Some(cursor) => {
+                    query = query.filter(
+                        trades::timestamp.gt(cursor.timestamp).or(trades::timestamp
+                            .eq(cursor.timestamp)
+                            .and(trades::id.gt(cursor.id.clone()))),
+                    )
+                }
+                None => query = query.offset(offset),
+            }
+            query.limit(limit + 1).load::<Trade>(conn)?
+        } else {
+            if pagination.cursor.is_some() {
+                return Err(PaginationError::CursorNeedsTimeSort {
+                    listing: "trades",
+                    field: "timestamp",
+                }
+                .into());
+            }
+            // Prices are text here, so the page is cut once they are sorted as numbers
+            let mut trades = query.load::<Trade>(conn)?;
+            trades.sort_by(|a, b| a.price.cmp(&b.price).then_with(|| a.id.cmp(&b.id)));
+            if descending {
+                trades.reverse();
+            }
+            trades
+                .into_iter()
+                .skip(offset as usize)
+                .take(limit as usize + 1)
+                .collect()
+        };
+
+        let has_more = trades.len() > limit as usize;
+        if has_more {
+            trades.pop();
+        }
+        let next_offset = if has_more && pagination.cursor.is_none() {
+            Some(offset + limit)
+        } else {
+            None
+        };
+        let next_cursor = trades
+            .last()
+            .filter(|_| has_more && by_time)
+            .map(|trade| Cursor::new(trade.timestamp, trade.id.clone()));
+
+        Ok(Paginated {
+            items: trades,
+            total_count,
+            next_offset,
+            has_more,
+            next_cursor,
+        })
+    }
+
+    fn export_trades(
+        &self,
+        filter: TradeFilter,
+        after: Option<&Cursor>,
+        limit: i64,
+    ) -> Result<Vec<Trade>> {
+        let conn = &mut *self.get_conn()?;
+        let mut query = filtered_trades(filter);
+        if let Some(after) = after {
+            query = query.filter(
+                trades::timestamp.gt(after.timestamp).or(trades::timestamp
+                    .eq(after.timestamp)
+                    .and(trades::id.gt(after.id.clone()))),
+            );
+        }
+        Ok(query
+            .order((trades::timestamp.asc(), trades::id.asc()))
+            .limit(limit)
+            .load::<Trade>(conn)?)
+    }
+
+    fn get_order_fills(&self, order_id: &str) -> Result<Vec<OrderFill>> {
+        let conn = &mut *self.get_conn()?;
+
+        // Trades of one second have no order among them but their id
+        let trades = trades::table
+            .filter(
+                trades::buyer_order_id
+                    .eq(order_id)
+                    .or(trades::seller_order_id.eq(order_id)),
+            )
+            .order((trades::timestamp.asc(), trades::id.asc()))
+            .load::<Trade>(conn)
+            .context("Failed to load order fills")?;
+
+        let mut filled_base = BigDecimal::from(0);
+        Ok(trades
+            .into_iter()
+            .map(|trade| {
+                let (side, fee) = if trade.buyer_order_id == order_id {
+                    (OrderSide::Buy, trade.buyer_fee.clone())
+                } else {
+                    (OrderSide::Sell, trade.seller_fee.clone())
+                };
+                let role = if trade.taker_side == side.as_str() {
+                    MarketRole::Taker
+                } else {
+                    MarketRole::Maker
+                };
+                filled_base += &trade.base_amount;
+                OrderFill {
+                    trade,
+                    side,
+                    role,
+                    fee,
+                    filled_base: filled_base.clone(),
+                }
+            })
+            .collect())
+    }
+
+    fn get_trade_detail(&self, trade_id: &str) -> Result<Option<TradeDetail>> {
+        let conn = &mut *self.get_conn()?;
+
+        let Some(trade) = trades::table
+            .find(trade_id)
+            .first::<Trade>(conn)
+            .optional()?
+        else {
+            return Ok(None);
+        };
+        let balance_snapshots = trade_balance_snapshots::table
+            .filter(trade_balance_snapshots::trade_id.eq(trade_id))
+            .order((
+                trade_balance_snapshots::user_id,
+                trade_balance_snapshots::asset,
+            ))
+            .load(conn)?;
+
+        Ok(Some(TradeDetail {
+            trade,
+            balance_snapshots,
+        }))
+    }
+
+    fn get_user_fees_paid(
+        &self,
+        user_id: &str,
+        start_time: Option<i64>,
+        end_time: Option<i64>,
+    ) -> Result<Vec<UserFeePaid>> {
+        let conn = &mut *self.get_conn()?;
+
+        let mut query = trades::table
+            .inner_join(markets::table)
+            .filter(
+                trades::buyer_user_id
+                    .eq(user_id)
+                    .or(trades::seller_user_id.eq(user_id)),
+            )
+            .select((
+                trades::buyer_user_id,
+                trades::buyer_fee,
+                trades::seller_fee,
+                markets::base_asset,
+                markets::quote_asset,
+            ))
+            .into_boxed();
+        if let Some(start_time) = start_time {
+            query = query.filter(trades::timestamp.ge(start_time));
+        }
+        if let Some(end_time) = end_time {
+            query = query.filter(trades::timestamp.le(end_time));
+        }
+        let trades = query
+            .load::<(String, BigDecimal, BigDecimal, String, String)>(conn)
+            .context("Failed to sum fees")?;
+
+        // Buyers pay their fee in the base asset, sellers in the quote asset. An asset can be
+        // paid both ways across markets.
+        let mut fees_by_asset: BTreeMap<String, BigDecimal> = BTreeMap::new();
+        for (buyer_user_id, buyer_fee, seller_fee, base_asset, quote_asset) in trades {
+            let (asset, fee) = match buyer_user_id == user_id {
+                true => (base_asset, buyer_fee),
+                false => (quote_asset, seller_fee),
+            };
+            *fees_by_asset
+                .entry(asset)
+                .or_insert_with(|| BigDecimal::from(0)) += fee;
+        }
+
+        Ok(fees_by_asset
+            .into_iter()
+            .map(|(asset, amount)| UserFeePaid { asset, amount })
+            .collect())
+    }
+
+    fn get_user_traded_volume(
+        &self,
+        user_id: &str,
+        market_id: &str,
+        start_time: i64,
+    ) -> Result<BigDecimal> {
+        let conn = &mut *self.get_conn()?;
+
+        let amounts = trades::table
+            .filter(trades::market_id.eq(market_id))
+            .filter(
+                trades::buyer_user_id
+                    .eq(user_id)
+                    .or(trades::seller_user_id.eq(user_id)),
+            )
+            .filter(trades::timestamp.ge(start_time))
+            .select(trades::quote_amount)
+            .load::<BigDecimal>(conn)
+            .context("Failed to sum traded volume")?;
+
+        Ok(amounts.into_iter().sum())
+    }
+
+    fn get_user_traded_volumes(
+        &self,
+        user_id: &str,
+        start_time: i64,
+    ) -> Result<Vec<UserTradedVolume>> {
+        let conn = &mut *self.get_conn()?;
+
+        let amounts = trades::table
+            .inner_join(markets::table)
+            .filter(
+                trades::buyer_user_id
+                    .eq(user_id)
+                    .or(trades::seller_user_id.eq(user_id)),
+            )
+            .filter(trades::timestamp.ge(start_time))
+            .select((markets::quote_asset, trades::quote_amount))
+            .load::<(String, BigDecimal)>(conn)
+            .context("Failed to sum traded volumes")?;
+
+        let mut volumes: BTreeMap<String, BigDecimal> = BTreeMap::new();
+        for (asset, amount) in amounts {
+            *volumes.entry(asset).or_default() += amount;
+        }
+        Ok(volumes
+            .into_iter()
+            .map(|(asset, volume)| UserTradedVolume { asset, volume })
+            .collect())
+    }
+}
+
+impl TradeDatabaseWriter for SqliteRepository {
+    fn execute_limit_trade(
+        &self,
+        is_buyer_taker: bool,
+        market_id: String,
+        base_asset: String,
+        quote_asset: String,
+        buyer_user_id: String,
+        seller_user_id: String,
+        buyer_order_id: String,
+        seller_order_id: String,
+        price: BigDecimal,
+        base_amount: BigDecimal,
+        quote_amount: BigDecimal,
+        buyer_fee_rate: BigDecimal,
+        seller_fee_rate: BigDecimal,
+    ) -> Result<NewTrade> {
+        let fill = TradeFill {
+            is_buyer_taker,
+            buyer_user_id,
+            seller_user_id,
+            buyer_order_id,
+            seller_order_id,
+            price,
+            base_amount,
+            quote_amount,
+            buyer_fee_rate,
+            seller_fee_rate,
+            trade_id: None,
+            timestamp: None,
+        };
+        self.execute_limit_trades(&market_id, &base_asset, &quote_asset, &[fill])?
+            .pop()
+            .context("Settlement returned no trade")
+    }
+
+    fn execute_limit_trades(
+        &self,
+        market_id: &str,
+        base_asset: &str,
+        quote_asset: &str,
+        fills: &[TradeFill],
+    ) -> Result<Vec<NewTrade>> {
+        check_fills(fills)?;
+        if fills.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let conn = &mut *self.get_conn()?;
+        conn.transaction(|conn| self.settle_fills(conn, market_id, base_asset, quote_asset, fills))
+    }
+
+    fn execute_journaled_trades(
+        &self,
+        market_id: &str,
+        base_asset: &str,
+        quote_asset: &str,
+        fills: &[TradeFill],
+        sequence: i64,
+    ) -> Result<Vec<NewTrade>> {
+        check_fills(fills)?;
+
+        let conn = &mut *self.get_conn()?;
+        conn.transaction(|conn| {
+            let trades = match fills.is_empty() {
+                true => Vec::new(),
+                false => self.settle_fills(conn, market_id, base_asset, quote_asset, fills)?,
+            };
+            store_applied_sequence(conn, market_id, sequence)?;
+            Ok(trades)
+        })
+    }
+
+    fn create_order_with_trades(
+        &self,
+        order_data: NewOrder,
+        base_asset: &str,
+        quote_asset: &str,
+        fills: &[TradeFill],
+    ) -> Result<(Order, Vec<NewTrade>)> {
+        check_fills(fills)?;
+
+        let conn = &mut *self.get_conn()?;
+        conn.transaction(|conn| {
+            let order = insert_order(conn, &order_data)?;
+            let trades = match fills.is_empty() {
+                true => Vec::new(),
+                false => {
+                    self.settle_fills(conn, &order_data.market_id, base_asset, quote_asset, fills)?
+                }
+            };
+            Ok((order, trades))
+        })
+    }
+}
@@ -0,0 +1,316 @@
+use super::SqliteRepository;
+use super::schema::*;
+use super::types::dec;
+use super::wallets::deposit_funds;
+use crate::models::models::*;
+use crate::provider::{LedgerDatabaseWriter, TransferDatabaseReader, TransferDatabaseWriter};
+use crate::repository::TransferError;
+use anyhow::{Context, Result};
+use bigdecimal::BigDecimal;
+use common::utils::{get_utc_now_millis, get_uuid_string};
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+
+/// Fetches a withdrawal and checks it is in one of the `expected` states.
+fn lock_withdrawal(
+    conn: &mut SqliteConnection,
+    transfer_id: &str,
+    expected: &[TransferStatus],
+    expected_name: &'static str,
+) -> Result<Transfer> {
+    let transfer = transfers::table
+        .find(transfer_id)
+        .filter(transfers::kind.eq(TransferKind::Withdrawal.as_str()))
+        .first::<Transfer>(conn)
+        .optional()
+        .context("Failed to fetch withdrawal")?
+        .ok_or_else(|| TransferError::NotFound(transfer_id.to_string()))?;
+
+    if !expected
+        .iter()
+        .any(|status| status.as_str() == transfer.status)
+    {
+        return Err(TransferError::InvalidStatus {
+            transfer_id: transfer.id,
+            status: transfer.status,
+            expected: expected_name,
+        }
+        .into());
+    }
+    Ok(transfer)
+}
+
+/// Moves the funds of a withdrawal out of the reserved balance. What leaves it goes to the
+/// `to` account, either back to available or out of the exchange.
+fn release_reserved(
+    conn: &mut SqliteConnection,
+    transfer: &Transfer,
+    to: LedgerAccount,
+) -> Result<Wallet> {
+    let wallet = wallets::table
+        .find((&transfer.user_id, &transfer.asset))
+        .first::<Wallet>(conn)
+        .context("Failed to fetch wallet")?;
+    if wallet.reserved < transfer.amount {
+        anyhow::bail!(
+            "Reserved balance of {} {} does not cover withdrawal {}",
+            wallet.reserved,
+            transfer.asset,
+            transfer.id
+        );
+    }
+
+    let (available, withdrawn) = match to {
+        LedgerAccount::Available => (&wallet.available + &transfer.amount, wallet.total_withdrawn),
+        _ => (wallet.available, &wallet.total_withdrawn + &transfer.amount),
+    };
+    let wallet = diesel::update(wallets::table.find((&transfer.user_id, &transfer.asset)))
+        .set((
+            wallets::available.eq(dec(&available)),
+            wallets::reserved.eq(dec(&(&wallet.reserved - &transfer.amount))),
+            wallets::total_withdrawn.eq(dec(&withdrawn)),
+            wallets::update_time.eq(get_utc_now_millis()),
+        ))
+        .get_result::<Wallet>(conn)
+        .context("Failed to release reserved balance")?;
+
+    conn.record_ledger_transfers(
+        Some(&transfer.id),
+        &[LedgerTransfer::new(
+            LedgerEntryKind::Withdrawal,
+            &transfer.asset,
+            (&transfer.user_id, LedgerAccount::Reserved),
+            (&transfer.user_id, to),
+            transfer.amount.clone(),
+        )],
+    )?;
+    Ok(wallet)
+}
+
+/// Moves a withdrawal to `status`. Only completion sets an external id and only rejection a
+/// reason, so neither overwrites a value set before.
+fn set_status(
+    conn: &mut SqliteConnection,
+    transfer_id: &str,
+    status: TransferStatus,
+    external_id: Option<&str>,
+    reject_reason: Option<&str>,
+) -> Result<Transfer> {
+    diesel::update(transfers::table.find(transfer_id))
+        .set((
+            transfers::status.eq(status.as_str()),
+            transfers::external_id.eq(external_id),
+            transfers::reject_reason.eq(reject_reason),
+            transfers::update_time.eq(get_utc_now_millis()),
+        ))
+        .get_result(conn)
+        .context("Failed to update transfer status")
+}
+
+fn insert_transfer(conn: &mut SqliteConnection, transfer: &NewTransfer) -> Result<Transfer> {
+    let result = diesel::insert_into(transfers::table)
+        .values((
+            transfers::id.eq(&transfer.id),
+            transfers::user_id.eq(&transfer.user_id),
+            transfers::asset.eq(&transfer.asset),
+            transfers::kind.eq(&transfer.kind),
+            transfers::amount.eq(dec(&transfer.amount)),
+            transfers::status.eq(&transfer.status),
+            transfers::address.eq(&transfer.address),
+            transfers::external_id.eq(&transfer.external_id),
+            transfers::reject_reason.eq(&transfer.reject_reason),
+            transfers::create_time.eq(transfer.create_time),
+            transfers::update_time.eq(transfer.update_time),
+        ))
+        .get_result(conn)?;
+    Ok(result)
+}
+
+impl TransferDatabaseReader for SqliteRepository {
+    fn get_transfer(&self, transfer_id: &str) -> Result<Option<Transfer>> {
+        let conn = &mut *self.get_conn()?;
+
+        let result = transfers::table.find(transfer_id).first(conn).optional()?;
+
+        Ok(result)
+    }
+
+    fn get_user_transfers(&self, user_id: &str) -> Result<Vec<Transfer>> {
+        let conn = &mut *self.get_conn()?;
+
+        transfers::table
+            .filter(transfers::user_id.eq(user_id))
+            .order((transfers::create_time.desc(), transfers::id.desc()))
+            .load(conn)
+            .context("Failed to fetch user transfers")
+    }
+}
+
+impl TransferDatabaseWriter for SqliteRepository {
+    fn complete_deposit(
+        &self,
+        user_id: &str,
+        asset: &str,
+        amount: BigDecimal,
+        external_id: &str,
+    ) -> Result<Transfer> {
+        let conn = &mut *self.get_conn()?;
+        conn.transaction(|conn| {
+            let recorded = transfers::table
+                .filter(transfers::kind.eq(TransferKind::Deposit.as_str()))
+                .filter(transfers::asset.eq(asset))
+                .filter(transfers::external_id.eq(external_id))
+                .first::<Transfer>(conn)
+                .optional()
+                .context("Failed to look up deposit")?;
+            if let Some(recorded) = recorded {
+                return Ok(recorded);
+            }
+
+            let now = get_utc_now_millis();
+            let transfer = insert_transfer(
+                conn,
+                &NewTransfer {
+                    id: get_uuid_string(),
+                    user_id: user_id.to_string(),
+                    asset: asset.to_string(),
+                    kind: TransferKind::Deposit.as_str().to_string(),
+                    amount: amount.clone(),
+                    status: TransferStatus::Completed.as_str().to_string(),
+                    address: None,
+                    external_id: Some(external_id.to_string()),
+                    reject_reason: None,
+                    create_time: now,
+                    update_time: now,
+                },
+            )
+            .context("Failed to record deposit")?;
+
+            deposit_funds(conn, user_id, asset, &amount)?;
+            conn.record_ledger_transfers(
+                Some(&transfer.id),
+                &[LedgerTransfer::new(
+                    LedgerEntryKind::Deposit,
+                    asset,
+                    (user_id, LedgerAccount::External),
+                    (user_id, LedgerAccount::Available),
+                    amount,
+                )],
+            )?;
+            Ok(transfer)
+        })
+    }
+
+    fn request_withdrawal(
+        &self,
+        user_id: &str,
+        asset: &str,
+        amount: BigDecimal,
+        address: &str,
+    ) -> Result<Transfer> {
+        let conn = &mut *self.get_conn()?;
+        conn.transaction(|conn| {
+            let wallet = wallets::table
+                .find((user_id, asset))
+                .first::<Wallet>(conn)
+                .optional()
+                .context("Failed to fetch wallet")?;
+            let available = wallet
+                .as_ref()
+                .map(|wallet| wallet.available.clone())
+                .unwrap_or_default();
+            let Some(wallet) = wallet.filter(|_| available >= amount) else {
+                return Err(TransferError::InsufficientBalance {
+                    asset: asset.to_string(),
+                    required: amount,
+                    available,
+                }
+                .into());
+            };
+
+            let now = get_utc_now_millis();
+            diesel::update(wallets::table.find((user_id, asset)))
+                .set((
+                    wallets::available.eq(dec(&(&wallet.available - &amount))),
+                    wallets::reserved.eq(dec(&(&wallet.reserved + &amount))),
+                    wallets::update_time.eq(now),
+                ))
+                .execute(conn)
+                .context("Failed to reserve balance")?;
+
+            let transfer = insert_transfer(
+                conn,
+                &NewTransfer {
+                    id: get_uuid_string(),
+                    user_id: user_id.to_string(),
+                    asset: asset.to_string(),
+                    kind: TransferKind::Withdrawal.as_str().to_string(),
+                    amount: amount.clone(),
+                    status: TransferStatus::Pending.as_str().to_string(),
+                    address: Some(address.to_string()),
+                    external_id: None,
+                    reject_reason: None,
+                    create_time: now,
+                    update_time: now,
+                },
+            )
+            .context("Failed to record withdrawal")?;
+
+            conn.record_ledger_transfers(
+                Some(&transfer.id),
+                &[LedgerTransfer::new(
+                    LedgerEntryKind::Withdrawal,
+                    asset,
+                    (user_id, LedgerAccount::Available),
+                    (user_id, LedgerAccount::Reserved),
+                    amount,
+                )],
+            )?;
+            Ok(transfer)
+        })
+    }
+
+    fn approve_withdrawal(&self, transfer_id: &str) -> Result<Transfer> {
+        let conn = &mut *self.get_conn()?;
+        conn.transaction(|conn| {
+            lock_withdrawal(conn, transfer_id, &[TransferStatus::Pending], "PENDING")?;
+            set_status(conn, transfer_id, TransferStatus::Approved, None, None)
+        })
+    }
+
+    fn reject_withdrawal(&self, transfer_id: &str, reason: &str) -> Result<Transfer> {
+        let conn = &mut *self.get_conn()?;
+        conn.transaction(|conn| {
+            let transfer = lock_withdrawal(
+                conn,
+                transfer_id,
+                &[TransferStatus::Pending, TransferStatus::Approved],
+                "PENDING or APPROVED",
+            )?;
+            release_reserved(conn, &transfer, LedgerAccount::Available)?;
+            set_status(
+                conn,
+                transfer_id,
+                TransferStatus::Rejected,
+                None,
+                Some(reason),
+            )
+        })
+    }
+
+    fn complete_withdrawal(&self, transfer_id: &str, external_id: &str) -> Result<Transfer> {
+        let conn = &mut *self.get_conn()?;
+        conn.transaction(|conn| {
+            let transfer =
+                lock_withdrawal(conn, transfer_id, &[TransferStatus::Approved], "APPROVED")?;
+            release_reserved(conn, &transfer, LedgerAccount::External)?;
+            set_status(
+                conn,
+                transfer_id,
+                TransferStatus::Completed,
+                Some(external_id),
+                None,
+            )
+        })
+    }
+}
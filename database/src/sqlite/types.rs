@@ -0,0 +1,51 @@
+use bigdecimal::{BigDecimal, RoundingMode};
+use diesel::deserialize::{self, FromSql};
+use diesel::expression::AsExpression;
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use diesel::sql_types::Text;
+use diesel::sqlite::{Sqlite, SqliteValue};
+use diesel::{QueryId, SqlType};
+use std::str::FromStr;
+
+/// Digits kept after the point, like the `DECIMAL(30, 8)` columns of the Postgres schema
+const SCALE: i64 = 8;
+
+/// A decimal column, stored as text. SQLite's own numeric type goes through a double and would
+/// lose the digits balances are made of.
+///
+/// Values are bound through [`dec`]. Text does not compare or add as numbers do, so queries on
+/// these columns only filter on equality and leave sorting and sums to the caller.
+#[derive(Debug, Clone, Copy, Default, QueryId, SqlType)]
+#[diesel(sqlite_type(name = "Text"))]
+pub struct TextDecimal;
+
+/// A value bound to a [`TextDecimal`] column
+#[derive(Debug, AsExpression)]
+#[diesel(sql_type = TextDecimal)]
+pub struct DecimalValue(BigDecimal);
+
+impl ToSql<TextDecimal, Sqlite> for DecimalValue {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> serialize::Result {
+        // Rounded as Postgres stores it, so equal amounts are equal text too
+        let value = self.0.with_scale_round(SCALE, RoundingMode::HalfUp);
+        out.set_value(value.to_plain_string());
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<TextDecimal, Sqlite> for BigDecimal {
+    fn from_sql(value: SqliteValue<'_, '_, '_>) -> deserialize::Result<Self> {
+        let text = <String as FromSql<Text, Sqlite>>::from_sql(value)?;
+        Ok(BigDecimal::from_str(&text)?)
+    }
+}
+
+/// `value` bound to a [`TextDecimal`] column
+pub fn dec(value: &BigDecimal) -> DecimalValue {
+    DecimalValue(value.clone())
+}
+
+/// `value` bound to a nullable [`TextDecimal`] column
+pub fn dec_opt(value: &Option<BigDecimal>) -> Option<DecimalValue> {
+    value.as_ref().map(dec)
+}
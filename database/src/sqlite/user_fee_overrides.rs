@@ -0,0 +1,62 @@
+use super::SqliteRepository;
+use super::schema::*;
+use super::types::dec;
+use crate::models::models::*;
+use crate::provider::{UserFeeOverrideDatabaseReader, UserFeeOverrideDatabaseWriter};
+use anyhow::{Context, Result};
+use bigdecimal::BigDecimal;
+use common::utils::get_utc_now_millis;
+use diesel::prelude::*;
+
+impl UserFeeOverrideDatabaseReader for SqliteRepository {
+    fn get_user_fee_override(&self, user_id: &str) -> Result<Option<UserFeeOverride>> {
+        let conn = &mut *self.get_conn()?;
+
+        let result = user_fee_overrides::table
+            .find(user_id)
+            .first(conn)
+            .optional()?;
+
+        Ok(result)
+    }
+}
+
+impl UserFeeOverrideDatabaseWriter for SqliteRepository {
+    fn set_user_fee_override(
+        &self,
+        user_id: &str,
+        maker_fee: BigDecimal,
+        taker_fee: BigDecimal,
+    ) -> Result<UserFeeOverride> {
+        let conn = &mut *self.get_conn()?;
+
+        let now = get_utc_now_millis();
+        diesel::insert_into(user_fee_overrides::table)
+            .values((
+                user_fee_overrides::user_id.eq(user_id),
+                user_fee_overrides::maker_fee.eq(dec(&maker_fee)),
+                user_fee_overrides::taker_fee.eq(dec(&taker_fee)),
+                user_fee_overrides::create_time.eq(now),
+                user_fee_overrides::update_time.eq(now),
+            ))
+            .on_conflict(user_fee_overrides::user_id)
+            .do_update()
+            .set((
+                user_fee_overrides::maker_fee.eq(dec(&maker_fee)),
+                user_fee_overrides::taker_fee.eq(dec(&taker_fee)),
+                user_fee_overrides::update_time.eq(now),
+            ))
+            .get_result(conn)
+            .context("Failed to store user fee override")
+    }
+
+    fn delete_user_fee_override(&self, user_id: &str) -> Result<bool> {
+        let conn = &mut *self.get_conn()?;
+
+        let deleted = diesel::delete(user_fee_overrides::table.find(user_id))
+            .execute(conn)
+            .context("Failed to delete user fee override")?;
+
+        Ok(deleted > 0)
+    }
+}
@@ -0,0 +1,419 @@
+use super::SqliteRepository;
+use super::schema::*;
+use super::types::dec;
+use crate::filters::WalletFilter;
+use crate::models::models::*;
+use crate::provider::{LedgerDatabaseWriter, WalletDatabaseReader, WalletDatabaseWriter};
+use anyhow::{Context, Result, bail};
+use bigdecimal::BigDecimal;
+use common::db::pagination::{Paginated, Pagination};
+use common::error::BitradeError;
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use std::collections::HashMap;
+
+/// Creates the wallet of `user_id` in `asset` with the given balances and nothing reserved.
+pub(super) fn insert_wallet(
+    conn: &mut SqliteConnection,
+    user_id: &str,
+    asset: &str,
+    available: &BigDecimal,
+    locked: &BigDecimal,
+    total_deposited: &BigDecimal,
+) -> Result<Wallet> {
+    let zero = BigDecimal::from(0);
+    let result = diesel::insert_into(wallets::table)
+        .values((
+            wallets::user_id.eq(user_id),
+            wallets::asset.eq(asset),
+            wallets::available.eq(dec(available)),
+            wallets::locked.eq(dec(locked)),
+            wallets::reserved.eq(dec(&zero)),
+            wallets::total_deposited.eq(dec(total_deposited)),
+            wallets::total_withdrawn.eq(dec(&zero)),
+            wallets::update_time.eq(common::utils::get_utc_now_millis()),
+        ))
+        .get_result(conn)?;
+    Ok(result)
+}
+
+fn update_or_create_balance(
+    conn: &mut SqliteConnection,
+    user_id: &str,
+    asset: &str,
+    available_delta: BigDecimal,
+    locked_delta: BigDecimal,
+) -> Result<Wallet> {
+    let wallet_option = wallets::table
+        .find((user_id, asset))
+        .first::<Wallet>(conn)
+        .optional()?;
+
+    match wallet_option {
+        Some(wallet) => {
+            let new_available = &wallet.available + available_delta.clone();
+            let new_locked = wallet.locked + locked_delta;
+
+            if new_available < 0 {
+                return Err(BitradeError::InsufficientBalance {
+                    asset: asset.to_string(),
+                    required: -available_delta,
+                    available: wallet.available,
+                }
+                .into());
+            }
+            if new_locked < 0 {
+                bail!("Insufficient locked balance");
+            }
+
+            let result = diesel::update(wallets::table.find((user_id, asset)))
+                .set((
+                    wallets::available.eq(dec(&new_available)),
+                    wallets::locked.eq(dec(&new_locked)),
+                    wallets::update_time.eq(common::utils::get_utc_now_millis()),
+                ))
+                .get_result(conn)?;
+
+            Ok(result)
+        }
+        None => {
+            if available_delta < 0 {
+                return Err(BitradeError::InsufficientBalance {
+                    asset: asset.to_string(),
+                    required: -available_delta,
+                    available: BigDecimal::from(0),
+                }
+                .into());
+            }
+            if locked_delta < 0 {
+                bail!("Insufficient locked balance");
+            }
+
+            insert_wallet(
+                conn,
+                user_id,
+                asset,
+                &available_delta,
+                &locked_delta,
+                &BigDecimal::from(0),
+            )
+        }
+    }
+}
+
+/// Credits `amount` to the available balance and counts it as deposited.
+pub(super) fn deposit_funds(
+    conn: &mut SqliteConnection,
+    user_id: &str,
+    asset: &str,
+    amount: &BigDecimal,
+) -> Result<Wallet> {
+    let wallet = wallets::table
+        .find((user_id, asset))
+        .first::<Wallet>(conn)
+        .optional()?;
+
+    match wallet {
+        Some(wallet) => {
+            let new_wallet = diesel::update(wallets::table.find((user_id, asset)))
+                .set((
+                    wallets::available.eq(dec(&(wallet.available + amount))),
+                    wallets::total_deposited.eq(dec(&(wallet.total_deposited + amount))),
+                    wallets::update_time.eq(common::utils::get_utc_now_millis()),
+                ))
+                .get_result(conn)?;
+
+            Ok(new_wallet)
+        }
+        None => insert_wallet(conn, user_id, asset, amount, &BigDecimal::from(0), amount),
+    }
+}
+
+fn withdraw_funds(
+    conn: &mut SqliteConnection,
+    user_id: &str,
+    asset: &str,
+    amount: &BigDecimal,
+) -> Result<Wallet> {
+    let balance = wallets::table
+        .find((user_id, asset))
+        .first::<Wallet>(conn)
+        .optional()?;
+
+    let insufficient = |available: BigDecimal| BitradeError::InsufficientBalance {
+        asset: asset.to_string(),
+        required: amount.clone(),
+        available,
+    };
+    match balance {
+        Some(balance) => {
+            if balance.available < *amount {
+                return Err(insufficient(balance.available).into());
+            }
+
+            let new_balance = diesel::update(wallets::table.find((user_id, asset)))
+                .set((
+                    wallets::available.eq(dec(&(balance.available - amount))),
+                    wallets::total_withdrawn.eq(dec(&(balance.total_withdrawn + amount))),
+                    wallets::update_time.eq(common::utils::get_utc_now_millis()),
+                ))
+                .get_result(conn)?;
+
+            Ok(new_balance)
+        }
+        None => Err(insufficient(BigDecimal::from(0)).into()),
+    }
+}
+
+/// Moves `amount` from the available to the locked balance of a wallet.
+pub(super) fn lock_funds(
+    conn: &mut SqliteConnection,
+    user_id: &str,
+    asset: &str,
+    amount: &BigDecimal,
+    reference_id: Option<&str>,
+) -> Result<Wallet> {
+    let wallet = update_or_create_balance(conn, user_id, asset, -amount.clone(), amount.clone())?;
+    conn.record_ledger_transfers(
+        reference_id,
+        &[LedgerTransfer::new(
+            LedgerEntryKind::Lock,
+            asset,
+            (user_id, LedgerAccount::Available),
+            (user_id, LedgerAccount::Locked),
+            amount.clone(),
+        )],
+    )?;
+    Ok(wallet)
+}
+
+/// Records `amount` of `asset` going back from the locked to the available balance of a user.
+pub(super) fn record_unlock(
+    conn: &mut SqliteConnection,
+    user_id: &str,
+    asset: &str,
+    amount: &BigDecimal,
+    reference_id: Option<&str>,
+) -> Result<()> {
+    conn.record_ledger_transfers(
+        reference_id,
+        &[LedgerTransfer::new(
+            LedgerEntryKind::Unlock,
+            asset,
+            (user_id, LedgerAccount::Locked),
+            (user_id, LedgerAccount::Available),
+            amount.clone(),
+        )],
+    )?;
+    Ok(())
+}
+
+impl WalletDatabaseReader for SqliteRepository {
+    fn get_wallet(&self, user_id: &str, asset: &str) -> Result<Option<Wallet>> {
+        let conn = &mut *self.get_conn()?;
+
+        let result = wallets::table
+            .find((user_id, asset))
+            .first(conn)
+            .optional()?;
+
+        Ok(result)
+    }
+
+    fn get_user_wallets(&self, user_id: &str) -> Result<Vec<Wallet>> {
+        let conn = &mut *self.get_conn()?;
+
+        wallets::table
+            .filter(wallets::user_id.eq(user_id))
+            .order(wallets::asset.asc())
+            .load(conn)
+            .context("Failed to load user wallets")
+    }
+
+    fn get_wallet_changes(&self, user_id: &str, since_time: i64) -> Result<Vec<Wallet>> {
+        let conn = &mut *self.get_conn()?;
+
+        wallets::table
+            .filter(wallets::user_id.eq(user_id))
+            .filter(wallets::update_time.gt(since_time))
+            .order((wallets::update_time.asc(), wallets::asset.asc()))
+            .load::<Wallet>(conn)
+            .context("Failed to fetch wallet changes")
+    }
+
+    fn list_wallets(
+        &self,
+        filter: WalletFilter,
+        pagination: Option<Pagination>,
+    ) -> Result<Paginated<Wallet>> {
+        let conn = &mut *self.get_conn()?;
+        let pagination = pagination.unwrap_or_default();
+        let limit = pagination.limit.unwrap_or(10).min(100);
+        let offset = pagination.offset.unwrap_or(0);
+        let order_by = pagination
+            .order_by
+            .unwrap_or_else(|| "update_time".to_string());
+        let order_direction = pagination
+            .order_direction
+            .unwrap_or_else(|| "desc".to_string());
+
+        let mut query = wallets::table.into_boxed();
+        let mut count_query = wallets::table.into_boxed();
+        if let Some(user_id) = filter.user_id {
+            query = query.filter(wallets::user_id.eq(user_id.clone()));
+            count_query = count_query.filter(wallets::user_id.eq(user_id));
+        }
+        if let Some(asset) = filter.asset {
+            query = query.filter(wallets::asset.eq(asset.clone()));
+            count_query = count_query.filter(wallets::asset.eq(asset));
+        }
+        let total: i64 = count_query.select(diesel::dsl::count_star()).first(conn)?;
+
+        query = match (order_by.as_str(), order_direction.to_lowercase().as_str()) {
+            ("update_time", "desc") => query.order(wallets::update_time.desc()),
+            ("update_time", "asc") => query.order(wallets::update_time.asc()),
+            ("asset", "desc") => query.order(wallets::asset.desc()),
+            ("asset", "asc") => query.order(wallets::asset.asc()),
+            ("user_id", "desc") => query.order(wallets::user_id.desc()),
+            ("user_id", "asc") => query.order(wallets::user_id.asc()),
+            (field, direction) => {
+                bail!(
+                    "Invalid order parameters: field '{}' or direction '{}'",
+                    field,
+                    direction
+                );
+            }
+        };
+
+        let result = query.offset(offset).limit(limit).load::<Wallet>(conn)?;
+
+        Ok(Paginated {
+            items: result,
+            total_count: total,
+            next_offset: None,
+            has_more: false,
+            next_cursor: None,
+        })
+    }
+}
+
+impl WalletDatabaseWriter for SqliteRepository {
+    fn lock_balance(&self, user_id: &str, asset: &str, amount: BigDecimal) -> Result<Wallet> {
+        let conn = &mut *self.get_conn()?;
+        conn.transaction(|conn| lock_funds(conn, user_id, asset, &amount, None))
+    }
+
+    fn unlock_balance(&self, user_id: &str, asset: &str, amount: BigDecimal) -> Result<Wallet> {
+        let conn = &mut *self.get_conn()?;
+        conn.transaction(|conn| {
+            let wallet =
+                update_or_create_balance(conn, user_id, asset, amount.clone(), -amount.clone())?;
+            record_unlock(conn, user_id, asset, &amount, None)?;
+            Ok(wallet)
+        })
+    }
+
+    fn deposit_balance(&self, user_id: &str, asset: &str, amount: BigDecimal) -> Result<Wallet> {
+        let conn = &mut *self.get_conn()?;
+        conn.transaction(|conn| {
+            let wallet = deposit_funds(conn, user_id, asset, &amount)?;
+            conn.record_ledger_transfers(
+                None,
+                &[LedgerTransfer::new(
+                    LedgerEntryKind::Deposit,
+                    asset,
+                    (user_id, LedgerAccount::External),
+                    (user_id, LedgerAccount::Available),
+                    amount.clone(),
+                )],
+            )?;
+            Ok(wallet)
+        })
+    }
+
+    fn withdraw_balance(&self, user_id: &str, asset: &str, amount: BigDecimal) -> Result<Wallet> {
+        let conn = &mut *self.get_conn()?;
+        conn.transaction(|conn| {
+            let wallet = withdraw_funds(conn, user_id, asset, &amount)?;
+            conn.record_ledger_transfers(
+                None,
+                &[LedgerTransfer::new(
+                    LedgerEntryKind::Withdrawal,
+                    asset,
+                    (user_id, LedgerAccount::Available),
+                    (user_id, LedgerAccount::External),
+                    amount.clone(),
+                )],
+            )?;
+            Ok(wallet)
+        })
+    }
+
+    /// Releases what the user's wallets hold locked beyond their open orders.
+    fn release_orphaned_locks(&self, user_id: &str) -> Result<Vec<Wallet>> {
+        let conn = &mut *self.get_conn()?;
+        conn.transaction::<Vec<Wallet>, anyhow::Error, _>(|conn| {
+            let user_wallets = wallets::table
+                .filter(wallets::user_id.eq(user_id))
+                .load::<Wallet>(conn)
+                .context("Failed to fetch wallets")?;
+
+            let active_orders = orders::table
+                .inner_join(markets::table)
+                .filter(orders::user_id.eq(user_id))
+                .filter(orders::status.eq_any(&[
+                    OrderStatus::Open.as_str(),
+                    OrderStatus::PartiallyFilled.as_str(),
+                ]))
+                .select((
+                    orders::side,
+                    orders::remained_base,
+                    orders::remained_quote,
+                    markets::base_asset,
+                    markets::quote_asset,
+                ))
+                .load::<(String, BigDecimal, BigDecimal, String, String)>(conn)
+                .context("Failed to fetch active orders")?;
+
+            // Sum the amount each asset is expected to have locked by open orders
+            let mut backed_amounts: HashMap<String, BigDecimal> = HashMap::new();
+            for (side, remained_base, remained_quote, base_asset, quote_asset) in active_orders {
+                let order_side = OrderSide::from_str(&side)
+                    .map_err(|e| anyhow::anyhow!("Failed to parse order side: {}", e))?;
+                let (asset, amount) = match order_side {
+                    OrderSide::Buy => (quote_asset, remained_quote),
+                    OrderSide::Sell => (base_asset, remained_base),
+                };
+                *backed_amounts
+                    .entry(asset)
+                    .or_insert_with(|| BigDecimal::from(0)) += amount;
+            }
+
+            let current_time = common::utils::get_utc_now_millis();
+            let mut released_wallets = Vec::new();
+            for wallet in user_wallets {
+                let backed_amount = backed_amounts
+                    .remove(&wallet.asset)
+                    .unwrap_or_else(|| BigDecimal::from(0));
+                let orphaned_amount = &wallet.locked - &backed_amount;
+                if orphaned_amount <= 0 {
+                    continue;
+                }
+
+                let released = diesel::update(wallets::table.find((user_id, &wallet.asset)))
+                    .set((
+                        wallets::available.eq(dec(&(&wallet.available + &orphaned_amount))),
+                        wallets::locked.eq(dec(&backed_amount)),
+                        wallets::update_time.eq(current_time),
+                    ))
+                    .get_result::<Wallet>(conn)
+                    .context("Failed to release orphaned lock")?;
+                record_unlock(conn, user_id, &wallet.asset, &orphaned_amount, None)?;
+
+                released_wallets.push(released);
+            }
+
+            Ok(released_wallets)
+        })
+    }
+}
@@ -0,0 +1,74 @@
+use crate::models::models::*;
+use crate::models::schema::engine_events;
+use crate::provider::{EngineEventDatabaseReader, EngineEventDatabaseWriter};
+use crate::tests::test_db::*;
+use diesel::prelude::*;
+
+fn new_event(market_id: &str, event_type: EngineEventType) -> NewEngineEvent {
+    NewEngineEvent {
+        market_id: market_id.to_string(),
+        event_type: event_type.as_str().to_string(),
+        order_id: None,
+        payload: "{}".to_string(),
+        create_time: 0,
+    }
+}
+
+#[test]
+fn test_engine_events_after_the_checkpoint_are_pending() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repo);
+    let other_market = create_test_market(&repo);
+    assert_eq!(repo.get_applied_sequence(&market.id).unwrap(), 0);
+
+    let first = repo
+        .append_engine_event(new_event(&market.id, EngineEventType::OrderAccepted))
+        .unwrap();
+    repo.append_engine_event(new_event(&other_market.id, EngineEventType::OrderAccepted))
+        .unwrap();
+    let second = repo
+        .append_engine_event(new_event(&market.id, EngineEventType::OrderCanceled))
+        .unwrap();
+
+    repo.set_applied_sequence(&market.id, first.sequence)
+        .unwrap();
+    let applied = repo.get_applied_sequence(&market.id).unwrap();
+    let pending = repo.get_engine_events(&market.id, applied).unwrap();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].sequence, second.sequence);
+    assert_eq!(pending[0].event_type, "ORDER_CANCELED");
+
+    repo.set_applied_sequence(&market.id, second.sequence)
+        .unwrap();
+    assert_eq!(
+        repo.get_applied_sequence(&market.id).unwrap(),
+        second.sequence
+    );
+}
+
+#[test]
+fn test_engine_events_are_append_only() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repo);
+    let event = repo
+        .append_engine_event(new_event(&market.id, EngineEventType::OrdersCanceled))
+        .unwrap();
+
+    let conn = &mut repo.get_conn().unwrap();
+    assert!(
+        diesel::delete(engine_events::table.find(event.sequence))
+            .execute(conn)
+            .is_err()
+    );
+    assert!(
+        repo.append_engine_event(NewEngineEvent {
+            event_type: "ORDER_LOST".to_string(),
+            ..new_event(&market.id, EngineEventType::OrderAccepted)
+        })
+        .is_err()
+    );
+}
@@ -0,0 +1,101 @@
+use crate::provider::{
+    FeeTierDatabaseReader, FeeTierDatabaseWriter, TradeDatabaseReader,
+    UserFeeOverrideDatabaseReader, UserFeeOverrideDatabaseWriter,
+};
+use crate::tests::test_db::*;
+use bigdecimal::BigDecimal;
+use chrono::Utc;
+use common::utils::get_uuid_string;
+
+#[test]
+fn test_fee_tiers_are_replaced_by_min_volume() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repo);
+
+    repo.set_fee_tier(
+        &market.id,
+        decimal("10000"),
+        decimal("0.0008"),
+        decimal("0.001"),
+    )
+    .unwrap();
+    repo.set_fee_tier(&market.id, decimal("0"), decimal("0.001"), decimal("0.002"))
+        .unwrap();
+    // Same tier, new rates
+    let tier = repo
+        .set_fee_tier(
+            &market.id,
+            decimal("10000"),
+            decimal("0.0005"),
+            decimal("0.0009"),
+        )
+        .unwrap();
+    assert_eq!(tier.maker_fee, decimal("0.0005"));
+
+    let tiers = repo.get_fee_tiers(&market.id).unwrap();
+    let min_volumes: Vec<BigDecimal> = tiers.iter().map(|tier| tier.min_volume.clone()).collect();
+    assert_eq!(min_volumes, vec![decimal("0"), decimal("10000")]);
+    assert_eq!(tiers[1].taker_fee, decimal("0.0009"));
+
+    assert!(repo.delete_fee_tier(&market.id, &decimal("0")).unwrap());
+    assert!(!repo.delete_fee_tier(&market.id, &decimal("0")).unwrap());
+    assert_eq!(repo.get_fee_tiers(&market.id).unwrap().len(), 1);
+}
+
+#[test]
+fn test_user_traded_volume_counts_both_sides() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repo);
+    let first = create_funded_user(
+        &repo,
+        &[(&market.base_asset, "10"), (&market.quote_asset, "1000")],
+    );
+    let second = create_funded_user(
+        &repo,
+        &[(&market.base_asset, "10"), (&market.quote_asset, "1000")],
+    );
+
+    execute_test_trade(&repo, &market, &first, &second, "100", "2");
+    execute_test_trade(&repo, &market, &second, &first, "100", "1");
+
+    let since = Utc::now().timestamp() - 60;
+    assert_eq!(
+        repo.get_user_traded_volume(&first, &market.id, since)
+            .unwrap(),
+        decimal("300")
+    );
+    // Trades before the window don't count
+    assert_eq!(
+        repo.get_user_traded_volume(&first, &market.id, Utc::now().timestamp() + 60)
+            .unwrap(),
+        BigDecimal::from(0)
+    );
+}
+
+#[test]
+fn test_user_fee_override_is_replaced_and_deleted() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let user_id = get_uuid_string();
+    assert!(repo.get_user_fee_override(&user_id).unwrap().is_none());
+
+    let created = repo
+        .set_user_fee_override(&user_id, decimal("0.0001"), decimal("0.0002"))
+        .unwrap();
+    let replaced = repo
+        .set_user_fee_override(&user_id, decimal("0"), decimal("0"))
+        .unwrap();
+    assert_eq!(replaced.create_time, created.create_time);
+    assert_eq!(
+        repo.get_user_fee_override(&user_id).unwrap(),
+        Some(replaced)
+    );
+
+    assert!(repo.delete_user_fee_override(&user_id).unwrap());
+    assert!(repo.get_user_fee_override(&user_id).unwrap().is_none());
+}
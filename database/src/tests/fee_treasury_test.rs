@@ -0,0 +1,80 @@
+use crate::filters::LedgerFilter;
+use crate::provider::{FeeTreasuryDatabaseReader, FeeTreasuryDatabaseWriter, LedgerDatabaseReader};
+use crate::repository::TreasuryError;
+use crate::tests::test_db::*;
+use bigdecimal::BigDecimal;
+
+#[test]
+fn test_withdraw_from_fee_treasury_moves_fees_out() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repo);
+    let buyer_id = create_funded_user(&repo, &[(&market.quote_asset, "1000")]);
+    let seller_id = create_funded_user(&repo, &[(&market.base_asset, "10")]);
+    let trade = execute_test_trade(&repo, &market, &buyer_id, &seller_id, "100", "2");
+    let collected = trade.seller_fee.clone();
+    assert!(collected > 0);
+
+    // More than was collected is refused and leaves the treasury untouched
+    let error = repo
+        .withdraw_from_fee_treasury(
+            &market.id,
+            &market.quote_asset,
+            &collected + BigDecimal::from(1),
+            "ops-wallet",
+        )
+        .unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<TreasuryError>(),
+        Some(TreasuryError::InsufficientFees { .. })
+    ));
+
+    let half = &collected / BigDecimal::from(2);
+    let withdrawal = repo
+        .withdraw_from_fee_treasury(&market.id, &market.quote_asset, half.clone(), "ops-wallet")
+        .unwrap();
+    assert_eq!(withdrawal.destination, "ops-wallet");
+
+    let treasury = repo
+        .get_fee_treasury(&market.id, &market.quote_asset)
+        .unwrap()
+        .unwrap();
+    assert_eq!(treasury.collected_amount, &collected - &half);
+    assert_eq!(treasury.total_withdrawn, half);
+
+    let entries = repo
+        .list_ledger_entries(
+            LedgerFilter::new().reference_id(Some(withdrawal.id.clone())),
+            None,
+        )
+        .unwrap()
+        .items;
+    let mut accounts: Vec<(&str, &str)> = entries
+        .iter()
+        .map(|entry| (entry.owner_id.as_str(), entry.account.as_str()))
+        .collect();
+    accounts.sort();
+    assert_eq!(
+        accounts,
+        vec![
+            (market.id.as_str(), "EXTERNAL"),
+            (market.id.as_str(), "FEE_TREASURY")
+        ]
+    );
+
+    // Each asset of a market has its own treasury
+    let treasuries = repo.list_fee_treasuries(Some(&market.id)).unwrap();
+    let assets: Vec<&str> = treasuries.iter().map(|t| t.asset.as_str()).collect();
+    let mut expected = vec![market.base_asset.as_str(), market.quote_asset.as_str()];
+    expected.sort();
+    assert_eq!(assets, expected);
+
+    let error = repo
+        .withdraw_from_fee_treasury("missing", &market.quote_asset, half, "ops-wallet")
+        .unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<TreasuryError>(),
+        Some(TreasuryError::NotFound { .. })
+    ));
+}
@@ -0,0 +1,199 @@
+use crate::models::models::{KlineInterval, Market, NewTrade};
+use crate::provider::KlineDatabaseReader;
+use crate::repository::{Repository, record_kline_trades};
+use crate::tests::test_db::*;
+use common::utils::get_uuid_string;
+
+fn record_trade(repo: &Repository, market: &Market, timestamp: i64, price: &str, base: &str) {
+    let trade = NewTrade {
+        id: get_uuid_string(),
+        timestamp,
+        market_id: market.id.clone(),
+        price: decimal(price),
+        base_amount: decimal(base),
+        quote_amount: decimal(price) * decimal(base),
+        buyer_user_id: get_uuid_string(),
+        buyer_order_id: get_uuid_string(),
+        buyer_fee: decimal("0"),
+        seller_user_id: get_uuid_string(),
+        seller_order_id: get_uuid_string(),
+        seller_fee: decimal("0"),
+        taker_side: "BUY".to_string(),
+        is_liquidation: None,
+    };
+    record_kline_trades(&mut repo.get_conn().unwrap(), &[trade]).unwrap();
+}
+
+#[test]
+fn test_trades_fold_into_candles_of_every_interval() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repo);
+    // 2025-01-01 00:00:00 UTC
+    let day = 1_735_689_600;
+
+    record_trade(&repo, &market, day + 10, "100", "1");
+    record_trade(&repo, &market, day + 20, "105", "2");
+    record_trade(&repo, &market, day + 50, "98", "1");
+    record_trade(&repo, &market, day + 70, "101", "3");
+
+    let minutes = repo
+        .list_klines(&market.id, KlineInterval::OneMinute, None, None, 100)
+        .unwrap();
+    let summary: Vec<_> = minutes
+        .iter()
+        .map(|k| {
+            (
+                k.open_time,
+                k.open.clone(),
+                k.high.clone(),
+                k.low.clone(),
+                k.close.clone(),
+                k.volume.clone(),
+                k.trade_count,
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            (
+                day,
+                decimal("100"),
+                decimal("105"),
+                decimal("98"),
+                decimal("98"),
+                decimal("4"),
+                3
+            ),
+            (
+                day + 60,
+                decimal("101"),
+                decimal("101"),
+                decimal("101"),
+                decimal("101"),
+                decimal("3"),
+                1
+            ),
+        ]
+    );
+
+    let days = repo
+        .list_klines(&market.id, KlineInterval::OneDay, None, None, 100)
+        .unwrap();
+    assert_eq!(days.len(), 1);
+    assert_eq!(days[0].open_time, day);
+    assert_eq!(days[0].close, decimal("101"));
+    assert_eq!(days[0].quote_volume, decimal("711"));
+    assert_eq!(days[0].trade_count, 4);
+
+    // Bounds apply to the candles' open time
+    let second_minute = repo
+        .list_klines(
+            &market.id,
+            KlineInterval::OneMinute,
+            Some(day + 1),
+            None,
+            100,
+        )
+        .unwrap();
+    assert_eq!(second_minute.len(), 1);
+    let limited = repo
+        .list_klines(
+            &market.id,
+            KlineInterval::OneMinute,
+            None,
+            Some(day + 60),
+            1,
+        )
+        .unwrap();
+    assert_eq!(limited[0].open_time, day);
+}
+
+#[test]
+fn test_settled_trade_updates_klines() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repo);
+    let funds = [
+        (market.base_asset.as_str(), "100"),
+        (market.quote_asset.as_str(), "1000"),
+    ];
+    let buyer_id = create_funded_user(&repo, &funds);
+    let seller_id = create_funded_user(&repo, &funds);
+
+    let trade = execute_test_trade(&repo, &market, &buyer_id, &seller_id, "10", "2");
+
+    for interval in KlineInterval::ALL {
+        let klines = repo
+            .list_klines(&market.id, interval, None, None, 100)
+            .unwrap();
+        assert_eq!(klines.len(), 1, "{} klines", interval.as_str());
+        assert_eq!(klines[0].open_time, interval.open_time(trade.timestamp));
+        assert_eq!(klines[0].close, trade.price);
+        assert_eq!(klines[0].volume, trade.base_amount);
+    }
+}
+
+#[test]
+fn test_batch_of_trades_folds_like_single_ones() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repo);
+    // 2025-01-01 00:00:00 UTC
+    let day = 1_735_689_600;
+    let trade = |timestamp: i64, price: &str, base: &str| NewTrade {
+        id: get_uuid_string(),
+        timestamp,
+        market_id: market.id.clone(),
+        price: decimal(price),
+        base_amount: decimal(base),
+        quote_amount: decimal(price) * decimal(base),
+        buyer_user_id: get_uuid_string(),
+        buyer_order_id: get_uuid_string(),
+        buyer_fee: decimal("0"),
+        seller_user_id: get_uuid_string(),
+        seller_order_id: get_uuid_string(),
+        seller_fee: decimal("0"),
+        taker_side: "BUY".to_string(),
+        is_liquidation: None,
+    };
+
+    record_trade(&repo, &market, day + 5, "100", "1");
+    // Three of them land in the candle the first trade opened, one in the next minute
+    let batch = [
+        trade(day + 10, "104", "1"),
+        trade(day + 20, "97", "2"),
+        trade(day + 30, "99", "1"),
+        trade(day + 65, "101", "1"),
+    ];
+    record_kline_trades(&mut repo.get_conn().unwrap(), &batch).unwrap();
+
+    let minutes = repo
+        .list_klines(&market.id, KlineInterval::OneMinute, None, None, 100)
+        .unwrap();
+    assert_eq!(minutes.len(), 2);
+    let first = &minutes[0];
+    assert_eq!(
+        (&first.open, &first.high, &first.low, &first.close),
+        (
+            &decimal("100"),
+            &decimal("104"),
+            &decimal("97"),
+            &decimal("99")
+        )
+    );
+    assert_eq!(first.volume, decimal("5"));
+    assert_eq!(first.quote_volume, decimal("497"));
+    assert_eq!(first.trade_count, 4);
+    assert_eq!(minutes[1].trade_count, 1);
+
+    let days = repo
+        .list_klines(&market.id, KlineInterval::OneDay, None, None, 100)
+        .unwrap();
+    assert_eq!(days[0].close, decimal("101"));
+    assert_eq!(days[0].trade_count, 5);
+}
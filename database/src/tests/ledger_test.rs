@@ -0,0 +1,176 @@
+use crate::filters::LedgerFilter;
+use crate::models::models::*;
+use crate::provider::{
+    LedgerDatabaseReader, OrderDatabaseWriter, WalletDatabaseReader, WalletDatabaseWriter,
+};
+use crate::repository::Repository;
+use crate::tests::test_db::*;
+use bigdecimal::BigDecimal;
+use common::db::pagination::Pagination;
+use std::collections::HashMap;
+
+fn entries(repo: &Repository, filter: LedgerFilter) -> Vec<LedgerEntry> {
+    let pagination = Pagination {
+        limit: Some(100),
+        ..Default::default()
+    };
+    repo.list_ledger_entries(filter, Some(pagination))
+        .unwrap()
+        .items
+}
+
+/// Debits minus credits of every transaction and asset, which double entry keeps at zero
+fn assert_balanced(entries: &[LedgerEntry]) {
+    let mut net: HashMap<(&str, &str), BigDecimal> = HashMap::new();
+    for entry in entries {
+        *net.entry((&entry.transaction_id, &entry.asset))
+            .or_insert_with(|| BigDecimal::from(0)) += &entry.debit - &entry.credit;
+    }
+    for ((transaction_id, asset), amount) in net {
+        assert_eq!(
+            amount,
+            BigDecimal::from(0),
+            "transaction {} does not balance in {}",
+            transaction_id,
+            asset
+        );
+    }
+}
+
+#[test]
+fn test_order_lifecycle_is_recorded_in_ledger() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repo);
+    let user_id = create_funded_user(&repo, &[(&market.quote_asset, "1000")]);
+
+    let order = repo
+        .create_order(new_limit_order(
+            &market,
+            &user_id,
+            OrderSide::Buy,
+            "100",
+            "5",
+        ))
+        .unwrap();
+    repo.cancel_order(&order.id, CancelReason::UserCanceled)
+        .unwrap();
+    repo.withdraw_balance(&user_id, &market.quote_asset, decimal("300"))
+        .unwrap();
+
+    let user_entries = entries(&repo, LedgerFilter::new().owner_id(Some(user_id.clone())));
+    assert_balanced(&user_entries);
+
+    // Newest first, two entries per movement
+    let movements: Vec<(&str, &str, BigDecimal)> = user_entries
+        .iter()
+        .filter(|entry| entry.credit > 0)
+        .map(|entry| {
+            (
+                entry.kind.as_str(),
+                entry.account.as_str(),
+                entry.credit.clone(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        movements,
+        vec![
+            ("WITHDRAWAL", "EXTERNAL", decimal("300")),
+            ("UNLOCK", "AVAILABLE", decimal("500")),
+            ("LOCK", "LOCKED", decimal("500")),
+            ("DEPOSIT", "AVAILABLE", decimal("1000")),
+        ]
+    );
+
+    let order_entries = entries(&repo, LedgerFilter::new().reference_id(Some(order.id)));
+    assert_eq!(order_entries.len(), 4);
+}
+
+#[test]
+fn test_trade_settlement_is_recorded_in_ledger() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repo);
+    let buyer_id = create_funded_user(&repo, &[(&market.quote_asset, "1000")]);
+    let seller_id = create_funded_user(&repo, &[(&market.base_asset, "10")]);
+
+    let trade = execute_test_trade(&repo, &market, &buyer_id, &seller_id, "100", "2");
+
+    let trade_entries = entries(&repo, LedgerFilter::new().reference_id(Some(trade.id)));
+    assert_balanced(&trade_entries);
+    assert!(
+        trade_entries
+            .iter()
+            .all(|entry| entry.transaction_id == trade_entries[0].transaction_id)
+    );
+
+    let credited = |owner_id: &str, account: &str, asset: &str| -> BigDecimal {
+        trade_entries
+            .iter()
+            .filter(|entry| {
+                entry.owner_id == owner_id && entry.account == account && entry.asset == asset
+            })
+            .map(|entry| &entry.credit - &entry.debit)
+            .sum()
+    };
+    assert_eq!(
+        credited(&buyer_id, "AVAILABLE", &market.base_asset),
+        &trade.base_amount - &trade.buyer_fee
+    );
+    assert_eq!(
+        credited(&seller_id, "AVAILABLE", &market.quote_asset),
+        &trade.quote_amount - &trade.seller_fee
+    );
+    assert_eq!(
+        credited(&market.id, "FEE_TREASURY", &market.base_asset),
+        trade.buyer_fee
+    );
+    assert_eq!(
+        credited(&market.id, "FEE_TREASURY", &market.quote_asset),
+        trade.seller_fee
+    );
+    assert_eq!(
+        credited(&seller_id, "LOCKED", &market.base_asset),
+        -trade.base_amount
+    );
+}
+
+#[test]
+fn test_unlock_balance_moves_locked_funds_back() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repo);
+    let user_id = create_funded_user(&repo, &[(&market.quote_asset, "100")]);
+
+    repo.lock_balance(&user_id, &market.quote_asset, decimal("40"))
+        .unwrap();
+    let wallet = repo
+        .unlock_balance(&user_id, &market.quote_asset, decimal("15"))
+        .unwrap();
+    assert_eq!(wallet.available, decimal("75"));
+    assert_eq!(wallet.locked, decimal("25"));
+
+    // Unlocking more than is locked fails without a trace in the ledger
+    assert!(
+        repo.unlock_balance(&user_id, &market.quote_asset, decimal("30"))
+            .is_err()
+    );
+    let user_entries = entries(
+        &repo,
+        LedgerFilter::new()
+            .owner_id(Some(user_id.clone()))
+            .kind(Some(LedgerEntryKind::Unlock.as_str().to_string())),
+    );
+    assert_eq!(user_entries.len(), 2);
+    assert_eq!(
+        repo.get_wallet(&user_id, &market.quote_asset)
+            .unwrap()
+            .unwrap()
+            .locked,
+        decimal("25")
+    );
+}
@@ -0,0 +1,152 @@
+use crate::models::models::Market;
+use crate::models::schema::trades;
+use crate::provider::{MarketStatDatabaseReader, MarketStatDatabaseWriter};
+use crate::repository::Repository;
+use crate::tests::test_db::*;
+use diesel::prelude::*;
+
+#[test]
+fn test_upsert_market_stats_rounds_to_market_precision() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let market = create_test_market_with_precision(&repo, 2, 4);
+
+    let stats = repo
+        .upsert_market_stats(
+            &market.id,
+            decimal("101.23456"),
+            decimal("99.995"),
+            decimal("12.3456789"),
+            decimal("-1.234"),
+            decimal("100.005"),
+        )
+        .unwrap();
+
+    assert_eq!(stats.high_24h, decimal("101.23"));
+    assert_eq!(stats.low_24h, decimal("100.00"));
+    assert_eq!(stats.volume_24h, decimal("12.3457"));
+    assert_eq!(stats.price_change_24h, decimal("-1.23"));
+    assert_eq!(stats.last_price, decimal("100.01"));
+
+    // The update path rounds the same way as the insert path
+    let stats = repo
+        .upsert_market_stats(
+            &market.id,
+            decimal("102.999"),
+            decimal("98.001"),
+            decimal("0.00005"),
+            decimal("0.005"),
+            decimal("101.5"),
+        )
+        .unwrap();
+
+    assert_eq!(stats.high_24h, decimal("103.00"));
+    assert_eq!(stats.low_24h, decimal("98.00"));
+    assert_eq!(stats.volume_24h, decimal("0.0001"));
+    assert_eq!(stats.price_change_24h, decimal("0.01"));
+    assert_eq!(stats.last_price, decimal("101.50"));
+}
+
+/// Settles a trade and moves it to `timestamp`
+fn trade_at(
+    repo: &Repository,
+    market: &Market,
+    users: (&str, &str),
+    price: &str,
+    base: &str,
+    timestamp: i64,
+) {
+    let trade = execute_test_trade(repo, market, users.0, users.1, price, base);
+    diesel::update(trades::table.find(&trade.id))
+        .set(trades::timestamp.eq(timestamp))
+        .execute(&mut repo.get_conn().unwrap())
+        .unwrap();
+}
+
+#[test]
+fn test_refresh_market_stats_covers_the_last_24h_of_trades() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repo);
+    let funds = [
+        (market.base_asset.as_str(), "100"),
+        (market.quote_asset.as_str(), "1000"),
+    ];
+    let buyer_id = create_funded_user(&repo, &funds);
+    let seller_id = create_funded_user(&repo, &funds);
+    let users = (buyer_id.as_str(), seller_id.as_str());
+    let now = 1_735_689_600;
+
+    assert!(
+        repo.refresh_market_stats(&market.id, now)
+            .unwrap()
+            .is_none()
+    );
+
+    trade_at(&repo, &market, users, "10", "1", now - 2 * 86_400);
+    trade_at(&repo, &market, users, "12", "2", now - 3_600);
+    trade_at(&repo, &market, users, "9", "3", now - 1_800);
+
+    let stats = repo.refresh_market_stats(&market.id, now).unwrap().unwrap();
+    assert_eq!(stats.high_24h, decimal("12"));
+    assert_eq!(stats.low_24h, decimal("9"));
+    assert_eq!(stats.volume_24h, decimal("5"));
+    // Against the last price before the window opened
+    assert_eq!(stats.price_change_24h, decimal("-1"));
+    assert_eq!(stats.last_price, decimal("9"));
+
+    // A day later nothing traded in the window
+    let stats = repo
+        .refresh_market_stats(&market.id, now + 86_400)
+        .unwrap()
+        .unwrap();
+    assert_eq!(stats.high_24h, decimal("9"));
+    assert_eq!(stats.low_24h, decimal("9"));
+    assert_eq!(stats.volume_24h, decimal("0"));
+    assert_eq!(stats.price_change_24h, decimal("0"));
+}
+
+#[test]
+fn test_list_tickers_joins_stats_and_quotes() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let traded = create_test_market(&repo);
+    let untraded = create_test_market(&repo);
+    repo.upsert_market_stats(
+        &traded.id,
+        decimal("12"),
+        decimal("9"),
+        decimal("5"),
+        decimal("-1"),
+        decimal("9"),
+    )
+    .unwrap();
+    repo.upsert_market_quote(&traded.id, Some(decimal("8.5")), None)
+        .unwrap();
+    // A later quote replaces the earlier one
+    repo.upsert_market_quote(&traded.id, Some(decimal("8.9")), Some(decimal("9.1")))
+        .unwrap();
+
+    let tickers = repo.list_tickers().unwrap();
+    let ticker = |market_id: &str| {
+        tickers
+            .iter()
+            .find(|ticker| ticker.market_id == market_id)
+            .unwrap()
+    };
+
+    let traded_ticker = ticker(&traded.id);
+    let stats = traded_ticker.stats.as_ref().unwrap();
+    assert_eq!(stats.last_price, decimal("9"));
+    assert_eq!(stats.volume_24h, decimal("5"));
+    let quote = traded_ticker.quote.as_ref().unwrap();
+    assert_eq!(quote.best_bid, Some(decimal("8.9")));
+    assert_eq!(quote.best_ask, Some(decimal("9.1")));
+
+    let untraded_ticker = ticker(&untraded.id);
+    assert!(untraded_ticker.stats.is_none());
+    assert!(untraded_ticker.quote.is_none());
+}
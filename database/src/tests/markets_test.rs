@@ -0,0 +1,65 @@
+use crate::models::models::MarketUpdate;
+use crate::provider::{MarketDatabaseReader, MarketDatabaseWriter};
+use crate::tests::test_db::*;
+use bigdecimal::BigDecimal;
+use std::thread;
+
+#[test]
+fn test_concurrent_create_market_with_same_id() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let new_market = new_test_market(8, 8);
+
+    let handles: Vec<_> = (0..8)
+        .map(|_| {
+            let repo = repo.clone();
+            let new_market = new_market.clone();
+            thread::spawn(move || repo.create_market(new_market))
+        })
+        .collect();
+
+    for handle in handles {
+        let market = handle.join().unwrap().unwrap();
+        assert_eq!(market.id, new_market.id);
+    }
+
+    let matching = repo
+        .list_markets()
+        .unwrap()
+        .into_iter()
+        .filter(|m| m.id == new_market.id)
+        .count();
+    assert_eq!(matching, 1);
+    assert!(repo.get_market(&new_market.id).unwrap().is_some());
+}
+
+#[test]
+fn test_update_market_changes_only_given_parameters() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repo);
+
+    let updated = repo
+        .update_market(
+            &market.id,
+            MarketUpdate {
+                min_base_amount: Some(BigDecimal::from(2)),
+                price_precision: Some(2),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert_eq!(updated.min_base_amount, BigDecimal::from(2));
+    assert_eq!(updated.price_precision, 2);
+    assert_eq!(updated.default_maker_fee, market.default_maker_fee);
+    assert_eq!(updated.min_quote_amount, market.min_quote_amount);
+    assert_eq!(updated.amount_precision, market.amount_precision);
+    assert!(updated.update_time >= market.update_time);
+
+    assert!(
+        repo.update_market(&unique_suffix(), MarketUpdate::default())
+            .is_err()
+    );
+}
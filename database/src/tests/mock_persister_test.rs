@@ -0,0 +1,110 @@
+use crate::mock::mock_persister::MockPersister;
+use crate::models::models::OrderSide;
+use crate::provider::{
+    OrderDatabaseReader, OrderDatabaseWriter, WalletDatabaseReader, WalletDatabaseWriter,
+};
+use crate::tests::test_db::*;
+use bigdecimal::BigDecimal;
+use common::error::BitradeError;
+
+#[test]
+fn test_mock_release_orphaned_locks() {
+    let mock = MockPersister::new();
+    let market = create_test_market(&mock);
+    let user_id = create_funded_user(&mock, &[(&market.quote_asset, "1000")]);
+
+    // An open buy order legitimately locks 500 quote
+    mock.create_order(new_limit_order(
+        &market,
+        &user_id,
+        OrderSide::Buy,
+        "100",
+        "5",
+    ))
+    .unwrap();
+    mock.lock_balance(&user_id, &market.quote_asset, BigDecimal::from(200))
+        .unwrap();
+
+    let released = mock.release_orphaned_locks(&user_id).unwrap();
+    assert_eq!(released.len(), 1);
+
+    let wallet = mock
+        .get_wallet(&user_id, &market.quote_asset)
+        .unwrap()
+        .unwrap();
+    assert_eq!(wallet.locked, BigDecimal::from(500));
+    assert_eq!(wallet.available, BigDecimal::from(500));
+    assert!(mock.release_orphaned_locks(&user_id).unwrap().is_empty());
+}
+
+#[test]
+fn test_mock_withdraw_balance() {
+    let mock = MockPersister::new();
+    mock.deposit_balance("user", "USD", BigDecimal::from(100))
+        .unwrap();
+
+    let wallet = mock
+        .withdraw_balance("user", "USD", BigDecimal::from(40))
+        .unwrap();
+    assert_eq!(wallet.available, BigDecimal::from(60));
+    assert_eq!(wallet.total_withdrawn, BigDecimal::from(40));
+
+    let error = mock
+        .withdraw_balance("user", "USD", BigDecimal::from(61))
+        .unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<BitradeError>(),
+        Some(BitradeError::InsufficientBalance { .. })
+    ));
+    assert!(
+        mock.withdraw_balance("other", "USD", BigDecimal::from(1))
+            .is_err()
+    );
+    assert!(mock.get_wallet("other", "USD").unwrap().is_none());
+}
+
+#[test]
+fn test_mock_lock_and_unlock_balance() {
+    let mock = MockPersister::new();
+    mock.deposit_balance("user", "USD", BigDecimal::from(100))
+        .unwrap();
+
+    let wallet = mock
+        .lock_balance("user", "USD", BigDecimal::from(70))
+        .unwrap();
+    assert_eq!(wallet.available, BigDecimal::from(30));
+    assert_eq!(wallet.locked, BigDecimal::from(70));
+
+    let wallet = mock
+        .unlock_balance("user", "USD", BigDecimal::from(20))
+        .unwrap();
+    assert_eq!(wallet.available, BigDecimal::from(50));
+    assert_eq!(wallet.locked, BigDecimal::from(50));
+
+    // Neither balance can go below zero, and a refusal changes nothing
+    assert!(
+        mock.lock_balance("user", "USD", BigDecimal::from(51))
+            .is_err()
+    );
+    assert!(
+        mock.unlock_balance("user", "USD", BigDecimal::from(51))
+            .is_err()
+    );
+    let wallet = mock.get_wallet("user", "USD").unwrap().unwrap();
+    assert_eq!(wallet.available, BigDecimal::from(50));
+    assert_eq!(wallet.locked, BigDecimal::from(50));
+
+    assert!(
+        mock.lock_balance("other", "USD", BigDecimal::from(1))
+            .is_err()
+    );
+    assert!(mock.get_wallet("other", "USD").unwrap().is_none());
+}
+
+#[test]
+fn test_mock_reports_what_it_does_not_simulate() {
+    let mock = MockPersister::new();
+
+    let error = mock.get_open_order_stats().unwrap_err();
+    assert!(error.to_string().contains("not simulated"));
+}
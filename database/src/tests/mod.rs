@@ -0,0 +1,38 @@
+pub mod test_db;
+
+#[cfg(test)]
+mod engine_events_test;
+#[cfg(test)]
+mod fee_tiers_test;
+#[cfg(test)]
+mod fee_treasury_test;
+#[cfg(test)]
+mod klines_test;
+#[cfg(test)]
+mod ledger_test;
+#[cfg(test)]
+mod market_stats_test;
+#[cfg(test)]
+mod markets_test;
+#[cfg(test)]
+mod mock_persister_test;
+#[cfg(test)]
+mod orders_test;
+#[cfg(test)]
+mod outbox_test;
+#[cfg(test)]
+mod price_bands_test;
+#[cfg(test)]
+mod reconciliation_test;
+#[cfg(test)]
+mod risk_limits_test;
+#[cfg(all(test, feature = "sqlite"))]
+mod sqlite_test;
+#[cfg(test)]
+mod trades_test;
+#[cfg(test)]
+mod transfers_test;
+#[cfg(test)]
+mod user_restrictions_test;
+#[cfg(test)]
+mod wallets_test;
@@ -0,0 +1,367 @@
+use crate::filters::OrderFilter;
+use crate::models::models::*;
+use crate::provider::{OrderDatabaseReader, OrderDatabaseWriter, WalletDatabaseReader};
+use crate::repository::PaginationError;
+use crate::tests::test_db::{
+    create_funded_user, create_test_market, execute_test_trade, new_limit_order, test_repository,
+};
+use bigdecimal::BigDecimal;
+use common::db::pagination::{Cursor, Pagination};
+use std::str::FromStr;
+
+fn count_of(counts: &[OrderStatusCount], status: OrderStatus) -> i64 {
+    counts
+        .iter()
+        .find(|c| c.status == status.as_str())
+        .map_or(0, |c| c.order_count)
+}
+
+#[test]
+fn test_get_user_order_counts_groups_by_status() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repo);
+    let other_market = create_test_market(&repo);
+    let user_id = create_funded_user(
+        &repo,
+        &[
+            (&market.quote_asset, "1000"),
+            (&other_market.quote_asset, "1000"),
+        ],
+    );
+    let seller_id = create_funded_user(&repo, &[(&market.base_asset, "10")]);
+
+    for price in ["1", "2"] {
+        repo.create_order(new_limit_order(
+            &market,
+            &user_id,
+            OrderSide::Buy,
+            price,
+            "1",
+        ))
+        .unwrap();
+    }
+    let canceled = repo
+        .create_order(new_limit_order(&market, &user_id, OrderSide::Buy, "3", "1"))
+        .unwrap();
+    repo.cancel_order(&canceled.id, CancelReason::UserCanceled)
+        .unwrap();
+    execute_test_trade(&repo, &market, &user_id, &seller_id, "4", "1");
+    repo.create_order(new_limit_order(
+        &other_market,
+        &user_id,
+        OrderSide::Buy,
+        "1",
+        "1",
+    ))
+    .unwrap();
+
+    let counts = repo
+        .get_user_order_counts(&user_id, Some(&market.id))
+        .unwrap();
+    assert_eq!(counts.len(), 3);
+    assert_eq!(count_of(&counts, OrderStatus::Open), 2);
+    assert_eq!(count_of(&counts, OrderStatus::Canceled), 1);
+    assert_eq!(count_of(&counts, OrderStatus::Filled), 1);
+
+    let all_markets = repo.get_user_order_counts(&user_id, None).unwrap();
+    assert_eq!(count_of(&all_markets, OrderStatus::Open), 3);
+    assert_eq!(count_of(&all_markets, OrderStatus::Filled), 1);
+}
+
+#[test]
+fn test_idempotent_cancel_returns_canceled_order_on_retry() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repo);
+    let user_id = create_funded_user(&repo, &[(&market.quote_asset, "100")]);
+    let order = repo
+        .create_order(new_limit_order(
+            &market,
+            &user_id,
+            OrderSide::Buy,
+            "10",
+            "2",
+        ))
+        .unwrap();
+
+    repo.cancel_order(&order.id, CancelReason::UserCanceled)
+        .unwrap();
+    // Without the mode a retry fails as before
+    assert!(
+        repo.cancel_order(&order.id, CancelReason::UserCanceled)
+            .is_err()
+    );
+
+    let idempotent_repo = repo.clone().with_idempotent_cancel(true);
+    let retried = idempotent_repo
+        .cancel_order(&order.id, CancelReason::AdminCancel)
+        .unwrap();
+    assert_eq!(retried.get_status().unwrap(), OrderStatus::Canceled);
+    assert_eq!(
+        retried.get_cancel_reason().unwrap(),
+        Some(CancelReason::UserCanceled)
+    );
+
+    // The locked funds were released once
+    let wallet = repo
+        .get_wallet(&user_id, &market.quote_asset)
+        .unwrap()
+        .unwrap();
+    assert_eq!(wallet.available, BigDecimal::from(100));
+    assert_eq!(wallet.locked, BigDecimal::from(0));
+
+    // A filled order still refuses to cancel
+    let seller_id = create_funded_user(&repo, &[(&market.base_asset, "10")]);
+    let trade = execute_test_trade(&repo, &market, &user_id, &seller_id, "10", "1");
+    assert!(
+        idempotent_repo
+            .cancel_order(&trade.buyer_order_id, CancelReason::UserCanceled)
+            .is_err()
+    );
+}
+
+#[test]
+fn test_orders_are_found_by_their_client_order_id() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repo);
+    let user_id = create_funded_user(&repo, &[(&market.quote_asset, "100")]);
+    let other_user_id = create_funded_user(&repo, &[(&market.quote_asset, "100")]);
+
+    // The same client order id may be reused by another user
+    for user in [&user_id, &other_user_id] {
+        repo.create_order(NewOrder {
+            client_order_id: Some("grid-1".to_string()),
+            ..new_limit_order(&market, user, OrderSide::Buy, "1", "1")
+        })
+        .unwrap();
+    }
+
+    let order = repo
+        .get_order_by_client_id(&user_id, "grid-1")
+        .unwrap()
+        .unwrap();
+    assert_eq!(order.user_id, user_id);
+    assert!(
+        repo.get_order_by_client_id(&user_id, "grid-2")
+            .unwrap()
+            .is_none()
+    );
+
+    let listed = repo
+        .list_orders(
+            OrderFilter::new()
+                .market_id(Some(market.id.clone()))
+                .client_order_id(Some("grid-1".to_string())),
+            None,
+        )
+        .unwrap();
+    assert_eq!(listed.total_count, 2);
+}
+
+#[test]
+fn test_orders_page_by_cursor_newest_first() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repo);
+    let user_id = create_funded_user(&repo, &[(&market.quote_asset, "100")]);
+    let mut created = Vec::new();
+    for _ in 0..5 {
+        let order = repo
+            .create_order(new_limit_order(&market, &user_id, OrderSide::Buy, "1", "1"))
+            .unwrap();
+        created.push((order.create_time, order.id));
+    }
+    created.sort();
+    created.reverse();
+
+    let first = repo
+        .list_orders(
+            OrderFilter::new().user_id(Some(user_id.clone())),
+            Some(Pagination {
+                limit: Some(3),
+                ..Default::default()
+            }),
+        )
+        .unwrap();
+    assert!(first.has_more);
+    assert_eq!(first.next_offset, Some(3));
+    let cursor = first.next_cursor.clone().unwrap();
+    assert_eq!(Cursor::decode(&cursor.encode()), Ok(cursor.clone()));
+
+    let second = repo
+        .list_orders(
+            OrderFilter::new().user_id(Some(user_id)),
+            Some(Pagination {
+                limit: Some(3),
+                cursor: Some(cursor),
+                ..Default::default()
+            }),
+        )
+        .unwrap();
+    assert!(!second.has_more);
+    assert_eq!(second.next_offset, None);
+    assert!(second.next_cursor.is_none());
+
+    let ids: Vec<_> = first
+        .items
+        .into_iter()
+        .chain(second.items)
+        .map(|order| (order.create_time, order.id))
+        .collect();
+    assert_eq!(ids, created);
+}
+
+#[test]
+fn test_orders_sort_by_whitelisted_columns_only() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repo);
+    let user_id = create_funded_user(&repo, &[(&market.quote_asset, "100")]);
+    for price in ["3", "1", "2"] {
+        repo.create_order(new_limit_order(
+            &market,
+            &user_id,
+            OrderSide::Buy,
+            price,
+            "1",
+        ))
+        .unwrap();
+    }
+    let list = |order_by: &str, order_direction: &str, cursor: Option<Cursor>| {
+        repo.list_orders(
+            OrderFilter::new().user_id(Some(user_id.clone())),
+            Some(Pagination {
+                limit: Some(10),
+                order_by: Some(order_by.to_string()),
+                order_direction: Some(order_direction.to_string()),
+                cursor,
+                ..Default::default()
+            }),
+        )
+    };
+
+    let prices: Vec<_> = list("price", "asc", None)
+        .unwrap()
+        .items
+        .into_iter()
+        .map(|order| order.price)
+        .collect();
+    assert_eq!(
+        prices,
+        ["1", "2", "3"].map(|p| BigDecimal::from_str(p).unwrap())
+    );
+
+    let error = list("user_id; DROP TABLE orders", "asc", None).unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<PaginationError>(),
+        Some(PaginationError::UnsortableField { .. })
+    ));
+    let error = list("price", "sideways", None).unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<PaginationError>(),
+        Some(PaginationError::InvalidDirection(_))
+    ));
+    let error = list("price", "desc", Some(Cursor::new(0, "x"))).unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<PaginationError>(),
+        Some(PaginationError::CursorNeedsTimeSort { .. })
+    ));
+}
+
+#[test]
+fn test_book_levels_sum_the_visible_amount_per_price() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repo);
+    let user_id = create_funded_user(
+        &repo,
+        &[(&market.quote_asset, "1000"), (&market.base_asset, "100")],
+    );
+    for (side, price, amount) in [
+        (OrderSide::Buy, "9", "1"),
+        (OrderSide::Buy, "9", "2"),
+        (OrderSide::Sell, "11", "4"),
+    ] {
+        repo.create_order(new_limit_order(&market, &user_id, side, price, amount))
+            .unwrap();
+    }
+    // An iceberg shows only its slice
+    repo.create_order(NewOrder {
+        display_amount: Some(BigDecimal::from(1)),
+        ..new_limit_order(&market, &user_id, OrderSide::Sell, "11", "10")
+    })
+    .unwrap();
+    let canceled = repo
+        .create_order(new_limit_order(
+            &market,
+            &user_id,
+            OrderSide::Sell,
+            "12",
+            "1",
+        ))
+        .unwrap();
+    repo.cancel_order(&canceled.id, CancelReason::UserCanceled)
+        .unwrap();
+
+    let mut levels = repo.get_book_levels(&market.id).unwrap();
+    levels.sort_by(|a, b| a.price.cmp(&b.price));
+    assert_eq!(
+        levels,
+        vec![
+            BookLevel {
+                side: "BUY".to_string(),
+                price: BigDecimal::from(9),
+                amount: BigDecimal::from(3),
+            },
+            BookLevel {
+                side: "SELL".to_string(),
+                price: BigDecimal::from(11),
+                amount: BigDecimal::from(5),
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_rejected_orders_lock_nothing_and_free_their_client_order_id() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repo);
+    let user_id = create_funded_user(&repo, &[(&market.quote_asset, "10")]);
+    let with_client_id = |price: &str, base: &str| NewOrder {
+        client_order_id: Some("bid-1".to_string()),
+        ..new_limit_order(&market, &user_id, OrderSide::Buy, price, base)
+    };
+
+    let rejected = repo
+        .reject_order(with_client_id("10", "5"), RejectReason::InsufficientFunds)
+        .unwrap();
+    assert_eq!(rejected.get_status().unwrap(), OrderStatus::Rejected);
+    assert_eq!(
+        rejected.get_reject_reason().unwrap(),
+        Some(RejectReason::InsufficientFunds)
+    );
+    assert_eq!(rejected.remained_quote, BigDecimal::from(0));
+    let wallet = repo
+        .get_wallet(&user_id, &market.quote_asset)
+        .unwrap()
+        .unwrap();
+    assert_eq!(wallet.locked, BigDecimal::from(0));
+
+    // Only the rejected order has the id so far
+    let found = repo.get_order_by_client_id(&user_id, "bid-1").unwrap();
+    assert_eq!(found.unwrap().id, rejected.id);
+
+    let placed = repo.create_order(with_client_id("10", "1")).unwrap();
+    let found = repo.get_order_by_client_id(&user_id, "bid-1").unwrap();
+    assert_eq!(found.unwrap().id, placed.id);
+}
@@ -0,0 +1,60 @@
+use crate::models::models::*;
+use crate::provider::{OutboxDatabaseReader, OutboxDatabaseWriter};
+use crate::tests::test_db::*;
+
+#[test]
+fn test_trade_writes_its_events_to_the_outbox() {
+    // Its own database, so no other test's events are pending
+    let Some(repo) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repo);
+    let funds = [
+        (market.base_asset.as_str(), "10"),
+        (market.quote_asset.as_str(), "100"),
+    ];
+    let buyer_id = create_funded_user(&repo, &funds);
+    let seller_id = create_funded_user(&repo, &funds);
+
+    // Off by default
+    execute_test_trade(&repo, &market, &buyer_id, &seller_id, "10", "1");
+    assert!(repo.get_unpublished_events(100).unwrap().is_empty());
+
+    let outbox_repo = repo.clone().with_outbox(true);
+    let trade = execute_test_trade(&outbox_repo, &market, &buyer_id, &seller_id, "10", "2");
+    let events = repo.get_unpublished_events(100).unwrap();
+    let topics: Vec<_> = events.iter().map(|e| e.get_topic().unwrap()).collect();
+    assert_eq!(
+        topics,
+        [
+            OutboxTopic::Trade,
+            OutboxTopic::Order,
+            OutboxTopic::Order,
+            OutboxTopic::Wallet,
+            OutboxTopic::Wallet,
+            OutboxTopic::Wallet,
+            OutboxTopic::Wallet,
+        ]
+    );
+    assert!(events.iter().all(|e| e.market_id == market.id));
+    assert!(events.windows(2).all(|pair| pair[0].id < pair[1].id));
+
+    let published: NewTrade = serde_json::from_str(&events[0].payload).unwrap();
+    assert_eq!(published.id, trade.id);
+    let buy_order: Order = serde_json::from_str(&events[1].payload).unwrap();
+    assert_eq!(buy_order.id, trade.buyer_order_id);
+    assert_eq!(buy_order.status, "FILLED");
+    let buyer_base: Wallet = serde_json::from_str(&events[3].payload).unwrap();
+    assert_eq!(
+        (buyer_base.user_id.as_str(), buyer_base.asset.as_str()),
+        (buyer_id.as_str(), market.base_asset.as_str())
+    );
+
+    // Marking is idempotent, and only the unmarked rest stays pending
+    let ids: Vec<i64> = events[..3].iter().map(|e| e.id).collect();
+    assert_eq!(repo.mark_events_published(&ids).unwrap(), 3);
+    assert_eq!(repo.mark_events_published(&ids).unwrap(), 0);
+    let pending = repo.get_unpublished_events(2).unwrap();
+    assert_eq!(pending.len(), 2);
+    assert_eq!(pending[0].id, events[3].id);
+}
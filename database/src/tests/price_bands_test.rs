@@ -0,0 +1,30 @@
+use crate::provider::{PriceBandDatabaseReader, PriceBandDatabaseWriter};
+use crate::tests::test_db::*;
+
+#[test]
+fn test_price_band_is_replaced_and_deleted() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repo);
+    assert!(repo.get_price_band(&market.id).unwrap().is_none());
+
+    repo.set_price_band(&market.id, Some(decimal("5")), None, 0, 0)
+        .unwrap();
+    let band = repo
+        .set_price_band(&market.id, None, Some(decimal("7.5")), 60_000, 30_000)
+        .unwrap();
+    assert_eq!(band.band_percent, None);
+    assert_eq!(band.halt_percent, Some(decimal("7.5")));
+    assert_eq!(repo.get_price_band(&market.id).unwrap(), Some(band));
+
+    // Percents have to be positive, a band of zero would refuse every price
+    assert!(
+        repo.set_price_band(&market.id, Some(decimal("0")), None, 0, 0)
+            .is_err()
+    );
+
+    assert!(repo.delete_price_band(&market.id).unwrap());
+    assert!(!repo.delete_price_band(&market.id).unwrap());
+    assert!(repo.get_price_band(&market.id).unwrap().is_none());
+}
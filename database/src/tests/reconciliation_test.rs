@@ -0,0 +1,67 @@
+use crate::models::models::*;
+use crate::provider::{
+    FeeTreasuryDatabaseWriter, OrderDatabaseWriter, ReconciliationDatabaseReader,
+    TransferDatabaseWriter, WalletDatabaseWriter,
+};
+use crate::tests::test_db::*;
+use bigdecimal::BigDecimal;
+
+#[test]
+fn test_balance_sheet_balances_after_trading() {
+    // Reads every wallet in the database, so other tests must not write alongside it
+    let Some(repo) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repo);
+    let buyer_id = create_funded_user(&repo, &[(&market.quote_asset, "1000")]);
+    let seller_id = create_funded_user(&repo, &[(&market.base_asset, "10")]);
+
+    let trade = execute_test_trade(&repo, &market, &buyer_id, &seller_id, "100", "2");
+    repo.create_order(new_limit_order(
+        &market,
+        &seller_id,
+        OrderSide::Sell,
+        "120",
+        "3",
+    ))
+    .unwrap();
+    let withdrawal = repo
+        .request_withdrawal(&buyer_id, &market.quote_asset, BigDecimal::from(50), "addr")
+        .unwrap();
+    repo.approve_withdrawal(&withdrawal.id).unwrap();
+    repo.complete_withdrawal(&withdrawal.id, "tx").unwrap();
+    repo.withdraw_from_fee_treasury(&market.id, &market.quote_asset, trade.seller_fee, "ops")
+        .unwrap();
+
+    let sheet = repo.get_balance_sheet().unwrap();
+    assert_eq!(sheet.assets.len(), 2);
+    for totals in &sheet.assets {
+        assert_eq!(
+            &totals.available + &totals.locked + &totals.reserved + &totals.fee_treasury,
+            &totals.total_deposited - &totals.total_withdrawn,
+            "{} does not balance",
+            totals.asset
+        );
+    }
+    assert_eq!(
+        sheet.locks,
+        vec![LockedBalance {
+            user_id: seller_id.clone(),
+            asset: market.base_asset.clone(),
+            locked: BigDecimal::from(3),
+            order_locked: BigDecimal::from(3),
+        }]
+    );
+
+    // A lock no order accounts for shows up against the orders
+    repo.lock_balance(&buyer_id, &market.quote_asset, BigDecimal::from(7))
+        .unwrap();
+    let sheet = repo.get_balance_sheet().unwrap();
+    let orphaned = sheet
+        .locks
+        .iter()
+        .find(|lock| lock.user_id == buyer_id)
+        .unwrap();
+    assert_eq!(orphaned.locked, BigDecimal::from(7));
+    assert_eq!(orphaned.order_locked, BigDecimal::from(0));
+}
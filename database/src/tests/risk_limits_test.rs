@@ -0,0 +1,82 @@
+use crate::models::models::*;
+use crate::provider::{
+    OrderDatabaseReader, OrderDatabaseWriter, RiskLimitDatabaseReader, RiskLimitDatabaseWriter,
+};
+use crate::tests::test_db::*;
+use bigdecimal::BigDecimal;
+
+#[test]
+fn test_risk_limit_is_replaced_and_deleted() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repo);
+    assert!(repo.get_risk_limit(&market.id).unwrap().is_none());
+
+    repo.set_risk_limit(&market.id, Some(5), None).unwrap();
+    let limit = repo
+        .set_risk_limit(&market.id, None, Some(decimal("2500")))
+        .unwrap();
+    assert_eq!(limit.max_open_orders, None);
+    assert_eq!(repo.get_risk_limit(&market.id).unwrap(), Some(limit));
+
+    assert!(repo.set_risk_limit(&market.id, Some(0), None).is_err());
+
+    assert!(repo.delete_risk_limit(&market.id).unwrap());
+    assert!(!repo.delete_risk_limit(&market.id).unwrap());
+    assert!(repo.get_risk_limit(&market.id).unwrap().is_none());
+}
+
+#[test]
+fn test_user_active_orders_count_and_locked_notional() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repo);
+    let user_id = create_funded_user(
+        &repo,
+        &[(&market.base_asset, "10"), (&market.quote_asset, "1000")],
+    );
+    assert_eq!(
+        repo.get_user_active_orders_count(&user_id, &market.id)
+            .unwrap(),
+        0
+    );
+    assert_eq!(
+        repo.get_user_locked_notional(&user_id, &market.id).unwrap(),
+        BigDecimal::from(0)
+    );
+
+    repo.create_order(new_limit_order(
+        &market,
+        &user_id,
+        OrderSide::Buy,
+        "10",
+        "2",
+    ))
+    .unwrap();
+    repo.create_order(new_limit_order(
+        &market,
+        &user_id,
+        OrderSide::Sell,
+        "30",
+        "1.5",
+    ))
+    .unwrap();
+    let canceled = repo
+        .create_order(new_limit_order(&market, &user_id, OrderSide::Buy, "5", "1"))
+        .unwrap();
+    repo.cancel_order(&canceled.id, CancelReason::UserCanceled)
+        .unwrap();
+
+    assert_eq!(
+        repo.get_user_active_orders_count(&user_id, &market.id)
+            .unwrap(),
+        2
+    );
+    // 20 left on the buy plus 1.5 at 30 on the sell
+    assert_eq!(
+        repo.get_user_locked_notional(&user_id, &market.id).unwrap(),
+        decimal("65")
+    );
+}
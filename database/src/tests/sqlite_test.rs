@@ -0,0 +1,400 @@
+use crate::filters::{LedgerFilter, OrderFilter};
+use crate::models::models::*;
+use crate::provider::*;
+use crate::repository::{MissingWalletPolicy, SettlementError};
+use crate::sqlite::SqliteRepository;
+use crate::tests::test_db::*;
+use bigdecimal::BigDecimal;
+use common::db::pagination::Pagination;
+
+fn sqlite_repository() -> SqliteRepository {
+    SqliteRepository::in_memory().expect("Failed to open in-memory SQLite database")
+}
+
+fn wallet(repo: &SqliteRepository, user_id: &str, asset: &str) -> Wallet {
+    repo.get_wallet(user_id, asset).unwrap().unwrap()
+}
+
+#[test]
+fn test_cancel_unlocks_what_each_side_holds() {
+    let repo = sqlite_repository();
+    let market = create_test_market(&repo);
+    let user_id = create_funded_user(
+        &repo,
+        &[(&market.quote_asset, "1000"), (&market.base_asset, "10")],
+    );
+
+    let buy = repo
+        .create_order(new_limit_order(
+            &market,
+            &user_id,
+            OrderSide::Buy,
+            "100",
+            "2.5",
+        ))
+        .unwrap();
+    let sell = repo
+        .create_order(new_limit_order(
+            &market,
+            &user_id,
+            OrderSide::Sell,
+            "120",
+            "4",
+        ))
+        .unwrap();
+    assert_eq!(
+        wallet(&repo, &user_id, &market.quote_asset).locked,
+        decimal("250")
+    );
+    assert_eq!(
+        wallet(&repo, &user_id, &market.base_asset).locked,
+        decimal("4")
+    );
+
+    // A buy gives back its remaining quote, a sell its remaining base
+    let canceled = repo
+        .cancel_order(&buy.id, CancelReason::UserCanceled)
+        .unwrap();
+    assert_eq!(canceled.get_status().unwrap(), OrderStatus::Canceled);
+    let quote = wallet(&repo, &user_id, &market.quote_asset);
+    assert_eq!(quote.available, decimal("1000"));
+    assert_eq!(quote.locked, decimal("0"));
+
+    repo.cancel_order(&sell.id, CancelReason::UserCanceled)
+        .unwrap();
+    let base = wallet(&repo, &user_id, &market.base_asset);
+    assert_eq!(base.available, decimal("10"));
+    assert_eq!(base.locked, decimal("0"));
+
+    assert!(
+        repo.cancel_order(&sell.id, CancelReason::UserCanceled)
+            .is_err()
+    );
+}
+
+#[test]
+fn test_trade_settles_balances_fees_and_treasury() {
+    let repo = sqlite_repository();
+    let market = create_test_market(&repo);
+    let buyer_id = create_funded_user(&repo, &[(&market.quote_asset, "1000")]);
+    let seller_id = create_funded_user(&repo, &[(&market.base_asset, "10")]);
+
+    let trade = execute_test_trade(&repo, &market, &buyer_id, &seller_id, "100", "2");
+    // The buyer takes at 0.002 of the base, the seller makes at 0.001 of the quote
+    assert_eq!(trade.buyer_fee, decimal("0.004"));
+    assert_eq!(trade.seller_fee, decimal("0.2"));
+
+    let buyer_base = wallet(&repo, &buyer_id, &market.base_asset);
+    assert_eq!(buyer_base.available, decimal("1.996"));
+    let buyer_quote = wallet(&repo, &buyer_id, &market.quote_asset);
+    assert_eq!(buyer_quote.available, decimal("800"));
+    assert_eq!(buyer_quote.locked, decimal("0"));
+    let seller_base = wallet(&repo, &seller_id, &market.base_asset);
+    assert_eq!(seller_base.available, decimal("8"));
+    assert_eq!(seller_base.locked, decimal("0"));
+    let seller_quote = wallet(&repo, &seller_id, &market.quote_asset);
+    assert_eq!(seller_quote.available, decimal("199.8"));
+
+    let treasury = |asset: &str| {
+        repo.get_fee_treasury(&market.id, asset)
+            .unwrap()
+            .unwrap()
+            .collected_amount
+    };
+    assert_eq!(treasury(&market.base_asset), decimal("0.004"));
+    assert_eq!(treasury(&market.quote_asset), decimal("0.2"));
+
+    let entries = repo
+        .list_ledger_entries(
+            LedgerFilter::new().reference_id(Some(trade.id.clone())),
+            Some(Pagination {
+                limit: Some(100),
+                ..Default::default()
+            }),
+        )
+        .unwrap()
+        .items;
+    let debits: BigDecimal = entries.iter().map(|entry| &entry.debit).sum();
+    let credits: BigDecimal = entries.iter().map(|entry| &entry.credit).sum();
+    assert!(!entries.is_empty());
+    assert_eq!(debits, credits);
+
+    // Every amount the trade moved is still accounted for
+    let sheet = repo.get_balance_sheet().unwrap();
+    for totals in &sheet.assets {
+        assert_eq!(
+            &totals.available + &totals.locked + &totals.reserved + &totals.fee_treasury,
+            &totals.total_deposited - &totals.total_withdrawn
+        );
+    }
+    assert!(sheet.locks.is_empty());
+}
+
+#[test]
+fn test_filled_buy_returns_the_quote_it_did_not_spend() {
+    let repo = sqlite_repository();
+    let market = create_test_market(&repo);
+    let buyer_id = create_funded_user(&repo, &[(&market.quote_asset, "1000")]);
+    let seller_id = create_funded_user(&repo, &[(&market.base_asset, "10")]);
+
+    // Bid at 11, filled at the seller's 10
+    let buy = repo
+        .create_order(new_limit_order(
+            &market,
+            &buyer_id,
+            OrderSide::Buy,
+            "11",
+            "1",
+        ))
+        .unwrap();
+    let sell = repo
+        .create_order(new_limit_order(
+            &market,
+            &seller_id,
+            OrderSide::Sell,
+            "10",
+            "1",
+        ))
+        .unwrap();
+    repo.execute_limit_trade(
+        true,
+        market.id.clone(),
+        market.base_asset.clone(),
+        market.quote_asset.clone(),
+        buyer_id.clone(),
+        seller_id,
+        buy.id.clone(),
+        sell.id,
+        decimal("10"),
+        decimal("1"),
+        decimal("10"),
+        market.default_taker_fee.clone(),
+        market.default_maker_fee.clone(),
+    )
+    .unwrap();
+
+    let order = repo.get_order(&buy.id).unwrap().unwrap();
+    assert_eq!(order.get_status().unwrap(), OrderStatus::Filled);
+    let quote = wallet(&repo, &buyer_id, &market.quote_asset);
+    assert_eq!(quote.available, decimal("990"));
+    assert_eq!(quote.locked, decimal("0"));
+}
+
+#[test]
+fn test_partially_filled_order_unlocks_its_remainder_on_cancel() {
+    let repo = sqlite_repository();
+    let market = create_test_market(&repo);
+    let buyer_id = create_funded_user(&repo, &[(&market.quote_asset, "1000")]);
+    let seller_id = create_funded_user(&repo, &[(&market.base_asset, "10")]);
+
+    let sell = repo
+        .create_order(new_limit_order(
+            &market,
+            &seller_id,
+            OrderSide::Sell,
+            "10",
+            "5",
+        ))
+        .unwrap();
+    let buy = repo
+        .create_order(new_limit_order(
+            &market,
+            &buyer_id,
+            OrderSide::Buy,
+            "10",
+            "2",
+        ))
+        .unwrap();
+    repo.execute_limit_trade(
+        true,
+        market.id.clone(),
+        market.base_asset.clone(),
+        market.quote_asset.clone(),
+        buyer_id,
+        seller_id.clone(),
+        buy.id,
+        sell.id.clone(),
+        buy.price,
+        buy.base_amount,
+        buy.quote_amount,
+        market.default_taker_fee.clone(),
+        market.default_maker_fee.clone(),
+    )
+    .unwrap();
+
+    let order = repo.get_order(&sell.id).unwrap().unwrap();
+    assert_eq!(order.get_status().unwrap(), OrderStatus::PartiallyFilled);
+    assert_eq!(order.remained_base, decimal("3"));
+    assert_eq!(
+        wallet(&repo, &seller_id, &market.base_asset).locked,
+        decimal("3")
+    );
+
+    repo.cancel_order(&sell.id, CancelReason::UserCanceled)
+        .unwrap();
+    let base = wallet(&repo, &seller_id, &market.base_asset);
+    assert_eq!(base.available, decimal("8"));
+    assert_eq!(base.locked, decimal("0"));
+}
+
+#[test]
+fn test_settlement_refuses_a_missing_counterparty_wallet() {
+    let repo = sqlite_repository().with_missing_wallet_policy(MissingWalletPolicy::Fail);
+    let market = create_test_market(&repo);
+    let buyer_id = create_funded_user(&repo, &[(&market.quote_asset, "1000")]);
+    let seller_id = create_funded_user(&repo, &[(&market.base_asset, "10")]);
+
+    let buy = repo
+        .create_order(new_limit_order(
+            &market,
+            &buyer_id,
+            OrderSide::Buy,
+            "10",
+            "1",
+        ))
+        .unwrap();
+    let sell = repo
+        .create_order(new_limit_order(
+            &market,
+            &seller_id,
+            OrderSide::Sell,
+            "10",
+            "1",
+        ))
+        .unwrap();
+    let error = repo
+        .execute_limit_trade(
+            true,
+            market.id.clone(),
+            market.base_asset.clone(),
+            market.quote_asset.clone(),
+            buyer_id.clone(),
+            seller_id,
+            buy.id.clone(),
+            sell.id,
+            buy.price,
+            buy.base_amount,
+            buy.quote_amount,
+            market.default_taker_fee.clone(),
+            market.default_maker_fee.clone(),
+        )
+        .unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<SettlementError>(),
+        Some(SettlementError::WalletMissing { .. })
+    ));
+
+    // Nothing of the refused trade was kept
+    let order = repo.get_order(&buy.id).unwrap().unwrap();
+    assert_eq!(order.get_status().unwrap(), OrderStatus::Open);
+    assert!(
+        repo.get_wallet(&buyer_id, &market.base_asset)
+            .unwrap()
+            .is_none()
+    );
+}
+
+#[test]
+fn test_amounts_sort_as_numbers() {
+    let repo = sqlite_repository();
+    let market = create_test_market(&repo);
+    let user_id = create_funded_user(&repo, &[(&market.quote_asset, "1000")]);
+    // As text, 10 would sort before 9
+    for price in ["9", "10", "9.5"] {
+        repo.create_order(new_limit_order(
+            &market,
+            &user_id,
+            OrderSide::Buy,
+            price,
+            "1",
+        ))
+        .unwrap();
+    }
+
+    let page = repo
+        .list_orders(
+            OrderFilter::new().market_id(Some(market.id.clone())),
+            Some(Pagination {
+                limit: Some(2),
+                order_by: Some("price".to_string()),
+                order_direction: Some("desc".to_string()),
+                ..Default::default()
+            }),
+        )
+        .unwrap();
+    let prices: Vec<BigDecimal> = page.items.iter().map(|order| order.price.clone()).collect();
+    assert_eq!(prices, vec![decimal("10"), decimal("9.5")]);
+    assert_eq!(page.total_count, 3);
+    assert!(page.has_more);
+
+    let levels = repo.get_book_levels(&market.id).unwrap();
+    assert_eq!(levels.len(), 3);
+    assert_eq!(
+        repo.get_user_locked_notional(&user_id, &market.id).unwrap(),
+        decimal("28.5")
+    );
+}
+
+#[test]
+fn test_withdrawals_and_treasury_sweeps_leave_the_books_balanced() {
+    let repo = sqlite_repository();
+    let market = create_test_market(&repo);
+    let buyer_id = create_funded_user(&repo, &[(&market.quote_asset, "1000")]);
+    let seller_id = create_funded_user(&repo, &[(&market.base_asset, "10")]);
+    execute_test_trade(&repo, &market, &buyer_id, &seller_id, "100", "2");
+
+    let withdrawal = repo
+        .request_withdrawal(&seller_id, &market.quote_asset, decimal("150"), "addr-1")
+        .unwrap();
+    let reserved = wallet(&repo, &seller_id, &market.quote_asset);
+    assert_eq!(reserved.available, decimal("49.8"));
+    assert_eq!(reserved.reserved, decimal("150"));
+    repo.approve_withdrawal(&withdrawal.id).unwrap();
+    repo.complete_withdrawal(&withdrawal.id, "tx-1").unwrap();
+    assert_eq!(
+        wallet(&repo, &seller_id, &market.quote_asset).total_withdrawn,
+        decimal("150")
+    );
+
+    repo.withdraw_from_fee_treasury(&market.id, &market.quote_asset, decimal("0.15"), "cold")
+        .unwrap();
+    let treasury = repo
+        .get_fee_treasury(&market.id, &market.quote_asset)
+        .unwrap()
+        .unwrap();
+    assert_eq!(treasury.collected_amount, decimal("0.05"));
+    assert_eq!(treasury.total_withdrawn, decimal("0.15"));
+    assert!(
+        repo.withdraw_from_fee_treasury(&market.id, &market.quote_asset, decimal("1"), "cold")
+            .is_err()
+    );
+
+    let sheet = repo.get_balance_sheet().unwrap();
+    for totals in &sheet.assets {
+        assert_eq!(
+            &totals.available + &totals.locked + &totals.reserved + &totals.fee_treasury,
+            &totals.total_deposited - &totals.total_withdrawn
+        );
+    }
+}
+
+#[test]
+fn test_settlement_dead_letters_are_kept_per_market() {
+    let repo = sqlite_repository();
+    let market = create_test_market(&repo);
+    let other_market = create_test_market(&repo);
+    for (market_id, sequence) in [(&market.id, 3), (&other_market.id, 4), (&market.id, 7)] {
+        repo.store_settlement_dead_letter(NewSettlementDeadLetter {
+            market_id: market_id.clone(),
+            sequence,
+            fills: "[]".to_string(),
+            error: "Failed to fetch orders".to_string(),
+            create_time: 0,
+        })
+        .unwrap();
+    }
+
+    let letters = repo.get_settlement_dead_letters(&market.id).unwrap();
+    let sequences: Vec<i64> = letters.iter().map(|letter| letter.sequence).collect();
+    assert_eq!(sequences, vec![3, 7]);
+}
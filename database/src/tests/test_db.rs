@@ -0,0 +1,330 @@
+use crate::models::models::*;
+use crate::provider::{
+    FeeTreasuryDatabaseWriter, MarketDatabaseWriter, OrderDatabaseWriter, TradeDatabaseWriter,
+    WalletDatabaseWriter,
+};
+use crate::repository::Repository;
+use crate::{DbConnection, DbPool, establish_connection_pool};
+use bigdecimal::BigDecimal;
+use common::utils::{get_utc_now_millis, get_uuid_string};
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::sql_query;
+use diesel::sql_types::Text;
+use diesel_migrations::{EmbeddedMigrations, MigrationHarness, embed_migrations};
+use std::env;
+use std::str::FromStr;
+use std::sync::{Mutex, OnceLock};
+
+/// Parses a decimal literal of a test
+pub fn decimal(value: &str) -> BigDecimal {
+    BigDecimal::from_str(value).unwrap()
+}
+
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("src/migrations");
+
+static TEST_POOL: OnceLock<DbPool> = OnceLock::new();
+
+/// Returns a repository backed by the database in `DATABASE_URL`, with all migrations applied.
+///
+/// Returns `None` when `DATABASE_URL` is not set, so database tests are skipped on machines
+/// without a Postgres instance.
+pub fn test_repository() -> Option<Repository> {
+    let database_url = match env::var("DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            eprintln!("DATABASE_URL is not set, skipping database test");
+            return None;
+        }
+    };
+
+    let pool = TEST_POOL.get_or_init(|| {
+        let pool = establish_connection_pool(database_url, 32);
+        let conn = &mut pool
+            .get()
+            .expect("Failed to get a connection from the pool");
+        conn.run_pending_migrations(MIGRATIONS)
+            .expect("Failed to run migrations");
+        pool
+    });
+
+    Some(Repository::new(pool.clone()))
+}
+
+/// Short random suffix, used to keep assets and market ids of concurrent tests apart.
+pub fn unique_suffix() -> String {
+    get_uuid_string().replace('-', "")[..8].to_uppercase()
+}
+
+/// Returns a repository on a database of its own, cloned from a migrated template.
+///
+/// Use it for tests that load every market or order in the database (e.g. through a
+/// `MarketManager`), so they cannot pick up rows written by tests running alongside them.
+pub fn isolated_test_repository() -> Option<Repository> {
+    let database_url = env::var("DATABASE_URL").ok()?;
+    let (server_url, database_name) = split_database_url(&database_url);
+    let template_name = format!("{}_template", database_name);
+
+    // Postgres refuses concurrent clones of one template, so creations are serialized
+    static TEMPLATE_LOCK: Mutex<bool> = Mutex::new(false);
+    let mut template_ready = TEMPLATE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let admin = &mut PgConnection::establish(&database_url)
+        .expect("Failed to connect to the test database");
+
+    if !*template_ready {
+        // Leftovers from previous runs are dropped before the template is rebuilt
+        let stale_databases =
+            sql_query("SELECT datname FROM pg_database WHERE datname = $1 OR datname LIKE $2")
+                .bind::<Text, _>(&template_name)
+                .bind::<Text, _>(format!("{}\\_iso\\_%", database_name))
+                .load::<DatabaseName>(admin)
+                .expect("Failed to list test databases");
+        for stale in stale_databases {
+            sql_query(format!(
+                "DROP DATABASE IF EXISTS \"{}\" WITH (FORCE)",
+                stale.datname
+            ))
+            .execute(admin)
+            .expect("Failed to drop stale test database");
+        }
+
+        sql_query(format!("CREATE DATABASE \"{}\"", template_name))
+            .execute(admin)
+            .expect("Failed to create template database");
+        let template = &mut PgConnection::establish(&format!("{}/{}", server_url, template_name))
+            .expect("Failed to connect to the template database");
+        template
+            .run_pending_migrations(MIGRATIONS)
+            .expect("Failed to run migrations");
+        *template_ready = true;
+    }
+
+    let isolated_name = format!("{}_iso_{}", database_name, unique_suffix().to_lowercase());
+    sql_query(format!(
+        "CREATE DATABASE \"{}\" TEMPLATE \"{}\"",
+        isolated_name, template_name
+    ))
+    .execute(admin)
+    .expect("Failed to create isolated test database");
+
+    let pool = establish_connection_pool(format!("{}/{}", server_url, isolated_name), 8);
+    Some(Repository::new(pool))
+}
+
+/// Lock taken by [`lock_table`] or [`lock_wallet`], held until dropped. Queries it blocks
+/// meanwhile let tests stall the code paths running them.
+pub struct TableLock {
+    conn: DbConnection,
+}
+
+impl Drop for TableLock {
+    fn drop(&mut self) {
+        let _ = sql_query("ROLLBACK").execute(&mut self.conn);
+    }
+}
+
+pub fn lock_table(repo: &Repository, table: &str) -> TableLock {
+    let mut conn = repo.get_conn().expect("Failed to get a connection");
+    sql_query("BEGIN")
+        .execute(&mut conn)
+        .expect("Failed to begin transaction");
+    sql_query(format!("LOCK TABLE {} IN ACCESS EXCLUSIVE MODE", table))
+        .execute(&mut conn)
+        .expect("Failed to lock table");
+    TableLock { conn }
+}
+
+/// Row lock on one wallet: updates of its balances block until the lock is dropped, queries
+/// of other wallets do not.
+pub fn lock_wallet(repo: &Repository, user_id: &str, asset: &str) -> TableLock {
+    let mut conn = repo.get_conn().expect("Failed to get a connection");
+    sql_query("BEGIN")
+        .execute(&mut conn)
+        .expect("Failed to begin transaction");
+    sql_query("SELECT 1 FROM wallets WHERE user_id = $1 AND asset = $2 FOR UPDATE")
+        .bind::<Text, _>(user_id)
+        .bind::<Text, _>(asset)
+        .execute(&mut conn)
+        .expect("Failed to lock wallet");
+    TableLock { conn }
+}
+
+#[derive(QueryableByName)]
+struct DatabaseName {
+    #[diesel(sql_type = Text)]
+    datname: String,
+}
+
+/// Splits `postgres://user@host:port/name?params` into the server part and the database name.
+fn split_database_url(database_url: &str) -> (String, String) {
+    let without_params = database_url.split('?').next().unwrap_or(database_url);
+    let (server_url, database_name) = without_params
+        .rsplit_once('/')
+        .expect("DATABASE_URL has no database name");
+    (server_url.to_string(), database_name.to_string())
+}
+
+/// Creates a market on two freshly named assets, together with its fee treasury rows.
+pub fn create_test_market(
+    repo: &(impl MarketDatabaseWriter + FeeTreasuryDatabaseWriter),
+) -> Market {
+    create_test_market_with_precision(repo, 8, 8)
+}
+
+/// Same as [`create_test_market`], with explicit price and amount precisions.
+pub fn create_test_market_with_precision(
+    repo: &(impl MarketDatabaseWriter + FeeTreasuryDatabaseWriter),
+    price_precision: i32,
+    amount_precision: i32,
+) -> Market {
+    create_market_with_treasuries(repo, new_test_market(price_precision, amount_precision))
+}
+
+/// Same as [`create_test_market`], on a fresh base asset quoted in `quote_asset`, which other
+/// test markets may share.
+pub fn create_test_market_quoted_in(repo: &Repository, quote_asset: &str) -> Market {
+    create_market_with_treasuries(
+        repo,
+        NewMarket {
+            quote_asset: quote_asset.to_string(),
+            ..new_test_market(8, 8)
+        },
+    )
+}
+
+fn create_market_with_treasuries(
+    repo: &(impl MarketDatabaseWriter + FeeTreasuryDatabaseWriter),
+    new_market: NewMarket,
+) -> Market {
+    let market = repo
+        .create_market(new_market)
+        .expect("Failed to create test market");
+
+    for asset in [&market.base_asset, &market.quote_asset] {
+        repo.create_fee_treasury(NewFeeTreasury {
+            market_id: market.id.clone(),
+            asset: asset.clone(),
+            treasury_address: format!("treasury-{}", asset),
+            collected_amount: BigDecimal::from(0),
+            last_update_time: get_utc_now_millis(),
+        })
+        .expect("Failed to create test fee treasury");
+    }
+
+    market
+}
+
+/// Builds an active market on two freshly named assets, ready to be passed to `create_market`.
+pub fn new_test_market(price_precision: i32, amount_precision: i32) -> NewMarket {
+    let suffix = unique_suffix();
+    NewMarket {
+        id: get_uuid_string(),
+        base_asset: format!("B{}", suffix),
+        quote_asset: format!("Q{}", suffix),
+        default_maker_fee: BigDecimal::from_str("0.001").unwrap(),
+        default_taker_fee: BigDecimal::from_str("0.002").unwrap(),
+        create_time: get_utc_now_millis(),
+        update_time: get_utc_now_millis(),
+        status: MarketStatus::Active.as_str().to_string(),
+        min_base_amount: BigDecimal::from(0),
+        min_quote_amount: BigDecimal::from(0),
+        price_precision,
+        amount_precision,
+    }
+}
+
+/// Creates a new user id and funds it with the given amount of each asset.
+pub fn create_funded_user(repo: &impl WalletDatabaseWriter, funds: &[(&str, &str)]) -> String {
+    let user_id = get_uuid_string();
+    for (asset, amount) in funds {
+        repo.deposit_balance(&user_id, asset, BigDecimal::from_str(amount).unwrap())
+            .expect("Failed to fund test user");
+    }
+    user_id
+}
+
+/// Builds an open GTC limit order for `market`, ready to be passed to `create_order`.
+pub fn new_limit_order(
+    market: &Market,
+    user_id: &str,
+    side: OrderSide,
+    price: &str,
+    base_amount: &str,
+) -> NewOrder {
+    let price = BigDecimal::from_str(price).unwrap();
+    let base_amount = BigDecimal::from_str(base_amount).unwrap();
+    let quote_amount = &price * &base_amount;
+    NewOrder {
+        id: get_uuid_string(),
+        market_id: market.id.clone(),
+        user_id: user_id.to_string(),
+        order_type: OrderType::Limit.as_str().to_string(),
+        side: side.as_str().to_string(),
+        price,
+        base_amount: base_amount.clone(),
+        quote_amount: quote_amount.clone(),
+        maker_fee: market.default_maker_fee.clone(),
+        taker_fee: market.default_taker_fee.clone(),
+        create_time: get_utc_now_millis(),
+        remained_base: base_amount,
+        remained_quote: quote_amount,
+        filled_base: BigDecimal::from(0),
+        filled_quote: BigDecimal::from(0),
+        filled_fee: BigDecimal::from(0),
+        update_time: get_utc_now_millis(),
+        status: OrderStatus::Open.as_str().to_string(),
+        client_order_id: None,
+        post_only: Some(false),
+        time_in_force: Some(TimeInForce::GTC.as_str().to_string()),
+        expires_at: None,
+        display_amount: None,
+        priority: 0,
+    }
+}
+
+/// Places a crossing buy and sell order of `base_amount` at `price` and settles them against
+/// each other, with the buyer as taker.
+pub fn execute_test_trade(
+    repo: &(impl OrderDatabaseWriter + TradeDatabaseWriter),
+    market: &Market,
+    buyer_id: &str,
+    seller_id: &str,
+    price: &str,
+    base_amount: &str,
+) -> NewTrade {
+    let buy_order = repo
+        .create_order(new_limit_order(
+            market,
+            buyer_id,
+            OrderSide::Buy,
+            price,
+            base_amount,
+        ))
+        .expect("Failed to create test buy order");
+    let sell_order = repo
+        .create_order(new_limit_order(
+            market,
+            seller_id,
+            OrderSide::Sell,
+            price,
+            base_amount,
+        ))
+        .expect("Failed to create test sell order");
+
+    repo.execute_limit_trade(
+        true,
+        market.id.clone(),
+        market.base_asset.clone(),
+        market.quote_asset.clone(),
+        buyer_id.to_string(),
+        seller_id.to_string(),
+        buy_order.id,
+        sell_order.id,
+        buy_order.price,
+        buy_order.base_amount,
+        buy_order.quote_amount,
+        market.default_taker_fee.clone(),
+        market.default_maker_fee.clone(),
+    )
+    .expect("Failed to execute test trade")
+}
@@ -0,0 +1,719 @@
+use crate::filters::{OrderFilter, TradeFilter};
+use crate::models::models::{
+    CancelReason, Market, MarketRole, NewTrade, OrderSide, OrderStatus, TradeFill, UserFeePaid,
+};
+use crate::provider::{
+    FeeTreasuryDatabaseReader, OrderDatabaseReader, OrderDatabaseWriter, TradeDatabaseReader,
+    TradeDatabaseWriter, WalletDatabaseReader,
+};
+use crate::repository::{MissingWalletPolicy, Repository, SettlementError};
+use crate::tests::test_db::*;
+use bigdecimal::BigDecimal;
+use common::db::pagination::{Cursor, Pagination};
+use std::str::FromStr;
+use std::thread;
+
+#[test]
+fn test_get_user_fees_paid() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repo);
+    let funds = [
+        (market.base_asset.as_str(), "100"),
+        (market.quote_asset.as_str(), "1000"),
+    ];
+    let user_id = create_funded_user(&repo, &funds);
+    let counterparty_id = create_funded_user(&repo, &funds);
+
+    // As taker buyer: 0.002 * 2 base
+    execute_test_trade(&repo, &market, &user_id, &counterparty_id, "10", "2");
+    // As maker seller: 0.001 * 30 quote
+    execute_test_trade(&repo, &market, &counterparty_id, &user_id, "10", "3");
+
+    let fees = repo.get_user_fees_paid(&user_id, None, None).unwrap();
+    assert_eq!(
+        fees,
+        vec![
+            UserFeePaid {
+                asset: market.base_asset.clone(),
+                amount: BigDecimal::from_str("0.004").unwrap(),
+            },
+            UserFeePaid {
+                asset: market.quote_asset.clone(),
+                amount: BigDecimal::from_str("0.03").unwrap(),
+            },
+        ]
+    );
+
+    // Nothing falls in a window that ends before the trades
+    assert!(
+        repo.get_user_fees_paid(&user_id, None, Some(0))
+            .unwrap()
+            .is_empty()
+    );
+}
+
+/// Places crossing orders of one base at 10 and settles them, with the buyer as taker.
+fn settle_crossing_orders(
+    repo: &Repository,
+    market: &Market,
+    buyer_id: &str,
+    seller_id: &str,
+) -> anyhow::Result<NewTrade> {
+    let buy_order = repo
+        .create_order(new_limit_order(market, buyer_id, OrderSide::Buy, "10", "1"))
+        .unwrap();
+    let sell_order = repo
+        .create_order(new_limit_order(
+            market,
+            seller_id,
+            OrderSide::Sell,
+            "10",
+            "1",
+        ))
+        .unwrap();
+
+    repo.execute_limit_trade(
+        true,
+        market.id.clone(),
+        market.base_asset.clone(),
+        market.quote_asset.clone(),
+        buyer_id.to_string(),
+        seller_id.to_string(),
+        buy_order.id,
+        sell_order.id,
+        buy_order.price,
+        buy_order.base_amount,
+        buy_order.quote_amount,
+        market.default_taker_fee.clone(),
+        market.default_maker_fee.clone(),
+    )
+}
+
+#[test]
+fn test_partially_filled_sell_order_keeps_remaining_fields_coherent() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repo);
+    let funds = [
+        (market.base_asset.as_str(), "100"),
+        (market.quote_asset.as_str(), "1000"),
+    ];
+    let seller_id = create_funded_user(&repo, &funds);
+    let sell_order = repo
+        .create_order(new_limit_order(
+            &market,
+            &seller_id,
+            OrderSide::Sell,
+            "10",
+            "5",
+        ))
+        .unwrap();
+
+    // Filled once at its own price as maker, once above it as taker
+    for (price, base_amount, is_buyer_taker) in [("10", "2", true), ("11", "1", false)] {
+        let buyer_id = create_funded_user(&repo, &funds);
+        let buy_order = repo
+            .create_order(new_limit_order(
+                &market,
+                &buyer_id,
+                OrderSide::Buy,
+                price,
+                base_amount,
+            ))
+            .unwrap();
+        repo.execute_limit_trade(
+            is_buyer_taker,
+            market.id.clone(),
+            market.base_asset.clone(),
+            market.quote_asset.clone(),
+            buyer_id,
+            seller_id.clone(),
+            buy_order.id,
+            sell_order.id.clone(),
+            buy_order.price,
+            buy_order.base_amount,
+            buy_order.quote_amount,
+            market.default_taker_fee.clone(),
+            market.default_maker_fee.clone(),
+        )
+        .unwrap();
+    }
+
+    let order = repo.get_order(&sell_order.id).unwrap().unwrap();
+    assert_eq!(order.get_status().unwrap(), OrderStatus::PartiallyFilled);
+    assert_eq!(order.filled_base, BigDecimal::from(3));
+    assert_eq!(order.filled_quote, BigDecimal::from(31));
+    assert_eq!(order.remained_base, BigDecimal::from(2));
+    assert_eq!(&order.filled_base + &order.remained_base, order.base_amount);
+    // Valued at the order's price, whatever the fills were priced at
+    assert_eq!(order.remained_quote, BigDecimal::from(20));
+    assert_eq!(
+        order.average_fill_price(),
+        Some(BigDecimal::from(31) / BigDecimal::from(3))
+    );
+    assert_eq!(sell_order.average_fill_price(), None);
+
+    let fills = repo.get_order_fills(&sell_order.id).unwrap();
+    assert_eq!(fills.len(), 2);
+    assert!(fills.iter().all(|fill| fill.side == OrderSide::Sell));
+    for fill in &fills {
+        let expected = if fill.trade.price == 10 {
+            MarketRole::Maker
+        } else {
+            MarketRole::Taker
+        };
+        assert_eq!(fill.role, expected);
+        assert_eq!(fill.fee, fill.trade.seller_fee);
+    }
+    assert_eq!(fills[1].filled_base, order.filled_base);
+    assert_eq!(fills[0].filled_base, fills[0].trade.base_amount);
+}
+
+#[test]
+fn test_settlement_with_missing_counterparty_wallet() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repo);
+    // Neither side holds a wallet in the asset it is about to receive
+    let buyer_id = create_funded_user(&repo, &[(&market.quote_asset, "100")]);
+    let seller_id = create_funded_user(&repo, &[(&market.base_asset, "10")]);
+
+    let strict_repo = repo
+        .clone()
+        .with_missing_wallet_policy(MissingWalletPolicy::Fail);
+    let error = settle_crossing_orders(&strict_repo, &market, &buyer_id, &seller_id).unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<SettlementError>(),
+        Some(SettlementError::WalletMissing { user_id, asset })
+            if *user_id == seller_id && *asset == market.quote_asset
+    ));
+
+    // The whole trade rolled back: no fills, funds still locked, no wallet created
+    let orders = repo
+        .list_orders(
+            OrderFilter::new().market_id(Some(market.id.clone())),
+            Some(Pagination::default()),
+        )
+        .unwrap();
+    assert_eq!(orders.items.len(), 2);
+    for order in &orders.items {
+        assert_eq!(order.get_status().unwrap(), OrderStatus::Open);
+        assert_eq!(order.filled_base, BigDecimal::from(0));
+    }
+    let seller_base = repo
+        .get_wallet(&seller_id, &market.base_asset)
+        .unwrap()
+        .unwrap();
+    assert_eq!(seller_base.locked, BigDecimal::from(1));
+    assert!(
+        repo.get_wallet(&seller_id, &market.quote_asset)
+            .unwrap()
+            .is_none()
+    );
+
+    // By default the missing wallets are created and credited
+    let trade = settle_crossing_orders(&repo, &market, &buyer_id, &seller_id).unwrap();
+    let seller_quote = repo
+        .get_wallet(&seller_id, &market.quote_asset)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        seller_quote.available,
+        &trade.quote_amount - &trade.seller_fee
+    );
+    let buyer_base = repo
+        .get_wallet(&buyer_id, &market.base_asset)
+        .unwrap()
+        .unwrap();
+    assert_eq!(buyer_base.available, &trade.base_amount - &trade.buyer_fee);
+}
+
+#[test]
+fn test_trade_detail_balance_snapshots_match_settled_wallets() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repo);
+    let funds = [
+        (market.base_asset.as_str(), "10"),
+        (market.quote_asset.as_str(), "100"),
+    ];
+    let buyer_id = create_funded_user(&repo, &funds);
+    let seller_id = create_funded_user(&repo, &funds);
+
+    // Off by default: the trade is found, without snapshots
+    let trade = execute_test_trade(&repo, &market, &buyer_id, &seller_id, "10", "1");
+    let detail = repo.get_trade_detail(&trade.id).unwrap().unwrap();
+    assert_eq!(detail.trade.id, trade.id);
+    assert!(detail.balance_snapshots.is_empty());
+
+    let snapshot_repo = repo.clone().with_balance_snapshots(true);
+    let trade = execute_test_trade(&snapshot_repo, &market, &buyer_id, &seller_id, "10", "2");
+    let detail = repo.get_trade_detail(&trade.id).unwrap().unwrap();
+    assert_eq!(detail.balance_snapshots.len(), 4);
+
+    for snapshot in &detail.balance_snapshots {
+        let wallet = repo
+            .get_wallet(&snapshot.user_id, &snapshot.asset)
+            .unwrap()
+            .unwrap();
+        assert_eq!(snapshot.available_after, wallet.available);
+        assert_eq!(snapshot.locked_after, wallet.locked);
+
+        let available_change = &snapshot.available_after - &snapshot.available_before;
+        let locked_change = &snapshot.locked_after - &snapshot.locked_before;
+        let (expected_available, expected_locked) = match (
+            snapshot.user_id == buyer_id,
+            snapshot.asset == market.base_asset,
+        ) {
+            (true, true) => (&trade.base_amount - &trade.buyer_fee, BigDecimal::from(0)),
+            (true, false) => (BigDecimal::from(0), -trade.quote_amount.clone()),
+            (false, true) => (BigDecimal::from(0), -trade.base_amount.clone()),
+            (false, false) => (&trade.quote_amount - &trade.seller_fee, BigDecimal::from(0)),
+        };
+        assert_eq!(available_change, expected_available, "{:?}", snapshot);
+        assert_eq!(locked_change, expected_locked, "{:?}", snapshot);
+    }
+
+    assert!(repo.get_trade_detail("missing-trade").unwrap().is_none());
+}
+
+#[test]
+fn test_parallel_trades_in_markets_sharing_a_quote_asset_settle_exactly() {
+    const MARKETS: usize = 8;
+    const TRADES_PER_MARKET: usize = 5;
+
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let quote_asset = format!("Q{}", unique_suffix());
+    let markets: Vec<Market> = (0..MARKETS)
+        .map(|_| create_test_market_quoted_in(&repo, &quote_asset))
+        .collect();
+    // The same two users trade in every market, so all settlements meet on their quote wallets
+    let buyer_funds: Vec<(&str, &str)> = markets
+        .iter()
+        .map(|market| (market.base_asset.as_str(), "0"))
+        .chain([(quote_asset.as_str(), "1000")])
+        .collect();
+    let seller_funds: Vec<(&str, &str)> = markets
+        .iter()
+        .map(|market| (market.base_asset.as_str(), "10"))
+        .chain([(quote_asset.as_str(), "0")])
+        .collect();
+    let buyer_id = create_funded_user(&repo, &buyer_funds);
+    let seller_id = create_funded_user(&repo, &seller_funds);
+
+    thread::scope(|scope| {
+        for market in &markets {
+            let (repo, buyer_id, seller_id) = (&repo, &buyer_id, &seller_id);
+            scope.spawn(move || {
+                for _ in 0..TRADES_PER_MARKET {
+                    execute_test_trade(repo, market, buyer_id, seller_id, "2", "1");
+                }
+            });
+        }
+    });
+
+    let wallet = |user_id: &str, asset: &str| repo.get_wallet(user_id, asset).unwrap().unwrap();
+    let trades = BigDecimal::from((MARKETS * TRADES_PER_MARKET) as i64);
+    let market = &markets[0];
+    // Each trade moves 2 quote: the buyer pays it whole, the seller gets it less the maker fee
+    let buyer_quote = wallet(&buyer_id, &quote_asset);
+    assert_eq!(buyer_quote.available, BigDecimal::from(1000) - &trades * 2);
+    assert_eq!(buyer_quote.locked, BigDecimal::from(0));
+    let seller_quote = wallet(&seller_id, &quote_asset);
+    let maker_fee = BigDecimal::from(2) * &market.default_maker_fee;
+    assert_eq!(
+        seller_quote.available,
+        &trades * (BigDecimal::from(2) - maker_fee)
+    );
+
+    let per_market = BigDecimal::from(TRADES_PER_MARKET as i64);
+    for market in &markets {
+        let buyer_base = wallet(&buyer_id, &market.base_asset);
+        let taker_fee = &market.default_taker_fee;
+        assert_eq!(
+            buyer_base.available,
+            &per_market * (BigDecimal::from(1) - taker_fee)
+        );
+        let seller_base = wallet(&seller_id, &market.base_asset);
+        assert_eq!(seller_base.available, BigDecimal::from(10) - &per_market);
+        assert_eq!(seller_base.locked, BigDecimal::from(0));
+    }
+}
+
+/// A buy of 3 at 12 taking asks of 1 at 10, 11 and 12 from three sellers, as fills to settle.
+/// Returns the market, the buy order and the fills.
+fn sweep_fills(repo: &Repository) -> (Market, String, Vec<TradeFill>) {
+    let market = create_test_market(repo);
+    let funds = [
+        (market.base_asset.as_str(), "100"),
+        (market.quote_asset.as_str(), "1000"),
+    ];
+    let buyer_id = create_funded_user(repo, &funds);
+    let buy_order = repo
+        .create_order(new_limit_order(
+            &market,
+            &buyer_id,
+            OrderSide::Buy,
+            "12",
+            "3",
+        ))
+        .unwrap();
+
+    let fills = ["10", "11", "12"]
+        .into_iter()
+        .map(|price| {
+            let seller_id = create_funded_user(repo, &funds);
+            let sell_order = repo
+                .create_order(new_limit_order(
+                    &market,
+                    &seller_id,
+                    OrderSide::Sell,
+                    price,
+                    "1",
+                ))
+                .unwrap();
+            TradeFill {
+                is_buyer_taker: true,
+                buyer_user_id: buyer_id.clone(),
+                seller_user_id: seller_id,
+                buyer_order_id: buy_order.id.clone(),
+                seller_order_id: sell_order.id,
+                price: sell_order.price,
+                base_amount: sell_order.base_amount,
+                quote_amount: sell_order.quote_amount,
+                buyer_fee_rate: market.default_taker_fee.clone(),
+                seller_fee_rate: market.default_maker_fee.clone(),
+                trade_id: None,
+                timestamp: None,
+            }
+        })
+        .collect();
+    (market, buy_order.id, fills)
+}
+
+/// What a sweep left: the trades, the wallets and buy order they settled and the fees
+/// collected, comparable across markets.
+fn sweep_outcome(
+    repo: &Repository,
+    market: &Market,
+    buy_order_id: &str,
+    trades: &[NewTrade],
+) -> Vec<String> {
+    let mut outcome: Vec<String> = trades
+        .iter()
+        .map(|t| {
+            format!(
+                "{} {} {} {}",
+                t.price, t.base_amount, t.buyer_fee, t.seller_fee
+            )
+        })
+        .collect();
+
+    let buyer_id = &trades[0].buyer_user_id;
+    let mut wallets = vec![
+        (buyer_id, &market.base_asset),
+        (buyer_id, &market.quote_asset),
+    ];
+    wallets.extend(
+        trades
+            .iter()
+            .map(|t| (&t.seller_user_id, &market.quote_asset)),
+    );
+    for (user_id, asset) in wallets {
+        let wallet = repo.get_wallet(user_id, asset).unwrap().unwrap();
+        outcome.push(format!("{} {}", wallet.available, wallet.locked));
+    }
+
+    let order = repo.get_order(buy_order_id).unwrap().unwrap();
+    outcome.push(format!(
+        "{} {} {} {} {} {}",
+        order.filled_base,
+        order.filled_quote,
+        order.filled_fee,
+        order.remained_base,
+        order.remained_quote,
+        order.status
+    ));
+    for asset in [&market.base_asset, &market.quote_asset] {
+        let treasury = repo.get_fee_treasury(&market.id, asset).unwrap().unwrap();
+        outcome.push(treasury.collected_amount.to_string());
+    }
+    outcome
+}
+
+#[test]
+fn test_batched_fills_settle_like_one_at_a_time() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let repo = repo.with_balance_snapshots(true);
+
+    let (market, buy_order_id, fills) = sweep_fills(&repo);
+    let trades: Vec<NewTrade> = fills
+        .into_iter()
+        .map(|fill| {
+            repo.execute_limit_trade(
+                fill.is_buyer_taker,
+                market.id.clone(),
+                market.base_asset.clone(),
+                market.quote_asset.clone(),
+                fill.buyer_user_id,
+                fill.seller_user_id,
+                fill.buyer_order_id,
+                fill.seller_order_id,
+                fill.price,
+                fill.base_amount,
+                fill.quote_amount,
+                fill.buyer_fee_rate,
+                fill.seller_fee_rate,
+            )
+            .unwrap()
+        })
+        .collect();
+    let one_at_a_time = sweep_outcome(&repo, &market, &buy_order_id, &trades);
+
+    let (market, buy_order_id, fills) = sweep_fills(&repo);
+    let trades = repo
+        .execute_limit_trades(&market.id, &market.base_asset, &market.quote_asset, &fills)
+        .unwrap();
+    assert_eq!(
+        sweep_outcome(&repo, &market, &buy_order_id, &trades),
+        one_at_a_time
+    );
+    // The buyer paid 33 of the 36 it locked, the rest went back to it with the last fill
+    let buyer_quote = repo
+        .get_wallet(&trades[0].buyer_user_id, &market.quote_asset)
+        .unwrap()
+        .unwrap();
+    assert_eq!(buyer_quote.available, BigDecimal::from(967));
+    assert_eq!(buyer_quote.locked, BigDecimal::from(0));
+
+    // Snapshots still follow the buyer's wallet from one fill of the batch to the next
+    let buyer_quote_snapshot = |trade: &NewTrade| {
+        repo.get_trade_detail(&trade.id)
+            .unwrap()
+            .unwrap()
+            .balance_snapshots
+            .into_iter()
+            .find(|s| s.user_id == trade.buyer_user_id && s.asset == market.quote_asset)
+            .unwrap()
+    };
+    for pair in trades.windows(2) {
+        let (first, second) = (
+            buyer_quote_snapshot(&pair[0]),
+            buyer_quote_snapshot(&pair[1]),
+        );
+        assert_eq!(first.locked_after, second.locked_before);
+    }
+}
+
+#[test]
+fn test_batch_with_a_refused_fill_settles_nothing() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let (market, buy_order_id, fills) = sweep_fills(&repo);
+    let buyer_quote_before = repo
+        .get_wallet(&fills[0].buyer_user_id, &market.quote_asset)
+        .unwrap()
+        .unwrap();
+    // The last ask is gone by the time the batch settles
+    repo.cancel_order(&fills[2].seller_order_id, CancelReason::UserCanceled)
+        .unwrap();
+
+    assert!(
+        repo.execute_limit_trades(&market.id, &market.base_asset, &market.quote_asset, &fills)
+            .is_err()
+    );
+
+    // The fills before it rolled back with it
+    for order_id in [&buy_order_id, &fills[0].seller_order_id] {
+        let order = repo.get_order(order_id).unwrap().unwrap();
+        assert_eq!(order.get_status().unwrap(), OrderStatus::Open);
+        assert_eq!(order.filled_base, BigDecimal::from(0));
+    }
+    let buyer_quote = repo
+        .get_wallet(&fills[0].buyer_user_id, &market.quote_asset)
+        .unwrap()
+        .unwrap();
+    assert_eq!(buyer_quote.locked, buyer_quote_before.locked);
+    assert_eq!(buyer_quote.available, buyer_quote_before.available);
+}
+
+#[test]
+fn test_trades_page_by_cursor_without_gaps_or_repeats() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repo);
+    let buyer_id = create_funded_user(&repo, &[(&market.quote_asset, "1000")]);
+    let seller_id = create_funded_user(&repo, &[(&market.base_asset, "10")]);
+    for _ in 0..5 {
+        execute_test_trade(&repo, &market, &buyer_id, &seller_id, "10", "1");
+    }
+    let filter = || TradeFilter::new().market_id(Some(market.id.clone()));
+    let all = repo
+        .list_trades(
+            filter(),
+            Some(Pagination {
+                limit: Some(10),
+                ..Default::default()
+            }),
+        )
+        .unwrap();
+    assert_eq!(all.items.len(), 5);
+    assert!(!all.has_more);
+    assert!(all.next_cursor.is_none());
+
+    let mut paged = Vec::new();
+    let mut cursor = None;
+    loop {
+        let page = repo
+            .list_trades(
+                filter(),
+                Some(Pagination {
+                    limit: Some(2),
+                    cursor: cursor.take(),
+                    ..Default::default()
+                }),
+            )
+            .unwrap();
+        paged.extend(page.items.into_iter().map(|trade| trade.id));
+        match page.next_cursor {
+            Some(next) => cursor = Some(next),
+            None => break,
+        }
+    }
+    let expected: Vec<_> = all.items.into_iter().map(|trade| trade.id).collect();
+    assert_eq!(paged, expected);
+}
+
+#[test]
+fn test_trades_page_oldest_first_by_cursor() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repo);
+    let buyer_id = create_funded_user(&repo, &[(&market.quote_asset, "1000")]);
+    let seller_id = create_funded_user(&repo, &[(&market.base_asset, "10")]);
+    for price in ["12", "10", "11"] {
+        execute_test_trade(&repo, &market, &buyer_id, &seller_id, price, "1");
+    }
+    let list = |order_by: &str, cursor| {
+        repo.list_trades(
+            TradeFilter::new().market_id(Some(market.id.clone())),
+            Some(Pagination {
+                limit: Some(2),
+                order_by: Some(order_by.to_string()),
+                order_direction: Some("asc".to_string()),
+                cursor,
+                ..Default::default()
+            }),
+        )
+        .unwrap()
+    };
+
+    let cheapest = list("price", None);
+    assert_eq!(cheapest.items[0].price, BigDecimal::from(10));
+    assert!(cheapest.has_more);
+    // Pages sorted by price go by offset
+    assert!(cheapest.next_cursor.is_none());
+
+    let first = list("timestamp", None);
+    let second = list("timestamp", first.next_cursor.clone());
+    assert!(!second.has_more);
+    let trades: Vec<_> = first.items.iter().chain(&second.items).collect();
+    assert_eq!(trades.len(), 3);
+    assert!(
+        trades
+            .windows(2)
+            .all(|pair| (pair[0].timestamp, &pair[0].id) < (pair[1].timestamp, &pair[1].id))
+    );
+}
+
+#[test]
+fn test_export_walks_every_trade_oldest_first() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repo);
+    let buyer_id = create_funded_user(&repo, &[(&market.quote_asset, "1000")]);
+    let seller_id = create_funded_user(&repo, &[(&market.base_asset, "10")]);
+    for _ in 0..5 {
+        execute_test_trade(&repo, &market, &buyer_id, &seller_id, "10", "1");
+    }
+    let filter = || TradeFilter::new().market_id(Some(market.id.clone()));
+
+    let mut exported = Vec::new();
+    let mut after: Option<Cursor> = None;
+    loop {
+        let chunk = repo.export_trades(filter(), after.as_ref(), 2).unwrap();
+        let Some(last) = chunk.last() else {
+            break;
+        };
+        after = Some(Cursor::new(last.timestamp, last.id.clone()));
+        exported.extend(chunk);
+    }
+    assert_eq!(exported.len(), 5);
+    assert!(
+        exported
+            .windows(2)
+            .all(|pair| (pair[0].timestamp, &pair[0].id) < (pair[1].timestamp, &pair[1].id))
+    );
+
+    let other = repo
+        .export_trades(
+            TradeFilter::new().market_id(Some("NOPE".to_string())),
+            None,
+            2,
+        )
+        .unwrap();
+    assert!(other.is_empty());
+}
+
+#[test]
+fn test_user_traded_volumes_sum_per_quote_asset() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let quote_asset = format!("Q{}", unique_suffix());
+    let first = create_test_market_quoted_in(&repo, &quote_asset);
+    let second = create_test_market_quoted_in(&repo, &quote_asset);
+    let other = create_test_market(&repo);
+    let user_id = create_funded_user(
+        &repo,
+        &[(quote_asset.as_str(), "1000"), (&other.base_asset, "10")],
+    );
+    let counterparty_id = create_funded_user(
+        &repo,
+        &[
+            (first.base_asset.as_str(), "10"),
+            (&second.base_asset, "10"),
+            (&other.quote_asset, "1000"),
+        ],
+    );
+    execute_test_trade(&repo, &first, &user_id, &counterparty_id, "10", "2");
+    execute_test_trade(&repo, &second, &user_id, &counterparty_id, "5", "1");
+    execute_test_trade(&repo, &other, &counterparty_id, &user_id, "7", "1");
+
+    let volumes = repo.get_user_traded_volumes(&user_id, 0).unwrap();
+    let volume = |asset: &str| {
+        volumes
+            .iter()
+            .find(|volume| volume.asset == asset)
+            .map(|volume| volume.volume.clone())
+    };
+    assert_eq!(volumes.len(), 2);
+    assert_eq!(volume(&quote_asset), Some(BigDecimal::from(25)));
+    assert_eq!(volume(&other.quote_asset), Some(BigDecimal::from(7)));
+
+    let later = repo.get_user_traded_volumes(&user_id, i64::MAX).unwrap();
+    assert!(later.is_empty());
+}
@@ -0,0 +1,108 @@
+use crate::models::models::*;
+use crate::provider::{TransferDatabaseReader, TransferDatabaseWriter, WalletDatabaseReader};
+use crate::repository::{Repository, TransferError};
+use crate::tests::test_db::*;
+use common::utils::get_uuid_string;
+
+fn wallet(repo: &Repository, user_id: &str, asset: &str) -> Wallet {
+    repo.get_wallet(user_id, asset).unwrap().unwrap()
+}
+
+#[test]
+fn test_deposit_is_credited_once_per_external_id() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let asset = format!("D{}", unique_suffix());
+    let user_id = get_uuid_string();
+    let external_id = get_uuid_string();
+
+    let deposit = repo
+        .complete_deposit(&user_id, &asset, decimal("25"), &external_id)
+        .unwrap();
+    assert_eq!(deposit.status, TransferStatus::Completed.as_str());
+
+    // A notification delivered twice returns the recorded deposit
+    let again = repo
+        .complete_deposit(&user_id, &asset, decimal("25"), &external_id)
+        .unwrap();
+    assert_eq!(again.id, deposit.id);
+
+    let wallet = wallet(&repo, &user_id, &asset);
+    assert_eq!(wallet.available, decimal("25"));
+    assert_eq!(wallet.total_deposited, decimal("25"));
+}
+
+#[test]
+fn test_withdrawal_is_reserved_until_completed() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let asset = format!("W{}", unique_suffix());
+    let user_id = create_funded_user(&repo, &[(&asset, "100")]);
+
+    let withdrawal = repo
+        .request_withdrawal(&user_id, &asset, decimal("40"), "addr-1")
+        .unwrap();
+    assert_eq!(withdrawal.status, TransferStatus::Pending.as_str());
+    let reserved = wallet(&repo, &user_id, &asset);
+    assert_eq!(reserved.available, decimal("60"));
+    assert_eq!(reserved.reserved, decimal("40"));
+
+    // Only approved withdrawals can be sent out
+    let error = repo
+        .complete_withdrawal(&withdrawal.id, "tx-1")
+        .unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<TransferError>(),
+        Some(TransferError::InvalidStatus { .. })
+    ));
+
+    repo.approve_withdrawal(&withdrawal.id).unwrap();
+    let completed = repo.complete_withdrawal(&withdrawal.id, "tx-1").unwrap();
+    assert_eq!(completed.status, TransferStatus::Completed.as_str());
+    assert_eq!(completed.external_id.as_deref(), Some("tx-1"));
+
+    let settled = wallet(&repo, &user_id, &asset);
+    assert_eq!(settled.available, decimal("60"));
+    assert_eq!(settled.reserved, decimal("0"));
+    assert_eq!(settled.total_withdrawn, decimal("40"));
+    assert_eq!(repo.get_user_transfers(&user_id).unwrap().len(), 1);
+}
+
+#[test]
+fn test_rejected_withdrawal_returns_reserved_funds() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let asset = format!("W{}", unique_suffix());
+    let user_id = create_funded_user(&repo, &[(&asset, "100")]);
+
+    let error = repo
+        .request_withdrawal(&user_id, &asset, decimal("150"), "addr-1")
+        .unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<TransferError>(),
+        Some(TransferError::InsufficientBalance { .. })
+    ));
+
+    let withdrawal = repo
+        .request_withdrawal(&user_id, &asset, decimal("70"), "addr-1")
+        .unwrap();
+    let rejected = repo
+        .reject_withdrawal(&withdrawal.id, "address not whitelisted")
+        .unwrap();
+    assert_eq!(rejected.status, TransferStatus::Rejected.as_str());
+    assert_eq!(
+        rejected.reject_reason.as_deref(),
+        Some("address not whitelisted")
+    );
+
+    let wallet = wallet(&repo, &user_id, &asset);
+    assert_eq!(wallet.available, decimal("100"));
+    assert_eq!(wallet.reserved, decimal("0"));
+    assert_eq!(wallet.total_withdrawn, decimal("0"));
+
+    // A settled withdrawal cannot be rejected again
+    assert!(repo.reject_withdrawal(&withdrawal.id, "again").is_err());
+}
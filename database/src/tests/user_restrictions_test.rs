@@ -0,0 +1,89 @@
+use crate::models::models::*;
+use crate::provider::{
+    OrderDatabaseReader, OrderDatabaseWriter, UserRestrictionDatabaseReader,
+    UserRestrictionDatabaseWriter,
+};
+use crate::tests::test_db::*;
+
+#[test]
+fn test_user_restriction_is_replaced_until_deleted() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let user_id = format!("restricted-{}", unique_suffix());
+    assert!(repo.get_user_restriction(&user_id).unwrap().is_none());
+
+    let restriction = repo
+        .set_user_restriction(&user_id, UserStatus::CancelOnly, "KYC review")
+        .unwrap();
+    assert_eq!(restriction.status, "CANCEL_ONLY");
+    assert_eq!(
+        repo.get_user_restriction(&user_id).unwrap(),
+        Some(restriction.clone())
+    );
+
+    let banned = repo
+        .set_user_restriction(&user_id, UserStatus::Banned, "wash trading")
+        .unwrap();
+    assert_eq!(banned.status, "BANNED");
+    assert_eq!(banned.reason, "wash trading");
+    let restrictions = repo.list_user_restrictions().unwrap();
+    assert!(restrictions.contains(&banned));
+    assert!(!restrictions.contains(&restriction));
+
+    assert!(repo.delete_user_restriction(&user_id).unwrap());
+    assert!(!repo.delete_user_restriction(&user_id).unwrap());
+    assert!(repo.get_user_restriction(&user_id).unwrap().is_none());
+}
+
+#[test]
+fn test_user_active_orders_span_markets() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let first = create_test_market(&repo);
+    let second = create_test_market(&repo);
+    let user_id = create_funded_user(
+        &repo,
+        &[
+            (&first.base_asset, "10"),
+            (&second.base_asset, "10"),
+            (&first.quote_asset, "1000"),
+        ],
+    );
+
+    let buy = repo
+        .create_order(new_limit_order(&first, &user_id, OrderSide::Buy, "10", "1"))
+        .unwrap();
+    let sell = repo
+        .create_order(new_limit_order(
+            &second,
+            &user_id,
+            OrderSide::Sell,
+            "20",
+            "1",
+        ))
+        .unwrap();
+    let canceled = repo
+        .create_order(new_limit_order(
+            &first,
+            &user_id,
+            OrderSide::Sell,
+            "30",
+            "1",
+        ))
+        .unwrap();
+    repo.cancel_order(&canceled.id, CancelReason::UserCanceled)
+        .unwrap();
+
+    let mut ids: Vec<_> = repo
+        .get_user_active_orders(&user_id)
+        .unwrap()
+        .into_iter()
+        .map(|order| order.id)
+        .collect();
+    ids.sort();
+    let mut expected = [buy.id, sell.id];
+    expected.sort();
+    assert_eq!(ids, expected);
+}
@@ -0,0 +1,91 @@
+use crate::models::models::OrderSide;
+use crate::provider::{OrderDatabaseWriter, WalletDatabaseReader, WalletDatabaseWriter};
+use crate::tests::test_db::*;
+use bigdecimal::BigDecimal;
+
+#[test]
+fn test_release_orphaned_locks() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repo);
+    let user_id = create_funded_user(&repo, &[(&market.quote_asset, "1000")]);
+
+    // An open buy order legitimately locks 500 quote
+    repo.create_order(new_limit_order(
+        &market,
+        &user_id,
+        OrderSide::Buy,
+        "100",
+        "5",
+    ))
+    .unwrap();
+    // Simulate a crash that left another 200 locked without an order behind it
+    repo.lock_balance(&user_id, &market.quote_asset, BigDecimal::from(200))
+        .unwrap();
+
+    let released = repo.release_orphaned_locks(&user_id).unwrap();
+    assert_eq!(released.len(), 1);
+
+    let wallet = repo
+        .get_wallet(&user_id, &market.quote_asset)
+        .unwrap()
+        .unwrap();
+    assert_eq!(wallet.locked, BigDecimal::from(500));
+    assert_eq!(wallet.available, BigDecimal::from(500));
+
+    // Nothing is left to release once the locks match the open orders
+    assert!(repo.release_orphaned_locks(&user_id).unwrap().is_empty());
+}
+
+#[test]
+fn test_get_wallet_changes_returns_only_updated_wallets() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repo);
+    let user_id = create_funded_user(
+        &repo,
+        &[(&market.base_asset, "10"), (&market.quote_asset, "100")],
+    );
+    let since_time = repo
+        .get_wallet(&user_id, &market.quote_asset)
+        .unwrap()
+        .unwrap()
+        .update_time;
+
+    // Timestamps are in milliseconds, make sure the next update lands after `since_time`
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    repo.deposit_balance(&user_id, &market.base_asset, BigDecimal::from(5))
+        .unwrap();
+
+    let changes = repo.get_wallet_changes(&user_id, since_time).unwrap();
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].asset, market.base_asset);
+    assert_eq!(changes[0].available, BigDecimal::from(15));
+
+    let latest = changes[0].update_time;
+    assert!(
+        repo.get_wallet_changes(&user_id, latest)
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn test_get_user_wallets_lists_every_asset_of_the_user() {
+    let Some(repo) = test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repo);
+    let user_id = create_funded_user(
+        &repo,
+        &[(&market.quote_asset, "1000"), (&market.base_asset, "5")],
+    );
+    create_funded_user(&repo, &[(&market.quote_asset, "1")]);
+
+    let wallets = repo.get_user_wallets(&user_id).unwrap();
+    assert_eq!(wallets.len(), 2);
+    assert!(wallets.iter().all(|wallet| wallet.user_id == user_id));
+    assert!(wallets.windows(2).all(|pair| pair[0].asset < pair[1].asset));
+}
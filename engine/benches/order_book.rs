@@ -0,0 +1,209 @@
+//! Throughput of the order book at different depths: the book side alone, the order book
+//! with its matching and settlement over the mock persister, and whole simulated runs.
+//!
+//! Run with `cargo bench -p bitrade --features test-utils,simulator`, adding `-- book_side` for
+//! one group.
+
+use std::sync::Arc;
+
+use bitrade::models::trade_order::{OrderSide, OrderType, TradeOrder};
+use bitrade::order_book::book_side::BookSide;
+use bitrade::order_book::OrderBook;
+use bitrade::simulator::scenario::ScenarioEvent;
+use bitrade::simulator::Simulator;
+use bitrade::tests::test_models::create_order;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use database::mock::mock_persister::MockPersister;
+use database::models::models::CancelReason;
+
+const MARKET_ID: &str = "BENCH";
+/// Resting orders of the books benchmarked
+const DEPTHS: [usize; 2] = [1_000, 100_000];
+/// Price levels the resting orders spread over, on either side of 10_000
+const LEVELS: usize = 1_000;
+/// Events of a simulated run
+const RUN_EVENTS: usize = 10_000;
+
+fn order(user_id: &str, order_type: OrderType, side: OrderSide, price: usize) -> TradeOrder {
+    TradeOrder {
+        user_id: user_id.to_string(),
+        ..create_order(
+            side,
+            &price.to_string(),
+            "1",
+            &price.to_string(),
+            order_type,
+            MARKET_ID,
+        )
+    }
+}
+
+/// Price of the `n`th resting ask, or bid, spread evenly over the levels of its side
+fn resting_price(side: OrderSide, n: usize) -> usize {
+    match side {
+        OrderSide::Sell => 10_001 + n % LEVELS,
+        OrderSide::Buy => 9_999 - n % LEVELS,
+    }
+}
+
+fn book_side(depth: usize) -> (BookSide, Vec<String>) {
+    let mut side = BookSide::new(OrderSide::Sell);
+    let ids = (0..depth)
+        .map(|n| {
+            let order = order(
+                "maker",
+                OrderType::Limit,
+                OrderSide::Sell,
+                resting_price(OrderSide::Sell, n),
+            );
+            let id = order.id.clone();
+            side.push(order);
+            id
+        })
+        .collect();
+    (side, ids)
+}
+
+fn bench_book_side(c: &mut Criterion) {
+    let mut group = c.benchmark_group("book_side");
+    for depth in DEPTHS {
+        let (mut side, ids) = book_side(depth);
+        let incoming = order("taker", OrderType::Limit, OrderSide::Sell, 10_500);
+        group.bench_function(format!("add_cancel/{}", depth), |b| {
+            b.iter_batched(
+                || incoming.clone(),
+                |order| {
+                    let id = order.id.clone();
+                    side.push(order);
+                    side.remove(&id)
+                },
+                BatchSize::SmallInput,
+            )
+        });
+
+        // Takes out an order from within its level and queues it again at the back
+        let mut next = 0;
+        group.bench_function(format!("cancel_resting/{}", depth), |b| {
+            b.iter(|| {
+                next = (next + LEVELS / 2 + 1) % ids.len();
+                let order = side.remove(&ids[next]).unwrap();
+                side.push(order)
+            })
+        });
+
+        // What matching does with the best order it only partly fills
+        group.bench_function(format!("pop_best/{}", depth), |b| {
+            b.iter(|| {
+                let order = side.pop_best().unwrap();
+                side.push_front(order)
+            })
+        });
+    }
+    group.finish();
+}
+
+/// An order book of `depth` resting orders on either side, over a mock persister that does
+/// not track funds
+fn order_book(depth: usize) -> OrderBook<MockPersister> {
+    let mut order_book = OrderBook::new(
+        Arc::new(MockPersister::new()),
+        "BASE".to_string(),
+        MARKET_ID.to_string(),
+        "QUOTE".to_string(),
+    )
+    .unwrap();
+    for n in 0..depth / 2 {
+        for side in [OrderSide::Buy, OrderSide::Sell] {
+            let order = order("maker", OrderType::Limit, side, resting_price(side, n));
+            order_book.add_order(order).unwrap();
+        }
+    }
+    order_book
+}
+
+fn bench_order_book(c: &mut Criterion) {
+    let mut group = c.benchmark_group("order_book");
+    for depth in DEPTHS {
+        let mut order_book = order_book(depth);
+        let best_ask = resting_price(OrderSide::Sell, 0);
+
+        group.bench_function(format!("add_cancel/{}", depth), |b| {
+            b.iter_batched(
+                || order("taker", OrderType::Limit, OrderSide::Buy, 9_000),
+                |order| {
+                    let id = order.id.clone();
+                    order_book.add_order(order).unwrap();
+                    order_book.cancel_order(id, CancelReason::UserCanceled)
+                },
+                BatchSize::SmallInput,
+            )
+        });
+
+        // A buy takes the best ask whole, and a new ask restores the level behind it
+        group.bench_function(format!("match/{}", depth), |b| {
+            b.iter_batched(
+                || {
+                    (
+                        order("taker", OrderType::Limit, OrderSide::Buy, best_ask),
+                        order("maker", OrderType::Limit, OrderSide::Sell, best_ask),
+                    )
+                },
+                |(buy, ask)| {
+                    let trades = order_book.add_order(buy).unwrap();
+                    order_book.add_order(ask).unwrap();
+                    trades
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+/// A run alternating resting orders and orders crossing them, with some canceled, made the
+/// same every time from a fixed seed
+fn run_events() -> Vec<ScenarioEvent> {
+    let mut seed: u64 = 42;
+    let mut next = move |bound: usize| {
+        seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (seed >> 33) as usize % bound
+    };
+    let mut ids = Vec::new();
+    (0..RUN_EVENTS)
+        .map(|n| {
+            if n % 10 == 9 && !ids.is_empty() {
+                return ScenarioEvent::Cancel {
+                    order_id: ids.swap_remove(next(ids.len())),
+                    reason: CancelReason::UserCanceled,
+                };
+            }
+            let (user_id, side) = match next(2) {
+                0 => ("buyer", OrderSide::Buy),
+                _ => ("seller", OrderSide::Sell),
+            };
+            let order = order(user_id, OrderType::Limit, side, 9_950 + next(100));
+            ids.push(order.id.clone());
+            ScenarioEvent::Add(Box::new(order))
+        })
+        .collect()
+}
+
+fn bench_simulator(c: &mut Criterion) {
+    let events = run_events();
+    let mut group = c.benchmark_group("simulator");
+    group.throughput(Throughput::Elements(events.len() as u64));
+    group.sample_size(10);
+    group.bench_function(format!("run/{}", events.len()), |b| {
+        b.iter_batched(
+            || (Simulator::new(MARKET_ID, "BASE", "QUOTE"), events.clone()),
+            |(mut simulator, events)| simulator.run(events),
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_book_side, bench_order_book, bench_simulator);
+criterion_main!(benches);
@@ -1,4 +1,21 @@
+use std::process::Command;
+
 fn main() {
-    tonic_build::compile_protos("src/grpc/proto/spot.proto")
+    tonic_build::configure()
+        .compile_protos(
+            &["src/grpc/proto/spot.proto", "src/grpc/proto/admin.proto"],
+            &["src/grpc/proto"],
+        )
         .unwrap_or_else(|e| panic!("Failed to compile protos {:?}", e));
+
+    // Builds outside a git checkout (e.g. docker images) report an unknown hash
+    let git_hash = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|hash| hash.trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=GIT_HASH={}", git_hash);
 }
@@ -0,0 +1,84 @@
+use anyhow::{Context, Result};
+use bitrade::config::app_config::get_database_url;
+use bitrade::simulator::scenario::{journal_events, parse_scenario, ScenarioEvent};
+use bitrade::simulator::Simulator;
+use common::config::ConfigOptions;
+use database::establish_connection_pool;
+use database::provider::{EngineEventDatabaseReader, MarketDatabaseReader};
+use database::repository::Repository;
+use std::fs;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Replays recorded orders through the matching engine without a database, printing the
+/// trades they make as CSV. Run summary and timings go to stderr.
+#[derive(Debug, StructOpt)]
+#[structopt(name = "simulator")]
+struct Options {
+    /// Scenario file to replay, see `parse_scenario` for its format
+    #[structopt(long, parse(from_os_str), required_unless = "journal")]
+    scenario: Option<PathBuf>,
+    /// Market whose journal to replay, read from the database at `DATABASE_URL`
+    #[structopt(long, conflicts_with = "scenario")]
+    journal: Option<String>,
+    /// Market the scenario orders are placed in
+    #[structopt(long, default_value = "SIM")]
+    market_id: String,
+    #[structopt(long, default_value = "BASE")]
+    base_asset: String,
+    #[structopt(long, default_value = "QUOTE")]
+    quote_asset: String,
+    /// Times to run the events, each on a fresh book, to time the matching
+    #[structopt(long, default_value = "1")]
+    repeat: usize,
+    #[structopt(flatten)]
+    config: ConfigOptions,
+}
+
+/// The events to replay and the market, base and quote asset to replay them in
+fn load_events(options: &Options) -> Result<(Vec<ScenarioEvent>, String, String, String)> {
+    if let Some(market_id) = &options.journal {
+        let repository = Repository::new(establish_connection_pool(get_database_url(), 1));
+        let market = repository
+            .get_market(market_id)?
+            .with_context(|| format!("Market {} not found", market_id))?;
+        let events = journal_events(&repository.get_engine_events(market_id, 0)?)?;
+        return Ok((events, market.id, market.base_asset, market.quote_asset));
+    }
+    let path = options.scenario.as_ref().context("No scenario given")?;
+    let scenario = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read scenario {}", path.display()))?;
+    Ok((
+        parse_scenario(&options.market_id, &scenario)?,
+        options.market_id.clone(),
+        options.base_asset.clone(),
+        options.quote_asset.clone(),
+    ))
+}
+
+fn main() -> Result<()> {
+    let options = Options::from_args();
+    options.config.load()?;
+    let (events, market_id, base_asset, quote_asset) = load_events(&options)?;
+
+    let mut trade_log = None;
+    for run in 1..=options.repeat.max(1) {
+        let report = Simulator::new(&market_id, &base_asset, &quote_asset).run(events.clone());
+        eprintln!(
+            "Run {}: {} events, {} refused, {} trades in {:?}",
+            run,
+            report.events,
+            report.refused.len(),
+            report.trades.len(),
+            report.elapsed
+        );
+        if trade_log.is_none() {
+            for (index, reason) in &report.refused {
+                eprintln!("Event {} refused: {}", index + 1, reason);
+            }
+            trade_log = Some(report.trade_log());
+        }
+    }
+    print!("{}", trade_log.unwrap_or_default());
+    Ok(())
+}
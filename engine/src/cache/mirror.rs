@@ -0,0 +1,208 @@
+use anyhow::Result;
+use common::utils::get_utc_now_millis;
+use database::cache::{CachedDepth, CachedPrice};
+use database::provider::DatabaseProvider;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::RwLock;
+use tracing::error;
+
+use super::store::MarketDataStore;
+use crate::market::market_manager::{run_blocking, MarketManager};
+use crate::models::matched_trade::SequencedTrade;
+use crate::order_book::depth_diff::{DepthDelta, DepthSnapshot};
+
+pub const DEFAULT_CACHED_DEPTH_LEVELS: usize = 50;
+
+/// Keeps a market data store in step with the engine: the depth of every book after each
+/// change to it, the price of each trade, and the market stats once the stats updater
+/// refreshed them.
+///
+/// A task follows each market through its depth and trade subscriptions. One that falls
+/// behind the book stops, and the market is followed afresh on the next sync.
+pub struct MarketDataMirror<P: DatabaseProvider + 'static, S: MarketDataStore> {
+    market_manager: Arc<RwLock<MarketManager<P>>>,
+    persister: Arc<P>,
+    store: S,
+    depth_levels: usize,
+    /// Markets a task is following
+    followed: Mutex<HashSet<String>>,
+    /// Update time of the stats last written, per market
+    stored_stats: Mutex<HashMap<String, i64>>,
+}
+
+impl<P: DatabaseProvider + 'static, S: MarketDataStore + 'static> MarketDataMirror<P, S> {
+    pub fn new(market_manager: Arc<RwLock<MarketManager<P>>>, persister: Arc<P>, store: S) -> Self {
+        Self {
+            market_manager,
+            persister,
+            store,
+            depth_levels: DEFAULT_CACHED_DEPTH_LEVELS,
+            followed: Mutex::new(HashSet::new()),
+            stored_stats: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// How many levels of each side are mirrored
+    pub fn with_depth_levels(mut self, depth_levels: usize) -> Self {
+        self.depth_levels = depth_levels;
+        self
+    }
+
+    /// Starts following the markets nobody follows yet and writes the stats refreshed since
+    /// the last sync. Returns how many markets it started following.
+    ///
+    /// A market that cannot be followed, a stopped one for instance, is tried again on the
+    /// next sync.
+    pub async fn sync(self: &Arc<Self>) -> Result<usize> {
+        let market_ids = self.market_manager.read().await.market_ids()?;
+        let mut started = 0;
+        for market_id in &market_ids {
+            match self.follow(market_id).await {
+                Ok(true) => started += 1,
+                Ok(false) => {}
+                Err(e) => error!("Failed to mirror market {}: {:?}", market_id, e),
+            }
+        }
+        for market_id in &market_ids {
+            self.store_market_stats(market_id).await?;
+        }
+        Ok(started)
+    }
+
+    async fn follow(self: &Arc<Self>, market_id: &str) -> Result<bool> {
+        if !self.followed.lock().unwrap().insert(market_id.to_string()) {
+            return Ok(false);
+        }
+        let followed_id = market_id.to_string();
+        let subscriptions = run_blocking(&self.market_manager, move |market_manager| {
+            let depth = market_manager.subscribe_order_book(&followed_id)?;
+            let trades = market_manager.subscribe_trades(&followed_id, Some(0))?;
+            Ok((depth, trades))
+        })
+        .await;
+        let ((depth, sequence, depth_updates), (recent_trades, trade_updates)) = match subscriptions
+        {
+            Ok(subscriptions) => subscriptions,
+            Err(e) => {
+                self.followed.lock().unwrap().remove(market_id);
+                return Err(e);
+            }
+        };
+
+        let mirror = self.clone();
+        let market_id = market_id.to_string();
+        tokio::spawn(async move {
+            if let Some(last) = recent_trades.last() {
+                mirror.store_last_price(last).await;
+            }
+            mirror
+                .follow_market(&market_id, depth, sequence, depth_updates, trade_updates)
+                .await;
+            mirror.followed.lock().unwrap().remove(&market_id);
+        });
+        Ok(true)
+    }
+
+    /// Mirrors the changes of one market until its book goes away or the depth updates
+    /// outrun the task.
+    async fn follow_market(
+        &self,
+        market_id: &str,
+        mut depth: DepthSnapshot,
+        sequence: u64,
+        mut depth_updates: Receiver<DepthDelta>,
+        mut trade_updates: Receiver<SequencedTrade>,
+    ) {
+        self.store_depth(market_id, &depth, sequence).await;
+        loop {
+            tokio::select! {
+                delta = depth_updates.recv() => match delta {
+                    Ok(delta) => {
+                        depth.apply(&delta.diff);
+                        self.store_depth(market_id, &depth, delta.sequence).await;
+                    }
+                    Err(RecvError::Lagged(_)) | Err(RecvError::Closed) => return,
+                },
+                trade = trade_updates.recv() => match trade {
+                    Ok(trade) => self.store_last_price(&trade).await,
+                    // Only the latest price is kept, the trades missed do not matter
+                    Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => return,
+                },
+            }
+        }
+    }
+
+    async fn store_depth(&self, market_id: &str, depth: &DepthSnapshot, sequence: u64) {
+        let cached = CachedDepth {
+            market_id: market_id.to_string(),
+            bids: depth
+                .bids
+                .iter()
+                .rev()
+                .take(self.depth_levels)
+                .map(|(price, amount)| (price.clone(), amount.clone()))
+                .collect(),
+            asks: depth
+                .asks
+                .iter()
+                .take(self.depth_levels)
+                .map(|(price, amount)| (price.clone(), amount.clone()))
+                .collect(),
+            sequence,
+            update_time: get_utc_now_millis(),
+        };
+        if let Err(e) = self.store.set_depth(cached).await {
+            error!("Failed to mirror depth of market {}: {:?}", market_id, e);
+        }
+    }
+
+    async fn store_last_price(&self, trade: &SequencedTrade) {
+        let price = CachedPrice {
+            market_id: trade.trade.market_id.clone(),
+            price: trade.trade.price.clone(),
+            trade_time: trade.trade.timestamp,
+        };
+        if let Err(e) = self.store.set_last_price(price).await {
+            error!(
+                "Failed to mirror last price of market {}: {:?}",
+                trade.trade.market_id, e
+            );
+        }
+    }
+
+    async fn store_market_stats(&self, market_id: &str) -> Result<bool> {
+        let Some(stats) = self.persister.get_market_stats(market_id)? else {
+            return Ok(false);
+        };
+        let update_time = stats.last_update_time;
+        if self.stored_stats.lock().unwrap().get(market_id) == Some(&update_time) {
+            return Ok(false);
+        }
+        self.store.set_market_stats(stats).await?;
+        self.stored_stats
+            .lock()
+            .unwrap()
+            .insert(market_id.to_string(), update_time);
+        Ok(true)
+    }
+}
+
+/// Syncs the mirror every `interval`, which bounds how long a new market or refreshed stats
+/// take to reach the store. Book changes and trades reach it as they happen.
+pub async fn run_market_data_mirror<P: DatabaseProvider + 'static, S: MarketDataStore + 'static>(
+    mirror: Arc<MarketDataMirror<P, S>>,
+    interval: Duration,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        if let Err(e) = mirror.sync().await {
+            error!("Failed to sync market data mirror: {:?}", e);
+        }
+    }
+}
@@ -0,0 +1,30 @@
+use anyhow::Result;
+#[cfg(feature = "redis-cache")]
+use database::cache::redis_cache::RedisMarketCache;
+use database::cache::{CachedDepth, CachedPrice};
+use database::models::models::MarketStat;
+use std::future::Future;
+
+/// Where the market data mirror writes what it follows
+pub trait MarketDataStore: Send + Sync {
+    fn set_depth(&self, depth: CachedDepth) -> impl Future<Output = Result<()>> + Send;
+
+    fn set_market_stats(&self, stats: MarketStat) -> impl Future<Output = Result<()>> + Send;
+
+    fn set_last_price(&self, price: CachedPrice) -> impl Future<Output = Result<()>> + Send;
+}
+
+#[cfg(feature = "redis-cache")]
+impl MarketDataStore for RedisMarketCache {
+    async fn set_depth(&self, depth: CachedDepth) -> Result<()> {
+        RedisMarketCache::set_depth(self, &depth).await
+    }
+
+    async fn set_market_stats(&self, stats: MarketStat) -> Result<()> {
+        RedisMarketCache::set_market_stats(self, &stats).await
+    }
+
+    async fn set_last_price(&self, price: CachedPrice) -> Result<()> {
+        RedisMarketCache::set_last_price(self, &price).await
+    }
+}
@@ -0,0 +1,142 @@
+use anyhow::{Context, Result};
+use bigdecimal::{BigDecimal, Zero};
+use common::utils::get_utc_now_millis;
+use database::models::models::{FeeTier, FeeTreasury, FeeTreasuryWithdrawal};
+use database::provider::DatabaseProvider;
+use std::sync::Arc;
+
+use crate::models::trade_order::TradeOrder;
+
+/// Traded volume fee tiers are picked on, in seconds like trade timestamps
+pub const FEE_VOLUME_WINDOW_SECS: i64 = 30 * 24 * 60 * 60;
+
+#[derive(Debug, Clone)]
+pub struct FeeService<P: DatabaseProvider> {
+    persister: Arc<P>,
+}
+
+impl<P: DatabaseProvider> FeeService<P> {
+    pub fn new(persister: Arc<P>) -> Self {
+        Self { persister }
+    }
+
+    /// The highest tier of `market_id` that `user_id`'s volume over the last 30 days reaches.
+    /// `None` when the market has no tiers or the volume is below all of them.
+    pub fn fee_tier(&self, user_id: &str, market_id: &str) -> Result<Option<FeeTier>> {
+        let tiers = self
+            .persister
+            .get_fee_tiers(market_id)
+            .context("Failed to fetch fee tiers")?;
+        if tiers.is_empty() {
+            return Ok(None);
+        }
+
+        let start_time = get_utc_now_millis() / 1000 - FEE_VOLUME_WINDOW_SECS;
+        let volume = self
+            .persister
+            .get_user_traded_volume(user_id, market_id, start_time)
+            .context("Failed to fetch traded volume")?;
+
+        Ok(tiers
+            .into_iter()
+            .rev()
+            .find(|tier| tier.min_volume <= volume))
+    }
+
+    /// Tiers of `market_id`, lowest volume first
+    pub fn fee_tiers(&self, market_id: &str) -> Result<Vec<FeeTier>> {
+        self.persister
+            .get_fee_tiers(market_id)
+            .context("Failed to fetch fee tiers")
+    }
+
+    /// Sets the rates of the tier of `market_id` starting at `min_volume`, replacing the ones
+    /// it had.
+    pub fn set_fee_tier(
+        &self,
+        market_id: &str,
+        min_volume: BigDecimal,
+        maker_fee: BigDecimal,
+        taker_fee: BigDecimal,
+    ) -> Result<FeeTier> {
+        self.persister
+            .set_fee_tier(market_id, min_volume, maker_fee, taker_fee)
+            .context("Failed to store fee tier")
+    }
+
+    /// Returns whether the market had a tier starting at `min_volume`.
+    pub fn delete_fee_tier(&self, market_id: &str, min_volume: &BigDecimal) -> Result<bool> {
+        self.persister
+            .delete_fee_tier(market_id, min_volume)
+            .context("Failed to delete fee tier")
+    }
+
+    /// Charges `order` the rates an operator set for its user or, without those, the rates of
+    /// its user's fee tier. Orders neither applies to keep the fees they were placed with.
+    pub fn apply_fees(&self, order: &mut TradeOrder) -> Result<()> {
+        let fee_override = self
+            .persister
+            .get_user_fee_override(&order.user_id)
+            .context("Failed to fetch user fee override")?;
+        if let Some(fee_override) = fee_override {
+            order.maker_fee = fee_override.maker_fee;
+            order.taker_fee = fee_override.taker_fee;
+        } else if let Some(tier) = self.fee_tier(&order.user_id, &order.market_id)? {
+            order.maker_fee = tier.maker_fee;
+            order.taker_fee = tier.taker_fee;
+        }
+        Ok(())
+    }
+
+    /// Sends collected fees out of a market's treasury, returning the withdrawal and the
+    /// treasury it left.
+    pub fn withdraw_from_treasury(
+        &self,
+        market_id: &str,
+        asset: &str,
+        amount: BigDecimal,
+        destination: &str,
+    ) -> Result<(FeeTreasuryWithdrawal, FeeTreasury)> {
+        let withdrawal = self
+            .persister
+            .withdraw_from_fee_treasury(market_id, asset, amount, destination)
+            .context("Failed to withdraw from fee treasury")?;
+        let treasury = self
+            .persister
+            .get_fee_treasury(market_id, asset)
+            .context("Failed to fetch fee treasury")?
+            .context("Fee treasury disappeared")?;
+        Ok((withdrawal, treasury))
+    }
+
+    /// Sends everything collected in the treasuries of `market_id`, or of every market when
+    /// `None`, to `destination`. Returns one withdrawal per treasury that held fees.
+    pub fn sweep_treasuries(
+        &self,
+        market_id: Option<&str>,
+        destination: &str,
+    ) -> Result<Vec<FeeTreasuryWithdrawal>> {
+        let treasuries = self
+            .persister
+            .list_fee_treasuries(market_id)
+            .context("Failed to list fee treasuries")?;
+
+        let mut withdrawals = Vec::new();
+        for treasury in treasuries {
+            if treasury.collected_amount.is_zero() {
+                continue;
+            }
+            let withdrawal = self
+                .persister
+                .withdraw_from_fee_treasury(
+                    &treasury.market_id,
+                    &treasury.asset,
+                    treasury.collected_amount,
+                    destination,
+                )
+                .context("Failed to withdraw from fee treasury")?;
+            withdrawals.push(withdrawal);
+        }
+        Ok(withdrawals)
+    }
+}
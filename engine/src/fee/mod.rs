@@ -0,0 +1 @@
+pub mod fee_service;
@@ -0,0 +1,436 @@
+use anyhow::Context;
+use common::error::{internal_status, BitradeError};
+use common::maintenance::MaintenanceMode;
+use common::utils::{bigdecimal_from_str, format_amount, normalize_user_id};
+use database::provider::DatabaseProvider;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tonic::{Request, Response, Status};
+use tracing::info;
+
+use crate::config::app_config::FeeDefaults;
+use crate::fee::fee_service::FeeService;
+use crate::grpc::admin::admin_service_server::AdminService;
+use crate::grpc::admin::{
+    DeleteFeeTierRequest, DeleteFeeTierResponse, GetUserStatusRequest, ListFeeTiersRequest,
+    ListFeeTiersResponse, ListUserRestrictionsRequest, ListUserRestrictionsResponse, ProtoFeeTier,
+    ProtoUserRestriction, ReleaseOrphanedLocksRequest, ReleaseOrphanedLocksResponse,
+    ReloadMarketsRequest, ReloadMarketsResponse, SetFeeTierRequest, SetUserStatusRequest,
+    SetUserStatusResponse, SweepTreasuryRequest, SweepTreasuryResponse, TriggerSnapshotRequest,
+    TriggerSnapshotResponse,
+};
+use crate::grpc::helper::user_restriction_response;
+use crate::grpc::spot::{
+    CancelAllOrdersRequest, CancelAllOrdersResponse, CreateMarketRequest, CreateMarketResponse,
+    GetReconciliationReportRequest, GetReconciliationReportResponse, SetMaintenanceModeRequest,
+    SetMaintenanceModeResponse, StartMarketRequest, StartMarketResponse, StopMarketRequest,
+    StopMarketResponse, UpdateMarketRequest, UpdateMarketResponse, UpdateMarketStatusRequest,
+    UpdateMarketStatusResponse, WithdrawFromTreasuryRequest, WithdrawFromTreasuryResponse,
+};
+use crate::market::market_manager::{run_blocking, MarketManager};
+use crate::reconciliation::reconciler::Reconciler;
+use crate::validation::{
+    validate_create_market_request, validate_delete_fee_tier_request,
+    validate_set_fee_tier_request, validate_set_user_status_request,
+    validate_sweep_treasury_request, validate_update_market_request,
+    validate_update_market_status_request, validate_withdraw_from_treasury_request, AssetRegistry,
+};
+use database::models::models::CancelReason;
+use database::repository::TreasuryError;
+
+/// Operational control of the engine, served apart from the trading API. Maintenance does not
+/// stop it, since that is when operators need it most.
+pub struct AdminServiceImpl<P: DatabaseProvider + 'static> {
+    pub market_manager: Arc<RwLock<MarketManager<P>>>,
+    pub fee_service: Arc<FeeService<P>>,
+    /// Maintenance mode of the trading API, which `SetMaintenanceMode` turns on and off
+    pub maintenance: MaintenanceMode,
+    /// Assets new markets may use
+    pub asset_registry: AssetRegistry,
+    /// Fees new markets get when the request leaves them empty
+    pub fee_defaults: FeeDefaults,
+    /// Balance checks behind `GetReconciliationReport`
+    pub reconciler: Arc<Reconciler<P>>,
+}
+
+fn treasury_status(e: anyhow::Error) -> Status {
+    let error = match e.downcast_ref::<TreasuryError>() {
+        Some(TreasuryError::NotFound { .. }) => BitradeError::NotFound(e.to_string()),
+        Some(TreasuryError::InsufficientFees { .. }) => {
+            BitradeError::FailedPrecondition(e.to_string())
+        }
+        None => BitradeError::from_anyhow(e),
+    };
+    error.into()
+}
+
+#[tonic::async_trait]
+impl<P: DatabaseProvider + Send + Sync + 'static> AdminService for AdminServiceImpl<P> {
+    async fn create_market(
+        &self,
+        request: Request<CreateMarketRequest>,
+    ) -> Result<Response<CreateMarketResponse>, Status> {
+        let mut req = request.into_inner();
+        if let (true, Some(fee)) = (
+            req.default_maker_fee.is_empty(),
+            &self.fee_defaults.maker_fee,
+        ) {
+            req.default_maker_fee = fee.to_string();
+        }
+        if let (true, Some(fee)) = (
+            req.default_taker_fee.is_empty(),
+            &self.fee_defaults.taker_fee,
+        ) {
+            req.default_taker_fee = fee.to_string();
+        }
+
+        // Validate the request
+        validate_create_market_request(&req, &self.asset_registry)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let market_id = req.market_id.clone();
+        run_blocking(&self.market_manager, move |market_manager| {
+            market_manager.create_market(
+                req.market_id,
+                req.base_asset,
+                req.quote_asset,
+                req.default_maker_fee,
+                req.default_taker_fee,
+            )
+        })
+        .await
+        .context("Failed to create market")
+        .map_err(internal_status)?;
+        Ok(Response::new(CreateMarketResponse {
+            success: true,
+            market_id,
+        }))
+    }
+
+    async fn start_market(
+        &self,
+        request: Request<StartMarketRequest>,
+    ) -> Result<Response<StartMarketResponse>, Status> {
+        let req = request.into_inner();
+        let market_id = req.market_id.clone();
+        run_blocking(&self.market_manager, move |market_manager| {
+            market_manager.start_market(&req.market_id)
+        })
+        .await
+        .context("Failed to start market")
+        .map_err(internal_status)?;
+        Ok(Response::new(StartMarketResponse {
+            success: true,
+            market_id,
+        }))
+    }
+
+    async fn stop_market(
+        &self,
+        request: Request<StopMarketRequest>,
+    ) -> Result<Response<StopMarketResponse>, Status> {
+        let req = request.into_inner();
+        let market_id = req.market_id.clone();
+        run_blocking(&self.market_manager, move |market_manager| {
+            market_manager.stop_market(&req.market_id)
+        })
+        .await
+        .context("Failed to stop market")
+        .map_err(internal_status)?;
+
+        Ok(Response::new(StopMarketResponse {
+            success: true,
+            market_id,
+        }))
+    }
+
+    async fn update_market_status(
+        &self,
+        request: Request<UpdateMarketStatusRequest>,
+    ) -> Result<Response<UpdateMarketStatusResponse>, Status> {
+        let req = request.into_inner();
+        let status = validate_update_market_status_request(&req)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let market_id = req.market_id.clone();
+        let previous = run_blocking(&self.market_manager, move |market_manager| {
+            market_manager.update_market_status(&market_id, status)
+        })
+        .await
+        .map_err(internal_status)?;
+
+        Ok(Response::new(UpdateMarketStatusResponse {
+            market_id: req.market_id,
+            status: status.as_str().to_string(),
+            previous_status: previous.as_str().to_string(),
+        }))
+    }
+
+    async fn update_market(
+        &self,
+        request: Request<UpdateMarketRequest>,
+    ) -> Result<Response<UpdateMarketResponse>, Status> {
+        let req = request.into_inner();
+        let changes = validate_update_market_request(&req)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let market = run_blocking(&self.market_manager, move |market_manager| {
+            market_manager.update_market(&req.market_id, changes)
+        })
+        .await
+        .map_err(internal_status)?;
+
+        Ok(Response::new(market.into()))
+    }
+
+    async fn reload_markets(
+        &self,
+        _request: Request<ReloadMarketsRequest>,
+    ) -> Result<Response<ReloadMarketsResponse>, Status> {
+        let reload = run_blocking(&self.market_manager, |market_manager| {
+            market_manager.reload_markets()
+        })
+        .await
+        .map_err(internal_status)?;
+        info!(
+            added = reload.added,
+            refreshed = reload.refreshed,
+            removed = reload.removed,
+            "Reloaded markets from database"
+        );
+
+        Ok(Response::new(ReloadMarketsResponse {
+            added: reload.added as u32,
+            refreshed: reload.refreshed as u32,
+            removed: reload.removed as u32,
+        }))
+    }
+
+    async fn set_fee_tier(
+        &self,
+        request: Request<SetFeeTierRequest>,
+    ) -> Result<Response<ProtoFeeTier>, Status> {
+        let req = request.into_inner();
+        let (min_volume, maker_fee, taker_fee) = validate_set_fee_tier_request(&req)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let tier = self
+            .fee_service
+            .set_fee_tier(&req.market_id, min_volume, maker_fee, taker_fee)
+            .map_err(internal_status)?;
+        Ok(Response::new(tier.into()))
+    }
+
+    async fn delete_fee_tier(
+        &self,
+        request: Request<DeleteFeeTierRequest>,
+    ) -> Result<Response<DeleteFeeTierResponse>, Status> {
+        let req = request.into_inner();
+        let min_volume = validate_delete_fee_tier_request(&req)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let deleted = self
+            .fee_service
+            .delete_fee_tier(&req.market_id, &min_volume)
+            .map_err(internal_status)?;
+        Ok(Response::new(DeleteFeeTierResponse { deleted }))
+    }
+
+    async fn list_fee_tiers(
+        &self,
+        request: Request<ListFeeTiersRequest>,
+    ) -> Result<Response<ListFeeTiersResponse>, Status> {
+        let req = request.into_inner();
+        let tiers = self
+            .fee_service
+            .fee_tiers(&req.market_id)
+            .map_err(internal_status)?;
+        Ok(Response::new(ListFeeTiersResponse {
+            tiers: tiers.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    async fn set_user_status(
+        &self,
+        request: Request<SetUserStatusRequest>,
+    ) -> Result<Response<SetUserStatusResponse>, Status> {
+        let req = request.into_inner();
+        let status = validate_set_user_status_request(&req)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let user_id =
+            normalize_user_id(&req.user_id).map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let restricted_id = user_id.clone();
+        let (restriction, canceled) = run_blocking(&self.market_manager, move |market_manager| {
+            market_manager.set_user_status(&restricted_id, status, &req.reason)
+        })
+        .await
+        .map_err(internal_status)?;
+        Ok(Response::new(SetUserStatusResponse {
+            restriction: Some(user_restriction_response(&user_id, restriction)),
+            canceled_orders: canceled as u32,
+        }))
+    }
+
+    async fn get_user_status(
+        &self,
+        request: Request<GetUserStatusRequest>,
+    ) -> Result<Response<ProtoUserRestriction>, Status> {
+        let user_id = normalize_user_id(&request.into_inner().user_id)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let restricted_id = user_id.clone();
+        let restriction = run_blocking(&self.market_manager, move |market_manager| {
+            market_manager.user_restriction(&restricted_id)
+        })
+        .await
+        .map_err(internal_status)?;
+        Ok(Response::new(user_restriction_response(
+            &user_id,
+            restriction,
+        )))
+    }
+
+    async fn list_user_restrictions(
+        &self,
+        _request: Request<ListUserRestrictionsRequest>,
+    ) -> Result<Response<ListUserRestrictionsResponse>, Status> {
+        let restrictions = run_blocking(&self.market_manager, |market_manager| {
+            market_manager.list_user_restrictions()
+        })
+        .await
+        .map_err(internal_status)?;
+        Ok(Response::new(ListUserRestrictionsResponse {
+            restrictions: restrictions.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    async fn cancel_all_orders(
+        &self,
+        request: Request<CancelAllOrdersRequest>,
+    ) -> Result<Response<CancelAllOrdersResponse>, Status> {
+        let req = request.into_inner();
+        let market_id = req.market_id.clone();
+        let success = run_blocking(&self.market_manager, move |market_manager| {
+            if market_id.is_empty() {
+                market_manager
+                    .cancel_all_orders_global(CancelReason::AdminCancel)
+                    .map(|()| true)
+            } else {
+                market_manager.cancel_all_orders(&market_id, CancelReason::AdminCancel)
+            }
+        })
+        .await
+        .context("Failed to cancel all orders")
+        .map_err(internal_status)?;
+
+        Ok(Response::new(CancelAllOrdersResponse {
+            success,
+            market_id: req.market_id,
+        }))
+    }
+
+    async fn sweep_treasury(
+        &self,
+        request: Request<SweepTreasuryRequest>,
+    ) -> Result<Response<SweepTreasuryResponse>, Status> {
+        let req = request.into_inner();
+        validate_sweep_treasury_request(&req)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let market_id = (!req.market_id.is_empty()).then_some(req.market_id.as_str());
+        let withdrawals = self
+            .fee_service
+            .sweep_treasuries(market_id, &req.destination)
+            .map_err(treasury_status)?;
+        Ok(Response::new(SweepTreasuryResponse {
+            withdrawals: withdrawals.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    async fn trigger_snapshot(
+        &self,
+        _request: Request<TriggerSnapshotRequest>,
+    ) -> Result<Response<TriggerSnapshotResponse>, Status> {
+        let snapshots = run_blocking(&self.market_manager, |market_manager| {
+            market_manager.snapshot_order_books()
+        })
+        .await
+        .map_err(internal_status)?;
+        Ok(Response::new(TriggerSnapshotResponse {
+            snapshots: snapshots as u32,
+        }))
+    }
+
+    async fn withdraw_from_treasury(
+        &self,
+        request: Request<WithdrawFromTreasuryRequest>,
+    ) -> Result<Response<WithdrawFromTreasuryResponse>, Status> {
+        let req = request.into_inner();
+        validate_withdraw_from_treasury_request(&req)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let amount = bigdecimal_from_str(&req.amount, "amount")
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let (withdrawal, treasury) = self
+            .fee_service
+            .withdraw_from_treasury(&req.market_id, &req.asset, amount, &req.destination)
+            .map_err(treasury_status)?;
+        Ok(Response::new(WithdrawFromTreasuryResponse {
+            withdrawal_id: withdrawal.id,
+            market_id: withdrawal.market_id,
+            asset: withdrawal.asset,
+            amount: format_amount(&withdrawal.amount),
+            destination: withdrawal.destination,
+            create_time: withdrawal.create_time,
+            collected_amount: format_amount(&treasury.collected_amount),
+        }))
+    }
+
+    async fn set_maintenance_mode(
+        &self,
+        request: Request<SetMaintenanceModeRequest>,
+    ) -> Result<Response<SetMaintenanceModeResponse>, Status> {
+        let req = request.into_inner();
+        self.maintenance.set_enabled(req.enabled);
+        info!("Maintenance mode set to {}", req.enabled);
+
+        Ok(Response::new(SetMaintenanceModeResponse {
+            success: true,
+            maintenance: self.maintenance.is_enabled(),
+        }))
+    }
+
+    async fn get_reconciliation_report(
+        &self,
+        request: Request<GetReconciliationReportRequest>,
+    ) -> Result<Response<GetReconciliationReportResponse>, Status> {
+        let req = request.into_inner();
+        let report = match self.reconciler.latest() {
+            Some(report) if !req.refresh => report,
+            _ => self.reconciler.reconcile().map_err(internal_status)?,
+        };
+
+        Ok(Response::new(report.into()))
+    }
+
+    async fn release_orphaned_locks(
+        &self,
+        request: Request<ReleaseOrphanedLocksRequest>,
+    ) -> Result<Response<ReleaseOrphanedLocksResponse>, Status> {
+        let req = request.into_inner();
+        let released = match req.user_id.is_empty() {
+            true => self.reconciler.sweep_orphaned_locks(),
+            false => {
+                let user_id = normalize_user_id(&req.user_id)
+                    .map_err(|e| Status::invalid_argument(e.to_string()))?;
+                self.reconciler.release_orphaned_locks(&user_id)
+            }
+        }
+        .map_err(internal_status)?;
+
+        Ok(Response::new(ReleaseOrphanedLocksResponse {
+            wallets: released.into_iter().map(Into::into).collect(),
+        }))
+    }
+}
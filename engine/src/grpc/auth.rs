@@ -0,0 +1,26 @@
+use common::auth::Scope;
+
+/// Scope an API key needs to call a `SpotService` method. Health checks, server info and
+/// rate limits are open, and a method not listed here needs `admin`, which covers every
+/// `AdminService` method.
+pub fn method_scope(method: &str) -> Option<Scope> {
+    match method {
+        "HealthCheck" | "GetServerInfo" | "GetRateLimits" => None,
+        "GetOrderBookDepth"
+        | "SubscribeOrderBook"
+        | "SubscribeTrades"
+        | "GetRecentTrades"
+        | "SubscribeUserEvents"
+        | "GetBalance" => Some(Scope::Read),
+        "AddOrder"
+        | "AddOcoOrder"
+        | "AmendOrder"
+        | "CancelOrder"
+        | "AddOrders"
+        | "CancelOrders"
+        | "OpenSession"
+        | "SetCancelOnDisconnect" => Some(Scope::Trade),
+        "RequestWithdrawal" => Some(Scope::Withdraw),
+        _ => Some(Scope::Admin),
+    }
+}
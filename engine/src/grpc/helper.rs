@@ -1,16 +1,66 @@
-use crate::grpc::spot::{AddOrderRequest, ProtoTrade};
+use crate::grpc::admin::{
+    ProtoFeeTier, ProtoReleasedLock, ProtoTreasuryWithdrawal, ProtoUserRestriction,
+};
+use crate::grpc::spot::{
+    AddOrderRequest, AddOrderResponse, DepthLevel, GetReconciliationReportResponse,
+    GetServerInfoResponse, OrderBookUpdate, OrderConstraintViolation, ProtoAssetDiscrepancy,
+    ProtoLockDiscrepancy, ProtoTrade, ProtoTransfer, ProtoUserEvent, RestingOrder, TradeUpdate,
+    UpdateMarketResponse,
+};
 use crate::models::{
-    matched_trade::MatchedTrade,
+    matched_trade::{MatchedTrade, SequencedTrade},
+    order_receipt::OrderReceipt,
     trade_order::{OrderSide, OrderType, TradeOrder},
+    user_event::UserEvent,
 };
+use crate::order_book::depth_diff::{DepthDelta, DepthSnapshot, LevelChange};
+use crate::reconciliation::reconciler::ReconciliationReport;
+use crate::validation::MarketConstraintError;
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Result};
 use bigdecimal::{BigDecimal, Zero};
-use common::utils::{get_utc_now_millis, get_uuid_string};
-use database::models::models::{OrderStatus, TimeInForce};
-use std::str::FromStr;
+use common::utils::{
+    bigdecimal_from_str, format_amount, get_utc_now_millis, get_uuid_string, normalize_user_id,
+};
+use database::models::models::{
+    FeeTier, FeeTreasuryWithdrawal, Market, OrderStatus, TimeInForce, Transfer, UserRestriction,
+    UserStatus, Wallet,
+};
+use futures::{stream, Stream};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
 use tonic::Status;
 
+/// Order types the engine accepts
+pub const SUPPORTED_ORDER_TYPES: [OrderType; 2] = [OrderType::Limit, OrderType::Market];
+
+/// Time-in-force values the engine honors when matching
+pub const SUPPORTED_TIME_IN_FORCE: [TimeInForce; 3] =
+    [TimeInForce::GTC, TimeInForce::IOC, TimeInForce::GTD];
+
+/// Reads the time-in-force of an order request, GTC when it is left empty.
+pub fn parse_time_in_force(value: &str) -> Result<TimeInForce> {
+    if value.is_empty() {
+        return Ok(TimeInForce::GTC);
+    }
+    let time_in_force = TimeInForce::from_str(value).map_err(|e| anyhow!(e))?;
+    if !SUPPORTED_TIME_IN_FORCE.contains(&time_in_force) {
+        return Err(anyhow!("Unsupported time in force: {}", value));
+    }
+    Ok(time_in_force)
+}
+
+pub fn server_info() -> GetServerInfoResponse {
+    GetServerInfoResponse {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        git_hash: env!("GIT_HASH").to_string(),
+        order_types: SUPPORTED_ORDER_TYPES.map(String::from).to_vec(),
+        time_in_force: SUPPORTED_TIME_IN_FORCE
+            .map(|tif| tif.as_str().to_string())
+            .to_vec(),
+    }
+}
+
 impl TryFrom<AddOrderRequest> for TradeOrder {
     type Error = anyhow::Error;
 
@@ -21,24 +71,30 @@ impl TryFrom<AddOrderRequest> for TradeOrder {
         let side = OrderSide::try_from(req.side.as_str())
             .map_err(|e| Status::invalid_argument(format!("Invalid order side: {}", e)))?;
 
-        let price = BigDecimal::from_str(&req.price)
-            .context("Failed to parse price as Decimal")
+        let price = bigdecimal_from_str(&req.price, "price")
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let base_amount = bigdecimal_from_str(&req.base_amount, "base_amount")
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let quote_amount = bigdecimal_from_str(&req.quote_amount, "quote_amount")
             .map_err(|e| Status::invalid_argument(e.to_string()))?;
 
-        let base_amount = BigDecimal::from_str(&req.base_amount)
-            .context("Failed to parse base amount as Decimal")
+        let maker_fee = bigdecimal_from_str(&req.maker_fee, "maker_fee")
             .map_err(|e| Status::invalid_argument(e.to_string()))?;
 
-        let quote_amount = BigDecimal::from_str(&req.quote_amount)
-            .context("Failed to parse quote amount as Decimal")
+        let user_id =
+            normalize_user_id(&req.user_id).map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let taker_fee = bigdecimal_from_str(&req.taker_fee, "taker_fee")
             .map_err(|e| Status::invalid_argument(e.to_string()))?;
 
-        let maker_fee = BigDecimal::from_str(&req.maker_fee)
-            .context("Failed to parse maker fee as Decimal")
+        let time_in_force = parse_time_in_force(&req.time_in_force)
             .map_err(|e| Status::invalid_argument(e.to_string()))?;
 
-        let taker_fee = BigDecimal::from_str(&req.taker_fee)
-            .context("Failed to parse taker fee as Decimal")
+        let display_amount = (!req.display_amount.is_empty())
+            .then(|| bigdecimal_from_str(&req.display_amount, "display_amount"))
+            .transpose()
             .map_err(|e| Status::invalid_argument(e.to_string()))?;
 
         Ok(TradeOrder {
@@ -46,24 +102,27 @@ impl TryFrom<AddOrderRequest> for TradeOrder {
             market_id: req.market_id,
             order_type,
             side,
-            user_id: req.user_id,
+            user_id,
             price,
             base_amount: base_amount.clone(),
             quote_amount: quote_amount.clone(),
             maker_fee,
             taker_fee,
             create_time: get_utc_now_millis(),
-            client_order_id: Some(get_uuid_string()),
-            expires_at: None,
-            post_only: Some(false),
+            client_order_id: (!req.client_order_id.is_empty()).then_some(req.client_order_id),
+            expires_at: (req.expires_at > 0).then_some(req.expires_at),
+            post_only: Some(req.post_only),
             remained_base: base_amount,
             remained_quote: quote_amount,
             filled_base: BigDecimal::zero(),
             filled_quote: BigDecimal::zero(),
             filled_fee: BigDecimal::zero(),
             update_time: get_utc_now_millis(),
-            time_in_force: Some(TimeInForce::GTC),
+            time_in_force: Some(time_in_force),
+            display_amount,
             status: OrderStatus::Open,
+            // Given by the book once the order reaches it
+            priority: 0,
         })
     }
 }
@@ -75,11 +134,24 @@ impl From<TradeOrder> for AddOrderRequest {
             order_type: order.order_type.into(),
             side: order.side.into(),
             user_id: order.user_id,
-            price: order.price.to_string(),
-            base_amount: order.base_amount.to_string(),
-            quote_amount: order.quote_amount.to_string(),
-            maker_fee: order.maker_fee.to_string(),
-            taker_fee: order.taker_fee.to_string(),
+            price: format_amount(&order.price),
+            base_amount: format_amount(&order.base_amount),
+            quote_amount: format_amount(&order.quote_amount),
+            maker_fee: format_amount(&order.maker_fee),
+            taker_fee: format_amount(&order.taker_fee),
+            test_order: false,
+            time_in_force: order
+                .time_in_force
+                .map(|tif| tif.as_str().to_string())
+                .unwrap_or_default(),
+            post_only: order.post_only.unwrap_or(false),
+            expires_at: order.expires_at.unwrap_or(0),
+            display_amount: order
+                .display_amount
+                .map(|amount| format_amount(&amount))
+                .unwrap_or_default(),
+            client_order_id: order.client_order_id.unwrap_or_default(),
+            idempotency_key: String::new(),
         }
     }
 }
@@ -90,17 +162,207 @@ impl From<MatchedTrade> for ProtoTrade {
             id: trade.id,
             timestamp: trade.timestamp,
             market_id: trade.market_id,
-            price: trade.price.to_string(),
-            base_amount: trade.base_amount.to_string(),
-            quote_amount: trade.quote_amount.to_string(),
+            price: format_amount(&trade.price),
+            base_amount: format_amount(&trade.base_amount),
+            quote_amount: format_amount(&trade.quote_amount),
             seller_user_id: trade.seller_user_id,
             seller_order_id: trade.seller_order_id,
 
-            seller_fee: trade.seller_fee.to_string(),
+            seller_fee: format_amount(&trade.seller_fee),
             buyer_user_id: trade.buyer_user_id,
             buyer_order_id: trade.buyer_order_id,
 
-            buyer_fee: trade.buyer_fee.to_string(),
+            buyer_fee: format_amount(&trade.buyer_fee),
+        }
+    }
+}
+
+impl From<SequencedTrade> for TradeUpdate {
+    fn from(trade: SequencedTrade) -> Self {
+        TradeUpdate {
+            sequence: trade.sequence,
+            trade: Some(trade.trade.into()),
+        }
+    }
+}
+
+impl From<UserEvent> for ProtoUserEvent {
+    fn from(event: UserEvent) -> Self {
+        ProtoUserEvent {
+            event_type: event.kind.as_str().to_string(),
+            user_id: event.user_id,
+            market_id: event.market_id,
+            order_id: event.order_id,
+            client_order_id: event.client_order_id.unwrap_or_default(),
+            side: event.side.map(String::from).unwrap_or_default(),
+            price: event.price.as_ref().map(format_amount).unwrap_or_default(),
+            filled_base: format_amount(&event.filled_base),
+            remained_base: format_amount(&event.remained_base),
+            trade: event.trade.map(ProtoTrade::from),
+            reason: event.reason.unwrap_or_default(),
+            timestamp: event.timestamp,
+        }
+    }
+}
+
+impl From<Transfer> for ProtoTransfer {
+    fn from(transfer: Transfer) -> Self {
+        ProtoTransfer {
+            id: transfer.id,
+            user_id: transfer.user_id,
+            asset: transfer.asset,
+            kind: transfer.kind,
+            amount: format_amount(&transfer.amount),
+            status: transfer.status,
+            address: transfer.address.unwrap_or_default(),
+            external_id: transfer.external_id.unwrap_or_default(),
+            reject_reason: transfer.reject_reason.unwrap_or_default(),
+            create_time: transfer.create_time,
+            update_time: transfer.update_time,
+        }
+    }
+}
+
+impl From<&MarketConstraintError> for OrderConstraintViolation {
+    fn from(error: &MarketConstraintError) -> Self {
+        match error {
+            MarketConstraintError::BelowMinimum {
+                field,
+                value,
+                minimum,
+            } => OrderConstraintViolation {
+                field: field.to_string(),
+                constraint: "MIN_AMOUNT".to_string(),
+                value: format_amount(value),
+                limit: format_amount(minimum),
+            },
+            MarketConstraintError::TooManyDecimals {
+                field,
+                value,
+                precision,
+            } => OrderConstraintViolation {
+                field: field.to_string(),
+                constraint: "PRECISION".to_string(),
+                value: value.to_string(),
+                limit: precision.to_string(),
+            },
+            MarketConstraintError::QuoteMismatch {
+                quote_amount,
+                expected,
+            } => OrderConstraintViolation {
+                field: "quote_amount".to_string(),
+                constraint: "QUOTE_MISMATCH".to_string(),
+                value: format_amount(quote_amount),
+                limit: format_amount(expected),
+            },
+        }
+    }
+}
+
+impl From<Market> for UpdateMarketResponse {
+    fn from(market: Market) -> Self {
+        UpdateMarketResponse {
+            market_id: market.id,
+            default_maker_fee: format_amount(&market.default_maker_fee),
+            default_taker_fee: format_amount(&market.default_taker_fee),
+            min_base_amount: format_amount(&market.min_base_amount),
+            min_quote_amount: format_amount(&market.min_quote_amount),
+            price_precision: market.price_precision,
+            amount_precision: market.amount_precision,
+        }
+    }
+}
+
+impl From<FeeTier> for ProtoFeeTier {
+    fn from(tier: FeeTier) -> Self {
+        ProtoFeeTier {
+            market_id: tier.market_id,
+            min_volume: format_amount(&tier.min_volume),
+            maker_fee: format_amount(&tier.maker_fee),
+            taker_fee: format_amount(&tier.taker_fee),
+            update_time: tier.update_time,
+        }
+    }
+}
+
+impl From<UserRestriction> for ProtoUserRestriction {
+    fn from(restriction: UserRestriction) -> Self {
+        ProtoUserRestriction {
+            user_id: restriction.user_id,
+            status: restriction.status,
+            reason: restriction.reason,
+            update_time: restriction.update_time,
+        }
+    }
+}
+
+/// The restriction of `user_id`, shown as `ACTIVE` when there is none
+pub fn user_restriction_response(
+    user_id: &str,
+    restriction: Option<UserRestriction>,
+) -> ProtoUserRestriction {
+    restriction
+        .map(Into::into)
+        .unwrap_or_else(|| ProtoUserRestriction {
+            user_id: user_id.to_string(),
+            status: UserStatus::Active.as_str().to_string(),
+            reason: String::new(),
+            update_time: 0,
+        })
+}
+
+impl From<FeeTreasuryWithdrawal> for ProtoTreasuryWithdrawal {
+    fn from(withdrawal: FeeTreasuryWithdrawal) -> Self {
+        ProtoTreasuryWithdrawal {
+            withdrawal_id: withdrawal.id,
+            market_id: withdrawal.market_id,
+            asset: withdrawal.asset,
+            amount: format_amount(&withdrawal.amount),
+            destination: withdrawal.destination,
+            create_time: withdrawal.create_time,
+        }
+    }
+}
+
+impl From<Wallet> for ProtoReleasedLock {
+    fn from(wallet: Wallet) -> Self {
+        ProtoReleasedLock {
+            user_id: wallet.user_id,
+            asset: wallet.asset,
+            available: format_amount(&wallet.available),
+            locked: format_amount(&wallet.locked),
+        }
+    }
+}
+
+impl From<ReconciliationReport> for GetReconciliationReportResponse {
+    fn from(report: ReconciliationReport) -> Self {
+        GetReconciliationReportResponse {
+            generated_at: report.generated_at,
+            balanced: report.is_balanced(),
+            assets_checked: report.assets_checked as u64,
+            locks_checked: report.locks_checked as u64,
+            asset_discrepancies: report
+                .asset_discrepancies
+                .into_iter()
+                .map(|discrepancy| ProtoAssetDiscrepancy {
+                    asset: discrepancy.asset,
+                    held: format_amount(&discrepancy.held),
+                    expected: format_amount(&discrepancy.expected),
+                    difference: format_amount(&discrepancy.difference),
+                })
+                .collect(),
+            lock_discrepancies: report
+                .lock_discrepancies
+                .into_iter()
+                .map(|discrepancy| ProtoLockDiscrepancy {
+                    user_id: discrepancy.user_id,
+                    asset: discrepancy.asset,
+                    locked: format_amount(&discrepancy.locked),
+                    order_locked: format_amount(&discrepancy.order_locked),
+                    difference: format_amount(&discrepancy.difference),
+                })
+                .collect(),
         }
     }
 }
@@ -111,15 +373,15 @@ impl From<&MatchedTrade> for ProtoTrade {
             id: trade.id.clone(),
             timestamp: trade.timestamp,
             market_id: trade.market_id.clone(),
-            price: trade.price.to_string(),
-            base_amount: trade.base_amount.to_string(),
-            quote_amount: trade.quote_amount.to_string(),
+            price: format_amount(&trade.price),
+            base_amount: format_amount(&trade.base_amount),
+            quote_amount: format_amount(&trade.quote_amount),
             seller_user_id: trade.seller_user_id.clone(),
             seller_order_id: trade.seller_order_id.clone(),
-            seller_fee: trade.seller_fee.to_string(),
+            seller_fee: format_amount(&trade.seller_fee),
             buyer_user_id: trade.buyer_user_id.clone(),
             buyer_order_id: trade.buyer_order_id.clone(),
-            buyer_fee: trade.buyer_fee.to_string(),
+            buyer_fee: format_amount(&trade.buyer_fee),
         }
     }
 }
@@ -127,3 +389,105 @@ impl From<&MatchedTrade> for ProtoTrade {
 pub fn convert_trades(trades: Vec<MatchedTrade>) -> Vec<ProtoTrade> {
     trades.iter().map(ProtoTrade::from).collect()
 }
+
+pub fn convert_depth_levels(levels: Vec<(BigDecimal, BigDecimal)>) -> Vec<DepthLevel> {
+    levels
+        .into_iter()
+        .map(|(price, amount)| DepthLevel {
+            price: format_amount(&price),
+            amount: format_amount(&amount),
+        })
+        .collect()
+}
+
+/// First update of an order book stream, bids and asks best price first
+pub fn depth_snapshot_update(
+    market_id: &str,
+    snapshot: DepthSnapshot,
+    sequence: u64,
+) -> OrderBookUpdate {
+    OrderBookUpdate {
+        market_id: market_id.to_string(),
+        snapshot: true,
+        bids: convert_depth_levels(snapshot.bids.into_iter().rev().collect()),
+        asks: convert_depth_levels(snapshot.asks.into_iter().collect()),
+        sequence,
+    }
+}
+
+pub fn depth_delta_update(market_id: &str, delta: DepthDelta) -> OrderBookUpdate {
+    let changes = |levels: Vec<LevelChange>| {
+        convert_depth_levels(levels.into_iter().map(|l| (l.price, l.amount)).collect())
+    };
+    OrderBookUpdate {
+        market_id: market_id.to_string(),
+        snapshot: false,
+        bids: changes(delta.diff.bids),
+        asks: changes(delta.diff.asks),
+        sequence: delta.sequence,
+    }
+}
+
+/// Turns a broadcast receiver into the stream of a subscription RPC. It ends when the sender
+/// goes away with its market, or with `DATA_LOSS` once the subscriber fell too far behind.
+pub fn subscription_stream<T, U, F>(
+    receiver: broadcast::Receiver<T>,
+    convert: F,
+) -> impl Stream<Item = Result<U, Status>> + Send + 'static
+where
+    T: Clone + Send + 'static,
+    U: Send + 'static,
+    F: Fn(T) -> U + Clone + Send + 'static,
+{
+    stream::unfold(Some(receiver), move |receiver| {
+        let convert = convert.clone();
+        async move {
+            let mut receiver = receiver?;
+            match receiver.recv().await {
+                Ok(update) => Some((Ok(convert(update)), Some(receiver))),
+                Err(RecvError::Lagged(missed)) => Some((
+                    Err(Status::data_loss(format!(
+                        "Missed {} updates, subscribe again",
+                        missed
+                    ))),
+                    None,
+                )),
+                Err(RecvError::Closed) => None,
+            }
+        }
+    })
+}
+
+/// Builds the `AddOrder` response, keeping at most `max_fills` trades.
+pub fn build_add_order_response(receipt: OrderReceipt, max_fills: usize) -> AddOrderResponse {
+    let total_fills = receipt.trades.len();
+    let fills_truncated = total_fills > max_fills;
+    AddOrderResponse {
+        order_id: receipt.order_id,
+        trades: receipt
+            .trades
+            .iter()
+            .take(max_fills)
+            .map(ProtoTrade::from)
+            .collect(),
+        total_fills: total_fills as u32,
+        fills_truncated,
+        resting: receipt.resting.map(RestingOrder::from),
+        reject_reason: String::new(),
+    }
+}
+
+impl From<TradeOrder> for RestingOrder {
+    fn from(order: TradeOrder) -> Self {
+        let reserved_amount = match order.side {
+            OrderSide::Buy => order.remained_quote,
+            OrderSide::Sell => order.remained_base.clone(),
+        };
+        RestingOrder {
+            side: order.side.into(),
+            price: format_amount(&order.price),
+            remained_base: format_amount(&order.remained_base),
+            reserved_amount: format_amount(&reserved_amount),
+        }
+    }
+}
@@ -1,6 +1,12 @@
+pub mod admin_service;
+pub mod auth;
 pub mod helper;
+pub mod rate_limit;
 pub mod server;
 pub mod service;
 pub mod spot {
     tonic::include_proto!("spot");
 }
+pub mod admin {
+    tonic::include_proto!("admin");
+}
@@ -0,0 +1,12 @@
+use common::rate_limit::RateClass;
+
+/// Rate limit a `SpotService` method counts against. Health, server info, rate limits and
+/// maintenance are never limited, and a method not listed here counts as a query.
+pub fn method_class(method: &str) -> Option<RateClass> {
+    match method {
+        "HealthCheck" | "GetServerInfo" | "GetRateLimits" | "SetMaintenanceMode" => None,
+        "AddOrder" | "AddOcoOrder" | "AmendOrder" | "AddOrders" => Some(RateClass::Order),
+        "CancelOrder" | "CancelOrders" | "CancelAllOrders" => Some(RateClass::Cancel),
+        _ => Some(RateClass::Query),
+    }
+}
@@ -1,36 +1,192 @@
+use common::auth::AuthLayer;
+use common::maintenance::MaintenanceMode;
+use common::metrics::{observe_db_pool, serve_metrics, GrpcMetricsLayer};
+use common::rate_limit::{RateLimitLayer, RateLimiter};
+use common::rounding::set_rounding_config;
 use database::establish_connection_pool;
 use database::repository::Repository;
+use std::future::Future;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{Notify, RwLock};
 
-use crate::config::app_config::get_database_url;
+use crate::config::app_config::{get_nats_url, EngineConfig};
+use crate::fee::fee_service::FeeService;
+use crate::grpc::admin::admin_service_server::AdminServiceServer;
+use crate::grpc::auth::method_scope;
+use crate::grpc::rate_limit::method_class;
 use crate::grpc::spot::spot_service_server::SpotServiceServer;
 use crate::{grpc::service::SpotServiceImpl, wallet::wallet_service::WalletService};
-use log::{error, info};
 use tonic::transport::Server;
+use tracing::{error, info, warn};
 
-use crate::market::market_manager::MarketManager;
-
-pub async fn start_server(address: String) -> Result<(), Box<dyn std::error::Error>> {
-    let adr = address.parse().unwrap();
-    info!("Bitrade Server listening on {}", address);
-
-    let database_url = get_database_url();
-    let pool_size = 10;
-    let pool = establish_connection_pool(database_url, pool_size);
-    let repository = Repository::new(pool);
-
-    if let Err(e) = Server::builder()
-        .add_service(SpotServiceServer::new(SpotServiceImpl {
-            market_manager: Arc::new(RwLock::new(MarketManager::new(Arc::new(
-                repository.clone(),
-            )))),
-            wallet_service: Arc::new(WalletService::new(Arc::new(repository))),
-        }))
-        .serve(adr)
-        .await
-    {
-        error!("Failed to start server: {:?}", e);
+use crate::market::expiry::run_expiry_sweeper;
+use crate::market::market_manager::{run_blocking, MarketManager};
+use crate::market::reload::run_market_reloader;
+use crate::market::snapshot::run_snapshot_writer;
+use crate::market::stats::{run_market_stats_updater, run_quote_updater};
+use crate::outbox::publisher::NatsPublisher;
+use crate::outbox::relay::{run_outbox_relay, OutboxRelay};
+use crate::reconciliation::reconciler::{run_reconciliation, Reconciler};
+
+/// Serves the engine as `config` has it until `shutdown` completes. New requests are then
+/// refused, those in flight get up to the shutdown timeout to finish, and every market is
+/// stopped with a last snapshot of its book before the outbox is flushed.
+pub async fn start_server(
+    config: EngineConfig,
+    shutdown: impl Future<Output = ()>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    info!("Bitrade Server listening on {}", config.server_address);
+    let api_keys = config.api_keys.clone();
+    if api_keys.is_empty() {
+        warn!("AUTH_DISABLED is set, gRPC requests are not authenticated");
+    }
+
+    let rate_limiter = Arc::new(RateLimiter::new(config.rate_limits));
+
+    set_rounding_config(config.rounding);
+
+    let pool = establish_connection_pool(config.database_url.clone(), config.pool_size);
+    let nats_url = get_nats_url();
+    let metrics_pool = pool.clone();
+    let metrics_address = config.metrics_address.to_string();
+    tokio::spawn(async move {
+        let on_scrape = move || {
+            let state = metrics_pool.state();
+            observe_db_pool(
+                state.connections,
+                state.idle_connections,
+                metrics_pool.max_size(),
+            );
+        };
+        if let Err(e) = serve_metrics(metrics_address.clone(), on_scrape).await {
+            error!("Failed to serve metrics on {}: {:?}", metrics_address, e);
+        }
+    });
+    let repository = Repository::new(pool)
+        .with_outbox(nats_url.is_some())
+        .with_missing_wallet_policy(config.missing_wallet_policy)
+        .with_idempotent_cancel(config.features.idempotent_cancel)
+        .with_balance_snapshots(config.features.trade_balance_snapshots);
+
+    let market_manager = Arc::new(RwLock::new(
+        MarketManager::with_config(Arc::new(repository.clone()), config.market.clone())
+            .with_idempotency_window(config.idempotency_window_ms)
+            .with_idempotent_cancel(config.features.idempotent_cancel),
+    ));
+    let intervals = &config.intervals;
+    tokio::spawn(run_expiry_sweeper(
+        market_manager.clone(),
+        intervals.order_expiry,
+    ));
+    tokio::spawn(run_market_stats_updater(
+        market_manager.clone(),
+        intervals.market_stats,
+    ));
+    tokio::spawn(run_quote_updater(
+        market_manager.clone(),
+        intervals.market_quotes,
+    ));
+    tokio::spawn(run_snapshot_writer(
+        market_manager.clone(),
+        intervals.order_book_snapshot,
+    ));
+    if let Some(interval) = intervals.market_reload {
+        tokio::spawn(run_market_reloader(market_manager.clone(), interval));
+    }
+    let relay = match nats_url {
+        Some(nats_url) => {
+            let publisher = NatsPublisher::connect(&nats_url).await?;
+            let relay = Arc::new(OutboxRelay::new(Arc::new(repository.clone()), publisher));
+            tokio::spawn(run_outbox_relay(relay.clone(), intervals.outbox_relay));
+            Some(relay)
+        }
+        None => None,
+    };
+    #[cfg(feature = "redis-cache")]
+    if let Some(redis_url) = crate::config::app_config::get_redis_url() {
+        use crate::cache::mirror::{run_market_data_mirror, MarketDataMirror};
+        use database::cache::redis_cache::RedisMarketCache;
+
+        let cache = RedisMarketCache::connect(&redis_url).await?;
+        let mirror = Arc::new(MarketDataMirror::new(
+            market_manager.clone(),
+            Arc::new(repository.clone()),
+            cache,
+        ));
+        tokio::spawn(run_market_data_mirror(mirror, intervals.market_cache));
+    }
+    let reconciler = Arc::new(Reconciler::new(Arc::new(repository.clone())));
+    tokio::spawn(run_reconciliation(
+        reconciler.clone(),
+        intervals.reconciliation,
+        config
+            .features
+            .release_orphaned_locks
+            .then_some(intervals.orphaned_lock_sweep),
+    ));
+
+    let maintenance = MaintenanceMode::new(config.maintenance_retry_after_secs);
+    let spot_service = SpotServiceImpl {
+        market_manager: market_manager.clone(),
+        wallet_service: Arc::new(WalletService::new(Arc::new(repository.clone()))),
+        fee_service: Arc::new(FeeService::new(Arc::new(repository))),
+        maintenance: maintenance.clone(),
+        max_response_fills: config.max_response_fills,
+        asset_registry: config.asset_registry.clone(),
+        fee_defaults: config.fee_defaults.clone(),
+        audit_orders: config.features.order_audit,
+        reconciler,
+        rate_limits: rate_limiter.limits(),
+    };
+    let admin_service = spot_service.admin_service();
+
+    let stopping = Arc::new(Notify::new());
+    let stop_serving = {
+        let stopping = stopping.clone();
+        async move {
+            shutdown.await;
+            info!("Shutting down, new orders are refused");
+            // Connections still open get a retry hint instead of a new order
+            maintenance.set_enabled(true);
+            stopping.notify_one();
+        }
+    };
+    let server = Server::builder()
+        .http2_keepalive_interval(Some(intervals.keepalive_interval))
+        .http2_keepalive_timeout(Some(intervals.keepalive_timeout))
+        .layer(GrpcMetricsLayer)
+        .layer(AuthLayer::new(api_keys, method_scope))
+        .layer(RateLimitLayer::new(rate_limiter.clone(), method_class))
+        .add_service(SpotServiceServer::new(spot_service))
+        .add_service(AdminServiceServer::new(admin_service))
+        .serve_with_shutdown(config.server_address, stop_serving);
+    // Streams never end on their own, so the requests in flight only get so long
+    let shutdown_timeout = intervals.shutdown_timeout;
+    tokio::select! {
+        served = server => {
+            if let Err(e) = served {
+                error!("Failed to start server: {:?}", e);
+            }
+        }
+        _ = async {
+            stopping.notified().await;
+            tokio::time::sleep(shutdown_timeout).await;
+        } => warn!("Requests still running after {:?}, stopping anyway", shutdown_timeout),
+    }
+
+    let stopped = run_blocking(&market_manager, |market_manager| {
+        market_manager.stop_all_markets()
+    })
+    .await;
+    match stopped {
+        Ok(written) => info!("Stopped markets, {} order book snapshots stored", written),
+        Err(e) => error!("Failed to stop markets: {:?}", e),
+    }
+    if let Some(relay) = relay {
+        match relay.flush().await {
+            Ok(published) => info!("Published {} outbox events", published),
+            Err(e) => error!("Failed to flush outbox: {:?}", e),
+        }
     }
 
     Ok(())
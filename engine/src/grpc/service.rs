@@ -1,181 +1,801 @@
-use super::helper::convert_trades;
-use super::spot::WithdrawResponse;
+use super::admin_service::AdminServiceImpl;
+use super::helper::{
+    build_add_order_response, convert_depth_levels, convert_trades, depth_delta_update,
+    depth_snapshot_update, server_info, subscription_stream,
+};
+use crate::config::app_config::FeeDefaults;
+use crate::fee::fee_service::FeeService;
 use crate::grpc::spot::spot_service_server::SpotService;
 use crate::grpc::spot::{
-    AddOrderRequest, AddOrderResponse, CancelOrderRequest, CancelOrderResponse,
-    CreateMarketRequest, CreateMarketResponse, StartMarketRequest, StartMarketResponse,
-    StopMarketRequest, StopMarketResponse,
+    AddOcoOrderRequest, AddOcoOrderResponse, AddOrderRequest, AddOrderResponse, AmendOrderRequest,
+    BatchAddOrderRequest, BatchAddOrderResponse, BatchAddOrderResult, BatchCancelRequest,
+    BatchCancelResponse, BatchCancelResult, CancelOrderRequest, CancelOrderResponse,
+};
+use crate::grpc::spot::{
+    ApproveWithdrawalRequest, CompleteDepositRequest, CompleteWithdrawalRequest,
+    RequestWithdrawalRequest, TransferResponse,
 };
 use crate::grpc::spot::{
-    CancelAllOrdersRequest, CancelAllOrdersResponse, DepositRequest, DepositResponse,
-    GetBalanceRequest, GetBalanceResponse, WithdrawRequest,
+    GetBalanceRequest, GetBalanceResponse, GetEngineStatsRequest, GetEngineStatsResponse,
+    GetOrderBookDepthRequest, GetOrderBookDepthResponse, GetRateLimitsRequest,
+    GetRateLimitsResponse, GetRecentTradesRequest, GetRecentTradesResponse, GetServerInfoRequest,
+    GetServerInfoResponse, HealthCheckRequest, HealthCheckResponse, OpenSessionRequest,
+    OrderBookUpdate, OrderConstraintViolation, ProtoUserEvent, SessionUpdate,
+    SetCancelOnDisconnectRequest, SetCancelOnDisconnectResponse, SubscribeOrderBookRequest,
+    SubscribeTradesRequest, SubscribeUserEventsRequest, TradeUpdate,
 };
-use crate::market::market_manager::MarketManager;
+use crate::market::market_manager::{run_blocking, MarketManager};
+use crate::market::MarketError;
 use crate::models::trade_order::TradeOrder;
-use crate::validation::{validate_add_order_request, validate_create_market_request};
+use crate::models::user_event::UserEvent;
+use crate::order_book::user_events::reject_reason;
+use crate::order_book::OrderBookError;
+use crate::reconciliation::reconciler::Reconciler;
+use crate::validation::{
+    validate_add_oco_order_request, validate_add_order_request, validate_amend_order_request,
+    validate_approve_withdrawal_request, validate_batch_size, validate_cancel_order_request,
+    validate_complete_deposit_request, validate_complete_withdrawal_request,
+    validate_get_order_book_depth_request, validate_request_withdrawal_request, AssetRegistry,
+    MarketConstraintError, DEFAULT_DEPTH_LEVELS,
+};
 use crate::wallet::wallet_service::WalletService;
 use anyhow::{Context, Result};
-use bigdecimal::BigDecimal;
+use common::auth::{authorize_principal, authorize_user, Principal};
+use common::error::{internal_status, with_error_code, BitradeError};
+use common::maintenance::MaintenanceMode;
+use common::rate_limit::RateLimits;
+use common::utils::{bigdecimal_from_str, format_amount, get_utc_now_millis, normalize_user_id};
+use database::models::models::{AuditAction, NewOrderAudit};
 use database::provider::DatabaseProvider;
-use std::str::FromStr;
+use database::repository::TransferError;
+use futures::{future, stream, Stream, StreamExt};
+use prost::Message;
+use std::fmt::Debug;
+use std::net::SocketAddr;
+use std::pin::Pin;
 use std::sync::Arc;
 use tokio::sync::RwLock;
-use tonic::{Request, Response, Status};
+use tonic::metadata::MetadataValue;
+use tonic::{Code, Request, Response, Status};
+
+/// Metadata key a failed `AddOrder` carries the id of the order stored as REJECTED under
+pub const ORDER_ID_METADATA: &str = "x-order-id";
+
+/// Metadata key a failed `AddOrder` carries the reason the order was rejected under
+pub const REJECT_REASON_METADATA: &str = "x-reject-reason";
 
 #[derive(Clone)]
 pub struct SpotServiceImpl<P: DatabaseProvider + 'static> {
     pub market_manager: Arc<RwLock<MarketManager<P>>>,
     pub wallet_service: Arc<WalletService<P>>,
+    /// Picks the rates orders are charged from user overrides and volume tiers
+    pub fee_service: Arc<FeeService<P>>,
+    pub maintenance: MaintenanceMode,
+    /// Maximum number of fills returned in an `AddOrder` response
+    pub max_response_fills: usize,
+    /// Assets new markets may use
+    pub asset_registry: AssetRegistry,
+    /// Fees new markets get when `CreateMarket` leaves them empty
+    pub fee_defaults: FeeDefaults,
+    /// Whether order create and cancel requests are written to the audit log
+    pub audit_orders: bool,
+    /// Balance checks behind the admin service's `GetReconciliationReport`
+    pub reconciler: Arc<Reconciler<P>>,
+    /// Limits the server's rate limiter enforces, returned by `GetRateLimits`
+    pub rate_limits: RateLimits,
 }
 
-#[tonic::async_trait]
-impl<P: DatabaseProvider + Send + Sync + 'static> SpotService for SpotServiceImpl<P> {
-    async fn create_market(
+impl<P: DatabaseProvider + 'static> SpotServiceImpl<P> {
+    /// The admin service over the same engine, which markets, maintenance, treasuries and
+    /// reconciliation are run through.
+    pub fn admin_service(&self) -> AdminServiceImpl<P> {
+        AdminServiceImpl {
+            market_manager: self.market_manager.clone(),
+            fee_service: self.fee_service.clone(),
+            maintenance: self.maintenance.clone(),
+            asset_registry: self.asset_registry.clone(),
+            fee_defaults: self.fee_defaults.clone(),
+            reconciler: self.reconciler.clone(),
+        }
+    }
+
+    /// Writes an order request to the audit log ahead of any validation, so it is on record
+    /// whatever happens to it afterwards. The request is refused if the entry can't be written.
+    async fn record_audit<R: Debug>(
         &self,
-        request: Request<CreateMarketRequest>,
-    ) -> Result<Response<CreateMarketResponse>, Status> {
-        let req = request.into_inner();
+        request: &Request<R>,
+        action: AuditAction,
+        market_id: &str,
+        user_id: Option<&str>,
+        order_id: Option<&str>,
+    ) -> Result<(), Status> {
+        self.record_audit_entry(
+            request.remote_addr(),
+            request.get_ref(),
+            action,
+            market_id,
+            user_id,
+            order_id,
+        )
+        .await
+    }
 
-        // Validate the request
-        validate_create_market_request(&req)
-            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+    /// Same as `record_audit`, for the entries of a batch that arrive in one request.
+    async fn record_audit_entry<R: Debug>(
+        &self,
+        remote_addr: Option<SocketAddr>,
+        request: &R,
+        action: AuditAction,
+        market_id: &str,
+        user_id: Option<&str>,
+        order_id: Option<&str>,
+    ) -> Result<(), Status> {
+        if !self.audit_orders {
+            return Ok(());
+        }
 
-        let market_id = req.market_id.clone();
-        let market_manager = self.market_manager.write().await;
-        market_manager
-            .create_market(
-                market_id.clone(),
-                req.base_asset,
-                req.quote_asset,
-                req.default_maker_fee,
-                req.default_taker_fee,
+        let entry = NewOrderAudit {
+            action: action.as_str().to_string(),
+            user_id: user_id.map(str::to_string),
+            remote_addr: remote_addr.map(|addr| addr.to_string()),
+            market_id: market_id.to_string(),
+            order_id: order_id.map(str::to_string),
+            request: format!("{:?}", request),
+            create_time: get_utc_now_millis(),
+        };
+        run_blocking(&self.market_manager, move |market_manager| {
+            market_manager.record_audit(entry)
+        })
+        .await
+        .map_err(internal_status)
+    }
+
+    /// Validates and places one order, once it is audited. Shared by `AddOrder` and `AddOrders`.
+    async fn place_order(&self, req: AddOrderRequest) -> Result<AddOrderResponse, Status> {
+        let test_order = req.test_order;
+        let (user_id, market_id) = (req.user_id.clone(), req.market_id.clone());
+        let client_order_id = Some(req.client_order_id.clone()).filter(|id| !id.is_empty());
+        let idempotency_key = Some(req.idempotency_key.clone()).filter(|key| !key.is_empty());
+
+        let order = match validate_add_order_request(&req) {
+            Ok(()) => TradeOrder::try_from(req)
+                .context("Failed to convert AddOrderRequest")
+                .map_err(internal_status),
+            Err(e) => Err(Status::invalid_argument(e.to_string())),
+        };
+        let mut order = match order {
+            Ok(order) => order,
+            Err(status) => {
+                // A request without a valid user has nobody to tell
+                if let (false, Ok(user_id)) = (test_order, normalize_user_id(&user_id)) {
+                    let event = UserEvent::rejected_request(
+                        user_id,
+                        market_id,
+                        client_order_id,
+                        status.message().to_string(),
+                    );
+                    self.market_manager.read().await.publish_user_event(event);
+                }
+                return Err(status);
+            }
+        };
+        self.fee_service
+            .apply_fees(&mut order)
+            .map_err(internal_status)?;
+
+        if test_order {
+            run_blocking(&self.market_manager, move |market_manager| {
+                market_manager.test_order(&order)
+            })
+            .await
+            .map_err(|e| {
+                if let Some(violation) = e.downcast_ref::<MarketConstraintError>() {
+                    return constraint_status(violation);
+                }
+                match e.downcast::<BitradeError>() {
+                    Ok(error) => error.into(),
+                    Err(e) => Status::invalid_argument(e.to_string()),
+                }
+            })?;
+
+            return Ok(AddOrderResponse::default());
+        }
+
+        // Markets lock themselves, so orders on different markets don't queue behind each other
+        let order_id = order.id.clone();
+        let receipt =
+            run_blocking(
+                &self.market_manager,
+                move |market_manager| match &idempotency_key {
+                    Some(key) => market_manager.add_order_idempotent(order, key),
+                    None => market_manager.add_order(order),
+                },
             )
-            .context("Failed to create market")
-            .map_err(|e| Status::internal(e.to_string()))?;
-        Ok(Response::new(CreateMarketResponse {
-            success: true,
-            market_id,
-        }))
+            .await
+            .map_err(|e| rejection_status(&order_id, e))?;
+
+        Ok(build_add_order_response(receipt, self.max_response_fills))
     }
 
-    async fn stop_market(
+    /// Checks `principal` may act for the owner of an order, which requests naming the order by
+    /// id leave out. The owner is read from the stored order, so orders that no longer rest are
+    /// covered too; an order that isn't stored is denied, as nobody is known to own it.
+    async fn authorize_order_owner(
         &self,
-        request: Request<StopMarketRequest>,
-    ) -> Result<Response<StopMarketResponse>, Status> {
-        let req = request.into_inner();
-        let market_id = req.market_id.clone();
-        let market_manager = self.market_manager.write().await;
-        market_manager
-            .stop_market(&market_id)
-            .context("Failed to create market")
-            .map_err(|e| Status::internal(e.to_string()))?;
-
-        Ok(Response::new(StopMarketResponse {
-            success: true,
-            market_id,
-        }))
+        principal: Option<&Principal>,
+        order_id: &str,
+    ) -> Result<(), Status> {
+        let Some(principal) = principal.filter(|p| **p != Principal::Admin) else {
+            return Ok(());
+        };
+        let id = order_id.to_string();
+        let order = run_blocking(&self.market_manager, move |market_manager| {
+            market_manager.get_stored_order(&id)
+        })
+        .await
+        .map_err(internal_status)?;
+        match order {
+            Some(order) => principal.authorize_user(&order.user_id),
+            None => Err(Status::permission_denied(format!(
+                "Order {} is not known to belong to the API key's user",
+                order_id
+            ))),
+        }
     }
 
-    async fn start_market(
+    async fn cancel_one_order(
         &self,
-        request: Request<StartMarketRequest>,
-    ) -> Result<Response<StartMarketResponse>, Status> {
-        let req = request.into_inner();
-        let market_id = req.market_id.clone();
-        let market_manager = self.market_manager.write().await;
-        let _ = market_manager
-            .start_market(&market_id)
-            .context("Failed to create market")
-            .map_err(|e| Status::internal(e.to_string()))?;
-        Ok(Response::new(StartMarketResponse {
-            success: true,
-            market_id,
-        }))
+        principal: Option<&Principal>,
+        req: CancelOrderRequest,
+    ) -> Result<CancelOrderResponse, Status> {
+        validate_cancel_order_request(&req).map_err(|e| Status::invalid_argument(e.to_string()))?;
+        if req.order_id.is_empty() {
+            authorize_principal(principal, &req.user_id)?;
+        } else {
+            self.authorize_order_owner(principal, &req.order_id).await?;
+        }
+
+        let order_id = if req.order_id.is_empty() {
+            let user_id = normalize_user_id(&req.user_id)
+                .map_err(|e| Status::invalid_argument(e.to_string()))?;
+            let client_order_id = req.client_order_id.clone();
+            run_blocking(&self.market_manager, move |market_manager| {
+                market_manager.get_order_by_client_id(&user_id, &client_order_id)
+            })
+            .await
+            .map_err(internal_status)?
+            .filter(|order| order.market_id == req.market_id)
+            .map(|order| order.id)
+            .ok_or_else(|| BitradeError::OrderNotFound(req.client_order_id.clone()))?
+        } else {
+            req.order_id
+        };
+        let (market_id, cancel_id) = (req.market_id.clone(), order_id.clone());
+        let success = run_blocking(&self.market_manager, move |market_manager| {
+            market_manager.cancel_order(&market_id, cancel_id)
+        })
+        .await
+        .map_err(cancel_status)?;
+
+        Ok(CancelOrderResponse {
+            success,
+            order_id,
+            market_id: req.market_id,
+        })
     }
+}
+
+/// Status for a failure to place an order, telling apart what the caller can act on.
+/// INVALID_ARGUMENT carrying an `OrderConstraintViolation` in its details, so clients can tell
+/// which limit an order broke without parsing the message.
+fn constraint_status(violation: &MarketConstraintError) -> Status {
+    let details = OrderConstraintViolation::from(violation).encode_to_vec();
+    let mut status =
+        Status::with_details(Code::InvalidArgument, violation.to_string(), details.into());
+    with_error_code(&mut status, BitradeError::from(violation).code());
+    status
+}
+
+fn order_placement_status(e: anyhow::Error) -> Status {
+    if let Some(violation) = e.downcast_ref::<MarketConstraintError>() {
+        return constraint_status(violation);
+    }
+    let message = e.to_string();
+    let error = match e.downcast_ref::<MarketError>() {
+        Some(MarketError::Recovering | MarketError::LoadFailed(_)) => {
+            BitradeError::Unavailable(message)
+        }
+        Some(MarketError::UserRestricted { .. }) => BitradeError::PermissionDenied(message),
+        Some(MarketError::StatusRestricted { .. } | MarketError::SettlementFailed(_)) => {
+            BitradeError::FailedPrecondition(message)
+        }
+        _ => match e.downcast_ref::<OrderBookError>() {
+            Some(
+                OrderBookError::PostOnlyWouldCross(_) | OrderBookError::OutsidePriceBand { .. },
+            ) => BitradeError::FailedPrecondition(message),
+            Some(OrderBookError::MatchingHalted(_)) => BitradeError::Unavailable(message),
+            Some(OrderBookError::DuplicateClientOrderId(_)) => BitradeError::AlreadyExists(message),
+            Some(OrderBookError::OpenOrderLimit(_) | OrderBookError::NotionalLimit { .. }) => {
+                BitradeError::RiskLimitExceeded(message)
+            }
+            None => BitradeError::from_anyhow(e),
+        },
+    };
+    error.into()
+}
+
+/// Like [`order_placement_status`], for an order the engine stored as REJECTED: its id and
+/// reason go in the `x-order-id` and `x-reject-reason` metadata.
+fn rejection_status(order_id: &str, e: anyhow::Error) -> Status {
+    let reason = reject_reason(&e);
+    let mut status = order_placement_status(e);
+    if let (Some(reason), Ok(order_id)) = (reason, MetadataValue::try_from(order_id)) {
+        let metadata = status.metadata_mut();
+        metadata.insert(ORDER_ID_METADATA, order_id);
+        metadata.insert(
+            REJECT_REASON_METADATA,
+            MetadataValue::from_static(reason.as_str()),
+        );
+    }
+    status
+}
 
+/// The `AddOrder` response of an order [`rejection_status`] reports as rejected
+fn rejected_order_response(status: &Status) -> Option<AddOrderResponse> {
+    let metadata = status.metadata();
+    let value = |key| Some(metadata.get(key)?.to_str().ok()?.to_string());
+    Some(AddOrderResponse {
+        order_id: value(ORDER_ID_METADATA)?,
+        reject_reason: value(REJECT_REASON_METADATA)?,
+        ..Default::default()
+    })
+}
+
+fn cancel_status(e: anyhow::Error) -> Status {
+    match e.downcast_ref::<MarketError>() {
+        Some(MarketError::StatusRestricted { .. } | MarketError::SettlementFailed(_)) => {
+            BitradeError::FailedPrecondition(e.to_string()).into()
+        }
+        Some(MarketError::LoadFailed(_)) => BitradeError::Unavailable(e.to_string()).into(),
+        _ => internal_status(e),
+    }
+}
+
+/// Status for a failed deposit or withdrawal step, telling apart what the caller can act on.
+fn transfer_status(e: anyhow::Error) -> Status {
+    let error = match e.downcast_ref::<TransferError>() {
+        Some(TransferError::NotFound(_)) => BitradeError::NotFound(e.to_string()),
+        Some(TransferError::InvalidStatus { .. }) => {
+            BitradeError::FailedPrecondition(e.to_string())
+        }
+        Some(TransferError::InsufficientBalance {
+            asset,
+            required,
+            available,
+        }) => BitradeError::InsufficientBalance {
+            asset: asset.clone(),
+            required: required.clone(),
+            available: available.clone(),
+        },
+        None => BitradeError::from_anyhow(e),
+    };
+    error.into()
+}
+
+#[tonic::async_trait]
+impl<P: DatabaseProvider + Send + Sync + 'static> SpotService for SpotServiceImpl<P> {
     async fn add_order(
         &self,
         request: Request<AddOrderRequest>,
     ) -> Result<Response<AddOrderResponse>, Status> {
+        self.maintenance.check()?;
+        authorize_user(&request, &request.get_ref().user_id)?;
+        self.record_audit(
+            &request,
+            AuditAction::CreateOrder,
+            &request.get_ref().market_id,
+            Some(&request.get_ref().user_id),
+            None,
+        )
+        .await?;
+
+        self.place_order(request.into_inner())
+            .await
+            .map(Response::new)
+    }
+
+    async fn add_orders(
+        &self,
+        request: Request<BatchAddOrderRequest>,
+    ) -> Result<Response<BatchAddOrderResponse>, Status> {
+        self.maintenance.check()?;
+        validate_batch_size(request.get_ref().orders.len())
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let remote_addr = request.remote_addr();
+        let principal = request.extensions().get::<Principal>().cloned();
+        let mut results = Vec::with_capacity(request.get_ref().orders.len());
+        for req in request.into_inner().orders {
+            if let Err(status) = authorize_principal(principal.as_ref(), &req.user_id) {
+                results.push(BatchAddOrderResult {
+                    code: status.code() as i32,
+                    message: status.message().to_string(),
+                    order: None,
+                });
+                continue;
+            }
+            let placed = match self
+                .record_audit_entry(
+                    remote_addr,
+                    &req,
+                    AuditAction::CreateOrder,
+                    &req.market_id,
+                    Some(&req.user_id),
+                    None,
+                )
+                .await
+            {
+                Ok(()) => self.place_order(req).await,
+                Err(status) => Err(status),
+            };
+            results.push(match placed {
+                Ok(order) => BatchAddOrderResult {
+                    order: Some(order),
+                    ..Default::default()
+                },
+                Err(status) => BatchAddOrderResult {
+                    code: status.code() as i32,
+                    message: status.message().to_string(),
+                    order: rejected_order_response(&status),
+                },
+            });
+        }
+
+        Ok(Response::new(BatchAddOrderResponse { results }))
+    }
+
+    async fn add_oco_order(
+        &self,
+        request: Request<AddOcoOrderRequest>,
+    ) -> Result<Response<AddOcoOrderResponse>, Status> {
+        self.maintenance.check()?;
+        let first = request.get_ref().first.clone().unwrap_or_default();
+        let second = request.get_ref().second.clone().unwrap_or_default();
+        authorize_user(&request, &first.user_id)?;
+        authorize_user(&request, &second.user_id)?;
+        self.record_audit(
+            &request,
+            AuditAction::CreateOcoOrder,
+            &first.market_id,
+            Some(&first.user_id),
+            None,
+        )
+        .await?;
+
         let req = request.into_inner();
 
-        // Validate the request
-        validate_add_order_request(&req).map_err(|e| Status::invalid_argument(e.to_string()))?;
+        validate_add_oco_order_request(&req)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
 
-        let order = TradeOrder::try_from(req)
-            .context("Failed to convert AddOrderRequest")
-            .map_err(|e| Status::internal(e.to_string()))?;
-        let market_manager = self.market_manager.write().await;
-        let res = market_manager
-            .add_order(order)
-            .map_err(|e| Status::internal(e.to_string()))?;
+        let [first, second] =
+            [req.first, req.second].map(|leg| TradeOrder::try_from(leg.unwrap_or_default()));
+        let (mut first, mut second) = first
+            .and_then(|first| Ok((first, second?)))
+            .context("Failed to convert AddOcoOrderRequest")
+            .map_err(internal_status)?;
+        for leg in [&mut first, &mut second] {
+            self.fee_service.apply_fees(leg).map_err(internal_status)?;
+        }
 
-        Ok(Response::new(AddOrderResponse {
-            trades: convert_trades(res.0),
-            order_id: res.1,
+        let receipt = run_blocking(&self.market_manager, move |market_manager| {
+            market_manager.add_oco_order(first, second)
+        })
+        .await
+        .map_err(order_placement_status)?;
+
+        Ok(Response::new(AddOcoOrderResponse {
+            oco_group_id: receipt.oco_group_id,
+            first: Some(build_add_order_response(
+                receipt.first,
+                self.max_response_fills,
+            )),
+            second: Some(build_add_order_response(
+                receipt.second,
+                self.max_response_fills,
+            )),
         }))
     }
 
+    async fn amend_order(
+        &self,
+        request: Request<AmendOrderRequest>,
+    ) -> Result<Response<AddOrderResponse>, Status> {
+        self.maintenance.check()?;
+        let req = request.get_ref();
+        self.authorize_order_owner(request.extensions().get(), &req.order_id)
+            .await?;
+        self.record_audit(
+            &request,
+            AuditAction::AmendOrder,
+            &request.get_ref().market_id,
+            None,
+            Some(&request.get_ref().order_id),
+        )
+        .await?;
+
+        let req = request.into_inner();
+        validate_amend_order_request(&req).map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let price = (!req.price.is_empty())
+            .then(|| bigdecimal_from_str(&req.price, "price"))
+            .transpose()
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let remained_base = (!req.remained_base.is_empty())
+            .then(|| bigdecimal_from_str(&req.remained_base, "remained_base"))
+            .transpose()
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let receipt = run_blocking(&self.market_manager, move |market_manager| {
+            market_manager.amend_order(&req.market_id, req.order_id, price, remained_base)
+        })
+        .await
+        .map_err(order_placement_status)?;
+
+        Ok(Response::new(build_add_order_response(
+            receipt,
+            self.max_response_fills,
+        )))
+    }
+
     async fn cancel_order(
         &self,
         request: Request<CancelOrderRequest>,
     ) -> Result<Response<CancelOrderResponse>, Status> {
+        self.maintenance.check()?;
+        self.record_audit(
+            &request,
+            AuditAction::CancelOrder,
+            &request.get_ref().market_id,
+            None,
+            Some(&request.get_ref().order_id),
+        )
+        .await?;
+
+        let principal = request.extensions().get::<Principal>().cloned();
+        self.cancel_one_order(principal.as_ref(), request.into_inner())
+            .await
+            .map(Response::new)
+    }
+
+    async fn cancel_orders(
+        &self,
+        request: Request<BatchCancelRequest>,
+    ) -> Result<Response<BatchCancelResponse>, Status> {
+        self.maintenance.check()?;
+        validate_batch_size(request.get_ref().orders.len())
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let remote_addr = request.remote_addr();
+        let principal = request.extensions().get::<Principal>().cloned();
+        let mut results = Vec::with_capacity(request.get_ref().orders.len());
+        for req in request.into_inner().orders {
+            let canceled = match self
+                .record_audit_entry(
+                    remote_addr,
+                    &req,
+                    AuditAction::CancelOrder,
+                    &req.market_id,
+                    None,
+                    Some(&req.order_id),
+                )
+                .await
+            {
+                Ok(()) => self.cancel_one_order(principal.as_ref(), req).await,
+                Err(status) => Err(status),
+            };
+            results.push(match canceled {
+                Ok(order) => BatchCancelResult {
+                    order: Some(order),
+                    ..Default::default()
+                },
+                Err(status) => BatchCancelResult {
+                    code: status.code() as i32,
+                    message: status.message().to_string(),
+                    order: None,
+                },
+            });
+        }
+
+        Ok(Response::new(BatchCancelResponse { results }))
+    }
+
+    async fn get_order_book_depth(
+        &self,
+        request: Request<GetOrderBookDepthRequest>,
+    ) -> Result<Response<GetOrderBookDepthResponse>, Status> {
+        self.maintenance.check()?;
+
         let req = request.into_inner();
-        let order_id = req.order_id.clone();
+        validate_get_order_book_depth_request(&req)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let levels = match req.levels {
+            0 => DEFAULT_DEPTH_LEVELS,
+            levels => levels as usize,
+        };
+        let price_step = (!req.price_aggregation.is_empty())
+            .then(|| bigdecimal_from_str(&req.price_aggregation, "price_aggregation"))
+            .transpose()
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
         let market_id = req.market_id.clone();
-        let market_manager = self.market_manager.write().await;
-        let success = market_manager
-            .cancel_order(&req.market_id, req.order_id)
-            .map_err(|e| Status::internal(e.to_string()))?;
+        let depth = run_blocking(&self.market_manager, move |market_manager| {
+            market_manager.get_order_book_depth(&market_id, levels, price_step)
+        })
+        .await
+        .map_err(internal_status)?;
 
-        Ok(Response::new(CancelOrderResponse {
-            success,
-            order_id,
-            market_id,
+        Ok(Response::new(GetOrderBookDepthResponse {
+            market_id: req.market_id,
+            bids: convert_depth_levels(depth.bids),
+            asks: convert_depth_levels(depth.asks),
+            sequence: depth.sequence,
         }))
     }
 
-    async fn cancel_all_orders(
+    type SubscribeOrderBookStream =
+        Pin<Box<dyn Stream<Item = Result<OrderBookUpdate, Status>> + Send + 'static>>;
+
+    async fn subscribe_order_book(
+        &self,
+        request: Request<SubscribeOrderBookRequest>,
+    ) -> Result<Response<Self::SubscribeOrderBookStream>, Status> {
+        self.maintenance.check()?;
+
+        let market_id = request.into_inner().market_id;
+        let subscribed_id = market_id.clone();
+        let (snapshot, sequence, receiver) =
+            run_blocking(&self.market_manager, move |market_manager| {
+                market_manager.subscribe_order_book(&subscribed_id)
+            })
+            .await
+            .map_err(internal_status)?;
+
+        let first = depth_snapshot_update(&market_id, snapshot, sequence);
+        let deltas =
+            subscription_stream(receiver, move |delta| depth_delta_update(&market_id, delta));
+
+        Ok(Response::new(
+            stream::once(async { Ok(first) }).chain(deltas).boxed(),
+        ))
+    }
+
+    type SubscribeTradesStream =
+        Pin<Box<dyn Stream<Item = Result<TradeUpdate, Status>> + Send + 'static>>;
+
+    async fn subscribe_trades(
         &self,
-        request: Request<CancelAllOrdersRequest>,
-    ) -> Result<Response<CancelAllOrdersResponse>, Status> {
+        request: Request<SubscribeTradesRequest>,
+    ) -> Result<Response<Self::SubscribeTradesStream>, Status> {
+        self.maintenance.check()?;
+
         let req = request.into_inner();
-        let market_id = req.market_id.clone();
-        let market_manager = self.market_manager.write().await;
-        let success = market_manager
-            .cancel_all_orders(&req.market_id)
-            .context("Failed to cancel all orders")
-            .map_err(|e| Status::internal(e.to_string()))?;
+        let (replay, receiver) = run_blocking(&self.market_manager, move |market_manager| {
+            market_manager.subscribe_trades(&req.market_id, req.since_sequence)
+        })
+        .await
+        .map_err(internal_status)?;
 
-        Ok(Response::new(CancelAllOrdersResponse {
-            success,
-            market_id,
+        let replay = stream::iter(replay.into_iter().map(TradeUpdate::from).map(Ok));
+        let live = subscription_stream(receiver, TradeUpdate::from);
+
+        Ok(Response::new(replay.chain(live).boxed()))
+    }
+
+    type SubscribeUserEventsStream =
+        Pin<Box<dyn Stream<Item = Result<ProtoUserEvent, Status>> + Send + 'static>>;
+
+    async fn subscribe_user_events(
+        &self,
+        request: Request<SubscribeUserEventsRequest>,
+    ) -> Result<Response<Self::SubscribeUserEventsStream>, Status> {
+        self.maintenance.check()?;
+        authorize_user(&request, &request.get_ref().user_id)?;
+
+        let user_id = normalize_user_id(&request.into_inner().user_id)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let receiver = self.market_manager.read().await.subscribe_user_events();
+
+        // Every user's events share one channel, so others' are dropped here
+        let events = subscription_stream(receiver, move |event: UserEvent| {
+            (event.user_id == user_id).then(|| ProtoUserEvent::from(event))
+        })
+        .filter_map(|event| future::ready(event.transpose()));
+
+        Ok(Response::new(events.boxed()))
+    }
+
+    type OpenSessionStream =
+        Pin<Box<dyn Stream<Item = Result<SessionUpdate, Status>> + Send + 'static>>;
+
+    async fn open_session(
+        &self,
+        request: Request<OpenSessionRequest>,
+    ) -> Result<Response<Self::OpenSessionStream>, Status> {
+        self.maintenance.check()?;
+        authorize_user(&request, &request.get_ref().user_id)?;
+
+        let user_id = normalize_user_id(&request.into_inner().user_id)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let (session, (sessions, _)) = {
+            let market_manager = self.market_manager.read().await;
+            let session = market_manager.open_session(&user_id);
+            (session, market_manager.user_sessions(&user_id))
+        };
+
+        let opened = SessionUpdate {
+            user_id,
+            sessions: sessions as u32,
+        };
+        // The stream is the user's session, closed with it when the client goes away
+        let updates = stream::once(future::ready(Ok(opened)))
+            .chain(stream::pending())
+            .inspect(move |_| {
+                let _session = &session;
+            });
+
+        Ok(Response::new(updates.boxed()))
+    }
+
+    async fn set_cancel_on_disconnect(
+        &self,
+        request: Request<SetCancelOnDisconnectRequest>,
+    ) -> Result<Response<SetCancelOnDisconnectResponse>, Status> {
+        self.maintenance.check()?;
+        authorize_user(&request, &request.get_ref().user_id)?;
+
+        let req = request.into_inner();
+        let user_id =
+            normalize_user_id(&req.user_id).map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let sessions = self
+            .market_manager
+            .read()
+            .await
+            .set_cancel_on_disconnect(&user_id, req.enabled)
+            .map_err(|e| match e.downcast_ref::<MarketError>() {
+                Some(MarketError::NoSession(_)) => {
+                    BitradeError::FailedPrecondition(e.to_string()).into()
+                }
+                _ => internal_status(e),
+            })?;
+
+        Ok(Response::new(SetCancelOnDisconnectResponse {
+            enabled: req.enabled,
+            sessions: sessions as u32,
         }))
     }
 
-    async fn deposit(
+    async fn get_recent_trades(
         &self,
-        request: Request<DepositRequest>,
-    ) -> Result<Response<DepositResponse>, Status> {
+        request: Request<GetRecentTradesRequest>,
+    ) -> Result<Response<GetRecentTradesResponse>, Status> {
+        self.maintenance.check()?;
+
         let req = request.into_inner();
+        let limit = match req.limit {
+            0 => usize::MAX,
+            limit => limit as usize,
+        };
+        let market_id = req.market_id.clone();
+        let trades = run_blocking(&self.market_manager, move |market_manager| {
+            market_manager.get_recent_trades(&market_id, limit)
+        })
+        .await
+        .map_err(internal_status)?;
 
-        let err_text = "Failed to convert amount from string";
-        let res = self
-            .wallet_service
-            .deposit(
-                &req.asset.clone(),
-                BigDecimal::from_str(&req.amount)
-                    .context(err_text)
-                    .map_err(|e| Status::internal(e.to_string()))?,
-                &req.user_id,
-            )
-            .context("Failed to deposit")
-            .map_err(|e| Status::internal(e.to_string()))?;
-        Ok(Response::new(DepositResponse {
-            success: true,
-            asset: res.asset,
-            amount: res.available.to_string(),
-            user_id: res.user_id,
+        Ok(Response::new(GetRecentTradesResponse {
+            market_id: req.market_id,
+            trades: convert_trades(trades),
         }))
     }
 
@@ -183,45 +803,156 @@ impl<P: DatabaseProvider + Send + Sync + 'static> SpotService for SpotServiceImp
         &self,
         request: Request<GetBalanceRequest>,
     ) -> Result<Response<GetBalanceResponse>, Status> {
+        self.maintenance.check()?;
+        authorize_user(&request, &request.get_ref().user_id)?;
+
         let req = request.into_inner();
+        let user_id =
+            normalize_user_id(&req.user_id).map_err(|e| Status::invalid_argument(e.to_string()))?;
 
         let balance = self
             .wallet_service
-            .get_balance(&req.asset, &req.user_id)
+            .get_balance(&req.asset, &user_id)
             .context("Failed to convert amount from string")
-            .map_err(|e| Status::internal(e.to_string()))?;
+            .map_err(internal_status)?;
 
         Ok(Response::new(GetBalanceResponse {
-            user_id: req.user_id,
+            user_id,
             asset: req.asset,
-            amount: balance.to_string(),
+            amount: format_amount(&balance),
         }))
     }
 
-    async fn withdraw(
+    async fn complete_deposit(
         &self,
-        request: Request<WithdrawRequest>,
-    ) -> Result<Response<WithdrawResponse>, Status> {
+        request: Request<CompleteDepositRequest>,
+    ) -> Result<Response<TransferResponse>, Status> {
+        self.maintenance.check()?;
+
         let req = request.into_inner();
+        validate_complete_deposit_request(&req)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let user_id =
+            normalize_user_id(&req.user_id).map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let amount = bigdecimal_from_str(&req.amount, "amount")
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
 
-        let err_text = "Failed to convert amount from string";
-        let res = self
+        let transfer = self
             .wallet_service
-            .withdraw(
-                &req.asset.clone(),
-                BigDecimal::from_str(&req.amount)
-                    .context(err_text)
-                    .map_err(|e| Status::internal(e.to_string()))?,
-                &req.user_id,
-            )
-            .context("Failed to withdraw")
-            .map_err(|e| Status::internal(e.to_string()))?;
-
-        Ok(Response::new(WithdrawResponse {
-            success: true,
-            asset: res.asset,
-            amount: res.available.to_string(),
-            user_id: res.user_id,
+            .complete_deposit(&req.asset, amount, &user_id, &req.external_id)
+            .map_err(transfer_status)?;
+        Ok(Response::new(TransferResponse {
+            transfer: Some(transfer.into()),
+        }))
+    }
+
+    async fn request_withdrawal(
+        &self,
+        request: Request<RequestWithdrawalRequest>,
+    ) -> Result<Response<TransferResponse>, Status> {
+        self.maintenance.check()?;
+        authorize_user(&request, &request.get_ref().user_id)?;
+
+        let req = request.into_inner();
+        validate_request_withdrawal_request(&req)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let user_id =
+            normalize_user_id(&req.user_id).map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let amount = bigdecimal_from_str(&req.amount, "amount")
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let transfer = self
+            .wallet_service
+            .request_withdrawal(&req.asset, amount, &user_id, &req.address)
+            .map_err(transfer_status)?;
+        Ok(Response::new(TransferResponse {
+            transfer: Some(transfer.into()),
+        }))
+    }
+
+    async fn approve_withdrawal(
+        &self,
+        request: Request<ApproveWithdrawalRequest>,
+    ) -> Result<Response<TransferResponse>, Status> {
+        self.maintenance.check()?;
+
+        let req = request.into_inner();
+        validate_approve_withdrawal_request(&req)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let transfer = self
+            .wallet_service
+            .approve_withdrawal(&req.transfer_id, req.approved, &req.reject_reason)
+            .map_err(transfer_status)?;
+        Ok(Response::new(TransferResponse {
+            transfer: Some(transfer.into()),
+        }))
+    }
+
+    async fn complete_withdrawal(
+        &self,
+        request: Request<CompleteWithdrawalRequest>,
+    ) -> Result<Response<TransferResponse>, Status> {
+        self.maintenance.check()?;
+
+        let req = request.into_inner();
+        validate_complete_withdrawal_request(&req)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let transfer = self
+            .wallet_service
+            .complete_withdrawal(&req.transfer_id, &req.external_id)
+            .map_err(transfer_status)?;
+        Ok(Response::new(TransferResponse {
+            transfer: Some(transfer.into()),
+        }))
+    }
+
+    async fn health_check(
+        &self,
+        _request: Request<HealthCheckRequest>,
+    ) -> Result<Response<HealthCheckResponse>, Status> {
+        Ok(Response::new(HealthCheckResponse {
+            serving: true,
+            maintenance: self.maintenance.is_enabled(),
+        }))
+    }
+
+    async fn get_server_info(
+        &self,
+        _request: Request<GetServerInfoRequest>,
+    ) -> Result<Response<GetServerInfoResponse>, Status> {
+        // Like health, build info stays readable during maintenance
+        Ok(Response::new(server_info()))
+    }
+
+    async fn get_rate_limits(
+        &self,
+        _request: Request<GetRateLimitsRequest>,
+    ) -> Result<Response<GetRateLimitsResponse>, Status> {
+        Ok(Response::new(GetRateLimitsResponse {
+            orders_per_sec: self.rate_limits.orders_per_sec,
+            cancels_per_sec: self.rate_limits.cancels_per_sec,
+            queries_per_sec: self.rate_limits.queries_per_sec,
+        }))
+    }
+
+    async fn get_engine_stats(
+        &self,
+        _request: Request<GetEngineStatsRequest>,
+    ) -> Result<Response<GetEngineStatsResponse>, Status> {
+        // Dashboards keep polling while the engine is in maintenance
+        let stats = run_blocking(&self.market_manager, |market_manager| {
+            market_manager.get_engine_stats()
+        })
+        .await
+        .map_err(internal_status)?;
+
+        Ok(Response::new(GetEngineStatsResponse {
+            active_markets: stats.active_markets as u64,
+            open_orders: stats.open_orders as u64,
+            bid_volume: format_amount(&stats.bid_volume),
+            ask_volume: format_amount(&stats.ask_volume),
         }))
     }
 }
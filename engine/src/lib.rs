@@ -1,8 +1,16 @@
+pub mod cache;
 pub mod config;
+pub mod fee;
 pub mod grpc;
 pub mod market;
+pub mod metrics;
 pub mod models;
 pub mod order_book;
+pub mod outbox;
+pub mod reconciliation;
+#[cfg(any(test, feature = "simulator"))]
+pub mod simulator;
+#[cfg(any(test, feature = "test-utils"))]
 pub mod tests;
 pub mod validation;
 pub mod wallet;
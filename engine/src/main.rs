@@ -1,19 +1,79 @@
-use bitrade::{config::app_config::get_server_address, grpc::server::start_server};
-use env_logger;
-use log::{error, info};
+use bitrade::{config::app_config::EngineConfig, grpc::server::start_server};
+use common::config::ConfigOptions;
+use structopt::StructOpt;
+use tokio::sync::oneshot;
+use tracing::{error, info};
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "bitrade")]
+struct Options {
+    #[structopt(flatten)]
+    config: ConfigOptions,
+}
 
 #[tokio::main]
 async fn main() {
-    // Initialize logging
-    env_logger::init();
+    // The config file comes first, as it may set the logging up
+    if let Err(e) = Options::from_args().config.load() {
+        eprintln!("{:#}", e);
+        std::process::exit(1);
+    }
+    common::logging::init_tracing();
 
     info!("Starting Bitrade Matching Engine...");
 
-    let server_address = get_server_address();
-    info!("Server will listen on: {}", server_address);
+    // Listening from the start, so a signal while markets load still stops the engine cleanly
+    let (stop_sender, stop_receiver) = oneshot::channel();
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        let _ = stop_sender.send(());
+    });
 
-    match start_server(server_address).await {
+    let config = match EngineConfig::load() {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Invalid configuration: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+    info!("Server will listen on: {}", config.server_address);
+
+    let shutdown = async {
+        let _ = stop_receiver.await;
+    };
+    match start_server(config, shutdown).await {
         Ok(_) => info!("Server stopped gracefully"),
         Err(e) => error!("Server error: {}", e),
     }
 }
+
+/// Completes on SIGTERM, as sent by orchestrators stopping the engine, or on Ctrl-C
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("Failed to listen for Ctrl-C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => info!("Received Ctrl-C"),
+        _ = terminate => info!("Received SIGTERM"),
+    }
+}
@@ -0,0 +1,28 @@
+use common::utils::get_utc_now_millis;
+use database::provider::DatabaseProvider;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tracing::{error, info};
+
+use super::market_manager::{run_blocking, MarketManager};
+
+/// Cancels expired GTD orders every `interval`, for as long as the engine runs.
+pub async fn run_expiry_sweeper<P: DatabaseProvider>(
+    market_manager: Arc<RwLock<MarketManager<P>>>,
+    interval: Duration,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        let expired = run_blocking(&market_manager, |market_manager| {
+            market_manager.expire_orders(get_utc_now_millis())
+        })
+        .await;
+        match expired {
+            Ok(0) => {}
+            Ok(expired) => info!("Expired {} orders", expired),
+            Err(e) => error!("Failed to expire orders: {:?}", e),
+        }
+    }
+}
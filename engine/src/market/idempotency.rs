@@ -0,0 +1,105 @@
+use common::utils::get_utc_now_millis;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use crate::models::order_receipt::OrderReceipt;
+
+/// How long, in milliseconds, a placed order is remembered under its idempotency key when
+/// nothing else is configured
+pub const DEFAULT_IDEMPOTENCY_WINDOW_MS: i64 = 10 * 60 * 1000;
+
+/// Receipts of recently placed orders by user and idempotency key, so a retried request is
+/// answered with the order it already placed. Entries are forgotten once they are older than
+/// the window.
+#[derive(Debug)]
+pub struct IdempotencyCache {
+    window_ms: i64,
+    receipts: HashMap<(String, String), OrderReceipt>,
+    /// Keys with the time they were stored, oldest first
+    placed: VecDeque<(i64, (String, String))>,
+}
+
+impl IdempotencyCache {
+    pub fn new(window_ms: i64) -> Self {
+        Self {
+            window_ms,
+            receipts: HashMap::new(),
+            placed: VecDeque::new(),
+        }
+    }
+
+    /// The receipt of the order `user_id` placed under `key` within the window, if any.
+    pub fn get(&mut self, user_id: &str, key: &str, now: i64) -> Option<OrderReceipt> {
+        self.forget_expired(now);
+        self.receipts
+            .get(&(user_id.to_string(), key.to_string()))
+            .cloned()
+    }
+
+    pub fn insert(&mut self, user_id: &str, key: &str, receipt: OrderReceipt, now: i64) {
+        self.forget_expired(now);
+        let cache_key = (user_id.to_string(), key.to_string());
+        if self.receipts.insert(cache_key.clone(), receipt).is_none() {
+            self.placed.push_back((now, cache_key));
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.receipts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.receipts.is_empty()
+    }
+
+    fn forget_expired(&mut self, now: i64) {
+        let window_start = now - self.window_ms;
+        while self
+            .placed
+            .front()
+            .is_some_and(|(time, _)| *time <= window_start)
+        {
+            if let Some((_, cache_key)) = self.placed.pop_front() {
+                self.receipts.remove(&cache_key);
+            }
+        }
+    }
+}
+
+/// An idempotency key together with the cache it is looked up in, handed to the order book
+/// thread with the order it guards
+#[derive(Debug, Clone)]
+pub struct IdempotentPlacement {
+    cache: Arc<Mutex<IdempotencyCache>>,
+    key: String,
+}
+
+impl IdempotentPlacement {
+    pub fn new(cache: Arc<Mutex<IdempotencyCache>>, key: &str) -> Self {
+        Self {
+            cache,
+            key: key.to_string(),
+        }
+    }
+
+    /// The receipt of the order `user_id` placed under this key within the window, if any.
+    pub fn placed(&self, user_id: &str) -> Option<OrderReceipt> {
+        self.cache().get(user_id, &self.key, get_utc_now_millis())
+    }
+
+    pub fn remember(&self, user_id: &str, receipt: OrderReceipt) {
+        self.cache()
+            .insert(user_id, &self.key, receipt, get_utc_now_millis());
+    }
+
+    fn cache(&self) -> MutexGuard<'_, IdempotencyCache> {
+        // Every change leaves the cache consistent, so one interrupted by a panic is usable
+        self.cache.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Default for IdempotencyCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_IDEMPOTENCY_WINDOW_MS)
+    }
+}
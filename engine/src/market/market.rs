@@ -1,13 +1,23 @@
 use anyhow::Result;
+use bigdecimal::BigDecimal;
 use crossbeam::channel;
+use database::models::models::{CancelReason, Market as MarketRow, MarketStatus};
 use database::provider::DatabaseProvider;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard};
 use std::thread;
+use tokio::sync::broadcast;
+use tracing::{error, info};
 
-use crate::models::matched_trade::MatchedTrade;
-use crate::models::trade_order::TradeOrder;
-use crate::order_book::OrderBook;
+use super::idempotency::IdempotentPlacement;
+
+use crate::models::matched_trade::{MatchedTrade, SequencedTrade};
+use crate::models::order_receipt::{OcoReceipt, OrderReceipt};
+use crate::models::trade_order::{OrderType, TradeOrder};
+use crate::models::user_event::UserEvent;
+use crate::order_book::depth_diff::{DepthDelta, DepthSnapshot, OrderBookDepth};
+use crate::order_book::{OrderBook, StalePricePolicy};
+use crate::validation::validate_order_against_market;
 
 /// Custom error type for market-related failures
 #[derive(Debug, thiserror::Error)]
@@ -23,21 +33,187 @@ pub enum MarketError {
 
     #[error("Market is already started")]
     MarketAlreadyStarted,
+
+    #[error("Markets are recovering open orders, retry shortly")]
+    Recovering,
+
+    #[error("User {user_id} is {status} and may not place or amend orders")]
+    UserRestricted {
+        user_id: String,
+        status: &'static str,
+    },
+
+    #[error("Market is {status} and does not accept {action}")]
+    StatusRestricted {
+        status: &'static str,
+        action: &'static str,
+    },
+
+    #[error("User {0} has no session open, subscribe to their events first")]
+    NoSession(String),
+
+    #[error("Market {0} failed to load its order book, reload markets once the cause is fixed")]
+    LoadFailed(String),
+
+    #[error("Market {0} is halted, its trades could not be stored")]
+    SettlementFailed(String),
 }
 
 type Task<P> = Box<dyn FnOnce(&mut OrderBook<P>) + Send + 'static>;
 
+/// Trades kept in memory per market when nothing else is configured
+pub const DEFAULT_RECENT_TRADES_CAPACITY: usize = 100;
+
+/// Settings handed to the order book of a market when it is created
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarketConfig {
+    /// Largest deviation, in percent of the last traded price, a trade price may have
+    pub price_collar: Option<BigDecimal>,
+    /// Age, in milliseconds, after which the last traded price is stale for the collar
+    pub market_price_max_age_ms: Option<i64>,
+    /// How the collar relaxes once the last traded price is stale
+    pub stale_price_policy: StalePricePolicy,
+    /// Number of recent trades the order book keeps in memory
+    pub recent_trades_capacity: usize,
+    /// Whether new orders are created in the transaction settling their first fills
+    pub atomic_placement: bool,
+    /// Whether trades are stored behind the order book by a settlement writer
+    pub async_settlement: bool,
+}
+
+impl Default for MarketConfig {
+    fn default() -> Self {
+        Self {
+            price_collar: None,
+            market_price_max_age_ms: None,
+            stale_price_policy: StalePricePolicy::default(),
+            recent_trades_capacity: DEFAULT_RECENT_TRADES_CAPACITY,
+            atomic_placement: false,
+            async_settlement: false,
+        }
+    }
+}
+
+/// Trading parameters of a market, cached from its database row so orders are checked without
+/// a round trip. `UpdateMarket` refreshes them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarketParams {
+    pub default_maker_fee: BigDecimal,
+    pub default_taker_fee: BigDecimal,
+    pub min_base_amount: BigDecimal,
+    pub min_quote_amount: BigDecimal,
+    pub price_precision: i32,
+    pub amount_precision: i32,
+}
+
+impl Default for MarketParams {
+    fn default() -> Self {
+        Self {
+            default_maker_fee: BigDecimal::from(0),
+            default_taker_fee: BigDecimal::from(0),
+            min_base_amount: BigDecimal::from(0),
+            min_quote_amount: BigDecimal::from(0),
+            price_precision: 8,
+            amount_precision: 8,
+        }
+    }
+}
+
+impl From<&MarketRow> for MarketParams {
+    fn from(row: &MarketRow) -> Self {
+        Self {
+            default_maker_fee: row.default_maker_fee.clone(),
+            default_taker_fee: row.default_taker_fee.clone(),
+            min_base_amount: row.min_base_amount.clone(),
+            min_quote_amount: row.min_quote_amount.clone(),
+            price_precision: row.price_precision,
+            amount_precision: row.amount_precision,
+        }
+    }
+}
+
+/// What a market lets through: its trading phase and the parameters orders are checked against
+#[derive(Debug, Clone)]
+struct Admission {
+    status: MarketStatus,
+    params: MarketParams,
+}
+
+impl Admission {
+    fn check_order(&self, order: &TradeOrder) -> Result<()> {
+        let action = match self.status {
+            MarketStatus::Active => None,
+            MarketStatus::PostOnly
+                if order.order_type == OrderType::Limit && order.post_only == Some(true) =>
+            {
+                None
+            }
+            MarketStatus::PostOnly => Some("orders other than post-only limits"),
+            _ => Some("new orders"),
+        };
+        match action {
+            Some(action) => Err(self.restricted(action)),
+            None => validate_order_against_market(order, &self.params),
+        }
+    }
+
+    fn check_cancel(&self) -> Result<()> {
+        match self.status.accepts_cancels() {
+            true => Ok(()),
+            false => Err(self.restricted("cancels")),
+        }
+    }
+
+    /// Amending can match the order again, so only an active market allows it. The amended
+    /// order is held to the market's minimums and precisions like a new one.
+    fn check_amend(&self, amended: &TradeOrder) -> Result<()> {
+        match self.status {
+            MarketStatus::Active => validate_order_against_market(amended, &self.params),
+            _ => Err(self.restricted("amendments")),
+        }
+    }
+
+    fn restricted(&self, action: &'static str) -> anyhow::Error {
+        MarketError::StatusRestricted {
+            status: self.status.as_str(),
+            action,
+        }
+        .into()
+    }
+}
+
+fn read(admission: &RwLock<Admission>) -> RwLockReadGuard<'_, Admission> {
+    // Writers only assign fields, a panic cannot leave it half updated
+    admission.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Handle to a market whose order book runs on a thread of its own, taking one task at a time
+/// from the market's channel. The book persists through blocking Diesel calls, which is why it
+/// is not a tokio task.
+///
+/// Callers share the handle without locking it: requests are ordered by the channel alone, and
+/// the checks a request goes through run on the book's thread as part of its task, against the
+/// market's status and parameters as they are when the task runs.
 #[derive(Debug)]
 pub struct Market<P>
 where
     P: DatabaseProvider + 'static,
 {
     task_sender: channel::Sender<Task<P>>,
+    #[allow(dead_code)]
     persister: Arc<P>,
     market_id: String,
     base_asset: String,
     quote_asset: String,
     started: Arc<AtomicBool>, // Track market status
+    /// Set once the order book has recovered its open orders from the database
+    ready: Arc<AtomicBool>,
+    /// Set if the order book could not be loaded, which leaves the market without a book
+    failed: Arc<AtomicBool>,
+    /// Set if the book's trades could not be stored, which halts the market and ends its book
+    settlement_failed: Arc<AtomicBool>,
+    /// Trading phase and parameters, shared with the book's thread
+    admission: Arc<RwLock<Admission>>,
 }
 
 impl<P: DatabaseProvider> Market<P> {
@@ -46,27 +222,80 @@ impl<P: DatabaseProvider> Market<P> {
         market_id: String,
         base_asset: String,
         quote_asset: String,
+        config: MarketConfig,
+        user_events: broadcast::Sender<UserEvent>,
     ) -> Result<Self> {
         let (task_sender, task_receiver): (channel::Sender<Task<P>>, channel::Receiver<Task<P>>) =
             channel::unbounded();
 
         let started = Arc::new(AtomicBool::new(false));
+        let ready = Arc::new(AtomicBool::new(false));
+        let failed = Arc::new(AtomicBool::new(false));
+        let settlement_failed = Arc::new(AtomicBool::new(false));
+        let admission = Arc::new(RwLock::new(Admission {
+            status: MarketStatus::Active,
+            params: MarketParams::default(),
+        }));
 
         let persister_clone = Arc::clone(&persister);
         let started_clone = Arc::clone(&started);
+        let ready_clone = Arc::clone(&ready);
+        let failed_clone = Arc::clone(&failed);
+        let settlement_failed_clone = Arc::clone(&settlement_failed);
+        let admission_clone = Arc::clone(&admission);
         let base_asset_clone = base_asset.clone();
         let market_id_clone = market_id.clone();
         let quote_asset_clone = quote_asset.clone();
         thread::spawn(move || {
-            let mut order_book = OrderBook::new(
-                persister_clone,
+            let loaded = OrderBook::new(
+                Arc::clone(&persister_clone),
                 base_asset_clone,
-                market_id_clone,
+                market_id_clone.clone(),
                 quote_asset_clone,
             );
+            let mut order_book = match loaded {
+                Ok(order_book) => order_book,
+                Err(e) => {
+                    error!(market_id = %market_id_clone, "Failed to load order book: {:?}", e);
+                    failed_clone.store(true, Ordering::SeqCst);
+                    return;
+                }
+            };
+            order_book.set_price_collar(config.price_collar);
+            order_book.set_market_price_max_age(
+                config.market_price_max_age_ms,
+                config.stale_price_policy,
+            );
+            order_book.set_recent_trades_capacity(config.recent_trades_capacity);
+            order_book.set_atomic_placement(config.atomic_placement);
+            order_book.set_async_settlement(config.async_settlement);
+            order_book.set_user_events(user_events);
+            ready_clone.store(true, Ordering::SeqCst);
             while let Ok(task) = task_receiver.recv() {
                 match started_clone.load(Ordering::SeqCst) {
-                    true => task(&mut order_book),
+                    true => {
+                        task(&mut order_book);
+                        order_book.publish_depth_changes();
+                        order_book.record_book_metrics();
+                        if let Some(failure) = order_book.settlement_failure() {
+                            error!(market_id = %market_id_clone, "Halting the market: {}", failure);
+                            settlement_failed_clone.store(true, Ordering::SeqCst);
+                            admission_clone
+                                .write()
+                                .unwrap_or_else(PoisonError::into_inner)
+                                .status = MarketStatus::HaltedMatching;
+                            if let Err(e) = persister_clone.update_market_status(
+                                &market_id_clone,
+                                MarketStatus::HaltedMatching,
+                            ) {
+                                error!(
+                                    market_id = %market_id_clone,
+                                    "Failed to persist the halt of the market: {:#}", e
+                                );
+                            }
+                            break;
+                        }
+                    }
                     false => break, // Stop processing if market is stopped
                 }
             }
@@ -77,22 +306,73 @@ impl<P: DatabaseProvider> Market<P> {
             persister,
             market_id,
             started,
+            ready,
+            failed,
+            settlement_failed,
             base_asset,
             quote_asset,
+            admission,
         })
     }
 
+    pub fn base_asset(&self) -> &str {
+        &self.base_asset
+    }
+
+    pub fn quote_asset(&self) -> &str {
+        &self.quote_asset
+    }
+
+    /// Replaces the parameters orders are checked against, from the next task on.
+    pub fn set_params(&self, params: MarketParams) {
+        let mut admission = self
+            .admission
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        admission.params = params;
+    }
+
+    /// Moves the market to `status` from the next task on, returning the status it had.
+    pub fn set_status(&self, status: MarketStatus) -> MarketStatus {
+        let mut admission = self
+            .admission
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        std::mem::replace(&mut admission.status, status)
+    }
+
+    /// Refuses a new order the market's status or parameters do not allow, as placing it
+    /// would right now.
+    pub fn check_order(&self, order: &TradeOrder) -> Result<()> {
+        read(&self.admission).check_order(order)
+    }
+
     pub fn get_market_id(&self) -> String {
         self.market_id.clone()
     }
 
+    pub fn is_started(&self) -> bool {
+        self.started.load(Ordering::SeqCst)
+    }
+
+    /// Tells whether the order book finished recovering its open orders
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::SeqCst)
+    }
+
+    /// Tells whether the order book failed to load, or to store its trades, in which case the
+    /// market takes no requests
+    pub fn has_failed(&self) -> bool {
+        self.failed.load(Ordering::SeqCst) || self.settlement_failed.load(Ordering::SeqCst)
+    }
+
     pub fn start_market(&self) -> Result<()> {
         if self.started.load(Ordering::SeqCst) {
             return Err(MarketError::MarketAlreadyStarted.into());
         }
 
         self.started.store(true, Ordering::SeqCst);
-        println!("Market {} started", self.market_id);
+        info!(market_id = %self.market_id, "Market started");
         Ok(())
     }
 
@@ -101,11 +381,17 @@ impl<P: DatabaseProvider> Market<P> {
             return Err(MarketError::MarketNotStarted.into());
         }
         self.started.store(false, Ordering::SeqCst);
-        println!("Market {} stopped", self.market_id);
+        info!(market_id = %self.market_id, "Market stopped");
         Ok(())
     }
 
     fn submit_task(&self, task: Task<P>) -> Result<()> {
+        if self.failed.load(Ordering::SeqCst) {
+            return Err(MarketError::LoadFailed(self.market_id.clone()).into());
+        }
+        if self.settlement_failed.load(Ordering::SeqCst) {
+            return Err(MarketError::SettlementFailed(self.market_id.clone()).into());
+        }
         if self.started.load(Ordering::SeqCst) {
             self.task_sender.send(task).map_err(|_| {
                 anyhow::anyhow!("Failed to send task").context(MarketError::TaskSendError)
@@ -118,15 +404,98 @@ impl<P: DatabaseProvider> Market<P> {
         }
     }
 
-    pub fn add_order(&self, order: TradeOrder) -> Result<Vec<MatchedTrade>> {
+    /// Places an order and reports its fills together with what rests in the book, so an
+    /// order that did not match still gets a receipt.
+    ///
+    /// With `idempotency`, an order its user already placed under the key is not placed again:
+    /// that order's receipt is returned. A retry queued behind its original finds it too.
+    pub fn add_order(
+        &self,
+        order: TradeOrder,
+        idempotency: Option<IdempotentPlacement>,
+    ) -> Result<OrderReceipt> {
         let (sender, receiver) = std::sync::mpsc::channel();
 
+        let market_id = self.get_market_id();
+        let admission = Arc::clone(&self.admission);
         self.submit_task(Box::new(move |order_book: &mut OrderBook<P>| {
-            let trades = order_book.add_order(order);
-            let _ = sender.send(trades);
+            let placed = idempotency
+                .as_ref()
+                .and_then(|key| key.placed(&order.user_id));
+            if let Some(receipt) = placed {
+                let _ = sender.send(Ok(receipt));
+                return;
+            }
+            let checked = read(&admission).check_order(&order);
+            if let Err(e) = checked {
+                order_book.reject_order(&order, &e);
+                let _ = sender.send(Err(e));
+                return;
+            }
+
+            let (order_id, user_id) = (order.id.clone(), order.user_id.clone());
+            let receipt = order_book.add_order(order).map(|trades| OrderReceipt {
+                resting: order_book.get_order_by_id(order_id.clone()).ok(),
+                order_id,
+                market_id,
+                trades,
+            });
+            if let (Some(key), Ok(receipt)) = (&idempotency, &receipt) {
+                key.remember(&user_id, receipt.clone());
+            }
+            let _ = sender.send(receipt);
         }))?;
 
-        receiver.recv()?
+        receiver
+            .recv()
+            .map_err(|_| MarketError::ResponseReceiveError)?
+    }
+
+    /// Places both legs of an OCO group in one order book task, so nothing can trade against
+    /// the first leg before the second one is linked to it.
+    pub fn add_oco_order(&self, first: TradeOrder, second: TradeOrder) -> Result<OcoReceipt> {
+        let (sender, receiver) = std::sync::mpsc::channel();
+
+        let market_id = self.get_market_id();
+        let admission = Arc::clone(&self.admission);
+        self.submit_task(Box::new(move |order_book: &mut OrderBook<P>| {
+            let checked = {
+                let admission = read(&admission);
+                admission
+                    .check_order(&first)
+                    .and_then(|()| admission.check_order(&second))
+            };
+            if let Err(e) = checked {
+                order_book.reject_order(&first, &e);
+                order_book.reject_order(&second, &e);
+                let _ = sender.send(Err(e));
+                return;
+            }
+
+            let (first_id, second_id) = (first.id.clone(), second.id.clone());
+            let receipt = order_book.add_oco_order(first, second).map(
+                |(group, first_trades, second_trades)| OcoReceipt {
+                    oco_group_id: group.id,
+                    first: OrderReceipt {
+                        resting: order_book.get_order_by_id(first_id.clone()).ok(),
+                        order_id: first_id,
+                        market_id: market_id.clone(),
+                        trades: first_trades,
+                    },
+                    second: OrderReceipt {
+                        resting: order_book.get_order_by_id(second_id.clone()).ok(),
+                        order_id: second_id,
+                        market_id,
+                        trades: second_trades,
+                    },
+                },
+            );
+            let _ = sender.send(receipt);
+        }))?;
+
+        receiver
+            .recv()
+            .map_err(|_| MarketError::ResponseReceiveError)?
     }
 
     pub fn get_order_by_id(&self, order_id: String) -> Result<TradeOrder> {
@@ -137,28 +506,175 @@ impl<P: DatabaseProvider> Market<P> {
             let _ = sender.send(result);
         }));
 
-        receiver.recv()?
+        receiver
+            .recv()
+            .map_err(|_| MarketError::ResponseReceiveError)?
+    }
+
+    /// Cancels an order at its user's request, refused while the market is halted or closed.
+    pub fn cancel_order(&self, order_id: String, reason: CancelReason) -> Result<bool> {
+        let (sender, receiver) = std::sync::mpsc::channel();
+
+        let admission = Arc::clone(&self.admission);
+        self.submit_task(Box::new(move |order_book: &mut OrderBook<P>| {
+            let checked = read(&admission).check_cancel();
+            let canceled = checked.and_then(|()| order_book.cancel_order(order_id, reason));
+            let _ = sender.send(canceled);
+        }))?;
+
+        receiver
+            .recv()
+            .map_err(|_| MarketError::ResponseReceiveError)?
     }
 
-    pub fn cancel_order(&self, order_id: String) -> Result<bool> {
+    /// Cancels an order on the exchange's behalf, as when its user is banned or disconnects,
+    /// whatever the market's status.
+    pub fn force_cancel_order(&self, order_id: String, reason: CancelReason) -> Result<bool> {
         let (sender, receiver) = std::sync::mpsc::channel();
 
         self.submit_task(Box::new(move |order_book: &mut OrderBook<P>| {
-            let canceled = order_book.cancel_order(order_id);
+            let canceled = order_book.cancel_order(order_id, reason);
             let _ = sender.send(canceled);
         }))?;
 
-        receiver.recv()?
+        receiver
+            .recv()
+            .map_err(|_| MarketError::ResponseReceiveError)?
+    }
+
+    /// Amends a resting order and reports it like a new placement: the fills the amendment
+    /// caused, if it crossed the book, and what rests afterwards.
+    pub fn amend_order(
+        &self,
+        order_id: String,
+        price: Option<BigDecimal>,
+        remained_base: Option<BigDecimal>,
+    ) -> Result<OrderReceipt> {
+        let (sender, receiver) = std::sync::mpsc::channel();
+
+        let market_id = self.get_market_id();
+        let admission = Arc::clone(&self.admission);
+        self.submit_task(Box::new(move |order_book: &mut OrderBook<P>| {
+            let checked = order_book
+                .amendment(&order_id, price.clone(), remained_base.clone())
+                .and_then(|amended| read(&admission).check_amend(&amended));
+            let receipt = checked
+                .and_then(|()| order_book.amend_order(order_id.clone(), price, remained_base))
+                .map(|trades| OrderReceipt {
+                    resting: order_book.get_order_by_id(order_id.clone()).ok(),
+                    order_id,
+                    market_id,
+                    trades,
+                });
+            let _ = sender.send(receipt);
+        }))?;
+
+        receiver
+            .recv()
+            .map_err(|_| MarketError::ResponseReceiveError)?
+    }
+
+    /// Cancels the orders of this market that expired by `now`, returning their ids.
+    pub fn expire_orders(&self, now: i64) -> Result<Vec<String>> {
+        let (sender, receiver) = std::sync::mpsc::channel();
+
+        self.submit_task(Box::new(move |order_book: &mut OrderBook<P>| {
+            let _ = sender.send(order_book.expire_orders(now));
+        }))?;
+
+        receiver
+            .recv()
+            .map_err(|_| MarketError::ResponseReceiveError)?
+    }
+
+    /// Stores a snapshot of the order book, see [`OrderBook::write_snapshot`].
+    pub fn write_snapshot(&self) -> Result<i64> {
+        let (sender, receiver) = std::sync::mpsc::channel();
+
+        self.submit_task(Box::new(move |order_book: &mut OrderBook<P>| {
+            let _ = sender.send(order_book.write_snapshot());
+        }))?;
+
+        receiver
+            .recv()
+            .map_err(|_| MarketError::ResponseReceiveError)?
+    }
+
+    /// Returns up to `limit` of the trades kept in memory, newest first.
+    pub fn recent_trades(&self, limit: usize) -> Result<Vec<MatchedTrade>> {
+        let (sender, receiver) = std::sync::mpsc::channel();
+
+        self.submit_task(Box::new(move |order_book: &mut OrderBook<P>| {
+            let _ = sender.send(order_book.recent_trades(limit));
+        }))?;
+
+        receiver
+            .recv()
+            .map_err(|_| MarketError::ResponseReceiveError.into())
+    }
+
+    pub fn depth(&self, levels: usize, price_step: Option<BigDecimal>) -> Result<OrderBookDepth> {
+        let (sender, receiver) = std::sync::mpsc::channel();
+
+        self.submit_task(Box::new(move |order_book: &mut OrderBook<P>| {
+            let _ = sender.send(order_book.depth(levels, price_step.as_ref()));
+        }))?;
+
+        receiver
+            .recv()
+            .map_err(|_| MarketError::ResponseReceiveError.into())
+    }
+
+    pub fn subscribe_depth(&self) -> Result<(DepthSnapshot, u64, broadcast::Receiver<DepthDelta>)> {
+        let (sender, receiver) = std::sync::mpsc::channel();
+
+        self.submit_task(Box::new(move |order_book: &mut OrderBook<P>| {
+            let _ = sender.send(order_book.subscribe_depth());
+        }))?;
+
+        receiver
+            .recv()
+            .map_err(|_| MarketError::ResponseReceiveError.into())
+    }
+
+    pub fn subscribe_trades(
+        &self,
+        since_sequence: Option<u64>,
+    ) -> Result<(Vec<SequencedTrade>, broadcast::Receiver<SequencedTrade>)> {
+        let (sender, receiver) = std::sync::mpsc::channel();
+
+        self.submit_task(Box::new(move |order_book: &mut OrderBook<P>| {
+            let _ = sender.send(order_book.subscribe_trades(since_sequence));
+        }))?;
+
+        receiver
+            .recv()
+            .map_err(|_| MarketError::ResponseReceiveError.into())
+    }
+
+    pub fn set_recent_trades_capacity(&self, capacity: usize) -> Result<()> {
+        let (sender, receiver) = std::sync::mpsc::channel();
+
+        self.submit_task(Box::new(move |order_book: &mut OrderBook<P>| {
+            order_book.set_recent_trades_capacity(capacity);
+            let _ = sender.send(());
+        }))?;
+
+        receiver
+            .recv()
+            .map_err(|_| MarketError::ResponseReceiveError.into())
     }
 
-    pub fn cancel_all_orders(&self) -> Result<bool> {
+    pub fn cancel_all_orders(&self, reason: CancelReason) -> Result<bool> {
         let (sender, receiver) = std::sync::mpsc::channel();
 
         self.submit_task(Box::new(move |order_book: &mut OrderBook<P>| {
-            let canceled = order_book.cancel_all_orders();
+            let canceled = order_book.cancel_all_orders(reason);
             let _ = sender.send(canceled);
         }))?;
 
-        receiver.recv()?
+        receiver
+            .recv()
+            .map_err(|_| MarketError::ResponseReceiveError)?
     }
 }
@@ -1,80 +1,231 @@
-use super::market::Market;
-use crate::models::matched_trade::MatchedTrade;
-use crate::models::trade_order::TradeOrder;
-use anyhow::{anyhow, Context, Result};
+use super::idempotency::{IdempotencyCache, IdempotentPlacement};
+use super::market::{Market, MarketConfig, MarketError, MarketParams};
+use super::session::{Session, SessionRegistry};
+use crate::models::matched_trade::{MatchedTrade, SequencedTrade};
+use crate::models::order_receipt::{OcoReceipt, OrderReceipt};
+use crate::models::trade_order::{OrderSide, TradeOrder};
+use crate::models::user_event::UserEvent;
+use crate::order_book::depth_diff::{DepthDelta, DepthSnapshot, OrderBookDepth};
+use crate::order_book::user_events::{store_rejection, USER_EVENTS_CAPACITY};
+use crate::validation::validate_sufficient_balance;
+use anyhow::{anyhow, bail, Context, Result};
 use bigdecimal::BigDecimal;
+use common::error::BitradeError;
 use common::utils::get_utc_now_millis;
-use database::models::models::{MarketStatus, NewMarket};
+use database::models::models::{
+    CancelReason, Market as MarketRow, MarketStatus, MarketUpdate, NewMarket, NewOrderAudit, Order,
+    OrderSide as DbOrderSide, OrderStatus, UserRestriction, UserStatus,
+};
 use database::provider::DatabaseProvider;
-use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
-use std::sync::{Arc, Mutex};
-use std::thread::{self, JoinHandle};
-use tonic::Status;
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
+use tokio::sync::broadcast;
+use tracing::{error, info, warn};
 
+type MarketMap<P> = HashMap<String, Arc<Market<P>>>;
+
+/// Best bid and ask of a market, `None` for an empty side
+pub type Quote = (Option<BigDecimal>, Option<BigDecimal>);
+
+/// Markets a [`MarketManager::reload_markets`] loaded, read again and unloaded
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MarketReload {
+    pub added: usize,
+    pub refreshed: usize,
+    pub removed: usize,
+}
+
+/// Engine-wide counters for status dashboards
+#[derive(Debug, Clone, PartialEq)]
+pub struct EngineStats {
+    pub active_markets: usize,
+    pub open_orders: i64,
+    pub bid_volume: BigDecimal,
+    pub ask_volume: BigDecimal,
+}
+
+/// Owns the running markets of the engine.
+///
+/// Each market is an actor: its order book runs on a thread of its own and takes requests
+/// one at a time from its channel, so markets never wait on each other. The map sits behind a
+/// `std::sync::RwLock` taken only to look a market up; nothing holds it across a round trip to
+/// a book. Calls wait for their book to answer and read the database, so async callers make
+/// them through [`run_blocking`].
 #[derive(Debug)]
 pub struct MarketManager<P>
 where
     P: DatabaseProvider + 'static,
 {
-    markets: Arc<Mutex<HashMap<String, Arc<Mutex<Market<P>>>>>>,
-    market_handles: Arc<Mutex<Vec<JoinHandle<()>>>>,
+    markets: Arc<RwLock<MarketMap<P>>>,
     persister: Arc<P>,
+    /// Settings every market starts with
+    market_config: MarketConfig,
+    /// Order events of every market, see [`Self::subscribe_user_events`]
+    user_events: broadcast::Sender<UserEvent>,
+    /// Orders placed with an idempotency key, see [`Self::add_order_idempotent`]
+    idempotency: Arc<Mutex<IdempotencyCache>>,
+    /// Connections of users, see [`Self::open_session`]
+    sessions: Arc<SessionRegistry>,
+    /// Whether canceling an already canceled order succeeds, see [`Self::cancel_order`]
+    idempotent_cancel: bool,
 }
 
 impl<P: DatabaseProvider> MarketManager<P> {
     pub fn new(persister: Arc<P>) -> Self {
+        Self::with_config(persister, MarketConfig::default())
+    }
+
+    /// Same as [`MarketManager::new`], with every market set up from `market_config`.
+    pub fn with_config(persister: Arc<P>, market_config: MarketConfig) -> Self {
         let manager = MarketManager {
-            markets: Arc::new(Mutex::new(HashMap::new())),
-            market_handles: Arc::new(Mutex::new(Vec::new())),
+            markets: Arc::new(RwLock::new(HashMap::new())),
             persister: persister.clone(),
+            market_config,
+            user_events: broadcast::channel(USER_EVENTS_CAPACITY).0,
+            idempotency: Arc::new(Mutex::new(IdempotencyCache::default())),
+            sessions: Arc::new(SessionRegistry::default()),
+            idempotent_cancel: false,
         };
 
-        manager.load_markets_from_db();
+        if let Err(e) = manager.reload_markets() {
+            error!("Failed to load markets from database: {:?}", e);
+        }
 
-        println!(
-            "market_manager : Loaded {} markets from database",
-            manager.markets.lock().unwrap().len()
+        info!(
+            "Loaded {} markets from database",
+            manager.markets.read().unwrap().len()
         );
         manager
     }
 
-    fn load_markets_from_db(&self) {
-        // Load existing markets from database
-        if let Ok(db_markets) = self.persister.list_markets() {
-            for db_market in db_markets {
-                println!(
-                    "Loading market: id={}, base={}, quote={}",
-                    db_market.id, db_market.base_asset, db_market.quote_asset
-                );
+    /// Sets how long orders are remembered under their idempotency key.
+    pub fn with_idempotency_window(mut self, window_ms: i64) -> Self {
+        self.idempotency = Arc::new(Mutex::new(IdempotencyCache::new(window_ms)));
+        self
+    }
 
-                let market = Arc::new(Mutex::new(
-                    Market::new(
-                        self.persister.clone(),
-                        db_market.id.clone(),
-                        db_market.base_asset,
-                        db_market.quote_asset,
-                    )
-                    .expect("Failed to create market"),
-                ));
-
-                if let Ok(mut markets) = self.markets.lock() {
-                    markets.insert(db_market.id, market);
+    /// Makes canceling an order that is already canceled succeed instead of failing.
+    pub fn with_idempotent_cancel(mut self, enabled: bool) -> Self {
+        self.idempotent_cancel = enabled;
+        self
+    }
+
+    /// Loads the markets of the database this engine has not loaded yet, each recovering its
+    /// open orders, reads the parameters and status of the loaded ones again, and unloads the
+    /// ones no longer in the database, their order books dropped with them.
+    ///
+    /// A loaded market the database now has closed cancels its resting orders, as
+    /// [`Self::update_market_status`] would. It stays loaded, so it can be reopened. A market
+    /// whose order book failed to load, or to store its trades, is loaded again, started if it
+    /// was.
+    pub fn reload_markets(&self) -> Result<MarketReload> {
+        // Taken before listing, so a market created meanwhile is not mistaken for a removed one
+        let loaded = self.market_ids()?;
+        let db_markets = self
+            .persister
+            .list_markets()
+            .context("Failed to list markets")?;
+
+        let mut reload = MarketReload::default();
+        let mut restart = HashSet::new();
+        for market_id in loaded {
+            if db_markets.iter().any(|db_market| db_market.id == market_id) {
+                let failed = self.get_market(&market_id).ok();
+                let Some(failed) = failed.filter(|market| market.has_failed()) else {
+                    continue;
+                };
+                self.markets
+                    .write()
+                    .map_err(|e| anyhow!("Failed to acquire lock on markets: {}", e))?
+                    .remove(&market_id);
+                warn!(market_id = %market_id, "Market's order book failed, loading it again");
+                if failed.is_started() {
+                    restart.insert(market_id);
                 }
+                continue;
+            }
+            let market = self
+                .markets
+                .write()
+                .map_err(|e| anyhow!("Failed to acquire lock on markets: {}", e))?
+                .remove(&market_id);
+            // Its book thread ends once the last request holding the market lets go of it
+            if let Some(market) = market {
+                let _ = market.stop_market();
+                warn!(market_id = %market_id, "Market is no longer in the database, unloaded it");
+                reload.removed += 1;
+            }
+        }
+
+        for db_market in db_markets {
+            let status = MarketStatus::from_str(&db_market.status).unwrap_or_else(|e| {
+                // Safer to take nothing than to trade on a market in an unknown phase
+                warn!(market_id = %db_market.id, "{}, market is loaded closed", e);
+                MarketStatus::Closed
+            });
+            if let Ok(market) = self.get_market(&db_market.id) {
+                market.set_params(MarketParams::from(&db_market));
+                let previous = market.set_status(status);
+                if status == MarketStatus::Closed
+                    && previous != MarketStatus::Closed
+                    && market.is_started()
+                {
+                    market.cancel_all_orders(CancelReason::MarketClosed)?;
+                }
+                reload.refreshed += 1;
+                continue;
+            }
+
+            info!(
+                market_id = %db_market.id,
+                base_asset = %db_market.base_asset,
+                quote_asset = %db_market.quote_asset,
+                "Loading market"
+            );
+            let market = Market::new(
+                self.persister.clone(),
+                db_market.id.clone(),
+                db_market.base_asset.clone(),
+                db_market.quote_asset.clone(),
+                self.market_config.clone(),
+                self.user_events.clone(),
+            )?;
+            market.set_params(MarketParams::from(&db_market));
+            market.set_status(status);
+            if restart.contains(&db_market.id) {
+                market.start_market()?;
+            }
+            let mut markets = self
+                .markets
+                .write()
+                .map_err(|e| anyhow!("Failed to acquire lock on markets: {}", e))?;
+            // Unless `create_market` got there first
+            if let Entry::Vacant(entry) = markets.entry(db_market.id) {
+                entry.insert(Arc::new(market));
+                reload.added += 1;
             }
         }
+        Ok(reload)
     }
 
-    fn get_market(&self, market_id: &str) -> Result<Arc<Mutex<Market<P>>>> {
-        let markets = self
-            .markets
-            .lock()
-            .map_err(|e| anyhow!("Failed to acquire lock on markets: {}", e))?;
+    fn read_markets(&self) -> Result<RwLockReadGuard<'_, MarketMap<P>>> {
+        self.markets
+            .read()
+            .map_err(|e| anyhow!("Failed to acquire lock on markets: {}", e))
+    }
 
-        markets
+    /// Every market, copied out of the map so no round trip to a book holds its lock
+    fn all_markets(&self) -> Result<Vec<Arc<Market<P>>>> {
+        Ok(self.read_markets()?.values().cloned().collect())
+    }
+
+    fn get_market(&self, market_id: &str) -> Result<Arc<Market<P>>> {
+        self.read_markets()?
             .get(market_id)
             .cloned()
-            .context(format!("Market {} not found", market_id))
+            .ok_or_else(|| BitradeError::MarketNotFound(market_id.to_string()).into())
     }
 
     pub fn create_market(
@@ -87,162 +238,674 @@ impl<P: DatabaseProvider> MarketManager<P> {
     ) -> Result<()> {
         let mut markets = self
             .markets
-            .lock()
+            .write()
             .map_err(|e| anyhow!("Failed to acquire lock on markets: {}", e))?;
 
         if !markets.contains_key(market_id.as_str()) {
-            let market = Arc::new(Mutex::new(Market::new(
-                self.persister.clone(),
-                market_id.to_string(),
-                base_asset.clone(),
-                quote_asset.clone(),
-            )?));
-            markets.insert(market_id.to_string(), market);
-            self.persister
+            // Persist first: if another instance created the same market, the stored row is
+            // returned and the in-memory market is built from it
+            let db_market = self
+                .persister
                 .create_market(NewMarket {
                     id: market_id.clone(),
                     base_asset: base_asset.clone(),
                     quote_asset: quote_asset.clone(),
-                    default_maker_fee: BigDecimal::from_str(&default_maker_fee)
-                        .context("Failed to parse amount as Decimal")
-                        .map_err(|e| Status::invalid_argument(e.to_string()))?,
-                    default_taker_fee: BigDecimal::from_str(&default_taker_fee)
-                        .context("Failed to parse amount as Decimal")
-                        .map_err(|e| Status::invalid_argument(e.to_string()))?,
+                    default_maker_fee: parse_fee(&default_maker_fee, "default_maker_fee")?,
+                    default_taker_fee: parse_fee(&default_taker_fee, "default_taker_fee")?,
                     create_time: get_utc_now_millis(),
                     update_time: get_utc_now_millis(),
                     amount_precision: 8,
-                    min_base_amount: BigDecimal::from_str("0.00000000")
-                        .context("Failed to parse amount as Decimal")
-                        .map_err(|e| Status::invalid_argument(e.to_string()))?,
-                    min_quote_amount: BigDecimal::from_str("0.00000000")
-                        .context("Failed to parse amount as Decimal")
-                        .map_err(|e| Status::invalid_argument(e.to_string()))?,
+                    min_base_amount: BigDecimal::from(0),
+                    min_quote_amount: BigDecimal::from(0),
                     price_precision: 8,
                     status: MarketStatus::Active.as_str().to_string(),
                 })
-                .context("Failed to persist market")
-                .map_err(|e| Status::internal(e.to_string()))?;
+                .context("Failed to persist market")?;
+
+            let market = Market::new(
+                self.persister.clone(),
+                db_market.id.clone(),
+                db_market.base_asset.clone(),
+                db_market.quote_asset.clone(),
+                self.market_config.clone(),
+                self.user_events.clone(),
+            )?;
+            market.set_params(MarketParams::from(&db_market));
+            markets.insert(db_market.id, Arc::new(market));
         }
-        println!("market_manager : Created market {}", market_id);
+        info!(market_id = %market_id, "Created market");
         Ok(())
     }
 
     pub fn start_market(&self, market_id: &str) -> Result<()> {
         let market = self.get_market(market_id)?;
 
-        // Spawn a dedicated thread for this market
-        let market_clone = Arc::clone(&market);
-        let handle = thread::spawn(move || {
-            let market = market_clone.lock().expect("Failed to lock market");
-            let _ = market.start_market();
-        });
-
-        // Store the thread handle
-        let mut handles = self
-            .market_handles
-            .lock()
-            .map_err(|e| anyhow!("Failed to acquire lock on market handles: {}", e))?;
-        handles.push(handle);
-
-        println!("market_manager : Started market {}", market_id);
+        // The order book already runs on its own thread, starting only flips the market state
+        market.start_market()?;
+
+        info!(market_id = %market_id, "Started market");
         Ok(())
     }
 
     pub fn stop_market(&self, market_id: &str) -> Result<()> {
         let market = self.get_market(market_id)?;
 
-        let market_guard = market
-            .lock()
-            .map_err(|e| anyhow!("Failed to lock market: {}", e))?;
+        let _ = market.stop_market();
+        info!(market_id = %market_id, "Stopped market");
+        Ok(())
+    }
+
+    /// Places an order, refused with [`MarketError::Recovering`] until every market has
+    /// recovered its open orders, so new orders cannot race the recovered ones.
+    pub fn add_order(&self, order: TradeOrder) -> Result<OrderReceipt> {
+        self.place_order(order, None)
+    }
+
+    /// Same as [`Self::add_order`], except that an order the user placed under the same
+    /// `idempotency_key` within the idempotency window is not placed again: its receipt is
+    /// returned instead.
+    pub fn add_order_idempotent(
+        &self,
+        order: TradeOrder,
+        idempotency_key: &str,
+    ) -> Result<OrderReceipt> {
+        self.place_order(order, Some(idempotency_key))
+    }
 
-        let _ = market_guard.stop_market();
-        println!("market_manager : Stopped market {}", market_id);
+    fn place_order(
+        &self,
+        order: TradeOrder,
+        idempotency_key: Option<&str>,
+    ) -> Result<OrderReceipt> {
+        let idempotency =
+            idempotency_key.map(|key| IdempotentPlacement::new(self.idempotency.clone(), key));
+        // A retry gets the order it placed back ahead of any check, which the funds that order
+        // locked or a market halted since would otherwise fail
+        if let Some(receipt) = idempotency
+            .as_ref()
+            .and_then(|idempotency| idempotency.placed(&order.user_id))
+        {
+            return Ok(receipt);
+        }
+
+        let market = self
+            .check_user_status(&order.user_id)
+            .and_then(|()| self.market_accepting_orders(&order.market_id))
+            .and_then(|market| {
+                self.check_sufficient_balance(&market, &[&order])
+                    .map(|()| market)
+            })
+            .inspect_err(|e| self.reject_orders(&[&order], e))?;
+
+        market.add_order(order, idempotency)
+    }
+
+    /// Places the two legs of an OCO group, refused like [`Self::add_order`] while markets
+    /// are recovering.
+    pub fn add_oco_order(&self, first: TradeOrder, second: TradeOrder) -> Result<OcoReceipt> {
+        let market = self
+            .check_user_status(&first.user_id)
+            .and_then(|()| self.market_accepting_orders(&first.market_id))
+            .and_then(|market| {
+                self.check_sufficient_balance(&market, &[&first, &second])
+                    .map(|()| market)
+            })
+            .inspect_err(|e| self.reject_orders(&[&first, &second], e))?;
+
+        market.add_oco_order(first, second)
+    }
+
+    /// The market new orders go to, unless markets are recovering or it isn't running.
+    fn market_accepting_orders(&self, market_id: &str) -> Result<Arc<Market<P>>> {
+        if self.is_recovering()? {
+            return Err(MarketError::Recovering.into());
+        }
+        let market = self.get_market(market_id)?;
+        if !market.is_started() {
+            return Err(MarketError::MarketNotStarted.into());
+        }
+        Ok(market)
+    }
+
+    /// Refuses orders the available balance of their user cannot lock funds for, before
+    /// anything of them is journaled or stored. The legs of an OCO order are counted together.
+    fn check_sufficient_balance(&self, market: &Market<P>, orders: &[&TradeOrder]) -> Result<()> {
+        for (side, asset) in [
+            (OrderSide::Buy, market.quote_asset()),
+            (OrderSide::Sell, market.base_asset()),
+        ] {
+            let locking: Vec<&TradeOrder> = orders
+                .iter()
+                .copied()
+                .filter(|order| order.side == side)
+                .collect();
+            let Some(order) = locking.first() else {
+                continue;
+            };
+            let wallet = self
+                .persister
+                .get_wallet(&order.user_id, asset)
+                .context("Failed to fetch wallet")?;
+            validate_sufficient_balance(&locking, asset, wallet.as_ref())?;
+        }
         Ok(())
     }
 
-    pub fn add_order(&self, order: TradeOrder) -> Result<(Vec<MatchedTrade>, String)> {
-        let market = self.get_market(&order.market_id)?;
+    /// Refuses the orders of a user an operator restricted to cancels or banned.
+    fn check_user_status(&self, user_id: &str) -> Result<()> {
+        let Some(restriction) = self.user_restriction(user_id)? else {
+            return Ok(());
+        };
+        let status = UserStatus::from_str(&restriction.status).map_err(|e| anyhow!(e))?;
+        if !status.accepts_orders() {
+            return Err(MarketError::UserRestricted {
+                user_id: user_id.to_string(),
+                status: status.as_str(),
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    /// The restriction of `user_id`, `None` while they are active
+    pub fn user_restriction(&self, user_id: &str) -> Result<Option<UserRestriction>> {
+        self.persister
+            .get_user_restriction(user_id)
+            .context("Failed to fetch user restriction")
+    }
 
-        let market_guard = market
-            .lock()
-            .map_err(|e| anyhow!("Failed to lock market: {}", e))?;
+    /// Moves `user_id` to `status`. Banning also cancels the orders they have open in every
+    /// loaded market. Returns the restriction stored, `None` once the user is active again,
+    /// and how many orders were canceled.
+    pub fn set_user_status(
+        &self,
+        user_id: &str,
+        status: UserStatus,
+        reason: &str,
+    ) -> Result<(Option<UserRestriction>, usize)> {
+        if status == UserStatus::Active {
+            if self
+                .persister
+                .delete_user_restriction(user_id)
+                .context("Failed to delete user restriction")?
+            {
+                info!(user_id = %user_id, "Lifted user restriction");
+            }
+            return Ok((None, 0));
+        }
+
+        let restriction = self
+            .persister
+            .set_user_restriction(user_id, status, reason)
+            .context("Failed to store user restriction")?;
+        info!(user_id = %user_id, status = status.as_str(), reason = %reason, "Restricted user");
+        if status != UserStatus::Banned {
+            return Ok((Some(restriction), 0));
+        }
 
-        let trade = market_guard.add_order(order)?;
-        Ok((trade, market_guard.get_market_id()))
+        let canceled = cancel_user_orders(
+            &self.markets,
+            self.persister.as_ref(),
+            user_id,
+            CancelReason::UserBanned,
+        )?;
+        Ok((Some(restriction), canceled))
     }
 
+    /// Opens a session of `user_id`, held for as long as their client stays connected.
+    /// Once they turned cancel-on-disconnect on, closing their last session cancels the
+    /// orders they have open in every loaded market.
+    pub fn open_session(&self, user_id: &str) -> Session {
+        let markets = self.markets.clone();
+        let persister = self.persister.clone();
+        self.sessions.open(
+            user_id,
+            Box::new(move |user_id| {
+                cancel_user_orders(
+                    &markets,
+                    persister.as_ref(),
+                    user_id,
+                    CancelReason::Disconnected,
+                )
+            }),
+        )
+    }
+
+    /// Turns cancel-on-disconnect of `user_id` on or off, returning the sessions they have
+    /// open. Refused while they have none.
+    pub fn set_cancel_on_disconnect(&self, user_id: &str, enabled: bool) -> Result<usize> {
+        let sessions = self
+            .sessions
+            .set_cancel_on_disconnect(user_id, enabled)
+            .ok_or_else(|| MarketError::NoSession(user_id.to_string()))?;
+        info!(user_id = %user_id, enabled, sessions, "Set cancel-on-disconnect");
+        Ok(sessions)
+    }
+
+    /// Sessions `user_id` has open and whether losing them all cancels their orders
+    pub fn user_sessions(&self, user_id: &str) -> (usize, bool) {
+        self.sessions.sessions(user_id)
+    }
+
+    pub fn list_user_restrictions(&self) -> Result<Vec<UserRestriction>> {
+        self.persister
+            .list_user_restrictions()
+            .context("Failed to list user restrictions")
+    }
+
+    /// Stores and tells the users of orders refused before reaching a book; the book handles
+    /// the rest.
+    fn reject_orders(&self, orders: &[&TradeOrder], error: &anyhow::Error) {
+        for order in orders {
+            store_rejection(self.persister.as_ref(), order, error);
+            self.publish_user_event(UserEvent::rejected(order, error.to_string()));
+        }
+    }
+
+    /// Sends an event to `SubscribeUserEvents` streams, for what happens outside the books.
+    pub fn publish_user_event(&self, event: UserEvent) {
+        // Nobody listening is the common case
+        let _ = self.user_events.send(event);
+    }
+
+    /// Receives the order events of every user in every market, from now on.
+    pub fn subscribe_user_events(&self) -> broadcast::Receiver<UserEvent> {
+        self.user_events.subscribe()
+    }
+
+    /// Runs every check an order would go through, without creating the order, locking funds
+    /// or matching.
+    pub fn test_order(&self, order: &TradeOrder) -> Result<()> {
+        self.check_user_status(&order.user_id)?;
+        // The market has to be running in this engine, not only present in the database
+        let market = self.get_market(&order.market_id)?;
+        if !market.is_started() {
+            return Err(MarketError::MarketNotStarted.into());
+        }
+        market.check_order(order)?;
+        self.check_sufficient_balance(&market, &[order])
+    }
+
+    /// Cancels an order at its user's request. With idempotent cancel on, a retry for an order
+    /// of the market that is canceled already succeeds without reaching the book, so the user
+    /// is not told about the cancel twice.
     pub fn cancel_order(&self, market_id: &str, order_id: String) -> Result<bool> {
         let market = self.get_market(market_id)?;
+        if self.idempotent_cancel {
+            let order = self
+                .persister
+                .get_order(&order_id)
+                .context("Failed to look up the order to cancel")?;
+            if order.is_some_and(|order| {
+                order.market_id == market_id
+                    && order.get_status().ok() == Some(OrderStatus::Canceled)
+            }) {
+                return Ok(true);
+            }
+        }
+        market.cancel_order(order_id, CancelReason::UserCanceled)
+    }
 
-        let market_guard = market
-            .lock()
-            .map_err(|e| anyhow!("Failed to lock market: {}", e))?;
+    /// Looks up the order a user placed under a client order id, in any market.
+    pub fn get_order_by_client_id(
+        &self,
+        user_id: &str,
+        client_order_id: &str,
+    ) -> Result<Option<Order>> {
+        self.persister
+            .get_order_by_client_id(user_id, client_order_id)
+    }
 
-        market_guard.cancel_order(order_id)
+    /// Amends a resting order, refused like [`Self::add_order`] while markets are recovering.
+    pub fn amend_order(
+        &self,
+        market_id: &str,
+        order_id: String,
+        price: Option<BigDecimal>,
+        remained_base: Option<BigDecimal>,
+    ) -> Result<OrderReceipt> {
+        if self.is_recovering()? {
+            return Err(MarketError::Recovering.into());
+        }
+        let market = self.get_market(market_id)?;
+        // An unknown order is left for the book to refuse
+        if let Ok(order) = market.get_order_by_id(order_id.clone()) {
+            self.check_user_status(&order.user_id)?;
+        }
+        market.amend_order(order_id, price, remained_base)
+    }
+
+    /// The order stored under `order_id`, whether or not it still rests in a book.
+    pub fn get_stored_order(&self, order_id: &str) -> Result<Option<Order>> {
+        match self.persister.get_order(order_id) {
+            Err(e) if matches!(e.downcast_ref(), Some(BitradeError::OrderNotFound(_))) => Ok(None),
+            order => order.context("Failed to look up the order"),
+        }
     }
 
     pub fn get_order_by_id(&self, market_id: &str, order_id: String) -> Result<TradeOrder> {
         let market = self.get_market(market_id)?;
 
-        let market_guard = market
-            .lock()
-            .map_err(|e| anyhow!("Failed to lock market: {}", e))?;
+        market.get_order_by_id(order_id)
+    }
+
+    pub fn get_recent_trades(&self, market_id: &str, limit: usize) -> Result<Vec<MatchedTrade>> {
+        let market = self.get_market(market_id)?;
 
-        market_guard.get_order_by_id(order_id)
+        market.recent_trades(limit)
     }
 
-    pub fn cancel_all_orders(&self, market_id: &str) -> Result<bool> {
+    pub fn get_order_book_depth(
+        &self,
+        market_id: &str,
+        levels: usize,
+        price_step: Option<BigDecimal>,
+    ) -> Result<OrderBookDepth> {
         let market = self.get_market(market_id)?;
 
-        let market_guard = market
-            .lock()
-            .map_err(|e| anyhow!("Failed to lock market: {}", e))?;
+        market.depth(levels, price_step)
+    }
 
-        market_guard.cancel_all_orders()
+    /// Depth of a market together with a receiver of its changes from then on.
+    pub fn subscribe_order_book(
+        &self,
+        market_id: &str,
+    ) -> Result<(DepthSnapshot, u64, broadcast::Receiver<DepthDelta>)> {
+        let market = self.get_market(market_id)?;
+
+        market.subscribe_depth()
     }
 
-    pub fn cancel_all_orders_global(&self) -> Result<()> {
-        let markets = self
-            .markets
-            .lock()
-            .map_err(|e| anyhow!("Failed to acquire lock on markets: {}", e))?;
+    /// Trades of a market after `since_sequence` still kept in memory, together with a
+    /// receiver of the trades executed from then on.
+    pub fn subscribe_trades(
+        &self,
+        market_id: &str,
+        since_sequence: Option<u64>,
+    ) -> Result<(Vec<SequencedTrade>, broadcast::Receiver<SequencedTrade>)> {
+        let market = self.get_market(market_id)?;
 
-        for market in markets.values() {
-            let market_guard = market
-                .lock()
-                .map_err(|e| anyhow!("Failed to lock market: {}", e))?;
-            market_guard.cancel_all_orders()?;
+        market.subscribe_trades(since_sequence)
+    }
+
+    /// Changes how many recent trades one market keeps, dropping the oldest ones if it shrinks.
+    pub fn set_recent_trades_capacity(&self, market_id: &str, capacity: usize) -> Result<()> {
+        let market = self.get_market(market_id)?;
+
+        market.set_recent_trades_capacity(capacity)
+    }
+
+    /// Moves a market to `status`, returning the status it had. Closing a running market
+    /// cancels its resting orders, since nothing can match or cancel them afterwards.
+    pub fn update_market_status(
+        &self,
+        market_id: &str,
+        status: MarketStatus,
+    ) -> Result<MarketStatus> {
+        let market = self.get_market(market_id)?;
+
+        self.persister
+            .update_market_status(market_id, status)
+            .context("Failed to persist market status")?;
+        let previous = market.set_status(status);
+
+        if status == MarketStatus::Closed && market.is_started() {
+            market.cancel_all_orders(CancelReason::MarketClosed)?;
+        }
+        info!(
+            market_id = %market_id,
+            "Market moved from {} to {}",
+            previous.as_str(),
+            status.as_str()
+        );
+        Ok(previous)
+    }
+
+    /// Persists new trading parameters and refreshes the ones the running market checks
+    /// orders against.
+    pub fn update_market(&self, market_id: &str, changes: MarketUpdate) -> Result<MarketRow> {
+        let market = self.get_market(market_id)?;
+
+        let db_market = self
+            .persister
+            .update_market(market_id, changes)
+            .context("Failed to persist market parameters")?;
+        market.set_params(MarketParams::from(&db_market));
+
+        info!(market_id = %market_id, "Updated market parameters");
+        Ok(db_market)
+    }
+
+    pub fn cancel_all_orders(&self, market_id: &str, reason: CancelReason) -> Result<bool> {
+        let market = self.get_market(market_id)?;
+
+        market.cancel_all_orders(reason)
+    }
+
+    pub fn cancel_all_orders_global(&self, reason: CancelReason) -> Result<()> {
+        let markets = self.all_markets()?;
+
+        for market in &markets {
+            market.cancel_all_orders(reason.clone())?;
         }
         Ok(())
     }
 
-    // Optional: Method to gracefully shutdown all market threads
-    pub fn shutdown(&self) -> Result<()> {
-        // Stop all markets first
-        self.cancel_all_orders_global()?;
+    /// Cancels the GTD orders that expired by `now` in every running market, returning how
+    /// many were canceled. A stopped market keeps its orders until it runs again.
+    pub fn expire_orders(&self, now: i64) -> Result<usize> {
+        let markets = self.all_markets()?;
+
+        let mut expired = 0;
+        for market in &markets {
+            if market.is_started() && market.is_ready() {
+                expired += market.expire_orders(now)?.len();
+            }
+        }
+        Ok(expired)
+    }
+
+    /// Snapshots the order book of every running market. Returns how many were written.
+    pub fn snapshot_order_books(&self) -> Result<usize> {
+        let markets = self.all_markets()?;
+
+        let mut written = 0;
+        for market in &markets {
+            if market.is_started() && market.is_ready() {
+                market.write_snapshot()?;
+                written += 1;
+            }
+        }
+        Ok(written)
+    }
 
-        // Join all market threads
-        let mut handles = self
-            .market_handles
-            .lock()
-            .map_err(|e| anyhow!("Failed to acquire lock on market handles: {}", e))?;
+    /// Recomputes the 24h stats of every market from its trades, `now` being in seconds.
+    /// Returns how many markets have traded at all.
+    pub fn refresh_market_stats(&self, now: i64) -> Result<usize> {
+        // The database is queried without holding the markets lock
+        let market_ids: Vec<String> = self.read_markets()?.keys().cloned().collect();
+
+        let mut refreshed = 0;
+        for market_id in market_ids {
+            if self
+                .persister
+                .refresh_market_stats(&market_id, now)?
+                .is_some()
+            {
+                refreshed += 1;
+            }
+        }
+        Ok(refreshed)
+    }
 
-        for handle in handles.drain(..) {
-            handle
-                .join()
-                .map_err(|_| anyhow!("Failed to join market thread"))?;
+    /// Stores the top of book of every market whose best bid or ask moved since `stored`, which
+    /// is kept up to date with what was written. Markets that are not running quote nothing.
+    /// Returns how many quotes were written.
+    pub fn store_quotes(&self, stored: &mut HashMap<String, Quote>) -> Result<usize> {
+        let mut written = 0;
+        for market in self.all_markets()? {
+            let market_id = market.get_market_id();
+            let quote = if market.is_started() && market.is_ready() {
+                let depth = market.depth(1, None)?;
+                let best = |levels: &[(BigDecimal, BigDecimal)]| {
+                    levels.first().map(|(price, _)| price.clone())
+                };
+                (best(&depth.bids), best(&depth.asks))
+            } else {
+                (None, None)
+            };
+            if stored.get(&market_id) == Some(&quote) {
+                continue;
+            }
+            self.persister
+                .upsert_market_quote(&market_id, quote.0.clone(), quote.1.clone())?;
+            stored.insert(market_id, quote);
+            written += 1;
         }
+        Ok(written)
+    }
 
+    /// Ids of the markets loaded in this engine, in no particular order
+    pub fn market_ids(&self) -> Result<Vec<String>> {
+        Ok(self.read_markets()?.keys().cloned().collect())
+    }
+
+    /// Tells whether any market is still recovering its open orders from the database. A
+    /// market whose book failed to load is not waited for, it refuses requests on its own.
+    pub fn is_recovering(&self) -> Result<bool> {
+        let markets = self.read_markets()?;
+        Ok(markets
+            .values()
+            .any(|market| !market.is_ready() && !market.has_failed()))
+    }
+
+    /// Appends an order request to the audit log.
+    pub fn record_audit(&self, entry: NewOrderAudit) -> Result<()> {
+        self.persister
+            .record_audit(entry)
+            .context("Failed to record order audit")?;
         Ok(())
     }
+
+    /// Counts the markets running in this engine and the orders resting in the database.
+    pub fn get_engine_stats(&self) -> Result<EngineStats> {
+        let active_markets = self
+            .read_markets()?
+            .values()
+            .filter(|market| market.is_started())
+            .count();
+
+        let mut stats = EngineStats {
+            active_markets,
+            open_orders: 0,
+            bid_volume: BigDecimal::from(0),
+            ask_volume: BigDecimal::from(0),
+        };
+        for side_stat in self
+            .persister
+            .get_open_order_stats()
+            .context("Failed to fetch open order stats")?
+        {
+            stats.open_orders += side_stat.order_count;
+            match DbOrderSide::from_str(&side_stat.side) {
+                Ok(DbOrderSide::Buy) => stats.bid_volume += side_stat.remained_base,
+                Ok(DbOrderSide::Sell) => stats.ask_volume += side_stat.remained_base,
+                Err(e) => return Err(anyhow!("Invalid order side: {}", e)),
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Stops every running market once its book is done with the requests queued to it,
+    /// after storing a last snapshot of the book. Resting orders stay open, for a restart to
+    /// restore them from the snapshot. Returns how many snapshots were written.
+    pub fn stop_all_markets(&self) -> Result<usize> {
+        let markets = self.all_markets()?;
+
+        let mut written = 0;
+        for market in markets.iter().filter(|market| market.is_started()) {
+            // Queued behind every request the book has yet to run
+            match market.write_snapshot() {
+                Ok(_) => written += 1,
+                Err(e) => {
+                    error!(market_id = %market.get_market_id(), "Failed to snapshot order book: {:?}", e)
+                }
+            }
+            market.stop_market()?;
+        }
+        Ok(written)
+    }
 }
 
-// Implement Drop trait for clean thread termination
-impl<P: DatabaseProvider> Drop for MarketManager<P> {
-    fn drop(&mut self) {
-        if let Ok(()) = self.shutdown() {
-            tracing::debug!(target: "market_manager", "Gracefully shutdown all markets");
+/// Runs `call` against the market manager on tokio's blocking pool, so waiting on an order book
+/// thread or the database does not hold up the runtime worker of the async caller.
+pub async fn run_blocking<P, T, F>(
+    market_manager: &Arc<tokio::sync::RwLock<MarketManager<P>>>,
+    call: F,
+) -> Result<T>
+where
+    P: DatabaseProvider + 'static,
+    T: Send + 'static,
+    F: FnOnce(&MarketManager<P>) -> Result<T> + Send + 'static,
+{
+    let market_manager = Arc::clone(market_manager).read_owned().await;
+    tokio::task::spawn_blocking(move || call(&market_manager))
+        .await
+        .context("Market manager call panicked")?
+}
+
+/// Cancels the orders `user_id` has open in the loaded markets, returning how many it canceled.
+/// An order that fails to cancel does not stop the rest; the failures are reported together
+/// once every market has been tried. The cancels are forced, so halted markets are no refuge.
+fn cancel_user_orders<P: DatabaseProvider>(
+    markets: &RwLock<MarketMap<P>>,
+    persister: &P,
+    user_id: &str,
+    reason: CancelReason,
+) -> Result<usize> {
+    let mut canceled = 0;
+    let mut failed = Vec::new();
+    for order in persister
+        .get_user_active_orders(user_id)
+        .context("Failed to fetch user orders")?
+    {
+        let market = markets
+            .read()
+            .map_err(|e| anyhow!("Failed to acquire lock on markets: {}", e))?
+            .get(&order.market_id)
+            .cloned();
+        let Some(market) = market else {
+            continue;
+        };
+        match market.force_cancel_order(order.id.clone(), reason.clone()) {
+            Ok(true) => canceled += 1,
+            Ok(false) => {}
+            Err(e) => {
+                error!(
+                    market_id = %order.market_id,
+                    order_id = %order.id,
+                    user_id = %user_id,
+                    "Failed to cancel user order: {:?}", e
+                );
+                failed.push(order.id);
+            }
         }
     }
+    if !failed.is_empty() {
+        bail!(
+            "Canceled {} orders of user {} but failed to cancel {}: {}",
+            canceled,
+            user_id,
+            failed.len(),
+            failed.join(", ")
+        );
+    }
+    Ok(canceled)
+}
+
+/// A fee of a new market, which is refused as an invalid argument when it is not a decimal
+fn parse_fee(fee: &str, field: &str) -> Result<BigDecimal> {
+    BigDecimal::from_str(fee).map_err(|e| {
+        BitradeError::InvalidArgument(format!("Invalid {} `{}`: {}", field, fee, e)).into()
+    })
 }
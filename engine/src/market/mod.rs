@@ -1,2 +1,11 @@
+pub mod expiry;
+pub mod idempotency;
+#[allow(clippy::module_inception)]
 mod market;
 pub mod market_manager;
+pub mod reload;
+pub mod session;
+pub mod snapshot;
+pub mod stats;
+
+pub use market::{MarketConfig, MarketError, MarketParams, DEFAULT_RECENT_TRADES_CAPACITY};
@@ -0,0 +1,38 @@
+use database::provider::DatabaseProvider;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tracing::{error, info};
+
+use super::market_manager::{run_blocking, MarketManager, MarketReload};
+
+/// Picks up markets created in or removed from the database by another engine or by hand
+/// every `interval`, as `ReloadMarkets` would.
+pub async fn run_market_reloader<P: DatabaseProvider>(
+    market_manager: Arc<RwLock<MarketManager<P>>>,
+    interval: Duration,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    // Markets were loaded with the manager, the first tick would do nothing
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        let reload = run_blocking(&market_manager, |market_manager| {
+            market_manager.reload_markets()
+        })
+        .await;
+        match reload {
+            Ok(MarketReload {
+                added: 0,
+                removed: 0,
+                ..
+            }) => {}
+            Ok(reload) => info!(
+                added = reload.added,
+                removed = reload.removed,
+                "Reloaded markets from database"
+            ),
+            Err(e) => error!("Failed to reload markets: {:?}", e),
+        }
+    }
+}
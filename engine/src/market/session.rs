@@ -0,0 +1,118 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use tokio::runtime::Handle;
+use tracing::{error, info};
+
+/// Cancels every open order of a user, returning how many it canceled
+pub type CancelUserOrders = Box<dyn FnOnce(&str) -> Result<usize> + Send + Sync>;
+
+#[derive(Debug, Default)]
+struct UserSessions {
+    open: usize,
+    cancel_on_disconnect: bool,
+}
+
+/// Connections users hold to the engine, and whether losing the last of them cancels their
+/// orders. The setting lasts as long as the user has a session open and is cleared with the
+/// last one, so a reconnecting client arms it again.
+#[derive(Debug, Default)]
+pub struct SessionRegistry {
+    users: Mutex<HashMap<String, UserSessions>>,
+}
+
+impl SessionRegistry {
+    fn users(&self) -> MutexGuard<'_, HashMap<String, UserSessions>> {
+        self.users.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Opens a session of `user_id`, which `cancel_orders` is called for if it is the last
+    /// one the user closes with cancel-on-disconnect on.
+    pub fn open(self: &Arc<Self>, user_id: &str, cancel_orders: CancelUserOrders) -> Session {
+        self.users().entry(user_id.to_string()).or_default().open += 1;
+        Session {
+            registry: self.clone(),
+            user_id: user_id.to_string(),
+            cancel_orders: Some(cancel_orders),
+        }
+    }
+
+    /// Turns cancel-on-disconnect of `user_id` on or off, returning the sessions they have
+    /// open. `None` when they have none, as there is nothing to disconnect from.
+    pub fn set_cancel_on_disconnect(&self, user_id: &str, enabled: bool) -> Option<usize> {
+        let mut users = self.users();
+        let sessions = users.get_mut(user_id)?;
+        sessions.cancel_on_disconnect = enabled;
+        Some(sessions.open)
+    }
+
+    /// Sessions `user_id` has open and whether losing them all cancels their orders
+    pub fn sessions(&self, user_id: &str) -> (usize, bool) {
+        self.users().get(user_id).map_or((0, false), |sessions| {
+            (sessions.open, sessions.cancel_on_disconnect)
+        })
+    }
+
+    /// Closes a session of `user_id`, telling whether it was their last with
+    /// cancel-on-disconnect on
+    fn close(&self, user_id: &str) -> bool {
+        let mut users = self.users();
+        let Some(sessions) = users.get_mut(user_id) else {
+            return false;
+        };
+        sessions.open -= 1;
+        if sessions.open > 0 {
+            return false;
+        }
+        users
+            .remove(user_id)
+            .is_some_and(|sessions| sessions.cancel_on_disconnect)
+    }
+}
+
+/// A connection of a user, closed when dropped, as a stream is once its client goes away
+pub struct Session {
+    registry: Arc<SessionRegistry>,
+    user_id: String,
+    cancel_orders: Option<CancelUserOrders>,
+}
+
+impl fmt::Debug for Session {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Session")
+            .field("user_id", &self.user_id)
+            .finish()
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        if !self.registry.close(&self.user_id) {
+            return;
+        }
+        let Some(cancel_orders) = self.cancel_orders.take() else {
+            return;
+        };
+        let user_id = std::mem::take(&mut self.user_id);
+        let cancel = move || match cancel_orders(&user_id) {
+            Ok(canceled) => info!(
+                user_id = %user_id,
+                canceled,
+                "Last session closed, canceled the user's orders"
+            ),
+            Err(e) => error!(
+                user_id = %user_id,
+                "Failed to cancel orders on disconnect: {:?}", e
+            ),
+        };
+        // Sessions of streams drop on a runtime worker, which canceling would block on the
+        // books and the database
+        match Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn_blocking(cancel);
+            }
+            Err(_) => cancel(),
+        }
+    }
+}
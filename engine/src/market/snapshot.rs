@@ -0,0 +1,26 @@
+use database::provider::DatabaseProvider;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tracing::error;
+
+use super::market_manager::{run_blocking, MarketManager};
+
+/// Snapshots the order book of every running market every `interval`, so a restart restores
+/// the books instead of matching every open order again.
+pub async fn run_snapshot_writer<P: DatabaseProvider>(
+    market_manager: Arc<RwLock<MarketManager<P>>>,
+    interval: Duration,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        let written = run_blocking(&market_manager, |market_manager| {
+            market_manager.snapshot_order_books()
+        })
+        .await;
+        if let Err(e) = written {
+            error!("Failed to snapshot order books: {:?}", e);
+        }
+    }
+}
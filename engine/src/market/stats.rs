@@ -0,0 +1,51 @@
+use common::utils::get_utc_now_millis;
+use database::provider::DatabaseProvider;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tracing::error;
+
+use super::market_manager::{run_blocking, MarketManager};
+
+/// Recomputes the 24h stats of every market every `interval`, so they stay current between
+/// trades as old ones leave the window.
+pub async fn run_market_stats_updater<P: DatabaseProvider>(
+    market_manager: Arc<RwLock<MarketManager<P>>>,
+    interval: Duration,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        let refreshed = run_blocking(&market_manager, |market_manager| {
+            // Trade timestamps are in seconds
+            market_manager.refresh_market_stats(get_utc_now_millis() / 1000)
+        })
+        .await;
+        if let Err(e) = refreshed {
+            error!("Failed to refresh market stats: {:?}", e);
+        }
+    }
+}
+
+/// Stores the best bid and ask of every market every `interval` for the query service's
+/// tickers, writing only the ones that moved.
+pub async fn run_quote_updater<P: DatabaseProvider>(
+    market_manager: Arc<RwLock<MarketManager<P>>>,
+    interval: Duration,
+) {
+    let stored = Arc::new(Mutex::new(HashMap::new()));
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        let stored = Arc::clone(&stored);
+        let written = run_blocking(&market_manager, move |market_manager| {
+            let mut stored = stored.lock().unwrap_or_else(PoisonError::into_inner);
+            market_manager.store_quotes(&mut stored)
+        })
+        .await;
+        if let Err(e) = written {
+            error!("Failed to store market quotes: {:?}", e);
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use prometheus::{
+    register_histogram_vec, register_int_counter_vec, register_int_gauge_vec, HistogramVec,
+    IntCounterVec, IntGaugeVec,
+};
+use std::sync::LazyLock;
+
+/// Orders accepted into a book, by market
+pub static ORDERS_ADDED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "bitrade_orders_added_total",
+        "Orders accepted into a book",
+        &["market"]
+    )
+    .unwrap()
+});
+
+/// Orders canceled out of a book, for any reason, by market
+pub static ORDERS_CANCELED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "bitrade_orders_canceled_total",
+        "Orders canceled",
+        &["market"]
+    )
+    .unwrap()
+});
+
+/// Time a book takes to check, persist and match an incoming order, settlement included
+pub static MATCH_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "bitrade_match_duration_seconds",
+        "Time to add an order to a book and settle its fills",
+        &["market"]
+    )
+    .unwrap()
+});
+
+/// Orders resting in a book after its last task, by market and side
+pub static BOOK_ORDERS: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!(
+        "bitrade_book_orders",
+        "Orders resting in a book",
+        &["market", "side"]
+    )
+    .unwrap()
+});
+
+/// Trades settled, by market
+pub static TRADES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!("bitrade_trades_total", "Trades settled", &["market"]).unwrap()
+});
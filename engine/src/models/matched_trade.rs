@@ -29,6 +29,14 @@ pub struct MatchedTrade {
     pub taker_side: String,
 }
 
+/// A trade numbered in the order its market executed it, starting at 1 each time the
+/// market's order book is created
+#[derive(Debug, Clone)]
+pub struct SequencedTrade {
+    pub sequence: u64,
+    pub trade: MatchedTrade,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
 pub enum MarketRole {
     Maker, // Order was on the book and matched
@@ -56,6 +64,27 @@ impl From<MarketRole> for String {
     }
 }
 
+impl From<NewTrade> for MatchedTrade {
+    fn from(trade: NewTrade) -> Self {
+        Self {
+            id: trade.id,
+            timestamp: trade.timestamp,
+            market_id: trade.market_id,
+            price: trade.price,
+            base_amount: trade.base_amount,
+            quote_amount: trade.quote_amount,
+            seller_user_id: trade.seller_user_id,
+            seller_order_id: trade.seller_order_id,
+            seller_fee: trade.seller_fee,
+            buyer_user_id: trade.buyer_user_id,
+            buyer_order_id: trade.buyer_order_id,
+            buyer_fee: trade.buyer_fee,
+            is_liquidation: trade.is_liquidation.unwrap_or(false),
+            taker_side: trade.taker_side,
+        }
+    }
+}
+
 impl From<MatchedTrade> for NewTrade {
     fn from(trade: MatchedTrade) -> Self {
         Self {
@@ -72,7 +101,7 @@ impl From<MatchedTrade> for NewTrade {
             buyer_order_id: trade.buyer_order_id,
             buyer_fee: trade.buyer_fee,
             is_liquidation: Some(trade.is_liquidation),
-            taker_side: trade.taker_side.into(),
+            taker_side: trade.taker_side,
         }
     }
 }
@@ -1,2 +1,4 @@
 pub mod matched_trade;
+pub mod order_receipt;
 pub mod trade_order;
+pub mod user_event;
@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+
+use super::matched_trade::MatchedTrade;
+use super::trade_order::TradeOrder;
+
+/// Outcome of placing an order, returned whether or not it matched
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OrderReceipt {
+    pub order_id: String,
+    pub market_id: String,
+    pub trades: Vec<MatchedTrade>,
+    /// What is left of the order in the book, `None` once it is filled or canceled
+    pub resting: Option<TradeOrder>,
+}
+
+/// Outcome of placing both legs of an OCO group
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OcoReceipt {
+    pub oco_group_id: String,
+    pub first: OrderReceipt,
+    pub second: OrderReceipt,
+}
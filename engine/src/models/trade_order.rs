@@ -1,7 +1,7 @@
 use bigdecimal::BigDecimal;
+use common::utils::round_amount;
 use database::models::models::*;
 use serde::{Deserialize, Serialize};
-use std::cmp::Ordering;
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TradeOrder {
     // Immutable order details
@@ -30,7 +30,13 @@ pub struct TradeOrder {
     pub post_only: Option<bool>,
     pub time_in_force: Option<TimeInForce>,
     pub expires_at: Option<i64>,
+    /// Iceberg orders show at most this much of `remained_base` in the book
+    pub display_amount: Option<BigDecimal>,
     pub status: OrderStatus,
+    /// Place in the queue of its price level, lower first. The book numbers orders as they
+    /// join it and the number is stored with the order, so a recovered book queues the same.
+    #[serde(default)]
+    pub priority: i64,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
@@ -87,45 +93,51 @@ impl From<OrderSide> for String {
     }
 }
 
-impl PartialEq for TradeOrder {
-    fn eq(&self, other: &Self) -> bool {
-        self.id == other.id
+impl TradeOrder {
+    /// The part of `remained_base` shown in the book and offered to a single fill. An iceberg
+    /// order shows one slice of `display_amount` at a time. Once a fill takes the whole slice,
+    /// the next one is drawn from the hidden rest and queues behind the orders at its price.
+    pub fn visible_base(&self) -> BigDecimal {
+        match &self.display_amount {
+            Some(display_amount) => display_amount.clone().min(self.remained_base.clone()),
+            None => self.remained_base.clone(),
+        }
     }
-}
 
-impl Eq for TradeOrder {}
-
-impl PartialOrd for TradeOrder {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+    /// This order as amending it to `price` and `remained_base` leaves it, keeping what it
+    /// filled so far. Its quote amount is what the whole order is worth at the new price, as a
+    /// placement of it would carry.
+    pub fn amended(&self, price: BigDecimal, remained_base: BigDecimal) -> TradeOrder {
+        let base_amount = &self.filled_base + &remained_base;
+        TradeOrder {
+            quote_amount: round_amount(&(&price * &base_amount)),
+            remained_quote: round_amount(&(&price * &remained_base)),
+            base_amount,
+            remained_base,
+            price,
+            ..self.clone()
+        }
     }
 }
 
-impl Ord for TradeOrder {
-    fn cmp(&self, other: &Self) -> Ordering {
-        match (self.side, other.side) {
-            (OrderSide::Sell, OrderSide::Sell) => {
-                // For asks, lower price comes first
-                match other.price.cmp(&self.price) {
-                    Ordering::Equal => other.create_time.cmp(&self.create_time), // Time priority
-                    ordering => ordering,
-                }
-            }
-            (OrderSide::Buy, OrderSide::Buy) => {
-                // For bids, lower price comes first
-                match self.price.cmp(&other.price) {
-                    Ordering::Equal => other.create_time.cmp(&self.create_time), // Time priority
-                    ordering => ordering,
-                }
-            }
-            _ => panic!("Cannot compare orders with different sides"),
-        }
+impl PartialEq for TradeOrder {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
     }
 }
 
+impl Eq for TradeOrder {}
+
 impl From<TradeOrder> for NewOrder {
     fn from(trade_order: TradeOrder) -> Self {
         let status = determine_order_status(&trade_order);
+        // IOC and FOK orders must be stored with an expiry, which is the moment they arrive
+        let expires_at = match trade_order.time_in_force {
+            Some(TimeInForce::IOC) | Some(TimeInForce::FOK) => {
+                trade_order.expires_at.or(Some(trade_order.create_time))
+            }
+            _ => trade_order.expires_at,
+        };
         Self {
             id: trade_order.id,
             market_id: trade_order.market_id,
@@ -149,16 +161,18 @@ impl From<TradeOrder> for NewOrder {
             time_in_force: trade_order
                 .time_in_force
                 .map(|tif| tif.as_str().to_string()),
-            expires_at: trade_order.expires_at,
+            expires_at,
+            display_amount: trade_order.display_amount,
             status,
+            priority: trade_order.priority,
         }
     }
 }
 
 pub fn determine_order_status(trade_order: &TradeOrder) -> String {
-    if trade_order.remained_base == BigDecimal::from(0) {
+    if trade_order.remained_base == 0 {
         "FILLED".to_string()
-    } else if trade_order.filled_base > BigDecimal::from(0) {
+    } else if trade_order.filled_base > 0 {
         "PARTIALLY_FILLED".to_string()
     } else {
         "OPEN".to_string()
@@ -199,8 +213,10 @@ impl TryFrom<Order> for TradeOrder {
                 .transpose()
                 .map_err(|e| anyhow::anyhow!("Invalid TimeInForce: {}", e))?,
             expires_at: order.expires_at,
+            display_amount: order.display_amount,
             status: OrderStatus::try_from(order.status.as_str())
                 .map_err(|e| anyhow::anyhow!("Invalid OrderStatus: {}", e))?,
+            priority: order.priority,
         })
     }
 }
@@ -0,0 +1,126 @@
+use bigdecimal::BigDecimal;
+use common::utils::get_utc_now_millis;
+use database::models::models::{CancelReason, OrderStatus};
+
+use super::matched_trade::MatchedTrade;
+use super::trade_order::{OrderSide, TradeOrder};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UserEventKind {
+    Accepted,
+    PartiallyFilled,
+    Filled,
+    Canceled,
+    /// Refused before it became an order, nothing was locked for it
+    Rejected,
+    Expired,
+}
+
+impl UserEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UserEventKind::Accepted => "ACCEPTED",
+            UserEventKind::PartiallyFilled => "PARTIALLY_FILLED",
+            UserEventKind::Filled => "FILLED",
+            UserEventKind::Canceled => "CANCELED",
+            UserEventKind::Rejected => "REJECTED",
+            UserEventKind::Expired => "EXPIRED",
+        }
+    }
+
+    /// Cancellations by expiry are told apart from every other cancel reason
+    pub fn for_cancel(reason: &CancelReason) -> Self {
+        match reason {
+            CancelReason::Expired => UserEventKind::Expired,
+            _ => UserEventKind::Canceled,
+        }
+    }
+}
+
+/// Something that happened to one of a user's orders, pushed to `SubscribeUserEvents`
+#[derive(Debug, Clone)]
+pub struct UserEvent {
+    pub kind: UserEventKind,
+    pub user_id: String,
+    pub market_id: String,
+    /// Empty for a request rejected before it got an id
+    pub order_id: String,
+    pub client_order_id: Option<String>,
+    pub side: Option<OrderSide>,
+    pub price: Option<BigDecimal>,
+    pub filled_base: BigDecimal,
+    pub remained_base: BigDecimal,
+    /// The fill behind a `PartiallyFilled` or `Filled` event
+    pub trade: Option<MatchedTrade>,
+    /// Why the order was canceled or rejected
+    pub reason: Option<String>,
+    pub timestamp: i64,
+}
+
+impl UserEvent {
+    pub fn for_order(kind: UserEventKind, order: &TradeOrder) -> Self {
+        Self {
+            kind,
+            user_id: order.user_id.clone(),
+            market_id: order.market_id.clone(),
+            order_id: order.id.clone(),
+            client_order_id: order.client_order_id.clone(),
+            side: Some(order.side),
+            price: Some(order.price.clone()),
+            filled_base: order.filled_base.clone(),
+            remained_base: order.remained_base.clone(),
+            trade: None,
+            reason: None,
+            timestamp: get_utc_now_millis(),
+        }
+    }
+
+    /// The fill `trade` made of `order`, as the order stands after it
+    pub fn for_fill(order: &TradeOrder, trade: &MatchedTrade) -> Self {
+        let kind = match order.status {
+            OrderStatus::Filled => UserEventKind::Filled,
+            _ => UserEventKind::PartiallyFilled,
+        };
+        Self {
+            trade: Some(trade.clone()),
+            ..Self::for_order(kind, order)
+        }
+    }
+
+    pub fn for_cancel(order: &TradeOrder, reason: &CancelReason) -> Self {
+        Self {
+            reason: Some(reason.as_str().to_string()),
+            ..Self::for_order(UserEventKind::for_cancel(reason), order)
+        }
+    }
+
+    pub fn rejected(order: &TradeOrder, reason: String) -> Self {
+        Self {
+            reason: Some(reason),
+            ..Self::for_order(UserEventKind::Rejected, order)
+        }
+    }
+
+    /// A request refused before it could be turned into an order
+    pub fn rejected_request(
+        user_id: String,
+        market_id: String,
+        client_order_id: Option<String>,
+        reason: String,
+    ) -> Self {
+        Self {
+            kind: UserEventKind::Rejected,
+            user_id,
+            market_id,
+            order_id: String::new(),
+            client_order_id,
+            side: None,
+            price: None,
+            filled_base: BigDecimal::from(0),
+            remained_base: BigDecimal::from(0),
+            trade: None,
+            reason: Some(reason),
+            timestamp: get_utc_now_millis(),
+        }
+    }
+}
@@ -0,0 +1,259 @@
+use crate::models::trade_order::{OrderSide, TradeOrder};
+use bigdecimal::{BigDecimal, RoundingMode};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+
+/// Orders resting at one price in time priority, with the amount they show in total
+#[derive(Debug, Clone, Default)]
+struct PriceLevel {
+    orders: VecDeque<TradeOrder>,
+    /// Sum of the visible base of the orders, kept as they come and go
+    visible: BigDecimal,
+}
+
+impl PriceLevel {
+    fn push_front(&mut self, order: TradeOrder) {
+        self.visible += order.visible_base();
+        self.orders.push_front(order);
+    }
+
+    fn pop_front(&mut self) -> Option<TradeOrder> {
+        let order = self.orders.pop_front()?;
+        self.visible -= order.visible_base();
+        Some(order)
+    }
+
+    fn insert(&mut self, position: usize, order: TradeOrder) {
+        self.visible += order.visible_base();
+        self.orders.insert(position, order);
+    }
+
+    fn remove(&mut self, position: usize) -> Option<TradeOrder> {
+        let order = self.orders.remove(position)?;
+        self.visible -= order.visible_base();
+        Some(order)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.orders.is_empty()
+    }
+}
+
+/// One side of an order book: orders queued per price level in time priority, plus an index
+/// from order id to the level holding it, so lookups and removals do not scan the side.
+#[derive(Debug, Clone)]
+pub struct BookSide {
+    side: OrderSide,
+    levels: BTreeMap<BigDecimal, PriceLevel>,
+    index: HashMap<String, BigDecimal>,
+    /// Bumped by every change to the side, never reset while the book lives
+    changes: u64,
+    /// Prices of the levels changed since [`Self::take_dirty_levels`] last ran
+    dirty: BTreeSet<BigDecimal>,
+}
+
+impl BookSide {
+    pub fn new(side: OrderSide) -> Self {
+        Self {
+            side,
+            levels: BTreeMap::new(),
+            index: HashMap::new(),
+            changes: 0,
+            dirty: BTreeSet::new(),
+        }
+    }
+
+    /// Highest bid or lowest ask
+    pub fn best_price(&self) -> Option<&BigDecimal> {
+        match self.side {
+            OrderSide::Buy => self.levels.keys().next_back(),
+            OrderSide::Sell => self.levels.keys().next(),
+        }
+    }
+
+    /// The order next in line to trade
+    pub fn best(&self) -> Option<&TradeOrder> {
+        self.levels.get(self.best_price()?)?.orders.front()
+    }
+
+    /// Takes the order next in line off the side. Put it back with [`Self::push_front`] if
+    /// it keeps resting, so it does not lose its place.
+    pub fn pop_best(&mut self) -> Option<TradeOrder> {
+        let price = self.best_price()?.clone();
+        let level = self.levels.get_mut(&price)?;
+        let order = level.pop_front()?;
+        if level.is_empty() {
+            self.levels.remove(&price);
+        }
+        self.index.remove(&order.id);
+        self.touch(&price);
+        Some(order)
+    }
+
+    /// Returns an order taken with [`Self::pop_best`] to the head of its level.
+    pub fn push_front(&mut self, order: TradeOrder) {
+        self.touch(&order.price);
+        self.index.insert(order.id.clone(), order.price.clone());
+        self.levels
+            .entry(order.price.clone())
+            .or_default()
+            .push_front(order);
+    }
+
+    /// Returns a maker taken with [`Self::pop_best`] after a fill. It keeps its place, unless
+    /// it is an iceberg the fill replenished: the new slice queues at the back of its level
+    /// like a new order, behind what already rests there, under the `replenished` priority.
+    pub fn requeue(&mut self, mut order: TradeOrder, replenished: Option<i64>) {
+        match replenished {
+            Some(priority) => {
+                order.priority = priority;
+                self.push(order);
+            }
+            None => self.push_front(order),
+        }
+    }
+
+    /// Queues an order at its price level behind every order of a lower priority. New orders
+    /// go straight to the back; only recovered ones may have to move further up.
+    pub fn push(&mut self, order: TradeOrder) {
+        self.touch(&order.price);
+        self.index.insert(order.id.clone(), order.price.clone());
+        let level = self.levels.entry(order.price.clone()).or_default();
+        let position = level
+            .orders
+            .iter()
+            .rposition(|queued| queued.priority <= order.priority)
+            .map_or(0, |index| index + 1);
+        level.insert(position, order);
+    }
+
+    /// Swaps in a new state of a resting order at the same price, keeping its place.
+    pub fn replace(&mut self, order: TradeOrder) -> Option<TradeOrder> {
+        let price = self.index.get(&order.id)?;
+        if *price != order.price {
+            return None;
+        }
+        let level = self.levels.get_mut(price)?;
+        let queued = level
+            .orders
+            .iter_mut()
+            .find(|queued| queued.id == order.id)?;
+        level.visible += order.visible_base() - queued.visible_base();
+        let replaced = std::mem::replace(queued, order);
+        self.touch(&replaced.price);
+        Some(replaced)
+    }
+
+    pub fn get(&self, order_id: &str) -> Option<&TradeOrder> {
+        let price = self.index.get(order_id)?;
+        self.levels
+            .get(price)?
+            .orders
+            .iter()
+            .find(|o| o.id == order_id)
+    }
+
+    pub fn remove(&mut self, order_id: &str) -> Option<TradeOrder> {
+        let price = self.index.remove(order_id)?;
+        let level = self.levels.get_mut(&price)?;
+        let position = level.orders.iter().position(|o| o.id == order_id)?;
+        let order = level.remove(position);
+        if level.is_empty() {
+            self.levels.remove(&price);
+        }
+        self.touch(&price);
+        order
+    }
+
+    /// Orders in the sequence they would trade: best price first, each level oldest first.
+    pub fn iter(&self) -> Box<dyn Iterator<Item = &TradeOrder> + '_> {
+        match self.side {
+            OrderSide::Buy => Box::new(self.levels.values().rev().flat_map(|l| &l.orders)),
+            OrderSide::Sell => Box::new(self.levels.values().flat_map(|l| &l.orders)),
+        }
+    }
+
+    /// Base amount shown at each price level, counting only the visible slice of icebergs
+    pub fn depth(&self) -> BTreeMap<BigDecimal, BigDecimal> {
+        self.levels
+            .iter()
+            .map(|(price, level)| (price.clone(), level.visible.clone()))
+            .collect()
+    }
+
+    /// The first `count` levels from the best price on, as (price, visible amount). With a
+    /// `price_step`, levels are merged into buckets at multiples of it, rounded away from the
+    /// spread: bids down and asks up, so no bucket shows a better price than it holds.
+    pub fn top_levels(
+        &self,
+        count: usize,
+        price_step: Option<&BigDecimal>,
+    ) -> Vec<(BigDecimal, BigDecimal)> {
+        let levels: Box<dyn Iterator<Item = _>> = match self.side {
+            OrderSide::Buy => Box::new(self.levels.iter().rev()),
+            OrderSide::Sell => Box::new(self.levels.iter()),
+        };
+        let rounding = match self.side {
+            OrderSide::Buy => RoundingMode::Floor,
+            OrderSide::Sell => RoundingMode::Ceiling,
+        };
+
+        let mut top: Vec<(BigDecimal, BigDecimal)> = Vec::with_capacity(count);
+        for (price, level) in levels {
+            let price = match price_step {
+                Some(step) => (price / step).with_scale_round(0, rounding) * step,
+                None => price.clone(),
+            };
+            let amount = level.visible.clone();
+            if let Some((_, total)) = top.last_mut().filter(|(last, _)| *last == price) {
+                *total += amount;
+            } else if top.len() == count {
+                break;
+            } else {
+                top.push((price, amount));
+            }
+        }
+        top
+    }
+
+    /// Counts a change to the level at `price`
+    fn touch(&mut self, price: &BigDecimal) {
+        self.changes += 1;
+        self.dirty.insert(price.clone());
+    }
+
+    /// The levels changed since the last call in ascending price, each with the amount it
+    /// shows now, zero for a level that is gone. A level changed back to what it was is
+    /// included too.
+    pub fn take_dirty_levels(&mut self) -> Vec<(BigDecimal, BigDecimal)> {
+        std::mem::take(&mut self.dirty)
+            .into_iter()
+            .map(|price| {
+                let visible = self
+                    .levels
+                    .get(&price)
+                    .map_or_else(|| BigDecimal::from(0), |level| level.visible.clone());
+                (price, visible)
+            })
+            .collect()
+    }
+
+    /// Number of changes made to the side so far
+    pub fn changes(&self) -> u64 {
+        self.changes
+    }
+
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.changes += 1;
+        let prices = std::mem::take(&mut self.levels).into_keys();
+        self.dirty.extend(prices);
+        self.index.clear();
+    }
+}
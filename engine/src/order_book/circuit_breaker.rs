@@ -0,0 +1,110 @@
+use bigdecimal::BigDecimal;
+use database::models::models::PriceBand;
+use std::collections::VecDeque;
+
+use super::OrderBookError;
+
+/// Price protection of a market, read from its `price_bands` row
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PriceBandConfig {
+    /// Largest deviation, in percent of the last traded price, a limit order price may have
+    pub band_percent: Option<BigDecimal>,
+    /// Price move, in percent, within `halt_window_ms` that halts matching
+    pub halt_percent: Option<BigDecimal>,
+    pub halt_window_ms: i64,
+    /// How long matching stays halted before it resumes on its own
+    pub halt_duration_ms: i64,
+}
+
+impl From<&PriceBand> for PriceBandConfig {
+    fn from(band: &PriceBand) -> Self {
+        Self {
+            band_percent: band.band_percent.as_ref().map(BigDecimal::normalized),
+            halt_percent: band.halt_percent.as_ref().map(BigDecimal::normalized),
+            halt_window_ms: band.halt_window_ms,
+            halt_duration_ms: band.halt_duration_ms,
+        }
+    }
+}
+
+/// Holds limit orders to a band around the last traded price, and halts matching for a while
+/// once trades move the price too far too fast.
+#[derive(Debug, Clone, Default)]
+pub struct CircuitBreaker {
+    config: PriceBandConfig,
+    /// Trades within the halt window as (time, price), oldest first
+    window: VecDeque<(i64, BigDecimal)>,
+    /// Matching resumes at this time, in milliseconds
+    halted_until: Option<i64>,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: PriceBandConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    pub fn config(&self) -> &PriceBandConfig {
+        &self.config
+    }
+
+    /// Refuses a limit `price` further from `market_price` than the band allows. Without a
+    /// band or a previous trade every price passes.
+    pub fn check_band(
+        &self,
+        price: &BigDecimal,
+        market_price: Option<&BigDecimal>,
+    ) -> Result<(), OrderBookError> {
+        let (Some(band), Some(reference)) = (&self.config.band_percent, market_price) else {
+            return Ok(());
+        };
+        let max_deviation = reference * band / BigDecimal::from(100);
+        if (price - reference).abs() > max_deviation {
+            return Err(OrderBookError::OutsidePriceBand {
+                price: price.clone(),
+                reference: reference.clone(),
+                band: band.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    /// When matching resumes, if it is halted at `now`. A halt that ran out is lifted here.
+    pub fn halted_until(&mut self, now: i64) -> Option<i64> {
+        if self.halted_until.is_some_and(|until| until <= now) {
+            self.halted_until = None;
+        }
+        self.halted_until
+    }
+
+    /// Records a trade about to execute at `price`. A trade moving the price more than the
+    /// halt percent away from any trade within the window is refused instead, and halts
+    /// matching.
+    pub fn admit_trade(&mut self, price: &BigDecimal, now: i64) -> bool {
+        let Some(halt_percent) = &self.config.halt_percent else {
+            return true;
+        };
+        let window_start = now - self.config.halt_window_ms;
+        while self
+            .window
+            .front()
+            .is_some_and(|(time, _)| *time < window_start)
+        {
+            self.window.pop_front();
+        }
+
+        let tripped = self.window.iter().any(|(_, reference)| {
+            (price - reference).abs() * BigDecimal::from(100) > reference * halt_percent
+        });
+        if tripped {
+            // Prices from before the halt are not held against the market once it resumes
+            self.window.clear();
+            self.halted_until = Some(now + self.config.halt_duration_ms);
+        } else {
+            self.window.push_back((now, price.clone()));
+        }
+        !tripped
+    }
+}
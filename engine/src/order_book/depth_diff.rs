@@ -0,0 +1,225 @@
+use super::OrderBook;
+use bigdecimal::BigDecimal;
+use common::utils::is_zero;
+use database::provider::DatabaseProvider;
+use std::collections::BTreeMap;
+use tokio::sync::broadcast;
+
+/// Depth changes buffered per subscriber before a slow one is dropped
+pub const DEPTH_UPDATES_CAPACITY: usize = 1024;
+
+/// Aggregated amount per price level on both sides of a book
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DepthSnapshot {
+    pub bids: BTreeMap<BigDecimal, BigDecimal>,
+    pub asks: BTreeMap<BigDecimal, BigDecimal>,
+}
+
+/// Best levels of both sides, best price first, with the book's sequence at the time taken
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OrderBookDepth {
+    pub bids: Vec<(BigDecimal, BigDecimal)>,
+    pub asks: Vec<(BigDecimal, BigDecimal)>,
+    pub sequence: u64,
+}
+
+/// New amount of one price level; zero means the level is gone
+#[derive(Debug, Clone, PartialEq)]
+pub struct LevelChange {
+    pub price: BigDecimal,
+    pub amount: BigDecimal,
+}
+
+/// Levels that changed between two snapshots. Unchanged levels are left out entirely.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DepthDiff {
+    pub bids: Vec<LevelChange>,
+    pub asks: Vec<LevelChange>,
+}
+
+/// Levels changed by one order book task, with the book's sequence once they applied
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepthDelta {
+    pub diff: DepthDiff,
+    pub sequence: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DepthUpdate {
+    Snapshot(DepthSnapshot),
+    Diff(DepthDiff),
+}
+
+impl DepthSnapshot {
+    /// Returns the changes that turn `self` into `next`.
+    pub fn diff(&self, next: &DepthSnapshot) -> DepthDiff {
+        DepthDiff {
+            bids: diff_side(&self.bids, &next.bids),
+            asks: diff_side(&self.asks, &next.asks),
+        }
+    }
+
+    pub fn apply(&mut self, diff: &DepthDiff) {
+        apply_side(&mut self.bids, &diff.bids);
+        apply_side(&mut self.asks, &diff.asks);
+    }
+}
+
+impl DepthDiff {
+    pub fn is_empty(&self) -> bool {
+        self.bids.is_empty() && self.asks.is_empty()
+    }
+}
+
+fn diff_side(
+    previous: &BTreeMap<BigDecimal, BigDecimal>,
+    next: &BTreeMap<BigDecimal, BigDecimal>,
+) -> Vec<LevelChange> {
+    let mut changes: Vec<LevelChange> = next
+        .iter()
+        .filter(|(price, amount)| previous.get(*price) != Some(*amount))
+        .map(|(price, amount)| LevelChange {
+            price: price.clone(),
+            amount: amount.clone(),
+        })
+        .collect();
+
+    changes.extend(
+        previous
+            .keys()
+            .filter(|price| !next.contains_key(*price))
+            .map(|price| LevelChange {
+                price: price.clone(),
+                amount: BigDecimal::from(0),
+            }),
+    );
+    changes.sort_by(|a, b| a.price.cmp(&b.price));
+    changes
+}
+
+fn apply_side(levels: &mut BTreeMap<BigDecimal, BigDecimal>, changes: &[LevelChange]) {
+    for change in changes {
+        if is_zero(&change.amount) {
+            levels.remove(&change.price);
+        } else {
+            levels.insert(change.price.clone(), change.amount.clone());
+        }
+    }
+}
+
+/// Brings `published` up to date with the `levels` changed since, returning the ones whose
+/// amount differs from what it held.
+fn update_side(
+    published: &mut BTreeMap<BigDecimal, BigDecimal>,
+    levels: Vec<(BigDecimal, BigDecimal)>,
+) -> Vec<LevelChange> {
+    let changes: Vec<LevelChange> = levels
+        .into_iter()
+        .filter(|(price, amount)| match published.get(price) {
+            Some(previous) => previous != amount,
+            None => !is_zero(amount),
+        })
+        .map(|(price, amount)| LevelChange { price, amount })
+        .collect();
+    apply_side(published, &changes);
+    changes
+}
+
+/// Turns successive depth snapshots into a stream of diffs, with a full snapshot first and
+/// then every `snapshot_interval` updates so a client that missed a diff can resync.
+#[derive(Debug, Clone)]
+pub struct DepthDiffEncoder {
+    snapshot_interval: usize,
+    previous: Option<DepthSnapshot>,
+    updates_since_snapshot: usize,
+}
+
+impl DepthDiffEncoder {
+    pub fn new(snapshot_interval: usize) -> Self {
+        Self {
+            snapshot_interval: snapshot_interval.max(1),
+            previous: None,
+            updates_since_snapshot: 0,
+        }
+    }
+
+    pub fn encode(&mut self, snapshot: DepthSnapshot) -> DepthUpdate {
+        let update = match &self.previous {
+            Some(previous) if self.updates_since_snapshot < self.snapshot_interval => {
+                self.updates_since_snapshot += 1;
+                DepthUpdate::Diff(previous.diff(&snapshot))
+            }
+            _ => {
+                self.updates_since_snapshot = 0;
+                DepthUpdate::Snapshot(snapshot.clone())
+            }
+        };
+        self.previous = Some(snapshot);
+        update
+    }
+}
+
+impl<P: DatabaseProvider> OrderBook<P> {
+    pub fn depth_snapshot(&self) -> DepthSnapshot {
+        DepthSnapshot {
+            bids: self.bids.depth(),
+            asks: self.asks.depth(),
+        }
+    }
+
+    /// Up to `levels` price levels per side, merged into `price_step` wide buckets if given.
+    pub fn depth(&self, levels: usize, price_step: Option<&BigDecimal>) -> OrderBookDepth {
+        OrderBookDepth {
+            bids: self.bids.top_levels(levels, price_step),
+            asks: self.asks.top_levels(levels, price_step),
+            sequence: self.sequence(),
+        }
+    }
+
+    /// Subscribes to the depth deltas of the book, returning the depth and sequence they
+    /// start from.
+    pub fn subscribe_depth(&mut self) -> (DepthSnapshot, u64, broadcast::Receiver<DepthDelta>) {
+        // The snapshot covers every change so far
+        self.bids.take_dirty_levels();
+        self.asks.take_dirty_levels();
+        let snapshot = self.depth_snapshot();
+        let sequence = self.sequence();
+        self.published_depth = Some((snapshot.clone(), sequence));
+        (snapshot, sequence, self.depth_updates.subscribe())
+    }
+
+    /// Sends subscribers the levels changed since the last call. The market calls it after
+    /// every task, so one delta covers a whole match or cancel. Only the levels the task
+    /// touched are compared, not the whole book.
+    pub fn publish_depth_changes(&mut self) {
+        let bids = self.bids.take_dirty_levels();
+        let asks = self.asks.take_dirty_levels();
+        if self.depth_updates.receiver_count() == 0 {
+            self.published_depth = None;
+            return;
+        }
+        let sequence = self.sequence();
+        let Some((published, published_sequence)) = &mut self.published_depth else {
+            return;
+        };
+        // Tasks that only read the book leave nothing to send
+        if *published_sequence == sequence {
+            return;
+        }
+
+        let diff = DepthDiff {
+            bids: update_side(&mut published.bids, bids),
+            asks: update_side(&mut published.asks, asks),
+        };
+        *published_sequence = sequence;
+        if !diff.is_empty() {
+            let _ = self.depth_updates.send(DepthDelta { diff, sequence });
+        }
+    }
+
+    /// Grows with every change to the book, so equal sequences mean an unchanged book. It
+    /// starts over when the market is started again.
+    pub fn sequence(&self) -> u64 {
+        self.bids.changes() + self.asks.changes()
+    }
+}
@@ -0,0 +1,282 @@
+use anyhow::{Context, Result};
+use bigdecimal::BigDecimal;
+use common::utils::get_utc_now_millis;
+use database::models::models::{
+    CancelReason, EngineEvent, EngineEventType, NewEngineEvent, NewOrder, OrderStatus, TradeFill,
+};
+use database::provider::DatabaseProvider;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use super::OrderBook;
+
+/// A change of an order book, written to the `engine_events` journal before it is applied
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum JournalEntry {
+    OrderAccepted(Box<NewOrder>),
+    TradeExecuted {
+        buyer_order_id: String,
+        seller_order_id: String,
+        price: BigDecimal,
+        base_amount: BigDecimal,
+        is_buyer_taker: bool,
+        /// Id and time, in seconds, of a trade stored behind the book, which replay stores
+        /// unless it made it in. Trades settled before the book moved on have none.
+        #[serde(default)]
+        trade_id: Option<String>,
+        #[serde(default)]
+        timestamp: Option<i64>,
+    },
+    OrderCanceled {
+        order_id: String,
+        reason: CancelReason,
+    },
+    OrdersCanceled {
+        reason: CancelReason,
+    },
+    OrderAmended {
+        order_id: String,
+        price: BigDecimal,
+        remained_base: BigDecimal,
+        /// Where the order queues after it, absent from entries journaled before amendments
+        /// stored it: those keep the priority the order has
+        #[serde(default)]
+        priority: Option<i64>,
+    },
+}
+
+impl JournalEntry {
+    pub fn event_type(&self) -> EngineEventType {
+        match self {
+            JournalEntry::OrderAccepted(_) => EngineEventType::OrderAccepted,
+            JournalEntry::TradeExecuted { .. } => EngineEventType::TradeExecuted,
+            JournalEntry::OrderCanceled { .. } => EngineEventType::OrderCanceled,
+            JournalEntry::OrdersCanceled { .. } => EngineEventType::OrdersCanceled,
+            JournalEntry::OrderAmended { .. } => EngineEventType::OrderAmended,
+        }
+    }
+
+    /// The order the entry is about, the incoming one for a trade
+    pub fn order_id(&self) -> Option<&str> {
+        match self {
+            JournalEntry::OrderAccepted(order) => Some(&order.id),
+            JournalEntry::TradeExecuted {
+                buyer_order_id,
+                seller_order_id,
+                is_buyer_taker,
+                ..
+            } => Some(match is_buyer_taker {
+                true => buyer_order_id,
+                false => seller_order_id,
+            }),
+            JournalEntry::OrderCanceled { order_id, .. }
+            | JournalEntry::OrderAmended { order_id, .. } => Some(order_id),
+            JournalEntry::OrdersCanceled { .. } => None,
+        }
+    }
+
+    /// The user or order whose trades still being stored the entry has to wait for before it
+    /// is applied. A new order locks funds of its user, other changes rewrite their order.
+    fn settled_first(&self) -> Option<&str> {
+        match self {
+            JournalEntry::OrderAccepted(order) => Some(&order.user_id),
+            JournalEntry::OrderCanceled { order_id, .. }
+            | JournalEntry::OrderAmended { order_id, .. } => Some(order_id),
+            JournalEntry::TradeExecuted { .. } | JournalEntry::OrdersCanceled { .. } => None,
+        }
+    }
+}
+
+impl TryFrom<&EngineEvent> for JournalEntry {
+    type Error = anyhow::Error;
+
+    fn try_from(event: &EngineEvent) -> Result<Self> {
+        serde_json::from_str(&event.payload)
+            .with_context(|| format!("Invalid payload of engine event {}", event.sequence))
+    }
+}
+
+impl<P: DatabaseProvider> OrderBook<P> {
+    /// Writes `entry` to the journal, then applies it with `apply`. The market's checkpoint
+    /// moves past the entry once `apply` returns, whether it succeeded or not: a refused
+    /// change is not tried again on replay.
+    pub(super) fn journaled<T>(
+        &self,
+        entry: JournalEntry,
+        apply: impl FnOnce() -> Result<T>,
+    ) -> Result<T> {
+        self.journaled_all(vec![entry], apply)
+    }
+
+    /// Like [`Self::journaled`] for changes applied together: all of `entries` are written in
+    /// one go, and the checkpoint moves past the last of them once `apply` returns.
+    ///
+    /// With trades stored behind the book, the ones `entries` depend on are stored first, and
+    /// the checkpoint stays where the settlement writer leaves it until it has caught up.
+    pub(super) fn journaled_all<T>(
+        &self,
+        entries: Vec<JournalEntry>,
+        apply: impl FnOnce() -> Result<T>,
+    ) -> Result<T> {
+        if let Some(writer) = &self.settlement_writer {
+            match entries
+                .iter()
+                .any(|entry| matches!(entry, JournalEntry::OrdersCanceled { .. }))
+            {
+                true => writer.wait_all()?,
+                false => writer.wait_for(entries.iter().filter_map(JournalEntry::settled_first))?,
+            }
+        }
+        let appended = self.append_journal(&entries)?;
+        let applied = apply();
+        let behind = self
+            .settlement_writer
+            .as_ref()
+            .is_some_and(|writer| !writer.is_idle());
+        if let (Some(last), false) = (appended.last(), behind) {
+            self.persister
+                .set_applied_sequence(&self.market_id, last.sequence)?;
+        }
+        applied
+    }
+
+    /// Writes `entries` to the journal, returning them with their sequences.
+    pub(super) fn append_journal(&self, entries: &[JournalEntry]) -> Result<Vec<EngineEvent>> {
+        let create_time = get_utc_now_millis();
+        let events = entries
+            .iter()
+            .map(|entry| {
+                Ok(NewEngineEvent {
+                    market_id: self.market_id.clone(),
+                    event_type: entry.event_type().as_str().to_string(),
+                    order_id: entry.order_id().map(str::to_string),
+                    payload: serde_json::to_string(entry)
+                        .context("Failed to encode engine event")?,
+                    create_time,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        self.persister.append_engine_events(events)
+    }
+
+    /// Applies the journal entries the engine wrote but stopped before applying, ahead of
+    /// rebuilding the book from the orders table. Returns how many entries were replayed.
+    ///
+    /// Entries are checked against the database first, so one whose effects made it in before
+    /// the checkpoint did is not applied twice. Trades settled before the book moved on are not
+    /// executed from the journal: the orders they were between are still open and match again
+    /// once the book is rebuilt. Trades stored behind the book were published already, those
+    /// the settlement writer did not store are settled here under the id they were given.
+    pub(super) fn replay_journal(&self) -> Result<usize> {
+        let applied_sequence = self.persister.get_applied_sequence(&self.market_id)?;
+        let pending = self
+            .persister
+            .get_engine_events(&self.market_id, applied_sequence)?;
+
+        for event in &pending {
+            let entry = JournalEntry::try_from(event)?;
+            if let Err(e) = self.replay_entry(entry) {
+                warn!(
+                    market_id = %self.market_id,
+                    "Engine event {} was not replayed: {}", event.sequence, e
+                );
+            }
+            self.persister
+                .set_applied_sequence(&self.market_id, event.sequence)?;
+        }
+        Ok(pending.len())
+    }
+
+    fn replay_entry(&self, entry: JournalEntry) -> Result<()> {
+        match entry {
+            JournalEntry::OrderAccepted(order) => {
+                if self.persister.get_order(&order.id).is_err() {
+                    self.persister.create_order(*order)?;
+                }
+            }
+            JournalEntry::TradeExecuted {
+                buyer_order_id,
+                seller_order_id,
+                price,
+                base_amount,
+                is_buyer_taker,
+                trade_id: Some(trade_id),
+                timestamp,
+            } => {
+                if self.persister.get_trade_detail(&trade_id)?.is_some() {
+                    return Ok(());
+                }
+                let buyer = self
+                    .persister
+                    .get_order(&buyer_order_id)?
+                    .with_context(|| format!("Buy order {} not found", buyer_order_id))?;
+                let seller = self
+                    .persister
+                    .get_order(&seller_order_id)?
+                    .with_context(|| format!("Sell order {} not found", seller_order_id))?;
+                let (buyer_fee_rate, seller_fee_rate) = match is_buyer_taker {
+                    true => (buyer.taker_fee, seller.maker_fee),
+                    false => (buyer.maker_fee, seller.taker_fee),
+                };
+                let fill = TradeFill {
+                    is_buyer_taker,
+                    buyer_user_id: buyer.user_id,
+                    seller_user_id: seller.user_id,
+                    buyer_order_id,
+                    seller_order_id,
+                    quote_amount: &base_amount * &price,
+                    price,
+                    base_amount,
+                    buyer_fee_rate,
+                    seller_fee_rate,
+                    trade_id: Some(trade_id),
+                    timestamp,
+                };
+                self.persister.execute_limit_trades(
+                    &self.market_id,
+                    &self.base_asset,
+                    &self.quote_asset,
+                    &[fill],
+                )?;
+            }
+            JournalEntry::TradeExecuted { trade_id: None, .. } => {}
+            JournalEntry::OrderCanceled { order_id, reason } => {
+                if self.is_open_in_db(&order_id)? {
+                    self.persister.cancel_order(&order_id, reason)?;
+                }
+            }
+            JournalEntry::OrdersCanceled { reason } => {
+                self.persister.cancel_all_orders(&self.market_id, reason)?;
+            }
+            JournalEntry::OrderAmended {
+                order_id,
+                price,
+                remained_base,
+                priority,
+            } => {
+                let Some(order) = self.persister.get_order(&order_id)? else {
+                    return Ok(());
+                };
+                let priority = priority.unwrap_or(order.priority);
+                let amended = order.price == price
+                    && order.remained_base == remained_base
+                    && order.priority == priority;
+                if !amended && self.is_open_in_db(&order_id)? {
+                    self.persister
+                        .amend_order(&order_id, price, remained_base, priority)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn is_open_in_db(&self, order_id: &str) -> Result<bool> {
+        let status = self
+            .persister
+            .get_order(order_id)?
+            .map(|order| order.status);
+        Ok(status.is_some_and(|status| {
+            status == OrderStatus::Open.as_str() || status == OrderStatus::PartiallyFilled.as_str()
+        }))
+    }
+}
@@ -1,44 +1,99 @@
-use super::OrderBook;
+use super::settlement::PendingFills;
+use super::{OrderBook, OrderBookError, StalePricePolicy};
 use crate::models::matched_trade::MatchedTrade;
 use crate::models::trade_order::{OrderSide, OrderType, TradeOrder};
-use bigdecimal::BigDecimal;
-use common::utils::is_zero;
+use bigdecimal::{BigDecimal, Zero};
+use common::utils::{get_utc_now_millis, is_zero, round_amount};
+use database::models::models::{CancelReason, TimeInForce};
 use database::provider::DatabaseProvider;
 
 impl<P: DatabaseProvider> OrderBook<P> {
-    pub fn match_limit_order(
+    pub fn match_limit_order(&mut self, order: TradeOrder) -> anyhow::Result<Vec<MatchedTrade>> {
+        self.match_order(order, PendingFills::default())
+    }
+
+    pub fn match_market_order(&mut self, order: TradeOrder) -> anyhow::Result<Vec<MatchedTrade>> {
+        self.match_market(order, PendingFills::default())
+    }
+
+    /// Matches an order that is not stored yet: it is created with its first fills, in the
+    /// transaction settling them, or on its own if nothing matches.
+    pub(super) fn match_unsaved_order(
+        &mut self,
+        order: TradeOrder,
+    ) -> anyhow::Result<Vec<MatchedTrade>> {
+        let fills = PendingFills::creating(&order);
+        self.match_order(order, fills)
+    }
+
+    fn match_order(
         &mut self,
         mut order: TradeOrder,
+        mut fills: PendingFills,
     ) -> anyhow::Result<Vec<MatchedTrade>> {
+        // Market orders never rest in the book, whichever path they come from
+        if order.order_type == OrderType::Market {
+            return self.match_market(order, fills);
+        }
+
+        // A post-only order only adds liquidity, taking any is refused and its funds released
+        if order.post_only == Some(true) {
+            if let Some(best_price) = self.crossing_price(&order) {
+                // Stored first when it is not yet, the cancel is kept like any other
+                self.settle_fills(&mut fills, &mut order)?;
+                self.cancel_order(order.id, CancelReason::PostOnly)?;
+                return Err(OrderBookError::PostOnlyWouldCross(best_price).into());
+            }
+        }
+
         let mut trades = Vec::new();
+        // Immediate-or-cancel orders take what the book offers now and never rest
+        let immediate_or_cancel = order.time_in_force == Some(TimeInForce::IOC);
+        let mut halted = false;
 
-        Self::print_order(&order);
-        // Add to depth maps before matching
-        self.handle_market_depth(&order);
+        Self::log_order(&order);
         match order.side {
             OrderSide::Buy => {
                 // Try to match the buy order with existing sell orders (asks)
-                while let Some(mut ask) = self.asks.pop() {
+                while let Some(mut ask) = self.asks.pop_best() {
                     // Stop if the ask price is higher than the buy order price for Limit orders
                     if ask.price > order.price {
                         // No more matching asks
-                        self.asks.push(ask); // Push it back to the heap
+                        self.asks.push_front(ask); // Put it back at the head of its level
                         break;
                     }
 
-                    // Calculate the trade amount
+                    // Calculate the trade amount, an iceberg ask offers one slice per fill
                     let trade_price = self.calculate_trade_price(&order, &ask, true)?;
-                    let trade_amount = self.calculate_trade_amount(&order, &ask, &trade_price)?;
+                    let trade_amount = self
+                        .calculate_trade_amount(&order, &ask, &trade_price)?
+                        .min(ask.visible_base());
+                    // Taking all an iceberg shows replenishes it from its hidden rest
+                    let replenished =
+                        ask.display_amount.is_some() && trade_amount == ask.visible_base();
+
+                    if self.halts_matching(&trade_price) {
+                        self.asks.push_front(ask);
+                        halted = true;
+                        break;
+                    }
 
-                    // Execute the trade
-                    let trade =
-                        self.execute_trade(&mut order, &mut ask, trade_amount, trade_price, true)?;
-                    trades.push(trade);
+                    // Execute the trade, settled with the other fills of the order
+                    self.queue_fill(
+                        &mut fills,
+                        &mut order,
+                        &mut ask,
+                        trade_amount,
+                        trade_price,
+                        true,
+                    )?;
 
                     // Remove the ask order if fully filled
                     if !is_zero(&ask.remained_base) {
-                        self.asks.push(ask); // Push the modified ask back into the heap
+                        let priority = replenished.then(|| self.next_priority());
+                        self.asks.requeue(ask, priority);
                     }
+                    trades.extend(self.settle_due_fills(&mut fills, &mut order)?);
 
                     // Stop if the buy order is fully filled
                     if is_zero(&order.remained_base) {
@@ -46,38 +101,59 @@ impl<P: DatabaseProvider> OrderBook<P> {
                     }
                 }
 
-                // Add the remaining buy order to the order book and update depth
+                trades.extend(self.settle_fills(&mut fills, &mut order)?);
+                // Add the remaining buy order to the order book
                 if !is_zero(&order.remained_base) {
-                    self.bids.push(order.clone());
+                    if halted {
+                        // It would cross the book it cannot match against
+                        self.cancel_order(order.id.clone(), CancelReason::CircuitBreaker)?;
+                    } else if immediate_or_cancel {
+                        self.cancel_order(order.id.clone(), CancelReason::Unfilled)?;
+                    } else {
+                        self.bids.push(order.clone());
+                    }
                 }
             }
             OrderSide::Sell => {
                 // Try to match the sell order with existing buy orders (bids)
-                while let Some(mut bid) = self.bids.pop() {
+                while let Some(mut bid) = self.bids.pop_best() {
                     // Stop if the bid price is lower than the sell order price for Limit orders
                     if bid.price < order.price {
                         // No more matching bids
-                        self.bids.push(bid); // Push it back to the heap
+                        self.bids.push_front(bid); // Put it back at the head of its level
                         break;
                     }
 
                     let trade_price = self.calculate_trade_price(&bid, &order, false)?;
-                    // Calculate the trade amount
-                    let trade_amount = self.calculate_trade_amount(&bid, &order, &trade_price)?;
+                    // Calculate the trade amount, an iceberg bid offers one slice per fill
+                    let trade_amount = self
+                        .calculate_trade_amount(&bid, &order, &trade_price)?
+                        .min(bid.visible_base());
+                    // Taking all an iceberg shows replenishes it from its hidden rest
+                    let replenished =
+                        bid.display_amount.is_some() && trade_amount == bid.visible_base();
+
+                    if self.halts_matching(&trade_price) {
+                        self.bids.push_front(bid);
+                        halted = true;
+                        break;
+                    }
 
-                    // Execute the trade
-                    let trade = self.execute_trade(
+                    // Execute the trade, settled with the other fills of the order
+                    self.queue_fill(
+                        &mut fills,
                         &mut bid,
                         &mut order,
-                        trade_amount.clone(),
+                        trade_amount,
                         trade_price,
                         false,
                     )?;
-                    trades.push(trade);
 
                     if !is_zero(&bid.remained_base) {
-                        self.bids.push(bid); // Push the modified bid back into the heap
+                        let priority = replenished.then(|| self.next_priority());
+                        self.bids.requeue(bid, priority);
                     }
+                    trades.extend(self.settle_due_fills(&mut fills, &mut order)?);
 
                     // Stop if the sell order is fully filled
                     if is_zero(&order.remained_base) {
@@ -85,41 +161,89 @@ impl<P: DatabaseProvider> OrderBook<P> {
                     }
                 }
 
-                // Add the remaining sell order to the order book and update depth
+                trades.extend(self.settle_fills(&mut fills, &mut order)?);
+                // Add the remaining sell order to the order book
                 if !is_zero(&order.remained_base) {
-                    self.asks.push(order.clone());
+                    if halted {
+                        self.cancel_order(order.id.clone(), CancelReason::CircuitBreaker)?;
+                    } else if immediate_or_cancel {
+                        self.cancel_order(order.id.clone(), CancelReason::Unfilled)?;
+                    } else {
+                        self.asks.push(order.clone());
+                    }
                 }
             }
         }
-        self.print_order_book();
+        self.log_order_book();
         Ok(trades)
     }
 
-    pub fn match_market_order(
+    /// Best price on the opposite side of the book, if `order` would trade against it.
+    pub fn crossing_price(&self, order: &TradeOrder) -> Option<BigDecimal> {
+        match order.side {
+            OrderSide::Buy => self
+                .asks
+                .best()
+                .filter(|ask| ask.price <= order.price)
+                .map(|ask| ask.price.clone()),
+            OrderSide::Sell => self
+                .bids
+                .best()
+                .filter(|bid| bid.price >= order.price)
+                .map(|bid| bid.price.clone()),
+        }
+    }
+
+    fn match_market(
         &mut self,
         mut order: TradeOrder,
+        mut fills: PendingFills,
     ) -> anyhow::Result<Vec<MatchedTrade>> {
         let mut trades = Vec::new();
+        let mut halted = false;
 
-        Self::print_order(&order);
+        Self::log_order(&order);
 
         match order.side {
             OrderSide::Buy => {
                 // Try to match the buy order with existing sell orders (asks)
-                while let Some(mut ask) = self.asks.pop() {
-                    // Calculate the trade amount
+                while let Some(mut ask) = self.asks.pop_best() {
+                    // Calculate the trade amount, an iceberg ask offers one slice per fill
                     let trade_price = self.calculate_trade_price(&order, &ask, true)?;
-                    let trade_amount = self.calculate_trade_amount(&order, &ask, &trade_price)?;
+                    let trade_amount = self
+                        .calculate_trade_amount(&order, &ask, &trade_price)?
+                        .min(ask.visible_base());
+                    // Taking all an iceberg shows replenishes it from its hidden rest
+                    let replenished =
+                        ask.display_amount.is_some() && trade_amount == ask.visible_base();
+
+                    // A spent quote budget buys nothing more, the rest is canceled below
+                    if is_zero(&trade_amount) {
+                        self.asks.push_front(ask);
+                        break;
+                    }
+                    if self.halts_matching(&trade_price) {
+                        self.asks.push_front(ask);
+                        halted = true;
+                        break;
+                    }
 
-                    // Execute the trade
-                    let trade =
-                        self.execute_trade(&mut order, &mut ask, trade_amount, trade_price, true)?;
-                    trades.push(trade);
+                    // Execute the trade, settled with the other fills of the order
+                    self.queue_fill(
+                        &mut fills,
+                        &mut order,
+                        &mut ask,
+                        trade_amount,
+                        trade_price,
+                        true,
+                    )?;
 
                     // Remove the ask order if fully filled
                     if !is_zero(&ask.remained_base) {
-                        self.asks.push(ask); // Push the modified ask back into the heap
+                        let priority = replenished.then(|| self.next_priority());
+                        self.asks.requeue(ask, priority);
                     }
+                    trades.extend(self.settle_due_fills(&mut fills, &mut order)?);
 
                     // Stop if the buy order is fully filled
                     if is_zero(&order.remained_base) {
@@ -127,31 +251,45 @@ impl<P: DatabaseProvider> OrderBook<P> {
                     }
                 }
 
+                trades.extend(self.settle_fills(&mut fills, &mut order)?);
                 // Cancel the MARKET order if not fully filled , we don't keep it in the order book
                 if !is_zero(&order.remained_base) {
-                    self.cancel_order(order.id)?;
+                    self.cancel_order(order.id, Self::unfilled_reason(halted))?;
                 }
             }
             OrderSide::Sell => {
                 // Try to match the sell order with existing buy orders (bids)
-                while let Some(mut bid) = self.bids.pop() {
+                while let Some(mut bid) = self.bids.pop_best() {
                     let trade_price = self.calculate_trade_price(&bid, &order, false)?;
-                    // Calculate the trade amount
-                    let trade_amount = self.calculate_trade_amount(&bid, &order, &trade_price)?;
+                    // Calculate the trade amount, an iceberg bid offers one slice per fill
+                    let trade_amount = self
+                        .calculate_trade_amount(&bid, &order, &trade_price)?
+                        .min(bid.visible_base());
+                    // Taking all an iceberg shows replenishes it from its hidden rest
+                    let replenished =
+                        bid.display_amount.is_some() && trade_amount == bid.visible_base();
+
+                    if self.halts_matching(&trade_price) {
+                        self.bids.push_front(bid);
+                        halted = true;
+                        break;
+                    }
 
-                    // Execute the trade
-                    let trade = self.execute_trade(
+                    // Execute the trade, settled with the other fills of the order
+                    self.queue_fill(
+                        &mut fills,
                         &mut bid,
                         &mut order,
-                        trade_amount.clone(),
+                        trade_amount,
                         trade_price,
                         false,
                     )?;
-                    trades.push(trade);
 
                     if !is_zero(&bid.remained_base) {
-                        self.bids.push(bid); // Push the modified bid back into the heap
+                        let priority = replenished.then(|| self.next_priority());
+                        self.bids.requeue(bid, priority);
                     }
+                    trades.extend(self.settle_due_fills(&mut fills, &mut order)?);
 
                     // Stop if the sell order is fully filled
                     if is_zero(&order.remained_base) {
@@ -159,138 +297,105 @@ impl<P: DatabaseProvider> OrderBook<P> {
                     }
                 }
 
+                trades.extend(self.settle_fills(&mut fills, &mut order)?);
                 // Cancel the MARKET order if not fully filled , we don't keep it in the order book
                 if !is_zero(&order.remained_base) {
-                    self.cancel_order(order.id)?;
+                    self.cancel_order(order.id, Self::unfilled_reason(halted))?;
                 }
             }
         }
-        self.print_order_book();
+        self.log_order_book();
         Ok(trades)
     }
 
     pub fn match_fok_order(&mut self, order: TradeOrder) -> anyhow::Result<Vec<MatchedTrade>> {
-        let mut pop_orders: Vec<TradeOrder> = Vec::new();
-        let mut is_fully_matched = false;
-        let mut tem_order = order.clone();
-        match order.side {
-            OrderSide::Buy => {
-                while let Some(ask) = self.asks.pop() {
-                    if ask.price > order.price {
-                        self.asks.push(ask);
-                        break;
-                    }
-                    pop_orders.push(ask.clone());
+        if !self.can_fill_completely(&order) {
+            self.cancel_order(order.id, CancelReason::Unfilled)?;
+            return Err(anyhow::anyhow!("FOK order not fully matched"));
+        }
+        self.match_limit_order(order)
+    }
 
-                    let trade_price = self.calculate_trade_price(&tem_order, &ask, true)?;
-                    let trade_amount =
-                        self.calculate_trade_amount(&tem_order, &ask, &trade_price)?;
+    /// Walks the opposite side of the book from the best price without touching it, and
+    /// tells whether `order` would be filled completely.
+    ///
+    /// A market buy spends a quote budget, so it is fillable only if buying its whole base
+    /// amount across the price levels costs no more than its remaining quote.
+    pub fn can_fill_completely(&self, order: &TradeOrder) -> bool {
+        // Both sides iterate from the best price
+        let levels = match order.side {
+            OrderSide::Buy => self.asks.iter(),
+            OrderSide::Sell => self.bids.iter(),
+        };
 
-                    tem_order.remained_base = &tem_order.remained_base - &trade_amount;
-                    tem_order.remained_quote =
-                        &tem_order.remained_quote - &trade_amount * &trade_price;
-                    if is_zero(&tem_order.remained_base) {
-                        is_fully_matched = true;
-                        break;
-                    }
-                }
+        let mut needed_base = order.remained_base.clone();
+        let mut cost = BigDecimal::zero();
+        for level in levels {
+            let crosses = match (order.order_type, order.side) {
+                (OrderType::Market, _) => true,
+                (OrderType::Limit, OrderSide::Buy) => level.price <= order.price,
+                (OrderType::Limit, OrderSide::Sell) => level.price >= order.price,
+            };
+            if !crosses {
+                break;
             }
-            OrderSide::Sell => {
-                while let Some(bid) = self.bids.pop() {
-                    if bid.price < order.price {
-                        self.bids.push(bid);
-                        break;
-                    }
-                    pop_orders.push(bid.clone());
-                    let trade_price = self.calculate_trade_price(&bid, &tem_order, false)?;
-                    let trade_amount =
-                        self.calculate_trade_amount(&bid, &tem_order, &trade_price)?;
-
-                    tem_order.remained_base = &tem_order.remained_base - &trade_amount;
-                    tem_order.remained_quote =
-                        &tem_order.remained_quote - &trade_amount * &trade_price;
-                    if is_zero(&tem_order.remained_base) {
-                        is_fully_matched = true;
-                        break;
-                    }
-                }
+
+            let fill = needed_base.clone().min(level.remained_base.clone());
+            cost += &fill * &level.price;
+            needed_base -= fill;
+            if is_zero(&needed_base) {
+                break;
             }
         }
-        for order in pop_orders {
-            self.asks.push(order);
+
+        if !is_zero(&needed_base) {
+            return false;
         }
-        if !is_fully_matched {
-            self.cancel_order(order.id)?;
-            return Err(anyhow::anyhow!("FOK order not fully matched"));
-        } else {
-            return self.match_limit_order(order);
+        match (order.order_type, order.side) {
+            (OrderType::Market, OrderSide::Buy) => cost <= order.remained_quote,
+            _ => true,
         }
     }
 
-    pub fn execute_trade(
-        &mut self,
-        buyer: &mut TradeOrder,
-        seller: &mut TradeOrder,
-        base_amount: BigDecimal,
-        trade_price: BigDecimal,
-        is_buyer_taker: bool,
-    ) -> anyhow::Result<MatchedTrade> {
-        // Calculate the fees for the buyer and seller
-        let (buyer_fee, seller_fee) = match is_buyer_taker {
-            true => (buyer.taker_fee.clone(), seller.maker_fee.clone()),
-            false => (buyer.maker_fee.clone(), seller.taker_fee.clone()),
-        };
+    /// Asks the circuit breaker before a trade at `trade_price`: true while matching is halted,
+    /// or when this trade moves the price far enough to halt it.
+    fn halts_matching(&mut self, trade_price: &BigDecimal) -> bool {
+        let now = get_utc_now_millis();
+        self.circuit_breaker.halted_until(now).is_some()
+            || !self.circuit_breaker.admit_trade(trade_price, now)
+    }
+
+    fn unfilled_reason(halted: bool) -> CancelReason {
+        match halted {
+            true => CancelReason::CircuitBreaker,
+            false => CancelReason::Unfilled,
+        }
+    }
 
-        // Calculate the trade quote amount
-        let trade_quote_amount = base_amount.clone() * trade_price.clone();
-
-        // Execute the trade in a transaction
-        let trade_data = self.persister.execute_limit_trade(
-            is_buyer_taker,
-            self.market_id.clone(),
-            self.base_asset.clone(),
-            self.quote_asset.clone(),
-            buyer.user_id.clone(),
-            seller.user_id.clone(),
-            buyer.id.clone(),
-            seller.id.clone(),
-            trade_price.clone(),
-            base_amount,
-            trade_quote_amount,
-            buyer_fee,
-            seller_fee,
-        )?;
-
-        *buyer = self.persister.get_order(&buyer.id)?.unwrap().try_into()?;
-        *seller = self.persister.get_order(&seller.id)?.unwrap().try_into()?;
-
-        // Update the market price
-        self.market_price = Some(trade_price);
-        let is_liquidation = trade_data.is_liquidation.unwrap_or(false);
-        self.handle_market_depth(&buyer);
-        self.handle_market_depth(&seller);
-        // Construct the trade object
-        let trade = MatchedTrade {
-            id: trade_data.id,
-            timestamp: trade_data.timestamp,
-            market_id: trade_data.market_id,
-            price: trade_data.price,
-            base_amount: trade_data.base_amount,
-            quote_amount: trade_data.quote_amount,
-            buyer_user_id: trade_data.buyer_user_id,
-            buyer_order_id: trade_data.buyer_order_id,
-            buyer_fee: trade_data.buyer_fee,
-            seller_user_id: trade_data.seller_user_id,
-            seller_order_id: trade_data.seller_order_id,
-            seller_fee: trade_data.seller_fee,
-            is_liquidation,
-            taker_side: trade_data.taker_side.into(),
+    /// Tells whether `trade_price` is within the configured collar around the last traded
+    /// price. Without a collar or a previous trade every price passes.
+    pub fn is_within_price_collar(&self, trade_price: &BigDecimal) -> bool {
+        let (Some(collar), Some(reference)) = (self.effective_price_collar(), &self.market_price)
+        else {
+            return true;
         };
+        let max_deviation = reference * collar / BigDecimal::from(100);
+        (trade_price - reference).abs() <= max_deviation
+    }
 
-        // Log trade execution
-        Self::print_trade(&trade);
-        // everything is done inside execute trade function so no need to call these functions her
-        Ok(trade)
+    /// The collar to hold trades to right now: a quiet market's last price may be far from
+    /// where it would trade today, so a stale reference relaxes the collar per the policy.
+    fn effective_price_collar(&self) -> Option<BigDecimal> {
+        let collar = self.price_collar.clone()?;
+        let is_stale = match (self.market_price_max_age_ms, self.market_price_time) {
+            (Some(max_age_ms), Some(price_time)) => get_utc_now_millis() - price_time > max_age_ms,
+            _ => false,
+        };
+        match (is_stale, &self.stale_price_policy) {
+            (false, _) => Some(collar),
+            (true, StalePricePolicy::Disable) => None,
+            (true, StalePricePolicy::Widen(factor)) => Some(collar * factor),
+        }
     }
 
     pub fn calculate_trade_price(
@@ -337,9 +442,14 @@ impl<P: DatabaseProvider> OrderBook<P> {
         trade_price: &BigDecimal,
     ) -> anyhow::Result<BigDecimal> {
         if buyer.order_type == OrderType::Market {
-            Ok((buyer.remained_quote.clone() / trade_price.clone())
-                .with_prec(8)
-                .min(seller.remained_base.clone()))
+            // The quote budget bounds a market buy, but it never buys more than it asked for.
+            // Every fill is applied to the buyer as it is queued, so this is the budget left
+            // after the levels already taken, not the one the order was placed with.
+            Ok(
+                round_amount(&(buyer.remained_quote.clone() / trade_price.clone()))
+                    .min(seller.remained_base.clone())
+                    .min(buyer.remained_base.clone()),
+            )
         } else {
             Ok(seller
                 .remained_base
@@ -1,26 +1,103 @@
-use crate::models::trade_order::TradeOrder;
+use crate::models::matched_trade::SequencedTrade;
+use crate::models::user_event::UserEvent;
 use bigdecimal::BigDecimal;
+use book_side::BookSide;
+use circuit_breaker::CircuitBreaker;
 use database::provider::DatabaseProvider;
-use std::collections::{BinaryHeap, HashMap};
+use depth_diff::{DepthDelta, DepthSnapshot};
+use settlement_writer::SettlementWriter;
+use std::collections::{HashMap, VecDeque};
 use std::sync::Arc;
+use tokio::sync::broadcast;
 
 #[derive(Debug, Clone)]
 pub struct OrderBook<P>
 where
     P: DatabaseProvider + 'static,
 {
-    bids: BinaryHeap<TradeOrder>, // Max-heap for bids (buy orders)
-    asks: BinaryHeap<TradeOrder>, // Min-heap for asks (sell orders)
-    bid_depth: HashMap<BigDecimal, BigDecimal>, // Price -> Total Amount
-    ask_depth: HashMap<BigDecimal, BigDecimal>, // Price -> Total Amount
+    bids: BookSide, // Buy orders, highest price first
+    asks: BookSide, // Sell orders, lowest price first
     persister: Arc<P>,
     market_price: Option<BigDecimal>,
+    /// When `market_price` was last set, in milliseconds
+    market_price_time: Option<i64>,
+    /// Largest deviation, in percent of `market_price`, a trade price may have
+    price_collar: Option<BigDecimal>,
+    /// Whether new orders are stored with their first fills, see [`OrderBook::set_atomic_placement`]
+    atomic_placement: bool,
+    /// Stores trades behind the book when set, see [`OrderBook::set_async_settlement`]
+    settlement_writer: Option<Arc<SettlementWriter>>,
+    /// Age after which `market_price` is too old to hold trades to the collar as is
+    market_price_max_age_ms: Option<i64>,
+    stale_price_policy: StalePricePolicy,
+    /// Price band of new limit orders and the halt of matching after a fast move
+    circuit_breaker: CircuitBreaker,
+    /// Last executed trades, oldest first, bounded by `recent_trades_capacity`. They are also
+    /// what `SubscribeTrades` replays to a reconnecting subscriber.
+    recent_trades: VecDeque<SequencedTrade>,
+    recent_trades_capacity: usize,
+    /// Sequence of the last executed trade
+    trade_sequence: u64,
+    /// Priority last given to an order joining the book, see [`TradeOrder::priority`]
+    order_priority: i64,
+    /// Executed trades pushed to `SubscribeTrades` streams
+    trade_updates: broadcast::Sender<SequencedTrade>,
+    /// Order events of all markets, pushed to `SubscribeUserEvents` streams
+    user_events: broadcast::Sender<UserEvent>,
+    /// Open OCO legs mapped to the other leg of their group, both directions
+    oco_siblings: HashMap<String, String>,
+    /// Depth changes pushed to `SubscribeOrderBook` streams
+    depth_updates: broadcast::Sender<DepthDelta>,
+    /// Depth as subscribers last saw it, kept only while there are subscribers
+    published_depth: Option<(DepthSnapshot, u64)>,
     base_asset: String,
     quote_asset: String,
     market_id: String,
 }
 
+/// Order book failures callers may want to tell apart
+#[derive(Debug, thiserror::Error)]
+pub enum OrderBookError {
+    #[error("Post-only order would trade immediately against the book at {0}")]
+    PostOnlyWouldCross(BigDecimal),
+    #[error("Client order ID {0} is already used by another order of the user")]
+    DuplicateClientOrderId(String),
+    #[error("Price {price} is more than {band}% away from the last traded price {reference}")]
+    OutsidePriceBand {
+        price: BigDecimal,
+        reference: BigDecimal,
+        band: BigDecimal,
+    },
+    #[error("Matching is halted by the circuit breaker until {0}")]
+    MatchingHalted(i64),
+    #[error("Order would take the user past the limit of {0} open orders in this market")]
+    OpenOrderLimit(i32),
+    #[error("Order would lock {notional} in this market, more than the user limit of {limit}")]
+    NotionalLimit {
+        notional: BigDecimal,
+        limit: BigDecimal,
+    },
+}
+
+/// What the price collar does once the last traded price is older than its maximum age
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum StalePricePolicy {
+    /// Trades are no longer checked against the stale price
+    #[default]
+    Disable,
+    /// The collar is multiplied by the given factor
+    Widen(BigDecimal),
+}
+
+pub mod book_side;
+pub mod circuit_breaker;
+pub mod depth_diff;
+pub mod journal;
 mod logger;
-mod market_depth;
 mod matching;
+#[allow(clippy::module_inception)]
 pub mod order_book;
+pub mod settlement;
+pub mod settlement_writer;
+pub mod snapshot;
+pub mod user_events;
@@ -1,61 +1,163 @@
-use crate::models::matched_trade::MatchedTrade;
+use crate::market::DEFAULT_RECENT_TRADES_CAPACITY;
+use crate::metrics::{BOOK_ORDERS, MATCH_DURATION, ORDERS_ADDED, ORDERS_CANCELED};
+use crate::models::matched_trade::{MatchedTrade, SequencedTrade};
 use crate::models::trade_order::{OrderSide, OrderType, TradeOrder};
-use anyhow::Result;
+use anyhow::{Context, Result};
 use bigdecimal::BigDecimal;
-use database::models::models::NewOrder;
+use common::utils::get_utc_now_millis;
+use database::models::models::{CancelReason, NewOcoGroup, NewOrder, OcoGroup, Order, OrderStatus};
 use database::provider::DatabaseProvider;
-use std::collections::{BinaryHeap, HashMap};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::Arc;
+use tokio::sync::broadcast;
+use tracing::{info, instrument};
+use uuid::Uuid;
 
-use super::OrderBook;
+use super::book_side::BookSide;
+use super::circuit_breaker::{CircuitBreaker, PriceBandConfig};
+use super::depth_diff::DEPTH_UPDATES_CAPACITY;
+use super::journal::JournalEntry;
+use super::settlement_writer::SettlementWriter;
+use super::user_events::USER_EVENTS_CAPACITY;
+use super::{OrderBook, OrderBookError, StalePricePolicy};
+
+/// Trades buffered per `SubscribeTrades` stream before a slow subscriber is dropped
+pub const TRADE_UPDATES_CAPACITY: usize = 1024;
 
 impl<P: DatabaseProvider> OrderBook<P> {
-    /// Add a new order asynchronously
+    /// Builds the order book of a market and loads it: its price band, the journal entries
+    /// past its checkpoint, then its open orders from the last snapshot or the orders table.
+    /// Fails if any of them can't be read or applied.
     pub fn new(
         persister: Arc<P>,
         base_asset: String,
         market_id: String,
         quote_asset: String,
-    ) -> Self {
+    ) -> Result<Self> {
         let mut order_book = OrderBook {
-            bids: BinaryHeap::new(),
-            asks: BinaryHeap::new(),
-            bid_depth: HashMap::new(),
-            ask_depth: HashMap::new(),
+            bids: BookSide::new(OrderSide::Buy),
+            asks: BookSide::new(OrderSide::Sell),
             base_asset,
             quote_asset,
             market_id,
             persister,
             market_price: None,
+            market_price_time: None,
+            price_collar: None,
+            atomic_placement: false,
+            settlement_writer: None,
+            market_price_max_age_ms: None,
+            stale_price_policy: StalePricePolicy::default(),
+            circuit_breaker: CircuitBreaker::default(),
+            recent_trades: VecDeque::new(),
+            recent_trades_capacity: DEFAULT_RECENT_TRADES_CAPACITY,
+            trade_sequence: 0,
+            order_priority: 0,
+            user_events: broadcast::channel(USER_EVENTS_CAPACITY).0,
+            trade_updates: broadcast::channel(TRADE_UPDATES_CAPACITY).0,
+            oco_siblings: HashMap::new(),
+            depth_updates: broadcast::channel(DEPTH_UPDATES_CAPACITY).0,
+            published_depth: None,
         };
 
-        order_book.recover_orders_from_db().unwrap();
+        let price_band = order_book
+            .persister
+            .get_price_band(&order_book.market_id)
+            .context("Failed to read the price band")?;
+        order_book.set_price_band(
+            price_band
+                .as_ref()
+                .map(PriceBandConfig::from)
+                .unwrap_or_default(),
+        );
         order_book
+            .replay_journal()
+            .context("Failed to replay the journal")?;
+        let restored = order_book
+            .restore_from_snapshot()
+            .context("Failed to restore the snapshot")?;
+        if !restored {
+            order_book
+                .recover_orders_from_db()
+                .context("Failed to recover open orders")?;
+        }
+        Ok(order_book)
     }
 
     pub fn recover_orders_from_db(&mut self) -> Result<()> {
         let orders = self.persister.get_active_orders(&self.market_id)?;
         let orders_len = orders.len();
+        self.recover_orders(orders)?;
+        info!(market_id = %self.market_id, "Loaded {} orders from database", orders_len);
+        Ok(())
+    }
 
-        // Clear existing depth data
-        self.bid_depth.clear();
-        self.ask_depth.clear();
+    /// Links the open OCO groups of the market, then matches `orders` into the book as if
+    /// they had just arrived, in the priority they were stored with.
+    pub(super) fn recover_orders(&mut self, mut orders: Vec<Order>) -> Result<()> {
+        // Each price level queues orders as they arrive, so they have to arrive first in line
+        // first. An amendment may have sent an order behind ones created after it.
+        orders.sort_by_key(|order| (order.priority, order.create_time));
+        self.keep_priorities_above(&orders);
+
+        self.oco_siblings.clear();
+        for group in self.persister.get_active_oco_groups(&self.market_id)? {
+            self.link_oco_legs(&group.first_order_id, &group.second_order_id);
+        }
+        let grouped: HashSet<String> = self.oco_siblings.keys().cloned().collect();
 
         for order in orders {
+            // A fill earlier in the recovery may have canceled this leg already
+            if grouped.contains(&order.id) && !self.oco_siblings.contains_key(&order.id) {
+                continue;
+            }
             let trade_order: TradeOrder = order.try_into()?;
             if trade_order.order_type == OrderType::Limit {
                 self.match_limit_order(trade_order)?;
             } else {
-                self.cancel_order(trade_order.id)?;
+                // Market orders never rest in the book, whatever is left of them is dropped
+                self.cancel_order(trade_order.id, CancelReason::Unfilled)?;
             }
         }
-        println!("Loaded {} orders from database", orders_len);
         Ok(())
     }
 
-    pub fn add_order(&mut self, order: TradeOrder) -> anyhow::Result<Vec<MatchedTrade>> {
+    #[instrument(
+        skip_all,
+        fields(order_id = %order.id, market_id = %self.market_id, user_id = %order.user_id)
+    )]
+    pub fn add_order(&mut self, mut order: TradeOrder) -> anyhow::Result<Vec<MatchedTrade>> {
+        let _timer = MATCH_DURATION
+            .with_label_values(&[&self.market_id])
+            .start_timer();
+        // Risk limits and the funds the order locks are read from the database
+        self.await_settlement([order.user_id.as_str()])?;
+        let checked = Self::validate_amounts(&order)
+            .and_then(|()| self.check_price_protection(&order))
+            .and_then(|()| self.check_risk_limits(&[&order]));
+        if let Err(e) = checked {
+            self.reject_order(&order, &e);
+            return Err(e);
+        }
+        order.priority = self.next_priority();
+
+        if self.atomic_placement && self.settlement_writer.is_none() {
+            self.check_client_order_id(&order)?;
+            ORDERS_ADDED.with_label_values(&[&self.market_id]).inc();
+            return self.match_unsaved_order(order);
+        }
+        self.persist_create_order(&order)?;
+        ORDERS_ADDED.with_label_values(&[&self.market_id]).inc();
+        if order.order_type == OrderType::Limit {
+            self.match_limit_order(order)
+        } else {
+            self.match_market_order(order)
+        }
+    }
+
+    fn validate_amounts(order: &TradeOrder) -> anyhow::Result<()> {
         // Validate order based on price, amount and quote_amount
-        if order.order_type == OrderType::Limit && order.price <= BigDecimal::from(0) {
+        if order.order_type == OrderType::Limit && order.price <= 0 {
             return Err(anyhow::anyhow!(
                 "Price must be greater than 0 for limit orders"
             ));
@@ -63,195 +165,520 @@ impl<P: DatabaseProvider> OrderBook<P> {
 
         match order.side {
             OrderSide::Buy => {
-                if order.quote_amount <= BigDecimal::from(0) {
+                if order.quote_amount <= 0 {
                     return Err(anyhow::anyhow!("Quote amount must be greater than 0"));
                 }
             }
             OrderSide::Sell => {
-                if order.base_amount <= BigDecimal::from(0) {
+                if order.base_amount <= 0 {
                     return Err(anyhow::anyhow!("Amount must be greater than 0"));
                 }
             }
         }
+        Ok(())
+    }
 
-        Self::print_order(&order);
-        println!("persist_create_order");
-        self.persist_create_order(&order)?;
-        println!("match_order: {:?}", order);
+    /// Refuses new orders while the circuit breaker halts matching, and limit prices outside
+    /// the band around the last traded price.
+    fn check_price_protection(&mut self, order: &TradeOrder) -> anyhow::Result<()> {
+        if let Some(until) = self.circuit_breaker.halted_until(get_utc_now_millis()) {
+            return Err(OrderBookError::MatchingHalted(until).into());
+        }
         if order.order_type == OrderType::Limit {
-            self.match_limit_order(order)
-        } else {
-            self.match_market_order(order)
+            self.circuit_breaker
+                .check_band(&order.price, self.market_price.as_ref())?;
         }
+        Ok(())
     }
 
-    pub fn cancel_order(&mut self, order_id: String) -> anyhow::Result<bool> {
-        self.persister.cancel_order(&order_id)?;
+    /// Refuses limit orders that would take their user past the risk limits of the market.
+    /// The legs of an OCO order are counted together.
+    fn check_risk_limits(&self, orders: &[&TradeOrder]) -> anyhow::Result<()> {
+        let resting: Vec<&TradeOrder> = orders
+            .iter()
+            .copied()
+            .filter(|order| order.order_type == OrderType::Limit)
+            .collect();
+        let Some(user_id) = resting.first().map(|order| &order.user_id) else {
+            return Ok(());
+        };
+        let Some(limit) = self.persister.get_risk_limit(&self.market_id)? else {
+            return Ok(());
+        };
 
-        // Find and update bid depth if needed
-        if let Some(index) = self.bids.iter().position(|o| o.id == order_id) {
-            let order = self.bids.iter().nth(index).unwrap().clone();
-            self.handle_market_depth(&order);
-            return Ok(true);
+        if let Some(max_open_orders) = limit.max_open_orders {
+            let open = self
+                .persister
+                .get_user_active_orders_count(user_id, &self.market_id)?;
+            if open + resting.len() as i64 > i64::from(max_open_orders) {
+                return Err(OrderBookError::OpenOrderLimit(max_open_orders).into());
+            }
+        }
+        if let Some(max_locked_notional) = &limit.max_locked_notional {
+            let locked = self
+                .persister
+                .get_user_locked_notional(user_id, &self.market_id)?;
+            let notional = resting
+                .iter()
+                .fold(locked, |total, order| match order.side {
+                    OrderSide::Buy => total + &order.quote_amount,
+                    OrderSide::Sell => total + &order.base_amount * &order.price,
+                });
+            if &notional > max_locked_notional {
+                return Err(OrderBookError::NotionalLimit {
+                    notional: notional.normalized(),
+                    limit: max_locked_notional.normalized(),
+                }
+                .into());
+            }
         }
+        Ok(())
+    }
 
-        // Find and update ask depth if needed
-        if let Some(index) = self.asks.iter().position(|o| o.id == order_id) {
-            let order = self.asks.iter().nth(index).unwrap().clone();
-            self.handle_market_depth(&order);
-            return Ok(true);
+    /// Refuses an amendment that takes its user past the locked notional limit of the market.
+    /// The order counts at its amended size in place of its current one, and an amendment that
+    /// locks less than the order did is always let through.
+    fn check_amend_risk_limits(
+        &self,
+        current: &TradeOrder,
+        amended: &TradeOrder,
+    ) -> anyhow::Result<()> {
+        let Some(limit) = self.persister.get_risk_limit(&self.market_id)? else {
+            return Ok(());
+        };
+        let Some(max_locked_notional) = &limit.max_locked_notional else {
+            return Ok(());
+        };
+        let locked_notional = |order: &TradeOrder| match order.side {
+            OrderSide::Buy => order.remained_quote.clone(),
+            OrderSide::Sell => &order.remained_base * &order.price,
+        };
+        let (before, after) = (locked_notional(current), locked_notional(amended));
+        if after <= before {
+            return Ok(());
+        }
+
+        let notional = self
+            .persister
+            .get_user_locked_notional(&current.user_id, &self.market_id)?
+            - before
+            + after;
+        if &notional > max_locked_notional {
+            return Err(OrderBookError::NotionalLimit {
+                notional: notional.normalized(),
+                limit: max_locked_notional.normalized(),
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Places the two legs of an OCO group, where filling or canceling one leg cancels the
+    /// other. The first leg is matched first, the second only if that left it open.
+    #[instrument(
+        skip_all,
+        fields(
+            order_id = %first.id,
+            second_order_id = %second.id,
+            market_id = %self.market_id,
+            user_id = %first.user_id
+        )
+    )]
+    pub fn add_oco_order(
+        &mut self,
+        mut first: TradeOrder,
+        mut second: TradeOrder,
+    ) -> anyhow::Result<(OcoGroup, Vec<MatchedTrade>, Vec<MatchedTrade>)> {
+        if first.order_type != OrderType::Limit || second.order_type != OrderType::Limit {
+            return Err(anyhow::anyhow!(
+                "Both legs of an OCO order must be limit orders"
+            ));
+        }
+        self.await_settlement([first.user_id.as_str()])?;
+        let checked = Self::validate_amounts(&first)
+            .and(Self::validate_amounts(&second))
+            .and_then(|()| self.check_price_protection(&first))
+            .and_then(|()| self.check_price_protection(&second))
+            .and_then(|()| self.check_risk_limits(&[&first, &second]));
+        if let Err(e) = checked {
+            for leg in [&first, &second] {
+                self.reject_order(leg, &e);
+            }
+            return Err(e);
+        }
+        first.priority = self.next_priority();
+        second.priority = self.next_priority();
+
+        self.persist_create_order(&first)?;
+        if let Err(e) = self.persist_create_order(&second) {
+            self.persist_cancel(&first.id, CancelReason::OneCancelsOther)?;
+            return Err(e);
+        }
+        let group = match self.persister.create_oco_group(NewOcoGroup {
+            id: Uuid::new_v4().to_string(),
+            market_id: self.market_id.clone(),
+            user_id: first.user_id.clone(),
+            first_order_id: first.id.clone(),
+            second_order_id: second.id.clone(),
+            create_time: get_utc_now_millis(),
+        }) {
+            Ok(group) => group,
+            Err(e) => {
+                self.persist_cancel(&first.id, CancelReason::OneCancelsOther)?;
+                self.persist_cancel(&second.id, CancelReason::OneCancelsOther)?;
+                return Err(e);
+            }
+        };
+        self.link_oco_legs(&first.id, &second.id);
+
+        let first_trades = self.match_limit_order(first)?;
+        // A fill of the first leg has canceled the second one in the database already
+        let second_trades = if self.oco_siblings.contains_key(&second.id) {
+            self.match_limit_order(second)?
+        } else {
+            Vec::new()
+        };
+        Ok((group, first_trades, second_trades))
+    }
+
+    fn link_oco_legs(&mut self, first_id: &str, second_id: &str) {
+        self.oco_siblings
+            .insert(first_id.to_string(), second_id.to_string());
+        self.oco_siblings
+            .insert(second_id.to_string(), first_id.to_string());
+    }
+
+    /// Takes the other leg of `order_id`'s OCO group off the book. The database cancels it
+    /// together with the fill or cancel of `order_id`, this only catches the book up.
+    pub(super) fn release_oco_sibling(&mut self, order_id: &str) {
+        let Some(sibling_id) = self.oco_siblings.remove(order_id) else {
+            return;
+        };
+        self.oco_siblings.remove(&sibling_id);
+        self.bids.remove(&sibling_id);
+        self.asks.remove(&sibling_id);
+        self.publish_canceled_by_id(&sibling_id, &CancelReason::OneCancelsOther);
+    }
+
+    #[instrument(skip(self), fields(market_id = %self.market_id))]
+    pub fn cancel_order(&mut self, order_id: String, reason: CancelReason) -> anyhow::Result<bool> {
+        self.persist_cancel(&order_id, reason)?;
+        ORDERS_CANCELED.with_label_values(&[&self.market_id]).inc();
+        self.release_oco_sibling(&order_id);
+
+        // Tells whether the order was resting in the book
+        let removed = self
+            .bids
+            .remove(&order_id)
+            .or_else(|| self.asks.remove(&order_id));
+        Ok(removed.is_some())
+    }
+
+    /// Changes the price and/or remaining amount of a resting order, `None` keeping the
+    /// current value. Reducing the amount keeps the order's place in its price level; a new
+    /// price or a larger amount sends it to the back of the level it lands on, where it may
+    /// first match like a new order if the price crosses the book. An amendment that locks
+    /// more is held to the market's locked notional limit.
+    #[instrument(skip(self, price, remained_base), fields(market_id = %self.market_id))]
+    pub fn amend_order(
+        &mut self,
+        order_id: String,
+        price: Option<BigDecimal>,
+        remained_base: Option<BigDecimal>,
+    ) -> anyhow::Result<Vec<MatchedTrade>> {
+        let current = self.resting_order(&order_id)?.clone();
+        let amendment = self.amendment(&order_id, price, remained_base)?;
+        let (price, remained_base) = (amendment.price.clone(), amendment.remained_base.clone());
+        if price != current.price {
+            self.circuit_breaker
+                .check_band(&price, self.market_price.as_ref())?;
+        }
+        // The notional the user has locked is read from the database
+        self.await_settlement([current.user_id.as_str()])?;
+        self.check_amend_risk_limits(&current, &amendment)?;
+
+        let keeps_priority = price == current.price && remained_base <= current.remained_base;
+        let priority = match keeps_priority {
+            true => current.priority,
+            false => self.next_priority(),
+        };
+        let entry = JournalEntry::OrderAmended {
+            order_id: order_id.clone(),
+            price: price.clone(),
+            remained_base: remained_base.clone(),
+            priority: Some(priority),
+        };
+        let amended: TradeOrder = self
+            .journaled(entry, || {
+                self.persister
+                    .amend_order(&order_id, price, remained_base, priority)
+            })?
+            .try_into()?;
+
+        if keeps_priority {
+            match amended.side {
+                OrderSide::Buy => self.bids.replace(amended),
+                OrderSide::Sell => self.asks.replace(amended),
+            };
+            return Ok(Vec::new());
         }
 
-        Ok(false)
+        match amended.side {
+            OrderSide::Buy => self.bids.remove(&order_id),
+            OrderSide::Sell => self.asks.remove(&order_id),
+        };
+        self.match_limit_order(amended)
+    }
+
+    /// The order `order_id` as amending it would leave it, a price or remaining amount left out
+    /// staying as it is. Only orders resting in the book can be amended.
+    pub fn amendment(
+        &self,
+        order_id: &str,
+        price: Option<BigDecimal>,
+        remained_base: Option<BigDecimal>,
+    ) -> anyhow::Result<TradeOrder> {
+        let current = self.resting_order(order_id)?;
+        Ok(current.amended(
+            price.unwrap_or_else(|| current.price.clone()),
+            remained_base.unwrap_or_else(|| current.remained_base.clone()),
+        ))
+    }
+
+    fn resting_order(&self, order_id: &str) -> anyhow::Result<&TradeOrder> {
+        self.bids
+            .get(order_id)
+            .or_else(|| self.asks.get(order_id))
+            .ok_or_else(|| anyhow::anyhow!("Order {} is not resting in the book", order_id))
+    }
+
+    /// Cancels the GTD orders of this market that reached their expiry by `now`, unlocking
+    /// their funds, and takes them off the book. Returns the ids of the expired orders.
+    pub fn expire_orders(&mut self, now: i64) -> anyhow::Result<Vec<String>> {
+        let expired: Vec<String> = self
+            .persister
+            .get_expired_orders(&self.market_id, now)?
+            .into_iter()
+            .map(|order| order.id)
+            .collect();
+
+        for order_id in &expired {
+            self.cancel_order(order_id.clone(), CancelReason::Expired)?;
+        }
+        Ok(expired)
     }
 
     pub fn get_order_by_id(&self, order_id: String) -> anyhow::Result<TradeOrder> {
-        if let Some(order) = self.bids.iter().find(|o| o.id == order_id) {
-            return Ok(order.clone());
-        } else if let Some(order) = self.asks.iter().find(|o| o.id == order_id) {
-            return Ok(order.clone());
+        self.bids
+            .get(&order_id)
+            .or_else(|| self.asks.get(&order_id))
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("can not find the order!"))
+    }
+
+    /// Sets the percentage a trade price may deviate from the last traded price before the
+    /// trade is refused. `None` turns the collar off.
+    pub fn set_price_collar(&mut self, price_collar: Option<BigDecimal>) {
+        self.price_collar = price_collar;
+    }
+
+    /// Sets whether a new order is created in the transaction settling its first fills rather
+    /// than ahead of matching, so a crash in between cannot leave it open with funds locked
+    /// and unmatched against the book it arrived at.
+    pub fn set_atomic_placement(&mut self, atomic_placement: bool) {
+        self.atomic_placement = atomic_placement;
+    }
+
+    /// Sets whether trades are stored behind the book: matching runs in memory and publishes
+    /// its trades as soon as their fills are journaled, and a [`SettlementWriter`] stores them
+    /// in journal order. Writes that read what a queued trade changes wait for it first. New
+    /// orders are stored ahead of matching with it, whatever atomic placement says.
+    pub fn set_async_settlement(&mut self, async_settlement: bool) {
+        self.settlement_writer = async_settlement.then(|| {
+            Arc::new(SettlementWriter::start(
+                Arc::clone(&self.persister),
+                self.market_id.clone(),
+                self.base_asset.clone(),
+                self.quote_asset.clone(),
+            ))
+        });
+    }
+
+    /// Blocks until every trade matched so far is stored. Returns at once unless trades are
+    /// stored behind the book.
+    pub fn flush_settlement(&self) -> anyhow::Result<()> {
+        match &self.settlement_writer {
+            Some(writer) => writer.wait_all(),
+            None => Ok(()),
+        }
+    }
+
+    /// Why trades stored behind the book can no longer be stored, if they cannot. The book
+    /// refuses every write from then on.
+    pub fn settlement_failure(&self) -> Option<String> {
+        self.settlement_writer
+            .as_ref()
+            .and_then(|writer| writer.failure())
+    }
+
+    /// Blocks until the queued trades touching `keys`, orders or users, are stored.
+    fn await_settlement<'a>(&self, keys: impl IntoIterator<Item = &'a str>) -> anyhow::Result<()> {
+        match &self.settlement_writer {
+            Some(writer) => writer.wait_for(keys),
+            None => Ok(()),
         }
-        Err(anyhow::anyhow!("can not find the order!"))
     }
 
-    pub fn cancel_all_orders(&mut self) -> anyhow::Result<bool> {
-        self.persister.cancel_all_orders(&self.market_id)?;
+    /// Sets how old the last traded price may get before the collar applies `stale_policy`
+    /// instead. `None` keeps the last traded price valid forever.
+    pub fn set_market_price_max_age(
+        &mut self,
+        max_age_ms: Option<i64>,
+        stale_policy: StalePricePolicy,
+    ) {
+        self.market_price_max_age_ms = max_age_ms;
+        self.stale_price_policy = stale_policy;
+    }
+
+    /// Sets the price band and circuit breaker of the market. A halt in progress is lifted.
+    pub fn set_price_band(&mut self, config: PriceBandConfig) {
+        self.circuit_breaker = CircuitBreaker::new(config);
+    }
+
+    /// Sets how many executed trades are kept in memory, dropping the oldest beyond it.
+    pub fn set_recent_trades_capacity(&mut self, capacity: usize) {
+        self.recent_trades_capacity = capacity;
+        self.trim_recent_trades();
+    }
+
+    /// Returns up to `limit` of the last executed trades, newest first.
+    pub fn recent_trades(&self, limit: usize) -> Vec<MatchedTrade> {
+        self.recent_trades
+            .iter()
+            .rev()
+            .take(limit)
+            .map(|recent| recent.trade.clone())
+            .collect()
+    }
+
+    /// Subscribes to the trades executed from now on. With `since_sequence`, the kept trades
+    /// after it come first, oldest first; trades older than those kept are not replayed.
+    pub fn subscribe_trades(
+        &self,
+        since_sequence: Option<u64>,
+    ) -> (Vec<SequencedTrade>, broadcast::Receiver<SequencedTrade>) {
+        let replay = match since_sequence {
+            Some(since) => self
+                .recent_trades
+                .iter()
+                .filter(|recent| recent.sequence > since)
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        };
+        (replay, self.trade_updates.subscribe())
+    }
+
+    pub(super) fn record_recent_trade(&mut self, trade: MatchedTrade) {
+        self.trade_sequence += 1;
+        let trade = SequencedTrade {
+            sequence: self.trade_sequence,
+            trade,
+        };
+        // Nobody listening is fine, the trade is kept for replay all the same
+        let _ = self.trade_updates.send(trade.clone());
+        self.recent_trades.push_back(trade);
+        self.trim_recent_trades();
+    }
+
+    fn trim_recent_trades(&mut self) {
+        while self.recent_trades.len() > self.recent_trades_capacity {
+            self.recent_trades.pop_front();
+        }
+    }
+
+    pub fn bids_len(&self) -> usize {
+        self.bids.len()
+    }
+
+    pub fn asks_len(&self) -> usize {
+        self.asks.len()
+    }
+
+    /// Sets the resting order gauges of the book's market
+    pub fn record_book_metrics(&self) {
+        BOOK_ORDERS
+            .with_label_values(&[&self.market_id, "bid"])
+            .set(self.bids.len() as i64);
+        BOOK_ORDERS
+            .with_label_values(&[&self.market_id, "ask"])
+            .set(self.asks.len() as i64);
+    }
+
+    pub fn cancel_all_orders(&mut self, reason: CancelReason) -> anyhow::Result<bool> {
+        let entry = JournalEntry::OrdersCanceled {
+            reason: reason.clone(),
+        };
+        let canceled = self.journaled(entry, || {
+            self.persister
+                .cancel_all_orders(&self.market_id, reason.clone())
+        })?;
+        for order in canceled {
+            self.publish_canceled(order, &reason);
+        }
         self.bids.clear();
         self.asks.clear();
-        self.bid_depth.clear();
-        self.ask_depth.clear();
+        self.oco_siblings.clear();
         Ok(true)
     }
-    pub fn persist_create_order(&self, order: &TradeOrder) -> anyhow::Result<()> {
-        let new_order: NewOrder = order.clone().into(); // Convert TradeOrder to NewOrder
+    /// Priority of the next order to join the book, behind every order it holds
+    pub(super) fn next_priority(&mut self) -> i64 {
+        self.order_priority += 1;
+        self.order_priority
+    }
+
+    /// Keeps the priorities given from now on above those `orders` were stored with.
+    pub(super) fn keep_priorities_above(&mut self, orders: &[Order]) {
+        let highest = orders.iter().map(|order| order.priority).max();
+        self.order_priority = self.order_priority.max(highest.unwrap_or_default());
+    }
 
-        self.persister.create_order(new_order)?;
+    pub fn persist_create_order(&self, order: &TradeOrder) -> anyhow::Result<()> {
+        self.check_client_order_id(order)?;
+        self.store_order(order)
+    }
 
+    /// Refuses `order` when its user has another order under its client order ID. Checked
+    /// ahead of locking funds, the unique index only guards the insert.
+    fn check_client_order_id(&self, order: &TradeOrder) -> anyhow::Result<()> {
+        if let Some(client_order_id) = &order.client_order_id {
+            let taken = self
+                .persister
+                .get_order_by_client_id(&order.user_id, client_order_id)?
+                .is_some_and(|taken| taken.status != OrderStatus::Rejected.as_str());
+            if taken {
+                let e = OrderBookError::DuplicateClientOrderId(client_order_id.clone()).into();
+                self.reject_order(order, &e);
+                return Err(e);
+            }
+        }
         Ok(())
     }
-}
 
-// #[cfg(test)]
-// mod tests {
-//     use super::*;
-//     use crate::{
-//         models::trade_order::{OrderSide, OrderType, TradeOrder},
-//         tests::test_models::create_persistence_mock,
-//     };
-//     use anyhow::Context;
-//     use bigdecimal::BigDecimal;
-//     use database::mock::mock_thread_safe_persistence::MockThreadSafePersistence;
-//     use env_logger;
-//     use std::str::FromStr;
-
-//     fn create_order(
-//         id: &str,
-//         side: OrderSide,
-//         price: &str,
-//         base_amount: &str,
-//         quote_amount: &str,
-//         create_time: i64,
-//         order_type: OrderType,
-//     ) -> TradeOrder {
-//         TradeOrder {
-//             id: id.to_string(),
-//             market_id: "BTC-USD".into(),
-//             order_type,
-//             side,
-//             user_id: "1".to_string(),
-//             price: BigDecimal::from_str(price).unwrap(),
-//             base_amount: BigDecimal::from_str(base_amount).unwrap(),
-//             quote_amount: BigDecimal::from_str(quote_amount).unwrap(),
-//             maker_fee: BigDecimal::from(0),
-//             taker_fee: BigDecimal::from(0),
-//             create_time,
-//             remained_base: BigDecimal::from_str(base_amount).unwrap(),
-//             remained_quote: BigDecimal::from_str(quote_amount).unwrap(),
-//             filled_base: BigDecimal::from(0),
-//             filled_quote: BigDecimal::from(0),
-//             filled_fee: BigDecimal::from(0),
-//             update_time: create_time,
-//         }
-//     }
-
-// #[test]
-// fn test_order_book_matching() {
-//     env_logger::init();
-//     let persister = MockThreadSafePersistence::new();
-//     let mut order_book = OrderBook::new(create_persistence_mock());
-
-//     // Add a bid (buy order)
-//     let bid = create_order("1", OrderSide::Buy, "50000", "1", 1, OrderType::Limit);
-//     let trades = order_book
-//         .add_order(bid)
-//         .context("can't add order")
-//         .unwrap();
-//     assert_eq!(trades.len(), 0); // No trades yet
-
-//     // Add an ask (sell order) that matches the bid
-//     let ask = create_order("2", OrderSide::Sell, "50000", "1", 2, OrderType::Limit);
-//     let trades = order_book
-//         .add_order(ask)
-//         .context("can't add order")
-//         .unwrap();
-//     assert_eq!(trades.len(), 1); // One trade should occur
-//     println!("{:?}", trades);
-//     // Verify the trade details
-//     let trade = &trades[0];
-//     assert_eq!(trade.price, BigDecimal::from_str("50000").unwrap());
-//     assert_eq!(trade.amount, BigDecimal::from_str("1").unwrap());
-//     assert_eq!(trade.taker_order_id, "2");
-//     assert_eq!(trade.maker_order_id, "1");
-
-//     // Verify the order book is empty after the match
-//     assert!(order_book.bids.is_empty());
-//     assert!(order_book.asks.is_empty());
-// }
-
-// #[test]
-// fn test_partial_match() {
-//     let mut order_book = OrderBook::new(create_persistence_mock());
-
-//     // Add a bid (buy order)
-//     let bid = create_order("1", OrderSide::Buy, "50000", "2", 1, OrderType::Limit);
-//     let trades = order_book.add_order(bid).unwrap();
-//     assert_eq!(trades.len(), 0); // No trades yet
-
-//     // Add an ask (sell order) that partially matches the bid
-//     let ask = create_order("2", OrderSide::Sell, "50000", "1", 2, OrderType::Limit);
-//     let trades = order_book.add_order(ask).unwrap();
-//     assert_eq!(trades.len(), 1); // One trade should occur
-//     println!("{:?}", trades);
-//     // Verify the trade details
-//     let trade = &trades[0];
-//     assert_eq!(trade.price, BigDecimal::from_str("50000").unwrap());
-//     assert_eq!(trade.amount, BigDecimal::from_str("1").unwrap());
-
-//     // Verify the remaining bid in the order book
-//     // assert_eq!(order_book.asks.len(), 1);
-//     assert_eq!(order_book.bids.len(), 1);
-//     let remaining_bid = order_book.bids.peek().unwrap();
-//     println!("{:?}", remaining_bid);
-//     assert_eq!(remaining_bid.id, "1");
-//     assert_eq!(remaining_bid.remain, BigDecimal::from_str("1").unwrap());
-
-//     // Verify the ask is fully filled and removed
-//     assert!(order_book.asks.is_empty());
-// }
-
-// #[test]
-// fn test_cancel_order() {
-//     let mut order_book = OrderBook::new(create_persistence_mock());
-
-//     // Add a bid (buy order)
-//     let bid = create_order("1", OrderSide::Buy, "50000", "1", 1, OrderType::Limit);
-//     order_book.add_order(bid);
-
-//     // Cancel the bid
-//     let canceled = order_book.cancel_order("1".to_string()).unwrap();
-//     assert_eq!(canceled, true);
-
-//     // Verify the bid is removed from the order book
-//     assert!(order_book.bids.is_empty());
-// }
-// }
+    /// Creates `order`, locking its funds, and tells its user whether it was accepted.
+    pub(super) fn store_order(&self, order: &TradeOrder) -> anyhow::Result<()> {
+        let new_order: NewOrder = order.clone().into(); // Convert TradeOrder to NewOrder
+
+        let entry = JournalEntry::OrderAccepted(Box::new(new_order.clone()));
+        match self.journaled(entry, || self.persister.create_order(new_order)) {
+            Ok(_) => {
+                self.publish_accepted(order);
+                Ok(())
+            }
+            Err(e) => {
+                self.reject_order(order, &e);
+                Err(e)
+            }
+        }
+    }
+}
@@ -0,0 +1,363 @@
+use anyhow::Context;
+use bigdecimal::BigDecimal;
+use common::utils::{get_utc_now_millis, round_amount};
+use database::models::models::{CancelReason, NewTrade, OrderStatus, TradeFill};
+use database::provider::DatabaseProvider;
+
+use super::journal::JournalEntry;
+use super::settlement_writer::SettlementWriter;
+use super::OrderBook;
+use crate::metrics::TRADES;
+use crate::models::matched_trade::MatchedTrade;
+use crate::models::trade_order::{OrderSide, TradeOrder};
+use uuid::Uuid;
+
+/// Fills settled in one transaction at most, so an order sweeping a deep book keeps its
+/// transaction and statements bounded
+pub const MAX_BATCHED_FILLS: usize = 256;
+
+/// Fills matched for one incoming order and not settled yet.
+///
+/// Each fill is applied to both orders in memory as it is matched, so the next one is matched
+/// against what it leaves. The database sees them all in one transaction once matching stops.
+#[derive(Debug, Default)]
+pub(super) struct PendingFills {
+    /// Incoming order as it arrived, when it is stored by the transaction settling its first
+    /// fills rather than ahead of matching
+    order: Option<TradeOrder>,
+    fills: Vec<TradeFill>,
+    /// Buyer and seller as each fill left them, for their users' fill events
+    filled: Vec<(TradeOrder, TradeOrder)>,
+    /// Resting orders as they were before their first fill, to put back if settlement fails
+    makers: Vec<TradeOrder>,
+    /// Market price and its time before the first fill
+    market_price: Option<(Option<BigDecimal>, Option<i64>)>,
+    /// Set once a fill must not wait for the rest of matching to settle
+    due: bool,
+}
+
+impl PendingFills {
+    /// Fills of `order`, which is not stored yet
+    pub(super) fn creating(order: &TradeOrder) -> Self {
+        Self {
+            order: Some(order.clone()),
+            ..Self::default()
+        }
+    }
+}
+
+/// Applies a fill to `order` the way settlement writes it to the orders table.
+fn fill_order(
+    order: &mut TradeOrder,
+    base_amount: &BigDecimal,
+    quote_amount: &BigDecimal,
+    fee: &BigDecimal,
+) {
+    order.filled_base =
+        round_amount(&(round_amount(&order.filled_base) + round_amount(base_amount)));
+    order.filled_quote =
+        round_amount(&(round_amount(&order.filled_quote) + round_amount(quote_amount)));
+    order.filled_fee = round_amount(&(round_amount(&order.filled_fee) + fee));
+    order.remained_base =
+        round_amount(&(round_amount(&order.remained_base) - round_amount(base_amount)));
+    order.remained_quote = match order.side {
+        OrderSide::Sell => round_amount(&(&order.remained_base * &order.price)),
+        OrderSide::Buy => {
+            round_amount(&(round_amount(&order.remained_quote) - round_amount(quote_amount)))
+        }
+    };
+    order.status = if order.filled_base >= round_amount(&order.base_amount) {
+        OrderStatus::Filled
+    } else {
+        OrderStatus::PartiallyFilled
+    };
+}
+
+impl<P: DatabaseProvider> OrderBook<P> {
+    /// Matches `buyer` and `seller` for `base_amount` at `trade_price`: the fill is applied to
+    /// both orders right away and settled later with the other fills in `pending`.
+    pub(super) fn queue_fill(
+        &mut self,
+        pending: &mut PendingFills,
+        buyer: &mut TradeOrder,
+        seller: &mut TradeOrder,
+        base_amount: BigDecimal,
+        trade_price: BigDecimal,
+        is_buyer_taker: bool,
+    ) -> anyhow::Result<()> {
+        // Last line of defence against a mispriced book: the fill is not settled, the resting
+        // order goes back to the book and the incoming one is canceled once the fills before
+        // it are settled
+        if !self.is_within_price_collar(&trade_price) {
+            let reference = self.market_price.clone().unwrap_or_default();
+            let taker = if is_buyer_taker {
+                self.asks.push_front(seller.clone());
+                buyer
+            } else {
+                self.bids.push_front(buyer.clone());
+                seller
+            };
+            self.settle_fills(pending, taker)?;
+            self.persist_cancel(&taker.id, CancelReason::PriceCollar)?;
+            self.release_oco_sibling(&taker.id);
+            return Err(anyhow::anyhow!(
+                "Trade price {} is outside the price collar around {}",
+                trade_price,
+                reference
+            ));
+        }
+
+        let maker = if is_buyer_taker { &*seller } else { &*buyer };
+        if !pending.makers.iter().any(|queued| queued.id == maker.id) {
+            pending.makers.push(maker.clone());
+        }
+        // The other leg of an OCO group has to leave the book before matching goes on
+        if [&buyer.id, &seller.id]
+            .iter()
+            .any(|id| self.oco_siblings.contains_key(*id))
+        {
+            pending.due = true;
+        }
+        if pending.market_price.is_none() {
+            pending.market_price = Some((self.market_price.clone(), self.market_price_time));
+        }
+
+        // Calculate the fees for the buyer and seller
+        let (buyer_fee_rate, seller_fee_rate) = match is_buyer_taker {
+            true => (buyer.taker_fee.clone(), seller.maker_fee.clone()),
+            false => (buyer.maker_fee.clone(), seller.taker_fee.clone()),
+        };
+        // Calculate the trade quote amount
+        let quote_amount = &base_amount * &trade_price;
+        // Settlement charges the buyer on the base it receives, the seller on the quote
+        let buyer_fee = round_amount(&(&buyer_fee_rate * &base_amount));
+        let seller_fee = round_amount(&(&seller_fee_rate * &quote_amount));
+        fill_order(buyer, &base_amount, &quote_amount, &buyer_fee);
+        fill_order(seller, &base_amount, &quote_amount, &seller_fee);
+        // A trade stored behind the book is named here, so it is published under the id and
+        // time it is stored with
+        let (trade_id, timestamp) = match self.settlement_writer {
+            Some(_) => (
+                Some(Uuid::new_v4().to_string()),
+                Some(get_utc_now_millis() / 1000),
+            ),
+            None => (None, None),
+        };
+
+        pending.fills.push(TradeFill {
+            is_buyer_taker,
+            buyer_user_id: buyer.user_id.clone(),
+            seller_user_id: seller.user_id.clone(),
+            buyer_order_id: buyer.id.clone(),
+            seller_order_id: seller.id.clone(),
+            price: trade_price.clone(),
+            base_amount,
+            quote_amount,
+            buyer_fee_rate,
+            seller_fee_rate,
+            trade_id,
+            timestamp,
+        });
+        pending.filled.push((buyer.clone(), seller.clone()));
+        pending.due |= pending.fills.len() >= MAX_BATCHED_FILLS;
+
+        // Later fills are collared and priced against this one
+        self.market_price = Some(trade_price);
+        self.market_price_time = Some(get_utc_now_millis());
+        Ok(())
+    }
+
+    /// Settles `pending` if one of its fills cannot wait for matching to stop.
+    pub(super) fn settle_due_fills(
+        &mut self,
+        pending: &mut PendingFills,
+        taker: &mut TradeOrder,
+    ) -> anyhow::Result<Vec<MatchedTrade>> {
+        match pending.due {
+            true => self.settle_fills(pending, taker),
+            false => Ok(Vec::new()),
+        }
+    }
+
+    /// Settles the fills in `pending` in one transaction, then reloads `taker` and the resting
+    /// orders they touched as the database has them and publishes the trades. A `taker` not
+    /// stored yet is created in the same transaction.
+    ///
+    /// With trades stored behind the book the fills are only journaled and queued to the
+    /// settlement writer, and `taker` and the resting orders stay as matching left them.
+    ///
+    /// If settlement fails nothing has traded: the resting orders go back to the head of the
+    /// book as they were, and the market price to what it was before the fills. A `taker` it
+    /// was to create is rejected.
+    pub(super) fn settle_fills(
+        &mut self,
+        pending: &mut PendingFills,
+        taker: &mut TradeOrder,
+    ) -> anyhow::Result<Vec<MatchedTrade>> {
+        let PendingFills {
+            order,
+            fills,
+            filled,
+            makers,
+            market_price,
+            ..
+        } = std::mem::take(pending);
+        if fills.is_empty() {
+            if let Some(order) = &order {
+                self.store_order(order)?;
+            }
+            return Ok(Vec::new());
+        }
+
+        let mut entries: Vec<JournalEntry> = order
+            .iter()
+            .map(|order| JournalEntry::OrderAccepted(Box::new(order.clone().into())))
+            .collect();
+        entries.extend(fills.iter().map(|fill| JournalEntry::TradeExecuted {
+            buyer_order_id: fill.buyer_order_id.clone(),
+            seller_order_id: fill.seller_order_id.clone(),
+            price: fill.price.clone(),
+            base_amount: fill.base_amount.clone(),
+            is_buyer_taker: fill.is_buyer_taker,
+            trade_id: fill.trade_id.clone(),
+            timestamp: fill.timestamp,
+        }));
+        let writer = self.settlement_writer.clone().filter(|_| order.is_none());
+        let settled = match &writer {
+            Some(writer) => self.queue_settlement(writer, &entries, &fills),
+            None => self.journaled_all(entries, || match &order {
+                Some(order) => self
+                    .persister
+                    .create_order_with_trades(
+                        order.clone().into(),
+                        &self.base_asset,
+                        &self.quote_asset,
+                        &fills,
+                    )
+                    .map(|(_, trades)| trades),
+                None => self.persister.execute_limit_trades(
+                    &self.market_id,
+                    &self.base_asset,
+                    &self.quote_asset,
+                    &fills,
+                ),
+            }),
+        };
+        let settled = match settled {
+            Ok(settled) => settled,
+            Err(e) => {
+                if let Some(order) = &order {
+                    self.reject_order(order, &e);
+                }
+                for maker in makers.into_iter().rev() {
+                    let side = match maker.side {
+                        OrderSide::Buy => &mut self.bids,
+                        OrderSide::Sell => &mut self.asks,
+                    };
+                    side.remove(&maker.id);
+                    side.push_front(maker);
+                }
+                if let Some((price, time)) = market_price {
+                    self.market_price = price;
+                    self.market_price_time = time;
+                }
+                return Err(e);
+            }
+        };
+
+        if let Some(order) = &order {
+            self.publish_accepted(order);
+        }
+        if writer.is_none() {
+            *taker = self
+                .persister
+                .get_order(&taker.id)?
+                .context("Settled order not found")?
+                .try_into()?;
+            for maker in &makers {
+                let side = match maker.side {
+                    OrderSide::Buy => &mut self.bids,
+                    OrderSide::Sell => &mut self.asks,
+                };
+                if side.get(&maker.id).is_some() {
+                    if let Some(order) = self.persister.get_order(&maker.id)? {
+                        side.replace(order.try_into()?);
+                    }
+                }
+            }
+        }
+        // The fills canceled the other leg of any OCO group their orders belong to
+        self.release_oco_sibling(&taker.id);
+        for maker in &makers {
+            self.release_oco_sibling(&maker.id);
+        }
+
+        let trades: Vec<MatchedTrade> = settled.into_iter().map(MatchedTrade::from).collect();
+        TRADES
+            .with_label_values(&[&self.market_id])
+            .inc_by(trades.len() as u64);
+        for (trade, (buyer, seller)) in trades.iter().zip(&filled) {
+            Self::log_trade(trade);
+            self.record_recent_trade(trade.clone());
+            self.publish_fill(buyer, trade);
+            self.publish_fill(seller, trade);
+        }
+        Ok(trades)
+    }
+
+    /// Journals `entries` and queues `fills` to `writer`, returning the trades they are stored
+    /// as. The orders and users the fills touch, and the other leg of any OCO group among the
+    /// orders, are recorded for the writes that have to wait for the trades.
+    fn queue_settlement(
+        &self,
+        writer: &SettlementWriter,
+        entries: &[JournalEntry],
+        fills: &[TradeFill],
+    ) -> anyhow::Result<Vec<NewTrade>> {
+        let sequence = self
+            .append_journal(entries)?
+            .last()
+            .context("No engine event appended")?
+            .sequence;
+
+        let mut touched = Vec::new();
+        for fill in fills {
+            for order_id in [&fill.buyer_order_id, &fill.seller_order_id] {
+                touched.push(order_id.clone());
+                touched.extend(self.oco_siblings.get(order_id).cloned());
+            }
+            touched.push(fill.buyer_user_id.clone());
+            touched.push(fill.seller_user_id.clone());
+        }
+        let trades = fills
+            .iter()
+            .map(|fill| settled_trade(&self.market_id, fill))
+            .collect();
+        writer.push(sequence, fills.to_vec(), touched);
+        Ok(trades)
+    }
+}
+
+/// The trade settlement stores for `fill`, which carries the id and time the book gave it.
+/// Fees are charged the way settlement charges them.
+fn settled_trade(market_id: &str, fill: &TradeFill) -> NewTrade {
+    NewTrade {
+        id: fill.trade_id.clone().unwrap_or_default(),
+        timestamp: fill.timestamp.unwrap_or_default(),
+        market_id: market_id.to_string(),
+        price: fill.price.clone(),
+        base_amount: fill.base_amount.clone(),
+        quote_amount: fill.quote_amount.clone(),
+        buyer_user_id: fill.buyer_user_id.clone(),
+        buyer_order_id: fill.buyer_order_id.clone(),
+        buyer_fee: round_amount(&(&fill.buyer_fee_rate * &fill.base_amount)),
+        seller_user_id: fill.seller_user_id.clone(),
+        seller_order_id: fill.seller_order_id.clone(),
+        seller_fee: round_amount(&(&fill.seller_fee_rate * &fill.quote_amount)),
+        taker_side: match fill.is_buyer_taker {
+            true => "BUY".to_string(),
+            false => "SELL".to_string(),
+        },
+        is_liquidation: None,
+    }
+}
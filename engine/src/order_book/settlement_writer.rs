@@ -0,0 +1,260 @@
+use anyhow::{anyhow, Result};
+use common::utils::get_utc_now_millis;
+use database::is_transient_error;
+use database::models::models::{NewSettlementDeadLetter, TradeFill};
+use database::provider::DatabaseProvider;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tracing::error;
+
+use super::settlement::MAX_BATCHED_FILLS;
+
+/// First wait before a batch that failed is tried again, doubled on each failure
+const RETRY_BACKOFF: Duration = Duration::from_millis(10);
+/// Longest wait between two tries of a batch
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(1);
+/// Tries of a batch the database was out of reach for before it is given up, some 15 seconds
+const MAX_RETRIES: u32 = 20;
+/// Longest a write waits for the settlements it depends on to be stored
+const WAIT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Fills of one settlement, journaled up to `sequence`
+#[derive(Debug)]
+struct SettlementJob {
+    sequence: i64,
+    fills: Vec<TradeFill>,
+}
+
+#[derive(Debug, Default)]
+struct WriterState {
+    /// Settlements not stored yet, in journal order
+    jobs: VecDeque<SettlementJob>,
+    /// Journal sequence of the last settlement stored
+    acked: i64,
+    /// Orders and users the queued settlements touch, mapped to the sequence of the last one
+    pending: HashMap<String, i64>,
+    /// Set once the book is gone: what is queued is stored, then the writer stops
+    closed: bool,
+    /// Why the writer gave up on a batch and stopped, nothing being stored after it
+    failure: Option<String>,
+}
+
+/// Stores the trades an order book matched in memory, in the order they were journaled.
+///
+/// The book journals the fills of a settlement and hands them over without waiting for the
+/// database. A thread of the market takes what is queued, up to [`MAX_BATCHED_FILLS`] fills
+/// at a time, and settles it in one transaction that also moves the market's checkpoint to
+/// the last journal entry of the batch. A batch that fails because the database is out of reach
+/// is tried again, up to [`MAX_RETRIES`] times; one still failing when the book stops is left
+/// to the journal replay of the next start.
+///
+/// A batch the database refuses, or that ran out of tries, is set aside as a dead letter and
+/// the writer stops: the trades after it cannot be stored on top of it. The writes of the book
+/// fail from then on, see [`Self::failure`].
+///
+/// Writes that read what a settlement changes wait for it with [`Self::wait_for`] first.
+#[derive(Debug)]
+pub struct SettlementWriter {
+    shared: Arc<(Mutex<WriterState>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl SettlementWriter {
+    /// Starts the writer of `market_id`, settling through `persister`.
+    pub fn start<P>(
+        persister: Arc<P>,
+        market_id: String,
+        base_asset: String,
+        quote_asset: String,
+    ) -> Self
+    where
+        P: DatabaseProvider + 'static,
+    {
+        let shared = Arc::new((Mutex::new(WriterState::default()), Condvar::new()));
+        let writer_shared = Arc::clone(&shared);
+        let thread = thread::spawn(move || {
+            let (state, changed) = &*writer_shared;
+            let mut backoff = RETRY_BACKOFF;
+            let mut retries = 0;
+            loop {
+                let (sequence, fills) = {
+                    let mut state = lock(state);
+                    while state.jobs.is_empty() && !state.closed {
+                        state = changed.wait(state).unwrap_or_else(|e| e.into_inner());
+                    }
+                    match take_batch(&state.jobs) {
+                        Some(batch) => batch,
+                        None => return,
+                    }
+                };
+
+                match persister.execute_journaled_trades(
+                    &market_id,
+                    &base_asset,
+                    &quote_asset,
+                    &fills,
+                    sequence,
+                ) {
+                    Ok(_) => {
+                        let mut state = lock(state);
+                        while state
+                            .jobs
+                            .front()
+                            .is_some_and(|job| job.sequence <= sequence)
+                        {
+                            state.jobs.pop_front();
+                        }
+                        state.acked = sequence;
+                        state.pending.retain(|_, pending| *pending > sequence);
+                        changed.notify_all();
+                        backoff = RETRY_BACKOFF;
+                        retries = 0;
+                    }
+                    Err(e) if !is_transient_error(&e) || retries == MAX_RETRIES => {
+                        let failure = format!(
+                            "Trades journaled up to {} could not be stored: {:#}",
+                            sequence, e
+                        );
+                        error!(market_id = %market_id, "{}, setting them aside", failure);
+                        let letter = serde_json::to_string(&fills)
+                            .map_err(anyhow::Error::from)
+                            .and_then(|fills| {
+                                persister.store_settlement_dead_letter(NewSettlementDeadLetter {
+                                    market_id: market_id.clone(),
+                                    sequence,
+                                    fills,
+                                    error: format!("{:#}", e),
+                                    create_time: get_utc_now_millis(),
+                                })
+                            });
+                        if let Err(e) = letter {
+                            error!(
+                                market_id = %market_id,
+                                "Failed to set aside trades journaled up to {}: {:#}", sequence, e
+                            );
+                        }
+                        lock(state).failure = Some(failure);
+                        changed.notify_all();
+                        return;
+                    }
+                    Err(e) => {
+                        error!(
+                            market_id = %market_id,
+                            "Failed to store trades journaled up to {}: {:#}", sequence, e
+                        );
+                        retries += 1;
+                        let state = lock(state);
+                        if state.closed {
+                            error!(
+                                market_id = %market_id,
+                                "Trades journaled after {} are left to the journal replay",
+                                state.acked
+                            );
+                            return;
+                        }
+                        drop(state);
+                        thread::sleep(backoff);
+                        backoff = (backoff * 2).min(MAX_RETRY_BACKOFF);
+                    }
+                }
+            }
+        });
+
+        Self {
+            shared,
+            thread: Some(thread),
+        }
+    }
+
+    /// Queues the fills of the settlement journaled up to `sequence`. `touched` are the orders
+    /// and users whose rows the fills change.
+    pub fn push(&self, sequence: i64, fills: Vec<TradeFill>, touched: Vec<String>) {
+        let (state, changed) = &*self.shared;
+        let mut state = lock(state);
+        state.jobs.push_back(SettlementJob { sequence, fills });
+        for key in touched {
+            state.pending.insert(key, sequence);
+        }
+        changed.notify_all();
+    }
+
+    /// Blocks until every queued settlement touching one of `keys`, orders or users, is
+    /// stored. Fails once the writer has stopped, or after [`WAIT_TIMEOUT`].
+    pub fn wait_for<'a>(&self, keys: impl IntoIterator<Item = &'a str>) -> Result<()> {
+        let (state, _) = &*self.shared;
+        let sequence = {
+            let state = lock(state);
+            keys.into_iter()
+                .filter_map(|key| state.pending.get(key).copied())
+                .max()
+        };
+        self.wait_until(|state| sequence.is_none_or(|sequence| state.acked >= sequence))
+    }
+
+    /// Blocks until every queued settlement is stored. Fails once the writer has stopped, or
+    /// after [`WAIT_TIMEOUT`].
+    pub fn wait_all(&self) -> Result<()> {
+        self.wait_until(|state| state.jobs.is_empty())
+    }
+
+    /// Whether every queued settlement is stored
+    pub fn is_idle(&self) -> bool {
+        lock(&self.shared.0).jobs.is_empty()
+    }
+
+    /// Why the writer stopped storing trades, if it did
+    pub fn failure(&self) -> Option<String> {
+        lock(&self.shared.0).failure.clone()
+    }
+
+    fn wait_until(&self, done: impl Fn(&WriterState) -> bool) -> Result<()> {
+        let (state, changed) = &*self.shared;
+        let (state, timeout) = changed
+            .wait_timeout_while(lock(state), WAIT_TIMEOUT, |state| {
+                state.failure.is_none() && !done(state)
+            })
+            .unwrap_or_else(|e| e.into_inner());
+        match (&state.failure, timeout.timed_out()) {
+            (Some(failure), _) => Err(anyhow!("{}", failure)),
+            (None, true) => Err(anyhow!(
+                "Trades were not stored within {:?}, the last stored was journaled at {}",
+                WAIT_TIMEOUT,
+                state.acked
+            )),
+            (None, false) => Ok(()),
+        }
+    }
+}
+
+impl Drop for SettlementWriter {
+    fn drop(&mut self) {
+        let (state, changed) = &*self.shared;
+        lock(state).closed = true;
+        changed.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn lock(state: &Mutex<WriterState>) -> MutexGuard<'_, WriterState> {
+    state.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Fills of the settlements at the head of `jobs`, up to [`MAX_BATCHED_FILLS`] of them unless
+/// the first alone has more, with the sequence of the last settlement taken
+fn take_batch(jobs: &VecDeque<SettlementJob>) -> Option<(i64, Vec<TradeFill>)> {
+    let first = jobs.front()?;
+    let mut sequence = first.sequence;
+    let mut fills = first.fills.clone();
+    for job in jobs.iter().skip(1) {
+        if fills.len() + job.fills.len() > MAX_BATCHED_FILLS {
+            break;
+        }
+        sequence = job.sequence;
+        fills.extend(job.fills.iter().cloned());
+    }
+    Some((sequence, fills))
+}
@@ -0,0 +1,97 @@
+use anyhow::{Context, Result};
+use bigdecimal::BigDecimal;
+use common::utils::get_utc_now_millis;
+use database::models::models::OrderBookSnapshot;
+use database::provider::DatabaseProvider;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::info;
+
+use super::OrderBook;
+use crate::models::trade_order::{OrderSide, TradeOrder};
+
+/// What a snapshot keeps of an order book
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookState {
+    /// Resting bids in the sequence they would trade
+    pub bids: Vec<TradeOrder>,
+    /// Resting asks in the sequence they would trade
+    pub asks: Vec<TradeOrder>,
+    pub market_price: Option<BigDecimal>,
+    pub market_price_time: Option<i64>,
+}
+
+impl<P: DatabaseProvider> OrderBook<P> {
+    /// Stores the resting orders of the book, in queue order, as the latest snapshot of its
+    /// market. Returns the journal sequence the snapshot reflects.
+    pub fn write_snapshot(&self) -> Result<i64> {
+        let state = BookState {
+            bids: self.bids.iter().cloned().collect(),
+            asks: self.asks.iter().cloned().collect(),
+            market_price: self.market_price.clone(),
+            market_price_time: self.market_price_time,
+        };
+        // Runs on the book's thread, so nothing is journaled while the snapshot is taken. Trades
+        // still being stored would leave the checkpoint behind the book.
+        self.flush_settlement()?;
+        let applied_sequence = self.persister.get_applied_sequence(&self.market_id)?;
+        self.persister
+            .store_order_book_snapshot(OrderBookSnapshot {
+                market_id: self.market_id.clone(),
+                applied_sequence,
+                payload: serde_json::to_string(&state)
+                    .context("Failed to encode order book snapshot")?,
+                create_time: get_utc_now_millis(),
+            })?;
+        Ok(applied_sequence)
+    }
+
+    /// Rebuilds the book from its latest snapshot, returning false when there is none.
+    ///
+    /// The snapshot only gives the queue order: each order it holds is put back as the orders
+    /// table has it now, and dropped if it is no longer open there. Open orders the snapshot
+    /// does not hold, or whose price has changed since, are matched in like on a full recovery.
+    pub(super) fn restore_from_snapshot(&mut self) -> Result<bool> {
+        let Some(snapshot) = self.persister.get_order_book_snapshot(&self.market_id)? else {
+            return Ok(false);
+        };
+        let state: BookState = serde_json::from_str(&snapshot.payload)
+            .context("Invalid order book snapshot payload")?;
+
+        let mut open_orders: HashMap<String, _> = self
+            .persister
+            .get_active_orders(&self.market_id)?
+            .into_iter()
+            .map(|order| (order.id.clone(), order))
+            .collect();
+        let stored: Vec<_> = open_orders.values().cloned().collect();
+        self.keep_priorities_above(&stored);
+        let mut restored = 0;
+        for queued in state.bids.iter().chain(&state.asks) {
+            let Some(order) = open_orders.remove(&queued.id) else {
+                continue;
+            };
+            if order.price != queued.price {
+                open_orders.insert(order.id.clone(), order);
+                continue;
+            }
+            let order: TradeOrder = order.try_into()?;
+            match queued.side {
+                OrderSide::Buy => self.bids.push(order),
+                OrderSide::Sell => self.asks.push(order),
+            }
+            restored += 1;
+        }
+        self.market_price = state.market_price;
+        self.market_price_time = state.market_price_time;
+
+        let changed = open_orders.len();
+        self.recover_orders(open_orders.into_values().collect())?;
+        info!(
+            market_id = %self.market_id,
+            "Restored {} orders from the snapshot at journal sequence {}, {} more from database",
+            restored, snapshot.applied_sequence, changed
+        );
+        Ok(true)
+    }
+}
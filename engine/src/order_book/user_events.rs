@@ -0,0 +1,123 @@
+use super::journal::JournalEntry;
+use super::{OrderBook, OrderBookError};
+use crate::market::MarketError;
+use crate::models::matched_trade::MatchedTrade;
+use crate::models::trade_order::TradeOrder;
+use crate::models::user_event::{UserEvent, UserEventKind};
+use crate::validation::MarketConstraintError;
+use common::error::BitradeError;
+use database::models::models::{CancelReason, Order, RejectReason};
+use database::provider::DatabaseProvider;
+use tokio::sync::broadcast;
+use tracing::warn;
+
+/// Events buffered per `SubscribeUserEvents` stream before a slow subscriber is dropped
+pub const USER_EVENTS_CAPACITY: usize = 4096;
+
+/// Why `error` refused an order before it reached the book. `None` for failures that are not
+/// the order's, those that leave it stored otherwise like a crossing post-only order, and
+/// recovery, which the client is told to retry after.
+pub fn reject_reason(error: &anyhow::Error) -> Option<RejectReason> {
+    if let Some(BitradeError::InsufficientBalance { .. }) = error.downcast_ref() {
+        return Some(RejectReason::InsufficientFunds);
+    }
+    if error.downcast_ref::<MarketConstraintError>().is_some() {
+        return Some(RejectReason::MarketConstraint);
+    }
+    match error.downcast_ref::<MarketError>() {
+        Some(MarketError::UserRestricted { .. }) => return Some(RejectReason::UserRestricted),
+        Some(MarketError::MarketNotStarted | MarketError::StatusRestricted { .. }) => {
+            return Some(RejectReason::MarketUnavailable)
+        }
+        _ => {}
+    }
+    match error.downcast_ref::<OrderBookError>()? {
+        OrderBookError::OutsidePriceBand { .. } => Some(RejectReason::PriceBand),
+        OrderBookError::MatchingHalted(_) => Some(RejectReason::CircuitBreaker),
+        OrderBookError::OpenOrderLimit(_) | OrderBookError::NotionalLimit { .. } => {
+            Some(RejectReason::RiskLimit)
+        }
+        OrderBookError::DuplicateClientOrderId(_) => Some(RejectReason::DuplicateClientOrderId),
+        OrderBookError::PostOnlyWouldCross(_) => None,
+    }
+}
+
+/// Stores `order` as REJECTED when `error` has a [`reject_reason`], so its user can look up
+/// why it never showed up. Failing to store it is only logged, the order is refused either way.
+pub fn store_rejection<P: DatabaseProvider>(
+    persister: &P,
+    order: &TradeOrder,
+    error: &anyhow::Error,
+) {
+    let Some(reason) = reject_reason(error) else {
+        return;
+    };
+    if let Err(e) = persister.reject_order(order.clone().into(), reason) {
+        warn!(order_id = %order.id, "Failed to store rejected order: {:?}", e);
+    }
+}
+
+impl<P: DatabaseProvider> OrderBook<P> {
+    /// Routes the events of this book's orders to `sender`, shared by all markets.
+    pub fn set_user_events(&mut self, sender: broadcast::Sender<UserEvent>) {
+        self.user_events = sender;
+    }
+
+    /// Sends the event `build` makes, built only if someone listens.
+    pub(super) fn publish_user_event(&self, build: impl FnOnce() -> Option<UserEvent>) {
+        if self.user_events.receiver_count() == 0 {
+            return;
+        }
+        if let Some(event) = build() {
+            let _ = self.user_events.send(event);
+        }
+    }
+
+    /// Stores `order` as refused before reaching the book, see [`store_rejection`], and tells
+    /// its user.
+    pub fn reject_order(&self, order: &TradeOrder, error: &anyhow::Error) {
+        store_rejection(self.persister.as_ref(), order, error);
+        self.publish_user_event(|| Some(UserEvent::rejected(order, error.to_string())));
+    }
+
+    pub(super) fn publish_accepted(&self, order: &TradeOrder) {
+        self.publish_user_event(|| Some(UserEvent::for_order(UserEventKind::Accepted, order)));
+    }
+
+    pub(super) fn publish_fill(&self, order: &TradeOrder, trade: &MatchedTrade) {
+        self.publish_user_event(|| Some(UserEvent::for_fill(order, trade)));
+    }
+
+    /// Cancels an order in the database and tells its user.
+    pub(super) fn persist_cancel(
+        &self,
+        order_id: &str,
+        reason: CancelReason,
+    ) -> anyhow::Result<()> {
+        let entry = JournalEntry::OrderCanceled {
+            order_id: order_id.to_string(),
+            reason: reason.clone(),
+        };
+        let order = self.journaled(entry, || {
+            self.persister.cancel_order(order_id, reason.clone())
+        })?;
+        self.publish_canceled(order, &reason);
+        Ok(())
+    }
+
+    /// Tells the user about an order the database has canceled already.
+    pub(super) fn publish_canceled(&self, order: Order, reason: &CancelReason) {
+        self.publish_user_event(|| {
+            let order: TradeOrder = order.try_into().ok()?;
+            Some(UserEvent::for_cancel(&order, reason))
+        });
+    }
+
+    /// Like [`Self::publish_canceled`], reading the order back first.
+    pub(super) fn publish_canceled_by_id(&self, order_id: &str, reason: &CancelReason) {
+        self.publish_user_event(|| {
+            let order: TradeOrder = self.persister.get_order(order_id).ok()??.try_into().ok()?;
+            Some(UserEvent::for_cancel(&order, reason))
+        });
+    }
+}
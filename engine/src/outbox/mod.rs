@@ -0,0 +1,2 @@
+pub mod publisher;
+pub mod relay;
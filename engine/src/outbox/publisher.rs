@@ -0,0 +1,51 @@
+use anyhow::{Context, Result};
+use async_nats::header::{HeaderMap, NATS_MESSAGE_ID};
+use async_nats::jetstream;
+use async_nats::ConnectOptions;
+use std::future::Future;
+
+/// Broker the outbox relay publishes events to
+pub trait EventPublisher: Send + Sync {
+    /// Publishes `payload` under `subject`, resolving once the broker has stored it.
+    /// `message_id` is the same on every attempt to publish one event.
+    fn publish(
+        &self,
+        subject: String,
+        message_id: String,
+        payload: String,
+    ) -> impl Future<Output = Result<()>> + Send;
+}
+
+/// Publishes to NATS JetStream. The subjects must be bound to a stream; JetStream drops a
+/// message whose id it stored within the stream's duplicate window.
+pub struct NatsPublisher {
+    jetstream: jetstream::Context,
+}
+
+impl NatsPublisher {
+    /// Connects to the NATS server at `url`. A server that is not up yet is retried in the
+    /// background, publishing fails until it is reached.
+    pub async fn connect(url: &str) -> Result<Self> {
+        let client = ConnectOptions::new()
+            .retry_on_initial_connect()
+            .connect(url)
+            .await
+            .with_context(|| format!("Failed to connect to NATS at {}", url))?;
+        Ok(Self {
+            jetstream: jetstream::new(client),
+        })
+    }
+}
+
+impl EventPublisher for NatsPublisher {
+    async fn publish(&self, subject: String, message_id: String, payload: String) -> Result<()> {
+        let mut headers = HeaderMap::new();
+        headers.insert(NATS_MESSAGE_ID, message_id.as_str());
+        self.jetstream
+            .publish_with_headers(subject, headers, payload.into())
+            .await?
+            .await
+            .context("JetStream did not acknowledge the event")?;
+        Ok(())
+    }
+}
@@ -0,0 +1,99 @@
+use anyhow::Result;
+use database::models::models::OutboxEvent;
+use database::provider::DatabaseProvider;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::error;
+
+use super::publisher::EventPublisher;
+
+pub const DEFAULT_OUTBOX_BATCH_SIZE: i64 = 500;
+
+/// Subject an outbox event is published under, `bitrade.<topic>.<market id>`
+pub fn event_subject(event: &OutboxEvent) -> String {
+    format!("bitrade.{}.{}", event.topic.to_lowercase(), event.market_id)
+}
+
+/// Moves events from the outbox to the broker. An event is marked published only after the
+/// broker acknowledged it, so one the relay stopped in between is published again: consumers
+/// get every event at least once, and drop repeats by message id.
+pub struct OutboxRelay<P: DatabaseProvider, E: EventPublisher> {
+    persister: Arc<P>,
+    publisher: E,
+    batch_size: i64,
+}
+
+impl<P: DatabaseProvider, E: EventPublisher> OutboxRelay<P, E> {
+    pub fn new(persister: Arc<P>, publisher: E) -> Self {
+        Self {
+            persister,
+            publisher,
+            batch_size: DEFAULT_OUTBOX_BATCH_SIZE,
+        }
+    }
+
+    pub fn with_batch_size(mut self, batch_size: i64) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Publishes a batch of pending events, oldest first, and returns how many were published.
+    ///
+    /// The batch stops at the first event the broker refuses, so no event overtakes an earlier
+    /// one. Those published before it are still marked.
+    pub async fn relay_pending(&self) -> Result<usize> {
+        let events = self.persister.get_unpublished_events(self.batch_size)?;
+        let mut published = Vec::with_capacity(events.len());
+        let mut failure = None;
+        for event in events {
+            let subject = event_subject(&event);
+            match self
+                .publisher
+                .publish(subject, event.id.to_string(), event.payload)
+                .await
+            {
+                Ok(()) => published.push(event.id),
+                Err(e) => {
+                    failure =
+                        Some(e.context(format!("Failed to publish outbox event {}", event.id)));
+                    break;
+                }
+            }
+        }
+
+        if !published.is_empty() {
+            self.persister.mark_events_published(&published)?;
+        }
+        match failure {
+            Some(e) => Err(e),
+            None => Ok(published.len()),
+        }
+    }
+
+    /// Publishes batch after batch until the outbox is empty, returning how many events were
+    /// published. Stops at the first batch that fails.
+    pub async fn flush(&self) -> Result<usize> {
+        let mut flushed = 0;
+        loop {
+            let published = self.relay_pending().await?;
+            flushed += published;
+            if (published as i64) < self.batch_size {
+                return Ok(flushed);
+            }
+        }
+    }
+}
+
+/// Drains the outbox every `interval`, batch after batch until it is empty.
+pub async fn run_outbox_relay<P: DatabaseProvider, E: EventPublisher>(
+    relay: Arc<OutboxRelay<P, E>>,
+    interval: Duration,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        if let Err(e) = relay.flush().await {
+            error!("Failed to relay outbox events: {:?}", e);
+        }
+    }
+}
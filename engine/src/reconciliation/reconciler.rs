@@ -0,0 +1,194 @@
+use anyhow::{Context, Result};
+use bigdecimal::BigDecimal;
+use common::utils::get_utc_now_millis;
+use database::models::models::{AssetBalanceTotals, LockedBalance, Wallet};
+use database::provider::DatabaseProvider;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+
+/// An asset whose balances across wallets and fee treasuries differ from what was deposited
+/// minus what was withdrawn.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssetDiscrepancy {
+    pub asset: String,
+    pub held: BigDecimal,
+    pub expected: BigDecimal,
+    pub difference: BigDecimal,
+}
+
+/// A user whose locked balance of an asset differs from what their open orders still need.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LockDiscrepancy {
+    pub user_id: String,
+    pub asset: String,
+    pub locked: BigDecimal,
+    pub order_locked: BigDecimal,
+    pub difference: BigDecimal,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReconciliationReport {
+    pub generated_at: i64,
+    pub assets_checked: usize,
+    pub locks_checked: usize,
+    pub asset_discrepancies: Vec<AssetDiscrepancy>,
+    pub lock_discrepancies: Vec<LockDiscrepancy>,
+}
+
+impl ReconciliationReport {
+    pub fn is_balanced(&self) -> bool {
+        self.asset_discrepancies.is_empty() && self.lock_discrepancies.is_empty()
+    }
+}
+
+fn asset_discrepancy(totals: &AssetBalanceTotals) -> Option<AssetDiscrepancy> {
+    let held = &totals.available + &totals.locked + &totals.reserved + &totals.fee_treasury;
+    let expected = &totals.total_deposited - &totals.total_withdrawn;
+    if held == expected {
+        return None;
+    }
+    Some(AssetDiscrepancy {
+        asset: totals.asset.clone(),
+        difference: &held - &expected,
+        held,
+        expected,
+    })
+}
+
+fn lock_discrepancy(balance: &LockedBalance) -> Option<LockDiscrepancy> {
+    if balance.locked == balance.order_locked {
+        return None;
+    }
+    Some(LockDiscrepancy {
+        user_id: balance.user_id.clone(),
+        asset: balance.asset.clone(),
+        locked: balance.locked.clone(),
+        order_locked: balance.order_locked.clone(),
+        difference: &balance.locked - &balance.order_locked,
+    })
+}
+
+/// Checks the balance invariants against a snapshot of the database and keeps the latest
+/// report for the `GetReconciliationReport` RPC.
+pub struct Reconciler<P: DatabaseProvider> {
+    persister: Arc<P>,
+    latest: RwLock<Option<ReconciliationReport>>,
+}
+
+impl<P: DatabaseProvider> Reconciler<P> {
+    pub fn new(persister: Arc<P>) -> Self {
+        Self {
+            persister,
+            latest: RwLock::new(None),
+        }
+    }
+
+    /// Runs a reconciliation now, logs every discrepancy found and stores the report.
+    pub fn reconcile(&self) -> Result<ReconciliationReport> {
+        let sheet = self
+            .persister
+            .get_balance_sheet()
+            .context("Failed to fetch balance sheet")?;
+
+        let report = ReconciliationReport {
+            generated_at: get_utc_now_millis(),
+            assets_checked: sheet.assets.len(),
+            locks_checked: sheet.locks.len(),
+            asset_discrepancies: sheet.assets.iter().filter_map(asset_discrepancy).collect(),
+            lock_discrepancies: sheet.locks.iter().filter_map(lock_discrepancy).collect(),
+        };
+
+        for discrepancy in &report.asset_discrepancies {
+            error!(
+                "Reconciliation: {} holds {} but deposits minus withdrawals are {} (difference {})",
+                discrepancy.asset, discrepancy.held, discrepancy.expected, discrepancy.difference
+            );
+        }
+        for discrepancy in &report.lock_discrepancies {
+            warn!(
+                "Reconciliation: user {} has {} {} locked but open orders need {} (difference {})",
+                discrepancy.user_id,
+                discrepancy.locked,
+                discrepancy.asset,
+                discrepancy.order_locked,
+                discrepancy.difference
+            );
+        }
+        if report.is_balanced() {
+            info!(
+                "Reconciliation: {} assets and {} locks balanced",
+                report.assets_checked, report.locks_checked
+            );
+        }
+
+        *self.latest.write().unwrap() = Some(report.clone());
+        Ok(report)
+    }
+
+    /// The report of the last successful reconciliation, if any ran yet.
+    pub fn latest(&self) -> Option<ReconciliationReport> {
+        self.latest.read().unwrap().clone()
+    }
+
+    /// Releases the funds `user_id` has locked beyond what their open orders need, returning
+    /// the wallets that changed.
+    pub fn release_orphaned_locks(&self, user_id: &str) -> Result<Vec<Wallet>> {
+        let released = self
+            .persister
+            .release_orphaned_locks(user_id)
+            .context("Failed to release orphaned locks")?;
+        for wallet in &released {
+            info!(
+                "Reconciliation: released orphaned {} locks of user {}, {} left locked",
+                wallet.asset, wallet.user_id, wallet.locked
+            );
+        }
+        Ok(released)
+    }
+
+    /// Reconciles, then releases the orphaned locks of every user found holding more locked
+    /// than their open orders need. Returns the wallets that changed.
+    pub fn sweep_orphaned_locks(&self) -> Result<Vec<Wallet>> {
+        let report = self.reconcile()?;
+        let mut user_ids: Vec<&str> = report
+            .lock_discrepancies
+            .iter()
+            .filter(|discrepancy| discrepancy.difference > 0)
+            .map(|discrepancy| discrepancy.user_id.as_str())
+            .collect();
+        user_ids.sort_unstable();
+        user_ids.dedup();
+
+        let mut released = Vec::new();
+        for user_id in user_ids {
+            released.extend(self.release_orphaned_locks(user_id)?);
+        }
+        Ok(released)
+    }
+}
+
+/// Reconciles balances every `interval`. Discrepancies are only reported, except that with
+/// `orphaned_lock_sweep` the first reconciliation after each such period also releases the
+/// locks no open order needs.
+pub async fn run_reconciliation<P: DatabaseProvider>(
+    reconciler: Arc<Reconciler<P>>,
+    interval: Duration,
+    orphaned_lock_sweep: Option<Duration>,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    let mut last_sweep = Instant::now();
+    loop {
+        ticker.tick().await;
+        let result = match orphaned_lock_sweep {
+            Some(sweep) if last_sweep.elapsed() >= sweep => {
+                last_sweep = Instant::now();
+                reconciler.sweep_orphaned_locks().map(|_| ())
+            }
+            _ => reconciler.reconcile().map(|_| ()),
+        };
+        if let Err(e) = result {
+            error!("Failed to reconcile balances: {:?}", e);
+        }
+    }
+}
@@ -0,0 +1,109 @@
+pub mod scenario;
+
+use database::mock::mock_persister::MockPersister;
+use std::fmt::Write;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::models::matched_trade::MatchedTrade;
+use crate::order_book::OrderBook;
+use scenario::ScenarioEvent;
+
+/// Feeds recorded events through the matching engine of one market, with the book kept by a
+/// [`MockPersister`] instead of the database. The same events always give the same trades,
+/// which makes the trade log of a run something to diff matching changes against, and the
+/// run time a benchmark of the matching alone.
+pub struct Simulator {
+    persister: Arc<MockPersister>,
+    order_book: OrderBook<MockPersister>,
+}
+
+/// What a [`Simulator`] run did
+#[derive(Debug, Default)]
+pub struct SimulationReport {
+    /// Events applied
+    pub events: usize,
+    /// Events the order book refused, each with why
+    pub refused: Vec<(usize, String)>,
+    pub trades: Vec<MatchedTrade>,
+    pub elapsed: Duration,
+}
+
+impl Simulator {
+    pub fn new(market_id: &str, base_asset: &str, quote_asset: &str) -> Self {
+        let persister = Arc::new(MockPersister::new());
+        Self {
+            order_book: OrderBook::new(
+                persister.clone(),
+                base_asset.to_string(),
+                market_id.to_string(),
+                quote_asset.to_string(),
+            )
+            .expect("An order book over an empty store has nothing to fail loading"),
+            persister,
+        }
+    }
+
+    /// What the run left in place of the database, the journal included
+    pub fn persister(&self) -> &MockPersister {
+        &self.persister
+    }
+
+    /// Applies `events` in order. A refused event is recorded in the report and the run goes
+    /// on, as the engine would with the next request.
+    pub fn run(&mut self, events: Vec<ScenarioEvent>) -> SimulationReport {
+        let mut report = SimulationReport::default();
+        let started = Instant::now();
+        for (index, event) in events.into_iter().enumerate() {
+            let applied = match event {
+                ScenarioEvent::Add(order) => self.order_book.add_order(*order),
+                ScenarioEvent::Cancel { order_id, reason } => self
+                    .order_book
+                    .cancel_order(order_id, reason)
+                    .map(|_| Vec::new()),
+                ScenarioEvent::Amend {
+                    order_id,
+                    price,
+                    remained_base,
+                } => self.order_book.amend_order(order_id, price, remained_base),
+                ScenarioEvent::CancelAll { reason } => self
+                    .order_book
+                    .cancel_all_orders(reason)
+                    .map(|_| Vec::new()),
+            };
+            match applied {
+                Ok(trades) => report.trades.extend(trades),
+                Err(e) => report.refused.push((index, e.to_string())),
+            }
+            report.events += 1;
+        }
+        report.elapsed = started.elapsed();
+        report
+    }
+}
+
+impl SimulationReport {
+    /// The trades as CSV, one per line after a header. Trade ids and times differ between
+    /// runs and are left out, and amounts are normalized, so two runs of the same events give
+    /// the same log byte for byte.
+    pub fn trade_log(&self) -> String {
+        let mut log = String::from(
+            "price,base_amount,quote_amount,buyer_order_id,seller_order_id,taker_side,buyer_fee,seller_fee\n",
+        );
+        for trade in &self.trades {
+            let _ = writeln!(
+                log,
+                "{},{},{},{},{},{},{},{}",
+                trade.price.normalized(),
+                trade.base_amount.normalized(),
+                trade.quote_amount.normalized(),
+                trade.buyer_order_id,
+                trade.seller_order_id,
+                trade.taker_side,
+                trade.buyer_fee.normalized(),
+                trade.seller_fee.normalized(),
+            );
+        }
+        log
+    }
+}
@@ -0,0 +1,162 @@
+use anyhow::{anyhow, bail, Context, Result};
+use bigdecimal::BigDecimal;
+use database::models::models::{CancelReason, EngineEvent, Order, OrderStatus, TimeInForce};
+use std::str::FromStr;
+
+use crate::models::trade_order::{OrderSide, OrderType, TradeOrder};
+use crate::order_book::journal::JournalEntry;
+
+/// One step of a simulation, applied to the order book in the order given
+#[derive(Debug, Clone)]
+pub enum ScenarioEvent {
+    Add(Box<TradeOrder>),
+    Cancel {
+        order_id: String,
+        reason: CancelReason,
+    },
+    Amend {
+        order_id: String,
+        price: Option<BigDecimal>,
+        remained_base: Option<BigDecimal>,
+    },
+    CancelAll {
+        reason: CancelReason,
+    },
+}
+
+/// Parses a scenario file of `market_id`, one event per line:
+///
+/// ```text
+/// add,<order_id>,<user_id>,<BUY|SELL>,<LIMIT|MARKET>,<price>,<base_amount>[,<quote_amount>]
+/// cancel,<order_id>
+/// amend,<order_id>,<price>,<remained_base>
+/// cancel_all
+/// ```
+///
+/// Blank lines and lines starting with `#` are skipped. The quote amount of an order defaults
+/// to price times base amount, fees are zero, and an order is created at the index of its
+/// line so that the same file always yields the same events. Empty amend fields keep the
+/// order's current value.
+pub fn parse_scenario(market_id: &str, scenario: &str) -> Result<Vec<ScenarioEvent>> {
+    scenario
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(index, line)| {
+            parse_line(market_id, index as i64, line)
+                .with_context(|| format!("Invalid scenario line {}: {}", index + 1, line))
+        })
+        .collect()
+}
+
+fn parse_line(market_id: &str, index: i64, line: &str) -> Result<ScenarioEvent> {
+    let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+    match fields.as_slice() {
+        ["add", order_id, user_id, side, order_type, price, base_amount, quote_amount @ ..] => {
+            let price = parse_amount(price)?;
+            let base_amount = parse_amount(base_amount)?;
+            let quote_amount = match quote_amount {
+                [] => &price * &base_amount,
+                [quote_amount] => parse_amount(quote_amount)?,
+                _ => bail!("Too many fields"),
+            };
+            Ok(ScenarioEvent::Add(Box::new(TradeOrder {
+                id: order_id.to_string(),
+                market_id: market_id.to_string(),
+                order_type: OrderType::try_from(*order_type).map_err(|e| anyhow!(e))?,
+                side: OrderSide::try_from(*side).map_err(|e| anyhow!(e))?,
+                user_id: user_id.to_string(),
+                price,
+                remained_base: base_amount.clone(),
+                base_amount,
+                remained_quote: quote_amount.clone(),
+                quote_amount,
+                maker_fee: BigDecimal::from(0),
+                taker_fee: BigDecimal::from(0),
+                create_time: index,
+                filled_base: BigDecimal::from(0),
+                filled_quote: BigDecimal::from(0),
+                filled_fee: BigDecimal::from(0),
+                update_time: index,
+                client_order_id: None,
+                post_only: Some(false),
+                time_in_force: Some(TimeInForce::GTC),
+                expires_at: None,
+                display_amount: None,
+                status: OrderStatus::Open,
+                priority: 0,
+            })))
+        }
+        ["cancel", order_id] => Ok(ScenarioEvent::Cancel {
+            order_id: order_id.to_string(),
+            reason: CancelReason::UserCanceled,
+        }),
+        ["amend", order_id, price, remained_base] => Ok(ScenarioEvent::Amend {
+            order_id: order_id.to_string(),
+            price: parse_optional_amount(price)?,
+            remained_base: parse_optional_amount(remained_base)?,
+        }),
+        ["cancel_all"] => Ok(ScenarioEvent::CancelAll {
+            reason: CancelReason::AdminCancel,
+        }),
+        _ => bail!("Unknown event"),
+    }
+}
+
+fn parse_amount(amount: &str) -> Result<BigDecimal> {
+    BigDecimal::from_str(amount).with_context(|| format!("Invalid amount {}", amount))
+}
+
+fn parse_optional_amount(amount: &str) -> Result<Option<BigDecimal>> {
+    match amount {
+        "" => Ok(None),
+        amount => parse_amount(amount).map(Some),
+    }
+}
+
+/// The events a market's journal recorded, to feed them through the matching again.
+///
+/// Only what came from outside the book is kept: trades and the cancels the engine made
+/// itself while matching follow from the orders, and the replay makes them anew.
+pub fn journal_events(events: &[EngineEvent]) -> Result<Vec<ScenarioEvent>> {
+    let mut scenario = Vec::new();
+    for event in events {
+        match JournalEntry::try_from(event)? {
+            JournalEntry::OrderAccepted(order) => {
+                let order = Order::from(*order).try_into()?;
+                scenario.push(ScenarioEvent::Add(Box::new(order)))
+            }
+            JournalEntry::OrderCanceled { order_id, reason } => {
+                if !is_matching_cancel(&reason) {
+                    scenario.push(ScenarioEvent::Cancel { order_id, reason })
+                }
+            }
+            JournalEntry::OrderAmended {
+                order_id,
+                price,
+                remained_base,
+                ..
+            } => scenario.push(ScenarioEvent::Amend {
+                order_id,
+                price: Some(price),
+                remained_base: Some(remained_base),
+            }),
+            JournalEntry::OrdersCanceled { reason } => {
+                scenario.push(ScenarioEvent::CancelAll { reason })
+            }
+            JournalEntry::TradeExecuted { .. } => {}
+        }
+    }
+    Ok(scenario)
+}
+
+fn is_matching_cancel(reason: &CancelReason) -> bool {
+    matches!(
+        reason,
+        CancelReason::Unfilled
+            | CancelReason::PostOnly
+            | CancelReason::PriceCollar
+            | CancelReason::OneCancelsOther
+            | CancelReason::CircuitBreaker
+    )
+}
@@ -0,0 +1,309 @@
+use bigdecimal::BigDecimal;
+use common::db::pagination::Pagination;
+use common::error::ERROR_CODE_METADATA;
+use database::filters::OrderFilter;
+use database::models::models::{CancelReason, OrderStatus, RejectReason};
+use database::provider::{EngineEventDatabaseReader, OrderDatabaseReader, WalletDatabaseReader};
+use database::tests::test_db::{create_funded_user, create_test_market, isolated_test_repository};
+use tonic::{Code, Request};
+
+use crate::grpc::admin::admin_service_server::AdminService;
+use crate::grpc::service::{SpotServiceImpl, ORDER_ID_METADATA, REJECT_REASON_METADATA};
+use crate::grpc::spot::spot_service_server::SpotService;
+use crate::grpc::spot::{AddOcoOrderRequest, AddOrderRequest, StartMarketRequest};
+use crate::tests::test_models::decimal;
+use crate::tests::test_service::{add_order_request, create_test_service};
+
+#[tokio::test]
+async fn test_test_order_validates_without_persisting() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let user_id = create_funded_user(&repository, &[(&market.quote_asset, "100")]);
+    let service = create_test_service(repository.clone());
+    service
+        .admin_service()
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+
+    let valid = AddOrderRequest {
+        test_order: true,
+        ..add_order_request(&market, &user_id, "BUY", "10", "5")
+    };
+    let response = service
+        .add_order(Request::new(valid))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(response.order_id.is_empty());
+    assert!(response.trades.is_empty());
+
+    // 20 * 10 needs more quote than the user holds
+    let invalid = AddOrderRequest {
+        test_order: true,
+        ..add_order_request(&market, &user_id, "BUY", "10", "20")
+    };
+    let status = service.add_order(Request::new(invalid)).await.unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+    assert_eq!(
+        status.metadata().get(ERROR_CODE_METADATA).unwrap(),
+        "INSUFFICIENT_FUNDS"
+    );
+
+    let orders = repository
+        .list_orders(
+            OrderFilter::new().user_id(Some(user_id.clone())),
+            Some(Pagination::default()),
+        )
+        .unwrap();
+    assert_eq!(orders.total_count, 0);
+
+    let wallet = repository
+        .get_wallet(&user_id, &market.quote_asset)
+        .unwrap()
+        .unwrap();
+    assert_eq!(wallet.available, BigDecimal::from(100));
+    assert_eq!(wallet.locked, BigDecimal::from(0));
+}
+
+#[tokio::test]
+async fn test_orders_beyond_the_available_balance_are_rejected_without_locking() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let user_id = create_funded_user(&repository, &[(&market.quote_asset, "100")]);
+    let service = create_test_service(repository.clone());
+    service
+        .admin_service()
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+
+    let status = service
+        .add_order(Request::new(add_order_request(
+            &market, &user_id, "BUY", "10", "20",
+        )))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+    assert_eq!(
+        status.metadata().get(ERROR_CODE_METADATA).unwrap(),
+        "INSUFFICIENT_FUNDS"
+    );
+    assert_eq!(
+        status.metadata().get(REJECT_REASON_METADATA).unwrap(),
+        "INSUFFICIENT_FUNDS"
+    );
+    let order_id = status.metadata().get(ORDER_ID_METADATA).unwrap();
+    let rejected = repository
+        .get_order(order_id.to_str().unwrap())
+        .unwrap()
+        .unwrap();
+    assert_eq!(rejected.get_status().unwrap(), OrderStatus::Rejected);
+    assert_eq!(
+        rejected.get_reject_reason().unwrap(),
+        Some(RejectReason::InsufficientFunds)
+    );
+    assert_eq!(rejected.remained_quote, BigDecimal::from(0));
+
+    // Each leg fits on its own, both together do not
+    let status = service
+        .add_oco_order(Request::new(AddOcoOrderRequest {
+            first: Some(add_order_request(&market, &user_id, "BUY", "10", "6")),
+            second: Some(add_order_request(&market, &user_id, "BUY", "9", "6")),
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+
+    let orders = repository
+        .list_orders(
+            OrderFilter::new().user_id(Some(user_id.clone())),
+            Some(Pagination::default()),
+        )
+        .unwrap();
+    assert_eq!(orders.total_count, 3);
+    assert!(orders
+        .items
+        .iter()
+        .all(|order| order.get_status().unwrap() == OrderStatus::Rejected));
+    assert!(repository
+        .get_engine_events(&market.id, 0)
+        .unwrap()
+        .is_empty());
+    let wallet = repository
+        .get_wallet(&user_id, &market.quote_asset)
+        .unwrap()
+        .unwrap();
+    assert_eq!(wallet.available, BigDecimal::from(100));
+}
+
+#[tokio::test]
+async fn test_non_crossing_order_gets_a_resting_receipt() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let user_id = create_funded_user(&repository, &[(&market.quote_asset, "100")]);
+    let service = create_test_service(repository.clone());
+    service
+        .admin_service()
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+
+    let response = service
+        .add_order(Request::new(add_order_request(
+            &market, &user_id, "BUY", "10", "5",
+        )))
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert!(response.trades.is_empty());
+    assert_eq!(response.total_fills, 0);
+    let order = repository.get_order(&response.order_id).unwrap().unwrap();
+    assert_eq!(order.user_id, user_id);
+
+    let resting = response.resting.unwrap();
+    assert_eq!(resting.side, "BUY");
+    assert_eq!(decimal(&resting.price), BigDecimal::from(10));
+    assert_eq!(decimal(&resting.remained_base), BigDecimal::from(5));
+    assert_eq!(decimal(&resting.reserved_amount), BigDecimal::from(50));
+}
+
+#[tokio::test]
+async fn test_add_order_response_caps_fills() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    // Trades credit existing wallets only, so both users hold both assets
+    let seller_id = create_funded_user(
+        &repository,
+        &[(&market.base_asset, "10"), (&market.quote_asset, "1")],
+    );
+    let buyer_id = create_funded_user(
+        &repository,
+        &[(&market.base_asset, "1"), (&market.quote_asset, "1000")],
+    );
+    let service = SpotServiceImpl {
+        max_response_fills: 2,
+        ..create_test_service(repository.clone())
+    };
+    service
+        .admin_service()
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+
+    for price in ["10", "11", "12"] {
+        service
+            .add_order(Request::new(add_order_request(
+                &market, &seller_id, "SELL", price, "1",
+            )))
+            .await
+            .unwrap();
+    }
+
+    let response = service
+        .add_order(Request::new(add_order_request(
+            &market, &buyer_id, "BUY", "12", "3",
+        )))
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(response.total_fills, 3);
+    assert_eq!(response.trades.len(), 2);
+    assert!(response.fills_truncated);
+    assert!(response.resting.is_none());
+}
+
+#[tokio::test]
+async fn test_crossing_post_only_order_is_rejected_and_unlocked() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let seller_id = create_funded_user(&repository, &[(&market.base_asset, "10")]);
+    let buyer_id = create_funded_user(&repository, &[(&market.quote_asset, "100")]);
+    let service = create_test_service(repository.clone());
+    service
+        .admin_service()
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+    service
+        .add_order(Request::new(add_order_request(
+            &market, &seller_id, "SELL", "10", "1",
+        )))
+        .await
+        .unwrap();
+
+    let crossing = AddOrderRequest {
+        post_only: true,
+        ..add_order_request(&market, &buyer_id, "BUY", "10", "1")
+    };
+    let status = service.add_order(Request::new(crossing)).await.unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+
+    let orders = repository
+        .list_orders(
+            OrderFilter::new().user_id(Some(buyer_id.clone())),
+            Some(Pagination::default()),
+        )
+        .unwrap();
+    assert_eq!(orders.items.len(), 1);
+    let rejected = &orders.items[0];
+    assert_eq!(rejected.get_status().unwrap(), OrderStatus::Canceled);
+    assert_eq!(
+        rejected.get_cancel_reason().unwrap(),
+        Some(CancelReason::PostOnly)
+    );
+    assert_eq!(rejected.filled_base, BigDecimal::from(0));
+    let wallet = repository
+        .get_wallet(&buyer_id, &market.quote_asset)
+        .unwrap()
+        .unwrap();
+    assert_eq!(wallet.available, BigDecimal::from(100));
+    assert_eq!(wallet.locked, BigDecimal::from(0));
+
+    // Below the best ask it adds liquidity and rests
+    let passive = AddOrderRequest {
+        post_only: true,
+        ..add_order_request(&market, &buyer_id, "BUY", "9", "1")
+    };
+    let response = service
+        .add_order(Request::new(passive))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(response.trades.is_empty());
+    assert!(response.resting.is_some());
+
+    // Market orders cannot be post-only
+    let market_order = AddOrderRequest {
+        post_only: true,
+        order_type: "MARKET".to_string(),
+        ..add_order_request(&market, &buyer_id, "BUY", "10", "1")
+    };
+    let status = service
+        .add_order(Request::new(market_order))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+}
@@ -0,0 +1,430 @@
+use database::provider::OrderDatabaseReader;
+use database::tests::test_db::{
+    create_funded_user, create_test_market, execute_test_trade, isolated_test_repository,
+};
+use tonic::{Code, Request};
+
+use crate::grpc::admin::admin_service_server::AdminService;
+use crate::grpc::admin::{
+    DeleteFeeTierRequest, GetUserStatusRequest, ListFeeTiersRequest, ListUserRestrictionsRequest,
+    ReloadMarketsRequest, SetFeeTierRequest, SetUserStatusRequest, SweepTreasuryRequest,
+};
+use crate::grpc::auth::method_scope;
+use crate::grpc::spot::spot_service_server::SpotService;
+use crate::grpc::spot::{
+    AmendOrderRequest, CancelAllOrdersRequest, CancelOrderRequest, GetOrderBookDepthRequest,
+    StartMarketRequest, UpdateMarketStatusRequest,
+};
+use crate::tests::test_service::{add_order_request, create_test_service};
+use common::auth::Scope;
+
+fn status_request(user_id: &str, status: &str) -> Request<SetUserStatusRequest> {
+    Request::new(SetUserStatusRequest {
+        user_id: user_id.to_string(),
+        status: status.to_string(),
+        reason: "Suspicious activity".to_string(),
+    })
+}
+
+#[test]
+fn test_admin_methods_need_admin_scope() {
+    for method in [
+        "ReloadMarkets",
+        "SetFeeTier",
+        "SetUserStatus",
+        "SweepTreasury",
+        "TriggerSnapshot",
+        "WithdrawFromTreasury",
+        "GetReconciliationReport",
+        "ReleaseOrphanedLocks",
+    ] {
+        assert_eq!(method_scope(method), Some(Scope::Admin), "{}", method);
+    }
+}
+
+#[tokio::test]
+async fn test_banned_user_cannot_trade_until_active_again() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let user_id = create_funded_user(&repository, &[(&market.quote_asset, "1000")]);
+
+    let service = create_test_service(repository.clone());
+    let admin = service.admin_service();
+    admin
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+    let order = service
+        .add_order(Request::new(add_order_request(
+            &market, &user_id, "BUY", "10", "1",
+        )))
+        .await
+        .unwrap()
+        .into_inner();
+
+    let status = admin
+        .set_user_status(Request::new(SetUserStatusRequest {
+            user_id: user_id.clone(),
+            status: "BANNED".to_string(),
+            reason: String::new(),
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    let banned = admin
+        .set_user_status(status_request(&user_id, "banned"))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(banned.canceled_orders, 1);
+    assert_eq!(banned.restriction.unwrap().status, "BANNED");
+    assert!(repository
+        .get_user_active_orders(&user_id)
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        repository
+            .get_order(&order.order_id)
+            .unwrap()
+            .unwrap()
+            .cancel_reason
+            .as_deref(),
+        Some("USER_BANNED")
+    );
+
+    let status = service
+        .add_order(Request::new(add_order_request(
+            &market, &user_id, "BUY", "10", "1",
+        )))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    let restrictions = admin
+        .list_user_restrictions(Request::new(ListUserRestrictionsRequest {}))
+        .await
+        .unwrap()
+        .into_inner()
+        .restrictions;
+    assert!(restrictions
+        .iter()
+        .any(|restriction| restriction.user_id == user_id));
+
+    let active = admin
+        .set_user_status(status_request(&user_id, "ACTIVE"))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(active.restriction.unwrap().status, "ACTIVE");
+    service
+        .add_order(Request::new(add_order_request(
+            &market, &user_id, "BUY", "10", "1",
+        )))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_ban_cancels_orders_in_halted_markets() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let user_id = create_funded_user(&repository, &[(&market.quote_asset, "1000")]);
+
+    let service = create_test_service(repository.clone());
+    let admin = service.admin_service();
+    admin
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+    let order = service
+        .add_order(Request::new(add_order_request(
+            &market, &user_id, "BUY", "10", "1",
+        )))
+        .await
+        .unwrap()
+        .into_inner();
+    admin
+        .update_market_status(Request::new(UpdateMarketStatusRequest {
+            market_id: market.id.clone(),
+            status: "HALTED_MATCHING".to_string(),
+        }))
+        .await
+        .unwrap();
+
+    // The halt refuses the user's own cancel but not the one the ban forces
+    let cancel = CancelOrderRequest {
+        order_id: order.order_id.clone(),
+        market_id: market.id.clone(),
+        ..Default::default()
+    };
+    let status = service
+        .cancel_order(Request::new(cancel))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+    let banned = admin
+        .set_user_status(status_request(&user_id, "BANNED"))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(banned.canceled_orders, 1);
+    assert_eq!(
+        repository
+            .get_order(&order.order_id)
+            .unwrap()
+            .unwrap()
+            .cancel_reason
+            .as_deref(),
+        Some("USER_BANNED")
+    );
+}
+
+#[tokio::test]
+async fn test_cancel_only_user_keeps_and_cancels_open_orders() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let user_id = create_funded_user(&repository, &[(&market.quote_asset, "1000")]);
+
+    let service = create_test_service(repository.clone());
+    let admin = service.admin_service();
+    admin
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+    let order = service
+        .add_order(Request::new(add_order_request(
+            &market, &user_id, "BUY", "10", "1",
+        )))
+        .await
+        .unwrap()
+        .into_inner();
+
+    let restricted = admin
+        .set_user_status(status_request(&user_id, "CANCEL_ONLY"))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(restricted.canceled_orders, 0);
+    let status = admin
+        .get_user_status(Request::new(GetUserStatusRequest {
+            user_id: user_id.clone(),
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(status.status, "CANCEL_ONLY");
+
+    let status = service
+        .amend_order(Request::new(AmendOrderRequest {
+            market_id: market.id.clone(),
+            order_id: order.order_id.clone(),
+            price: "11".to_string(),
+            remained_base: String::new(),
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+    service
+        .cancel_order(Request::new(CancelOrderRequest {
+            market_id: market.id.clone(),
+            order_id: order.order_id,
+            user_id: String::new(),
+            client_order_id: String::new(),
+        }))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_fee_tiers_are_set_listed_and_deleted() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let admin = create_test_service(repository).admin_service();
+
+    let status = admin
+        .set_fee_tier(Request::new(SetFeeTierRequest {
+            market_id: market.id.clone(),
+            min_volume: "1000".to_string(),
+            maker_fee: "1.5".to_string(),
+            taker_fee: "0.001".to_string(),
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    let tier = admin
+        .set_fee_tier(Request::new(SetFeeTierRequest {
+            market_id: market.id.clone(),
+            min_volume: "1000".to_string(),
+            maker_fee: "0.0005".to_string(),
+            taker_fee: "0.001".to_string(),
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(tier.market_id, market.id);
+
+    let list = || {
+        Request::new(ListFeeTiersRequest {
+            market_id: market.id.clone(),
+        })
+    };
+    let tiers = admin
+        .list_fee_tiers(list())
+        .await
+        .unwrap()
+        .into_inner()
+        .tiers;
+    assert_eq!(tiers.len(), 1);
+
+    let delete = || {
+        Request::new(DeleteFeeTierRequest {
+            market_id: market.id.clone(),
+            min_volume: "1000".to_string(),
+        })
+    };
+    assert!(
+        admin
+            .delete_fee_tier(delete())
+            .await
+            .unwrap()
+            .into_inner()
+            .deleted
+    );
+    assert!(
+        !admin
+            .delete_fee_tier(delete())
+            .await
+            .unwrap()
+            .into_inner()
+            .deleted
+    );
+    let tiers = admin
+        .list_fee_tiers(list())
+        .await
+        .unwrap()
+        .into_inner()
+        .tiers;
+    assert!(tiers.is_empty());
+}
+
+#[tokio::test]
+async fn test_reload_picks_up_markets_created_in_the_database() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let admin = create_test_service(repository.clone()).admin_service();
+
+    let market = create_test_market(&repository);
+    let reloaded = admin
+        .reload_markets(Request::new(ReloadMarketsRequest {}))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(reloaded.added, 1);
+
+    admin
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+    let reloaded = admin
+        .reload_markets(Request::new(ReloadMarketsRequest {}))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(reloaded.added, 0);
+    assert_eq!(reloaded.refreshed, 1);
+    assert_eq!(reloaded.removed, 0);
+}
+
+#[tokio::test]
+async fn test_sweep_empties_every_treasury_of_a_market() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let buyer_id = create_funded_user(&repository, &[(&market.quote_asset, "1000")]);
+    let seller_id = create_funded_user(&repository, &[(&market.base_asset, "10")]);
+    execute_test_trade(&repository, &market, &buyer_id, &seller_id, "100", "2");
+    let admin = create_test_service(repository).admin_service();
+    let sweep = |destination: &str| {
+        Request::new(SweepTreasuryRequest {
+            market_id: market.id.clone(),
+            destination: destination.to_string(),
+        })
+    };
+
+    let status = admin.sweep_treasury(sweep("")).await.unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    let withdrawals = admin
+        .sweep_treasury(sweep("cold"))
+        .await
+        .unwrap()
+        .into_inner()
+        .withdrawals;
+    assert!(!withdrawals.is_empty());
+    assert!(withdrawals
+        .iter()
+        .all(|withdrawal| withdrawal.destination == "cold" && withdrawal.market_id == market.id));
+
+    // Nothing is left to sweep
+    let withdrawals = admin
+        .sweep_treasury(sweep("cold"))
+        .await
+        .unwrap()
+        .into_inner()
+        .withdrawals;
+    assert!(withdrawals.is_empty());
+}
+
+#[tokio::test]
+async fn test_admin_runs_during_maintenance() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let service = create_test_service(repository);
+    service.maintenance.set_enabled(true);
+    service
+        .admin_service()
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+
+    // The trading API is refused while the admin service goes through
+    let status = service
+        .get_order_book_depth(Request::new(GetOrderBookDepthRequest {
+            market_id: market.id.clone(),
+            ..Default::default()
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::Unavailable);
+    service
+        .admin_service()
+        .cancel_all_orders(Request::new(CancelAllOrdersRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+}
@@ -0,0 +1,264 @@
+use bigdecimal::BigDecimal;
+use common::error::ERROR_CODE_METADATA;
+use database::models::models::{Market, MarketUpdate};
+use database::provider::{
+    MarketDatabaseWriter, OrderDatabaseReader, RiskLimitDatabaseWriter, WalletDatabaseReader,
+};
+use database::repository::Repository;
+use database::tests::test_db::{create_funded_user, create_test_market, isolated_test_repository};
+use tonic::{Code, Request};
+
+use crate::grpc::admin::admin_service_server::AdminService;
+use crate::grpc::service::SpotServiceImpl;
+use crate::grpc::spot::spot_service_server::SpotService;
+use crate::grpc::spot::{AmendOrderRequest, StartMarketRequest};
+use crate::tests::test_service::{add_order_request, create_test_service};
+
+async fn place(
+    service: &SpotServiceImpl<Repository>,
+    market: &Market,
+    user_id: &str,
+    side: &str,
+    price: &str,
+    base: &str,
+) -> String {
+    service
+        .add_order(Request::new(add_order_request(
+            market, user_id, side, price, base,
+        )))
+        .await
+        .unwrap()
+        .into_inner()
+        .order_id
+}
+
+async fn started_service(repository: &Repository, market: &Market) -> SpotServiceImpl<Repository> {
+    let service = create_test_service(repository.clone());
+    service
+        .admin_service()
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+    service
+}
+
+fn amend(market: &Market, order_id: &str, price: &str, remained_base: &str) -> AmendOrderRequest {
+    AmendOrderRequest {
+        market_id: market.id.clone(),
+        order_id: order_id.to_string(),
+        price: price.to_string(),
+        remained_base: remained_base.to_string(),
+    }
+}
+
+#[tokio::test]
+async fn test_only_a_smaller_amount_keeps_time_priority() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    // Trades credit existing wallets only, so every user holds both assets
+    let funds = [
+        (market.base_asset.as_str(), "10"),
+        (market.quote_asset.as_str(), "1000"),
+    ];
+    let first_id = create_funded_user(&repository, &funds);
+    let second_id = create_funded_user(&repository, &funds);
+    let buyer_id = create_funded_user(&repository, &funds);
+    let service = started_service(&repository, &market).await;
+
+    let first = place(&service, &market, &first_id, "SELL", "10", "5").await;
+    let second = place(&service, &market, &second_id, "SELL", "10", "5").await;
+
+    // Shrinking releases the base it no longer needs and stays first in line
+    let response = service
+        .amend_order(Request::new(amend(&market, &first, "", "2")))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(response.trades.is_empty());
+    assert_eq!(response.resting.unwrap().remained_base, "2");
+    let base = repository
+        .get_wallet(&first_id, &market.base_asset)
+        .unwrap()
+        .unwrap();
+    assert_eq!(base.available, BigDecimal::from(8));
+    assert_eq!(base.locked, BigDecimal::from(2));
+
+    let fill = service
+        .add_order(Request::new(add_order_request(
+            &market, &buyer_id, "BUY", "10", "1",
+        )))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(fill.trades[0].seller_order_id, first);
+
+    // Growing locks more base and queues behind the second order
+    service
+        .amend_order(Request::new(amend(&market, &first, "", "4")))
+        .await
+        .unwrap();
+    let base = repository
+        .get_wallet(&first_id, &market.base_asset)
+        .unwrap()
+        .unwrap();
+    assert_eq!(base.locked, BigDecimal::from(4));
+    let order = repository.get_order(&first).unwrap().unwrap();
+    assert_eq!(order.base_amount, BigDecimal::from(5));
+
+    let fill = service
+        .add_order(Request::new(add_order_request(
+            &market, &buyer_id, "BUY", "10", "1",
+        )))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(fill.trades[0].seller_order_id, second);
+}
+
+#[tokio::test]
+async fn test_amended_price_relocks_quote_and_may_cross() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let funds = [
+        (market.base_asset.as_str(), "10"),
+        (market.quote_asset.as_str(), "30"),
+    ];
+    let buyer_id = create_funded_user(&repository, &funds);
+    let seller_id = create_funded_user(&repository, &funds);
+    let service = started_service(&repository, &market).await;
+
+    let bid = place(&service, &market, &buyer_id, "BUY", "10", "2").await;
+    let ask = place(&service, &market, &seller_id, "SELL", "13", "1").await;
+
+    // 2 at 12 holds 24 of quote instead of 20
+    service
+        .amend_order(Request::new(amend(&market, &bid, "12", "")))
+        .await
+        .unwrap();
+    let quote = repository
+        .get_wallet(&buyer_id, &market.quote_asset)
+        .unwrap()
+        .unwrap();
+    assert_eq!(quote.available, BigDecimal::from(6));
+    assert_eq!(quote.locked, BigDecimal::from(24));
+
+    // 2 at 20 would need 40, more than the buyer holds, and nothing changes
+    let status = service
+        .amend_order(Request::new(amend(&market, &bid, "20", "")))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+    assert_eq!(
+        status.metadata().get(ERROR_CODE_METADATA).unwrap(),
+        "INSUFFICIENT_FUNDS"
+    );
+    let order = repository.get_order(&bid).unwrap().unwrap();
+    assert_eq!(order.price, BigDecimal::from(12));
+
+    // Moving the ask down onto the bid trades like a new order would
+    let response = service
+        .amend_order(Request::new(amend(&market, &ask, "12", "")))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(response.total_fills, 1);
+    assert!(response.resting.is_none());
+}
+
+#[tokio::test]
+async fn test_amendment_needs_something_to_change() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let service = create_test_service(repository.clone());
+
+    for request in [
+        amend(&market, "some-order", "", ""),
+        amend(&market, "some-order", "-1", ""),
+        amend(&market, "", "10", ""),
+    ] {
+        let status = service
+            .amend_order(Request::new(request))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+    }
+}
+
+#[tokio::test]
+async fn test_amendment_is_held_to_market_constraints() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    repository
+        .update_market(
+            &market.id,
+            MarketUpdate {
+                min_base_amount: Some(BigDecimal::from(1)),
+                min_quote_amount: Some(BigDecimal::from(5)),
+                price_precision: Some(1),
+                amount_precision: Some(2),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    let user_id = create_funded_user(&repository, &[(&market.quote_asset, "1000")]);
+    let service = started_service(&repository, &market).await;
+    let bid = place(&service, &market, &user_id, "BUY", "10", "2").await;
+
+    // Too precise a price or amount, too little base or too little quote
+    for (price, remained_base) in [("10.25", ""), ("", "1.005"), ("", "0.5"), ("2", "")] {
+        let status = service
+            .amend_order(Request::new(amend(&market, &bid, price, remained_base)))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument, "{}", status.message());
+    }
+    let order = repository.get_order(&bid).unwrap().unwrap();
+    assert_eq!(order.price, BigDecimal::from(10));
+    assert_eq!(order.remained_base, BigDecimal::from(2));
+}
+
+#[tokio::test]
+async fn test_amendment_is_held_to_locked_notional_limit() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    repository
+        .set_risk_limit(&market.id, None, Some(BigDecimal::from(100)))
+        .unwrap();
+    let user_id = create_funded_user(&repository, &[(&market.quote_asset, "1000")]);
+    let service = started_service(&repository, &market).await;
+    let bid = place(&service, &market, &user_id, "BUY", "10", "6").await;
+
+    // The order counts at its amended size instead of on top of its current one
+    let status = service
+        .amend_order(Request::new(amend(&market, &bid, "", "11")))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::ResourceExhausted);
+    service
+        .amend_order(Request::new(amend(&market, &bid, "", "9")))
+        .await
+        .unwrap();
+
+    // Under a lower limit the order may still shrink
+    repository
+        .set_risk_limit(&market.id, None, Some(BigDecimal::from(50)))
+        .unwrap();
+    service
+        .amend_order(Request::new(amend(&market, &bid, "", "8")))
+        .await
+        .unwrap();
+    let order = repository.get_order(&bid).unwrap().unwrap();
+    assert_eq!(order.remained_base, BigDecimal::from(8));
+}
@@ -0,0 +1,55 @@
+use database::provider::MarketDatabaseReader;
+use database::tests::test_db::isolated_test_repository;
+use tonic::{Code, Request};
+
+use crate::grpc::admin::admin_service_server::AdminService;
+use crate::grpc::service::SpotServiceImpl;
+use crate::grpc::spot::CreateMarketRequest;
+use crate::tests::test_service::create_test_service;
+use crate::validation::AssetRegistry;
+
+fn create_market_request(
+    market_id: &str,
+    base_asset: &str,
+    quote_asset: &str,
+) -> CreateMarketRequest {
+    CreateMarketRequest {
+        market_id: market_id.to_string(),
+        base_asset: base_asset.to_string(),
+        quote_asset: quote_asset.to_string(),
+        default_maker_fee: "0.001".to_string(),
+        default_taker_fee: "0.002".to_string(),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_create_market_rejects_unregistered_asset() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let service = SpotServiceImpl {
+        asset_registry: AssetRegistry::new(["BTC", "USDT"]),
+        ..create_test_service(repository.clone())
+    };
+
+    let status = service
+        .admin_service()
+        .create_market(Request::new(create_market_request(
+            "BTC-USDX", "BTC", "USDX",
+        )))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+    assert!(status.message().contains("Unknown quote asset: USDX"));
+    assert!(repository.get_market("BTC-USDX").unwrap().is_none());
+
+    service
+        .admin_service()
+        .create_market(Request::new(create_market_request(
+            "BTC-USDT", "BTC", "USDT",
+        )))
+        .await
+        .unwrap();
+    assert!(repository.get_market("BTC-USDT").unwrap().is_some());
+}
@@ -0,0 +1,195 @@
+use common::utils::get_utc_now_millis;
+use database::models::models::{CancelReason, Market, NewEngineEvent, OrderSide as DbOrderSide};
+use database::provider::{
+    EngineEventDatabaseReader, EngineEventDatabaseWriter, OrderDatabaseReader, OrderDatabaseWriter,
+    TradeDatabaseReader, WalletDatabaseReader,
+};
+use database::tests::test_db::{
+    create_funded_user, create_test_market, isolated_test_repository, new_limit_order,
+};
+use uuid::Uuid;
+
+use crate::models::trade_order::{OrderSide, TradeOrder};
+use crate::order_book::journal::JournalEntry;
+use crate::tests::test_models::{create_test_order_book, decimal, limit_order};
+
+/// A limit order of `user_id` paying 0.1% as maker and 0.2% as taker
+fn charged_order(
+    user_id: &str,
+    market: &Market,
+    side: OrderSide,
+    price: &str,
+    base: &str,
+) -> TradeOrder {
+    TradeOrder {
+        maker_fee: decimal("0.001"),
+        taker_fee: decimal("0.002"),
+        ..limit_order(user_id, market, side, price, base)
+    }
+}
+
+#[test]
+fn test_trades_stored_behind_the_book_are_the_ones_published() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let funds = [
+        (market.base_asset.as_str(), "10"),
+        (market.quote_asset.as_str(), "1000"),
+    ];
+    let seller_id = create_funded_user(&repository, &funds);
+    let buyer_id = create_funded_user(&repository, &funds);
+    let mut order_book = create_test_order_book(&repository, &market);
+    order_book.set_async_settlement(true);
+
+    let ask = charged_order(&seller_id, &market, OrderSide::Sell, "10", "3");
+    order_book.add_order(ask.clone()).unwrap();
+    let mut published = Vec::new();
+    for _ in 0..3 {
+        let bid = charged_order(&buyer_id, &market, OrderSide::Buy, "10", "1");
+        published.extend(order_book.add_order(bid).unwrap());
+    }
+    assert_eq!(published.len(), 3);
+    order_book.flush_settlement().unwrap();
+
+    for trade in &published {
+        let stored = repository
+            .get_trade_detail(&trade.id)
+            .unwrap()
+            .expect("Published trade was not stored")
+            .trade;
+        assert_eq!(stored.timestamp, trade.timestamp);
+        assert_eq!(stored.buyer_fee, trade.buyer_fee);
+        assert_eq!(stored.seller_fee, trade.seller_fee);
+    }
+    let stored_ask = repository.get_order(&ask.id).unwrap().unwrap();
+    assert_eq!(stored_ask.status, "FILLED");
+    let seller_quote = repository
+        .get_wallet(&seller_id, &market.quote_asset)
+        .unwrap()
+        .unwrap();
+    // 30 received less the 0.1% maker fee
+    assert_eq!(seller_quote.available, decimal("1029.97"));
+    let buyer_base = repository
+        .get_wallet(&buyer_id, &market.base_asset)
+        .unwrap()
+        .unwrap();
+    // 3 received less the 0.2% taker fee
+    assert_eq!(buyer_base.available, decimal("12.994"));
+
+    let events = repository.get_engine_events(&market.id, 0).unwrap();
+    assert_eq!(
+        repository.get_applied_sequence(&market.id).unwrap(),
+        events.last().unwrap().sequence
+    );
+}
+
+#[test]
+fn test_journaled_trades_not_stored_yet_are_stored_on_load() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let seller_id = create_funded_user(&repository, &[(&market.base_asset, "1")]);
+    let buyer_id = create_funded_user(&repository, &[(&market.quote_asset, "10")]);
+    let ask = repository
+        .create_order(new_limit_order(
+            &market,
+            &seller_id,
+            DbOrderSide::Sell,
+            "10",
+            "1",
+        ))
+        .unwrap();
+    let bid = repository
+        .create_order(new_limit_order(
+            &market,
+            &buyer_id,
+            DbOrderSide::Buy,
+            "10",
+            "1",
+        ))
+        .unwrap();
+
+    // Matched and published, but the engine stopped before the writer stored it
+    let trade_id = Uuid::new_v4().to_string();
+    let entry = JournalEntry::TradeExecuted {
+        buyer_order_id: bid.id.clone(),
+        seller_order_id: ask.id.clone(),
+        price: decimal("10"),
+        base_amount: decimal("1"),
+        is_buyer_taker: true,
+        trade_id: Some(trade_id.clone()),
+        timestamp: Some(get_utc_now_millis() / 1000),
+    };
+    let sequence = repository
+        .append_engine_event(NewEngineEvent {
+            market_id: market.id.clone(),
+            event_type: entry.event_type().as_str().to_string(),
+            order_id: entry.order_id().map(str::to_string),
+            payload: serde_json::to_string(&entry).unwrap(),
+            create_time: get_utc_now_millis(),
+        })
+        .unwrap()
+        .sequence;
+
+    let order_book = create_test_order_book(&repository, &market);
+    assert_eq!(order_book.bids_len(), 0);
+    assert_eq!(order_book.asks_len(), 0);
+    let stored = repository
+        .get_trade_detail(&trade_id)
+        .unwrap()
+        .unwrap()
+        .trade;
+    assert_eq!(stored.buyer_order_id, bid.id);
+    assert_eq!(
+        repository.get_order(&ask.id).unwrap().unwrap().status,
+        "FILLED"
+    );
+    assert_eq!(
+        repository.get_applied_sequence(&market.id).unwrap(),
+        sequence
+    );
+
+    // Replaying the entry again stores nothing twice
+    repository
+        .set_applied_sequence(&market.id, sequence - 1)
+        .unwrap();
+    create_test_order_book(&repository, &market);
+    let trades = repository.get_order_fills(&bid.id).unwrap();
+    assert_eq!(trades.len(), 1);
+}
+
+#[test]
+fn test_trades_the_database_refuses_are_set_aside_and_stop_the_book() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let seller_id = create_funded_user(&repository, &[(&market.base_asset, "1")]);
+    let buyer_id = create_funded_user(&repository, &[(&market.quote_asset, "20")]);
+    let mut order_book = create_test_order_book(&repository, &market);
+    order_book.set_async_settlement(true);
+
+    let ask = limit_order(&seller_id, &market, OrderSide::Sell, "10", "1");
+    order_book.add_order(ask.clone()).unwrap();
+    // Canceled behind the book, the ask can no longer be settled
+    repository
+        .cancel_order(&ask.id, CancelReason::AdminCancel)
+        .unwrap();
+    let bid = limit_order(&buyer_id, &market, OrderSide::Buy, "10", "1");
+    assert_eq!(order_book.add_order(bid).unwrap().len(), 1);
+
+    let error = order_book.flush_settlement().unwrap_err();
+    assert!(error.to_string().contains("could not be stored"));
+    assert!(order_book.settlement_failure().is_some());
+    let letters = repository.get_settlement_dead_letters(&market.id).unwrap();
+    assert_eq!(letters.len(), 1);
+    assert!(letters[0].fills.contains(&ask.id));
+
+    // Nothing is written on top of the trade that was set aside
+    let bid = limit_order(&buyer_id, &market, OrderSide::Buy, "9", "1");
+    assert!(order_book.add_order(bid.clone()).is_err());
+    assert!(repository.get_order(&bid.id).is_err());
+}
@@ -0,0 +1,245 @@
+use std::convert::Infallible;
+
+use std::sync::{Arc, Mutex};
+
+use common::auth::{ApiKeys, AuthLayer, Principal, Scope, API_KEY_HEADER};
+use common::utils::get_uuid_string;
+use database::tests::test_db::{create_funded_user, create_test_market, isolated_test_repository};
+use tonic::body::BoxBody;
+use tonic::{Code, Request, Status};
+use tower::{service_fn, Layer, Service};
+
+use crate::grpc::admin::admin_service_server::AdminService;
+use crate::grpc::auth::method_scope;
+use crate::grpc::spot::spot_service_server::SpotService;
+use crate::grpc::spot::{CancelOrderRequest, GetBalanceRequest, StartMarketRequest};
+use crate::tests::test_service::{add_order_request, create_test_service};
+
+fn status_of(response: &http::Response<BoxBody>) -> Code {
+    Status::from_header_map(response.headers())
+        .map(|status| status.code())
+        .unwrap_or(Code::Ok)
+}
+
+async fn call(keys: &str, method: &str, key: Option<&str>) -> Code {
+    let mut service = AuthLayer::new(ApiKeys::parse(keys).unwrap(), method_scope).layer(
+        service_fn(|_request: http::Request<()>| async {
+            Ok::<_, Infallible>(http::Response::new(BoxBody::default()))
+        }),
+    );
+    let mut request = http::Request::builder().uri(format!("/spot.SpotService/{}", method));
+    if let Some(key) = key {
+        request = request.header(API_KEY_HEADER, key);
+    }
+    let response = service.call(request.body(()).unwrap()).await.unwrap();
+    status_of(&response)
+}
+
+/// A request as the auth layer would pass it on for `principal`
+fn as_principal<T>(message: T, principal: Principal) -> Request<T> {
+    let mut request = Request::new(message);
+    request.extensions_mut().insert(principal);
+    request
+}
+
+#[test]
+fn test_api_keys_parse_and_grant_scopes() {
+    let keys = ApiKeys::parse("reader:read; trader:read,trade:0042 ;ops:admin").unwrap();
+    assert!(keys.authorize(Some("reader"), Scope::Read).is_ok());
+    assert_eq!(
+        keys.authorize(Some("reader"), Scope::Trade)
+            .unwrap_err()
+            .code(),
+        Code::PermissionDenied
+    );
+    assert!(keys.authorize(Some("trader"), Scope::Trade).is_ok());
+    // Admin holds every scope
+    assert!(keys.authorize(Some("ops"), Scope::Trade).is_ok());
+    assert_eq!(
+        keys.authorize(Some("unknown"), Scope::Read)
+            .unwrap_err()
+            .code(),
+        Code::Unauthenticated
+    );
+    assert_eq!(
+        keys.authorize(None, Scope::Read).unwrap_err().code(),
+        Code::Unauthenticated
+    );
+
+    assert!(ApiKeys::parse("key").is_err());
+    assert!(ApiKeys::parse("key:superuser").is_err());
+    assert!(ApiKeys::parse(":read").is_err());
+}
+
+#[test]
+fn test_api_keys_are_bound_to_the_user_they_act_for() {
+    let keys = ApiKeys::parse("reader:read;trader:read,trade,withdraw:0042;ops:admin").unwrap();
+    assert_eq!(
+        keys.authorize(Some("trader"), Scope::Withdraw).unwrap(),
+        Principal::User("42".to_string())
+    );
+    assert_eq!(
+        keys.authorize(Some("reader"), Scope::Read).unwrap(),
+        Principal::MarketData
+    );
+    assert_eq!(
+        keys.authorize(Some("ops"), Scope::Withdraw).unwrap(),
+        Principal::Admin
+    );
+
+    let trader = Principal::User("42".to_string());
+    assert!(trader.authorize_user(" 42").is_ok());
+    assert_eq!(
+        trader.authorize_user("43").unwrap_err().code(),
+        Code::PermissionDenied
+    );
+    assert!(trader.authorize_user("").is_err());
+    assert!(Principal::MarketData.authorize_user("42").is_err());
+    assert!(Principal::Admin.authorize_user("43").is_ok());
+
+    // Only read keys may be unbound, and admin keys act for everybody
+    assert!(ApiKeys::parse("trader:trade").is_err());
+    assert!(ApiKeys::parse("payout:withdraw").is_err());
+    assert!(ApiKeys::parse("ops:admin:42").is_err());
+    assert!(ApiKeys::parse("trader:trade:").is_err());
+}
+
+#[test]
+fn test_serving_without_keys_needs_auth_disabled() {
+    assert!(ApiKeys::default().require(false).is_err());
+    assert!(ApiKeys::default().require(true).is_ok());
+    let keys = ApiKeys::parse("ops:admin").unwrap();
+    assert!(keys.clone().require(false).is_ok());
+    assert!(keys.require(true).is_err());
+}
+
+#[test]
+fn test_admin_methods_need_the_admin_scope() {
+    for method in [
+        "CreateMarket",
+        "UpdateMarketStatus",
+        "CancelAllOrders",
+        "SetMaintenanceMode",
+        "CompleteDeposit",
+        "WithdrawWhatever",
+    ] {
+        assert_eq!(method_scope(method), Some(Scope::Admin), "{}", method);
+    }
+    assert_eq!(method_scope("AddOrder"), Some(Scope::Trade));
+    assert_eq!(method_scope("OpenSession"), Some(Scope::Trade));
+    assert_eq!(method_scope("RequestWithdrawal"), Some(Scope::Withdraw));
+    assert_eq!(method_scope("SubscribeTrades"), Some(Scope::Read));
+    assert_eq!(method_scope("HealthCheck"), None);
+}
+
+#[tokio::test]
+async fn test_auth_layer_checks_the_method_called() {
+    let keys = "trader:read,trade:42;ops:admin";
+    assert_eq!(call(keys, "AddOrder", Some("trader")).await, Code::Ok);
+    assert_eq!(
+        call(keys, "CreateMarket", Some("trader")).await,
+        Code::PermissionDenied
+    );
+    assert_eq!(call(keys, "CreateMarket", Some("ops")).await, Code::Ok);
+    assert_eq!(call(keys, "AddOrder", None).await, Code::Unauthenticated);
+    assert_eq!(call(keys, "HealthCheck", None).await, Code::Ok);
+    // Without keys auth is off
+    assert_eq!(call("", "CreateMarket", None).await, Code::Ok);
+}
+
+#[tokio::test]
+async fn test_auth_layer_passes_on_the_principal() {
+    let seen = Arc::new(Mutex::new(None));
+    let mut service = AuthLayer::new(ApiKeys::parse("trader:trade:42").unwrap(), method_scope)
+        .layer(service_fn(|request: http::Request<()>| {
+            *seen.lock().unwrap() = request.extensions().get::<Principal>().cloned();
+            async { Ok::<_, Infallible>(http::Response::new(BoxBody::default())) }
+        }));
+    let request = http::Request::builder()
+        .uri("/spot.SpotService/AddOrder")
+        .header(API_KEY_HEADER, "trader")
+        .body(())
+        .unwrap();
+    service.call(request).await.unwrap();
+
+    assert_eq!(
+        *seen.lock().unwrap(),
+        Some(Principal::User("42".to_string()))
+    );
+}
+
+#[tokio::test]
+async fn test_user_key_acts_for_its_own_user_only() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let user_id = create_funded_user(&repository, &[(market.quote_asset.as_str(), "1000")]);
+    let other = Principal::User(format!("{}-other", user_id));
+    let owner = Principal::User(user_id.clone());
+    let service = create_test_service(repository.clone());
+    service
+        .admin_service()
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+
+    let order = add_order_request(&market, &user_id, "BUY", "10", "1");
+    let denied = service
+        .add_order(as_principal(order.clone(), other.clone()))
+        .await
+        .unwrap_err();
+    assert_eq!(denied.code(), Code::PermissionDenied);
+    let order_id = service
+        .add_order(as_principal(order, owner.clone()))
+        .await
+        .unwrap()
+        .into_inner()
+        .order_id;
+
+    let balance = GetBalanceRequest {
+        user_id: user_id.clone(),
+        asset: market.quote_asset.clone(),
+    };
+    let denied = service
+        .get_balance(as_principal(balance, other.clone()))
+        .await
+        .unwrap_err();
+    assert_eq!(denied.code(), Code::PermissionDenied);
+
+    // Canceling by order id checks the order's owner
+    let cancel = CancelOrderRequest {
+        order_id,
+        market_id: market.id.clone(),
+        ..Default::default()
+    };
+    let denied = service
+        .cancel_order(as_principal(cancel.clone(), other.clone()))
+        .await
+        .unwrap_err();
+    assert_eq!(denied.code(), Code::PermissionDenied);
+    let canceled = service
+        .cancel_order(as_principal(cancel.clone(), owner.clone()))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(canceled.success);
+
+    // Orders that no longer rest keep their owner, and unknown orders have none
+    let denied = service
+        .cancel_order(as_principal(cancel.clone(), other))
+        .await
+        .unwrap_err();
+    assert_eq!(denied.code(), Code::PermissionDenied);
+    let unknown = CancelOrderRequest {
+        order_id: get_uuid_string(),
+        ..cancel
+    };
+    let denied = service
+        .cancel_order(as_principal(unknown, owner))
+        .await
+        .unwrap_err();
+    assert_eq!(denied.code(), Code::PermissionDenied);
+}
@@ -0,0 +1,110 @@
+use database::provider::OrderDatabaseReader;
+use database::tests::test_db::{create_funded_user, create_test_market, isolated_test_repository};
+use tonic::{Code, Request};
+
+use crate::grpc::admin::admin_service_server::AdminService;
+use crate::grpc::spot::spot_service_server::SpotService;
+use crate::grpc::spot::{
+    BatchAddOrderRequest, BatchCancelRequest, CancelOrderRequest, StartMarketRequest,
+};
+use crate::tests::test_service::{add_order_request, create_test_service};
+use crate::validation::MAX_BATCH_SIZE;
+
+#[tokio::test]
+async fn test_batch_entries_succeed_or_fail_on_their_own() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let user_id = create_funded_user(&repository, &[(market.quote_asset.as_str(), "1000")]);
+    let service = create_test_service(repository.clone());
+    service
+        .admin_service()
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+
+    let mut invalid = add_order_request(&market, &user_id, "BUY", "10", "1");
+    invalid.price = "-1".to_string();
+    let results = service
+        .add_orders(Request::new(BatchAddOrderRequest {
+            orders: vec![
+                add_order_request(&market, &user_id, "BUY", "10", "1"),
+                invalid,
+                add_order_request(&market, &user_id, "BUY", "9", "1"),
+                add_order_request(&market, &user_id, "BUY", "10", "200"),
+            ],
+        }))
+        .await
+        .unwrap()
+        .into_inner()
+        .results;
+
+    assert_eq!(results.len(), 4);
+    assert_eq!(results[0].code, Code::Ok as i32);
+    assert_eq!(results[1].code, Code::InvalidArgument as i32);
+    assert!(results[1].order.is_none());
+    assert_eq!(results[2].code, Code::Ok as i32);
+    // An order the engine refused is stored, and its result says why
+    assert_eq!(results[3].code, Code::FailedPrecondition as i32);
+    let rejected = results[3].order.as_ref().unwrap();
+    assert_eq!(rejected.reject_reason, "INSUFFICIENT_FUNDS");
+    let order = repository.get_order(&rejected.order_id).unwrap().unwrap();
+    assert_eq!(order.status, "REJECTED");
+    let placed: Vec<String> = [&results[0], &results[2]]
+        .map(|result| result.order.as_ref().unwrap().order_id.clone())
+        .to_vec();
+
+    let cancel = |order_id: &str| CancelOrderRequest {
+        order_id: order_id.to_string(),
+        market_id: market.id.clone(),
+        ..Default::default()
+    };
+    let results = service
+        .cancel_orders(Request::new(BatchCancelRequest {
+            orders: vec![
+                cancel(&placed[0]),
+                cancel("no-such-order"),
+                cancel(&placed[1]),
+            ],
+        }))
+        .await
+        .unwrap()
+        .into_inner()
+        .results;
+
+    assert_eq!(results.len(), 3);
+    assert!(results[0].order.as_ref().unwrap().success);
+    assert_ne!(results[1].code, Code::Ok as i32);
+    assert!(results[2].order.as_ref().unwrap().success);
+    for order_id in &placed {
+        let order = repository.get_order(order_id).unwrap().unwrap();
+        assert_eq!(order.status, "CANCELED");
+    }
+}
+
+#[tokio::test]
+async fn test_batch_size_is_limited() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let service = create_test_service(repository.clone());
+
+    let status = service
+        .add_orders(Request::new(BatchAddOrderRequest { orders: vec![] }))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    let request = add_order_request(&market, "some-user", "BUY", "10", "1");
+    let status = service
+        .add_orders(Request::new(BatchAddOrderRequest {
+            orders: vec![request; MAX_BATCH_SIZE + 1],
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+}
@@ -0,0 +1,185 @@
+use bigdecimal::BigDecimal;
+use std::str::FromStr;
+
+use crate::models::trade_order::{OrderSide, OrderType, TradeOrder};
+use crate::order_book::book_side::BookSide;
+use crate::tests::test_models::create_order;
+
+/// An order created and queued `n`th
+fn order(side: OrderSide, price: &str, base_amount: &str, n: i64) -> TradeOrder {
+    TradeOrder {
+        create_time: n,
+        priority: n,
+        ..create_order(side, price, base_amount, "0", OrderType::Limit, "")
+    }
+}
+
+fn ids(side: &BookSide) -> Vec<String> {
+    side.iter().map(|o| o.id.clone()).collect()
+}
+
+#[test]
+fn test_orders_trade_in_price_then_time_priority() {
+    let mut bids = BookSide::new(OrderSide::Buy);
+    let late = order(OrderSide::Buy, "10", "1", 3);
+    let best = order(OrderSide::Buy, "11", "1", 4);
+    let low = order(OrderSide::Buy, "9", "1", 1);
+    // Recovered orders can arrive out of creation order
+    let early = order(OrderSide::Buy, "10", "1", 2);
+    for o in [&late, &best, &low, &early] {
+        bids.push(o.clone());
+    }
+    assert_eq!(
+        ids(&bids),
+        [&best.id, &early.id, &late.id, &low.id].map(String::clone)
+    );
+
+    let mut asks = BookSide::new(OrderSide::Sell);
+    let high = order(OrderSide::Sell, "12", "1", 1);
+    let lowest = order(OrderSide::Sell, "11", "1", 2);
+    asks.push(high.clone());
+    asks.push(lowest.clone());
+    assert_eq!(asks.best_price(), Some(&BigDecimal::from(11)));
+
+    // A popped order put back keeps its place ahead of its level
+    let popped = bids.pop_best().unwrap();
+    assert_eq!(popped.id, best.id);
+    let mut head = bids.pop_best().unwrap();
+    head.remained_base = "0.5".parse().unwrap();
+    bids.push_front(head);
+    assert_eq!(bids.best().unwrap().id, early.id);
+    assert_eq!(bids.len(), 3);
+}
+
+#[test]
+fn test_remove_and_depth_follow_the_index() {
+    let mut asks = BookSide::new(OrderSide::Sell);
+    let first = order(OrderSide::Sell, "10", "2", 1);
+    let second = order(OrderSide::Sell, "10", "3", 2);
+    let iceberg = TradeOrder {
+        display_amount: Some(BigDecimal::from(1)),
+        ..order(OrderSide::Sell, "12", "5", 3)
+    };
+    for o in [&first, &second, &iceberg] {
+        asks.push(o.clone());
+    }
+
+    let depth = asks.depth();
+    assert_eq!(depth.get(&BigDecimal::from(10)), Some(&BigDecimal::from(5)));
+    assert_eq!(depth.get(&BigDecimal::from(12)), Some(&BigDecimal::from(1)));
+
+    assert_eq!(asks.remove(&first.id).unwrap().id, first.id);
+    assert!(asks.remove(&first.id).is_none());
+    assert!(asks.get(&first.id).is_none());
+    assert_eq!(asks.get(&second.id).unwrap().id, second.id);
+
+    // An emptied level disappears from the depth
+    asks.remove(&second.id);
+    assert_eq!(asks.best_price(), Some(&BigDecimal::from(12)));
+    assert!(!asks.depth().contains_key(&BigDecimal::from(10)));
+
+    asks.clear();
+    assert!(asks.is_empty());
+    assert!(asks.best().is_none());
+}
+
+#[test]
+fn test_level_totals_follow_fills_and_replacements() {
+    let mut asks = BookSide::new(OrderSide::Sell);
+    let plain = order(OrderSide::Sell, "10", "4", 1);
+    let iceberg = TradeOrder {
+        display_amount: Some(BigDecimal::from(2)),
+        ..order(OrderSide::Sell, "10", "5", 2)
+    };
+    asks.push(plain.clone());
+    asks.push(iceberg.clone());
+    assert_eq!(asks.top_levels(1, None), [level("10", "6")]);
+
+    // A partly filled head put back shows what is left of it
+    let mut head = asks.pop_best().unwrap();
+    assert_eq!(asks.top_levels(1, None), [level("10", "2")]);
+    head.remained_base = BigDecimal::from(1);
+    asks.push_front(head);
+    assert_eq!(asks.top_levels(1, None), [level("10", "3")]);
+
+    // An iceberg down to less than its slice shows only that
+    let smaller = TradeOrder {
+        remained_base: BigDecimal::from(1),
+        ..iceberg.clone()
+    };
+    asks.replace(smaller).unwrap();
+    assert_eq!(
+        asks.depth().get(&BigDecimal::from(10)),
+        Some(&BigDecimal::from(2))
+    );
+
+    asks.remove(&plain.id);
+    asks.remove(&iceberg.id);
+    assert!(asks.depth().is_empty());
+}
+
+fn level(price: &str, amount: &str) -> (BigDecimal, BigDecimal) {
+    (
+        BigDecimal::from_str(price).unwrap(),
+        BigDecimal::from_str(amount).unwrap(),
+    )
+}
+
+#[test]
+fn test_top_levels_merge_into_buckets_away_from_the_spread() {
+    let mut bids = BookSide::new(OrderSide::Buy);
+    let mut asks = BookSide::new(OrderSide::Sell);
+    for (price, amount) in [("10.4", "1"), ("10.1", "2"), ("9.9", "4"), ("9", "8")] {
+        bids.push(order(OrderSide::Buy, price, amount, 1));
+    }
+    for (price, amount) in [("10.6", "1"), ("10.9", "2"), ("11.2", "4")] {
+        asks.push(order(OrderSide::Sell, price, amount, 1));
+    }
+
+    assert_eq!(
+        bids.top_levels(2, None),
+        [level("10.4", "1"), level("10.1", "2")]
+    );
+    let step = BigDecimal::from(1);
+    assert_eq!(
+        bids.top_levels(2, Some(&step)),
+        [level("10", "3"), level("9", "12")]
+    );
+    assert_eq!(
+        asks.top_levels(5, Some(&step)),
+        [level("11", "3"), level("12", "4")]
+    );
+}
+
+#[test]
+fn test_every_change_is_counted() {
+    let mut asks = BookSide::new(OrderSide::Sell);
+    let resting = order(OrderSide::Sell, "10", "1", 1);
+    asks.push(resting.clone());
+    assert_eq!(asks.changes(), 1);
+
+    // Looking at the side changes nothing, failing to remove neither
+    asks.top_levels(10, None);
+    asks.remove("missing");
+    assert_eq!(asks.changes(), 1);
+
+    asks.remove(&resting.id);
+    assert_eq!(asks.changes(), 2);
+}
+
+#[test]
+fn test_dirty_levels_are_the_ones_touched_since_last_taken() {
+    let mut bids = BookSide::new(OrderSide::Buy);
+    let ten = order(OrderSide::Buy, "10", "1", 1);
+    bids.push(ten.clone());
+    bids.push(order(OrderSide::Buy, "9", "2", 2));
+    assert_eq!(
+        bids.take_dirty_levels(),
+        [level("9", "2"), level("10", "1")]
+    );
+    assert!(bids.take_dirty_levels().is_empty());
+
+    // Only the level that changed comes back, a removed one at zero
+    bids.remove(&ten.id);
+    assert_eq!(bids.take_dirty_levels(), [level("10", "0")]);
+}
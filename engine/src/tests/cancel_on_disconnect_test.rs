@@ -0,0 +1,114 @@
+use std::time::Duration;
+
+use database::provider::OrderDatabaseReader;
+use database::tests::test_db::{create_funded_user, create_test_market, isolated_test_repository};
+use futures::StreamExt;
+use tonic::{Code, Request};
+
+use crate::grpc::admin::admin_service_server::AdminService;
+use crate::grpc::spot::spot_service_server::SpotService;
+use crate::grpc::spot::{
+    OpenSessionRequest, SetCancelOnDisconnectRequest, StartMarketRequest,
+    SubscribeUserEventsRequest,
+};
+use crate::tests::test_service::{add_order_request, create_test_service};
+
+fn cancel_on_disconnect(user_id: &str, enabled: bool) -> Request<SetCancelOnDisconnectRequest> {
+    Request::new(SetCancelOnDisconnectRequest {
+        user_id: user_id.to_string(),
+        enabled,
+    })
+}
+
+#[tokio::test]
+async fn test_closing_the_stream_cancels_orders_in_every_market() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let markets = [
+        create_test_market(&repository),
+        create_test_market(&repository),
+    ];
+    let user_id = create_funded_user(
+        &repository,
+        &[
+            (markets[0].quote_asset.as_str(), "100"),
+            (markets[1].quote_asset.as_str(), "100"),
+        ],
+    );
+    let service = create_test_service(repository.clone());
+
+    let status = service
+        .set_cancel_on_disconnect(cancel_on_disconnect(&user_id, true))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+
+    let mut order_ids = Vec::new();
+    for market in &markets {
+        service
+            .admin_service()
+            .start_market(Request::new(StartMarketRequest {
+                market_id: market.id.clone(),
+            }))
+            .await
+            .unwrap();
+        let order = service
+            .add_order(Request::new(add_order_request(
+                market, &user_id, "BUY", "10", "1",
+            )))
+            .await
+            .unwrap()
+            .into_inner();
+        order_ids.push(order.order_id);
+    }
+
+    // Watching the user's events is not a session of theirs
+    let _events = service
+        .subscribe_user_events(Request::new(SubscribeUserEventsRequest {
+            user_id: user_id.clone(),
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    let status = service
+        .set_cancel_on_disconnect(cancel_on_disconnect(&user_id, true))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+
+    let mut session = service
+        .open_session(Request::new(OpenSessionRequest {
+            user_id: user_id.clone(),
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    let opened = session.next().await.unwrap().unwrap();
+    assert_eq!(opened.user_id, user_id);
+    assert_eq!(opened.sessions, 1);
+
+    let response = service
+        .set_cancel_on_disconnect(cancel_on_disconnect(&user_id, true))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(response.enabled);
+    assert_eq!(response.sessions, 1);
+
+    drop(session);
+
+    // The orders are canceled off the runtime's workers, shortly after the stream closes
+    for order_id in &order_ids {
+        let mut order = repository.get_order(order_id).unwrap().unwrap();
+        for _ in 0..100 {
+            if order.status == "CANCELED" {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            order = repository.get_order(order_id).unwrap().unwrap();
+        }
+        assert_eq!(order.status, "CANCELED");
+        assert_eq!(order.cancel_reason.as_deref(), Some("DISCONNECTED"));
+    }
+}
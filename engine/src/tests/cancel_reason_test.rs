@@ -0,0 +1,164 @@
+use std::sync::Arc;
+
+use database::models::models::{CancelReason, Market, OrderSide, OrderStatus};
+use database::provider::{OrderDatabaseReader, OrderDatabaseWriter};
+use database::repository::Repository;
+use database::tests::test_db::{
+    create_funded_user, create_test_market, isolated_test_repository, new_limit_order,
+};
+use tokio::sync::RwLock;
+use tonic::Request;
+
+use crate::grpc::admin::admin_service_server::AdminService;
+use crate::grpc::spot::spot_service_server::SpotService;
+use crate::grpc::spot::{CancelAllOrdersRequest, CancelOrderRequest, StartMarketRequest};
+use crate::tests::test_service::create_test_service;
+
+/// Places two resting orders on `market` and returns their ids.
+fn place_resting_orders(repository: &Repository, market: &Market, user_id: &str) -> Vec<String> {
+    [
+        new_limit_order(market, user_id, OrderSide::Buy, "10", "1"),
+        new_limit_order(market, user_id, OrderSide::Sell, "20", "1"),
+    ]
+    .into_iter()
+    .map(|order| repository.create_order(order).unwrap().id)
+    .collect()
+}
+
+fn cancel_reason(repository: &Repository, order_id: &str) -> Option<CancelReason> {
+    let order = repository.get_order(order_id).unwrap().unwrap();
+    assert_eq!(order.get_status().unwrap(), OrderStatus::Canceled);
+    order.get_cancel_reason().unwrap()
+}
+
+#[tokio::test]
+async fn test_mass_cancels_stamp_their_reason() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let admin_market = create_test_market(&repository);
+    let closed_market = create_test_market(&repository);
+    let user_id = create_funded_user(
+        &repository,
+        &[
+            (&admin_market.base_asset, "10"),
+            (&admin_market.quote_asset, "100"),
+            (&closed_market.base_asset, "10"),
+            (&closed_market.quote_asset, "100"),
+        ],
+    );
+    let admin_orders = place_resting_orders(&repository, &admin_market, &user_id);
+    let closed_orders = place_resting_orders(&repository, &closed_market, &user_id);
+    let user_order = repository
+        .create_order(new_limit_order(
+            &closed_market,
+            &user_id,
+            OrderSide::Buy,
+            "5",
+            "1",
+        ))
+        .unwrap()
+        .id;
+
+    let service = create_test_service(repository.clone());
+    for market in [&admin_market, &closed_market] {
+        service
+            .admin_service()
+            .start_market(Request::new(StartMarketRequest {
+                market_id: market.id.clone(),
+            }))
+            .await
+            .unwrap();
+    }
+
+    service
+        .cancel_order(Request::new(CancelOrderRequest {
+            order_id: user_order.clone(),
+            market_id: closed_market.id.clone(),
+            ..Default::default()
+        }))
+        .await
+        .unwrap();
+    service
+        .admin_service()
+        .cancel_all_orders(Request::new(CancelAllOrdersRequest {
+            market_id: admin_market.id.clone(),
+        }))
+        .await
+        .unwrap();
+
+    // Closing every market cancels the orders that are still open
+    service
+        .market_manager
+        .read()
+        .await
+        .cancel_all_orders_global(CancelReason::MarketClosed)
+        .unwrap();
+
+    assert_eq!(
+        cancel_reason(&repository, &user_order),
+        Some(CancelReason::UserCanceled)
+    );
+    for order_id in &admin_orders {
+        assert_eq!(
+            cancel_reason(&repository, order_id),
+            Some(CancelReason::AdminCancel)
+        );
+    }
+    for order_id in &closed_orders {
+        assert_eq!(
+            cancel_reason(&repository, order_id),
+            Some(CancelReason::MarketClosed)
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_idempotent_cancel_retry_succeeds_once_canceled() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let user_id = create_funded_user(
+        &repository,
+        &[(&market.base_asset, "10"), (&market.quote_asset, "100")],
+    );
+    let order_id = place_resting_orders(&repository, &market, &user_id).remove(0);
+
+    let mut service = create_test_service(repository.clone());
+    let market_manager = Arc::into_inner(service.market_manager)
+        .unwrap()
+        .into_inner()
+        .with_idempotent_cancel(true);
+    service.market_manager = Arc::new(RwLock::new(market_manager));
+    service
+        .admin_service()
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+
+    let cancel = || {
+        Request::new(CancelOrderRequest {
+            order_id: order_id.clone(),
+            market_id: market.id.clone(),
+            ..Default::default()
+        })
+    };
+    let first = service.cancel_order(cancel()).await.unwrap().into_inner();
+    let canceled = repository.get_order(&order_id).unwrap().unwrap();
+
+    let retry = service.cancel_order(cancel()).await.unwrap().into_inner();
+
+    assert!(first.success);
+    assert!(retry.success);
+    assert_eq!(retry.order_id, order_id);
+    // The retry leaves the order as the first cancel did
+    let after_retry = repository.get_order(&order_id).unwrap().unwrap();
+    assert_eq!(after_retry.update_time, canceled.update_time);
+    assert_eq!(
+        cancel_reason(&repository, &order_id),
+        Some(CancelReason::UserCanceled)
+    );
+}
@@ -0,0 +1,134 @@
+use std::thread;
+use std::time::Duration;
+
+use database::models::models::{CancelReason, RejectReason};
+use database::provider::{OrderDatabaseReader, PriceBandDatabaseWriter};
+use database::tests::test_db::{create_funded_user, create_test_market, isolated_test_repository};
+
+use crate::models::matched_trade::MatchedTrade;
+use crate::models::trade_order::OrderSide;
+use crate::order_book::circuit_breaker::{CircuitBreaker, PriceBandConfig};
+use crate::order_book::OrderBookError;
+use crate::tests::test_models::{create_test_order_book, decimal, limit_order};
+
+fn book_error(result: anyhow::Result<Vec<MatchedTrade>>) -> String {
+    let e = result.unwrap_err();
+    assert!(e.downcast_ref::<OrderBookError>().is_some(), "{}", e);
+    e.to_string()
+}
+
+#[test]
+fn test_circuit_breaker_halts_after_fast_move_and_resumes() {
+    let mut breaker = CircuitBreaker::new(PriceBandConfig {
+        band_percent: Some(decimal("20")),
+        halt_percent: Some(decimal("10")),
+        halt_window_ms: 1_000,
+        halt_duration_ms: 500,
+    });
+
+    assert!(breaker.check_band(&decimal("15"), None).is_ok());
+    assert!(breaker
+        .check_band(&decimal("12"), Some(&decimal("10")))
+        .is_ok());
+    assert!(breaker
+        .check_band(&decimal("7.9"), Some(&decimal("10")))
+        .is_err());
+
+    assert!(breaker.admit_trade(&decimal("10"), 0));
+    assert!(breaker.admit_trade(&decimal("10.9"), 400));
+    // 10 traded 1.2 seconds ago has left the window, 10.9 has not
+    assert!(breaker.admit_trade(&decimal("11.8"), 1_200));
+    assert!(!breaker.admit_trade(&decimal("13.5"), 1_300));
+    assert_eq!(breaker.halted_until(1_300), Some(1_800));
+    assert_eq!(breaker.halted_until(1_799), Some(1_800));
+
+    // Once resumed, prices from before the halt no longer count
+    assert_eq!(breaker.halted_until(1_800), None);
+    assert!(breaker.admit_trade(&decimal("13.5"), 1_800));
+}
+
+#[test]
+fn test_order_book_enforces_its_market_price_band() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    repository
+        .set_price_band(&market.id, Some(decimal("20")), None, 0, 0)
+        .unwrap();
+    let funds = [
+        (market.base_asset.as_str(), "100"),
+        (market.quote_asset.as_str(), "1000"),
+    ];
+    let seller_id = create_funded_user(&repository, &funds);
+    let buyer_id = create_funded_user(&repository, &funds);
+    let mut order_book = create_test_order_book(&repository, &market);
+
+    // Without a last trade there is nothing to hold prices to
+    for (user_id, side) in [(&seller_id, OrderSide::Sell), (&buyer_id, OrderSide::Buy)] {
+        let order = limit_order(user_id, &market, side, "10", "1");
+        order_book.add_order(order).unwrap();
+    }
+
+    let far_ask = limit_order(&seller_id, &market, OrderSide::Sell, "12.5", "1");
+    let message = book_error(order_book.add_order(far_ask.clone()));
+    assert!(message.contains("20%"), "{}", message);
+    // Refused before it reached the book, and stored as such
+    let rejected = repository.get_order(&far_ask.id).unwrap().unwrap();
+    assert_eq!(
+        rejected.get_reject_reason().unwrap(),
+        Some(RejectReason::PriceBand)
+    );
+    assert_eq!(order_book.asks_len(), 0);
+
+    let near_ask = limit_order(&seller_id, &market, OrderSide::Sell, "12", "1");
+    order_book.add_order(near_ask).unwrap();
+    assert_eq!(order_book.asks_len(), 1);
+}
+
+#[test]
+fn test_order_book_halts_matching_on_fast_move() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    repository
+        .set_price_band(&market.id, None, Some(decimal("10")), 60_000, 300)
+        .unwrap();
+    let funds = [
+        (market.base_asset.as_str(), "100"),
+        (market.quote_asset.as_str(), "1000"),
+    ];
+    let seller_id = create_funded_user(&repository, &funds);
+    let buyer_id = create_funded_user(&repository, &funds);
+    let mut order_book = create_test_order_book(&repository, &market);
+
+    for (user_id, side) in [(&seller_id, OrderSide::Sell), (&buyer_id, OrderSide::Buy)] {
+        let order = limit_order(user_id, &market, side, "10", "1");
+        order_book.add_order(order).unwrap();
+    }
+
+    // Buying the ask at 12 would move the price 20% within the window
+    let ask = limit_order(&seller_id, &market, OrderSide::Sell, "12", "1");
+    order_book.add_order(ask.clone()).unwrap();
+    let buy = limit_order(&buyer_id, &market, OrderSide::Buy, "12", "1");
+    let trades = order_book.add_order(buy.clone()).unwrap();
+    assert!(trades.is_empty());
+    let order = repository.get_order(&buy.id).unwrap().unwrap();
+    assert_eq!(
+        order.get_cancel_reason().unwrap(),
+        Some(CancelReason::CircuitBreaker)
+    );
+    assert!(order_book.get_order_by_id(ask.id.clone()).is_ok());
+
+    let retry = limit_order(&buyer_id, &market, OrderSide::Buy, "12", "1");
+    let message = book_error(order_book.add_order(retry));
+    assert!(message.contains("halted"), "{}", message);
+
+    // Matching resumes on its own once the halt runs out
+    thread::sleep(Duration::from_millis(350));
+    let retry = limit_order(&buyer_id, &market, OrderSide::Buy, "12", "1");
+    let trades = order_book.add_order(retry).unwrap();
+    assert_eq!(trades.len(), 1);
+    assert_eq!(trades[0].price, decimal("12"));
+}
@@ -0,0 +1,132 @@
+use bigdecimal::BigDecimal;
+use database::provider::{OrderDatabaseReader, WalletDatabaseReader};
+use database::tests::test_db::{create_funded_user, create_test_market, isolated_test_repository};
+use tonic::{Code, Request};
+
+use crate::grpc::admin::admin_service_server::AdminService;
+use crate::grpc::service::REJECT_REASON_METADATA;
+use crate::grpc::spot::spot_service_server::SpotService;
+use crate::grpc::spot::{AddOrderRequest, CancelOrderRequest, StartMarketRequest};
+use crate::tests::test_service::{add_order_request, create_test_service};
+
+#[tokio::test]
+async fn test_client_order_id_is_unique_per_user_and_cancels_the_order() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let user_id = create_funded_user(&repository, &[(market.quote_asset.as_str(), "100")]);
+    let service = create_test_service(repository.clone());
+    service
+        .admin_service()
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+
+    let request = AddOrderRequest {
+        client_order_id: "bid-1".to_string(),
+        ..add_order_request(&market, &user_id, "BUY", "10", "1")
+    };
+    let order_id = service
+        .add_order(Request::new(request.clone()))
+        .await
+        .unwrap()
+        .into_inner()
+        .order_id;
+    let order = repository.get_order(&order_id).unwrap().unwrap();
+    assert_eq!(order.client_order_id.as_deref(), Some("bid-1"));
+
+    // A reused id is refused before any funds are locked for it
+    let status = service.add_order(Request::new(request)).await.unwrap_err();
+    assert_eq!(status.code(), Code::AlreadyExists);
+    assert_eq!(
+        status.metadata().get(REJECT_REASON_METADATA).unwrap(),
+        "DUPLICATE_CLIENT_ORDER_ID"
+    );
+    let quote = repository
+        .get_wallet(&user_id, &market.quote_asset)
+        .unwrap()
+        .unwrap();
+    assert_eq!(quote.locked, BigDecimal::from(10));
+
+    // A rejected order does not take its id, the user retries under it
+    let retry = |base: &str| AddOrderRequest {
+        client_order_id: "bid-2".to_string(),
+        ..add_order_request(&market, &user_id, "BUY", "10", base)
+    };
+    let status = service
+        .add_order(Request::new(retry("100")))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+    let retried = service
+        .add_order(Request::new(retry("1")))
+        .await
+        .unwrap()
+        .into_inner()
+        .order_id;
+    let order = repository
+        .get_order_by_client_id(&user_id, "bid-2")
+        .unwrap()
+        .unwrap();
+    assert_eq!(order.id, retried);
+
+    let cancel = CancelOrderRequest {
+        market_id: market.id.clone(),
+        user_id: user_id.clone(),
+        client_order_id: "bid-1".to_string(),
+        ..Default::default()
+    };
+    let response = service
+        .cancel_order(Request::new(cancel.clone()))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(response.success);
+    assert_eq!(response.order_id, order_id);
+
+    // Another user has no order under that id
+    let status = service
+        .cancel_order(Request::new(CancelOrderRequest {
+            user_id: "someone-else".to_string(),
+            ..cancel
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
+}
+
+#[tokio::test]
+async fn test_cancel_needs_exactly_one_way_to_find_the_order() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let service = create_test_service(repository.clone());
+
+    for request in [
+        CancelOrderRequest {
+            market_id: market.id.clone(),
+            ..Default::default()
+        },
+        CancelOrderRequest {
+            market_id: market.id.clone(),
+            order_id: "some-order".to_string(),
+            client_order_id: "bid-1".to_string(),
+            user_id: "some-user".to_string(),
+        },
+        CancelOrderRequest {
+            market_id: market.id.clone(),
+            client_order_id: "bid-1".to_string(),
+            ..Default::default()
+        },
+    ] {
+        let status = service
+            .cancel_order(Request::new(request))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+    }
+}
@@ -0,0 +1,76 @@
+use std::time::Duration;
+
+use common::db::pagination::Pagination;
+use database::filters::TradeFilter;
+use database::provider::TradeDatabaseReader;
+use database::tests::test_db::{create_funded_user, create_test_market, isolated_test_repository};
+use tonic::Request;
+
+use crate::grpc::admin::admin_service_server::AdminService;
+use crate::grpc::spot::spot_service_server::SpotService;
+use crate::grpc::spot::StartMarketRequest;
+use crate::tests::test_service::{add_order_request, create_test_service};
+
+const CONCURRENT_ORDERS: usize = 32;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_concurrent_add_orders_match_without_deadlock() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let service = create_test_service(repository.clone());
+    service
+        .admin_service()
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+
+    let seller_id = create_funded_user(
+        &repository,
+        &[(&market.base_asset, "100"), (&market.quote_asset, "0")],
+    );
+    for _ in 0..CONCURRENT_ORDERS {
+        service
+            .add_order(Request::new(add_order_request(
+                &market, &seller_id, "SELL", "10", "1",
+            )))
+            .await
+            .unwrap();
+    }
+
+    // Every buyer takes exactly one resting ask, whatever order the requests land in
+    let mut handles = Vec::new();
+    for _ in 0..CONCURRENT_ORDERS {
+        let buyer_id = create_funded_user(
+            &repository,
+            &[(&market.base_asset, "0"), (&market.quote_asset, "10")],
+        );
+        let service = service.clone();
+        let request = add_order_request(&market, &buyer_id, "BUY", "10", "1");
+        handles.push(tokio::spawn(async move {
+            service.add_order(Request::new(request)).await
+        }));
+    }
+
+    let fills = tokio::time::timeout(Duration::from_secs(60), async {
+        let mut fills = 0;
+        for handle in handles {
+            fills += handle.await.unwrap().unwrap().into_inner().trades.len();
+        }
+        fills
+    })
+    .await
+    .expect("concurrent add_order calls did not finish");
+
+    assert_eq!(fills, CONCURRENT_ORDERS);
+    let trades = repository
+        .list_trades(
+            TradeFilter::new().market_id(Some(market.id.clone())),
+            Some(Pagination::default()),
+        )
+        .unwrap();
+    assert_eq!(trades.total_count, CONCURRENT_ORDERS as i64);
+}
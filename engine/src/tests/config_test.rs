@@ -0,0 +1,91 @@
+use std::fs;
+use std::path::PathBuf;
+
+use common::auth::{ApiKeys, Scope};
+use common::config::read_config_file;
+use uuid::Uuid;
+
+use crate::config::app_config::EngineConfig;
+use crate::validation::AssetRegistry;
+
+/// Writes `contents` to a TOML file of its own in the temp directory
+fn config_file(contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("bitrade-config-{}.toml", Uuid::new_v4()));
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn test_config_file_settings_are_named_as_environment_variables() {
+    let path = config_file(
+        r#"
+database_url = "postgres://localhost/bitrade"
+server_port = 50030
+idempotent_cancel = true
+
+[rate_limit]
+orders_per_sec = 50
+
+[order_expiry]
+interval_ms = 250
+"#,
+    );
+
+    let settings = read_config_file(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(settings["DATABASE_URL"], "postgres://localhost/bitrade");
+    assert_eq!(settings["SERVER_PORT"], "50030");
+    assert_eq!(settings["IDEMPOTENT_CANCEL"], "true");
+    assert_eq!(settings["RATE_LIMIT_ORDERS_PER_SEC"], "50");
+    assert_eq!(settings["ORDER_EXPIRY_INTERVAL_MS"], "250");
+    assert_eq!(settings.len(), 5);
+}
+
+#[test]
+fn test_config_file_arrays_are_joined_as_their_setting_is_parsed() {
+    let path = config_file(
+        r#"
+api_keys = ["admin:admin", "trader:trade,read:42"]
+supported_assets = ["BTC", "USDT"]
+"#,
+    );
+
+    let settings = read_config_file(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let api_keys = ApiKeys::parse(&settings["API_KEYS"]).unwrap();
+    assert!(api_keys.authorize(Some("admin"), Scope::Admin).is_ok());
+    assert!(api_keys.authorize(Some("trader"), Scope::Trade).is_ok());
+    assert!(api_keys.authorize(Some("trader"), Scope::Read).is_ok());
+    assert!(api_keys.authorize(Some("trader"), Scope::Admin).is_err());
+
+    let assets = AssetRegistry::new(settings["SUPPORTED_ASSETS"].split(','));
+    assert!(assets.contains("BTC"));
+    assert!(assets.contains("USDT"));
+    assert!(!assets.contains("ETH"));
+}
+
+#[test]
+fn test_unreadable_config_file_is_an_error() {
+    let missing = std::env::temp_dir().join(format!("bitrade-missing-{}.toml", Uuid::new_v4()));
+    let error = read_config_file(&missing).unwrap_err();
+    assert!(error.to_string().contains("Failed to read config file"));
+
+    let path = config_file("server_port = ");
+    assert!(read_config_file(&path).is_err());
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_invalid_fee_default_fails_to_load() {
+    // No other test reads the fee defaults or AUTH_DISABLED, so setting them here cannot leak
+    // into them
+    std::env::set_var("AUTH_DISABLED", "true");
+    std::env::set_var("DEFAULT_MAKER_FEE", "1.5");
+    let error = EngineConfig::load().unwrap_err();
+    std::env::remove_var("DEFAULT_MAKER_FEE");
+    std::env::remove_var("AUTH_DISABLED");
+
+    assert!(error.to_string().contains("DEFAULT_MAKER_FEE"));
+}
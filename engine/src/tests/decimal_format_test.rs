@@ -0,0 +1,38 @@
+use common::utils::{format_amount, format_decimal};
+
+use crate::tests::test_models::decimal;
+
+#[test]
+fn test_format_decimal_trims_without_scientific_notation() {
+    // value, scale, expected
+    let cases = [
+        ("1.50000000", 8, "1.5"),
+        ("100", 8, "100"),
+        ("1E+3", 8, "1000"),
+        ("0", 8, "0"),
+        ("0.00000000", 8, "0"),
+        ("1E-7", 8, "0.0000001"),
+        ("0.000000001", 8, "0"),
+        ("0.000000005", 8, "0.00000001"),
+        ("-0.25000", 8, "-0.25"),
+        ("-0.000000001", 8, "0"),
+        ("123456789.123456789", 4, "123456789.1235"),
+        ("2.999", 2, "3"),
+    ];
+
+    for (value, scale, expected) in cases {
+        assert_eq!(
+            format_decimal(&decimal(value), scale),
+            expected,
+            "{} at scale {}",
+            value,
+            scale
+        );
+    }
+}
+
+#[test]
+fn test_format_amount_uses_the_default_scale() {
+    assert_eq!(format_amount(&decimal("0.123456789")), "0.12345679");
+    assert_eq!(format_amount(&decimal("42.10")), "42.1");
+}
@@ -0,0 +1,86 @@
+use bigdecimal::BigDecimal;
+use std::str::FromStr;
+
+use crate::order_book::depth_diff::{DepthDiffEncoder, DepthSnapshot, DepthUpdate, LevelChange};
+
+fn snapshot(bids: &[(&str, &str)], asks: &[(&str, &str)]) -> DepthSnapshot {
+    let levels = |side: &[(&str, &str)]| {
+        side.iter()
+            .map(|(price, amount)| {
+                (
+                    BigDecimal::from_str(price).unwrap(),
+                    BigDecimal::from_str(amount).unwrap(),
+                )
+            })
+            .collect()
+    };
+    DepthSnapshot {
+        bids: levels(bids),
+        asks: levels(asks),
+    }
+}
+
+#[test]
+fn test_diff_applied_to_previous_snapshot_reproduces_next() {
+    let previous = snapshot(
+        &[("99", "1"), ("98", "2"), ("97", "3")],
+        &[("101", "1"), ("102", "2")],
+    );
+    // 98 changes, 97 disappears, 96 appears and the asks are untouched
+    let next = snapshot(
+        &[("99", "1"), ("98", "0.5"), ("96", "4")],
+        &[("101", "1"), ("102", "2")],
+    );
+
+    let diff = previous.diff(&next);
+    assert!(diff.asks.is_empty());
+    assert_eq!(
+        diff.bids,
+        vec![
+            LevelChange {
+                price: BigDecimal::from(96),
+                amount: BigDecimal::from(4),
+            },
+            LevelChange {
+                price: BigDecimal::from(97),
+                amount: BigDecimal::from(0),
+            },
+            LevelChange {
+                price: BigDecimal::from(98),
+                amount: BigDecimal::from_str("0.5").unwrap(),
+            },
+        ]
+    );
+
+    let mut rebuilt = previous.clone();
+    rebuilt.apply(&diff);
+    assert_eq!(rebuilt, next);
+}
+
+#[test]
+fn test_encoder_sends_full_snapshot_every_interval() {
+    let mut encoder = DepthDiffEncoder::new(2);
+    let states = [
+        snapshot(&[("99", "1")], &[]),
+        snapshot(&[("99", "2")], &[]),
+        snapshot(&[("99", "2")], &[("101", "1")]),
+        snapshot(&[], &[("101", "1")]),
+    ];
+
+    let mut client_view = DepthSnapshot::default();
+    let mut kinds = Vec::new();
+    for state in &states {
+        match encoder.encode(state.clone()) {
+            DepthUpdate::Snapshot(full) => {
+                kinds.push("snapshot");
+                client_view = full;
+            }
+            DepthUpdate::Diff(diff) => {
+                kinds.push("diff");
+                client_view.apply(&diff);
+            }
+        }
+        assert_eq!(&client_view, state);
+    }
+    assert_eq!(kinds, ["snapshot", "diff", "diff", "snapshot"]);
+}
@@ -0,0 +1,59 @@
+use database::models::models::OrderSide;
+use database::provider::OrderDatabaseWriter;
+use database::tests::test_db::{
+    create_funded_user, create_test_market, isolated_test_repository, new_limit_order,
+};
+use tonic::Request;
+
+use crate::grpc::admin::admin_service_server::AdminService;
+use crate::grpc::spot::spot_service_server::SpotService;
+use crate::grpc::spot::{GetEngineStatsRequest, StartMarketRequest};
+use crate::tests::test_service::create_test_service;
+
+#[tokio::test]
+async fn test_engine_stats_count_markets_and_open_orders() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let first = create_test_market(&repository);
+    let second = create_test_market(&repository);
+    let user_id = create_funded_user(
+        &repository,
+        &[
+            (&first.base_asset, "100"),
+            (&first.quote_asset, "1000"),
+            (&second.base_asset, "100"),
+            (&second.quote_asset, "1000"),
+        ],
+    );
+
+    // Resting orders that do not cross, so they are all still open when the books load
+    for order in [
+        new_limit_order(&first, &user_id, OrderSide::Buy, "10", "2"),
+        new_limit_order(&first, &user_id, OrderSide::Sell, "12", "1.5"),
+        new_limit_order(&second, &user_id, OrderSide::Buy, "5", "3"),
+    ] {
+        repository.create_order(order).unwrap();
+    }
+
+    let service = create_test_service(repository);
+    service
+        .admin_service()
+        .start_market(Request::new(StartMarketRequest {
+            market_id: first.id.clone(),
+        }))
+        .await
+        .unwrap();
+
+    let stats = service
+        .get_engine_stats(Request::new(GetEngineStatsRequest {}))
+        .await
+        .unwrap()
+        .into_inner();
+
+    // Only the started market counts as active, orders are counted across all markets
+    assert_eq!(stats.active_markets, 1);
+    assert_eq!(stats.open_orders, 3);
+    assert_eq!(stats.bid_volume.parse::<f64>().unwrap(), 5.0);
+    assert_eq!(stats.ask_volume.parse::<f64>().unwrap(), 1.5);
+}
@@ -0,0 +1,42 @@
+use anyhow::anyhow;
+use bigdecimal::BigDecimal;
+use common::error::{internal_status, BitradeError, ErrorDetail, ERROR_CODE_METADATA};
+use prost::Message;
+use tonic::{Code, Status};
+
+fn error_code(status: &Status) -> String {
+    ErrorDetail::decode(status.details()).unwrap().code
+}
+
+#[test]
+fn test_typed_errors_keep_their_code_through_anyhow() {
+    let status = internal_status(
+        anyhow::Error::from(BitradeError::InsufficientBalance {
+            asset: "USDT".to_string(),
+            required: BigDecimal::from(40),
+            available: BigDecimal::from(6),
+        })
+        .context("Failed to amend order"),
+    );
+    assert_eq!(status.code(), Code::FailedPrecondition);
+    assert_eq!(error_code(&status), "INSUFFICIENT_FUNDS");
+    assert_eq!(
+        status.metadata().get(ERROR_CODE_METADATA).unwrap(),
+        "INSUFFICIENT_FUNDS"
+    );
+    assert!(status.message().contains("40"));
+
+    let status = internal_status(BitradeError::MarketNotFound("BTC-USDT".to_string()).into());
+    assert_eq!(status.code(), Code::NotFound);
+    assert_eq!(error_code(&status), "MARKET_NOT_FOUND");
+}
+
+#[test]
+fn test_internal_errors_hide_their_message() {
+    let status = internal_status(anyhow!(
+        "duplicate key value violates unique constraint \"orders_pkey\""
+    ));
+    assert_eq!(status.code(), Code::Internal);
+    assert_eq!(status.message(), "Internal error");
+    assert_eq!(error_code(&status), "INTERNAL");
+}
@@ -0,0 +1,119 @@
+use database::provider::{
+    FeeTierDatabaseWriter, OrderDatabaseReader, UserFeeOverrideDatabaseWriter,
+};
+use database::tests::test_db::{create_funded_user, create_test_market, isolated_test_repository};
+use tonic::Request;
+
+use crate::grpc::admin::admin_service_server::AdminService;
+use crate::grpc::spot::spot_service_server::SpotService;
+use crate::grpc::spot::StartMarketRequest;
+use crate::tests::test_models::decimal;
+use crate::tests::test_service::{add_order_request, create_test_service};
+
+#[tokio::test]
+async fn test_orders_are_charged_their_volume_tier() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let seller_id = create_funded_user(&repository, &[(&market.base_asset, "10")]);
+    let buyer_id = create_funded_user(&repository, &[(&market.quote_asset, "1000")]);
+    let outsider_id = create_funded_user(&repository, &[(&market.quote_asset, "1000")]);
+    repository
+        .set_fee_tier(&market.id, decimal("0"), decimal("0.003"), decimal("0.004"))
+        .unwrap();
+    repository
+        .set_fee_tier(&market.id, decimal("20"), decimal("0"), decimal("0.001"))
+        .unwrap();
+
+    let service = create_test_service(repository.clone());
+    service
+        .admin_service()
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+    let fees = |order_id: &str| {
+        let order = repository.get_order(order_id).unwrap().unwrap();
+        (order.maker_fee, order.taker_fee)
+    };
+
+    // Without volume both start in the lowest tier, whatever fees they asked for
+    let ask = service
+        .add_order(Request::new(add_order_request(
+            &market, &seller_id, "SELL", "10", "2",
+        )))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(fees(&ask.order_id), (decimal("0.003"), decimal("0.004")));
+    service
+        .add_order(Request::new(add_order_request(
+            &market, &buyer_id, "BUY", "10", "2",
+        )))
+        .await
+        .unwrap();
+
+    // The trade's 20 of quote volume moves both counterparties up a tier
+    let ask = service
+        .add_order(Request::new(add_order_request(
+            &market, &seller_id, "SELL", "12", "1",
+        )))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(fees(&ask.order_id), (decimal("0"), decimal("0.001")));
+    let bid = service
+        .add_order(Request::new(add_order_request(
+            &market,
+            &outsider_id,
+            "BUY",
+            "9",
+            "1",
+        )))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(fees(&bid.order_id), (decimal("0.003"), decimal("0.004")));
+}
+
+#[tokio::test]
+async fn test_user_fee_override_replaces_tiers() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let maker_id = create_funded_user(&repository, &[(&market.quote_asset, "1000")]);
+    repository
+        .set_fee_tier(&market.id, decimal("0"), decimal("0.003"), decimal("0.004"))
+        .unwrap();
+    repository
+        .set_user_fee_override(&maker_id, decimal("0"), decimal("0"))
+        .unwrap();
+
+    let service = create_test_service(repository.clone());
+    service
+        .admin_service()
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+    let place_bid = || async {
+        let bid = service
+            .add_order(Request::new(add_order_request(
+                &market, &maker_id, "BUY", "9", "1",
+            )))
+            .await
+            .unwrap()
+            .into_inner();
+        let order = repository.get_order(&bid.order_id).unwrap().unwrap();
+        (order.maker_fee, order.taker_fee)
+    };
+
+    assert_eq!(place_bid().await, (decimal("0"), decimal("0")));
+
+    assert!(repository.delete_user_fee_override(&maker_id).unwrap());
+    assert_eq!(place_bid().await, (decimal("0.003"), decimal("0.004")));
+}
@@ -0,0 +1,174 @@
+use bigdecimal::BigDecimal;
+use database::provider::{OrderDatabaseReader, WalletDatabaseReader, WalletDatabaseWriter};
+use database::tests::test_db::{create_funded_user, create_test_market, isolated_test_repository};
+use tonic::{Code, Request};
+
+use crate::grpc::admin::admin_service_server::AdminService;
+use crate::grpc::spot::spot_service_server::SpotService;
+use crate::grpc::spot::StartMarketRequest;
+use crate::market::idempotency::IdempotencyCache;
+use crate::models::order_receipt::OrderReceipt;
+use crate::tests::test_service::{add_order_request, create_test_service};
+
+fn receipt(order_id: &str) -> OrderReceipt {
+    OrderReceipt {
+        order_id: order_id.to_string(),
+        market_id: "market".to_string(),
+        trades: vec![],
+        resting: None,
+    }
+}
+
+#[test]
+fn test_idempotency_cache_forgets_keys_after_the_window() {
+    let mut cache = IdempotencyCache::new(1_000);
+    cache.insert("alice", "retry-1", receipt("first"), 0);
+    cache.insert("alice", "retry-2", receipt("second"), 600);
+
+    assert_eq!(
+        cache.get("alice", "retry-1", 999).unwrap().order_id,
+        "first"
+    );
+    // Keys belong to their user
+    assert!(cache.get("bob", "retry-1", 999).is_none());
+
+    assert!(cache.get("alice", "retry-1", 1_000).is_none());
+    assert_eq!(
+        cache.get("alice", "retry-2", 1_000).unwrap().order_id,
+        "second"
+    );
+    assert_eq!(cache.len(), 1);
+}
+
+#[tokio::test]
+async fn test_retried_order_returns_the_original() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let user_id = create_funded_user(&repository, &[(&market.quote_asset, "1000")]);
+
+    let service = create_test_service(repository.clone());
+    service
+        .admin_service()
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+    let mut request = add_order_request(&market, &user_id, "BUY", "10", "1");
+    request.idempotency_key = "7f3c-retry".to_string();
+
+    let original = service
+        .add_order(Request::new(request.clone()))
+        .await
+        .unwrap()
+        .into_inner();
+    let retry = service
+        .add_order(Request::new(request.clone()))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(retry, original);
+    let wallet = repository
+        .get_wallet(&user_id, &market.quote_asset)
+        .unwrap()
+        .unwrap();
+    assert_eq!(wallet.locked, BigDecimal::from(10));
+
+    // Another key places another order
+    request.idempotency_key = "7f3c-other".to_string();
+    let other = service
+        .add_order(Request::new(request.clone()))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_ne!(other.order_id, original.order_id);
+    assert!(repository.get_order(&other.order_id).unwrap().is_some());
+
+    request.idempotency_key = "k".repeat(65);
+    let status = service.add_order(Request::new(request)).await.unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn test_retry_is_answered_before_the_order_checks() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    // Exactly enough for one order, which leaves nothing for a second
+    let user_id = create_funded_user(&repository, &[(&market.quote_asset, "10")]);
+
+    let service = create_test_service(repository.clone());
+    service
+        .admin_service()
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+    let mut request = add_order_request(&market, &user_id, "BUY", "10", "1");
+    request.idempotency_key = "locked-funds".to_string();
+
+    let original = service
+        .add_order(Request::new(request.clone()))
+        .await
+        .unwrap()
+        .into_inner();
+    let retry = service
+        .add_order(Request::new(request.clone()))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(retry, original);
+
+    // Nor does a market stopped since turn the retry away
+    service
+        .market_manager
+        .read()
+        .await
+        .stop_market(&market.id)
+        .unwrap();
+    let retry = service
+        .add_order(Request::new(request))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(retry, original);
+}
+
+#[tokio::test]
+async fn test_refused_order_is_not_remembered() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let user_id = create_funded_user(&repository, &[(&market.quote_asset, "5")]);
+
+    let service = create_test_service(repository.clone());
+    service
+        .admin_service()
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+    let mut request = add_order_request(&market, &user_id, "BUY", "10", "1");
+    request.idempotency_key = "after-deposit".to_string();
+    assert!(service
+        .add_order(Request::new(request.clone()))
+        .await
+        .is_err());
+
+    // Once the user can pay for it, the retry is placed
+    repository
+        .deposit_balance(&user_id, &market.quote_asset, 100.into())
+        .unwrap();
+    let placed = service
+        .add_order(Request::new(request))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(repository.get_order(&placed.order_id).unwrap().is_some());
+}
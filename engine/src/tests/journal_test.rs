@@ -0,0 +1,143 @@
+use common::utils::get_utc_now_millis;
+use database::models::models::{CancelReason, Market, NewEngineEvent, OrderSide as DbOrderSide};
+use database::provider::{
+    EngineEventDatabaseReader, EngineEventDatabaseWriter, OrderDatabaseReader, OrderDatabaseWriter,
+};
+use database::repository::Repository;
+use database::tests::test_db::{
+    create_funded_user, create_test_market, isolated_test_repository, new_limit_order,
+};
+
+use crate::models::trade_order::{OrderSide, OrderType, TradeOrder};
+use crate::order_book::journal::JournalEntry;
+use crate::tests::test_models::{create_order, create_test_order_book};
+
+/// Journals `entry` without applying it, as if the engine stopped right after writing it.
+fn append_unapplied(repository: &Repository, market: &Market, entry: &JournalEntry) -> i64 {
+    repository
+        .append_engine_event(NewEngineEvent {
+            market_id: market.id.clone(),
+            event_type: entry.event_type().as_str().to_string(),
+            order_id: entry.order_id().map(str::to_string),
+            payload: serde_json::to_string(entry).unwrap(),
+            create_time: get_utc_now_millis(),
+        })
+        .unwrap()
+        .sequence
+}
+
+#[test]
+fn test_book_changes_are_journaled_before_they_apply() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let funds = [
+        (market.base_asset.as_str(), "10"),
+        (market.quote_asset.as_str(), "1000"),
+    ];
+    let seller_id = create_funded_user(&repository, &funds);
+    let buyer_id = create_funded_user(&repository, &funds);
+    let mut order_book = create_test_order_book(&repository, &market);
+
+    let ask = TradeOrder {
+        user_id: seller_id,
+        ..create_order(
+            OrderSide::Sell,
+            "10",
+            "2",
+            "20",
+            OrderType::Limit,
+            &market.id,
+        )
+    };
+    let bid = TradeOrder {
+        user_id: buyer_id,
+        ..create_order(
+            OrderSide::Buy,
+            "10",
+            "1",
+            "10",
+            OrderType::Limit,
+            &market.id,
+        )
+    };
+    order_book.add_order(ask.clone()).unwrap();
+    order_book.add_order(bid.clone()).unwrap();
+    order_book
+        .cancel_order(ask.id.clone(), CancelReason::UserCanceled)
+        .unwrap();
+
+    let events = repository.get_engine_events(&market.id, 0).unwrap();
+    let journaled: Vec<(&str, Option<&str>)> = events
+        .iter()
+        .map(|event| (event.event_type.as_str(), event.order_id.as_deref()))
+        .collect();
+    assert_eq!(
+        journaled,
+        vec![
+            ("ORDER_ACCEPTED", Some(ask.id.as_str())),
+            ("ORDER_ACCEPTED", Some(bid.id.as_str())),
+            ("TRADE_EXECUTED", Some(bid.id.as_str())),
+            ("ORDER_CANCELED", Some(ask.id.as_str())),
+        ]
+    );
+    assert_eq!(
+        repository.get_applied_sequence(&market.id).unwrap(),
+        events.last().unwrap().sequence
+    );
+}
+
+#[test]
+fn test_unapplied_journal_entries_are_replayed_on_load() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let user_id = create_funded_user(&repository, &[(&market.quote_asset, "1000")]);
+
+    // Placed before the crash, its cancel was journaled but never applied
+    let canceled = repository
+        .create_order(new_limit_order(
+            &market,
+            &user_id,
+            DbOrderSide::Buy,
+            "9",
+            "1",
+        ))
+        .unwrap();
+    append_unapplied(
+        &repository,
+        &market,
+        &JournalEntry::OrderCanceled {
+            order_id: canceled.id.clone(),
+            reason: CancelReason::UserCanceled,
+        },
+    );
+    // Accepted, but the engine stopped before storing it
+    let accepted = new_limit_order(&market, &user_id, DbOrderSide::Buy, "10", "1");
+    let last = append_unapplied(
+        &repository,
+        &market,
+        &JournalEntry::OrderAccepted(Box::new(accepted.clone())),
+    );
+
+    let order_book = create_test_order_book(&repository, &market);
+    assert_eq!(order_book.bids_len(), 1);
+    assert!(order_book.get_order_by_id(accepted.id.clone()).is_ok());
+    assert_eq!(
+        repository.get_order(&canceled.id).unwrap().unwrap().status,
+        "CANCELED"
+    );
+    assert_eq!(repository.get_applied_sequence(&market.id).unwrap(), last);
+
+    // Replaying again changes nothing
+    let order_book = create_test_order_book(&repository, &market);
+    assert_eq!(order_book.bids_len(), 1);
+    assert_eq!(
+        repository
+            .get_user_active_orders_count(&user_id, &market.id)
+            .unwrap(),
+        1
+    );
+}
@@ -0,0 +1,57 @@
+use common::maintenance::MaintenanceMode;
+use database::tests::test_db::isolated_test_repository;
+use tonic::{Code, Request};
+
+use crate::grpc::admin::admin_service_server::AdminService;
+use crate::grpc::service::SpotServiceImpl;
+use crate::grpc::spot::spot_service_server::SpotService;
+use crate::grpc::spot::{GetBalanceRequest, HealthCheckRequest, SetMaintenanceModeRequest};
+use crate::tests::test_service::create_test_service;
+
+#[tokio::test]
+async fn test_maintenance_mode_rejects_requests_but_keeps_health() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let service = SpotServiceImpl {
+        maintenance: MaintenanceMode::new(15),
+        ..create_test_service(repository)
+    };
+
+    service
+        .admin_service()
+        .set_maintenance_mode(Request::new(SetMaintenanceModeRequest { enabled: true }))
+        .await
+        .unwrap();
+
+    let status = service
+        .get_balance(Request::new(GetBalanceRequest {
+            user_id: "maintenance-user".to_string(),
+            asset: "BTC".to_string(),
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::Unavailable);
+    assert_eq!(status.metadata().get("retry-after").unwrap(), "15");
+
+    let health = service
+        .health_check(Request::new(HealthCheckRequest {}))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(health.serving);
+    assert!(health.maintenance);
+
+    service
+        .admin_service()
+        .set_maintenance_mode(Request::new(SetMaintenanceModeRequest { enabled: false }))
+        .await
+        .unwrap();
+    assert!(service
+        .get_balance(Request::new(GetBalanceRequest {
+            user_id: "maintenance-user".to_string(),
+            asset: "BTC".to_string(),
+        }))
+        .await
+        .is_ok());
+}
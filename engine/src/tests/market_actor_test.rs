@@ -0,0 +1,70 @@
+use std::time::Duration;
+
+use database::tests::test_db::{
+    create_funded_user, create_test_market, isolated_test_repository, lock_wallet,
+};
+use tonic::{Code, Request};
+
+use crate::grpc::admin::admin_service_server::AdminService;
+use crate::grpc::spot::spot_service_server::SpotService;
+use crate::grpc::spot::{StartMarketRequest, UpdateMarketStatusRequest};
+use crate::tests::test_service::{add_order_request, create_test_service};
+
+#[tokio::test]
+async fn test_stalled_market_holds_up_only_its_own_requests() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let stalled = create_test_market(&repository);
+    let other = create_test_market(&repository);
+    let stalled_user = create_funded_user(&repository, &[(&stalled.quote_asset, "100")]);
+    let other_user = create_funded_user(&repository, &[(&other.quote_asset, "100")]);
+
+    let service = create_test_service(repository.clone());
+    for market in [&stalled, &other] {
+        service
+            .admin_service()
+            .start_market(Request::new(StartMarketRequest {
+                market_id: market.id.clone(),
+            }))
+            .await
+            .unwrap();
+    }
+
+    // Locking the funds of the first order stalls its market's book until the lock is dropped
+    let wallet_lock = lock_wallet(&repository, &stalled_user, &stalled.quote_asset);
+    let first = {
+        let service = service.clone();
+        let request = add_order_request(&stalled, &stalled_user, "BUY", "10", "1");
+        tokio::spawn(async move { service.add_order(Request::new(request)).await })
+    };
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    tokio::time::timeout(Duration::from_secs(10), async {
+        let request = add_order_request(&other, &other_user, "BUY", "10", "1");
+        service.add_order(Request::new(request)).await.unwrap();
+        service
+            .admin_service()
+            .update_market_status(Request::new(UpdateMarketStatusRequest {
+                market_id: stalled.id.clone(),
+                status: "CANCEL_ONLY".to_string(),
+            }))
+            .await
+            .unwrap();
+    })
+    .await
+    .expect("Another market waited on the stalled one");
+
+    // Queued behind the stalled order, the next one is checked against the new status
+    let second = {
+        let service = service.clone();
+        let request = add_order_request(&stalled, &stalled_user, "BUY", "10", "1");
+        tokio::spawn(async move { service.add_order(Request::new(request)).await })
+    };
+    drop(wallet_lock);
+
+    let first = first.await.unwrap().unwrap().into_inner();
+    assert!(first.resting.is_some());
+    let status = second.await.unwrap().unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+}
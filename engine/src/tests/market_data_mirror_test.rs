@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use common::utils::get_utc_now_millis;
+use database::cache::{CachedDepth, CachedPrice};
+use database::models::models::MarketStat;
+use database::tests::test_db::{create_funded_user, create_test_market, isolated_test_repository};
+use tonic::Request;
+
+use crate::cache::mirror::MarketDataMirror;
+use crate::cache::store::MarketDataStore;
+use crate::grpc::admin::admin_service_server::AdminService;
+use crate::grpc::spot::spot_service_server::SpotService;
+use crate::grpc::spot::StartMarketRequest;
+use crate::tests::test_models::decimal;
+use crate::tests::test_service::{add_order_request, create_test_service};
+
+/// Keeps the last value written under each market
+#[derive(Default)]
+struct RecordingStore {
+    depth: Mutex<HashMap<String, CachedDepth>>,
+    stats: Mutex<HashMap<String, MarketStat>>,
+    prices: Mutex<HashMap<String, CachedPrice>>,
+}
+
+impl MarketDataStore for Arc<RecordingStore> {
+    async fn set_depth(&self, depth: CachedDepth) -> Result<()> {
+        let market_id = depth.market_id.clone();
+        self.depth.lock().unwrap().insert(market_id, depth);
+        Ok(())
+    }
+
+    async fn set_market_stats(&self, stats: MarketStat) -> Result<()> {
+        let market_id = stats.market_id.clone();
+        self.stats.lock().unwrap().insert(market_id, stats);
+        Ok(())
+    }
+
+    async fn set_last_price(&self, price: CachedPrice) -> Result<()> {
+        let market_id = price.market_id.clone();
+        self.prices.lock().unwrap().insert(market_id, price);
+        Ok(())
+    }
+}
+
+async fn wait_for(mut condition: impl FnMut() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !condition() {
+        assert!(Instant::now() < deadline, "Mirror did not catch up");
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_mirror_follows_book_trades_and_stats() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let funds = [
+        (market.base_asset.as_str(), "10"),
+        (market.quote_asset.as_str(), "1000"),
+    ];
+    let buyer_id = create_funded_user(&repository, &funds);
+    let seller_id = create_funded_user(&repository, &funds);
+    let service = create_test_service(repository.clone());
+    service
+        .admin_service()
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+
+    let store = Arc::new(RecordingStore::default());
+    let mirror = Arc::new(MarketDataMirror::new(
+        service.market_manager.clone(),
+        Arc::new(repository.clone()),
+        store.clone(),
+    ));
+    assert_eq!(mirror.sync().await.unwrap(), 1);
+    // Already followed
+    assert_eq!(mirror.sync().await.unwrap(), 0);
+
+    for (user_id, side, base) in [(&seller_id, "SELL", "2"), (&buyer_id, "BUY", "0.5")] {
+        service
+            .add_order(Request::new(add_order_request(
+                &market, user_id, side, "10", base,
+            )))
+            .await
+            .unwrap();
+    }
+    wait_for(|| {
+        store
+            .depth
+            .lock()
+            .unwrap()
+            .get(&market.id)
+            .is_some_and(|depth| {
+                depth.bids.is_empty() && depth.asks == [(decimal("10"), decimal("1.5"))]
+            })
+    })
+    .await;
+    wait_for(|| {
+        store
+            .prices
+            .lock()
+            .unwrap()
+            .get(&market.id)
+            .is_some_and(|last| last.price == decimal("10"))
+    })
+    .await;
+
+    // Stats reach the store once refreshed
+    assert!(store.stats.lock().unwrap().is_empty());
+    service
+        .market_manager
+        .read()
+        .await
+        .refresh_market_stats(get_utc_now_millis() / 1000)
+        .unwrap();
+    mirror.sync().await.unwrap();
+    let stats = store
+        .stats
+        .lock()
+        .unwrap()
+        .get(&market.id)
+        .cloned()
+        .unwrap();
+    assert_eq!(stats.last_price, decimal("10"));
+    assert_eq!(stats.volume_24h, decimal("0.5"));
+}
@@ -1,78 +1,256 @@
-use crate::market::market_manager::MarketManager;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use bigdecimal::BigDecimal;
+use common::error::internal_status;
+use database::mock::mock_persister::MockPersister;
+use database::models::models::{CancelReason, MarketStatus, NewEngineEvent, OrderStatus};
+use database::provider::{
+    EngineEventDatabaseReader, EngineEventDatabaseWriter, MarketDatabaseReader,
+    OrderBookSnapshotDatabaseReader, OrderDatabaseReader, OrderDatabaseWriter,
+    WalletDatabaseReader, WalletDatabaseWriter,
+};
+use database::tests::test_db::create_test_market;
+use tonic::Code;
+
+use crate::market::market_manager::{MarketManager, MarketReload};
+use crate::market::{MarketConfig, MarketError};
 use crate::models::trade_order::{OrderSide, OrderType, TradeOrder};
 use crate::tests::test_models::create_order;
-use database::mock::mock_thread_safe_persistence::MockThreadSafePersistence;
-use std::sync::Arc;
+
+/// A manager over an empty mock, running a started BTC-USD market, and two users funded to
+/// trade one BTC in it
+fn create_test_manager() -> (Arc<MockPersister>, MarketManager<MockPersister>) {
+    let persister = Arc::new(MockPersister::new());
+    let market_manager = MarketManager::new(persister.clone());
+    market_manager
+        .create_market(
+            "BTC-USD".to_string(),
+            "BTC".to_string(),
+            "USD".to_string(),
+            "0.001".to_string(),
+            "0.002".to_string(),
+        )
+        .unwrap();
+    market_manager.start_market("BTC-USD").unwrap();
+    wait_until_ready(&market_manager);
+
+    for (user_id, asset, amount) in [("buyer", "USD", "60000"), ("seller", "BTC", "1")] {
+        persister
+            .deposit_balance(user_id, asset, amount.parse().unwrap())
+            .unwrap();
+    }
+    (persister, market_manager)
+}
+
+fn wait_until_ready(market_manager: &MarketManager<MockPersister>) {
+    for _ in 0..100 {
+        if !market_manager.is_recovering().unwrap() {
+            return;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    panic!("Markets did not recover");
+}
+
+fn user_order(user_id: &str, side: OrderSide, price: &str) -> TradeOrder {
+    TradeOrder {
+        user_id: user_id.to_string(),
+        ..create_order(side, price, "1", price, OrderType::Limit, "BTC-USD")
+    }
+}
+
+fn wallet(persister: &MockPersister, user_id: &str, asset: &str) -> (BigDecimal, BigDecimal) {
+    let wallet = persister.get_wallet(user_id, asset).unwrap().unwrap();
+    (wallet.available, wallet.locked)
+}
 
 #[test]
 fn test_market_manager_creation() {
-    let mock_persister = Arc::new(MockThreadSafePersistence::new());
-    let market_manager = MarketManager::new(mock_persister);
+    let market_manager = MarketManager::new(Arc::new(MockPersister::new()));
 
-    // Verify market manager is created successfully
-    assert!(market_manager.markets.lock().unwrap().is_empty());
+    assert!(market_manager.market_ids().unwrap().is_empty());
 }
 
 #[test]
 fn test_create_market() {
-    let mock_persister = Arc::new(MockThreadSafePersistence::new());
-    let market_manager = MarketManager::new(mock_persister);
+    let (persister, market_manager) = create_test_manager();
 
-    let result = market_manager.create_market(
-        "BTC-USD".to_string(),
-        "BTC".to_string(),
-        "USD".to_string(),
-        "0.001".to_string(),
-        "0.002".to_string(),
-    );
-
-    assert!(result.is_ok());
+    assert_eq!(market_manager.market_ids().unwrap(), vec!["BTC-USD"]);
 
-    // Verify market was created
-    let markets = market_manager.markets.lock().unwrap();
-    assert!(markets.contains_key("BTC-USD"));
+    // The market is stored, so a new manager over the same store loads it
+    let reloaded = MarketManager::new(persister);
+    assert_eq!(reloaded.market_ids().unwrap(), vec!["BTC-USD"]);
 }
 
 #[test]
-fn test_add_order() {
-    let mock_persister = Arc::new(MockThreadSafePersistence::new());
-    let market_manager = MarketManager::new(mock_persister);
+fn test_create_market_with_invalid_fee_is_an_invalid_argument() {
+    let market_manager = MarketManager::new(Arc::new(MockPersister::new()));
 
-    // Create a market first
-    market_manager
+    let error = market_manager
         .create_market(
-            "BTC-USD".to_string(),
-            "BTC".to_string(),
+            "ETH-USD".to_string(),
+            "ETH".to_string(),
             "USD".to_string(),
-            "0.001".to_string(),
+            "cheap".to_string(),
             "0.002".to_string(),
         )
+        .unwrap_err();
+
+    let status = internal_status(error);
+    assert_eq!(status.code(), Code::InvalidArgument);
+    assert!(status.message().contains("default_maker_fee"));
+    assert!(market_manager.market_ids().unwrap().is_empty());
+}
+
+#[test]
+fn test_add_order() {
+    let (persister, market_manager) = create_test_manager();
+
+    let receipt = market_manager
+        .add_order(user_order("buyer", OrderSide::Buy, "50000"))
         .unwrap();
 
-    // Create a buy order
-    let buy_order = create_order(
-        OrderSide::Buy,
-        "50000",
-        "1",
-        "50000",
-        OrderType::Limit,
-        "BTC-USD",
+    assert!(receipt.trades.is_empty());
+    assert_eq!(receipt.market_id, "BTC-USD");
+    assert!(receipt.resting.is_some());
+    assert_eq!(
+        wallet(&persister, "buyer", "USD"),
+        (BigDecimal::from(10000), BigDecimal::from(50000))
+    );
+}
+
+#[test]
+fn test_add_order_without_funds_is_rejected() {
+    let (persister, market_manager) = create_test_manager();
+    let order = user_order("buyer", OrderSide::Buy, "70000");
+
+    assert!(market_manager.add_order(order.clone()).is_err());
+
+    let stored = persister.get_order(&order.id).unwrap().unwrap();
+    assert_eq!(stored.status, OrderStatus::Rejected.as_str());
+    assert_eq!(
+        wallet(&persister, "buyer", "USD"),
+        (BigDecimal::from(60000), BigDecimal::from(0))
     );
+}
+
+#[test]
+fn test_crossing_orders_settle() {
+    let (persister, market_manager) = create_test_manager();
 
-    let result = market_manager.add_order(buy_order);
-    assert!(result.is_ok());
+    market_manager
+        .add_order(user_order("seller", OrderSide::Sell, "50000"))
+        .unwrap();
+    let receipt = market_manager
+        .add_order(user_order("buyer", OrderSide::Buy, "50000"))
+        .unwrap();
 
-    let (trades, market_id) = result.unwrap();
-    assert_eq!(trades.len(), 0); // No trades yet
-    assert_eq!(market_id, "BTC-USD");
+    assert_eq!(receipt.trades.len(), 1);
+    assert!(receipt.resting.is_none());
+    // The orders carry no fees
+    assert_eq!(
+        wallet(&persister, "buyer", "BTC"),
+        (BigDecimal::from(1), BigDecimal::from(0))
+    );
+    assert_eq!(
+        wallet(&persister, "seller", "USD"),
+        (BigDecimal::from(50000), BigDecimal::from(0))
+    );
+    assert_eq!(
+        wallet(&persister, "buyer", "USD"),
+        (BigDecimal::from(10000), BigDecimal::from(0))
+    );
 }
 
 #[test]
 fn test_cancel_order() {
-    let mock_persister = Arc::new(MockThreadSafePersistence::new());
-    let market_manager = MarketManager::new(mock_persister);
+    let (persister, market_manager) = create_test_manager();
+    let buy_order = user_order("buyer", OrderSide::Buy, "50000");
+    market_manager.add_order(buy_order.clone()).unwrap();
+
+    let canceled = market_manager
+        .cancel_order("BTC-USD", buy_order.id.clone())
+        .unwrap();
+
+    assert!(canceled);
+    let stored = persister.get_order(&buy_order.id).unwrap().unwrap();
+    assert_eq!(stored.status, OrderStatus::Canceled.as_str());
+    assert_eq!(
+        wallet(&persister, "buyer", "USD"),
+        (BigDecimal::from(60000), BigDecimal::from(0))
+    );
+}
+
+#[test]
+fn test_cancel_all_orders() {
+    let (persister, market_manager) = create_test_manager();
+    market_manager
+        .add_order(user_order("buyer", OrderSide::Buy, "50000"))
+        .unwrap();
+    market_manager
+        .add_order(user_order("seller", OrderSide::Sell, "51000"))
+        .unwrap();
+
+    let canceled = market_manager
+        .cancel_all_orders("BTC-USD", CancelReason::AdminCancel)
+        .unwrap();
 
-    // Create a market first
+    assert!(canceled);
+    assert!(persister.get_active_orders("BTC-USD").unwrap().is_empty());
+    assert_eq!(
+        wallet(&persister, "seller", "BTC"),
+        (BigDecimal::from(1), BigDecimal::from(0))
+    );
+}
+
+#[test]
+fn test_reload_markets_follows_the_database() {
+    let (persister, market_manager) = create_test_manager();
+    let market = create_test_market(&*persister);
+    persister.remove_market("BTC-USD").unwrap();
+
+    let reload = market_manager.reload_markets().unwrap();
+
+    assert_eq!(
+        reload,
+        MarketReload {
+            added: 1,
+            refreshed: 0,
+            removed: 1,
+        }
+    );
+    assert_eq!(
+        market_manager.market_ids().unwrap(),
+        vec![market.id.clone()]
+    );
+    assert!(market_manager
+        .add_order(user_order("buyer", OrderSide::Buy, "50000"))
+        .is_err());
+
+    // The new market loads stopped, like every market the engine starts with
+    market_manager.start_market(&market.id).unwrap();
+    let reload = market_manager.reload_markets().unwrap();
+    assert_eq!((reload.added, reload.refreshed, reload.removed), (0, 1, 0));
+}
+
+#[test]
+fn test_market_whose_book_fails_to_load_is_loaded_again_by_reload() {
+    let persister = Arc::new(MockPersister::new());
+    persister
+        .append_engine_event(NewEngineEvent {
+            market_id: "BTC-USD".to_string(),
+            event_type: "ORDER_ACCEPTED".to_string(),
+            order_id: None,
+            payload: "not a journal entry".to_string(),
+            create_time: 0,
+        })
+        .unwrap();
+    persister
+        .deposit_balance("buyer", "USD", BigDecimal::from(50000))
+        .unwrap();
+    let market_manager = MarketManager::new(persister.clone());
     market_manager
         .create_market(
             "BTC-USD".to_string(),
@@ -82,31 +260,139 @@ fn test_cancel_order() {
             "0.002".to_string(),
         )
         .unwrap();
+    market_manager.start_market("BTC-USD").unwrap();
+
+    // The failed market holds up no other market, and refuses what is sent to it
+    wait_until_ready(&market_manager);
+    let error = market_manager
+        .add_order(user_order("buyer", OrderSide::Buy, "50000"))
+        .unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<MarketError>(),
+        Some(MarketError::LoadFailed(_))
+    ));
+
+    // Once the journal is past the bad entry, a reload loads the book and starts it again
+    persister.set_applied_sequence("BTC-USD", 1).unwrap();
+    assert_eq!(market_manager.reload_markets().unwrap().added, 1);
+    wait_until_ready(&market_manager);
+    market_manager
+        .add_order(user_order("buyer", OrderSide::Buy, "50000"))
+        .unwrap();
+}
+
+#[test]
+fn test_last_session_closed_cancels_orders() {
+    let (persister, market_manager) = create_test_manager();
+    let buy_order = user_order("buyer", OrderSide::Buy, "50000");
+    market_manager.add_order(buy_order.clone()).unwrap();
+
+    // Without cancel-on-disconnect a session closes like any other stream
+    drop(market_manager.open_session("buyer"));
+    assert!(market_manager
+        .set_cancel_on_disconnect("buyer", true)
+        .is_err());
 
-    // Create and add a buy order
-    let buy_order = create_order(
-        OrderSide::Buy,
-        "50000",
-        "1",
-        "50000",
-        OrderType::Limit,
-        "BTC-USD",
+    let first = market_manager.open_session("buyer");
+    let second = market_manager.open_session("buyer");
+    assert_eq!(
+        market_manager
+            .set_cancel_on_disconnect("buyer", true)
+            .unwrap(),
+        2
     );
+    drop(first);
+    assert_eq!(market_manager.user_sessions("buyer"), (1, true));
+    let stored = persister.get_order(&buy_order.id).unwrap().unwrap();
+    assert_eq!(stored.status, OrderStatus::Open.as_str());
 
-    let (_, _) = market_manager.add_order(buy_order.clone()).unwrap();
+    drop(second);
+    assert_eq!(market_manager.user_sessions("buyer"), (0, false));
+    let stored = persister.get_order(&buy_order.id).unwrap().unwrap();
+    assert_eq!(stored.status, OrderStatus::Canceled.as_str());
+    assert_eq!(stored.cancel_reason.as_deref(), Some("DISCONNECTED"));
+    assert_eq!(
+        wallet(&persister, "buyer", "USD"),
+        (BigDecimal::from(60000), BigDecimal::from(0))
+    );
+}
+
+#[test]
+fn test_disconnect_cancels_orders_in_halted_markets() {
+    let (persister, market_manager) = create_test_manager();
+    let buy_order = user_order("buyer", OrderSide::Buy, "50000");
+    market_manager.add_order(buy_order.clone()).unwrap();
+    let session = market_manager.open_session("buyer");
+    market_manager
+        .set_cancel_on_disconnect("buyer", true)
+        .unwrap();
+    market_manager
+        .update_market_status("BTC-USD", MarketStatus::HaltedMatching)
+        .unwrap();
+
+    // The user can't cancel while the market is halted, losing their session still does
+    assert!(market_manager
+        .cancel_order("BTC-USD", buy_order.id.clone())
+        .is_err());
+    drop(session);
 
-    // Cancel the order
-    let result = market_manager.cancel_order("BTC-USD", buy_order.id);
-    assert!(result.is_ok());
-    assert!(result.unwrap());
+    let stored = persister.get_order(&buy_order.id).unwrap().unwrap();
+    assert_eq!(stored.status, OrderStatus::Canceled.as_str());
+    assert_eq!(stored.cancel_reason.as_deref(), Some("DISCONNECTED"));
+    assert_eq!(
+        wallet(&persister, "buyer", "USD"),
+        (BigDecimal::from(60000), BigDecimal::from(0))
+    );
 }
 
 #[test]
-fn test_cancel_all_orders() {
-    let mock_persister = Arc::new(MockThreadSafePersistence::new());
-    let market_manager = MarketManager::new(mock_persister);
+fn test_dropping_the_manager_keeps_orders_open() {
+    let (persister, market_manager) = create_test_manager();
+    let buy_order = user_order("buyer", OrderSide::Buy, "50000");
+    market_manager.add_order(buy_order.clone()).unwrap();
+
+    // Markets still running when the manager goes away are not closed
+    drop(market_manager);
+    let stored = persister.get_order(&buy_order.id).unwrap().unwrap();
+    assert_eq!(stored.status, OrderStatus::Open.as_str());
+    assert_eq!(
+        wallet(&persister, "buyer", "USD"),
+        (BigDecimal::from(10000), BigDecimal::from(50000))
+    );
+}
 
-    // Create a market first
+#[test]
+fn test_stop_all_markets_keeps_orders_open() {
+    let (persister, market_manager) = create_test_manager();
+    let buy_order = user_order("buyer", OrderSide::Buy, "50000");
+    market_manager.add_order(buy_order.clone()).unwrap();
+
+    assert_eq!(market_manager.stop_all_markets().unwrap(), 1);
+
+    let snapshot = persister.get_order_book_snapshot("BTC-USD").unwrap();
+    assert!(snapshot.is_some());
+    assert!(market_manager
+        .add_order(user_order("seller", OrderSide::Sell, "50000"))
+        .is_err());
+
+    // Dropping the stopped manager cancels nothing either
+    drop(market_manager);
+    let stored = persister.get_order(&buy_order.id).unwrap().unwrap();
+    assert_eq!(stored.status, OrderStatus::Open.as_str());
+    assert_eq!(
+        wallet(&persister, "buyer", "USD"),
+        (BigDecimal::from(10000), BigDecimal::from(50000))
+    );
+}
+
+#[test]
+fn test_market_whose_trades_cannot_be_stored_is_halted() {
+    let persister = Arc::new(MockPersister::new());
+    let config = MarketConfig {
+        async_settlement: true,
+        ..MarketConfig::default()
+    };
+    let market_manager = MarketManager::with_config(persister.clone(), config);
     market_manager
         .create_market(
             "BTC-USD".to_string(),
@@ -116,31 +402,55 @@ fn test_cancel_all_orders() {
             "0.002".to_string(),
         )
         .unwrap();
+    market_manager.start_market("BTC-USD").unwrap();
+    wait_until_ready(&market_manager);
+    for (user_id, asset, amount) in [("buyer", "USD", "60000"), ("seller", "BTC", "1")] {
+        persister
+            .deposit_balance(user_id, asset, amount.parse().unwrap())
+            .unwrap();
+    }
 
-    // Create and add multiple orders
-    let buy_order = create_order(
-        OrderSide::Buy,
-        "50000",
-        "1",
-        "50000",
-        OrderType::Limit,
-        "BTC-USD",
-    );
+    let ask = user_order("seller", OrderSide::Sell, "50000");
+    market_manager.add_order(ask.clone()).unwrap();
+    // Canceled behind the book, the trade against the ask cannot be stored
+    persister
+        .cancel_order(&ask.id, CancelReason::AdminCancel)
+        .unwrap();
+    market_manager
+        .add_order(user_order("buyer", OrderSide::Buy, "50000"))
+        .unwrap();
 
-    let sell_order = create_order(
-        OrderSide::Sell,
-        "51000",
-        "1",
-        "51000",
-        OrderType::Limit,
-        "BTC-USD",
+    // The book halts the market after the task that finds the writer stopped
+    let halted = (0..100).any(|_| {
+        let error = market_manager.get_recent_trades("BTC-USD", 10).err();
+        let halted = matches!(
+            error.as_ref().and_then(|e| e.downcast_ref::<MarketError>()),
+            Some(MarketError::SettlementFailed(_))
+        );
+        thread::sleep(Duration::from_millis(10));
+        halted
+    });
+    assert!(halted, "Market was not halted");
+    assert_eq!(
+        persister.get_market("BTC-USD").unwrap().unwrap().status,
+        MarketStatus::HaltedMatching.as_str()
+    );
+    assert_eq!(
+        persister
+            .get_settlement_dead_letters("BTC-USD")
+            .unwrap()
+            .len(),
+        1
     );
 
-    market_manager.add_order(buy_order).unwrap();
-    market_manager.add_order(sell_order).unwrap();
-
-    // Cancel all orders
-    let result = market_manager.cancel_all_orders("BTC-USD");
-    assert!(result.is_ok());
-    assert!(result.unwrap());
+    // Reloaded, the market comes back halted until an operator reopens it
+    assert_eq!(market_manager.reload_markets().unwrap().added, 1);
+    wait_until_ready(&market_manager);
+    let error = market_manager
+        .add_order(user_order("buyer", OrderSide::Buy, "5000"))
+        .unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<MarketError>(),
+        Some(MarketError::StatusRestricted { .. })
+    ));
 }
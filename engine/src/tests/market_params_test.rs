@@ -0,0 +1,205 @@
+use bigdecimal::BigDecimal;
+use common::db::pagination::Pagination;
+use common::error::ERROR_CODE_METADATA;
+use database::filters::OrderFilter;
+use database::models::models::{MarketUpdate, OrderStatus, RejectReason};
+use database::provider::{MarketDatabaseReader, MarketDatabaseWriter, OrderDatabaseReader};
+use database::tests::test_db::{create_funded_user, create_test_market, isolated_test_repository};
+use prost::Message;
+use tonic::{Code, Request, Status};
+
+use crate::grpc::admin::admin_service_server::AdminService;
+use crate::grpc::spot::spot_service_server::SpotService;
+use crate::grpc::spot::{
+    AddOcoOrderRequest, AddOrderRequest, OrderConstraintViolation, StartMarketRequest,
+    UpdateMarketRequest,
+};
+use crate::tests::test_service::{add_order_request, create_test_service};
+
+#[tokio::test]
+async fn test_update_market_refreshes_running_market() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let user_id = create_funded_user(&repository, &[(&market.quote_asset, "1000")]);
+    let service = create_test_service(repository.clone());
+    service
+        .admin_service()
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+    let test_order = |price: &str, base: &str| {
+        Request::new(AddOrderRequest {
+            test_order: true,
+            ..add_order_request(&market, &user_id, "BUY", price, base)
+        })
+    };
+    service.add_order(test_order("10.5", "1")).await.unwrap();
+
+    let response = service
+        .admin_service()
+        .update_market(Request::new(UpdateMarketRequest {
+            market_id: market.id.clone(),
+            min_base_amount: Some("2".to_string()),
+            price_precision: Some(0),
+            ..Default::default()
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(response.min_base_amount, "2");
+    assert_eq!(response.price_precision, 0);
+    assert_eq!(response.amount_precision, market.amount_precision);
+    let stored = repository.get_market(&market.id).unwrap().unwrap();
+    assert_eq!(stored.price_precision, 0);
+
+    // The running market checks orders against the new parameters without a restart
+    let status = service
+        .add_order(test_order("10.5", "2"))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+    let status = service.add_order(test_order("10", "1")).await.unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+    service.add_order(test_order("10", "2")).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_update_market_rejects_invalid_parameters() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let service = create_test_service(repository.clone());
+
+    let invalid = [
+        UpdateMarketRequest {
+            market_id: market.id.clone(),
+            default_taker_fee: Some("1.5".to_string()),
+            ..Default::default()
+        },
+        UpdateMarketRequest {
+            market_id: market.id.clone(),
+            min_quote_amount: Some("-1".to_string()),
+            ..Default::default()
+        },
+        UpdateMarketRequest {
+            market_id: market.id.clone(),
+            amount_precision: Some(19),
+            ..Default::default()
+        },
+    ];
+    for request in invalid {
+        let status = service
+            .admin_service()
+            .update_market(Request::new(request))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+    }
+    assert_eq!(
+        repository
+            .get_market(&market.id)
+            .unwrap()
+            .unwrap()
+            .default_taker_fee,
+        market.default_taker_fee
+    );
+}
+
+fn violation(status: &Status) -> OrderConstraintViolation {
+    assert_eq!(status.code(), Code::InvalidArgument);
+    OrderConstraintViolation::decode(status.details()).unwrap()
+}
+
+#[tokio::test]
+async fn test_orders_breaking_market_constraints_are_rejected_with_details() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    repository
+        .update_market(
+            &market.id,
+            MarketUpdate {
+                min_base_amount: Some(BigDecimal::from(1)),
+                min_quote_amount: Some(BigDecimal::from(5)),
+                price_precision: Some(1),
+                amount_precision: Some(2),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    let user_id = create_funded_user(&repository, &[(&market.quote_asset, "1000")]);
+    let service = create_test_service(repository.clone());
+    service
+        .admin_service()
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+    let add = |price: &str, base: &str| {
+        Request::new(add_order_request(&market, &user_id, "BUY", price, base))
+    };
+
+    let status = service.add_order(add("10", "0.5")).await.unwrap_err();
+    let details = violation(&status);
+    assert_eq!(
+        (details.field.as_str(), details.constraint.as_str()),
+        ("base_amount", "MIN_AMOUNT")
+    );
+    assert_eq!(
+        (details.value.as_str(), details.limit.as_str()),
+        ("0.5", "1")
+    );
+    assert_eq!(
+        status.metadata().get(ERROR_CODE_METADATA).unwrap(),
+        "BELOW_MINIMUM"
+    );
+
+    let status = service.add_order(add("4", "1")).await.unwrap_err();
+    assert_eq!(violation(&status).field, "quote_amount");
+
+    let status = service.add_order(add("10.25", "1")).await.unwrap_err();
+    let details = violation(&status);
+    assert_eq!(
+        (details.field.as_str(), details.constraint.as_str()),
+        ("price", "PRECISION")
+    );
+    assert_eq!(details.limit, "1");
+    assert_eq!(
+        status.metadata().get(ERROR_CODE_METADATA).unwrap(),
+        "INVALID_PRECISION"
+    );
+
+    let status = service.add_order(add("10", "1.125")).await.unwrap_err();
+    assert_eq!(violation(&status).field, "base_amount");
+
+    // Either leg breaking a constraint refuses the whole group
+    let status = service
+        .add_oco_order(Request::new(AddOcoOrderRequest {
+            first: Some(add_order_request(&market, &user_id, "BUY", "8", "1")),
+            second: Some(add_order_request(&market, &user_id, "BUY", "7.55", "1")),
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(violation(&status).constraint, "PRECISION");
+
+    // Every refused order is stored as rejected, none of them holds funds
+    let orders = repository
+        .list_orders(
+            OrderFilter::new().user_id(Some(user_id.clone())),
+            Some(Pagination::default()),
+        )
+        .unwrap();
+    assert_eq!(orders.total_count, 6);
+    assert!(orders.items.iter().all(|order| {
+        order.get_status().unwrap() == OrderStatus::Rejected
+            && order.get_reject_reason().unwrap() == Some(RejectReason::MarketConstraint)
+    }));
+    service.add_order(add("10.5", "1.25")).await.unwrap();
+}
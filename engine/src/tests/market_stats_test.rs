@@ -0,0 +1,104 @@
+use bigdecimal::BigDecimal;
+use common::utils::get_utc_now_millis;
+use database::provider::MarketStatDatabaseReader;
+use database::tests::test_db::{create_funded_user, create_test_market, isolated_test_repository};
+use std::collections::HashMap;
+use tonic::Request;
+
+use crate::grpc::admin::admin_service_server::AdminService;
+use crate::grpc::spot::spot_service_server::SpotService;
+use crate::grpc::spot::StartMarketRequest;
+use crate::tests::test_service::{add_order_request, create_test_service};
+
+#[tokio::test]
+async fn test_market_stats_are_refreshed_from_engine_trades() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let idle_market = create_test_market(&repository);
+    let funds = [
+        (market.base_asset.as_str(), "10"),
+        (market.quote_asset.as_str(), "1000"),
+    ];
+    let buyer_id = create_funded_user(&repository, &funds);
+    let seller_id = create_funded_user(&repository, &funds);
+    let service = create_test_service(repository.clone());
+    service
+        .admin_service()
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+
+    for (price, base) in [("10", "1"), ("12", "2")] {
+        service
+            .add_order(Request::new(add_order_request(
+                &market, &seller_id, "SELL", price, base,
+            )))
+            .await
+            .unwrap();
+        service
+            .add_order(Request::new(add_order_request(
+                &market, &buyer_id, "BUY", price, base,
+            )))
+            .await
+            .unwrap();
+    }
+
+    let market_manager = service.market_manager.read().await;
+    let now = get_utc_now_millis() / 1000;
+    assert_eq!(market_manager.refresh_market_stats(now).unwrap(), 1);
+    drop(market_manager);
+
+    let stats = repository.get_market_stats(&market.id).unwrap().unwrap();
+    assert_eq!(stats.high_24h, BigDecimal::from(12));
+    assert_eq!(stats.low_24h, BigDecimal::from(10));
+    assert_eq!(stats.volume_24h, BigDecimal::from(3));
+    assert_eq!(stats.last_price, BigDecimal::from(12));
+    assert!(repository
+        .get_market_stats(&idle_market.id)
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_quotes_are_stored_when_the_top_of_book_moves() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let user_id = create_funded_user(
+        &repository,
+        &[(&market.base_asset, "10"), (&market.quote_asset, "1000")],
+    );
+    let service = create_test_service(repository.clone());
+    service
+        .admin_service()
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+    let mut stored = HashMap::new();
+
+    for (side, price) in [("BUY", "9"), ("BUY", "8"), ("SELL", "11")] {
+        service
+            .add_order(Request::new(add_order_request(
+                &market, &user_id, side, price, "1",
+            )))
+            .await
+            .unwrap();
+    }
+    let market_manager = service.market_manager.read().await;
+    assert_eq!(market_manager.store_quotes(&mut stored).unwrap(), 1);
+    // Nothing moved since
+    assert_eq!(market_manager.store_quotes(&mut stored).unwrap(), 0);
+    drop(market_manager);
+
+    let tickers = repository.list_tickers().unwrap();
+    let quote = tickers[0].quote.as_ref().unwrap();
+    assert_eq!(quote.best_bid, Some(BigDecimal::from(9)));
+    assert_eq!(quote.best_ask, Some(BigDecimal::from(11)));
+}
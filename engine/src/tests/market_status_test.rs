@@ -0,0 +1,193 @@
+use database::provider::{MarketDatabaseReader, OrderDatabaseReader};
+use database::tests::test_db::{create_funded_user, create_test_market, isolated_test_repository};
+use tonic::{Code, Request};
+
+use crate::grpc::admin::admin_service_server::AdminService;
+use crate::grpc::spot::spot_service_server::SpotService;
+use crate::grpc::spot::{CancelOrderRequest, StartMarketRequest, UpdateMarketStatusRequest};
+use crate::tests::test_service::{add_order_request, create_test_service};
+
+fn status_request(market_id: &str, status: &str) -> Request<UpdateMarketStatusRequest> {
+    Request::new(UpdateMarketStatusRequest {
+        market_id: market_id.to_string(),
+        status: status.to_string(),
+    })
+}
+
+#[tokio::test]
+async fn test_post_only_market_accepts_only_post_only_limits() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let user_id = create_funded_user(&repository, &[(&market.quote_asset, "1000")]);
+
+    let service = create_test_service(repository.clone());
+    service
+        .admin_service()
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+    let response = service
+        .admin_service()
+        .update_market_status(status_request(&market.id, "post_only"))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(response.status, "POST_ONLY");
+    assert_eq!(response.previous_status, "ACTIVE");
+    assert_eq!(
+        repository.get_market(&market.id).unwrap().unwrap().status,
+        "POST_ONLY"
+    );
+
+    let status = service
+        .add_order(Request::new(add_order_request(
+            &market, &user_id, "BUY", "10", "1",
+        )))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+
+    let mut request = add_order_request(&market, &user_id, "BUY", "10", "1");
+    request.post_only = true;
+    service.add_order(Request::new(request)).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_cancel_only_and_halted_markets_restrict_requests() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let user_id = create_funded_user(&repository, &[(&market.quote_asset, "1000")]);
+
+    let service = create_test_service(repository.clone());
+    service
+        .admin_service()
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+    let first = service
+        .add_order(Request::new(add_order_request(
+            &market, &user_id, "BUY", "10", "1",
+        )))
+        .await
+        .unwrap()
+        .into_inner();
+    let second = service
+        .add_order(Request::new(add_order_request(
+            &market, &user_id, "BUY", "9", "1",
+        )))
+        .await
+        .unwrap()
+        .into_inner();
+    let cancel = |order_id: &str| {
+        Request::new(CancelOrderRequest {
+            order_id: order_id.to_string(),
+            market_id: market.id.clone(),
+            ..Default::default()
+        })
+    };
+
+    // Cancel-only takes no new orders but lets users pull theirs
+    service
+        .admin_service()
+        .update_market_status(status_request(&market.id, "CANCEL_ONLY"))
+        .await
+        .unwrap();
+    let status = service
+        .add_order(Request::new(add_order_request(
+            &market, &user_id, "BUY", "10", "1",
+        )))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+    service.cancel_order(cancel(&first.order_id)).await.unwrap();
+
+    // A halted market freezes its book, cancels included
+    service
+        .admin_service()
+        .update_market_status(status_request(&market.id, "HALTED_MATCHING"))
+        .await
+        .unwrap();
+    let status = service
+        .cancel_order(cancel(&second.order_id))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+    assert_eq!(
+        repository
+            .get_order(&second.order_id)
+            .unwrap()
+            .unwrap()
+            .status,
+        "OPEN"
+    );
+
+    let status = service
+        .admin_service()
+        .update_market_status(status_request(&market.id, "SUSPENDED"))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn test_closing_a_market_cancels_resting_orders_until_reopened() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let user_id = create_funded_user(&repository, &[(&market.quote_asset, "1000")]);
+
+    let service = create_test_service(repository.clone());
+    service
+        .admin_service()
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+    let bid = service
+        .add_order(Request::new(add_order_request(
+            &market, &user_id, "BUY", "10", "1",
+        )))
+        .await
+        .unwrap()
+        .into_inner();
+
+    service
+        .admin_service()
+        .update_market_status(status_request(&market.id, "CLOSED"))
+        .await
+        .unwrap();
+    let order = repository.get_order(&bid.order_id).unwrap().unwrap();
+    assert_eq!(order.status, "CANCELED");
+    assert_eq!(order.cancel_reason.as_deref(), Some("MARKET_CLOSED"));
+    let status = service
+        .add_order(Request::new(add_order_request(
+            &market, &user_id, "BUY", "10", "1",
+        )))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+
+    let response = service
+        .admin_service()
+        .update_market_status(status_request(&market.id, "ACTIVE"))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(response.previous_status, "CLOSED");
+    service
+        .add_order(Request::new(add_order_request(
+            &market, &user_id, "BUY", "10", "1",
+        )))
+        .await
+        .unwrap();
+}
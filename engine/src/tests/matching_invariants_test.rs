@@ -0,0 +1,156 @@
+use std::sync::Arc;
+
+use bigdecimal::BigDecimal;
+use database::mock::mock_persister::MockPersister;
+use database::models::models::CancelReason;
+use database::tests::test_db::{create_funded_user, create_test_market};
+use proptest::prelude::*;
+
+use crate::models::trade_order::{OrderSide, OrderType, TradeOrder};
+use crate::tests::test_mock::{
+    assert_book_not_crossed, assert_funds_consistent, create_mock_order_book,
+};
+use crate::tests::test_models::create_order;
+
+const USERS: usize = 3;
+
+/// A request of a random order stream. Prices are whole numbers around 100 and amounts
+/// tenths, so the orders cross often and leave residues to refund.
+#[derive(Debug, Clone)]
+enum Step {
+    Limit {
+        user: usize,
+        side: OrderSide,
+        price: u32,
+        base_tenths: u32,
+    },
+    Market {
+        user: usize,
+        side: OrderSide,
+        base_tenths: u32,
+    },
+    Cancel {
+        order: usize,
+    },
+    Amend {
+        order: usize,
+        price: u32,
+        base_tenths: u32,
+    },
+}
+
+fn side() -> impl Strategy<Value = OrderSide> {
+    prop_oneof![Just(OrderSide::Buy), Just(OrderSide::Sell)]
+}
+
+fn step() -> impl Strategy<Value = Step> {
+    prop_oneof![
+        6 => (0..USERS, side(), 95..=105u32, 1..=50u32).prop_map(
+            |(user, side, price, base_tenths)| Step::Limit {
+                user,
+                side,
+                price,
+                base_tenths,
+            }
+        ),
+        2 => (0..USERS, side(), 1..=50u32).prop_map(|(user, side, base_tenths)| {
+            Step::Market {
+                user,
+                side,
+                base_tenths,
+            }
+        }),
+        2 => any::<usize>().prop_map(|order| Step::Cancel { order }),
+        1 => (any::<usize>(), 95..=105u32, 1..=50u32).prop_map(|(order, price, base_tenths)| {
+            Step::Amend {
+                order,
+                price,
+                base_tenths,
+            }
+        }),
+    ]
+}
+
+fn new_order(
+    market_id: &str,
+    user_id: &str,
+    order_type: OrderType,
+    side: OrderSide,
+    price: u32,
+    base_tenths: u32,
+) -> TradeOrder {
+    let base = BigDecimal::new(base_tenths.into(), 1);
+    // A market buy spends up to a budget covering the whole book
+    let quote = match order_type {
+        OrderType::Limit => BigDecimal::from(price) * &base,
+        OrderType::Market => BigDecimal::from(110) * &base,
+    };
+    TradeOrder {
+        user_id: user_id.to_string(),
+        maker_fee: BigDecimal::new(1.into(), 3),
+        taker_fee: BigDecimal::new(2.into(), 3),
+        ..create_order(
+            side,
+            &price.to_string(),
+            &base.to_string(),
+            &quote.to_string(),
+            order_type,
+            market_id,
+        )
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn test_matching_keeps_books_and_funds_consistent(
+        steps in prop::collection::vec(step(), 1..60)
+    ) {
+        let persister = Arc::new(MockPersister::new());
+        let market = create_test_market(persister.as_ref());
+        let funds = [
+            (market.base_asset.as_str(), "50"),
+            (market.quote_asset.as_str(), "5000"),
+        ];
+        let user_ids: Vec<String> = (0..USERS)
+            .map(|_| create_funded_user(persister.as_ref(), &funds))
+            .collect();
+        let mut order_book = create_mock_order_book(&persister, &market);
+
+        let mut order_ids: Vec<String> = Vec::new();
+        for step in steps {
+            // Refused steps are part of the stream, the invariants hold either way
+            match step {
+                Step::Limit { user, side, price, base_tenths } => {
+                    let order =
+                        new_order(&market.id, &user_ids[user], OrderType::Limit, side, price, base_tenths);
+                    order_ids.push(order.id.clone());
+                    let _ = order_book.add_order(order);
+                }
+                Step::Market { user, side, base_tenths } => {
+                    let order =
+                        new_order(&market.id, &user_ids[user], OrderType::Market, side, 0, base_tenths);
+                    order_ids.push(order.id.clone());
+                    let _ = order_book.add_order(order);
+                }
+                Step::Cancel { order } if !order_ids.is_empty() => {
+                    let order_id = order_ids[order % order_ids.len()].clone();
+                    let _ = order_book.cancel_order(order_id, CancelReason::UserCanceled);
+                }
+                Step::Amend { order, price, base_tenths } if !order_ids.is_empty() => {
+                    let order_id = order_ids[order % order_ids.len()].clone();
+                    let _ = order_book.amend_order(
+                        order_id,
+                        Some(BigDecimal::from(price)),
+                        Some(BigDecimal::new(base_tenths.into(), 1)),
+                    );
+                }
+                Step::Cancel { .. } | Step::Amend { .. } => {}
+            }
+
+            assert_book_not_crossed(&order_book);
+            assert_funds_consistent(&persister, &market, &user_ids);
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use std::convert::Infallible;
+
+use common::metrics::{encode_metrics, GrpcMetricsLayer, GRPC_REQUEST_DURATION};
+use database::models::models::CancelReason;
+use database::tests::test_db::{create_funded_user, create_test_market, isolated_test_repository};
+use tower::{service_fn, Layer, Service};
+
+use crate::metrics::{BOOK_ORDERS, MATCH_DURATION, ORDERS_ADDED, ORDERS_CANCELED, TRADES};
+use crate::models::trade_order::OrderSide;
+use crate::tests::test_models::{create_test_order_book, limit_order};
+
+#[test]
+fn test_order_book_counts_orders_trades_and_resting_orders() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let funds = [
+        (market.base_asset.as_str(), "10"),
+        (market.quote_asset.as_str(), "1000"),
+    ];
+    let seller_id = create_funded_user(&repository, &funds);
+    let buyer_id = create_funded_user(&repository, &funds);
+    let mut order_book = create_test_order_book(&repository, &market);
+
+    for price in ["10", "11"] {
+        let ask = limit_order(&seller_id, &market, OrderSide::Sell, price, "1");
+        order_book.add_order(ask).unwrap();
+    }
+    let bid = limit_order(&buyer_id, &market, OrderSide::Buy, "9", "1");
+    order_book.add_order(bid.clone()).unwrap();
+    let buy = limit_order(&buyer_id, &market, OrderSide::Buy, "10", "1");
+    assert_eq!(order_book.add_order(buy).unwrap().len(), 1);
+    order_book
+        .cancel_order(bid.id, CancelReason::UserCanceled)
+        .unwrap();
+    order_book.record_book_metrics();
+
+    let labels = [market.id.as_str()];
+    assert_eq!(ORDERS_ADDED.with_label_values(&labels).get(), 4);
+    assert_eq!(ORDERS_CANCELED.with_label_values(&labels).get(), 1);
+    assert_eq!(TRADES.with_label_values(&labels).get(), 1);
+    assert_eq!(
+        MATCH_DURATION.with_label_values(&labels).get_sample_count(),
+        4
+    );
+    let resting = |side| BOOK_ORDERS.with_label_values(&[&market.id, side]).get();
+    assert_eq!((resting("bid"), resting("ask")), (0, 1));
+
+    let exposed = encode_metrics();
+    let trades = format!("bitrade_trades_total{{market=\"{}\"}} 1", market.id);
+    assert!(exposed.contains(&trades), "{}", exposed);
+}
+
+#[tokio::test]
+async fn test_grpc_requests_are_timed_by_method() {
+    let mut service = GrpcMetricsLayer.layer(service_fn(|_request: http::Request<()>| async {
+        Ok::<_, Infallible>(http::Response::new(()))
+    }));
+    let method = "/test.MetricsProbe/Call";
+    for _ in 0..2 {
+        let request = http::Request::builder().uri(method).body(()).unwrap();
+        service.call(request).await.unwrap();
+    }
+    let timed = GRPC_REQUEST_DURATION.with_label_values(&[method]);
+    assert_eq!(timed.get_sample_count(), 2);
+    assert!(timed.get_sample_sum() >= 0.0);
+}
@@ -1 +1,110 @@
+pub mod test_mock;
 pub mod test_models;
+pub mod test_service;
+
+#[cfg(test)]
+mod add_order_test;
+#[cfg(test)]
+mod admin_service_test;
+#[cfg(test)]
+mod amend_order_test;
+#[cfg(test)]
+mod asset_registry_test;
+#[cfg(test)]
+mod async_settlement_test;
+#[cfg(test)]
+mod auth_test;
+#[cfg(test)]
+mod batch_orders_test;
+#[cfg(test)]
+mod book_side_test;
+#[cfg(test)]
+mod cancel_on_disconnect_test;
+#[cfg(test)]
+mod cancel_reason_test;
+#[cfg(test)]
+mod circuit_breaker_test;
+#[cfg(test)]
+mod client_order_id_test;
+#[cfg(test)]
+mod concurrent_orders_test;
+#[cfg(test)]
+mod config_test;
+#[cfg(test)]
+mod decimal_format_test;
+#[cfg(test)]
+mod depth_diff_test;
+#[cfg(test)]
+mod engine_stats_test;
+#[cfg(test)]
+mod error_test;
+#[cfg(test)]
+mod fee_test;
+#[cfg(test)]
+mod idempotency_test;
+#[cfg(test)]
+mod journal_test;
+#[cfg(test)]
+mod maintenance_test;
+#[cfg(test)]
+mod market_actor_test;
+#[cfg(test)]
+mod market_data_mirror_test;
+#[cfg(test)]
+mod market_manager_test;
+#[cfg(test)]
+mod market_params_test;
+#[cfg(test)]
+mod market_stats_test;
+#[cfg(test)]
+mod market_status_test;
+#[cfg(test)]
+mod matching_invariants_test;
+#[cfg(test)]
+mod metrics_test;
+#[cfg(test)]
+mod oco_order_test;
+#[cfg(test)]
+mod order_audit_test;
+#[cfg(test)]
+mod order_book_depth_test;
+#[cfg(test)]
+mod order_book_stream_test;
+#[cfg(test)]
+mod order_book_test;
+#[cfg(test)]
+mod order_expiry_test;
+#[cfg(test)]
+mod order_lifecycle_test;
+#[cfg(test)]
+mod outbox_relay_test;
+#[cfg(test)]
+mod rate_limit_test;
+#[cfg(test)]
+mod reconciliation_test;
+#[cfg(test)]
+mod recovery_test;
+#[cfg(test)]
+mod risk_limit_test;
+#[cfg(test)]
+mod rounding_test;
+#[cfg(test)]
+mod server_info_test;
+#[cfg(test)]
+mod settlement_test;
+#[cfg(test)]
+mod simulator_test;
+#[cfg(test)]
+mod snapshot_test;
+#[cfg(test)]
+mod tracing_test;
+#[cfg(test)]
+mod trade_stream_test;
+#[cfg(test)]
+mod transfer_test;
+#[cfg(test)]
+mod user_events_test;
+#[cfg(test)]
+mod user_id_test;
+#[cfg(test)]
+mod validation_test;
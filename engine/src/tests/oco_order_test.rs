@@ -0,0 +1,190 @@
+use bigdecimal::BigDecimal;
+use database::models::models::{CancelReason, OrderStatus};
+use database::provider::{OcoGroupDatabaseReader, OrderDatabaseReader, WalletDatabaseReader};
+use database::repository::Repository;
+use database::tests::test_db::{create_funded_user, create_test_market, isolated_test_repository};
+use tonic::{Code, Request};
+
+use crate::grpc::admin::admin_service_server::AdminService;
+use crate::grpc::spot::spot_service_server::SpotService;
+use crate::grpc::spot::{AddOcoOrderRequest, CancelOrderRequest, StartMarketRequest};
+use crate::tests::test_service::{add_order_request, create_test_service};
+
+fn assert_canceled_by_sibling(repository: &Repository, order_id: &str) {
+    let order = repository.get_order(order_id).unwrap().unwrap();
+    assert_eq!(order.status, OrderStatus::Canceled.as_str());
+    assert_eq!(
+        order.get_cancel_reason().unwrap(),
+        Some(CancelReason::OneCancelsOther)
+    );
+}
+
+#[tokio::test]
+async fn test_filling_one_leg_cancels_the_other() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    // Trades credit existing wallets only, so both users hold both assets
+    let seller_id = create_funded_user(
+        &repository,
+        &[(&market.base_asset, "10"), (&market.quote_asset, "1")],
+    );
+    let buyer_id = create_funded_user(
+        &repository,
+        &[(&market.base_asset, "1"), (&market.quote_asset, "1000")],
+    );
+    let service = create_test_service(repository.clone());
+    service
+        .admin_service()
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+
+    let oco = service
+        .add_oco_order(Request::new(AddOcoOrderRequest {
+            first: Some(add_order_request(&market, &seller_id, "SELL", "12", "5")),
+            second: Some(add_order_request(&market, &seller_id, "SELL", "20", "5")),
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    let (first, second) = (oco.first.unwrap(), oco.second.unwrap());
+    assert!(first.resting.is_some());
+    assert!(second.resting.is_some());
+    let group = repository
+        .get_oco_group_by_order(&second.order_id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(group.id, oco.oco_group_id);
+    assert_eq!(group.sibling_of(&second.order_id), first.order_id);
+
+    let fill = service
+        .add_order(Request::new(add_order_request(
+            &market, &buyer_id, "BUY", "12", "5",
+        )))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(fill.total_fills, 1);
+    assert_canceled_by_sibling(&repository, &second.order_id);
+
+    // The base the canceled leg held is back in the seller's hands
+    let base = repository
+        .get_wallet(&seller_id, &market.base_asset)
+        .unwrap()
+        .unwrap();
+    assert_eq!(base.available, BigDecimal::from(5));
+    assert_eq!(base.locked, BigDecimal::from(0));
+
+    // Nor is the canceled leg left in the book for anyone to trade against
+    let late = service
+        .add_order(Request::new(add_order_request(
+            &market, &buyer_id, "BUY", "20", "5",
+        )))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(late.trades.is_empty());
+    assert!(repository
+        .get_active_oco_groups(&market.id)
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test]
+async fn test_canceling_one_leg_cancels_the_other() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let user_id = create_funded_user(
+        &repository,
+        &[(&market.base_asset, "1"), (&market.quote_asset, "10")],
+    );
+    let service = create_test_service(repository.clone());
+    service
+        .admin_service()
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+
+    let oco = service
+        .add_oco_order(Request::new(AddOcoOrderRequest {
+            first: Some(add_order_request(&market, &user_id, "BUY", "8", "1")),
+            second: Some(add_order_request(&market, &user_id, "SELL", "12", "1")),
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    let (first, second) = (oco.first.unwrap(), oco.second.unwrap());
+    assert_eq!(
+        repository.get_active_oco_groups(&market.id).unwrap().len(),
+        1
+    );
+
+    service
+        .cancel_order(Request::new(CancelOrderRequest {
+            order_id: first.order_id.clone(),
+            market_id: market.id.clone(),
+            ..Default::default()
+        }))
+        .await
+        .unwrap();
+    assert_canceled_by_sibling(&repository, &second.order_id);
+
+    for (asset, amount) in [(&market.base_asset, 1), (&market.quote_asset, 10)] {
+        let wallet = repository.get_wallet(&user_id, asset).unwrap().unwrap();
+        assert_eq!(wallet.available, BigDecimal::from(amount));
+        assert_eq!(wallet.locked, BigDecimal::from(0));
+    }
+}
+
+#[tokio::test]
+async fn test_oco_legs_are_validated_together() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let user_id = create_funded_user(
+        &repository,
+        &[(&market.base_asset, "10"), (&market.quote_asset, "100")],
+    );
+    let other_id = create_funded_user(&repository, &[(&market.base_asset, "10")]);
+    let service = create_test_service(repository.clone());
+
+    let invalid = [
+        // Legs that cross would trade against each other
+        (
+            add_order_request(&market, &user_id, "BUY", "12", "1"),
+            add_order_request(&market, &user_id, "SELL", "10", "1"),
+        ),
+        (
+            add_order_request(&market, &user_id, "SELL", "12", "1"),
+            add_order_request(&market, &other_id, "SELL", "14", "1"),
+        ),
+    ];
+    for (first, second) in invalid {
+        let status = service
+            .add_oco_order(Request::new(AddOcoOrderRequest {
+                first: Some(first),
+                second: Some(second),
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+    }
+
+    let status = service
+        .add_oco_order(Request::new(AddOcoOrderRequest {
+            first: Some(add_order_request(&market, &user_id, "SELL", "12", "1")),
+            second: None,
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+}
@@ -0,0 +1,43 @@
+use database::models::models::AuditAction;
+use database::provider::AuditDatabaseReader;
+use database::tests::test_db::{create_funded_user, create_test_market, isolated_test_repository};
+use tonic::{Code, Request};
+
+use crate::grpc::spot::spot_service_server::SpotService;
+use crate::tests::test_service::{add_order_request, create_test_service};
+
+#[tokio::test]
+async fn test_rejected_order_is_still_audited() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let user_id = create_funded_user(&repository, &[(&market.quote_asset, "100")]);
+    let service = create_test_service(repository.clone());
+
+    let mut request = add_order_request(&market, &user_id, "BUY", "10", "1");
+    request.price = "-10".to_string();
+    let status = service.add_order(Request::new(request)).await.unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    let entries = repository.get_user_order_audit(&user_id).unwrap();
+    assert_eq!(entries.len(), 1);
+    let entry = &entries[0];
+    assert_eq!(
+        AuditAction::from_str(&entry.action).unwrap(),
+        AuditAction::CreateOrder
+    );
+    assert_eq!(entry.market_id, market.id);
+    assert!(
+        entry.request.contains("price: \"-10\""),
+        "{}",
+        entry.request
+    );
+
+    // Nothing is written once auditing is off
+    let mut service = service;
+    service.audit_orders = false;
+    let request = add_order_request(&market, &user_id, "BUY", "0", "1");
+    assert!(service.add_order(Request::new(request)).await.is_err());
+    assert_eq!(repository.get_user_order_audit(&user_id).unwrap().len(), 1);
+}
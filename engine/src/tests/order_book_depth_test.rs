@@ -0,0 +1,94 @@
+use database::tests::test_db::{create_funded_user, create_test_market, isolated_test_repository};
+use tonic::{Code, Request};
+
+use crate::grpc::admin::admin_service_server::AdminService;
+use crate::grpc::spot::spot_service_server::SpotService;
+use crate::grpc::spot::{GetOrderBookDepthRequest, StartMarketRequest};
+use crate::tests::test_service::{add_order_request, create_test_service};
+
+#[tokio::test]
+async fn test_depth_shows_aggregated_levels_and_a_growing_sequence() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let funds = [
+        (market.base_asset.as_str(), "10"),
+        (market.quote_asset.as_str(), "1000"),
+    ];
+    let user_id = create_funded_user(&repository, &funds);
+    let service = create_test_service(repository.clone());
+    service
+        .admin_service()
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+
+    for (side, price) in [("BUY", "9"), ("BUY", "8"), ("BUY", "7"), ("SELL", "11")] {
+        service
+            .add_order(Request::new(add_order_request(
+                &market, &user_id, side, price, "1",
+            )))
+            .await
+            .unwrap();
+    }
+
+    let request = GetOrderBookDepthRequest {
+        market_id: market.id.clone(),
+        levels: 2,
+        price_aggregation: String::new(),
+    };
+    let depth = service
+        .get_order_book_depth(Request::new(request.clone()))
+        .await
+        .unwrap()
+        .into_inner();
+    let prices: Vec<&str> = depth.bids.iter().map(|l| l.price.as_str()).collect();
+    assert_eq!(prices, ["9", "8"]);
+    assert_eq!(depth.asks.len(), 1);
+
+    let aggregated = service
+        .get_order_book_depth(Request::new(GetOrderBookDepthRequest {
+            price_aggregation: "5".to_string(),
+            ..request.clone()
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(aggregated.bids.len(), 1);
+    assert_eq!(aggregated.bids[0].price, "5");
+    assert_eq!(aggregated.bids[0].amount, "3");
+    assert_eq!(aggregated.asks[0].price, "15");
+
+    // Reading leaves the sequence alone, a new order moves it
+    let unchanged = service
+        .get_order_book_depth(Request::new(request.clone()))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(unchanged.sequence, depth.sequence);
+    service
+        .add_order(Request::new(add_order_request(
+            &market, &user_id, "SELL", "12", "1",
+        )))
+        .await
+        .unwrap();
+    let changed = service
+        .get_order_book_depth(Request::new(request))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(changed.sequence > depth.sequence);
+
+    let status = service
+        .get_order_book_depth(Request::new(GetOrderBookDepthRequest {
+            market_id: market.id.clone(),
+            levels: 0,
+            price_aggregation: "-1".to_string(),
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+}
@@ -0,0 +1,102 @@
+use database::tests::test_db::{create_funded_user, create_test_market, isolated_test_repository};
+use futures::StreamExt;
+use std::time::Duration;
+use tokio::time::timeout;
+use tonic::Request;
+
+use crate::grpc::admin::admin_service_server::AdminService;
+use crate::grpc::spot::spot_service_server::SpotService;
+use crate::grpc::spot::{
+    CancelOrderRequest, DepthLevel, OrderBookUpdate, StartMarketRequest, SubscribeOrderBookRequest,
+};
+use crate::tests::test_service::{add_order_request, create_test_service};
+
+fn levels(levels: &[DepthLevel]) -> Vec<(&str, &str)> {
+    levels
+        .iter()
+        .map(|l| (l.price.as_str(), l.amount.as_str()))
+        .collect()
+}
+
+#[tokio::test]
+async fn test_subscribers_get_a_snapshot_then_changed_levels() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let funds = [
+        (market.base_asset.as_str(), "10"),
+        (market.quote_asset.as_str(), "1000"),
+    ];
+    let buyer_id = create_funded_user(&repository, &funds);
+    let seller_id = create_funded_user(&repository, &funds);
+    let service = create_test_service(repository.clone());
+    service
+        .admin_service()
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+    service
+        .add_order(Request::new(add_order_request(
+            &market, &buyer_id, "BUY", "9", "2",
+        )))
+        .await
+        .unwrap();
+
+    let mut updates = service
+        .subscribe_order_book(Request::new(SubscribeOrderBookRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    let mut next = async || -> OrderBookUpdate {
+        timeout(Duration::from_secs(5), updates.next())
+            .await
+            .expect("no order book update")
+            .unwrap()
+            .unwrap()
+    };
+
+    let snapshot = next().await;
+    assert!(snapshot.snapshot);
+    assert_eq!(levels(&snapshot.bids), [("9", "2")]);
+    assert!(snapshot.asks.is_empty());
+
+    let ask = service
+        .add_order(Request::new(add_order_request(
+            &market, &seller_id, "SELL", "11", "3",
+        )))
+        .await
+        .unwrap()
+        .into_inner();
+    let added = next().await;
+    assert!(!added.snapshot);
+    assert!(added.bids.is_empty());
+    assert_eq!(levels(&added.asks), [("11", "3")]);
+    assert!(added.sequence > snapshot.sequence);
+
+    // A match changes both the level it took from and nothing else
+    service
+        .add_order(Request::new(add_order_request(
+            &market, &seller_id, "SELL", "9", "1",
+        )))
+        .await
+        .unwrap();
+    let matched = next().await;
+    assert_eq!(levels(&matched.bids), [("9", "1")]);
+    assert!(matched.asks.is_empty());
+
+    service
+        .cancel_order(Request::new(CancelOrderRequest {
+            order_id: ask.order_id,
+            market_id: market.id.clone(),
+            ..Default::default()
+        }))
+        .await
+        .unwrap();
+    let removed = next().await;
+    assert_eq!(levels(&removed.asks), [("11", "0")]);
+}
@@ -0,0 +1,876 @@
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use bigdecimal::BigDecimal;
+use common::utils::get_utc_now_millis;
+use database::mock::mock_persister::MockPersister;
+use database::models::models::{CancelReason, Market, OrderStatus, TimeInForce};
+use database::provider::{FeeTreasuryDatabaseReader, OrderDatabaseReader, WalletDatabaseReader};
+use database::repository::Repository;
+use database::tests::test_db::{create_funded_user, create_test_market, isolated_test_repository};
+
+use crate::models::trade_order::{OrderSide, OrderType, TradeOrder};
+use crate::order_book::{OrderBook, StalePricePolicy};
+use crate::tests::test_mock::create_mock_order_book;
+use crate::tests::test_models::{create_order, create_test_order_book, limit_order};
+
+fn user_order(
+    user_id: &str,
+    market: &Market,
+    side: OrderSide,
+    order_type: OrderType,
+    price: &str,
+    base_amount: &str,
+    quote_amount: &str,
+) -> TradeOrder {
+    TradeOrder {
+        user_id: user_id.to_string(),
+        ..create_order(
+            side,
+            price,
+            base_amount,
+            quote_amount,
+            order_type,
+            &market.id,
+        )
+    }
+}
+
+fn order_status(repository: &Repository, order_id: &str) -> String {
+    repository.get_order(order_id).unwrap().unwrap().status
+}
+
+#[test]
+fn test_unfillable_market_order_never_rests() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let funds = [
+        (market.base_asset.as_str(), "100"),
+        (market.quote_asset.as_str(), "1000"),
+    ];
+    let user_id = create_funded_user(&repository, &funds);
+    let mut order_book = create_test_order_book(&repository, &market);
+
+    // Only bids rest, nothing a market buy could match against
+    let bid = user_order(
+        &user_id,
+        &market,
+        OrderSide::Buy,
+        OrderType::Limit,
+        "9",
+        "1",
+        "9",
+    );
+    order_book.add_order(bid).unwrap();
+
+    let market_buy = user_order(
+        &user_id,
+        &market,
+        OrderSide::Buy,
+        OrderType::Market,
+        "10",
+        "1",
+        "10",
+    );
+    let trades = order_book.add_order(market_buy.clone()).unwrap();
+
+    assert!(trades.is_empty());
+    assert_eq!(order_book.bids_len(), 1);
+    assert_eq!(order_book.asks_len(), 0);
+    assert!(order_book.get_order_by_id(market_buy.id.clone()).is_err());
+    assert_eq!(
+        order_status(&repository, &market_buy.id),
+        OrderStatus::Canceled.as_str()
+    );
+}
+
+#[test]
+fn test_market_buy_spends_remaining_quote_on_each_level() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let funds = [
+        (market.base_asset.as_str(), "100"),
+        (market.quote_asset.as_str(), "1000"),
+    ];
+    let seller_id = create_funded_user(&repository, &funds);
+    let buyer_id = create_funded_user(&repository, &funds);
+    let mut order_book = create_test_order_book(&repository, &market);
+
+    for (price, base_amount, quote_amount) in [("10", "1", "10"), ("20", "5", "100")] {
+        let ask = user_order(
+            &seller_id,
+            &market,
+            OrderSide::Sell,
+            OrderType::Limit,
+            price,
+            base_amount,
+            quote_amount,
+        );
+        order_book.add_order(ask).unwrap();
+    }
+
+    // 40 quote buys the whole first level for 10, which leaves 30 for 1.5 at 20. Sizing the
+    // second fill from the original 40 would buy 2 and overspend the budget.
+    let market_buy = user_order(
+        &buyer_id,
+        &market,
+        OrderSide::Buy,
+        OrderType::Market,
+        "20",
+        "3",
+        "40",
+    );
+    let trades = order_book.add_order(market_buy.clone()).unwrap();
+
+    assert_eq!(trades.len(), 2);
+    assert_eq!(trades[0].price, BigDecimal::from(10));
+    assert_eq!(trades[0].base_amount, BigDecimal::from(1));
+    assert_eq!(trades[1].price, BigDecimal::from(20));
+    assert_eq!(trades[1].base_amount, BigDecimal::from_str("1.5").unwrap());
+
+    let order = repository.get_order(&market_buy.id).unwrap().unwrap();
+    assert_eq!(order.filled_quote, BigDecimal::from(40));
+    assert_eq!(order.remained_quote, BigDecimal::from(0));
+    assert_eq!(order.get_status().unwrap(), OrderStatus::Canceled);
+    assert_eq!(order_book.asks_len(), 1);
+}
+
+#[test]
+fn test_market_buy_credits_base_net_of_fee_per_fill() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let seller_id = create_funded_user(
+        &repository,
+        &[
+            (market.base_asset.as_str(), "100"),
+            (market.quote_asset.as_str(), "1"),
+        ],
+    );
+    let buyer_id = create_funded_user(&repository, &[(market.quote_asset.as_str(), "1000")]);
+    let mut order_book = create_test_order_book(&repository, &market);
+
+    for (price, quote_amount) in [("10", "10"), ("20", "20")] {
+        let ask = user_order(
+            &seller_id,
+            &market,
+            OrderSide::Sell,
+            OrderType::Limit,
+            price,
+            "1",
+            quote_amount,
+        );
+        order_book.add_order(ask).unwrap();
+    }
+
+    // 25 quote buys 1 at 10 and 0.75 at 20
+    let market_buy = TradeOrder {
+        taker_fee: market.default_taker_fee.clone(),
+        ..user_order(
+            &buyer_id,
+            &market,
+            OrderSide::Buy,
+            OrderType::Market,
+            "20",
+            "2",
+            "25",
+        )
+    };
+    let trades = order_book.add_order(market_buy).unwrap();
+    assert_eq!(trades.len(), 2);
+
+    let mut expected_base = BigDecimal::from(0);
+    for trade in &trades {
+        // The taker fee is charged on the base each fill delivered
+        assert_eq!(
+            trade.buyer_fee,
+            &trade.base_amount * &market.default_taker_fee
+        );
+        expected_base += &trade.base_amount - &trade.buyer_fee;
+    }
+    assert_eq!(expected_base, BigDecimal::from_str("1.7465").unwrap());
+
+    let base_wallet = repository
+        .get_wallet(&buyer_id, &market.base_asset)
+        .unwrap()
+        .unwrap();
+    assert_eq!(base_wallet.available, expected_base);
+}
+
+#[test]
+fn test_market_buy_refund_of_unspent_quote_is_fee_free() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let seller_id = create_funded_user(
+        &repository,
+        &[
+            (market.base_asset.as_str(), "100"),
+            (market.quote_asset.as_str(), "1"),
+        ],
+    );
+    let buyer_id = create_funded_user(&repository, &[(market.quote_asset.as_str(), "1000")]);
+    let mut order_book = create_test_order_book(&repository, &market);
+
+    let ask = user_order(
+        &seller_id,
+        &market,
+        OrderSide::Sell,
+        OrderType::Limit,
+        "10",
+        "1",
+        "10",
+    );
+    order_book.add_order(ask).unwrap();
+
+    // 30 quote only finds 1 at 10, the other 20 go back to the buyer
+    let market_buy = TradeOrder {
+        taker_fee: market.default_taker_fee.clone(),
+        ..user_order(
+            &buyer_id,
+            &market,
+            OrderSide::Buy,
+            OrderType::Market,
+            "10",
+            "3",
+            "30",
+        )
+    };
+    let trades = order_book.add_order(market_buy.clone()).unwrap();
+    assert_eq!(trades.len(), 1);
+    let fee = &trades[0].base_amount * &market.default_taker_fee;
+    assert_eq!(trades[0].buyer_fee, fee);
+
+    let order = repository.get_order(&market_buy.id).unwrap().unwrap();
+    assert_eq!(order.get_status().unwrap(), OrderStatus::Canceled);
+    assert_eq!(order.filled_fee, fee);
+
+    // The refund comes back whole, the fee only came off the base received
+    let quote_wallet = repository
+        .get_wallet(&buyer_id, &market.quote_asset)
+        .unwrap()
+        .unwrap();
+    assert_eq!(quote_wallet.available, BigDecimal::from(990));
+    assert_eq!(quote_wallet.locked, BigDecimal::from(0));
+    let base_wallet = repository
+        .get_wallet(&buyer_id, &market.base_asset)
+        .unwrap()
+        .unwrap();
+    assert_eq!(base_wallet.available, BigDecimal::from(1) - &fee);
+
+    let treasuries = repository.list_fee_treasuries(None).unwrap();
+    let collected = |asset: &str| {
+        treasuries
+            .iter()
+            .find(|t| t.market_id == market.id && t.asset == asset)
+            .unwrap()
+            .collected_amount
+            .clone()
+    };
+    assert_eq!(collected(&market.base_asset), fee);
+    assert_eq!(collected(&market.quote_asset), BigDecimal::from(0));
+}
+
+#[test]
+fn test_ioc_remainder_is_canceled_and_unlocked() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let seller_id = create_funded_user(&repository, &[(market.base_asset.as_str(), "100")]);
+    let buyer_id = create_funded_user(&repository, &[(market.quote_asset.as_str(), "1000")]);
+    let mut order_book = create_test_order_book(&repository, &market);
+
+    let ask = user_order(
+        &seller_id,
+        &market,
+        OrderSide::Sell,
+        OrderType::Limit,
+        "10",
+        "1",
+        "10",
+    );
+    order_book.add_order(ask).unwrap();
+
+    // Only 1 of the 3 is on offer, the other 2 are dropped instead of resting at 10
+    let ioc_buy = TradeOrder {
+        time_in_force: Some(TimeInForce::IOC),
+        ..user_order(
+            &buyer_id,
+            &market,
+            OrderSide::Buy,
+            OrderType::Limit,
+            "10",
+            "3",
+            "30",
+        )
+    };
+    let trades = order_book.add_order(ioc_buy.clone()).unwrap();
+    assert_eq!(trades.len(), 1);
+    assert_eq!(trades[0].base_amount, BigDecimal::from(1));
+    assert_eq!(order_book.bids_len(), 0);
+
+    let order = repository.get_order(&ioc_buy.id).unwrap().unwrap();
+    assert_eq!(order.get_status().unwrap(), OrderStatus::Canceled);
+    assert_eq!(
+        order.get_cancel_reason().unwrap(),
+        Some(CancelReason::Unfilled)
+    );
+    let quote_wallet = repository
+        .get_wallet(&buyer_id, &market.quote_asset)
+        .unwrap()
+        .unwrap();
+    assert_eq!(quote_wallet.available, BigDecimal::from(990));
+    assert_eq!(quote_wallet.locked, BigDecimal::from(0));
+
+    // With nothing to match, an IOC sell is canceled whole
+    let ioc_sell = TradeOrder {
+        time_in_force: Some(TimeInForce::IOC),
+        ..user_order(
+            &seller_id,
+            &market,
+            OrderSide::Sell,
+            OrderType::Limit,
+            "10",
+            "2",
+            "20",
+        )
+    };
+    assert!(order_book.add_order(ioc_sell.clone()).unwrap().is_empty());
+    assert_eq!(order_book.asks_len(), 0);
+    assert_eq!(order_status(&repository, &ioc_sell.id), "CANCELED");
+}
+
+#[test]
+fn test_iceberg_order_shows_one_slice_and_replenishes_after_each_fill() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let seller_id = create_funded_user(&repository, &[(market.base_asset.as_str(), "100")]);
+    let buyer_id = create_funded_user(&repository, &[(market.quote_asset.as_str(), "1000")]);
+    let mut order_book = create_test_order_book(&repository, &market);
+
+    let iceberg = TradeOrder {
+        display_amount: Some(BigDecimal::from(2)),
+        ..user_order(
+            &seller_id,
+            &market,
+            OrderSide::Sell,
+            OrderType::Limit,
+            "10",
+            "10",
+            "100",
+        )
+    };
+    order_book.add_order(iceberg.clone()).unwrap();
+    let depth = order_book.depth_snapshot();
+    assert_eq!(
+        depth.asks.get(&BigDecimal::from(10)),
+        Some(&BigDecimal::from(2))
+    );
+    let stored = repository.get_order(&iceberg.id).unwrap().unwrap();
+    assert_eq!(stored.display_amount, Some(BigDecimal::from(2)));
+
+    // The buy takes slice after slice, the last one only in part
+    let buy = user_order(
+        &buyer_id,
+        &market,
+        OrderSide::Buy,
+        OrderType::Limit,
+        "10",
+        "5",
+        "50",
+    );
+    let trades = order_book.add_order(buy).unwrap();
+    let amounts: Vec<BigDecimal> = trades.iter().map(|t| t.base_amount.clone()).collect();
+    assert_eq!(
+        amounts,
+        [2, 2, 1].map(BigDecimal::from).to_vec(),
+        "fills of an iceberg are capped at its display amount"
+    );
+
+    let resting = order_book.get_order_by_id(iceberg.id.clone()).unwrap();
+    assert_eq!(resting.remained_base, BigDecimal::from(5));
+    assert_eq!(resting.visible_base(), BigDecimal::from(2));
+}
+
+#[test]
+fn test_canceled_order_leaves_the_book_and_its_depth() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let buyer_id = create_funded_user(&repository, &[(market.quote_asset.as_str(), "100")]);
+    let mut order_book = create_test_order_book(&repository, &market);
+
+    let bids: Vec<TradeOrder> = ["9", "10"]
+        .into_iter()
+        .map(|price| {
+            user_order(
+                &buyer_id,
+                &market,
+                OrderSide::Buy,
+                OrderType::Limit,
+                price,
+                "1",
+                price,
+            )
+        })
+        .collect();
+    for bid in &bids {
+        order_book.add_order(bid.clone()).unwrap();
+    }
+
+    assert!(order_book
+        .cancel_order(bids[1].id.clone(), CancelReason::UserCanceled)
+        .unwrap());
+    assert_eq!(order_book.bids_len(), 1);
+    assert!(order_book.get_order_by_id(bids[1].id.clone()).is_err());
+    let depth = order_book.depth_snapshot();
+    assert_eq!(
+        depth.bids.into_iter().collect::<Vec<_>>(),
+        [(BigDecimal::from(9), BigDecimal::from(1))]
+    );
+}
+
+#[test]
+fn test_market_order_on_limit_path_never_rests() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let funds = [
+        (market.base_asset.as_str(), "100"),
+        (market.quote_asset.as_str(), "1000"),
+    ];
+    let seller_id = create_funded_user(&repository, &funds);
+    let buyer_id = create_funded_user(&repository, &funds);
+    let mut order_book = create_test_order_book(&repository, &market);
+
+    let ask = user_order(
+        &seller_id,
+        &market,
+        OrderSide::Sell,
+        OrderType::Limit,
+        "10",
+        "1",
+        "10",
+    );
+    order_book.add_order(ask).unwrap();
+
+    // Persisted like any order, then handed to the limit matcher as recovery or FOK would
+    let market_buy = user_order(
+        &buyer_id,
+        &market,
+        OrderSide::Buy,
+        OrderType::Market,
+        "10",
+        "2",
+        "20",
+    );
+    order_book.persist_create_order(&market_buy).unwrap();
+    let trades = order_book.match_limit_order(market_buy.clone()).unwrap();
+
+    assert_eq!(trades.len(), 1);
+    assert_eq!(order_book.bids_len(), 0);
+    assert_eq!(order_book.asks_len(), 0);
+    assert!(order_book.get_order_by_id(market_buy.id.clone()).is_err());
+    assert_eq!(
+        order_status(&repository, &market_buy.id),
+        OrderStatus::Canceled.as_str()
+    );
+}
+
+#[test]
+fn test_fok_market_buy_short_of_quote_budget_does_not_execute() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let funds = [
+        (market.base_asset.as_str(), "100"),
+        (market.quote_asset.as_str(), "1000"),
+    ];
+    let seller_id = create_funded_user(&repository, &funds);
+    let buyer_id = create_funded_user(&repository, &funds);
+    let mut order_book = create_test_order_book(&repository, &market);
+
+    for price in ["10", "12"] {
+        let ask = user_order(
+            &seller_id,
+            &market,
+            OrderSide::Sell,
+            OrderType::Limit,
+            price,
+            "1",
+            price,
+        );
+        order_book.add_order(ask).unwrap();
+    }
+
+    // Two base cost 10 + 12 = 22 across the levels, one cent more than the budget
+    let fok_buy = TradeOrder {
+        time_in_force: Some(TimeInForce::FOK),
+        expires_at: Some(get_utc_now_millis()),
+        ..user_order(
+            &buyer_id,
+            &market,
+            OrderSide::Buy,
+            OrderType::Market,
+            "11",
+            "2",
+            "21.99",
+        )
+    };
+    order_book.persist_create_order(&fok_buy).unwrap();
+
+    assert!(order_book.match_fok_order(fok_buy.clone()).is_err());
+    assert_eq!(order_book.asks_len(), 2);
+    assert_eq!(
+        order_status(&repository, &fok_buy.id),
+        OrderStatus::Canceled.as_str()
+    );
+
+    // With the full cost available the same order fills across both levels
+    let fok_buy = TradeOrder {
+        time_in_force: Some(TimeInForce::FOK),
+        expires_at: Some(get_utc_now_millis()),
+        ..user_order(
+            &buyer_id,
+            &market,
+            OrderSide::Buy,
+            OrderType::Market,
+            "11",
+            "2",
+            "22",
+        )
+    };
+    order_book.persist_create_order(&fok_buy).unwrap();
+
+    let trades = order_book.match_fok_order(fok_buy.clone()).unwrap();
+    assert_eq!(trades.len(), 2);
+    assert_eq!(order_book.asks_len(), 0);
+    assert_eq!(
+        order_status(&repository, &fok_buy.id),
+        OrderStatus::Filled.as_str()
+    );
+}
+
+#[test]
+fn test_price_collar_blocks_trade_far_from_last_price() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let funds = [
+        (market.base_asset.as_str(), "100"),
+        (market.quote_asset.as_str(), "1000"),
+    ];
+    let seller_id = create_funded_user(&repository, &funds);
+    let buyer_id = create_funded_user(&repository, &funds);
+    let mut order_book = create_test_order_book(&repository, &market);
+    order_book.set_price_collar(Some(BigDecimal::from(10)));
+
+    // A first trade at 10 sets the reference price
+    for (user_id, side) in [(&seller_id, OrderSide::Sell), (&buyer_id, OrderSide::Buy)] {
+        let order = user_order(user_id, &market, side, OrderType::Limit, "10", "1", "10");
+        order_book.add_order(order).unwrap();
+    }
+
+    // A fat-fingered ask at 15 rests, but a buy crossing it would trade 50% off the reference
+    let far_ask = user_order(
+        &seller_id,
+        &market,
+        OrderSide::Sell,
+        OrderType::Limit,
+        "15",
+        "1",
+        "15",
+    );
+    order_book.add_order(far_ask.clone()).unwrap();
+    let buy = user_order(
+        &buyer_id,
+        &market,
+        OrderSide::Buy,
+        OrderType::Limit,
+        "15",
+        "1",
+        "15",
+    );
+    assert!(order_book.add_order(buy.clone()).is_err());
+
+    let order = repository.get_order(&buy.id).unwrap().unwrap();
+    assert_eq!(order.get_status().unwrap(), OrderStatus::Canceled);
+    assert_eq!(
+        order.get_cancel_reason().unwrap(),
+        Some(CancelReason::PriceCollar)
+    );
+    assert_eq!(order.filled_base, BigDecimal::from(0));
+    assert_eq!(order_book.asks_len(), 1);
+    assert!(order_book.get_order_by_id(far_ask.id.clone()).is_ok());
+
+    // Within 10% of the reference the book trades as usual
+    let near_ask = user_order(
+        &seller_id,
+        &market,
+        OrderSide::Sell,
+        OrderType::Limit,
+        "10.5",
+        "1",
+        "10.5",
+    );
+    order_book.add_order(near_ask).unwrap();
+    let buy = user_order(
+        &buyer_id,
+        &market,
+        OrderSide::Buy,
+        OrderType::Limit,
+        "10.5",
+        "1",
+        "10.5",
+    );
+    assert_eq!(order_book.add_order(buy).unwrap().len(), 1);
+}
+
+#[test]
+fn test_price_collar_relaxes_on_stale_reference_price() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let funds = [
+        (market.base_asset.as_str(), "100"),
+        (market.quote_asset.as_str(), "1000"),
+    ];
+    let seller_id = create_funded_user(&repository, &funds);
+    let buyer_id = create_funded_user(&repository, &funds);
+    let mut order_book = create_test_order_book(&repository, &market);
+    order_book.set_price_collar(Some(BigDecimal::from(10)));
+    order_book.set_market_price_max_age(Some(200), StalePricePolicy::Widen(BigDecimal::from(3)));
+    let cross = |order_book: &mut OrderBook<Repository>, price: &str| {
+        let ask = user_order(
+            &seller_id,
+            &market,
+            OrderSide::Sell,
+            OrderType::Limit,
+            price,
+            "1",
+            price,
+        );
+        order_book.add_order(ask).unwrap();
+        let buy = user_order(
+            &buyer_id,
+            &market,
+            OrderSide::Buy,
+            OrderType::Limit,
+            price,
+            "1",
+            price,
+        );
+        order_book.add_order(buy)
+    };
+
+    assert_eq!(cross(&mut order_book, "10").unwrap().len(), 1);
+    thread::sleep(Duration::from_millis(300));
+
+    // The reference is stale, so the collar is widened to 30% and a trade 25% off passes
+    assert_eq!(cross(&mut order_book, "12.5").unwrap().len(), 1);
+
+    // That trade refreshed the reference, so the plain 10% collar is back
+    assert!(cross(&mut order_book, "15").is_err());
+    assert_eq!(order_book.asks_len(), 1);
+
+    // Once the reference goes stale again, disabling lets the resting ask trade
+    thread::sleep(Duration::from_millis(300));
+    order_book.set_market_price_max_age(Some(200), StalePricePolicy::Disable);
+    let buy = user_order(
+        &buyer_id,
+        &market,
+        OrderSide::Buy,
+        OrderType::Limit,
+        "15",
+        "1",
+        "15",
+    );
+    assert_eq!(order_book.add_order(buy).unwrap().len(), 1);
+    assert_eq!(order_book.asks_len(), 0);
+}
+
+#[test]
+fn test_recent_trades_evict_oldest_beyond_capacity() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let funds = [
+        (market.base_asset.as_str(), "100"),
+        (market.quote_asset.as_str(), "1000"),
+    ];
+    let seller_id = create_funded_user(&repository, &funds);
+    let buyer_id = create_funded_user(&repository, &funds);
+    let mut order_book = create_test_order_book(&repository, &market);
+    order_book.set_recent_trades_capacity(3);
+
+    // One trade per price, so each trade can be told apart by its price
+    for price in ["10", "11", "12", "13", "14"] {
+        for (user_id, side) in [(&seller_id, OrderSide::Sell), (&buyer_id, OrderSide::Buy)] {
+            let order = user_order(user_id, &market, side, OrderType::Limit, price, "1", price);
+            order_book.add_order(order).unwrap();
+        }
+    }
+
+    let prices: Vec<BigDecimal> = order_book
+        .recent_trades(10)
+        .into_iter()
+        .map(|trade| trade.price)
+        .collect();
+    assert_eq!(
+        prices,
+        vec![
+            BigDecimal::from(14),
+            BigDecimal::from(13),
+            BigDecimal::from(12)
+        ]
+    );
+    assert_eq!(order_book.recent_trades(1)[0].price, BigDecimal::from(14));
+
+    // Shrinking the buffer drops the oldest of the kept trades
+    order_book.set_recent_trades_capacity(1);
+    assert_eq!(order_book.recent_trades(10).len(), 1);
+    assert_eq!(order_book.recent_trades(10)[0].price, BigDecimal::from(14));
+}
+
+#[test]
+fn test_crossing_orders_trade_and_leave_the_book() {
+    let persister = Arc::new(MockPersister::new());
+    let market = create_test_market(&*persister);
+    let funds = [
+        (market.base_asset.as_str(), "1"),
+        (market.quote_asset.as_str(), "50000"),
+    ];
+    let buyer_id = create_funded_user(&*persister, &funds);
+    let seller_id = create_funded_user(&*persister, &funds);
+    let mut order_book = create_mock_order_book(&persister, &market);
+
+    let bid = limit_order(&buyer_id, &market, OrderSide::Buy, "50000", "1");
+    assert!(order_book.add_order(bid.clone()).unwrap().is_empty());
+
+    let ask = limit_order(&seller_id, &market, OrderSide::Sell, "50000", "1");
+    let trades = order_book.add_order(ask.clone()).unwrap();
+
+    assert_eq!(trades.len(), 1);
+    assert_eq!(trades[0].price, BigDecimal::from(50000));
+    assert_eq!(trades[0].base_amount, BigDecimal::from(1));
+    assert_eq!(trades[0].seller_order_id, ask.id);
+    assert_eq!(trades[0].buyer_order_id, bid.id);
+    assert_eq!(order_book.bids_len(), 0);
+    assert_eq!(order_book.asks_len(), 0);
+}
+
+#[test]
+fn test_partial_match_leaves_the_rest_of_the_maker_resting() {
+    let persister = Arc::new(MockPersister::new());
+    let market = create_test_market(&*persister);
+    let funds = [
+        (market.base_asset.as_str(), "2"),
+        (market.quote_asset.as_str(), "100000"),
+    ];
+    let buyer_id = create_funded_user(&*persister, &funds);
+    let seller_id = create_funded_user(&*persister, &funds);
+    let mut order_book = create_mock_order_book(&persister, &market);
+
+    let bid = limit_order(&buyer_id, &market, OrderSide::Buy, "50000", "2");
+    order_book.add_order(bid.clone()).unwrap();
+    let ask = limit_order(&seller_id, &market, OrderSide::Sell, "50000", "1");
+    let trades = order_book.add_order(ask).unwrap();
+
+    assert_eq!(trades.len(), 1);
+    assert_eq!(trades[0].base_amount, BigDecimal::from(1));
+    assert_eq!(order_book.asks_len(), 0);
+    assert_eq!(order_book.bids_len(), 1);
+    let resting = order_book.get_order_by_id(bid.id).unwrap();
+    assert_eq!(resting.remained_base, BigDecimal::from(1));
+}
+
+#[test]
+fn test_replenished_iceberg_slice_queues_behind_its_level() {
+    let persister = Arc::new(MockPersister::new());
+    let market = create_test_market(&*persister);
+    let seller_id = create_funded_user(&*persister, &[(market.base_asset.as_str(), "100")]);
+    let buyer_id = create_funded_user(&*persister, &[(market.quote_asset.as_str(), "1000")]);
+    let mut order_book = create_mock_order_book(&persister, &market);
+
+    let iceberg = TradeOrder {
+        display_amount: Some(BigDecimal::from(2)),
+        ..limit_order(&seller_id, &market, OrderSide::Sell, "10", "10")
+    };
+    order_book.add_order(iceberg.clone()).unwrap();
+    let behind = limit_order(&seller_id, &market, OrderSide::Sell, "10", "3");
+    order_book.add_order(behind.clone()).unwrap();
+
+    // The first slice trades, then the order that was waiting behind the iceberg
+    let buy = limit_order(&buyer_id, &market, OrderSide::Buy, "10", "6");
+    let trades = order_book.add_order(buy).unwrap();
+    let fills: Vec<(String, BigDecimal)> = trades
+        .iter()
+        .map(|trade| (trade.seller_order_id.clone(), trade.base_amount.clone()))
+        .collect();
+    assert_eq!(
+        fills,
+        [
+            (iceberg.id.clone(), BigDecimal::from(2)),
+            (behind.id.clone(), BigDecimal::from(3)),
+            (iceberg.id.clone(), BigDecimal::from(1)),
+        ]
+    );
+
+    // A fill short of the slice keeps the iceberg at the head of its level
+    let resting = order_book.get_order_by_id(iceberg.id.clone()).unwrap();
+    assert_eq!(resting.remained_base, BigDecimal::from(7));
+    let later = limit_order(&seller_id, &market, OrderSide::Sell, "10", "1");
+    order_book.add_order(later).unwrap();
+    let buy = limit_order(&buyer_id, &market, OrderSide::Buy, "10", "1");
+    let trades = order_book.add_order(buy).unwrap();
+    assert_eq!(trades[0].seller_order_id, iceberg.id);
+}
+
+#[test]
+fn test_recovered_book_keeps_the_queue_an_amendment_left() {
+    let persister = Arc::new(MockPersister::new());
+    let market = create_test_market(&*persister);
+    let seller_id = create_funded_user(&*persister, &[(market.base_asset.as_str(), "100")]);
+    let buyer_id = create_funded_user(&*persister, &[(market.quote_asset.as_str(), "1000")]);
+    let mut order_book = create_mock_order_book(&persister, &market);
+
+    let first = limit_order(&seller_id, &market, OrderSide::Sell, "10", "1");
+    let second = limit_order(&seller_id, &market, OrderSide::Sell, "10", "1");
+    order_book.add_order(first.clone()).unwrap();
+    order_book.add_order(second.clone()).unwrap();
+    // Growing the first ask sends it behind the second, though it was created before it
+    order_book
+        .amend_order(first.id.clone(), None, Some(BigDecimal::from(2)))
+        .unwrap();
+    drop(order_book);
+
+    let mut order_book = create_mock_order_book(&persister, &market);
+    // New orders still queue behind every recovered one
+    let later = limit_order(&seller_id, &market, OrderSide::Sell, "10", "1");
+    order_book.add_order(later.clone()).unwrap();
+
+    let buy = limit_order(&buyer_id, &market, OrderSide::Buy, "10", "4");
+    let trades = order_book.add_order(buy).unwrap();
+    let sellers: Vec<&str> = trades
+        .iter()
+        .map(|trade| trade.seller_order_id.as_str())
+        .collect();
+    assert_eq!(sellers, [&second.id, &first.id, &later.id]);
+}
@@ -0,0 +1,108 @@
+use bigdecimal::BigDecimal;
+use common::utils::get_utc_now_millis;
+use database::models::models::{CancelReason, OrderStatus};
+use database::provider::{OrderDatabaseReader, WalletDatabaseReader};
+use database::tests::test_db::{create_funded_user, create_test_market, isolated_test_repository};
+use tonic::{Code, Request};
+
+use crate::grpc::admin::admin_service_server::AdminService;
+use crate::grpc::spot::spot_service_server::SpotService;
+use crate::grpc::spot::{AddOrderRequest, StartMarketRequest};
+use crate::tests::test_service::{add_order_request, create_test_service};
+
+#[tokio::test]
+async fn test_gtd_order_is_canceled_once_expired() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let seller_id = create_funded_user(
+        &repository,
+        &[(&market.base_asset, "10"), (&market.quote_asset, "1")],
+    );
+    let buyer_id = create_funded_user(
+        &repository,
+        &[(&market.base_asset, "1"), (&market.quote_asset, "1000")],
+    );
+    let service = create_test_service(repository.clone());
+    service
+        .admin_service()
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+
+    let expires_at = get_utc_now_millis() + 60_000;
+    let gtd = service
+        .add_order(Request::new(AddOrderRequest {
+            time_in_force: "GTD".to_string(),
+            expires_at,
+            ..add_order_request(&market, &seller_id, "SELL", "10", "4")
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    let gtc = service
+        .add_order(Request::new(add_order_request(
+            &market, &seller_id, "SELL", "11", "1",
+        )))
+        .await
+        .unwrap()
+        .into_inner();
+
+    let market_manager = service.market_manager.read().await;
+    assert_eq!(market_manager.expire_orders(expires_at - 1).unwrap(), 0);
+    assert_eq!(market_manager.expire_orders(expires_at).unwrap(), 1);
+    drop(market_manager);
+
+    let order = repository.get_order(&gtd.order_id).unwrap().unwrap();
+    assert_eq!(order.get_status().unwrap(), OrderStatus::Canceled);
+    assert_eq!(
+        order.get_cancel_reason().unwrap(),
+        Some(CancelReason::Expired)
+    );
+    let gtc_order = repository.get_order(&gtc.order_id).unwrap().unwrap();
+    assert_eq!(gtc_order.get_status().unwrap(), OrderStatus::Open);
+
+    // Only the GTC order still holds base
+    let base = repository
+        .get_wallet(&seller_id, &market.base_asset)
+        .unwrap()
+        .unwrap();
+    assert_eq!(base.available, BigDecimal::from(9));
+    assert_eq!(base.locked, BigDecimal::from(1));
+
+    // The expired order is off the book, a buy at its price finds nothing
+    let buy = service
+        .add_order(Request::new(add_order_request(
+            &market, &buyer_id, "BUY", "10", "4",
+        )))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(buy.trades.is_empty());
+}
+
+#[tokio::test]
+async fn test_expires_at_is_only_accepted_on_future_gtd_orders() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let user_id = create_funded_user(&repository, &[(&market.quote_asset, "100")]);
+    let service = create_test_service(repository.clone());
+
+    let now = get_utc_now_millis();
+    for (time_in_force, expires_at) in [("GTD", now - 1), ("GTD", 0), ("GTC", now + 60_000)] {
+        let status = service
+            .add_order(Request::new(AddOrderRequest {
+                time_in_force: time_in_force.to_string(),
+                expires_at,
+                ..add_order_request(&market, &user_id, "BUY", "10", "1")
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument, "{}", time_in_force);
+    }
+}
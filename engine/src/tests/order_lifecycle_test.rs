@@ -0,0 +1,172 @@
+use common::utils::get_uuid_string;
+use database::repository::Repository;
+use database::tests::test_db::{create_test_market, isolated_test_repository};
+use spot_query::service::SpotQueryServiceImp;
+use spot_query::spot_query::spot_query_service_server::SpotQueryService;
+use spot_query::spot_query::{
+    GetOrderRequest, GetWalletRequest, ListTradesRequest, ProtoOrder, ProtoTradeFilter, ProtoWallet,
+};
+use tonic::Request;
+
+use crate::grpc::admin::admin_service_server::AdminService;
+use crate::grpc::spot::spot_service_server::SpotService;
+use crate::grpc::spot::{
+    CancelOrderRequest, CompleteDepositRequest, GetBalanceRequest, StartMarketRequest,
+};
+use crate::tests::test_models::decimal;
+use crate::tests::test_service::{add_order_request, create_test_service};
+
+async fn get_order(query: &SpotQueryServiceImp<Repository>, order_id: &str) -> ProtoOrder {
+    query
+        .get_order(Request::new(GetOrderRequest {
+            order_id: order_id.to_string(),
+        }))
+        .await
+        .unwrap()
+        .into_inner()
+        .order
+        .unwrap()
+}
+
+async fn get_wallet(
+    query: &SpotQueryServiceImp<Repository>,
+    user_id: &str,
+    asset: &str,
+) -> ProtoWallet {
+    query
+        .get_wallet(Request::new(GetWalletRequest {
+            user_id: user_id.to_string(),
+            asset: asset.to_string(),
+        }))
+        .await
+        .unwrap()
+        .into_inner()
+        .wallet
+        .unwrap()
+}
+
+/// Drives crossing orders through the engine service and reads the outcome back through the
+/// query service, both running over the same database.
+#[tokio::test]
+async fn test_crossing_orders_are_consistent_across_engine_and_query() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let engine = create_test_service(repository.clone());
+    let query = SpotQueryServiceImp::new(repository);
+    engine
+        .admin_service()
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+
+    let seller_id = get_uuid_string();
+    let buyer_id = get_uuid_string();
+    for (user_id, asset, amount) in [
+        (&seller_id, &market.base_asset, "5"),
+        (&buyer_id, &market.quote_asset, "100"),
+    ] {
+        engine
+            .complete_deposit(Request::new(CompleteDepositRequest {
+                user_id: user_id.clone(),
+                asset: asset.clone(),
+                amount: amount.to_string(),
+                external_id: get_uuid_string(),
+            }))
+            .await
+            .unwrap();
+    }
+
+    // The ask rests, the larger bid takes all of it and rests with the remainder
+    let ask = engine
+        .add_order(Request::new(add_order_request(
+            &market, &seller_id, "SELL", "10", "2",
+        )))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(ask.trades.is_empty());
+    let bid = engine
+        .add_order(Request::new(add_order_request(
+            &market, &buyer_id, "BUY", "10", "3",
+        )))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(bid.trades.len(), 1);
+    assert_eq!(bid.resting.as_ref().unwrap().remained_base, "1");
+
+    let sell_order = get_order(&query, &ask.order_id).await;
+    assert_eq!(sell_order.status, "FILLED");
+    assert_eq!(sell_order.filled_base, "2");
+    assert_eq!(sell_order.average_fill_price, "10");
+    let buy_order = get_order(&query, &bid.order_id).await;
+    assert_eq!(buy_order.status, "PARTIALLY_FILLED");
+    assert_eq!(buy_order.filled_base, "2");
+    assert_eq!(buy_order.remained_base, "1");
+    assert_eq!(buy_order.average_fill_price, "10");
+
+    let trades = query
+        .list_trades(Request::new(ListTradesRequest {
+            filter: Some(ProtoTradeFilter {
+                market_id: Some(market.id.clone()),
+                ..Default::default()
+            }),
+            pagination: None,
+        }))
+        .await
+        .unwrap()
+        .into_inner()
+        .trades;
+    assert_eq!(trades.len(), 1);
+    let trade = &trades[0];
+    assert_eq!(trade.id, bid.trades[0].id);
+    assert_eq!(trade.price, "10");
+    assert_eq!(trade.base_amount, "2");
+    assert_eq!(trade.buyer_order_id, bid.order_id);
+    assert_eq!(trade.seller_order_id, ask.order_id);
+    let buyer_fee = decimal(&trade.buyer_fee);
+    let seller_fee = decimal(&trade.seller_fee);
+    assert_eq!(buyer_fee, decimal("2") * &market.default_taker_fee);
+    assert_eq!(seller_fee, decimal("20") * &market.default_maker_fee);
+
+    let seller_base = get_wallet(&query, &seller_id, &market.base_asset).await;
+    assert_eq!(decimal(&seller_base.available), decimal("3"));
+    assert_eq!(decimal(&seller_base.locked), decimal("0"));
+    let seller_quote = get_wallet(&query, &seller_id, &market.quote_asset).await;
+    assert_eq!(
+        decimal(&seller_quote.available),
+        decimal("20") - &seller_fee
+    );
+    let buyer_base = get_wallet(&query, &buyer_id, &market.base_asset).await;
+    assert_eq!(decimal(&buyer_base.available), decimal("2") - &buyer_fee);
+    let buyer_quote = get_wallet(&query, &buyer_id, &market.quote_asset).await;
+    assert_eq!(decimal(&buyer_quote.available), decimal("70"));
+    assert_eq!(decimal(&buyer_quote.locked), decimal("10"));
+
+    // Canceling the rest releases the quote it held, which both services agree on
+    engine
+        .cancel_order(Request::new(CancelOrderRequest {
+            order_id: bid.order_id.clone(),
+            market_id: market.id.clone(),
+            ..Default::default()
+        }))
+        .await
+        .unwrap();
+    assert_eq!(get_order(&query, &bid.order_id).await.status, "CANCELED");
+    let buyer_quote = get_wallet(&query, &buyer_id, &market.quote_asset).await;
+    assert_eq!(decimal(&buyer_quote.available), decimal("80"));
+    assert_eq!(decimal(&buyer_quote.locked), decimal("0"));
+    let balance = engine
+        .get_balance(Request::new(GetBalanceRequest {
+            user_id: buyer_id.clone(),
+            asset: market.quote_asset.clone(),
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(balance.amount, buyer_quote.available);
+}
@@ -0,0 +1,97 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Result};
+use database::provider::OutboxDatabaseReader;
+use database::tests::test_db::{
+    create_funded_user, create_test_market, execute_test_trade, isolated_test_repository,
+};
+
+use crate::outbox::publisher::EventPublisher;
+use crate::outbox::relay::OutboxRelay;
+
+/// Records what it is given, and refuses everything once `accept` runs out
+#[derive(Default)]
+struct RecordingPublisher {
+    published: Mutex<Vec<(String, String)>>,
+    accept: Mutex<usize>,
+}
+
+impl EventPublisher for Arc<RecordingPublisher> {
+    async fn publish(&self, subject: String, message_id: String, _payload: String) -> Result<()> {
+        let mut accept = self.accept.lock().unwrap();
+        if *accept == 0 {
+            return Err(anyhow!("broker unavailable"));
+        }
+        *accept -= 1;
+        self.published.lock().unwrap().push((subject, message_id));
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_relay_publishes_outbox_in_order_and_resumes_after_failure() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let repository = repository.with_outbox(true);
+    let market = create_test_market(&repository);
+    let funds = [
+        (market.base_asset.as_str(), "10"),
+        (market.quote_asset.as_str(), "100"),
+    ];
+    let buyer_id = create_funded_user(&repository, &funds);
+    let seller_id = create_funded_user(&repository, &funds);
+    execute_test_trade(&repository, &market, &buyer_id, &seller_id, "10", "1");
+    let pending = repository.get_unpublished_events(100).unwrap();
+    assert_eq!(pending.len(), 7);
+
+    let publisher = Arc::new(RecordingPublisher {
+        accept: Mutex::new(3),
+        ..Default::default()
+    });
+    let relay = OutboxRelay::new(Arc::new(repository.clone()), publisher.clone());
+
+    // The broker goes away after three events: those stay published, the rest wait
+    assert!(relay.relay_pending().await.is_err());
+    assert_eq!(repository.get_unpublished_events(100).unwrap().len(), 4);
+
+    *publisher.accept.lock().unwrap() = usize::MAX;
+    assert_eq!(relay.relay_pending().await.unwrap(), 4);
+    assert_eq!(relay.relay_pending().await.unwrap(), 0);
+
+    let published = publisher.published.lock().unwrap();
+    let expected: Vec<_> = pending
+        .iter()
+        .map(|event| {
+            let subject = format!("bitrade.{}.{}", event.topic.to_lowercase(), market.id);
+            (subject, event.id.to_string())
+        })
+        .collect();
+    assert_eq!(*published, expected);
+    assert_eq!(published[0].0, format!("bitrade.trade.{}", market.id));
+}
+
+#[tokio::test]
+async fn test_flush_relays_every_batch() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let repository = repository.with_outbox(true);
+    let market = create_test_market(&repository);
+    let funds = [
+        (market.base_asset.as_str(), "10"),
+        (market.quote_asset.as_str(), "100"),
+    ];
+    let buyer_id = create_funded_user(&repository, &funds);
+    let seller_id = create_funded_user(&repository, &funds);
+    execute_test_trade(&repository, &market, &buyer_id, &seller_id, "10", "1");
+
+    let publisher = Arc::new(RecordingPublisher {
+        accept: Mutex::new(usize::MAX),
+        ..Default::default()
+    });
+    let relay = OutboxRelay::new(Arc::new(repository.clone()), publisher).with_batch_size(2);
+
+    assert_eq!(relay.flush().await.unwrap(), 7);
+    assert!(repository.get_unpublished_events(100).unwrap().is_empty());
+}
@@ -0,0 +1,116 @@
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use common::auth::API_KEY_HEADER;
+use common::rate_limit::{RateClass, RateLimitLayer, RateLimiter, RateLimits};
+use database::tests::test_db::isolated_test_repository;
+use tonic::body::BoxBody;
+use tonic::{Code, Request, Status};
+use tower::{service_fn, Layer, Service};
+
+use crate::grpc::rate_limit::method_class;
+use crate::grpc::service::SpotServiceImpl;
+use crate::grpc::spot::spot_service_server::SpotService;
+use crate::grpc::spot::GetRateLimitsRequest;
+use crate::tests::test_service::create_test_service;
+
+fn limits() -> RateLimits {
+    RateLimits {
+        orders_per_sec: 2,
+        cancels_per_sec: 1,
+        queries_per_sec: 0,
+    }
+}
+
+#[test]
+fn test_bucket_refills_over_time() {
+    let limiter = RateLimiter::new(limits());
+    let start = Instant::now();
+    assert!(limiter.check("alice", RateClass::Order, start).is_ok());
+    assert!(limiter.check("alice", RateClass::Order, start).is_ok());
+
+    let status = limiter.check("alice", RateClass::Order, start).unwrap_err();
+    assert_eq!(status.code(), Code::ResourceExhausted);
+    assert_eq!(status.metadata().get("retry-after").unwrap(), "1");
+    assert_eq!(status.metadata().get("retry-after-ms").unwrap(), "500");
+
+    // Half a second brings one order back at 2 per second
+    let later = start + Duration::from_millis(500);
+    assert!(limiter.check("alice", RateClass::Order, later).is_ok());
+    assert!(limiter.check("alice", RateClass::Order, later).is_err());
+}
+
+#[test]
+fn test_buckets_are_per_client_and_class() {
+    let limiter = RateLimiter::new(limits());
+    let now = Instant::now();
+    assert!(limiter.check("alice", RateClass::Cancel, now).is_ok());
+    assert!(limiter.check("alice", RateClass::Cancel, now).is_err());
+    // Other clients and other classes are not held back
+    assert!(limiter.check("bob", RateClass::Cancel, now).is_ok());
+    assert!(limiter.check("alice", RateClass::Order, now).is_ok());
+    // Queries are unlimited
+    for _ in 0..100 {
+        assert!(limiter.check("alice", RateClass::Query, now).is_ok());
+    }
+}
+
+#[test]
+fn test_order_and_cancel_methods_are_classed() {
+    assert_eq!(method_class("AddOrders"), Some(RateClass::Order));
+    assert_eq!(method_class("AmendOrder"), Some(RateClass::Order));
+    assert_eq!(method_class("CancelAllOrders"), Some(RateClass::Cancel));
+    assert_eq!(method_class("GetOrderBookDepth"), Some(RateClass::Query));
+    assert_eq!(method_class("HealthCheck"), None);
+    assert_eq!(method_class("GetRateLimits"), None);
+}
+
+#[tokio::test]
+async fn test_rate_limit_layer_limits_each_api_key() {
+    let limiter = Arc::new(RateLimiter::new(limits()));
+    let mut service = RateLimitLayer::new(limiter, method_class).layer(service_fn(
+        |_request: http::Request<()>| async {
+            Ok::<_, Infallible>(http::Response::new(BoxBody::default()))
+        },
+    ));
+    let mut call = |method: &str, key: &str| {
+        let request = http::Request::builder()
+            .uri(format!("/spot.SpotService/{}", method))
+            .header(API_KEY_HEADER, key)
+            .body(())
+            .unwrap();
+        let response = service.call(request);
+        async move {
+            let response = response.await.unwrap();
+            Status::from_header_map(response.headers())
+                .map(|status| status.code())
+                .unwrap_or(Code::Ok)
+        }
+    };
+
+    assert_eq!(call("CancelOrder", "k1").await, Code::Ok);
+    assert_eq!(call("CancelOrder", "k1").await, Code::ResourceExhausted);
+    assert_eq!(call("CancelOrder", "k2").await, Code::Ok);
+    assert_eq!(call("HealthCheck", "k1").await, Code::Ok);
+}
+
+#[tokio::test]
+async fn test_get_rate_limits_returns_the_configured_limits() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let service = SpotServiceImpl {
+        rate_limits: limits(),
+        ..create_test_service(repository)
+    };
+
+    let response = service
+        .get_rate_limits(Request::new(GetRateLimitsRequest {}))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(response.orders_per_sec, 2);
+    assert_eq!(response.cancels_per_sec, 1);
+    assert_eq!(response.queries_per_sec, 0);
+}
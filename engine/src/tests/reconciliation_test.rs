@@ -0,0 +1,138 @@
+use bigdecimal::BigDecimal;
+use database::models::models::OrderSide;
+use database::provider::{OrderDatabaseWriter, WalletDatabaseWriter};
+use database::tests::test_db::{
+    create_funded_user, create_test_market, execute_test_trade, isolated_test_repository,
+    new_limit_order,
+};
+use tonic::Request;
+
+use crate::grpc::admin::{admin_service_server::AdminService, ReleaseOrphanedLocksRequest};
+use crate::grpc::spot::GetReconciliationReportRequest;
+use crate::tests::test_service::create_test_service;
+
+#[tokio::test]
+async fn test_reconciliation_report_flags_orphaned_locks() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let buyer_id = create_funded_user(&repository, &[(&market.quote_asset, "1000")]);
+    let seller_id = create_funded_user(&repository, &[(&market.base_asset, "10")]);
+    execute_test_trade(&repository, &market, &buyer_id, &seller_id, "100", "2");
+    repository
+        .create_order(new_limit_order(
+            &market,
+            &buyer_id,
+            OrderSide::Buy,
+            "90",
+            "1",
+        ))
+        .unwrap();
+
+    let service = create_test_service(repository.clone());
+    let report = service
+        .admin_service()
+        .get_reconciliation_report(Request::new(GetReconciliationReportRequest {
+            refresh: false,
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(report.balanced);
+    assert_eq!(report.assets_checked, 2);
+    assert_eq!(report.locks_checked, 1);
+
+    // Funds locked outside of any order break the lock invariant but not the asset one
+    repository
+        .lock_balance(&seller_id, &market.base_asset, BigDecimal::from(3))
+        .unwrap();
+
+    // Without a refresh the last report is served as is
+    let cached = service
+        .admin_service()
+        .get_reconciliation_report(Request::new(GetReconciliationReportRequest {
+            refresh: false,
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(cached.generated_at, report.generated_at);
+    assert!(cached.balanced);
+
+    let report = service
+        .admin_service()
+        .get_reconciliation_report(Request::new(GetReconciliationReportRequest {
+            refresh: true,
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(!report.balanced);
+    assert!(report.asset_discrepancies.is_empty());
+    assert_eq!(report.lock_discrepancies.len(), 1);
+    let discrepancy = &report.lock_discrepancies[0];
+    assert_eq!(discrepancy.user_id, seller_id);
+    assert_eq!(discrepancy.asset, market.base_asset);
+    assert_eq!(discrepancy.difference.parse::<f64>().unwrap(), 3.0);
+}
+
+#[tokio::test]
+async fn test_orphaned_locks_are_released_for_a_user_or_swept() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let first_id = create_funded_user(&repository, &[(&market.base_asset, "10")]);
+    let second_id = create_funded_user(&repository, &[(&market.base_asset, "10")]);
+    repository
+        .create_order(new_limit_order(
+            &market,
+            &first_id,
+            OrderSide::Sell,
+            "100",
+            "1",
+        ))
+        .unwrap();
+    for user_id in [&first_id, &second_id] {
+        repository
+            .lock_balance(user_id, &market.base_asset, BigDecimal::from(3))
+            .unwrap();
+    }
+    let admin = create_test_service(repository.clone()).admin_service();
+
+    // What the open order needs stays locked
+    let released = admin
+        .release_orphaned_locks(Request::new(ReleaseOrphanedLocksRequest {
+            user_id: first_id.clone(),
+        }))
+        .await
+        .unwrap()
+        .into_inner()
+        .wallets;
+    assert_eq!(released.len(), 1);
+    assert_eq!(
+        (released[0].available.as_str(), released[0].locked.as_str()),
+        ("9", "1")
+    );
+
+    // Without a user, every one reconciliation flags is released
+    let released = admin
+        .release_orphaned_locks(Request::new(ReleaseOrphanedLocksRequest {
+            user_id: String::new(),
+        }))
+        .await
+        .unwrap()
+        .into_inner()
+        .wallets;
+    assert_eq!(released.len(), 1);
+    assert_eq!(released[0].user_id, second_id);
+    let report = admin
+        .get_reconciliation_report(Request::new(GetReconciliationReportRequest {
+            refresh: true,
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(report.balanced);
+}
@@ -0,0 +1,57 @@
+use std::time::Duration;
+
+use database::tests::test_db::{
+    create_funded_user, create_test_market, isolated_test_repository, lock_table,
+};
+use tonic::{Code, Request};
+
+use crate::grpc::admin::admin_service_server::AdminService;
+use crate::grpc::spot::spot_service_server::SpotService;
+use crate::grpc::spot::StartMarketRequest;
+use crate::tests::test_service::{add_order_request, create_recovering_test_service};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_orders_are_rejected_until_recovery_finishes() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let user_id = create_funded_user(&repository, &[(&market.quote_asset, "100")]);
+
+    // Recovery reads the open orders, so it stalls while the table is locked
+    let orders_lock = lock_table(&repository, "orders");
+    let service = create_recovering_test_service(repository.clone());
+    service
+        .admin_service()
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+
+    let status = service
+        .add_order(Request::new(add_order_request(
+            &market, &user_id, "BUY", "10", "1",
+        )))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::Unavailable);
+
+    drop(orders_lock);
+    tokio::time::timeout(Duration::from_secs(30), async {
+        while service.market_manager.read().await.is_recovering().unwrap() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("Recovery did not finish");
+
+    let response = service
+        .add_order(Request::new(add_order_request(
+            &market, &user_id, "BUY", "10", "1",
+        )))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(response.resting.is_some());
+}
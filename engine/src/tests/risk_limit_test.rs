@@ -0,0 +1,133 @@
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use database::provider::{OrderDatabaseReader, RiskLimitDatabaseWriter};
+use database::tests::test_db::{create_funded_user, create_test_market, isolated_test_repository};
+use tonic::{Code, Request};
+
+use crate::grpc::admin::admin_service_server::AdminService;
+use crate::grpc::spot::spot_service_server::SpotService;
+use crate::grpc::spot::{CancelOrderRequest, StartMarketRequest};
+use crate::tests::test_service::{add_order_request, create_test_service};
+
+#[tokio::test]
+async fn test_open_order_limit_counts_only_active_orders() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    repository
+        .set_risk_limit(&market.id, Some(2), None)
+        .unwrap();
+    let user_id = create_funded_user(&repository, &[(&market.quote_asset, "1000")]);
+    let other_id = create_funded_user(&repository, &[(&market.quote_asset, "1000")]);
+
+    let service = create_test_service(repository.clone());
+    service
+        .admin_service()
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+    let mut placed = Vec::new();
+    for price in ["10", "9"] {
+        let response = service
+            .add_order(Request::new(add_order_request(
+                &market, &user_id, "BUY", price, "1",
+            )))
+            .await
+            .unwrap()
+            .into_inner();
+        placed.push(response.order_id);
+    }
+
+    let status = service
+        .add_order(Request::new(add_order_request(
+            &market, &user_id, "BUY", "8", "1",
+        )))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::ResourceExhausted);
+    assert!(
+        status.message().contains("2 open orders"),
+        "{}",
+        status.message()
+    );
+    // The limit applies to each user on their own
+    service
+        .add_order(Request::new(add_order_request(
+            &market, &other_id, "BUY", "8", "1",
+        )))
+        .await
+        .unwrap();
+
+    // Canceling frees a slot
+    service
+        .cancel_order(Request::new(CancelOrderRequest {
+            order_id: placed[0].clone(),
+            market_id: market.id.clone(),
+            ..Default::default()
+        }))
+        .await
+        .unwrap();
+    service
+        .add_order(Request::new(add_order_request(
+            &market, &user_id, "BUY", "8", "1",
+        )))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_locked_notional_limit_refuses_before_locking_funds() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    repository
+        .set_risk_limit(&market.id, None, Some(BigDecimal::from(100)))
+        .unwrap();
+    let user_id = create_funded_user(
+        &repository,
+        &[(&market.base_asset, "100"), (&market.quote_asset, "1000")],
+    );
+
+    let service = create_test_service(repository.clone());
+    service
+        .admin_service()
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+    service
+        .add_order(Request::new(add_order_request(
+            &market, &user_id, "BUY", "10", "6",
+        )))
+        .await
+        .unwrap();
+
+    // 60 on the bid plus 4 at 12 on the ask goes past 100
+    let status = service
+        .add_order(Request::new(add_order_request(
+            &market, &user_id, "SELL", "12", "4",
+        )))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::ResourceExhausted);
+    assert!(status.message().contains("108"), "{}", status.message());
+    assert_eq!(
+        repository
+            .get_user_locked_notional(&user_id, &market.id)
+            .unwrap(),
+        BigDecimal::from_str("60").unwrap()
+    );
+
+    service
+        .add_order(Request::new(add_order_request(
+            &market, &user_id, "SELL", "12", "3",
+        )))
+        .await
+        .unwrap();
+}
@@ -0,0 +1,54 @@
+use common::rounding::{Rounding, RoundingConfig};
+
+use crate::tests::test_models::decimal;
+
+#[test]
+fn test_each_rounding_mode_on_boundary_values() {
+    // value, truncate, half-up, half-even
+    let cases = [
+        ("1.005", "1.00", "1.01", "1.00"),
+        ("1.015", "1.01", "1.02", "1.02"),
+        ("1.0049999", "1.00", "1.00", "1.00"),
+        ("1.0050001", "1.00", "1.01", "1.01"),
+        ("1.999", "1.99", "2.00", "2.00"),
+        ("-1.005", "-1.00", "-1.01", "-1.00"),
+        ("0.009", "0.00", "0.01", "0.01"),
+        ("7.25", "7.25", "7.25", "7.25"),
+    ];
+
+    for (value, truncate, half_up, half_even) in cases {
+        for (mode, expected) in [
+            (Rounding::Truncate, truncate),
+            (Rounding::HalfUp, half_up),
+            (Rounding::HalfEven, half_even),
+        ] {
+            let config = RoundingConfig {
+                mode,
+                default_scale: 2,
+            };
+            assert_eq!(
+                config.round(&decimal(value)),
+                decimal(expected),
+                "{} of {}",
+                mode.as_str(),
+                value
+            );
+        }
+    }
+}
+
+#[test]
+fn test_rounding_to_an_explicit_scale_and_mode_names() {
+    let config = RoundingConfig {
+        mode: Rounding::Truncate,
+        default_scale: 8,
+    };
+    let value = decimal("0.123456789");
+    assert_eq!(config.round(&value).to_string(), "0.12345678");
+    assert_eq!(config.round_to_scale(&value, 3).to_string(), "0.123");
+
+    for mode in [Rounding::Truncate, Rounding::HalfUp, Rounding::HalfEven] {
+        assert_eq!(Rounding::from_str(mode.as_str()).unwrap(), mode);
+    }
+    assert!(Rounding::from_str("CEILING").is_err());
+}
@@ -0,0 +1,12 @@
+use crate::grpc::helper::server_info;
+
+#[test]
+fn test_server_info_reports_package_version() {
+    let info = server_info();
+
+    assert!(!info.version.is_empty());
+    assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+    assert!(!info.git_hash.is_empty());
+    assert_eq!(info.order_types, vec!["LIMIT", "MARKET"]);
+    assert_eq!(info.time_in_force, vec!["GTC", "IOC", "GTD"]);
+}
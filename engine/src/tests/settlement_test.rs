@@ -0,0 +1,218 @@
+use database::models::models::{EngineEventType, Market, OrderStatus};
+use database::provider::{EngineEventDatabaseReader, OrderDatabaseReader, WalletDatabaseReader};
+use database::tests::test_db::{create_funded_user, create_test_market, isolated_test_repository};
+
+use crate::models::trade_order::{OrderSide, TradeOrder};
+use crate::tests::test_models::{self, create_test_order_book, decimal};
+
+/// A limit order paying the fees the settlement checks are worked out with
+fn limit_order(
+    user_id: &str,
+    market: &Market,
+    side: OrderSide,
+    price: &str,
+    base: &str,
+) -> TradeOrder {
+    TradeOrder {
+        maker_fee: decimal("0.001"),
+        taker_fee: decimal("0.002"),
+        ..test_models::limit_order(user_id, market, side, price, base)
+    }
+}
+
+#[test]
+fn test_sweeping_order_settles_its_fills_together() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let funds = [
+        (market.base_asset.as_str(), "100"),
+        (market.quote_asset.as_str(), "1000"),
+    ];
+    let seller_id = create_funded_user(&repository, &funds);
+    let buyer_id = create_funded_user(&repository, &funds);
+    let mut order_book = create_test_order_book(&repository, &market);
+
+    let mut asks = Vec::new();
+    for price in ["10", "11", "12"] {
+        let ask = limit_order(&seller_id, &market, OrderSide::Sell, price, "1");
+        order_book.add_order(ask.clone()).unwrap();
+        asks.push(ask);
+    }
+    let applied_before = repository.get_applied_sequence(&market.id).unwrap();
+
+    let buy = limit_order(&buyer_id, &market, OrderSide::Buy, "12", "2.5");
+    let trades = order_book.add_order(buy.clone()).unwrap();
+    let amounts: Vec<_> = trades.iter().map(|t| t.base_amount.clone()).collect();
+    assert_eq!(amounts, [decimal("1"), decimal("1"), decimal("0.5")]);
+
+    // The order, then one journal entry per fill, checkpointed once the batch settled
+    let events = repository
+        .get_engine_events(&market.id, applied_before)
+        .unwrap();
+    let types: Vec<_> = events.iter().map(|e| e.event_type.as_str()).collect();
+    assert_eq!(
+        types,
+        [
+            EngineEventType::OrderAccepted.as_str(),
+            EngineEventType::TradeExecuted.as_str(),
+            EngineEventType::TradeExecuted.as_str(),
+            EngineEventType::TradeExecuted.as_str(),
+        ]
+    );
+    assert_eq!(
+        repository.get_applied_sequence(&market.id).unwrap(),
+        events[3].sequence
+    );
+
+    let order = repository.get_order(&buy.id).unwrap().unwrap();
+    assert_eq!(order.get_status().unwrap(), OrderStatus::Filled);
+    assert_eq!(order.filled_quote, decimal("30"));
+    // The partly taken ask rests with what the database settled it at
+    assert_eq!(order_book.asks_len(), 1);
+    let rest = order_book.get_order_by_id(asks[2].id.clone()).unwrap();
+    assert_eq!(rest.remained_base, decimal("0.5"));
+    assert_eq!(rest.status, OrderStatus::PartiallyFilled);
+
+    // 2.5 base less the 0.2% taker fee, for all the quote it locked
+    let buyer_base = repository
+        .get_wallet(&buyer_id, &market.base_asset)
+        .unwrap()
+        .unwrap();
+    assert_eq!(buyer_base.available, decimal("102.495"));
+    let buyer_quote = repository
+        .get_wallet(&buyer_id, &market.quote_asset)
+        .unwrap()
+        .unwrap();
+    assert_eq!(buyer_quote.available, decimal("970"));
+    assert_eq!(buyer_quote.locked, decimal("0"));
+}
+
+#[test]
+fn test_refused_settlement_puts_the_book_back() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let funds = [
+        (market.base_asset.as_str(), "100"),
+        (market.quote_asset.as_str(), "1000"),
+    ];
+    let seller_id = create_funded_user(&repository, &funds);
+    let buyer_id = create_funded_user(&repository, &funds);
+    let mut order_book = create_test_order_book(&repository, &market);
+
+    let ask = limit_order(&seller_id, &market, OrderSide::Sell, "10", "1");
+    order_book.add_order(ask.clone()).unwrap();
+    // The buyer's own ask makes the second fill a trade with itself, refused at settlement
+    let own_ask = limit_order(&buyer_id, &market, OrderSide::Sell, "11", "1");
+    order_book.add_order(own_ask.clone()).unwrap();
+
+    let buy = limit_order(&buyer_id, &market, OrderSide::Buy, "11", "2");
+    assert!(order_book.add_order(buy).is_err());
+
+    // Neither fill settled, and both asks rest as they did, in the same order
+    assert_eq!(order_book.asks_len(), 2);
+    for queued in [&ask, &own_ask] {
+        let resting = order_book.get_order_by_id(queued.id.clone()).unwrap();
+        assert_eq!(resting.remained_base, decimal("1"));
+        let order = repository.get_order(&queued.id).unwrap().unwrap();
+        assert_eq!(order.get_status().unwrap(), OrderStatus::Open);
+    }
+    let trades = order_book
+        .add_order(limit_order(&buyer_id, &market, OrderSide::Buy, "10", "1"))
+        .unwrap();
+    assert_eq!(trades.len(), 1);
+    assert_eq!(trades[0].seller_order_id, ask.id);
+}
+
+#[test]
+fn test_atomic_placement_stores_the_order_with_its_fills() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let funds = [
+        (market.base_asset.as_str(), "100"),
+        (market.quote_asset.as_str(), "1000"),
+    ];
+    let seller_id = create_funded_user(&repository, &funds);
+    let buyer_id = create_funded_user(&repository, &funds);
+    let mut order_book = create_test_order_book(&repository, &market);
+    order_book.set_atomic_placement(true);
+
+    let ask = limit_order(&seller_id, &market, OrderSide::Sell, "10", "1");
+    order_book.add_order(ask.clone()).unwrap();
+    assert_eq!(
+        repository
+            .get_order(&ask.id)
+            .unwrap()
+            .unwrap()
+            .get_status()
+            .unwrap(),
+        OrderStatus::Open
+    );
+    let applied_before = repository.get_applied_sequence(&market.id).unwrap();
+
+    let buy = limit_order(&buyer_id, &market, OrderSide::Buy, "10", "2");
+    let trades = order_book.add_order(buy.clone()).unwrap();
+    assert_eq!(trades.len(), 1);
+
+    // The order is journaled with its fill, and both are written in one go
+    let events = repository
+        .get_engine_events(&market.id, applied_before)
+        .unwrap();
+    let types: Vec<_> = events.iter().map(|e| e.event_type.as_str()).collect();
+    assert_eq!(
+        types,
+        [
+            EngineEventType::OrderAccepted.as_str(),
+            EngineEventType::TradeExecuted.as_str(),
+        ]
+    );
+    assert_eq!(events[0].create_time, events[1].create_time);
+
+    // The rest of the buy locks its funds and rests
+    let order = repository.get_order(&buy.id).unwrap().unwrap();
+    assert_eq!(order.get_status().unwrap(), OrderStatus::PartiallyFilled);
+    assert_eq!(order_book.bids_len(), 1);
+    let buyer_quote = repository
+        .get_wallet(&buyer_id, &market.quote_asset)
+        .unwrap()
+        .unwrap();
+    assert_eq!(buyer_quote.available, decimal("980"));
+    assert_eq!(buyer_quote.locked, decimal("10"));
+}
+
+#[test]
+fn test_atomic_placement_stores_nothing_of_a_refused_order() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let funds = [
+        (market.base_asset.as_str(), "100"),
+        (market.quote_asset.as_str(), "1000"),
+    ];
+    let buyer_id = create_funded_user(&repository, &funds);
+    let mut order_book = create_test_order_book(&repository, &market);
+    order_book.set_atomic_placement(true);
+
+    // A trade with itself, refused at settlement
+    let own_ask = limit_order(&buyer_id, &market, OrderSide::Sell, "10", "1");
+    order_book.add_order(own_ask.clone()).unwrap();
+    let buy = limit_order(&buyer_id, &market, OrderSide::Buy, "10", "1");
+    assert!(order_book.add_order(buy.clone()).is_err());
+
+    // Neither the order nor the funds it would have locked made it in
+    assert!(repository.get_order(&buy.id).is_err());
+    let buyer_quote = repository
+        .get_wallet(&buyer_id, &market.quote_asset)
+        .unwrap()
+        .unwrap();
+    assert_eq!(buyer_quote.available, decimal("1000"));
+    assert_eq!(buyer_quote.locked, decimal("0"));
+    assert_eq!(order_book.asks_len(), 1);
+    assert_eq!(order_book.bids_len(), 0);
+}
@@ -0,0 +1,83 @@
+use database::provider::{EngineEventDatabaseReader, OrderDatabaseReader};
+
+use crate::simulator::scenario::{journal_events, parse_scenario};
+use crate::simulator::Simulator;
+
+const MARKET_ID: &str = "SIM";
+
+const SCENARIO: &str = "
+# Two asks, the better one placed last
+add,s1,seller,SELL,LIMIT,101,2
+add,s2,seller,SELL,LIMIT,100,1
+add,b1,buyer,BUY,LIMIT,101,2.5
+amend,s1,,0.2
+add,b2,buyer,BUY,MARKET,0,1,500
+add,b3,buyer,BUY,LIMIT,99,1
+cancel,b3
+add,s3,seller,SELL,LIMIT,102,1
+cancel_all
+";
+
+fn simulator() -> Simulator {
+    Simulator::new(MARKET_ID, "BASE", "QUOTE")
+}
+
+#[test]
+fn test_scenario_gives_the_same_trade_log_every_run() {
+    let events = parse_scenario(MARKET_ID, SCENARIO).unwrap();
+    let report = simulator().run(events.clone());
+
+    assert_eq!(report.events, 9);
+    assert!(report.refused.is_empty(), "{:?}", report.refused);
+    assert_eq!(
+        report.trade_log(),
+        "price,base_amount,quote_amount,buyer_order_id,seller_order_id,taker_side,buyer_fee,seller_fee\n\
+         101,1,101,b1,s2,BUY,0,0\n\
+         101,1.5,151.5,b1,s1,BUY,0,0\n\
+         101,0.2,20.2,b2,s1,BUY,0,0\n"
+    );
+    assert_eq!(simulator().run(events).trade_log(), report.trade_log());
+}
+
+#[test]
+fn test_replayed_journal_gives_the_same_trade_log() {
+    let mut recorded = simulator();
+    let report = recorded.run(parse_scenario(MARKET_ID, SCENARIO).unwrap());
+    assert!(recorded
+        .persister()
+        .get_active_orders(MARKET_ID)
+        .unwrap()
+        .is_empty());
+
+    let journal = recorded
+        .persister()
+        .get_engine_events(MARKET_ID, 0)
+        .unwrap();
+    let replayed = simulator().run(journal_events(&journal).unwrap());
+    assert!(replayed.refused.is_empty(), "{:?}", replayed.refused);
+    assert_eq!(replayed.trade_log(), report.trade_log());
+}
+
+#[test]
+fn test_refused_events_do_not_stop_the_run() {
+    let events = parse_scenario(
+        MARKET_ID,
+        "cancel,missing\nadd,s1,seller,SELL,LIMIT,10,1\nadd,b1,buyer,BUY,LIMIT,10,1",
+    )
+    .unwrap();
+    let report = simulator().run(events);
+    assert_eq!(report.refused.len(), 1);
+    assert_eq!(report.refused[0].0, 0);
+    assert_eq!(report.trades.len(), 1);
+}
+
+#[test]
+fn test_invalid_scenario_line_is_reported() {
+    let error = parse_scenario(
+        MARKET_ID,
+        "add,s1,seller,SELL,LIMIT,10,1\nadd,b1,buyer,HOLD,LIMIT,10,1",
+    )
+    .unwrap_err();
+    assert!(format!("{:#}", error).contains("line 2"), "{:#}", error);
+    assert!(parse_scenario(MARKET_ID, "fill,s1").is_err());
+}
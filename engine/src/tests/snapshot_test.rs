@@ -0,0 +1,67 @@
+use bigdecimal::BigDecimal;
+use database::models::models::CancelReason;
+use database::provider::{OrderBookSnapshotDatabaseReader, OrderDatabaseWriter};
+use database::tests::test_db::{create_funded_user, create_test_market, isolated_test_repository};
+
+use crate::models::trade_order::OrderSide;
+use crate::tests::test_models::{create_test_order_book, limit_order};
+
+#[test]
+fn test_snapshot_restores_queue_order_and_drops_closed_orders() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let funds = [
+        (market.base_asset.as_str(), "100"),
+        (market.quote_asset.as_str(), "1000"),
+    ];
+    let first_id = create_funded_user(&repository, &funds);
+    let second_id = create_funded_user(&repository, &funds);
+    let seller_id = create_funded_user(&repository, &funds);
+
+    let mut order_book = create_test_order_book(&repository, &market);
+    assert!(repository
+        .get_order_book_snapshot(&market.id)
+        .unwrap()
+        .is_none());
+    let first = limit_order(&first_id, &market, OrderSide::Buy, "10", "1");
+    let second = limit_order(&second_id, &market, OrderSide::Buy, "10", "1");
+    let third = limit_order(&second_id, &market, OrderSide::Buy, "10", "1");
+    order_book.add_order(first.clone()).unwrap();
+    order_book.add_order(second.clone()).unwrap();
+    order_book.add_order(third.clone()).unwrap();
+    // Growing the first bid sends it behind the others
+    order_book
+        .amend_order(first.id.clone(), None, Some(BigDecimal::from(2)))
+        .unwrap();
+    let sequence = order_book.write_snapshot().unwrap();
+    let snapshot = repository
+        .get_order_book_snapshot(&market.id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(snapshot.applied_sequence, sequence);
+
+    // Changes after the snapshot come from the orders table
+    repository
+        .cancel_order(&third.id, CancelReason::UserCanceled)
+        .unwrap();
+    let late = limit_order(&first_id, &market, OrderSide::Buy, "10", "1");
+    order_book.add_order(late.clone()).unwrap();
+    drop(order_book);
+
+    let mut order_book = create_test_order_book(&repository, &market);
+    assert_eq!(order_book.bids_len(), 3);
+    assert!(order_book.get_order_by_id(third.id.clone()).is_err());
+
+    let sell = limit_order(&seller_id, &market, OrderSide::Sell, "10", "4");
+    let trades = order_book.add_order(sell).unwrap();
+    let buyers: Vec<&str> = trades
+        .iter()
+        .map(|trade| trade.buyer_order_id.as_str())
+        .collect();
+    assert_eq!(
+        buyers,
+        vec![second.id.as_str(), first.id.as_str(), late.id.as_str()]
+    );
+}
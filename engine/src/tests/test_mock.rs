@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use bigdecimal::BigDecimal;
+use database::mock::mock_persister::MockPersister;
+use database::models::models::{Market, OrderStatus};
+use database::provider::{FeeTreasuryDatabaseReader, OrderDatabaseReader, WalletDatabaseReader};
+
+use crate::order_book::OrderBook;
+
+/// An order book of `market` kept by `persister` instead of the database
+pub fn create_mock_order_book(
+    persister: &Arc<MockPersister>,
+    market: &Market,
+) -> OrderBook<MockPersister> {
+    OrderBook::new(
+        persister.clone(),
+        market.base_asset.clone(),
+        market.id.clone(),
+        market.quote_asset.clone(),
+    )
+    .unwrap()
+}
+
+/// Asserts the best bid of `order_book` is below its best ask, as matching leaves the book
+pub fn assert_book_not_crossed(order_book: &OrderBook<MockPersister>) {
+    let depth = order_book.depth(1, None);
+    if let (Some((bid, _)), Some((ask, _))) = (depth.bids.first(), depth.asks.first()) {
+        assert!(
+            bid < ask,
+            "Book crossed: best bid {} >= best ask {}",
+            bid,
+            ask
+        );
+    }
+}
+
+/// Asserts what the mock holds of `market` adds up:
+/// - no wallet of `user_ids` goes negative, and each locks what its open orders may spend
+/// - every order accepted has its base amount split between filled and remaining
+/// - each fee treasury holds the fees of every trade, and no funds appeared or vanished
+pub fn assert_funds_consistent(persister: &MockPersister, market: &Market, user_ids: &[String]) {
+    let zero = BigDecimal::from(0);
+    let mut assets_held = HashMap::new();
+    for user_id in user_ids {
+        let open_orders = persister.get_user_active_orders(user_id).unwrap();
+        for (asset, side) in [(&market.base_asset, "SELL"), (&market.quote_asset, "BUY")] {
+            let wallet = persister.get_wallet(user_id, asset).unwrap().unwrap();
+            assert!(wallet.available >= zero, "Negative balance {:?}", wallet);
+            assert!(
+                wallet.locked >= zero,
+                "Negative locked balance {:?}",
+                wallet
+            );
+
+            let locked: BigDecimal = open_orders
+                .iter()
+                .filter(|order| order.side == side)
+                .map(|order| match side {
+                    "BUY" => order.remained_quote.clone(),
+                    _ => order.remained_base.clone(),
+                })
+                .sum();
+            assert_eq!(wallet.locked, locked, "Locked {} of {}", asset, user_id);
+            *assets_held.entry(asset).or_insert_with(|| zero.clone()) +=
+                &wallet.available + &wallet.locked - &wallet.total_deposited;
+        }
+    }
+
+    for order in persister.orders() {
+        if order.market_id != market.id || order.status == OrderStatus::Rejected.as_str() {
+            continue;
+        }
+        assert_eq!(
+            &order.filled_base + &order.remained_base,
+            order.base_amount,
+            "Amounts of order {:?}",
+            order
+        );
+    }
+
+    let trades = persister.trades();
+    for (asset, fees) in [
+        (
+            &market.base_asset,
+            trades
+                .iter()
+                .map(|trade| &trade.buyer_fee)
+                .sum::<BigDecimal>(),
+        ),
+        (
+            &market.quote_asset,
+            trades
+                .iter()
+                .map(|trade| &trade.seller_fee)
+                .sum::<BigDecimal>(),
+        ),
+    ] {
+        let treasury = persister
+            .get_fee_treasury(&market.id, asset)
+            .unwrap()
+            .unwrap();
+        assert_eq!(treasury.collected_amount, fees, "{} fee treasury", asset);
+        assert_eq!(
+            &assets_held[asset] + &treasury.collected_amount,
+            zero,
+            "{} created or destroyed",
+            asset
+        );
+    }
+}
@@ -1,10 +1,45 @@
 use std::str::FromStr;
 
+use std::sync::Arc;
+
 use bigdecimal::BigDecimal;
 use common::utils::{self, get_uuid_string};
-use database::models::models::{OrderStatus, TimeInForce};
+use database::models::models::{Market, OrderStatus, TimeInForce};
+use database::repository::Repository;
 
 use crate::models::trade_order::{OrderSide, OrderType, TradeOrder};
+use crate::order_book::OrderBook;
+
+/// Parses a decimal literal of a test
+pub fn decimal(value: &str) -> BigDecimal {
+    BigDecimal::from_str(value).unwrap()
+}
+
+/// An order book of `market` persisting to `repository`
+pub fn create_test_order_book(repository: &Repository, market: &Market) -> OrderBook<Repository> {
+    OrderBook::new(
+        Arc::new(repository.clone()),
+        market.base_asset.clone(),
+        market.id.clone(),
+        market.quote_asset.clone(),
+    )
+    .unwrap()
+}
+
+/// A fee-free limit order of `user_id` on `market` for `base` at `price`
+pub fn limit_order(
+    user_id: &str,
+    market: &Market,
+    side: OrderSide,
+    price: &str,
+    base: &str,
+) -> TradeOrder {
+    let quote = (decimal(price) * decimal(base)).to_string();
+    TradeOrder {
+        user_id: user_id.to_string(),
+        ..create_order(side, price, base, &quote, OrderType::Limit, &market.id)
+    }
+}
 
 pub fn create_order(
     side: OrderSide,
@@ -39,9 +74,11 @@ pub fn create_order(
         filled_fee: BigDecimal::from(0),
         update_time: utils::get_utc_now_millis(),
         client_order_id: None,
-        expires_at: Some(utils::get_utc_now_millis() + 1000 * 60 * 60 * 24),
+        expires_at: None,
         post_only: Some(false),
+        display_amount: None,
         time_in_force: Some(TimeInForce::GTC),
         status: OrderStatus::Open,
+        priority: 0,
     }
 }
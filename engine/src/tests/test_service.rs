@@ -0,0 +1,83 @@
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bigdecimal::BigDecimal;
+use common::maintenance::MaintenanceMode;
+use common::rate_limit::RateLimits;
+use database::models::models::Market;
+use database::repository::Repository;
+use tokio::sync::RwLock;
+
+use crate::config::app_config::{FeeDefaults, DEFAULT_MAX_RESPONSE_FILLS};
+use crate::fee::fee_service::FeeService;
+use crate::grpc::service::SpotServiceImpl;
+use crate::grpc::spot::AddOrderRequest;
+use crate::market::market_manager::MarketManager;
+use crate::reconciliation::reconciler::Reconciler;
+use crate::validation::AssetRegistry;
+use crate::wallet::wallet_service::WalletService;
+
+/// Builds a `SpotServiceImpl` over `repository`, loading every market it already holds, and
+/// returns once the markets have recovered their open orders.
+pub fn create_test_service(repository: Repository) -> SpotServiceImpl<Repository> {
+    let service = create_recovering_test_service(repository);
+    let deadline = Instant::now() + Duration::from_secs(30);
+    while service
+        .market_manager
+        .try_read()
+        .unwrap()
+        .is_recovering()
+        .unwrap()
+    {
+        assert!(Instant::now() < deadline, "Markets did not finish recovery");
+        thread::sleep(Duration::from_millis(5));
+    }
+    service
+}
+
+/// Same as [`create_test_service`], without waiting for the markets to recover.
+pub fn create_recovering_test_service(repository: Repository) -> SpotServiceImpl<Repository> {
+    let repository = Arc::new(repository);
+    SpotServiceImpl {
+        market_manager: Arc::new(RwLock::new(MarketManager::new(repository.clone()))),
+        wallet_service: Arc::new(WalletService::new(repository.clone())),
+        fee_service: Arc::new(FeeService::new(repository.clone())),
+        maintenance: MaintenanceMode::default(),
+        max_response_fills: DEFAULT_MAX_RESPONSE_FILLS,
+        asset_registry: AssetRegistry::unrestricted(),
+        fee_defaults: FeeDefaults::default(),
+        audit_orders: true,
+        reconciler: Arc::new(Reconciler::new(repository)),
+        rate_limits: RateLimits::default(),
+    }
+}
+
+/// Builds a limit `AddOrderRequest` for `market` with the market's default fees.
+pub fn add_order_request(
+    market: &Market,
+    user_id: &str,
+    side: &str,
+    price: &str,
+    base: &str,
+) -> AddOrderRequest {
+    let quote = price.parse::<BigDecimal>().unwrap() * base.parse::<BigDecimal>().unwrap();
+    AddOrderRequest {
+        market_id: market.id.clone(),
+        order_type: "LIMIT".to_string(),
+        side: side.to_string(),
+        user_id: user_id.to_string(),
+        price: price.to_string(),
+        base_amount: base.to_string(),
+        quote_amount: quote.to_string(),
+        maker_fee: market.default_maker_fee.to_string(),
+        taker_fee: market.default_taker_fee.to_string(),
+        test_order: false,
+        time_in_force: String::new(),
+        post_only: false,
+        display_amount: String::new(),
+        expires_at: 0,
+        client_order_id: String::new(),
+        idempotency_key: String::new(),
+    }
+}
@@ -0,0 +1,86 @@
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use database::repository::Repository;
+use database::tests::test_db::{create_funded_user, create_test_market, isolated_test_repository};
+use serde_json::Value;
+use tracing::Level;
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::models::trade_order::OrderSide;
+use crate::order_book::OrderBook;
+use crate::tests::test_models::limit_order;
+
+/// Collects what a subscriber writes
+#[derive(Clone, Default)]
+struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+impl io::Write for CapturedLogs {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for CapturedLogs {
+    type Writer = Self;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+#[test]
+fn test_order_events_carry_the_order_span() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let funds = [
+        (market.base_asset.as_str(), "10"),
+        (market.quote_asset.as_str(), "1000"),
+    ];
+    let seller_id = create_funded_user(&repository, &funds);
+    let buyer_id = create_funded_user(&repository, &funds);
+    let mut order_book: OrderBook<Repository> = OrderBook::new(
+        Arc::new(repository.clone()),
+        market.base_asset.clone(),
+        market.id.clone(),
+        market.quote_asset.clone(),
+    )
+    .unwrap();
+    order_book
+        .add_order(limit_order(&seller_id, &market, OrderSide::Sell, "10", "1"))
+        .unwrap();
+
+    let logs = CapturedLogs::default();
+    let subscriber = tracing_subscriber::fmt()
+        .json()
+        .with_current_span(true)
+        .with_max_level(Level::DEBUG)
+        .with_writer(logs.clone())
+        .finish();
+    let buy = limit_order(&buyer_id, &market, OrderSide::Buy, "10", "1");
+    tracing::subscriber::with_default(subscriber, || {
+        order_book.add_order(buy.clone()).unwrap();
+    });
+
+    let output = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+    let events: Vec<Value> = output
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let trade = events
+        .iter()
+        .find(|event| event["fields"]["message"] == "New trade matched")
+        .unwrap_or_else(|| panic!("no trade event in {}", output));
+    let span = &trade["span"];
+    assert_eq!(span["name"], "add_order");
+    assert_eq!(span["order_id"], buy.id.as_str());
+    assert_eq!(span["market_id"], market.id.as_str());
+    assert_eq!(span["user_id"], buyer_id.as_str());
+}
@@ -0,0 +1,92 @@
+use database::tests::test_db::{create_funded_user, create_test_market, isolated_test_repository};
+use futures::StreamExt;
+use std::time::Duration;
+use tokio::time::timeout;
+use tonic::Request;
+
+use crate::grpc::admin::admin_service_server::AdminService;
+use crate::grpc::service::SpotServiceImpl;
+use crate::grpc::spot::spot_service_server::SpotService;
+use crate::grpc::spot::{StartMarketRequest, SubscribeTradesRequest, TradeUpdate};
+use crate::tests::test_service::{add_order_request, create_test_service};
+use database::models::models::Market;
+use database::repository::Repository;
+
+async fn trade(
+    service: &SpotServiceImpl<Repository>,
+    market: &Market,
+    buyer_id: &str,
+    seller_id: &str,
+    price: &str,
+) -> String {
+    service
+        .add_order(Request::new(add_order_request(
+            market, seller_id, "SELL", price, "1",
+        )))
+        .await
+        .unwrap();
+    service
+        .add_order(Request::new(add_order_request(
+            market, buyer_id, "BUY", price, "1",
+        )))
+        .await
+        .unwrap()
+        .into_inner()
+        .trades[0]
+        .id
+        .clone()
+}
+
+#[tokio::test]
+async fn test_trade_stream_replays_from_a_sequence_then_goes_live() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let funds = [
+        (market.base_asset.as_str(), "10"),
+        (market.quote_asset.as_str(), "1000"),
+    ];
+    let buyer_id = create_funded_user(&repository, &funds);
+    let seller_id = create_funded_user(&repository, &funds);
+    let service = create_test_service(repository.clone());
+    service
+        .admin_service()
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+
+    trade(&service, &market, &buyer_id, &seller_id, "10").await;
+    let second = trade(&service, &market, &buyer_id, &seller_id, "11").await;
+
+    let subscribe = |since_sequence| {
+        service.subscribe_trades(Request::new(SubscribeTradesRequest {
+            market_id: market.id.clone(),
+            since_sequence,
+        }))
+    };
+    let mut replaying = subscribe(Some(1)).await.unwrap().into_inner();
+    let mut live_only = subscribe(None).await.unwrap().into_inner();
+
+    let third = trade(&service, &market, &buyer_id, &seller_id, "12").await;
+
+    let mut received = Vec::new();
+    for _ in 0..2 {
+        let update: TradeUpdate = timeout(Duration::from_secs(5), replaying.next())
+            .await
+            .expect("no trade update")
+            .unwrap()
+            .unwrap();
+        received.push((update.sequence, update.trade.unwrap().id));
+    }
+    assert_eq!(received, [(2, second), (3, third.clone())]);
+
+    let update = timeout(Duration::from_secs(5), live_only.next())
+        .await
+        .expect("no trade update")
+        .unwrap()
+        .unwrap();
+    assert_eq!((update.sequence, update.trade.unwrap().id), (3, third));
+}
@@ -0,0 +1,216 @@
+use bigdecimal::BigDecimal;
+use database::provider::WalletDatabaseReader;
+use database::tests::test_db::{
+    create_funded_user, create_test_market, execute_test_trade, isolated_test_repository,
+};
+use tonic::{Code, Request};
+
+use crate::grpc::admin::admin_service_server::AdminService;
+use crate::grpc::spot::spot_service_server::SpotService;
+use crate::grpc::spot::{
+    ApproveWithdrawalRequest, CompleteDepositRequest, CompleteWithdrawalRequest,
+    RequestWithdrawalRequest, WithdrawFromTreasuryRequest,
+};
+use crate::tests::test_service::create_test_service;
+
+fn deposit_request(external_id: &str) -> CompleteDepositRequest {
+    CompleteDepositRequest {
+        user_id: "alice".to_string(),
+        asset: "BTC".to_string(),
+        amount: "10".to_string(),
+        external_id: external_id.to_string(),
+    }
+}
+
+fn withdrawal_request(amount: &str) -> RequestWithdrawalRequest {
+    RequestWithdrawalRequest {
+        user_id: "alice".to_string(),
+        asset: "BTC".to_string(),
+        amount: amount.to_string(),
+        address: "bc1-alice".to_string(),
+    }
+}
+
+#[tokio::test]
+async fn test_withdrawal_workflow_moves_funds_through_reserved() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let service = create_test_service(repository.clone());
+
+    let deposit = service
+        .complete_deposit(Request::new(deposit_request("tx-in-1")))
+        .await
+        .unwrap()
+        .into_inner()
+        .transfer
+        .unwrap();
+    assert_eq!(deposit.status, "COMPLETED");
+    // A redelivered deposit is not credited twice
+    service
+        .complete_deposit(Request::new(deposit_request("tx-in-1")))
+        .await
+        .unwrap();
+
+    let withdrawal = service
+        .request_withdrawal(Request::new(withdrawal_request("4")))
+        .await
+        .unwrap()
+        .into_inner()
+        .transfer
+        .unwrap();
+    assert_eq!(withdrawal.status, "PENDING");
+    let wallet = repository.get_wallet("alice", "BTC").unwrap().unwrap();
+    assert_eq!(wallet.available, BigDecimal::from(6));
+    assert_eq!(wallet.reserved, BigDecimal::from(4));
+
+    let approved = service
+        .approve_withdrawal(Request::new(ApproveWithdrawalRequest {
+            transfer_id: withdrawal.id.clone(),
+            approved: true,
+            reject_reason: String::new(),
+        }))
+        .await
+        .unwrap()
+        .into_inner()
+        .transfer
+        .unwrap();
+    assert_eq!(approved.status, "APPROVED");
+
+    let completed = service
+        .complete_withdrawal(Request::new(CompleteWithdrawalRequest {
+            transfer_id: withdrawal.id.clone(),
+            external_id: "tx-out-1".to_string(),
+        }))
+        .await
+        .unwrap()
+        .into_inner()
+        .transfer
+        .unwrap();
+    assert_eq!(completed.status, "COMPLETED");
+
+    let wallet = repository.get_wallet("alice", "BTC").unwrap().unwrap();
+    assert_eq!(wallet.available, BigDecimal::from(6));
+    assert_eq!(wallet.reserved, BigDecimal::from(0));
+    assert_eq!(wallet.total_deposited, BigDecimal::from(10));
+    assert_eq!(wallet.total_withdrawn, BigDecimal::from(4));
+}
+
+#[tokio::test]
+async fn test_withdrawal_errors_map_to_status_codes() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let service = create_test_service(repository);
+    service
+        .complete_deposit(Request::new(deposit_request("tx-in-1")))
+        .await
+        .unwrap();
+
+    let status = service
+        .request_withdrawal(Request::new(withdrawal_request("11")))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+
+    let status = service
+        .approve_withdrawal(Request::new(ApproveWithdrawalRequest {
+            transfer_id: "missing".to_string(),
+            approved: true,
+            reject_reason: String::new(),
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
+
+    let withdrawal = service
+        .request_withdrawal(Request::new(withdrawal_request("3")))
+        .await
+        .unwrap()
+        .into_inner()
+        .transfer
+        .unwrap();
+    let status = service
+        .approve_withdrawal(Request::new(ApproveWithdrawalRequest {
+            transfer_id: withdrawal.id.clone(),
+            approved: false,
+            reject_reason: String::new(),
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    let rejected = service
+        .approve_withdrawal(Request::new(ApproveWithdrawalRequest {
+            transfer_id: withdrawal.id.clone(),
+            approved: false,
+            reject_reason: "manual review failed".to_string(),
+        }))
+        .await
+        .unwrap()
+        .into_inner()
+        .transfer
+        .unwrap();
+    assert_eq!(rejected.status, "REJECTED");
+
+    // Only approved withdrawals can be completed
+    let status = service
+        .complete_withdrawal(Request::new(CompleteWithdrawalRequest {
+            transfer_id: withdrawal.id,
+            external_id: "tx-out-1".to_string(),
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+}
+
+#[tokio::test]
+async fn test_treasury_withdrawal_takes_collected_fees_only() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let buyer_id = create_funded_user(&repository, &[(&market.quote_asset, "1000")]);
+    let seller_id = create_funded_user(&repository, &[(&market.base_asset, "10")]);
+    let trade = execute_test_trade(&repository, &market, &buyer_id, &seller_id, "100", "2");
+    let service = create_test_service(repository);
+    let request = |market_id: &str, amount: String, destination: &str| {
+        Request::new(WithdrawFromTreasuryRequest {
+            market_id: market_id.to_string(),
+            asset: market.quote_asset.clone(),
+            amount,
+            destination: destination.to_string(),
+        })
+    };
+
+    let status = service
+        .admin_service()
+        .withdraw_from_treasury(request(&market.id, trade.seller_fee.to_string(), ""))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+    let status = service
+        .admin_service()
+        .withdraw_from_treasury(request("missing", trade.seller_fee.to_string(), "ops"))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
+    let status = service
+        .admin_service()
+        .withdraw_from_treasury(request(
+            &market.id,
+            (&trade.seller_fee + BigDecimal::from(1)).to_string(),
+            "ops",
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+
+    let withdrawal = service
+        .admin_service()
+        .withdraw_from_treasury(request(&market.id, trade.seller_fee.to_string(), "ops"))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(withdrawal.collected_amount.parse::<f64>().unwrap(), 0.0);
+}
@@ -0,0 +1,107 @@
+use database::tests::test_db::{create_funded_user, create_test_market, isolated_test_repository};
+use futures::{Stream, StreamExt};
+use std::time::Duration;
+use tokio::time::timeout;
+use tonic::{Request, Status};
+
+use crate::grpc::admin::admin_service_server::AdminService;
+use crate::grpc::spot::spot_service_server::SpotService;
+use crate::grpc::spot::{
+    CancelOrderRequest, ProtoUserEvent, StartMarketRequest, SubscribeUserEventsRequest,
+};
+use crate::tests::test_service::{add_order_request, create_test_service};
+
+async fn next_event(
+    events: &mut (impl Stream<Item = Result<ProtoUserEvent, Status>> + Unpin),
+) -> ProtoUserEvent {
+    timeout(Duration::from_secs(5), events.next())
+        .await
+        .expect("no user event")
+        .unwrap()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_user_events_follow_each_users_orders() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let market = create_test_market(&repository);
+    let funds = [
+        (market.base_asset.as_str(), "10"),
+        (market.quote_asset.as_str(), "1000"),
+    ];
+    let buyer_id = create_funded_user(&repository, &funds);
+    let seller_id = create_funded_user(&repository, &funds);
+    let service = create_test_service(repository.clone());
+    service
+        .admin_service()
+        .start_market(Request::new(StartMarketRequest {
+            market_id: market.id.clone(),
+        }))
+        .await
+        .unwrap();
+
+    let subscribe = |user_id: &str| {
+        service.subscribe_user_events(Request::new(SubscribeUserEventsRequest {
+            user_id: user_id.to_string(),
+        }))
+    };
+    let mut buyer_events = subscribe(&buyer_id).await.unwrap().into_inner();
+    let mut seller_events = subscribe(&seller_id).await.unwrap().into_inner();
+
+    let sell = service
+        .add_order(Request::new(add_order_request(
+            &market, &seller_id, "SELL", "10", "2",
+        )))
+        .await
+        .unwrap()
+        .into_inner();
+    service
+        .add_order(Request::new(add_order_request(
+            &market, &buyer_id, "BUY", "10", "1",
+        )))
+        .await
+        .unwrap();
+    service
+        .cancel_order(Request::new(CancelOrderRequest {
+            market_id: market.id.clone(),
+            order_id: sell.order_id.clone(),
+            ..Default::default()
+        }))
+        .await
+        .unwrap();
+    let rejected = service
+        .add_order(Request::new(add_order_request(
+            &market, &buyer_id, "BUY", "-1", "1",
+        )))
+        .await;
+    assert!(rejected.is_err());
+
+    let mut seller_received = Vec::new();
+    for _ in 0..3 {
+        let event = next_event(&mut seller_events).await;
+        assert_eq!(event.user_id, seller_id);
+        assert_eq!(event.order_id, sell.order_id);
+        seller_received.push((event.event_type, event.remained_base));
+    }
+    assert_eq!(
+        seller_received,
+        [
+            ("ACCEPTED".to_string(), "2".to_string()),
+            ("PARTIALLY_FILLED".to_string(), "1".to_string()),
+            ("CANCELED".to_string(), "1".to_string()),
+        ]
+    );
+
+    let accepted = next_event(&mut buyer_events).await;
+    assert_eq!(accepted.event_type, "ACCEPTED");
+    let filled = next_event(&mut buyer_events).await;
+    assert_eq!(filled.event_type, "FILLED");
+    assert_eq!(filled.order_id, accepted.order_id);
+    assert_eq!(filled.trade.unwrap().seller_order_id, sell.order_id);
+    let rejected = next_event(&mut buyer_events).await;
+    assert_eq!(rejected.event_type, "REJECTED");
+    assert!(rejected.order_id.is_empty());
+    assert!(!rejected.reason.is_empty());
+}
@@ -0,0 +1,70 @@
+use bigdecimal::BigDecimal;
+use common::utils::normalize_user_id;
+use database::provider::WalletDatabaseReader;
+use database::tests::test_db::isolated_test_repository;
+use tonic::{Code, Request};
+
+use crate::grpc::spot::spot_service_server::SpotService;
+use crate::grpc::spot::{CompleteDepositRequest, GetBalanceRequest};
+use crate::tests::test_service::create_test_service;
+
+fn deposit_request(user_id: &str, amount: &str, external_id: &str) -> CompleteDepositRequest {
+    CompleteDepositRequest {
+        user_id: user_id.to_string(),
+        asset: "BTC".to_string(),
+        amount: amount.to_string(),
+        external_id: external_id.to_string(),
+    }
+}
+
+#[tokio::test]
+async fn test_numeric_and_string_user_ids_are_stored_consistently() {
+    let Some(repository) = isolated_test_repository() else {
+        return;
+    };
+    let service = create_test_service(repository.clone());
+
+    // A numeric id, whether padded or passed as a number, names a single wallet
+    let response = service
+        .complete_deposit(Request::new(deposit_request("00042", "5", "tx-1")))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(response.transfer.unwrap().user_id, "42");
+    service
+        .complete_deposit(Request::new(deposit_request(
+            &normalize_user_id(42u64).unwrap(),
+            "3",
+            "tx-2",
+        )))
+        .await
+        .unwrap();
+
+    let wallet = repository.get_wallet("42", "BTC").unwrap().unwrap();
+    assert_eq!(wallet.available, BigDecimal::from(8));
+
+    let balance = service
+        .get_balance(Request::new(GetBalanceRequest {
+            user_id: " 042 ".to_string(),
+            asset: "BTC".to_string(),
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(balance.user_id, "42");
+    assert_eq!(balance.amount, "8");
+
+    // String ids are kept as given
+    let response = service
+        .complete_deposit(Request::new(deposit_request("alice_01", "1", "tx-3")))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(response.transfer.unwrap().user_id, "alice_01");
+
+    let status = service
+        .complete_deposit(Request::new(deposit_request("bob smith", "1", "tx-4")))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+}
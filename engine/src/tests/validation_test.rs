@@ -0,0 +1,163 @@
+use common::utils::parse_decimal;
+
+use crate::grpc::spot::AddOrderRequest;
+use crate::tests::test_models::decimal;
+use crate::validation::{validate_add_order_request, validate_quote_amount, MarketConstraintError};
+
+#[test]
+fn test_quote_check_on_large_amounts_uses_the_market_precision() {
+    let price = decimal("98765.4321");
+    let base_amount = decimal("12345.6789");
+    let check = |quote: &str, precision| {
+        validate_quote_amount(&price, &base_amount, &decimal(quote), precision)
+    };
+
+    // price * base_amount = 1219326311.12635269, off by more than a fixed 0.0000001 epsilon
+    // once rounded to the 4 decimals of the market
+    assert!(check("1219326311.12635269", 4).is_ok());
+    assert!(check("1219326311.1264", 4).is_ok());
+    assert!(check("1219326311.1263", 4).is_ok());
+
+    // Sending fewer decimals does not widen the tolerance
+    assert!(check("1219326311.13", 4).is_err());
+    assert!(check("1219326311", 4).is_err());
+    let error = check("1219326311.15", 4).unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<MarketConstraintError>(),
+        Some(MarketConstraintError::QuoteMismatch { .. })
+    ));
+
+    // A market counting quotes in cents takes the quote a client rounded to cents
+    assert!(check("1219326311.13", 2).is_ok());
+    assert!(check("1219326311.15", 2).is_err());
+}
+
+#[test]
+fn test_digit_separators_are_rejected_with_a_clear_error() {
+    for (input, offending) in [
+        ("1_000", "'_'"),
+        ("1,000.5", "','"),
+        ("1,000_000", "',', '_'"),
+        ("1 000", "' '"),
+    ] {
+        let message = parse_decimal(input, "base_amount", false)
+            .unwrap_err()
+            .to_string();
+        assert!(message.contains("Invalid number format for base_amount"));
+        assert!(message.contains(offending), "{}", message);
+    }
+
+    // The order request surfaces the same error instead of a generic parse failure
+    let request = AddOrderRequest {
+        market_id: "BTC-USD".to_string(),
+        order_type: "LIMIT".to_string(),
+        side: "BUY".to_string(),
+        user_id: "42".to_string(),
+        price: "50,000".to_string(),
+        base_amount: "1".to_string(),
+        ..Default::default()
+    };
+    let message = validate_add_order_request(&request)
+        .unwrap_err()
+        .to_string();
+    assert!(message.contains("Invalid number format for price"));
+}
+
+#[test]
+fn test_digit_separators_are_stripped_when_allowed() {
+    assert_eq!(
+        parse_decimal("1_000", "amount", true).unwrap(),
+        decimal("1000")
+    );
+    assert_eq!(
+        parse_decimal("1,000,000.25", "amount", true).unwrap(),
+        decimal("1000000.25")
+    );
+    // Stripping separators does not make other characters acceptable
+    assert!(parse_decimal("1,000 USD", "amount", true).is_err());
+}
+
+#[test]
+fn test_time_in_force_must_be_supported() {
+    let request = AddOrderRequest {
+        market_id: "BTC-USD".to_string(),
+        order_type: "LIMIT".to_string(),
+        side: "BUY".to_string(),
+        user_id: "42".to_string(),
+        price: "10".to_string(),
+        base_amount: "1".to_string(),
+        ..Default::default()
+    };
+    for time_in_force in ["", "GTC", "ioc"] {
+        let request = AddOrderRequest {
+            time_in_force: time_in_force.to_string(),
+            ..request.clone()
+        };
+        assert!(
+            validate_add_order_request(&request).is_ok(),
+            "{}",
+            time_in_force
+        );
+    }
+
+    for (time_in_force, message) in [
+        ("FOK", "Unsupported time in force: FOK"),
+        ("DAY", "Unknown time in force: DAY"),
+    ] {
+        let request = AddOrderRequest {
+            time_in_force: time_in_force.to_string(),
+            ..request.clone()
+        };
+        let error = validate_add_order_request(&request).unwrap_err();
+        assert_eq!(error.to_string(), message);
+    }
+}
+
+#[test]
+fn test_display_amount_needs_a_resting_limit_order() {
+    let request = AddOrderRequest {
+        market_id: "BTC-USD".to_string(),
+        order_type: "LIMIT".to_string(),
+        side: "SELL".to_string(),
+        user_id: "42".to_string(),
+        price: "10".to_string(),
+        base_amount: "5".to_string(),
+        display_amount: "1".to_string(),
+        ..Default::default()
+    };
+    assert!(validate_add_order_request(&request).is_ok());
+
+    for (invalid, message) in [
+        (
+            AddOrderRequest {
+                display_amount: "6".to_string(),
+                ..request.clone()
+            },
+            "Display amount cannot exceed the base amount",
+        ),
+        (
+            AddOrderRequest {
+                display_amount: "0".to_string(),
+                ..request.clone()
+            },
+            "display_amount must be greater than zero",
+        ),
+        (
+            AddOrderRequest {
+                time_in_force: "IOC".to_string(),
+                ..request.clone()
+            },
+            "Display amount is only valid for GTC and GTD limit orders",
+        ),
+        (
+            AddOrderRequest {
+                order_type: "MARKET".to_string(),
+                ..request.clone()
+            },
+            "Display amount is only valid for GTC and GTD limit orders",
+        ),
+    ] {
+        let error = validate_add_order_request(&invalid).unwrap_err();
+        assert_eq!(error.to_string(), message);
+    }
+}
@@ -0,0 +1,33 @@
+use std::collections::HashSet;
+
+/// Assets markets may be created on.
+///
+/// A registry built without assets accepts any asset, which keeps deployments that never
+/// configured one working as before.
+#[derive(Debug, Clone, Default)]
+pub struct AssetRegistry {
+    assets: Option<HashSet<String>>,
+}
+
+impl AssetRegistry {
+    pub fn new<I, S>(assets: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            assets: Some(assets.into_iter().map(Into::into).collect()),
+        }
+    }
+
+    /// Registry that accepts every asset
+    pub fn unrestricted() -> Self {
+        Self::default()
+    }
+
+    pub fn contains(&self, asset: &str) -> bool {
+        self.assets
+            .as_ref()
+            .is_none_or(|assets| assets.contains(asset))
+    }
+}
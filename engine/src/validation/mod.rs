@@ -1,30 +1,103 @@
-use crate::grpc::spot::{AddOrderRequest, CreateMarketRequest};
+use crate::grpc::admin::{
+    DeleteFeeTierRequest, SetFeeTierRequest, SetUserStatusRequest, SweepTreasuryRequest,
+};
+use crate::grpc::helper::parse_time_in_force;
+use crate::grpc::spot::{
+    AddOcoOrderRequest, AddOrderRequest, AmendOrderRequest, ApproveWithdrawalRequest,
+    CancelOrderRequest, CompleteDepositRequest, CompleteWithdrawalRequest, CreateMarketRequest,
+    GetOrderBookDepthRequest, RequestWithdrawalRequest, UpdateMarketRequest,
+    UpdateMarketStatusRequest, WithdrawFromTreasuryRequest,
+};
+use crate::market::MarketParams;
+use crate::models::trade_order::{OrderSide, OrderType, TradeOrder};
 use anyhow::{anyhow, Result};
-use bigdecimal::BigDecimal;
-use common::utils::validate_positive_decimal;
-use std::str::FromStr;
+use bigdecimal::{BigDecimal, RoundingMode};
+use common::error::BitradeError;
+use common::utils::{bigdecimal_from_str, get_utc_now_millis, validate_positive_decimal};
+use database::models::models::{MarketStatus, MarketUpdate, TimeInForce, UserStatus, Wallet};
+
+pub mod asset_registry;
+pub use asset_registry::AssetRegistry;
+
+/// An order breaking one of the trading constraints of its market
+#[derive(Debug, thiserror::Error)]
+pub enum MarketConstraintError {
+    #[error("{field} ({value}) is below the market minimum ({minimum})")]
+    BelowMinimum {
+        field: &'static str,
+        value: BigDecimal,
+        minimum: BigDecimal,
+    },
+
+    #[error("{field} ({value}) has more than {precision} decimal places")]
+    TooManyDecimals {
+        field: &'static str,
+        value: BigDecimal,
+        precision: i32,
+    },
+
+    #[error("Quote amount ({quote_amount}) does not match price * base_amount ({expected})")]
+    QuoteMismatch {
+        quote_amount: BigDecimal,
+        expected: BigDecimal,
+    },
+}
+
+impl From<&MarketConstraintError> for BitradeError {
+    fn from(error: &MarketConstraintError) -> Self {
+        match error {
+            MarketConstraintError::BelowMinimum {
+                field,
+                value,
+                minimum,
+            } => BitradeError::BelowMinimum {
+                field: field.to_string(),
+                value: value.clone(),
+                minimum: minimum.clone(),
+            },
+            MarketConstraintError::TooManyDecimals {
+                field,
+                value,
+                precision,
+            } => BitradeError::InvalidPrecision {
+                field: field.to_string(),
+                value: value.clone(),
+                precision: *precision,
+            },
+            MarketConstraintError::QuoteMismatch { .. } => {
+                BitradeError::InvalidArgument(error.to_string())
+            }
+        }
+    }
+}
+
+/// Most decimal places a market may require of prices and amounts, the scale of their columns
+pub const MAX_MARKET_PRECISION: i32 = 18;
+
+/// Longest client order id accepted, the width of its column
+pub const MAX_CLIENT_ORDER_ID_LEN: usize = 50;
+
+/// Longest idempotency key accepted on an order request
+pub const MAX_IDEMPOTENCY_KEY_LEN: usize = 64;
+
+/// Price levels per side returned by `GetOrderBookDepth` unless asked otherwise
+pub const DEFAULT_DEPTH_LEVELS: usize = 20;
+/// Most price levels per side a `GetOrderBookDepth` call may ask for
+pub const MAX_DEPTH_LEVELS: usize = 500;
+
+/// Most entries a single `AddOrders` or `CancelOrders` call may carry
+pub const MAX_BATCH_SIZE: usize = 100;
 
 pub fn validate_add_order_request(req: &AddOrderRequest) -> Result<()> {
     // Validate price is positive
-    let price = validate_positive_decimal(&req.price, "price")?;
+    validate_positive_decimal(&req.price, "price")?;
 
     // Validate base amount is positive
     let base_amount = validate_positive_decimal(&req.base_amount, "base_amount")?;
 
-    // If quote_amount is provided, validate it equals price * base_amount
+    // A quote_amount is checked against price * base_amount once the market is known
     if !req.quote_amount.is_empty() {
-        let quote_amount = validate_positive_decimal(&req.quote_amount, "quote_amount")?;
-        let calculated_quote = &price * &base_amount;
-
-        // Use a small epsilon for floating-point comparison
-        let epsilon = BigDecimal::from_str("0.0000001").unwrap();
-        if (&calculated_quote - &quote_amount).abs() > epsilon {
-            return Err(anyhow!(
-                "Quote amount ({}) does not match price * base_amount ({})",
-                quote_amount,
-                calculated_quote
-            ));
-        }
+        validate_positive_decimal(&req.quote_amount, "quote_amount")?;
     }
 
     // Validate market ID is not empty
@@ -37,10 +110,258 @@ pub fn validate_add_order_request(req: &AddOrderRequest) -> Result<()> {
         return Err(anyhow!("User ID cannot be empty"));
     }
 
+    let time_in_force = parse_time_in_force(&req.time_in_force)?;
+
+    // Only GTD orders carry an expiry, and it has to leave them time to rest
+    if time_in_force == TimeInForce::GTD {
+        if req.expires_at <= get_utc_now_millis() {
+            return Err(anyhow!("GTD orders need an expires_at in the future"));
+        }
+    } else if req.expires_at != 0 {
+        return Err(anyhow!("expires_at is only valid for GTD orders"));
+    }
+
+    // Market orders always take liquidity
+    if req.post_only
+        && matches!(
+            OrderType::try_from(req.order_type.as_str()),
+            Ok(OrderType::Market)
+        )
+    {
+        return Err(anyhow!("Post-only is only valid for limit orders"));
+    }
+
+    // Only an order that rests in the book has anything to hide
+    if !req.display_amount.is_empty() {
+        let display_amount = validate_positive_decimal(&req.display_amount, "display_amount")?;
+        if display_amount > base_amount {
+            return Err(anyhow!("Display amount cannot exceed the base amount"));
+        }
+        if !matches!(
+            OrderType::try_from(req.order_type.as_str()),
+            Ok(OrderType::Limit)
+        ) || time_in_force == TimeInForce::IOC
+        {
+            return Err(anyhow!(
+                "Display amount is only valid for GTC and GTD limit orders"
+            ));
+        }
+    }
+
+    if !req.client_order_id.is_empty() {
+        validate_client_order_id(&req.client_order_id)?;
+    }
+    if req.idempotency_key.len() > MAX_IDEMPOTENCY_KEY_LEN {
+        return Err(anyhow!(
+            "Idempotency key cannot be longer than {} characters",
+            MAX_IDEMPOTENCY_KEY_LEN
+        ));
+    }
+
+    Ok(())
+}
+
+pub fn validate_client_order_id(client_order_id: &str) -> Result<()> {
+    if client_order_id.len() > MAX_CLIENT_ORDER_ID_LEN {
+        return Err(anyhow!(
+            "Client order ID cannot be longer than {} characters",
+            MAX_CLIENT_ORDER_ID_LEN
+        ));
+    }
+    if !client_order_id.chars().all(|c| c.is_ascii_graphic()) {
+        return Err(anyhow!(
+            "Client order ID may only hold printable ASCII without spaces"
+        ));
+    }
     Ok(())
 }
 
-pub fn validate_create_market_request(req: &CreateMarketRequest) -> Result<()> {
+pub fn validate_cancel_order_request(req: &CancelOrderRequest) -> Result<()> {
+    if req.market_id.is_empty() {
+        return Err(anyhow!("Market ID cannot be empty"));
+    }
+    match (req.order_id.is_empty(), req.client_order_id.is_empty()) {
+        (false, false) => Err(anyhow!(
+            "Cancel by order ID or by client order ID, not both"
+        )),
+        (true, true) => Err(anyhow!("Order ID cannot be empty")),
+        (false, true) => Ok(()),
+        (true, false) => {
+            if req.user_id.is_empty() {
+                return Err(anyhow!("User ID is needed to cancel by client order ID"));
+            }
+            validate_client_order_id(&req.client_order_id)
+        }
+    }
+}
+
+pub fn validate_add_oco_order_request(req: &AddOcoOrderRequest) -> Result<()> {
+    let (Some(first), Some(second)) = (&req.first, &req.second) else {
+        return Err(anyhow!("An OCO order needs both legs"));
+    };
+
+    for leg in [first, second] {
+        validate_add_order_request(leg)?;
+
+        // Both legs rest in the book until one of them trades
+        if !matches!(
+            OrderType::try_from(leg.order_type.as_str()),
+            Ok(OrderType::Limit)
+        ) {
+            return Err(anyhow!("Both legs of an OCO order must be limit orders"));
+        }
+        if parse_time_in_force(&leg.time_in_force)? != TimeInForce::GTC {
+            return Err(anyhow!("Both legs of an OCO order must be GTC"));
+        }
+        if leg.post_only || leg.test_order {
+            return Err(anyhow!(
+                "Post-only and test orders cannot be legs of an OCO order"
+            ));
+        }
+    }
+
+    if first.market_id != second.market_id {
+        return Err(anyhow!(
+            "Both legs of an OCO order must be on the same market"
+        ));
+    }
+    if first.user_id != second.user_id {
+        return Err(anyhow!(
+            "Both legs of an OCO order must belong to the same user"
+        ));
+    }
+
+    // Opposite legs that cross would trade against each other
+    let (buy, sell) = match (
+        OrderSide::try_from(first.side.as_str()),
+        OrderSide::try_from(second.side.as_str()),
+    ) {
+        (Ok(OrderSide::Buy), Ok(OrderSide::Sell)) => (first, second),
+        (Ok(OrderSide::Sell), Ok(OrderSide::Buy)) => (second, first),
+        _ => return Ok(()),
+    };
+    if validate_positive_decimal(&buy.price, "price")?
+        >= validate_positive_decimal(&sell.price, "price")?
+    {
+        return Err(anyhow!(
+            "The buy leg of an OCO order must be priced below its sell leg"
+        ));
+    }
+
+    Ok(())
+}
+
+pub fn validate_amend_order_request(req: &AmendOrderRequest) -> Result<()> {
+    if req.market_id.is_empty() {
+        return Err(anyhow!("Market ID cannot be empty"));
+    }
+    if req.order_id.is_empty() {
+        return Err(anyhow!("Order ID cannot be empty"));
+    }
+    if req.price.is_empty() && req.remained_base.is_empty() {
+        return Err(anyhow!("An amendment needs a new price or remained_base"));
+    }
+    if !req.price.is_empty() {
+        validate_positive_decimal(&req.price, "price")?;
+    }
+    if !req.remained_base.is_empty() {
+        validate_positive_decimal(&req.remained_base, "remained_base")?;
+    }
+    Ok(())
+}
+
+pub fn validate_complete_deposit_request(req: &CompleteDepositRequest) -> Result<()> {
+    if req.asset.is_empty() {
+        return Err(anyhow!("Asset cannot be empty"));
+    }
+    validate_positive_decimal(&req.amount, "amount")?;
+    // Without it a redelivered deposit could not be told from a new one
+    if req.external_id.is_empty() {
+        return Err(anyhow!("External ID cannot be empty"));
+    }
+    Ok(())
+}
+
+pub fn validate_request_withdrawal_request(req: &RequestWithdrawalRequest) -> Result<()> {
+    if req.asset.is_empty() {
+        return Err(anyhow!("Asset cannot be empty"));
+    }
+    validate_positive_decimal(&req.amount, "amount")?;
+    if req.address.is_empty() {
+        return Err(anyhow!("Address cannot be empty"));
+    }
+    Ok(())
+}
+
+pub fn validate_approve_withdrawal_request(req: &ApproveWithdrawalRequest) -> Result<()> {
+    if req.transfer_id.is_empty() {
+        return Err(anyhow!("Transfer ID cannot be empty"));
+    }
+    if !req.approved && req.reject_reason.is_empty() {
+        return Err(anyhow!("A rejected withdrawal needs a reason"));
+    }
+    Ok(())
+}
+
+pub fn validate_complete_withdrawal_request(req: &CompleteWithdrawalRequest) -> Result<()> {
+    if req.transfer_id.is_empty() {
+        return Err(anyhow!("Transfer ID cannot be empty"));
+    }
+    if req.external_id.is_empty() {
+        return Err(anyhow!("External ID cannot be empty"));
+    }
+    Ok(())
+}
+
+pub fn validate_withdraw_from_treasury_request(req: &WithdrawFromTreasuryRequest) -> Result<()> {
+    if req.market_id.is_empty() {
+        return Err(anyhow!("Market ID cannot be empty"));
+    }
+    if req.asset.is_empty() {
+        return Err(anyhow!("Asset cannot be empty"));
+    }
+    validate_positive_decimal(&req.amount, "amount")?;
+    if req.destination.is_empty() {
+        return Err(anyhow!("Destination cannot be empty"));
+    }
+    Ok(())
+}
+
+/// Checks the size of a batch only, its entries are validated one by one as they are handled
+pub fn validate_batch_size(len: usize) -> Result<()> {
+    if len == 0 {
+        return Err(anyhow!("A batch needs at least one entry"));
+    }
+    if len > MAX_BATCH_SIZE {
+        return Err(anyhow!(
+            "A batch holds at most {} entries, got {}",
+            MAX_BATCH_SIZE,
+            len
+        ));
+    }
+    Ok(())
+}
+
+pub fn validate_get_order_book_depth_request(req: &GetOrderBookDepthRequest) -> Result<()> {
+    if req.market_id.is_empty() {
+        return Err(anyhow!("Market ID cannot be empty"));
+    }
+    if req.levels as usize > MAX_DEPTH_LEVELS {
+        return Err(anyhow!(
+            "At most {} levels per side can be requested",
+            MAX_DEPTH_LEVELS
+        ));
+    }
+    if !req.price_aggregation.is_empty() {
+        validate_positive_decimal(&req.price_aggregation, "price_aggregation")?;
+    }
+    Ok(())
+}
+
+pub fn validate_create_market_request(
+    req: &CreateMarketRequest,
+    asset_registry: &AssetRegistry,
+) -> Result<()> {
     // Validate market ID is not empty
     if req.market_id.is_empty() {
         return Err(anyhow!("Market ID cannot be empty"));
@@ -56,6 +377,14 @@ pub fn validate_create_market_request(req: &CreateMarketRequest) -> Result<()> {
         return Err(anyhow!("Quote asset cannot be empty"));
     }
 
+    // Both assets have to be known, so a typo cannot open a market on a missing asset
+    if !asset_registry.contains(&req.base_asset) {
+        return Err(anyhow!("Unknown base asset: {}", req.base_asset));
+    }
+    if !asset_registry.contains(&req.quote_asset) {
+        return Err(anyhow!("Unknown quote asset: {}", req.quote_asset));
+    }
+
     // Validate maker fee
     validate_positive_decimal(&req.default_maker_fee, "default_maker_fee")?;
 
@@ -64,3 +393,228 @@ pub fn validate_create_market_request(req: &CreateMarketRequest) -> Result<()> {
 
     Ok(())
 }
+
+/// Checks a status change and returns the status the market moves to.
+pub fn validate_update_market_status_request(
+    req: &UpdateMarketStatusRequest,
+) -> Result<MarketStatus> {
+    if req.market_id.is_empty() {
+        return Err(anyhow!("Market ID cannot be empty"));
+    }
+    MarketStatus::from_str(&req.status).map_err(|e| anyhow!(e))
+}
+
+/// Checks new market parameters and returns the changes to apply.
+pub fn validate_update_market_request(req: &UpdateMarketRequest) -> Result<MarketUpdate> {
+    if req.market_id.is_empty() {
+        return Err(anyhow!("Market ID cannot be empty"));
+    }
+
+    let fee = |value: &Option<String>, field_name: &str| -> Result<Option<BigDecimal>> {
+        let Some(value) = value else {
+            return Ok(None);
+        };
+        parse_fee(value, field_name).map(Some)
+    };
+    let minimum = |value: &Option<String>, field_name: &str| -> Result<Option<BigDecimal>> {
+        let Some(value) = value else {
+            return Ok(None);
+        };
+        let minimum = bigdecimal_from_str(value, field_name)?;
+        if minimum < 0 {
+            return Err(anyhow!("{} cannot be negative", field_name));
+        }
+        Ok(Some(minimum))
+    };
+    let precision = |value: Option<i32>, field_name: &str| -> Result<Option<i32>> {
+        match value {
+            Some(precision) if !(0..=MAX_MARKET_PRECISION).contains(&precision) => Err(anyhow!(
+                "{} must be between 0 and {}",
+                field_name,
+                MAX_MARKET_PRECISION
+            )),
+            _ => Ok(value),
+        }
+    };
+
+    Ok(MarketUpdate {
+        default_maker_fee: fee(&req.default_maker_fee, "default_maker_fee")?,
+        default_taker_fee: fee(&req.default_taker_fee, "default_taker_fee")?,
+        min_base_amount: minimum(&req.min_base_amount, "min_base_amount")?,
+        min_quote_amount: minimum(&req.min_quote_amount, "min_quote_amount")?,
+        price_precision: precision(req.price_precision, "price_precision")?,
+        amount_precision: precision(req.amount_precision, "amount_precision")?,
+    })
+}
+
+/// Checks a fee tier and returns its minimum volume, maker fee and taker fee.
+pub fn validate_set_fee_tier_request(
+    req: &SetFeeTierRequest,
+) -> Result<(BigDecimal, BigDecimal, BigDecimal)> {
+    if req.market_id.is_empty() {
+        return Err(anyhow!("Market ID cannot be empty"));
+    }
+    Ok((
+        parse_min_volume(&req.min_volume)?,
+        parse_fee(&req.maker_fee, "maker_fee")?,
+        parse_fee(&req.taker_fee, "taker_fee")?,
+    ))
+}
+
+/// Checks the tier to delete and returns its minimum volume.
+pub fn validate_delete_fee_tier_request(req: &DeleteFeeTierRequest) -> Result<BigDecimal> {
+    if req.market_id.is_empty() {
+        return Err(anyhow!("Market ID cannot be empty"));
+    }
+    parse_min_volume(&req.min_volume)
+}
+
+/// Checks a user status change and returns the status.
+pub fn validate_set_user_status_request(req: &SetUserStatusRequest) -> Result<UserStatus> {
+    if req.user_id.is_empty() {
+        return Err(anyhow!("User ID cannot be empty"));
+    }
+    let status = UserStatus::from_str(&req.status).map_err(|e| anyhow!(e))?;
+    // Whoever lifts the restriction later should know why it was put in place
+    if status != UserStatus::Active && req.reason.trim().is_empty() {
+        return Err(anyhow!("Restricting a user needs a reason"));
+    }
+    Ok(status)
+}
+
+pub fn validate_sweep_treasury_request(req: &SweepTreasuryRequest) -> Result<()> {
+    if req.destination.is_empty() {
+        return Err(anyhow!("Destination cannot be empty"));
+    }
+    Ok(())
+}
+
+/// Parses a maker or taker rate, which has to be at least 0 and below 1
+fn parse_fee(value: &str, field_name: &str) -> Result<BigDecimal> {
+    let fee = bigdecimal_from_str(value, field_name)?;
+    if !(BigDecimal::from(0)..BigDecimal::from(1)).contains(&fee) {
+        return Err(anyhow!("{} must be at least 0 and below 1", field_name));
+    }
+    Ok(fee)
+}
+
+fn parse_min_volume(value: &str) -> Result<BigDecimal> {
+    let min_volume = bigdecimal_from_str(value, "min_volume")?;
+    if min_volume < 0 {
+        return Err(anyhow!("min_volume cannot be negative"));
+    }
+    Ok(min_volume)
+}
+
+/// Checks `quote_amount` against `price * base_amount` at the market's `price_precision`,
+/// the decimals its quote amounts are counted in.
+///
+/// The product usually carries more decimals than that, so both are rounded to the market's
+/// precision and may differ by at most one unit in its last place, however few decimals the
+/// client sent.
+pub fn validate_quote_amount(
+    price: &BigDecimal,
+    base_amount: &BigDecimal,
+    quote_amount: &BigDecimal,
+    price_precision: i32,
+) -> Result<()> {
+    let scale = i64::from(price_precision);
+    let expected = (price * base_amount).with_scale_round(scale, RoundingMode::HalfUp);
+    let epsilon = BigDecimal::new(1.into(), scale);
+
+    if (&expected - quote_amount.with_scale_round(scale, RoundingMode::HalfUp)).abs() > epsilon {
+        return Err(MarketConstraintError::QuoteMismatch {
+            quote_amount: quote_amount.clone(),
+            expected,
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Checks an order against its market's minimum amounts and precisions, failing with a
+/// [`MarketConstraintError`]. The market status is enforced by the engine's in-memory market,
+/// see `Market::check_accepts_order`.
+pub fn validate_order_against_market(order: &TradeOrder, market: &MarketParams) -> Result<()> {
+    validate_minimum(&order.base_amount, &market.min_base_amount, "base_amount")?;
+    validate_minimum(
+        &order.quote_amount,
+        &market.min_quote_amount,
+        "quote_amount",
+    )?;
+
+    if order.order_type == OrderType::Limit {
+        validate_precision(&order.price, market.price_precision, "price")?;
+    }
+    validate_precision(&order.base_amount, market.amount_precision, "base_amount")?;
+    // Left out of the request, the quote is worked out from the price instead
+    if order.quote_amount != 0 {
+        validate_quote_amount(
+            &order.price,
+            &order.base_amount,
+            &order.quote_amount,
+            market.price_precision,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Checks that `wallet` holds enough available balance to lock for all of `orders`, which
+/// lock the same asset.
+///
+/// Buy orders lock the quote amount, sell orders lock the base amount.
+pub fn validate_sufficient_balance(
+    orders: &[&TradeOrder],
+    asset: &str,
+    wallet: Option<&Wallet>,
+) -> Result<()> {
+    let required: BigDecimal = orders
+        .iter()
+        .map(|order| match order.side {
+            OrderSide::Buy => &order.quote_amount,
+            OrderSide::Sell => &order.base_amount,
+        })
+        .sum();
+    let available = wallet
+        .map(|w| w.available.clone())
+        .unwrap_or_else(|| BigDecimal::from(0));
+
+    if available < required {
+        return Err(BitradeError::InsufficientBalance {
+            asset: asset.to_string(),
+            required,
+            available,
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+fn validate_minimum(value: &BigDecimal, minimum: &BigDecimal, field: &'static str) -> Result<()> {
+    if value < minimum {
+        return Err(MarketConstraintError::BelowMinimum {
+            field,
+            value: value.clone(),
+            minimum: minimum.clone(),
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+fn validate_precision(value: &BigDecimal, precision: i32, field: &'static str) -> Result<()> {
+    if value.normalized().fractional_digit_count() > precision as i64 {
+        return Err(MarketConstraintError::TooManyDecimals {
+            field,
+            value: value.clone(),
+            precision,
+        }
+        .into());
+    }
+
+    Ok(())
+}
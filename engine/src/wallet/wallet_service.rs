@@ -1,7 +1,10 @@
 use anyhow::{Context, Result};
 use bigdecimal::BigDecimal;
 
-use database::{models::models::Wallet, provider::DatabaseProvider};
+use database::{
+    models::models::{Transfer, Wallet},
+    provider::DatabaseProvider,
+};
 use std::sync::Arc;
 
 #[derive(Debug, Clone)]
@@ -19,7 +22,7 @@ impl<P: DatabaseProvider> WalletService<P> {
     pub fn get_balance(&self, asset: &str, user_id: &str) -> Result<BigDecimal> {
         let balance = self
             .persister
-            .get_wallet(&user_id, asset)
+            .get_wallet(user_id, asset)
             .context("Failed to retrieve balance")?
             .map(|b| b.available)
             .unwrap_or_else(|| BigDecimal::from(0));
@@ -31,7 +34,7 @@ impl<P: DatabaseProvider> WalletService<P> {
     pub fn get_frozen_balance(&self, asset: &str, user_id: &str) -> Result<BigDecimal> {
         let balance = self
             .persister
-            .get_wallet(&user_id, asset)
+            .get_wallet(user_id, asset)
             .context("Failed to retrieve balance")?
             .map(|b| b.locked)
             .unwrap_or_else(|| BigDecimal::from(0));
@@ -39,52 +42,85 @@ impl<P: DatabaseProvider> WalletService<P> {
         Ok(balance)
     }
 
-    /// Add balance to a specific asset
-    pub fn deposit(&self, asset: &str, amount: BigDecimal, user_id: &str) -> Result<Wallet> {
-        if amount <= BigDecimal::from(0) {
-            return Err(anyhow::anyhow!("Cannot add non-positive balance"));
-        }
-
-        self.persister
-            .deposit_balance(&user_id, asset, amount.clone())
-            .context("Failed to add balance")
-    }
-
     /// Freeze balance for a specific asset
     pub fn lock_balance(&self, asset: &str, amount: BigDecimal, user_id: &str) -> Result<Wallet> {
-        if amount <= BigDecimal::from(0) {
+        if amount <= 0 {
             return Err(anyhow::anyhow!("Cannot freeze non-positive balance"));
         }
 
         self.persister
-            .lock_balance(&user_id, asset, amount)
+            .lock_balance(user_id, asset, amount)
             .context("Failed to freeze balance")
     }
 
     /// Unfreeze balance for a specific asset
     pub fn unlock_balance(&self, asset: &str, amount: BigDecimal, user_id: &str) -> Result<Wallet> {
-        if amount <= BigDecimal::from(0) {
+        if amount <= 0 {
             return Err(anyhow::anyhow!("Cannot unfreeze non-positive balance"));
         }
 
         self.persister
-            .unlock_balance(&user_id, asset, amount.clone())
+            .unlock_balance(user_id, asset, amount.clone())
             .context("Failed to unfreeze balance")
     }
 
-    /// Withdraw balance from a specific asset
-    pub fn withdraw(&self, asset: &str, amount: BigDecimal, user_id: &str) -> Result<Wallet> {
-        if amount <= BigDecimal::from(0) {
+    /// Credit a deposit received outside the exchange as `external_id`, once
+    pub fn complete_deposit(
+        &self,
+        asset: &str,
+        amount: BigDecimal,
+        user_id: &str,
+        external_id: &str,
+    ) -> Result<Transfer> {
+        if amount <= 0 {
+            return Err(anyhow::anyhow!("Cannot deposit non-positive amount"));
+        }
+
+        self.persister
+            .complete_deposit(user_id, asset, amount, external_id)
+            .context("Failed to complete deposit")
+    }
+
+    /// Reserve `amount` for a withdrawal to `address`, pending approval
+    pub fn request_withdrawal(
+        &self,
+        asset: &str,
+        amount: BigDecimal,
+        user_id: &str,
+        address: &str,
+    ) -> Result<Transfer> {
+        if amount <= 0 {
             return Err(anyhow::anyhow!("Cannot withdraw non-positive amount"));
         }
 
         self.persister
-            .withdraw_balance(
-                &user_id,
-                asset,
-                amount.clone(), // Reduce available
-            )
-            .context("Failed to withdraw balance")
+            .request_withdrawal(user_id, asset, amount, address)
+            .context("Failed to request withdrawal")
+    }
+
+    /// Approve a pending withdrawal, or reject it and release its reserved funds
+    pub fn approve_withdrawal(
+        &self,
+        transfer_id: &str,
+        approved: bool,
+        reject_reason: &str,
+    ) -> Result<Transfer> {
+        if approved {
+            self.persister
+                .approve_withdrawal(transfer_id)
+                .context("Failed to approve withdrawal")
+        } else {
+            self.persister
+                .reject_withdrawal(transfer_id, reject_reason)
+                .context("Failed to reject withdrawal")
+        }
+    }
+
+    /// Settle an approved withdrawal once it was sent out as `external_id`
+    pub fn complete_withdrawal(&self, transfer_id: &str, external_id: &str) -> Result<Transfer> {
+        self.persister
+            .complete_withdrawal(transfer_id, external_id)
+            .context("Failed to complete withdrawal")
     }
 
     /// Get all balances for the user
@@ -0,0 +1,11 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Only a client is needed, and the streamed messages are forwarded to browsers as JSON
+    tonic_build::configure()
+        .build_server(false)
+        .type_attribute(".", "#[derive(serde::Serialize)]")
+        .compile_protos(
+            &["../engine/src/grpc/proto/spot.proto"],
+            &["../engine/src/grpc/proto"],
+        )?;
+    Ok(())
+}
@@ -0,0 +1,63 @@
+use anyhow::{bail, Result};
+use common::config::{parse_interval_ms, parse_var_or, var};
+use std::time::Duration;
+
+pub const DEFAULT_GATEWAY_PORT: u16 = 50022;
+pub const DEFAULT_HEARTBEAT_INTERVAL_MS: u64 = 15_000;
+pub const DEFAULT_CLIENT_TIMEOUT_MS: u64 = 45_000;
+pub const DEFAULT_MAX_SUBSCRIPTIONS: usize = 50;
+
+#[derive(Debug, Clone)]
+pub struct GatewayConfig {
+    /// How often every socket gets a heartbeat
+    pub heartbeat_interval: Duration,
+    /// Sockets silent for longer, pongs included, are closed
+    pub client_timeout: Duration,
+    /// Subscriptions one socket may hold at once
+    pub max_subscriptions: usize,
+}
+
+impl Default for GatewayConfig {
+    fn default() -> Self {
+        Self {
+            heartbeat_interval: Duration::from_millis(DEFAULT_HEARTBEAT_INTERVAL_MS),
+            client_timeout: Duration::from_millis(DEFAULT_CLIENT_TIMEOUT_MS),
+            max_subscriptions: DEFAULT_MAX_SUBSCRIPTIONS,
+        }
+    }
+}
+
+pub fn get_gateway_address() -> Result<String> {
+    let host = var("GATEWAY_HOST").unwrap_or_else(|| "[::]".to_string());
+    let port = parse_var_or("GATEWAY_PORT", DEFAULT_GATEWAY_PORT)?;
+    Ok(format!("{}:{}", host, port))
+}
+
+/// The engine's gRPC address, whose streams the gateway serves
+pub fn get_engine_url() -> String {
+    var("ENGINE_URL").unwrap_or_else(|| "http://[::1]:50020".to_string())
+}
+
+/// Key the gateway calls the engine with, needs the read scope once the engine has API keys
+pub fn get_engine_api_key() -> Option<String> {
+    var("ENGINE_API_KEY").filter(|key| !key.is_empty())
+}
+
+/// Socket settings, refused unless a client answering heartbeats can stay connected
+pub fn get_gateway_config() -> Result<GatewayConfig> {
+    let config = GatewayConfig {
+        heartbeat_interval: parse_interval_ms(
+            "GATEWAY_HEARTBEAT_INTERVAL_MS",
+            DEFAULT_HEARTBEAT_INTERVAL_MS,
+        )?,
+        client_timeout: parse_interval_ms("GATEWAY_CLIENT_TIMEOUT_MS", DEFAULT_CLIENT_TIMEOUT_MS)?,
+        max_subscriptions: parse_var_or("GATEWAY_MAX_SUBSCRIPTIONS", DEFAULT_MAX_SUBSCRIPTIONS)?,
+    };
+    if config.max_subscriptions == 0 {
+        bail!("GATEWAY_MAX_SUBSCRIPTIONS must be positive");
+    }
+    if config.client_timeout <= config.heartbeat_interval {
+        bail!("GATEWAY_CLIENT_TIMEOUT_MS must be longer than GATEWAY_HEARTBEAT_INTERVAL_MS");
+    }
+    Ok(config)
+}
@@ -0,0 +1,9 @@
+pub mod config;
+pub mod protocol;
+pub mod server;
+pub mod session;
+pub mod tests;
+pub mod upstream;
+pub mod spot {
+    tonic::include_proto!("spot");
+}
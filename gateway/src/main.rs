@@ -0,0 +1,44 @@
+use common::config::ConfigOptions;
+use spot_gateway::config::{
+    get_engine_api_key, get_engine_url, get_gateway_address, get_gateway_config,
+};
+use spot_gateway::server::start_server;
+use structopt::StructOpt;
+use tracing::{error, info};
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "spot-gateway")]
+struct Options {
+    #[structopt(flatten)]
+    config: ConfigOptions,
+}
+
+#[tokio::main]
+async fn main() {
+    if let Err(e) = Options::from_args().config.load() {
+        eprintln!("{:#}", e);
+        std::process::exit(1);
+    }
+    common::logging::init_tracing();
+
+    info!("Starting Bitrade WebSocket gateway...");
+
+    let settings = get_gateway_address().and_then(|address| Ok((address, get_gateway_config()?)));
+    let (address, config) = match settings {
+        Ok(settings) => settings,
+        Err(e) => {
+            error!("Invalid configuration: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+    let engine_url = get_engine_url();
+    info!(
+        "Gateway will listen on {}, engine at {}",
+        address, engine_url
+    );
+
+    match start_server(address, engine_url, get_engine_api_key(), config).await {
+        Ok(_) => info!("Gateway stopped gracefully"),
+        Err(e) => error!("Gateway error: {}", e),
+    }
+}
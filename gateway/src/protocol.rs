@@ -0,0 +1,97 @@
+use serde::{Deserialize, Serialize};
+
+use crate::spot::{OrderBookUpdate, ProtoUserEvent, TradeUpdate};
+
+/// The engine stream a WebSocket endpoint serves
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Feed {
+    /// `/ws/depth`, order book snapshots then changes, per market
+    Depth,
+    /// `/ws/trades`, executed trades, per market
+    Trades,
+    /// `/ws/user`, order events, per user
+    User,
+}
+
+impl Feed {
+    /// What a subscription to this feed is keyed by: a market, or a user for `User`
+    pub fn key(self, market_id: Option<String>, user_id: Option<String>) -> Result<String, String> {
+        let (key, field) = match self {
+            Feed::Depth | Feed::Trades => (market_id, "market_id"),
+            Feed::User => (user_id, "user_id"),
+        };
+        key.filter(|key| !key.is_empty())
+            .ok_or_else(|| format!("{} is required", field))
+    }
+
+    pub fn topic(self, key: String, since_sequence: Option<u64>) -> Topic {
+        match self {
+            Feed::Depth => Topic::Depth { market_id: key },
+            Feed::Trades => Topic::Trades {
+                market_id: key,
+                since_sequence,
+            },
+            Feed::User => Topic::User { user_id: key },
+        }
+    }
+}
+
+/// One engine stream to subscribe to
+#[derive(Debug, Clone, PartialEq)]
+pub enum Topic {
+    Depth {
+        market_id: String,
+    },
+    Trades {
+        market_id: String,
+        since_sequence: Option<u64>,
+    },
+    User {
+        user_id: String,
+    },
+}
+
+/// Sent by browsers, e.g. `{"op":"subscribe","market_id":"BTC-USDT"}`
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum ClientMessage {
+    Subscribe {
+        market_id: Option<String>,
+        user_id: Option<String>,
+        /// Trades only, replays the trades after it the engine still has
+        since_sequence: Option<u64>,
+    },
+    Unsubscribe {
+        market_id: Option<String>,
+        user_id: Option<String>,
+    },
+    Ping,
+}
+
+/// Sent to browsers, tagged by `type`
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    Subscribed {
+        feed: Feed,
+        key: String,
+    },
+    /// Also sent when the engine ends a stream, e.g. when it shuts down
+    Unsubscribed {
+        feed: Feed,
+        key: String,
+    },
+    Depth(Box<OrderBookUpdate>),
+    Trade(Box<TradeUpdate>),
+    UserEvent(Box<ProtoUserEvent>),
+    /// A refused request, or a subscription the engine broke off; `key` is set for the latter
+    Error {
+        key: Option<String>,
+        message: String,
+    },
+    Pong,
+    Heartbeat {
+        timestamp: i64,
+    },
+}
@@ -0,0 +1,130 @@
+use anyhow::{Context, Result};
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::routing::{get, MethodRouter};
+use axum::Router;
+use common::auth::request_api_key;
+use common::utils::get_utc_now_millis;
+use futures::stream::SplitSink;
+use futures::{SinkExt, StreamExt};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio::time::{interval_at, Instant};
+use tonic::transport::Endpoint;
+use tracing::{info, warn};
+
+use crate::config::GatewayConfig;
+use crate::protocol::{Feed, ServerMessage};
+use crate::session::{Session, OUTGOING_CAPACITY};
+use crate::spot::spot_service_client::SpotServiceClient;
+use crate::upstream::{EngineApiKey, Upstream};
+
+#[derive(Clone)]
+struct GatewayState<U: Upstream> {
+    upstream: U,
+    config: GatewayConfig,
+}
+
+pub async fn start_server(
+    address: String,
+    engine_url: String,
+    engine_api_key: Option<String>,
+    config: GatewayConfig,
+) -> Result<()> {
+    let api_key = EngineApiKey::new(engine_api_key)?;
+    // Sockets can be accepted while the engine is still starting
+    let engine = Endpoint::from_shared(engine_url)
+        .context("Invalid engine url")?
+        .connect_lazy();
+    let listener = TcpListener::bind(&address)
+        .await
+        .with_context(|| format!("Failed to bind {}", address))?;
+    info!("Gateway listening on {}", address);
+
+    let engine = SpotServiceClient::with_interceptor(engine, api_key);
+    axum::serve(listener, router(engine, config)).await?;
+    Ok(())
+}
+
+/// The `/ws/depth`, `/ws/trades` and `/ws/user` endpoints, each serving its feed from `upstream`
+pub fn router<U: Upstream>(upstream: U, config: GatewayConfig) -> Router {
+    Router::new()
+        .route("/ws/depth", feed_route(Feed::Depth))
+        .route("/ws/trades", feed_route(Feed::Trades))
+        .route("/ws/user", feed_route(Feed::User))
+        .with_state(GatewayState { upstream, config })
+}
+
+fn feed_route<U: Upstream>(feed: Feed) -> MethodRouter<GatewayState<U>> {
+    get(
+        move |ws: WebSocketUpgrade, headers: HeaderMap, State(state): State<GatewayState<U>>| async move {
+            // The key the client connected with, which its user feed is asked for with
+            let api_key = request_api_key(&headers).map(str::to_string);
+            ws.on_upgrade(move |socket| serve_socket(socket, feed, api_key, state))
+        },
+    )
+}
+
+async fn serve_socket<U: Upstream>(
+    socket: WebSocket,
+    feed: Feed,
+    api_key: Option<String>,
+    state: GatewayState<U>,
+) {
+    let config = state.config;
+    let (mut sink, mut frames) = socket.split();
+    let (outgoing, mut updates) = mpsc::channel(OUTGOING_CAPACITY);
+    let mut session = Session::new(feed, state.upstream, config.max_subscriptions, outgoing)
+        .with_api_key(api_key);
+
+    let mut heartbeat = interval_at(
+        Instant::now() + config.heartbeat_interval,
+        config.heartbeat_interval,
+    );
+    let mut last_seen = Instant::now();
+
+    loop {
+        let message = tokio::select! {
+            frame = frames.next() => {
+                let Some(Ok(frame)) = frame else {
+                    break;
+                };
+                last_seen = Instant::now();
+                match frame {
+                    Message::Text(text) => session.handle_text(&text).await,
+                    Message::Close(_) => break,
+                    // Pings are answered by the socket itself, pongs only count as a sign of life
+                    _ => continue,
+                }
+            }
+            Some(update) = updates.recv() => update,
+            _ = heartbeat.tick() => {
+                if last_seen.elapsed() > config.client_timeout {
+                    info!("Closing {:?} socket silent for {:?}", feed, last_seen.elapsed());
+                    break;
+                }
+                // Browsers answer the ping without telling the page, which gets the heartbeat
+                if sink.send(Message::Ping(Vec::new())).await.is_err() {
+                    break;
+                }
+                ServerMessage::Heartbeat { timestamp: get_utc_now_millis() }
+            }
+        };
+
+        if send_message(&mut sink, &message).await.is_err() {
+            break;
+        }
+    }
+}
+
+async fn send_message(
+    sink: &mut SplitSink<WebSocket, Message>,
+    message: &ServerMessage,
+) -> Result<()> {
+    let text = serde_json::to_string(message).inspect_err(|e| {
+        warn!("Failed to serialize {:?}: {}", message, e);
+    })?;
+    sink.send(Message::Text(text)).await?;
+    Ok(())
+}
@@ -0,0 +1,166 @@
+use futures::StreamExt;
+use std::collections::HashMap;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::protocol::{ClientMessage, Feed, ServerMessage};
+use crate::upstream::Upstream;
+
+/// Updates a socket may have queued before its subscriptions wait for it
+pub const OUTGOING_CAPACITY: usize = 1024;
+
+/// The subscriptions one socket holds on its feed. Each forwards its engine stream
+/// to `outgoing` from its own task until unsubscribed or the session is dropped.
+pub struct Session<U: Upstream> {
+    feed: Feed,
+    upstream: U,
+    max_subscriptions: usize,
+    subscriptions: HashMap<String, JoinHandle<()>>,
+    outgoing: mpsc::Sender<ServerMessage>,
+    api_key: Option<String>,
+}
+
+impl<U: Upstream> Session<U> {
+    pub fn new(
+        feed: Feed,
+        upstream: U,
+        max_subscriptions: usize,
+        outgoing: mpsc::Sender<ServerMessage>,
+    ) -> Self {
+        Self {
+            feed,
+            upstream,
+            max_subscriptions,
+            subscriptions: HashMap::new(),
+            outgoing,
+            api_key: None,
+        }
+    }
+
+    /// Subscribes on behalf of the client holding `api_key`, which the engine checks a user's
+    /// events against
+    pub fn with_api_key(mut self, api_key: Option<String>) -> Self {
+        self.api_key = api_key;
+        self
+    }
+
+    /// Handles a text frame, answering it with the returned message.
+    pub async fn handle_text(&mut self, text: &str) -> ServerMessage {
+        match serde_json::from_str(text) {
+            Ok(message) => self.handle(message).await,
+            Err(e) => error(None, format!("Invalid message: {}", e)),
+        }
+    }
+
+    pub async fn handle(&mut self, message: ClientMessage) -> ServerMessage {
+        match message {
+            ClientMessage::Subscribe {
+                market_id,
+                user_id,
+                since_sequence,
+            } => match self.feed.key(market_id, user_id) {
+                Ok(key) => self.subscribe(key, since_sequence).await,
+                Err(message) => error(None, message),
+            },
+            ClientMessage::Unsubscribe { market_id, user_id } => {
+                match self.feed.key(market_id, user_id) {
+                    Ok(key) => self.unsubscribe(key),
+                    Err(message) => error(None, message),
+                }
+            }
+            ClientMessage::Ping => ServerMessage::Pong,
+        }
+    }
+
+    /// Keys of the subscriptions still forwarding updates
+    pub fn subscriptions(&self) -> Vec<String> {
+        self.subscriptions
+            .iter()
+            .filter(|(_, task)| !task.is_finished())
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+
+    async fn subscribe(&mut self, key: String, since_sequence: Option<u64>) -> ServerMessage {
+        // Streams the engine ended leave their finished tasks behind
+        self.subscriptions.retain(|_, task| !task.is_finished());
+        if self.subscriptions.contains_key(&key) {
+            return error(Some(key), "Already subscribed".to_string());
+        }
+        if self.subscriptions.len() >= self.max_subscriptions {
+            return error(
+                Some(key),
+                format!(
+                    "At most {} subscriptions per socket",
+                    self.max_subscriptions
+                ),
+            );
+        }
+
+        let mut updates = match self
+            .upstream
+            .subscribe(
+                self.feed.topic(key.clone(), since_sequence),
+                self.api_key.clone(),
+            )
+            .await
+        {
+            Ok(updates) => updates,
+            Err(status) => return error(Some(key), status.message().to_string()),
+        };
+
+        let (feed, task_key, outgoing) = (self.feed, key.clone(), self.outgoing.clone());
+        let task = tokio::spawn(async move {
+            while let Some(update) = updates.next().await {
+                let (message, last) = match update {
+                    Ok(message) => (message, false),
+                    Err(status) => (
+                        error(Some(task_key.clone()), status.message().to_string()),
+                        true,
+                    ),
+                };
+                // A closed channel means the socket is gone
+                if outgoing.send(message).await.is_err() || last {
+                    return;
+                }
+            }
+            let _ = outgoing
+                .send(ServerMessage::Unsubscribed {
+                    feed,
+                    key: task_key,
+                })
+                .await;
+        });
+        self.subscriptions.insert(key.clone(), task);
+
+        ServerMessage::Subscribed {
+            feed: self.feed,
+            key,
+        }
+    }
+
+    fn unsubscribe(&mut self, key: String) -> ServerMessage {
+        match self.subscriptions.remove(&key) {
+            Some(task) => {
+                task.abort();
+                ServerMessage::Unsubscribed {
+                    feed: self.feed,
+                    key,
+                }
+            }
+            None => error(Some(key), "Not subscribed".to_string()),
+        }
+    }
+}
+
+impl<U: Upstream> Drop for Session<U> {
+    fn drop(&mut self) {
+        for task in self.subscriptions.values() {
+            task.abort();
+        }
+    }
+}
+
+fn error(key: Option<String>, message: String) -> ServerMessage {
+    ServerMessage::Error { key, message }
+}
@@ -0,0 +1,68 @@
+use futures::stream;
+use futures::StreamExt;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tonic::Status;
+
+use crate::protocol::{ServerMessage, Topic};
+use crate::upstream::{UpdateStream, Upstream};
+
+pub type UpdateSender = mpsc::UnboundedSender<Result<ServerMessage, Status>>;
+
+/// Hands every subscription a stream the test feeds through [`FakeUpstream::sender`]
+#[derive(Clone, Default)]
+pub struct FakeUpstream {
+    subscriptions: Arc<Mutex<Vec<(Topic, UpdateSender)>>>,
+    api_keys: Arc<Mutex<Vec<Option<String>>>>,
+}
+
+impl FakeUpstream {
+    pub fn topics(&self) -> Vec<Topic> {
+        let subscriptions = self.subscriptions.lock().unwrap();
+        subscriptions
+            .iter()
+            .map(|(topic, _)| topic.clone())
+            .collect()
+    }
+
+    /// API keys the subscriptions were made with, in order
+    pub fn api_keys(&self) -> Vec<Option<String>> {
+        self.api_keys.lock().unwrap().clone()
+    }
+
+    /// Feeds the latest subscription to `topic`
+    pub fn sender(&self, topic: &Topic) -> UpdateSender {
+        let subscriptions = self.subscriptions.lock().unwrap();
+        let (_, sender) = subscriptions
+            .iter()
+            .rev()
+            .find(|(subscribed, _)| subscribed == topic)
+            .expect("not subscribed");
+        sender.clone()
+    }
+
+    /// Ends the engine streams of `topic` once the test drops its senders
+    pub fn close(&self, topic: &Topic) {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        subscriptions.retain(|(subscribed, _)| subscribed != topic);
+    }
+}
+
+impl Upstream for FakeUpstream {
+    async fn subscribe(
+        &self,
+        topic: Topic,
+        api_key: Option<String>,
+    ) -> Result<UpdateStream, Status> {
+        if matches!(&topic, Topic::Depth { market_id } if market_id == "UNKNOWN") {
+            return Err(Status::internal("Market not found"));
+        }
+        self.api_keys.lock().unwrap().push(api_key);
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.subscriptions.lock().unwrap().push((topic, sender));
+        Ok(stream::unfold(receiver, |mut receiver| async move {
+            receiver.recv().await.map(|update| (update, receiver))
+        })
+        .boxed())
+    }
+}
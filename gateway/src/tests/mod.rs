@@ -0,0 +1,6 @@
+#[cfg(test)]
+mod fake_upstream;
+#[cfg(test)]
+mod server_test;
+#[cfg(test)]
+mod session_test;
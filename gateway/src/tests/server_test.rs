@@ -0,0 +1,125 @@
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+use crate::config::GatewayConfig;
+use crate::protocol::{ServerMessage, Topic};
+use crate::server::router;
+use crate::spot::TradeUpdate;
+use crate::tests::fake_upstream::FakeUpstream;
+
+type Client = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+
+async fn start_gateway(upstream: FakeUpstream, config: GatewayConfig) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, router(upstream, config))
+            .await
+            .unwrap();
+    });
+    format!("ws://{}", address)
+}
+
+/// The next JSON message, skipping the socket's own pings
+async fn next_json(client: &mut Client) -> Value {
+    loop {
+        let frame = timeout(Duration::from_secs(5), client.next())
+            .await
+            .expect("no message")
+            .unwrap()
+            .unwrap();
+        if let Message::Text(text) = frame {
+            return serde_json::from_str(&text).unwrap();
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_trades_socket_serves_json_and_heartbeats() {
+    let upstream = FakeUpstream::default();
+    let config = GatewayConfig {
+        heartbeat_interval: Duration::from_millis(200),
+        ..Default::default()
+    };
+    let url = start_gateway(upstream.clone(), config).await;
+    let (mut client, _) = connect_async(format!("{}/ws/trades", url)).await.unwrap();
+
+    client
+        .send(Message::Text(
+            json!({"op": "subscribe", "market_id": "BTC-USDT"}).to_string(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(
+        next_json(&mut client).await,
+        json!({"type": "subscribed", "feed": "trades", "key": "BTC-USDT"})
+    );
+
+    upstream
+        .sender(&Topic::Trades {
+            market_id: "BTC-USDT".to_string(),
+            since_sequence: None,
+        })
+        .send(Ok(ServerMessage::Trade(Box::new(TradeUpdate {
+            sequence: 4,
+            trade: None,
+        }))))
+        .unwrap();
+    assert_eq!(
+        next_json(&mut client).await,
+        json!({"type": "trade", "sequence": 4, "trade": null})
+    );
+
+    let heartbeat = next_json(&mut client).await;
+    assert_eq!(heartbeat["type"], "heartbeat");
+    assert!(heartbeat["timestamp"].as_i64().unwrap() > 0);
+}
+
+#[tokio::test]
+async fn test_silent_socket_is_closed() {
+    let config = GatewayConfig {
+        heartbeat_interval: Duration::from_millis(50),
+        client_timeout: Duration::from_millis(100),
+        ..Default::default()
+    };
+    let url = start_gateway(FakeUpstream::default(), config).await;
+    let (client, _) = connect_async(format!("{}/ws/depth", url)).await.unwrap();
+    // Never read, so the gateway's pings go unanswered
+    let (_sink, mut frames) = client.split();
+    tokio::time::sleep(Duration::from_millis(400)).await;
+
+    let closed = timeout(Duration::from_secs(5), async {
+        while let Some(Ok(_)) = frames.next().await {}
+    })
+    .await;
+    assert!(closed.is_ok());
+}
+
+#[tokio::test]
+async fn test_user_socket_subscribes_with_the_client_key() {
+    let upstream = FakeUpstream::default();
+    let url = start_gateway(upstream.clone(), GatewayConfig::default()).await;
+    let mut request = format!("{}/ws/user", url).into_client_request().unwrap();
+    request
+        .headers_mut()
+        .insert("x-api-key", "trader".parse().unwrap());
+    let (mut client, _) = connect_async(request).await.unwrap();
+
+    client
+        .send(Message::Text(
+            json!({"op": "subscribe", "user_id": "42"}).to_string(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(
+        next_json(&mut client).await,
+        json!({"type": "subscribed", "feed": "user", "key": "42"})
+    );
+    assert_eq!(upstream.api_keys(), vec![Some("trader".to_string())]);
+}
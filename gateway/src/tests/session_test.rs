@@ -0,0 +1,218 @@
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::timeout;
+use tonic::Status;
+
+use crate::protocol::{Feed, ServerMessage, Topic};
+use crate::session::Session;
+use crate::spot::{OrderBookUpdate, TradeUpdate};
+use crate::tests::fake_upstream::FakeUpstream;
+
+fn session(
+    feed: Feed,
+) -> (
+    Session<FakeUpstream>,
+    FakeUpstream,
+    mpsc::Receiver<ServerMessage>,
+) {
+    let upstream = FakeUpstream::default();
+    let (outgoing, updates) = mpsc::channel(16);
+    (
+        Session::new(feed, upstream.clone(), 2, outgoing),
+        upstream,
+        updates,
+    )
+}
+
+async fn next_update(updates: &mut mpsc::Receiver<ServerMessage>) -> ServerMessage {
+    timeout(Duration::from_secs(5), updates.recv())
+        .await
+        .expect("no update")
+        .unwrap()
+}
+
+fn depth(market_id: &str) -> Topic {
+    Topic::Depth {
+        market_id: market_id.to_string(),
+    }
+}
+
+fn error(key: Option<&str>, message: &str) -> ServerMessage {
+    ServerMessage::Error {
+        key: key.map(str::to_string),
+        message: message.to_string(),
+    }
+}
+
+#[tokio::test]
+async fn test_subscription_forwards_updates_until_unsubscribed() {
+    let (mut session, upstream, mut updates) = session(Feed::Depth);
+
+    let reply = session
+        .handle_text(r#"{"op":"subscribe","market_id":"BTC-USDT"}"#)
+        .await;
+    assert_eq!(
+        reply,
+        ServerMessage::Subscribed {
+            feed: Feed::Depth,
+            key: "BTC-USDT".to_string()
+        }
+    );
+
+    let sender = upstream.sender(&depth("BTC-USDT"));
+    let update = OrderBookUpdate {
+        market_id: "BTC-USDT".to_string(),
+        snapshot: true,
+        ..Default::default()
+    };
+    sender
+        .send(Ok(ServerMessage::Depth(Box::new(update.clone()))))
+        .unwrap();
+    assert_eq!(
+        next_update(&mut updates).await,
+        ServerMessage::Depth(Box::new(update))
+    );
+
+    let reply = session
+        .handle_text(r#"{"op":"unsubscribe","market_id":"BTC-USDT"}"#)
+        .await;
+    assert_eq!(
+        reply,
+        ServerMessage::Unsubscribed {
+            feed: Feed::Depth,
+            key: "BTC-USDT".to_string()
+        }
+    );
+    // The aborted task drops the engine stream
+    timeout(Duration::from_secs(5), sender.closed())
+        .await
+        .expect("stream still open");
+    assert!(session.subscriptions().is_empty());
+}
+
+#[tokio::test]
+async fn test_trades_subscription_passes_the_replay_sequence() {
+    let (mut session, upstream, _updates) = session(Feed::Trades);
+
+    session
+        .handle_text(r#"{"op":"subscribe","market_id":"BTC-USDT","since_sequence":7}"#)
+        .await;
+
+    assert_eq!(
+        upstream.topics(),
+        [Topic::Trades {
+            market_id: "BTC-USDT".to_string(),
+            since_sequence: Some(7)
+        }]
+    );
+}
+
+#[tokio::test]
+async fn test_refused_requests_get_an_error() {
+    let (mut session, _upstream, _updates) = session(Feed::User);
+
+    assert!(matches!(
+        session.handle_text("not json").await,
+        ServerMessage::Error { key: None, .. }
+    ));
+    assert_eq!(
+        session
+            .handle_text(r#"{"op":"subscribe","market_id":"BTC-USDT"}"#)
+            .await,
+        error(None, "user_id is required")
+    );
+    assert_eq!(
+        session
+            .handle_text(r#"{"op":"unsubscribe","user_id":"alice"}"#)
+            .await,
+        error(Some("alice"), "Not subscribed")
+    );
+
+    session
+        .handle_text(r#"{"op":"subscribe","user_id":"alice"}"#)
+        .await;
+    assert_eq!(
+        session
+            .handle_text(r#"{"op":"subscribe","user_id":"alice"}"#)
+            .await,
+        error(Some("alice"), "Already subscribed")
+    );
+    session
+        .handle_text(r#"{"op":"subscribe","user_id":"bob"}"#)
+        .await;
+    assert_eq!(
+        session
+            .handle_text(r#"{"op":"subscribe","user_id":"carol"}"#)
+            .await,
+        error(Some("carol"), "At most 2 subscriptions per socket")
+    );
+
+    assert_eq!(
+        session.handle_text(r#"{"op":"ping"}"#).await,
+        ServerMessage::Pong
+    );
+}
+
+#[tokio::test]
+async fn test_engine_errors_end_the_subscription() {
+    let (mut session, upstream, mut updates) = session(Feed::Depth);
+
+    assert_eq!(
+        session
+            .handle_text(r#"{"op":"subscribe","market_id":"UNKNOWN"}"#)
+            .await,
+        error(Some("UNKNOWN"), "Market not found")
+    );
+
+    session
+        .handle_text(r#"{"op":"subscribe","market_id":"BTC-USDT"}"#)
+        .await;
+    upstream
+        .sender(&depth("BTC-USDT"))
+        .send(Err(Status::data_loss("Missed 3 updates, subscribe again")))
+        .unwrap();
+    assert_eq!(
+        next_update(&mut updates).await,
+        error(Some("BTC-USDT"), "Missed 3 updates, subscribe again")
+    );
+
+    // The key is free again for the resubscription the error asks for
+    let reply = session
+        .handle_text(r#"{"op":"subscribe","market_id":"BTC-USDT"}"#)
+        .await;
+    assert!(matches!(reply, ServerMessage::Subscribed { .. }));
+}
+
+#[tokio::test]
+async fn test_ended_engine_stream_unsubscribes() {
+    let (mut session, upstream, mut updates) = session(Feed::Trades);
+
+    session
+        .handle_text(r#"{"op":"subscribe","market_id":"BTC-USDT"}"#)
+        .await;
+    let topic = Topic::Trades {
+        market_id: "BTC-USDT".to_string(),
+        since_sequence: None,
+    };
+    let sender = upstream.sender(&topic);
+    sender
+        .send(Ok(ServerMessage::Trade(Box::new(TradeUpdate {
+            sequence: 1,
+            trade: None,
+        }))))
+        .unwrap();
+    drop(sender);
+    upstream.close(&topic);
+
+    assert!(matches!(
+        next_update(&mut updates).await,
+        ServerMessage::Trade(_)
+    ));
+    assert_eq!(
+        next_update(&mut updates).await,
+        ServerMessage::Unsubscribed {
+            feed: Feed::Trades,
+            key: "BTC-USDT".to_string()
+        }
+    );
+}
@@ -0,0 +1,106 @@
+use common::auth::API_KEY_HEADER;
+use futures::future::Future;
+use futures::stream::BoxStream;
+use futures::{StreamExt, TryStreamExt};
+use tonic::metadata::{Ascii, MetadataValue};
+use tonic::service::interceptor::InterceptedService;
+use tonic::service::Interceptor;
+use tonic::transport::Channel;
+use tonic::{Request, Status};
+
+use crate::protocol::{ServerMessage, Topic};
+use crate::spot::spot_service_client::SpotServiceClient;
+use crate::spot::{SubscribeOrderBookRequest, SubscribeTradesRequest, SubscribeUserEventsRequest};
+
+pub type UpdateStream = BoxStream<'static, Result<ServerMessage, Status>>;
+
+/// Where subscriptions get their updates, the engine outside of tests. A user's events are
+/// asked for with `api_key`, the key of the client the socket belongs to, so the engine checks
+/// it is bound to that user.
+pub trait Upstream: Clone + Send + Sync + 'static {
+    fn subscribe(
+        &self,
+        topic: Topic,
+        api_key: Option<String>,
+    ) -> impl Future<Output = Result<UpdateStream, Status>> + Send;
+}
+
+/// Sends the gateway's API key with every call to the engine that does not carry a client's,
+/// when it has one
+#[derive(Debug, Clone, Default)]
+pub struct EngineApiKey(Option<MetadataValue<Ascii>>);
+
+impl EngineApiKey {
+    pub fn new(key: Option<String>) -> anyhow::Result<Self> {
+        let key = key
+            .map(|key| key.parse())
+            .transpose()
+            .map_err(|_| anyhow::anyhow!("The engine API key is not a valid header value"))?;
+        Ok(Self(key))
+    }
+}
+
+impl Interceptor for EngineApiKey {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        if let Some(key) = &self.0 {
+            if !request.metadata().contains_key(API_KEY_HEADER) {
+                request.metadata_mut().insert(API_KEY_HEADER, key.clone());
+            }
+        }
+        Ok(request)
+    }
+}
+
+pub type EngineClient = SpotServiceClient<InterceptedService<Channel, EngineApiKey>>;
+
+impl Upstream for EngineClient {
+    async fn subscribe(
+        &self,
+        topic: Topic,
+        api_key: Option<String>,
+    ) -> Result<UpdateStream, Status> {
+        let mut client = self.clone();
+        let updates = match topic {
+            Topic::Depth { market_id } => client
+                .subscribe_order_book(SubscribeOrderBookRequest { market_id })
+                .await?
+                .into_inner()
+                .map_ok(|update| ServerMessage::Depth(Box::new(update)))
+                .boxed(),
+            Topic::Trades {
+                market_id,
+                since_sequence,
+            } => client
+                .subscribe_trades(SubscribeTradesRequest {
+                    market_id,
+                    since_sequence,
+                })
+                .await?
+                .into_inner()
+                .map_ok(|update| ServerMessage::Trade(Box::new(update)))
+                .boxed(),
+            Topic::User { user_id } => client
+                .subscribe_user_events(client_request(
+                    SubscribeUserEventsRequest { user_id },
+                    api_key,
+                )?)
+                .await?
+                .into_inner()
+                .map_ok(|event| ServerMessage::UserEvent(Box::new(event)))
+                .boxed(),
+        };
+        Ok(updates)
+    }
+}
+
+/// A request made on behalf of the client holding `api_key`, refused without one
+#[allow(clippy::result_large_err)]
+fn client_request<T>(message: T, api_key: Option<String>) -> Result<Request<T>, Status> {
+    let api_key = api_key.ok_or_else(|| Status::unauthenticated("API key required"))?;
+    let api_key: MetadataValue<Ascii> = api_key
+        .parse()
+        .map_err(|_| Status::unauthenticated("Invalid API key"))?;
+    let mut request = Request::new(message);
+    request.metadata_mut().insert(API_KEY_HEADER, api_key);
+    Ok(request)
+}
@@ -1,10 +1,19 @@
-use common::db::pagination::Pagination;
-use database::filters::{OrderFilter, TradeFilter};
-use database::models::models::{FeeTreasury, Market, MarketStat, Order, Trade, Wallet};
+use anyhow::{anyhow, Result};
+use bigdecimal::BigDecimal;
+use common::db::pagination::{Cursor, Pagination};
+use common::utils::format_amount;
+use database::filters::{LedgerFilter, OrderFilter, TradeFilter};
+use database::models::models::{
+    FeeTreasury, Kline, LedgerEntry, LedgerEntryKind, Market, MarketStat, Order, OrderFill,
+    OrderSide, OrderStatus, OrderStatusCount, OrderType, Ticker, Trade, TradeBalanceSnapshot,
+    UserFeePaid, UserTradedVolume, Wallet,
+};
 
 use crate::spot_query::{
-    PaginationRequest, ProtoFeeTreasury, ProtoMarket, ProtoMarketStats,
-    ProtoOrder, ProtoOrderFilter, ProtoTrade, ProtoTradeFilter, ProtoWallet,
+    PaginationRequest, ProtoFeeTreasury, ProtoKline, ProtoLedgerEntry, ProtoLedgerFilter,
+    ProtoMarket, ProtoMarketStats, ProtoOrder, ProtoOrderFill, ProtoOrderFilter,
+    ProtoOrderStatusCount, ProtoTicker, ProtoTrade, ProtoTradeBalanceSnapshot, ProtoTradeFilter,
+    ProtoTradedVolume, ProtoUserFeePaid, ProtoWallet,
 };
 
 impl From<Market> for ProtoMarket {
@@ -13,13 +22,13 @@ impl From<Market> for ProtoMarket {
             id: m.id,
             base_asset: m.base_asset,
             quote_asset: m.quote_asset,
-            default_maker_fee: m.default_maker_fee.to_string(),
-            default_taker_fee: m.default_taker_fee.to_string(),
+            default_maker_fee: format_amount(&m.default_maker_fee),
+            default_taker_fee: format_amount(&m.default_taker_fee),
             create_time: m.create_time,
             update_time: m.update_time,
             status: m.status,
-            min_base_amount: m.min_base_amount.to_string(),
-            min_quote_amount: m.min_quote_amount.to_string(),
+            min_base_amount: format_amount(&m.min_base_amount),
+            min_quote_amount: format_amount(&m.min_quote_amount),
             price_precision: m.price_precision,
             amount_precision: m.amount_precision,
         }
@@ -28,29 +37,40 @@ impl From<Market> for ProtoMarket {
 
 impl From<Order> for ProtoOrder {
     fn from(o: Order) -> Self {
+        let average_fill_price = o
+            .average_fill_price()
+            .map(|price| format_amount(&price))
+            .unwrap_or_default();
         ProtoOrder {
             id: o.id,
             market_id: o.market_id,
             user_id: o.user_id,
             order_type: o.order_type,
             side: o.side,
-            price: o.price.to_string(),
-            base_amount: o.base_amount.to_string(),
-            quote_amount: o.quote_amount.to_string(),
-            maker_fee: o.maker_fee.to_string(),
-            taker_fee: o.taker_fee.to_string(),
+            price: format_amount(&o.price),
+            base_amount: format_amount(&o.base_amount),
+            quote_amount: format_amount(&o.quote_amount),
+            maker_fee: format_amount(&o.maker_fee),
+            taker_fee: format_amount(&o.taker_fee),
             create_time: o.create_time,
-            remained_base: o.remained_base.to_string(),
-            remained_quote: o.remained_quote.to_string(),
-            filled_base: o.filled_base.to_string(),
-            filled_quote: o.filled_quote.to_string(),
-            filled_fee: o.filled_fee.to_string(),
+            remained_base: format_amount(&o.remained_base),
+            remained_quote: format_amount(&o.remained_quote),
+            filled_base: format_amount(&o.filled_base),
+            filled_quote: format_amount(&o.filled_quote),
+            filled_fee: format_amount(&o.filled_fee),
             update_time: o.update_time,
             status: o.status,
             client_order_id: o.client_order_id.unwrap_or_default(),
             post_only: o.post_only.unwrap_or(false),
             time_in_force: o.time_in_force.unwrap_or_default(),
             expires_at: o.expires_at.unwrap_or(0),
+            cancel_reason: o.cancel_reason.unwrap_or_default(),
+            reject_reason: o.reject_reason.unwrap_or_default(),
+            display_amount: o
+                .display_amount
+                .map(|amount| format_amount(&amount))
+                .unwrap_or_default(),
+            average_fill_price,
         }
     }
 }
@@ -61,31 +81,43 @@ impl From<Trade> for ProtoTrade {
             id: t.id,
             timestamp: t.timestamp,
             market_id: t.market_id,
-            price: t.price.to_string(),
-            base_amount: t.base_amount.to_string(),
-            quote_amount: t.quote_amount.to_string(),
+            price: format_amount(&t.price),
+            base_amount: format_amount(&t.base_amount),
+            quote_amount: format_amount(&t.quote_amount),
             buyer_user_id: t.buyer_user_id,
             buyer_order_id: t.buyer_order_id,
-            buyer_fee: t.buyer_fee.to_string(),
+            buyer_fee: format_amount(&t.buyer_fee),
             seller_user_id: t.seller_user_id,
             seller_order_id: t.seller_order_id,
-            seller_fee: t.seller_fee.to_string(),
+            seller_fee: format_amount(&t.seller_fee),
             taker_side: t.taker_side,
             is_liquidation: t.is_liquidation.unwrap_or(false),
         }
     }
 }
 
+impl From<OrderFill> for ProtoOrderFill {
+    fn from(f: OrderFill) -> Self {
+        ProtoOrderFill {
+            trade: Some(f.trade.into()),
+            side: f.side.as_str().to_string(),
+            role: f.role.as_str().to_string(),
+            fee: format_amount(&f.fee),
+            filled_base: format_amount(&f.filled_base),
+        }
+    }
+}
+
 impl From<Wallet> for ProtoWallet {
     fn from(w: Wallet) -> Self {
         ProtoWallet {
             user_id: w.user_id,
             asset: w.asset,
-            available: w.available.to_string(),
-            locked: w.locked.to_string(),
-            reserved: w.reserved.to_string(),
-            total_deposited: w.total_deposited.to_string(),
-            total_withdrawn: w.total_withdrawn.to_string(),
+            available: format_amount(&w.available),
+            locked: format_amount(&w.locked),
+            reserved: format_amount(&w.reserved),
+            total_deposited: format_amount(&w.total_deposited),
+            total_withdrawn: format_amount(&w.total_withdrawn),
             update_time: w.update_time,
         }
     }
@@ -95,48 +127,180 @@ impl From<MarketStat> for ProtoMarketStats {
     fn from(s: MarketStat) -> Self {
         ProtoMarketStats {
             market_id: s.market_id,
-            high_24h: s.high_24h.to_string(),
-            low_24h: s.low_24h.to_string(),
-            volume_24h: s.volume_24h.to_string(),
-            price_change_24h: s.price_change_24h.to_string(),
-            last_price: s.last_price.to_string(),
+            high_24h: format_amount(&s.high_24h),
+            low_24h: format_amount(&s.low_24h),
+            volume_24h: format_amount(&s.volume_24h),
+            price_change_24h: format_amount(&s.price_change_24h),
+            last_price: format_amount(&s.last_price),
             last_update_time: s.last_update_time,
         }
     }
 }
 
+impl From<Ticker> for ProtoTicker {
+    fn from(t: Ticker) -> Self {
+        let format = |amount: Option<&BigDecimal>| amount.map(format_amount).unwrap_or_default();
+        let stats = t.stats.as_ref();
+        let quote = t.quote.as_ref();
+        ProtoTicker {
+            last_price: format(stats.map(|s| &s.last_price)),
+            best_bid: format(quote.and_then(|q| q.best_bid.as_ref())),
+            best_ask: format(quote.and_then(|q| q.best_ask.as_ref())),
+            volume_24h: format(stats.map(|s| &s.volume_24h)),
+            price_change_24h: format(stats.map(|s| &s.price_change_24h)),
+            stats_update_time: stats.map_or(0, |s| s.last_update_time),
+            quote_update_time: quote.map_or(0, |q| q.update_time),
+            market_id: t.market_id,
+        }
+    }
+}
+
+impl From<Kline> for ProtoKline {
+    fn from(k: Kline) -> Self {
+        ProtoKline {
+            market_id: k.market_id,
+            interval: k.interval,
+            open_time: k.open_time,
+            open: format_amount(&k.open),
+            high: format_amount(&k.high),
+            low: format_amount(&k.low),
+            close: format_amount(&k.close),
+            volume: format_amount(&k.volume),
+            quote_volume: format_amount(&k.quote_volume),
+            trade_count: k.trade_count,
+        }
+    }
+}
+
 impl From<FeeTreasury> for ProtoFeeTreasury {
     fn from(f: FeeTreasury) -> Self {
         ProtoFeeTreasury {
             treasury_address: f.treasury_address,
             market_id: f.market_id,
             asset: f.asset,
-            collected_amount: f.collected_amount.to_string(),
+            collected_amount: format_amount(&f.collected_amount),
             last_update_time: f.last_update_time,
+            total_withdrawn: format_amount(&f.total_withdrawn),
         }
     }
 }
 
-impl From<PaginationRequest> for Pagination {
-    fn from(p: PaginationRequest) -> Self {
-        Pagination {
-            limit: Some(p.limit),
-            offset: Some(p.offset),
-            order_by: Some(p.order_by.to_string()),
-            order_direction: Some(p.order_direction.to_string()),
+impl From<TradeBalanceSnapshot> for ProtoTradeBalanceSnapshot {
+    fn from(s: TradeBalanceSnapshot) -> Self {
+        ProtoTradeBalanceSnapshot {
+            user_id: s.user_id,
+            asset: s.asset,
+            available_before: format_amount(&s.available_before),
+            locked_before: format_amount(&s.locked_before),
+            available_after: format_amount(&s.available_after),
+            locked_after: format_amount(&s.locked_after),
         }
     }
 }
 
-impl From<ProtoOrderFilter> for OrderFilter {
-    fn from(f: ProtoOrderFilter) -> Self {
-        OrderFilter::new()
+impl From<LedgerEntry> for ProtoLedgerEntry {
+    fn from(e: LedgerEntry) -> Self {
+        ProtoLedgerEntry {
+            id: e.id,
+            transaction_id: e.transaction_id,
+            kind: e.kind,
+            owner_id: e.owner_id,
+            account: e.account,
+            asset: e.asset,
+            debit: format_amount(&e.debit),
+            credit: format_amount(&e.credit),
+            reference_id: e.reference_id.unwrap_or_default(),
+            create_time: e.create_time,
+        }
+    }
+}
+
+impl From<UserTradedVolume> for ProtoTradedVolume {
+    fn from(v: UserTradedVolume) -> Self {
+        ProtoTradedVolume {
+            asset: v.asset,
+            volume: format_amount(&v.volume),
+        }
+    }
+}
+
+impl From<UserFeePaid> for ProtoUserFeePaid {
+    fn from(f: UserFeePaid) -> Self {
+        ProtoUserFeePaid {
+            asset: f.asset,
+            amount: format_amount(&f.amount),
+        }
+    }
+}
+
+impl From<OrderStatusCount> for ProtoOrderStatusCount {
+    fn from(c: OrderStatusCount) -> Self {
+        ProtoOrderStatusCount {
+            status: c.status,
+            order_count: c.order_count,
+        }
+    }
+}
+
+impl TryFrom<PaginationRequest> for Pagination {
+    type Error = anyhow::Error;
+
+    fn try_from(p: PaginationRequest) -> Result<Self> {
+        let cursor = Some(p.cursor)
+            .filter(|cursor| !cursor.is_empty())
+            .map(|cursor| Cursor::decode(&cursor).map_err(|e| anyhow!(e)))
+            .transpose()?;
+        Ok(Pagination {
+            // An absent pagination decodes to a zero limit, which means the default page size
+            limit: (p.limit > 0).then_some(p.limit),
+            offset: Some(p.offset),
+            order_by: Some(p.order_by).filter(|field| !field.is_empty()),
+            order_direction: Some(p.order_direction).filter(|direction| !direction.is_empty()),
+            cursor,
+        })
+    }
+}
+
+/// Checks an enum-like filter value and returns its stored spelling, so a typo is reported
+/// instead of filtering on a value no row can match.
+fn canonical_filter_value<T>(
+    value: Option<String>,
+    parse: impl Fn(&str) -> Result<T, String>,
+    as_str: impl Fn(&T) -> &'static str,
+) -> Result<Option<String>> {
+    value
+        .map(|value| {
+            parse(&value)
+                .map(|parsed| as_str(&parsed).to_string())
+                .map_err(|e| anyhow!(e))
+        })
+        .transpose()
+}
+
+impl TryFrom<ProtoOrderFilter> for OrderFilter {
+    type Error = anyhow::Error;
+
+    fn try_from(f: ProtoOrderFilter) -> Result<Self> {
+        Ok(OrderFilter::new()
             .user_id(f.user_id)
             .market_id(f.market_id)
             .order_id(f.order_id)
-            .side(f.side)
-            .status(f.status)
-            .order_type(f.order_type)
+            .side(canonical_filter_value(
+                f.side,
+                OrderSide::from_str,
+                OrderSide::as_str,
+            )?)
+            .status(canonical_filter_value(
+                f.status,
+                OrderStatus::from_str,
+                OrderStatus::as_str,
+            )?)
+            .order_type(canonical_filter_value(
+                f.order_type,
+                OrderType::from_str,
+                OrderType::as_str,
+            )?)
+            .client_order_id(f.client_order_id))
     }
 }
 
@@ -154,3 +318,60 @@ impl From<ProtoTradeFilter> for TradeFilter {
             .end_time(f.end_time)
     }
 }
+
+impl TryFrom<ProtoLedgerFilter> for LedgerFilter {
+    type Error = anyhow::Error;
+
+    fn try_from(f: ProtoLedgerFilter) -> Result<Self> {
+        Ok(LedgerFilter::new()
+            .owner_id(f.owner_id)
+            .asset(f.asset)
+            .kind(canonical_filter_value(
+                f.kind,
+                LedgerEntryKind::from_str,
+                LedgerEntryKind::as_str,
+            )?)
+            .reference_id(f.reference_id)
+            .start_time(f.start_time)
+            .end_time(f.end_time))
+    }
+}
+
+/// Columns of a trade export, in the order `write_trade_csv` writes them
+pub const TRADE_CSV_HEADER: &str = "id,timestamp,market_id,price,base_amount,quote_amount,\
+buyer_user_id,buyer_order_id,buyer_fee,seller_user_id,seller_order_id,seller_fee,taker_side,\
+is_liquidation\n";
+
+/// Appends a trade as a CSV row, with the same values `ListTrades` returns.
+pub fn write_trade_csv(trade: Trade, out: &mut String) {
+    let t = ProtoTrade::from(trade);
+    let fields = [
+        t.id,
+        t.timestamp.to_string(),
+        t.market_id,
+        t.price,
+        t.base_amount,
+        t.quote_amount,
+        t.buyer_user_id,
+        t.buyer_order_id,
+        t.buyer_fee,
+        t.seller_user_id,
+        t.seller_order_id,
+        t.seller_fee,
+        t.taker_side,
+        t.is_liquidation.to_string(),
+    ];
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        if field.contains([',', '"', '\n', '\r']) {
+            out.push('"');
+            out.push_str(&field.replace('"', "\"\""));
+            out.push('"');
+        } else {
+            out.push_str(field);
+        }
+    }
+    out.push('\n');
+}
@@ -0,0 +1,16 @@
+use common::auth::Scope;
+
+/// Scope an API key needs to call a `SpotQueryService` method. Health checks and rate limits
+/// are open, and a method not listed here needs `admin`, bulk exports of every user's trades
+/// among them.
+pub fn method_scope(method: &str) -> Option<Scope> {
+    match method {
+        "HealthCheck" | "GetRateLimits" => None,
+        "GetMarket" | "ListMarkets" | "GetOrder" | "GetOrderByClientId" | "ListOrders"
+        | "GetUserOrderCounts" | "GetOrderFills" | "ListTrades" | "GetUserTrades"
+        | "GetUserFeesPaid" | "GetTradeDetail" | "GetWallet" | "ListWallets"
+        | "GetWalletChanges" | "ListLedgerEntries" | "GetAccountSummary" | "GetMarketStats"
+        | "GetDepthSnapshot" | "GetKlines" | "ListTickers" => Some(Scope::Read),
+        _ => Some(Scope::Admin),
+    }
+}
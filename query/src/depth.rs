@@ -0,0 +1,35 @@
+use bigdecimal::{BigDecimal, RoundingMode, Zero};
+use std::collections::BTreeMap;
+
+pub const DEFAULT_DEPTH_LEVELS: u32 = 50;
+pub const MAX_DEPTH_LEVELS: u32 = 500;
+
+/// Merges price levels of one side into buckets of `precision` decimals and returns the best
+/// `count` of them, best price first. Prices round away from the spread, bids down and asks
+/// up, so no bucket shows a better price than the orders in it, as the engine's own
+/// aggregated depth does.
+pub fn aggregate_levels(
+    levels: impl IntoIterator<Item = (BigDecimal, BigDecimal)>,
+    is_bid: bool,
+    precision: i64,
+    count: usize,
+) -> Vec<(BigDecimal, BigDecimal)> {
+    let rounding = if is_bid {
+        RoundingMode::Floor
+    } else {
+        RoundingMode::Ceiling
+    };
+    let mut buckets: BTreeMap<BigDecimal, BigDecimal> = BTreeMap::new();
+    for (price, amount) in levels {
+        *buckets
+            .entry(price.with_scale_round(precision, rounding))
+            .or_default() += amount;
+    }
+
+    let buckets = buckets.into_iter().filter(|(_, amount)| !amount.is_zero());
+    if is_bid {
+        buckets.rev().take(count).collect()
+    } else {
+        buckets.take(count).collect()
+    }
+}
@@ -1,6 +1,11 @@
 pub mod adapter;
+pub mod auth;
+pub mod config;
+pub mod depth;
+pub mod rate_limit;
 pub mod server;
 pub mod service;
+pub mod tests;
 pub mod spot_query {
     tonic::include_proto!("spot_query");
 }
@@ -1,6 +1,28 @@
+use common::config::ConfigOptions;
+use spot_query::config::QueryConfig;
 use spot_query::server::start_server;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "spot-query")]
+struct Options {
+    #[structopt(flatten)]
+    config: ConfigOptions,
+}
 
 #[tokio::main]
 async fn main() {
-    start_server("[::]:50021".to_string()).await.unwrap();
+    if let Err(e) = Options::from_args().config.load() {
+        eprintln!("{:#}", e);
+        std::process::exit(1);
+    }
+    common::logging::init_tracing();
+    let config = match QueryConfig::load() {
+        Ok(config) => config,
+        Err(e) => {
+            tracing::error!("Invalid configuration: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+    start_server(config).await.unwrap();
 }
@@ -0,0 +1,10 @@
+use common::rate_limit::RateClass;
+
+/// Rate limit a `SpotQueryService` method counts against: every method is a query, except
+/// health, rate limits and maintenance, which are never limited.
+pub fn method_class(method: &str) -> Option<RateClass> {
+    match method {
+        "HealthCheck" | "GetRateLimits" | "SetMaintenanceMode" => None,
+        _ => Some(RateClass::Query),
+    }
+}
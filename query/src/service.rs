@@ -1,29 +1,108 @@
+use crate::adapter::{write_trade_csv, TRADE_CSV_HEADER};
+use crate::depth::{aggregate_levels, DEFAULT_DEPTH_LEVELS, MAX_DEPTH_LEVELS};
 use crate::spot_query::{
-    spot_query_service_server::SpotQueryService, GetFeeTreasuryRequest, GetFeeTreasuryResponse,
-    GetMarketRequest, GetMarketResponse, GetMarketStatsRequest, GetMarketStatsResponse,
-    GetOrderRequest, GetOrderResponse, GetUserTradesRequest, GetUserTradesResponse,
-    GetWalletRequest, GetWalletResponse, ListMarketsRequest, ListMarketsResponse,
-    ListOrdersRequest, ListOrdersResponse, ListTradesRequest, ListTradesResponse,
-    ListWalletsRequest, ListWalletsResponse, PaginationResponse,
+    spot_query_service_server::SpotQueryService, ExportTradesChunk, ExportTradesRequest,
+    GetAccountSummaryRequest, GetAccountSummaryResponse, GetDepthSnapshotRequest,
+    GetDepthSnapshotResponse, GetFeeTreasuryRequest, GetFeeTreasuryResponse, GetKlinesRequest,
+    GetKlinesResponse, GetMarketRequest, GetMarketResponse, GetMarketStatsRequest,
+    GetMarketStatsResponse, GetOrderByClientIdRequest, GetOrderFillsRequest, GetOrderFillsResponse,
+    GetOrderRequest, GetOrderResponse, GetRateLimitsRequest, GetRateLimitsResponse,
+    GetTradeDetailRequest, GetTradeDetailResponse, GetUserFeesPaidRequest, GetUserFeesPaidResponse,
+    GetUserOrderCountsRequest, GetUserOrderCountsResponse, GetUserTradesRequest,
+    GetUserTradesResponse, GetWalletChangesRequest, GetWalletChangesResponse, GetWalletRequest,
+    GetWalletResponse, HealthCheckRequest, HealthCheckResponse, ListFeeTreasuriesRequest,
+    ListFeeTreasuriesResponse, ListLedgerEntriesRequest, ListLedgerEntriesResponse,
+    ListMarketsRequest, ListMarketsResponse, ListOrdersRequest, ListOrdersResponse,
+    ListTickersRequest, ListTickersResponse, ListTradesRequest, ListTradesResponse,
+    ListWalletsRequest, ListWalletsResponse, PaginationResponse, ProtoDepthLevel,
+    SetMaintenanceModeRequest, SetMaintenanceModeResponse,
 };
 use anyhow::Result;
-use common::db::pagination::Pagination;
+use bigdecimal::BigDecimal;
+use common::auth::{authorize_principal, authorize_user, Principal};
+use common::db::pagination::{Cursor, Pagination};
+use common::error::{internal_status, BitradeError};
+use common::maintenance::MaintenanceMode;
+use common::rate_limit::RateLimits;
+use common::utils::{format_amount, get_utc_now_millis, normalize_user_id};
+#[cfg(feature = "redis-cache")]
+use database::cache::redis_cache::RedisMarketCache;
+use database::models::models::{KlineInterval, OrderSide, OrderStatus};
 use database::{
-    filters::{OrderFilter, TradeFilter, WalletFilter},
+    filters::{LedgerFilter, OrderFilter, TradeFilter, WalletFilter},
     provider::{
-        FeeTreasuryDatabaseReader, MarketDatabaseReader, MarketStatDatabaseReader,
-        OrderDatabaseReader, TradeDatabaseReader, WalletDatabaseReader,
+        FeeTreasuryDatabaseReader, KlineDatabaseReader, LedgerDatabaseReader, MarketDatabaseReader,
+        MarketStatDatabaseReader, OrderDatabaseReader, TradeDatabaseReader, WalletDatabaseReader,
     },
+    repository::PaginationError,
 };
+use futures::{stream, Stream};
+use std::pin::Pin;
 use tonic::{Request, Response, Status};
 
+pub const DEFAULT_KLINES_LIMIT: u32 = 500;
+pub const MAX_KLINES_LIMIT: u32 = 1000;
+pub const DEFAULT_EXPORT_CHUNK_ROWS: u32 = 1000;
+pub const MAX_EXPORT_CHUNK_ROWS: u32 = 10_000;
+/// Window of the traded volume in an account summary, in seconds like trade timestamps
+pub const SUMMARY_VOLUME_WINDOW_SECS: i64 = 24 * 60 * 60;
+
 pub struct SpotQueryServiceImp<R> {
     pub repository: R,
+    pub maintenance: MaintenanceMode,
+    /// Limits the server's rate limiter enforces, returned by `GetRateLimits`
+    pub rate_limits: RateLimits,
+    /// Market data the engine mirrors, read ahead of the database
+    #[cfg(feature = "redis-cache")]
+    pub cache: Option<RedisMarketCache>,
 }
 
 impl<R> SpotQueryServiceImp<R> {
     pub fn new(repository: R) -> Self {
-        Self { repository }
+        Self {
+            repository,
+            maintenance: MaintenanceMode::default(),
+            rate_limits: RateLimits::default(),
+            #[cfg(feature = "redis-cache")]
+            cache: None,
+        }
+    }
+
+    pub fn with_maintenance(mut self, maintenance: MaintenanceMode) -> Self {
+        self.maintenance = maintenance;
+        self
+    }
+
+    pub fn with_rate_limits(mut self, rate_limits: RateLimits) -> Self {
+        self.rate_limits = rate_limits;
+        self
+    }
+
+    #[cfg(feature = "redis-cache")]
+    pub fn with_cache(mut self, cache: RedisMarketCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+}
+
+/// Status for a failed listing, telling a sort or cursor the listing cannot page by apart.
+fn listing_status(e: anyhow::Error) -> Status {
+    match e.downcast_ref::<PaginationError>() {
+        Some(_) => Status::invalid_argument(e.to_string()),
+        None => internal_status(e),
+    }
+}
+
+/// Checks `principal` may act for the buyer or the seller of a trade
+#[allow(clippy::result_large_err)]
+fn authorize_trade_party(
+    principal: Option<&Principal>,
+    buyer_user_id: &str,
+    seller_user_id: &str,
+) -> Result<(), Status> {
+    match authorize_principal(principal, buyer_user_id) {
+        Ok(()) => Ok(()),
+        Err(_) => authorize_principal(principal, seller_user_id),
     }
 }
 
@@ -35,21 +114,29 @@ where
         + TradeDatabaseReader
         + WalletDatabaseReader
         + MarketStatDatabaseReader
+        + KlineDatabaseReader
+        + LedgerDatabaseReader
         + FeeTreasuryDatabaseReader
+        + Clone
         + Send
         + Sync
         + 'static,
 {
+    type ExportTradesStream =
+        Pin<Box<dyn Stream<Item = Result<ExportTradesChunk, Status>> + Send + 'static>>;
+
     async fn get_market(
         &self,
         request: Request<GetMarketRequest>,
     ) -> Result<Response<GetMarketResponse>, Status> {
+        self.maintenance.check()?;
+
         let market_id = &request.into_inner().market_id;
         let market = self
             .repository
             .get_market(market_id)
-            .map_err(|e| Status::internal(e.to_string()))?
-            .ok_or_else(|| Status::not_found("Market not found"))?;
+            .map_err(internal_status)?
+            .ok_or_else(|| BitradeError::MarketNotFound(market_id.to_string()))?;
 
         Ok(Response::new(GetMarketResponse {
             market: Some(market.into()),
@@ -60,10 +147,9 @@ where
         &self,
         _request: Request<ListMarketsRequest>,
     ) -> Result<Response<ListMarketsResponse>, Status> {
-        let markets = self
-            .repository
-            .list_markets()
-            .map_err(|e| Status::internal(e.to_string()))?;
+        self.maintenance.check()?;
+
+        let markets = self.repository.list_markets().map_err(internal_status)?;
 
         Ok(Response::new(ListMarketsResponse {
             markets: markets.into_iter().map(|m| m.into()).collect(),
@@ -74,12 +160,37 @@ where
         &self,
         request: Request<GetOrderRequest>,
     ) -> Result<Response<GetOrderResponse>, Status> {
+        self.maintenance.check()?;
+
+        let principal = request.extensions().get::<Principal>().cloned();
         let order_id = &request.into_inner().order_id;
         let order = self
             .repository
             .get_order(order_id)
-            .map_err(|e| Status::internal(e.to_string()))?
-            .ok_or_else(|| Status::not_found("Order not found"))?;
+            .map_err(internal_status)?
+            .ok_or_else(|| BitradeError::OrderNotFound(order_id.to_string()))?;
+        authorize_principal(principal.as_ref(), &order.user_id)?;
+
+        Ok(Response::new(GetOrderResponse {
+            order: Some(order.into()),
+        }))
+    }
+
+    async fn get_order_by_client_id(
+        &self,
+        request: Request<GetOrderByClientIdRequest>,
+    ) -> Result<Response<GetOrderResponse>, Status> {
+        self.maintenance.check()?;
+        authorize_user(&request, &request.get_ref().user_id)?;
+
+        let req = request.into_inner();
+        let user_id =
+            normalize_user_id(&req.user_id).map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let order = self
+            .repository
+            .get_order_by_client_id(&user_id, &req.client_order_id)
+            .map_err(internal_status)?
+            .ok_or_else(|| BitradeError::OrderNotFound(req.client_order_id.clone()))?;
 
         Ok(Response::new(GetOrderResponse {
             order: Some(order.into()),
@@ -90,14 +201,24 @@ where
         &self,
         request: Request<ListOrdersRequest>,
     ) -> Result<Response<ListOrdersResponse>, Status> {
+        self.maintenance.check()?;
+
+        let principal = request.extensions().get::<Principal>().cloned();
         let req = request.into_inner();
-        let filter = OrderFilter::from(req.filter.unwrap());
-        let pagination = Pagination::from(req.pagination.unwrap());
+        let filter = OrderFilter::try_from(req.filter.unwrap_or_default())
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        // A key bound to a user lists that user's orders, and has to say so
+        authorize_principal(
+            principal.as_ref(),
+            filter.user_id.as_deref().unwrap_or_default(),
+        )?;
+        let pagination = Pagination::try_from(req.pagination.unwrap_or_default())
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
 
         let paginated = self
             .repository
             .list_orders(filter, Some(pagination))
-            .map_err(|e| Status::internal(e.to_string()))?;
+            .map_err(listing_status)?;
 
         Ok(Response::new(ListOrdersResponse {
             orders: paginated.items.into_iter().map(|o| o.into()).collect(),
@@ -105,22 +226,59 @@ where
                 total_count: paginated.total_count,
                 has_more: paginated.has_more,
                 next_offset: paginated.next_offset.unwrap_or(0),
+                next_cursor: paginated
+                    .next_cursor
+                    .map(|cursor| cursor.encode())
+                    .unwrap_or_default(),
             }),
         }))
     }
 
+    async fn get_user_order_counts(
+        &self,
+        request: Request<GetUserOrderCountsRequest>,
+    ) -> Result<Response<GetUserOrderCountsResponse>, Status> {
+        self.maintenance.check()?;
+        authorize_user(&request, &request.get_ref().user_id)?;
+
+        let req = request.into_inner();
+        let user_id =
+            normalize_user_id(&req.user_id).map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let counts = self
+            .repository
+            .get_user_order_counts(
+                &user_id,
+                Some(req.market_id.as_str()).filter(|m| !m.is_empty()),
+            )
+            .map_err(internal_status)?;
+
+        Ok(Response::new(GetUserOrderCountsResponse {
+            counts: counts.into_iter().map(|c| c.into()).collect(),
+        }))
+    }
+
     async fn list_trades(
         &self,
         request: Request<ListTradesRequest>,
     ) -> Result<Response<ListTradesResponse>, Status> {
+        self.maintenance.check()?;
+
+        let principal = request.extensions().get::<Principal>().cloned();
         let req = request.into_inner();
         let filter = TradeFilter::from(req.filter.unwrap_or_default());
-        let pagination = Pagination::from(req.pagination.unwrap_or_default());
+        // A key bound to a user lists the trades that user was a party to, and has to say so
+        authorize_trade_party(
+            principal.as_ref(),
+            filter.buyer_user_id.as_deref().unwrap_or_default(),
+            filter.seller_user_id.as_deref().unwrap_or_default(),
+        )?;
+        let pagination = Pagination::try_from(req.pagination.unwrap_or_default())
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
 
         let paginated = self
             .repository
             .list_trades(filter, Some(pagination))
-            .map_err(|e| Status::internal(e.to_string()))?;
+            .map_err(listing_status)?;
 
         Ok(Response::new(ListTradesResponse {
             trades: paginated.items.into_iter().map(|t| t.into()).collect(),
@@ -128,49 +286,148 @@ where
                 total_count: paginated.total_count,
                 has_more: paginated.has_more,
                 next_offset: paginated.next_offset.unwrap_or(0),
+                next_cursor: paginated
+                    .next_cursor
+                    .map(|cursor| cursor.encode())
+                    .unwrap_or_default(),
             }),
         }))
     }
 
+    async fn export_trades(
+        &self,
+        request: Request<ExportTradesRequest>,
+    ) -> Result<Response<Self::ExportTradesStream>, Status> {
+        self.maintenance.check()?;
+
+        let req = request.into_inner();
+        match req.format.to_lowercase().as_str() {
+            "" | "csv" => {}
+            "parquet" => return Err(Status::unimplemented("Parquet exports are not supported")),
+            other => {
+                return Err(Status::invalid_argument(format!(
+                    "Unknown export format {}",
+                    other
+                )))
+            }
+        }
+        let chunk_rows = match req.chunk_rows {
+            0 => DEFAULT_EXPORT_CHUNK_ROWS,
+            rows => rows.min(MAX_EXPORT_CHUNK_ROWS),
+        };
+        let filter = TradeFilter::from(req.filter.unwrap_or_default());
+
+        // Each chunk is one page after the last trade sent, so only a chunk is ever in memory
+        let repository = self.repository.clone();
+        let chunks = stream::unfold(Some((None::<Cursor>, true)), move |state| {
+            let repository = repository.clone();
+            let filter = filter.clone();
+            async move {
+                let (after, first) = state?;
+                let trades =
+                    match repository.export_trades(filter, after.as_ref(), i64::from(chunk_rows)) {
+                        Ok(trades) => trades,
+                        Err(e) => return Some((Err(internal_status(e)), None)),
+                    };
+                if trades.is_empty() && !first {
+                    return None;
+                }
+
+                let rows = trades.len() as u32;
+                let next = trades
+                    .last()
+                    .filter(|_| rows == chunk_rows)
+                    .map(|trade| (Some(Cursor::new(trade.timestamp, trade.id.clone())), false));
+                let mut data = String::new();
+                if first {
+                    data.push_str(TRADE_CSV_HEADER);
+                }
+                for trade in trades {
+                    write_trade_csv(trade, &mut data);
+                }
+                Some((
+                    Ok(ExportTradesChunk {
+                        data: data.into_bytes(),
+                        rows,
+                    }),
+                    next,
+                ))
+            }
+        });
+        Ok(Response::new(Box::pin(chunks)))
+    }
+
     async fn get_wallet(
         &self,
         request: Request<GetWalletRequest>,
     ) -> Result<Response<GetWalletResponse>, Status> {
+        self.maintenance.check()?;
+        authorize_user(&request, &request.get_ref().user_id)?;
+
         let req = request.into_inner();
+        let user_id =
+            normalize_user_id(&req.user_id).map_err(|e| Status::invalid_argument(e.to_string()))?;
         let wallet = self
             .repository
-            .get_wallet(&req.user_id, &req.asset)
-            .map_err(|e| Status::internal(e.to_string()))?
-            .ok_or_else(|| Status::not_found("Wallet not found"))?;
+            .get_wallet(&user_id, &req.asset)
+            .map_err(internal_status)?
+            .ok_or_else(|| BitradeError::NotFound("Wallet not found".to_string()))?;
 
         Ok(Response::new(GetWalletResponse {
             wallet: Some(wallet.into()),
         }))
     }
 
+    async fn get_wallet_changes(
+        &self,
+        request: Request<GetWalletChangesRequest>,
+    ) -> Result<Response<GetWalletChangesResponse>, Status> {
+        self.maintenance.check()?;
+        authorize_user(&request, &request.get_ref().user_id)?;
+
+        let req = request.into_inner();
+        let user_id =
+            normalize_user_id(&req.user_id).map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let wallets = self
+            .repository
+            .get_wallet_changes(&user_id, req.since_time)
+            .map_err(internal_status)?;
+
+        Ok(Response::new(GetWalletChangesResponse {
+            wallets: wallets.into_iter().map(Into::into).collect(),
+        }))
+    }
+
     async fn list_wallets(
         &self,
         request: Request<ListWalletsRequest>,
     ) -> Result<Response<ListWalletsResponse>, Status> {
+        self.maintenance.check()?;
+
+        let principal = request.extensions().get::<Principal>().cloned();
         let req = request.into_inner();
         let pagination = req.pagination.map(|p| Pagination {
-            limit: Some(p.limit as i64),
-            offset: Some(p.offset as i64),
+            limit: Some(p.limit),
+            offset: Some(p.offset),
             order_by: Some(p.order_by),
             order_direction: Some(p.order_direction),
+            cursor: None,
         });
         let filter = req.filter.unwrap_or_default();
+        authorize_principal(
+            principal.as_ref(),
+            filter.user_id.as_deref().unwrap_or_default(),
+        )?;
         let paginated_wallets = self
             .repository
             .list_wallets(
                 WalletFilter {
                     user_id: filter.user_id,
                     asset: filter.asset,
-                    ..Default::default()
                 },
                 pagination,
             )
-            .map_err(|e| Status::internal(e.to_string()))?;
+            .map_err(internal_status)?;
 
         Ok(Response::new(ListWalletsResponse {
             wallets: paginated_wallets
@@ -182,53 +439,297 @@ where
                 total_count: paginated_wallets.total_count,
                 has_more: paginated_wallets.has_more,
                 next_offset: paginated_wallets.next_offset.unwrap_or(0),
+                next_cursor: paginated_wallets
+                    .next_cursor
+                    .map(|cursor| cursor.encode())
+                    .unwrap_or_default(),
             }),
         }))
     }
 
+    async fn list_ledger_entries(
+        &self,
+        request: Request<ListLedgerEntriesRequest>,
+    ) -> Result<Response<ListLedgerEntriesResponse>, Status> {
+        self.maintenance.check()?;
+
+        let principal = request.extensions().get::<Principal>().cloned();
+        let req = request.into_inner();
+        let filter = LedgerFilter::try_from(req.filter.unwrap_or_default())
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        authorize_principal(
+            principal.as_ref(),
+            filter.owner_id.as_deref().unwrap_or_default(),
+        )?;
+        let pagination = Pagination::try_from(req.pagination.unwrap_or_default())
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let paginated = self
+            .repository
+            .list_ledger_entries(filter, Some(pagination))
+            .map_err(internal_status)?;
+
+        Ok(Response::new(ListLedgerEntriesResponse {
+            entries: paginated.items.into_iter().map(Into::into).collect(),
+            pagination: Some(PaginationResponse {
+                total_count: paginated.total_count,
+                has_more: paginated.has_more,
+                next_offset: paginated.next_offset.unwrap_or(0),
+                next_cursor: paginated
+                    .next_cursor
+                    .map(|cursor| cursor.encode())
+                    .unwrap_or_default(),
+            }),
+        }))
+    }
+
+    async fn get_account_summary(
+        &self,
+        request: Request<GetAccountSummaryRequest>,
+    ) -> Result<Response<GetAccountSummaryResponse>, Status> {
+        self.maintenance.check()?;
+        authorize_user(&request, &request.get_ref().user_id)?;
+
+        let user_id = normalize_user_id(&request.into_inner().user_id)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let wallets = self
+            .repository
+            .get_user_wallets(&user_id)
+            .map_err(internal_status)?;
+        let open_order_count = self
+            .repository
+            .get_user_order_counts(&user_id, None)
+            .map_err(internal_status)?
+            .into_iter()
+            .filter(|count| {
+                count.status == OrderStatus::Open.as_str()
+                    || count.status == OrderStatus::PartiallyFilled.as_str()
+            })
+            .map(|count| count.order_count)
+            .sum();
+        let volume_since = get_utc_now_millis() / 1000 - SUMMARY_VOLUME_WINDOW_SECS;
+        let volumes = self
+            .repository
+            .get_user_traded_volumes(&user_id, volume_since)
+            .map_err(internal_status)?;
+        let fees = self
+            .repository
+            .get_user_fees_paid(&user_id, None, None)
+            .map_err(internal_status)?;
+
+        Ok(Response::new(GetAccountSummaryResponse {
+            user_id,
+            wallets: wallets.into_iter().map(Into::into).collect(),
+            open_order_count,
+            traded_volume_24h: volumes.into_iter().map(Into::into).collect(),
+            fees_paid: fees.into_iter().map(Into::into).collect(),
+        }))
+    }
+
     async fn get_market_stats(
         &self,
         request: Request<GetMarketStatsRequest>,
     ) -> Result<Response<GetMarketStatsResponse>, Status> {
+        self.maintenance.check()?;
+
         let market_id = &request.into_inner().market_id;
+        #[cfg(feature = "redis-cache")]
+        if let Some(cache) = &self.cache {
+            // A cache that is down or does not have the market yet leaves it to the database
+            match cache.get_market_stats(market_id).await {
+                Ok(Some(stats)) => {
+                    return Ok(Response::new(GetMarketStatsResponse {
+                        stats: Some(stats.into()),
+                    }))
+                }
+                Ok(None) => {}
+                Err(e) => tracing::warn!("Failed to read market stats from cache: {:?}", e),
+            }
+        }
         let stats = self
             .repository
             .get_market_stats(market_id)
-            .map_err(|e| Status::internal(e.to_string()))?
-            .ok_or_else(|| Status::not_found("Market stats not found"))?;
+            .map_err(internal_status)?
+            .ok_or_else(|| BitradeError::NotFound("Market stats not found".to_string()))?;
 
         Ok(Response::new(GetMarketStatsResponse {
             stats: Some(stats.into()),
         }))
     }
 
+    async fn get_depth_snapshot(
+        &self,
+        request: Request<GetDepthSnapshotRequest>,
+    ) -> Result<Response<GetDepthSnapshotResponse>, Status> {
+        self.maintenance.check()?;
+
+        let req = request.into_inner();
+        let market = self
+            .repository
+            .get_market(&req.market_id)
+            .map_err(internal_status)?
+            .ok_or_else(|| BitradeError::MarketNotFound(req.market_id.clone()))?;
+        let precision = req.precision.unwrap_or(market.price_precision);
+        if !(0..=market.price_precision).contains(&precision) {
+            return Err(Status::invalid_argument(format!(
+                "precision must be between 0 and {}",
+                market.price_precision
+            )));
+        }
+        let levels = match req.levels {
+            0 => DEFAULT_DEPTH_LEVELS,
+            levels => levels.min(MAX_DEPTH_LEVELS),
+        } as usize;
+
+        // The mirrored depth is as fresh as the book, the database only as its last write
+        #[cfg(feature = "redis-cache")]
+        let cached = match &self.cache {
+            Some(cache) => match cache.get_depth(&market.id).await {
+                Ok(depth) => depth.map(|depth| (depth.bids, depth.asks)),
+                Err(e) => {
+                    tracing::warn!("Failed to read depth from cache: {:?}", e);
+                    None
+                }
+            },
+            None => None,
+        };
+        #[cfg(not(feature = "redis-cache"))]
+        let cached = None;
+        let (bids, asks) = match cached {
+            Some(sides) => sides,
+            None => self
+                .repository
+                .get_book_levels(&market.id)
+                .map_err(internal_status)?
+                .into_iter()
+                .map(|level| (level.side, (level.price, level.amount)))
+                .fold(
+                    (Vec::new(), Vec::new()),
+                    |(mut bids, mut asks), (side, level)| {
+                        if side == OrderSide::Buy.as_str() {
+                            bids.push(level);
+                        } else {
+                            asks.push(level);
+                        }
+                        (bids, asks)
+                    },
+                ),
+        };
+
+        let to_proto = |levels: Vec<(BigDecimal, BigDecimal)>| {
+            levels
+                .into_iter()
+                .map(|(price, amount)| ProtoDepthLevel {
+                    price: format_amount(&price),
+                    amount: format_amount(&amount),
+                })
+                .collect()
+        };
+        Ok(Response::new(GetDepthSnapshotResponse {
+            market_id: market.id,
+            bids: to_proto(aggregate_levels(bids, true, precision.into(), levels)),
+            asks: to_proto(aggregate_levels(asks, false, precision.into(), levels)),
+            precision,
+        }))
+    }
+
+    async fn list_tickers(
+        &self,
+        _request: Request<ListTickersRequest>,
+    ) -> Result<Response<ListTickersResponse>, Status> {
+        self.maintenance.check()?;
+
+        let tickers = self.repository.list_tickers().map_err(internal_status)?;
+
+        Ok(Response::new(ListTickersResponse {
+            tickers: tickers.into_iter().map(|t| t.into()).collect(),
+        }))
+    }
+
+    async fn get_klines(
+        &self,
+        request: Request<GetKlinesRequest>,
+    ) -> Result<Response<GetKlinesResponse>, Status> {
+        self.maintenance.check()?;
+
+        let req = request.into_inner();
+        let interval = KlineInterval::from_str(&req.interval).map_err(Status::invalid_argument)?;
+        let limit = match req.limit {
+            0 => DEFAULT_KLINES_LIMIT,
+            limit => limit.min(MAX_KLINES_LIMIT),
+        };
+        let klines = self
+            .repository
+            .list_klines(
+                &req.market_id,
+                interval,
+                (req.start_time > 0).then_some(req.start_time),
+                (req.end_time > 0).then_some(req.end_time),
+                limit as i64,
+            )
+            .map_err(internal_status)?;
+
+        Ok(Response::new(GetKlinesResponse {
+            klines: klines.into_iter().map(|k| k.into()).collect(),
+        }))
+    }
+
     async fn get_fee_treasury(
         &self,
         request: Request<GetFeeTreasuryRequest>,
     ) -> Result<Response<GetFeeTreasuryResponse>, Status> {
+        self.maintenance.check()?;
+
         let req = request.into_inner();
+        // A market keeps one treasury per asset
+        if req.asset.is_empty() {
+            return Err(Status::invalid_argument("asset is required"));
+        }
         let treasury = self
             .repository
-            .get_fee_treasury(&req.market_id)
-            .map_err(|e| Status::internal(e.to_string()))?
-            .ok_or_else(|| Status::not_found("Fee treasury not found"))?;
+            .get_fee_treasury(&req.market_id, &req.asset)
+            .map_err(internal_status)?
+            .ok_or_else(|| BitradeError::NotFound("Fee treasury not found".to_string()))?;
 
         Ok(Response::new(GetFeeTreasuryResponse {
             treasury: Some(treasury.into()),
         }))
     }
 
+    async fn list_fee_treasuries(
+        &self,
+        request: Request<ListFeeTreasuriesRequest>,
+    ) -> Result<Response<ListFeeTreasuriesResponse>, Status> {
+        self.maintenance.check()?;
+
+        let req = request.into_inner();
+        let treasuries = self
+            .repository
+            .list_fee_treasuries(Some(req.market_id.as_str()).filter(|id| !id.is_empty()))
+            .map_err(internal_status)?;
+
+        Ok(Response::new(ListFeeTreasuriesResponse {
+            treasuries: treasuries.into_iter().map(|t| t.into()).collect(),
+        }))
+    }
+
     async fn get_user_trades(
         &self,
         request: Request<GetUserTradesRequest>,
     ) -> Result<Response<GetUserTradesResponse>, Status> {
+        self.maintenance.check()?;
+        authorize_user(&request, &request.get_ref().user_id)?;
+
         let req = request.into_inner();
-        let pagination = Pagination::from(req.pagination.unwrap_or_default());
+        let user_id =
+            normalize_user_id(&req.user_id).map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let pagination = Pagination::try_from(req.pagination.unwrap_or_default())
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
 
         // Create a filter for user trades
         let filter = TradeFilter::new()
-            .buyer_user_id(Some(req.user_id.clone()))
-            .seller_user_id(Some(req.user_id.clone()))
+            .buyer_user_id(Some(user_id.clone()))
+            .seller_user_id(Some(user_id))
             .start_time(if req.start_time > 0 {
                 Some(req.start_time)
             } else {
@@ -243,7 +744,7 @@ where
         let paginated_trades = self
             .repository
             .list_trades(filter, Some(pagination))
-            .map_err(|e| Status::internal(e.to_string()))?;
+            .map_err(listing_status)?;
 
         Ok(Response::new(GetUserTradesResponse {
             trades: paginated_trades
@@ -255,7 +756,124 @@ where
                 total_count: paginated_trades.total_count,
                 has_more: paginated_trades.has_more,
                 next_offset: paginated_trades.next_offset.unwrap_or(0),
+                next_cursor: paginated_trades
+                    .next_cursor
+                    .map(|cursor| cursor.encode())
+                    .unwrap_or_default(),
             }),
         }))
     }
+
+    async fn get_user_fees_paid(
+        &self,
+        request: Request<GetUserFeesPaidRequest>,
+    ) -> Result<Response<GetUserFeesPaidResponse>, Status> {
+        self.maintenance.check()?;
+        authorize_user(&request, &request.get_ref().user_id)?;
+
+        let req = request.into_inner();
+        let user_id =
+            normalize_user_id(&req.user_id).map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let fees = self
+            .repository
+            .get_user_fees_paid(
+                &user_id,
+                (req.start_time > 0).then_some(req.start_time),
+                (req.end_time > 0).then_some(req.end_time),
+            )
+            .map_err(internal_status)?;
+
+        Ok(Response::new(GetUserFeesPaidResponse {
+            fees: fees.into_iter().map(|f| f.into()).collect(),
+        }))
+    }
+
+    async fn get_order_fills(
+        &self,
+        request: Request<GetOrderFillsRequest>,
+    ) -> Result<Response<GetOrderFillsResponse>, Status> {
+        self.maintenance.check()?;
+
+        let principal = request.extensions().get::<Principal>().cloned();
+        let order_id = &request.into_inner().order_id;
+        if let Some(principal) = principal.filter(|p| *p != Principal::Admin) {
+            let order = self
+                .repository
+                .get_order(order_id)
+                .map_err(internal_status)?
+                .ok_or_else(|| BitradeError::OrderNotFound(order_id.to_string()))?;
+            principal.authorize_user(&order.user_id)?;
+        }
+        let fills = self
+            .repository
+            .get_order_fills(order_id)
+            .map_err(internal_status)?;
+
+        Ok(Response::new(GetOrderFillsResponse {
+            fills: fills.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    async fn get_trade_detail(
+        &self,
+        request: Request<GetTradeDetailRequest>,
+    ) -> Result<Response<GetTradeDetailResponse>, Status> {
+        self.maintenance.check()?;
+
+        let principal = request.extensions().get::<Principal>().cloned();
+        let trade_id = &request.into_inner().trade_id;
+        let detail = self
+            .repository
+            .get_trade_detail(trade_id)
+            .map_err(internal_status)?
+            .ok_or_else(|| BitradeError::NotFound("Trade not found".to_string()))?;
+        authorize_trade_party(
+            principal.as_ref(),
+            &detail.trade.buyer_user_id,
+            &detail.trade.seller_user_id,
+        )?;
+
+        Ok(Response::new(GetTradeDetailResponse {
+            trade: Some(detail.trade.into()),
+            balance_snapshots: detail
+                .balance_snapshots
+                .into_iter()
+                .map(|s| s.into())
+                .collect(),
+        }))
+    }
+
+    async fn health_check(
+        &self,
+        _request: Request<HealthCheckRequest>,
+    ) -> Result<Response<HealthCheckResponse>, Status> {
+        Ok(Response::new(HealthCheckResponse {
+            serving: true,
+            maintenance: self.maintenance.is_enabled(),
+        }))
+    }
+
+    async fn get_rate_limits(
+        &self,
+        _request: Request<GetRateLimitsRequest>,
+    ) -> Result<Response<GetRateLimitsResponse>, Status> {
+        Ok(Response::new(GetRateLimitsResponse {
+            orders_per_sec: self.rate_limits.orders_per_sec,
+            cancels_per_sec: self.rate_limits.cancels_per_sec,
+            queries_per_sec: self.rate_limits.queries_per_sec,
+        }))
+    }
+
+    async fn set_maintenance_mode(
+        &self,
+        request: Request<SetMaintenanceModeRequest>,
+    ) -> Result<Response<SetMaintenanceModeResponse>, Status> {
+        let enabled = request.into_inner().enabled;
+        self.maintenance.set_enabled(enabled);
+
+        Ok(Response::new(SetMaintenanceModeResponse {
+            success: true,
+            maintenance: self.maintenance.is_enabled(),
+        }))
+    }
 }
@@ -0,0 +1,193 @@
+use bigdecimal::BigDecimal;
+use common::db::pagination::{Cursor, Pagination};
+use database::filters::{LedgerFilter, OrderFilter};
+use database::models::models::{Kline, MarketQuote, Ticker, Trade};
+use std::str::FromStr;
+
+use crate::adapter::{write_trade_csv, TRADE_CSV_HEADER};
+use crate::spot_query::{
+    PaginationRequest, ProtoKline, ProtoLedgerFilter, ProtoOrderFilter, ProtoTicker,
+};
+
+#[test]
+fn test_absent_order_filter_matches_everything() {
+    let filter = OrderFilter::try_from(ProtoOrderFilter::default()).unwrap();
+    assert_eq!(filter.user_id, None);
+    assert_eq!(filter.side, None);
+    assert_eq!(filter.status, None);
+    assert_eq!(filter.order_type, None);
+}
+
+#[test]
+fn test_order_filter_enum_values_are_normalized() {
+    let filter = OrderFilter::try_from(ProtoOrderFilter {
+        user_id: Some("user-1".to_string()),
+        side: Some("buy".to_string()),
+        status: Some("partially_filled".to_string()),
+        order_type: Some("Limit".to_string()),
+        ..Default::default()
+    })
+    .unwrap();
+    assert_eq!(filter.user_id.as_deref(), Some("user-1"));
+    assert_eq!(filter.side.as_deref(), Some("BUY"));
+    assert_eq!(filter.status.as_deref(), Some("PARTIALLY_FILLED"));
+    assert_eq!(filter.order_type.as_deref(), Some("LIMIT"));
+}
+
+#[test]
+fn test_order_filter_rejects_unknown_enum_values() {
+    let invalid = [
+        ProtoOrderFilter {
+            side: Some("LONG".to_string()),
+            ..Default::default()
+        },
+        ProtoOrderFilter {
+            status: Some("CLOSED".to_string()),
+            ..Default::default()
+        },
+        ProtoOrderFilter {
+            order_type: Some("STOP".to_string()),
+            ..Default::default()
+        },
+    ];
+    for proto in invalid {
+        let error = OrderFilter::try_from(proto).unwrap_err().to_string();
+        assert!(error.starts_with("Unknown order"), "{}", error);
+    }
+}
+
+#[test]
+fn test_ledger_filter_kind_is_normalized() {
+    let filter = LedgerFilter::try_from(ProtoLedgerFilter {
+        owner_id: Some("user-1".to_string()),
+        kind: Some("unlock".to_string()),
+        ..Default::default()
+    })
+    .unwrap();
+    assert_eq!(filter.owner_id.as_deref(), Some("user-1"));
+    assert_eq!(filter.kind.as_deref(), Some("UNLOCK"));
+
+    let error = LedgerFilter::try_from(ProtoLedgerFilter {
+        kind: Some("TRANSFER".to_string()),
+        ..Default::default()
+    })
+    .unwrap_err()
+    .to_string();
+    assert!(error.starts_with("Unknown ledger entry kind"), "{}", error);
+}
+
+#[test]
+fn test_absent_pagination_uses_the_default_limit() {
+    let pagination = Pagination::try_from(PaginationRequest::default()).unwrap();
+    assert_eq!(pagination.limit, None);
+
+    let pagination = Pagination::try_from(PaginationRequest {
+        limit: 25,
+        ..Default::default()
+    })
+    .unwrap();
+    assert_eq!(pagination.limit, Some(25));
+}
+
+#[test]
+fn test_pagination_cursor_is_decoded() {
+    let pagination = Pagination::try_from(PaginationRequest {
+        cursor: "1735689600000:3f0c2a".to_string(),
+        ..Default::default()
+    })
+    .unwrap();
+    assert_eq!(
+        pagination.cursor,
+        Some(Cursor::new(1_735_689_600_000, "3f0c2a"))
+    );
+
+    let error = Pagination::try_from(PaginationRequest {
+        cursor: "yesterday".to_string(),
+        ..Default::default()
+    })
+    .unwrap_err();
+    assert!(error.to_string().starts_with("Invalid cursor"), "{}", error);
+}
+
+#[test]
+fn test_kline_amounts_are_formatted() {
+    let decimal = |value: &str| BigDecimal::from_str(value).unwrap();
+    let kline = ProtoKline::from(Kline {
+        market_id: "BTC-USDT".to_string(),
+        interval: "1m".to_string(),
+        open_time: 1_735_689_600,
+        open: decimal("100.00000000"),
+        high: decimal("105.50000000"),
+        low: decimal("98.00000000"),
+        close: decimal("101.25000000"),
+        volume: decimal("4.00000000"),
+        quote_volume: decimal("411.50000000"),
+        trade_count: 3,
+    });
+
+    assert_eq!(
+        [
+            kline.open,
+            kline.high,
+            kline.low,
+            kline.close,
+            kline.volume,
+            kline.quote_volume
+        ],
+        ["100", "105.5", "98", "101.25", "4", "411.5"]
+    );
+    assert_eq!((kline.open_time, kline.trade_count), (1_735_689_600, 3));
+}
+
+#[test]
+fn test_ticker_leaves_missing_data_empty() {
+    let decimal = |value: &str| BigDecimal::from_str(value).unwrap();
+    let ticker = ProtoTicker::from(Ticker {
+        market_id: "BTC-USDT".to_string(),
+        stats: None,
+        quote: Some(MarketQuote {
+            market_id: "BTC-USDT".to_string(),
+            best_bid: Some(decimal("99.50000000")),
+            best_ask: None,
+            update_time: 42,
+        }),
+    });
+
+    assert_eq!(ticker.best_bid, "99.5");
+    assert_eq!(ticker.best_ask, "");
+    assert_eq!(ticker.last_price, "");
+    assert_eq!(ticker.volume_24h, "");
+    assert_eq!(
+        (ticker.stats_update_time, ticker.quote_update_time),
+        (0, 42)
+    );
+}
+
+#[test]
+fn test_trade_csv_row_matches_the_header() {
+    let trade = Trade {
+        id: "trade-1".to_string(),
+        timestamp: 1_700_000_000,
+        market_id: "BTC-USDT".to_string(),
+        price: BigDecimal::from_str("100.50").unwrap(),
+        base_amount: BigDecimal::from(2),
+        quote_amount: BigDecimal::from_str("201").unwrap(),
+        buyer_user_id: "buyer, \"vip\"".to_string(),
+        buyer_order_id: "order-1".to_string(),
+        buyer_fee: BigDecimal::from(0),
+        seller_user_id: "seller".to_string(),
+        seller_order_id: "order-2".to_string(),
+        seller_fee: BigDecimal::from(0),
+        taker_side: "BUY".to_string(),
+        is_liquidation: None,
+    };
+    let mut csv = String::new();
+    write_trade_csv(trade, &mut csv);
+
+    assert!(csv.ends_with('\n'));
+    assert!(csv.starts_with("trade-1,1700000000,BTC-USDT,100.5"));
+    // Quoted values keep their commas and double their quotes
+    assert!(csv.contains(",\"buyer, \"\"vip\"\"\",order-1,"));
+    assert!(csv.ends_with(",BUY,false\n"));
+    assert_eq!(TRADE_CSV_HEADER.matches(',').count(), 13);
+}
@@ -0,0 +1,16 @@
+use common::auth::Scope;
+
+use crate::auth::method_scope;
+
+#[test]
+fn test_query_methods_are_read_except_admin_ones() {
+    assert_eq!(method_scope("ListOrders"), Some(Scope::Read));
+    assert_eq!(method_scope("GetMarketStats"), Some(Scope::Read));
+    assert_eq!(method_scope("GetAccountSummary"), Some(Scope::Read));
+    assert_eq!(method_scope("GetOrderFills"), Some(Scope::Read));
+    assert_eq!(method_scope("GetDepthSnapshot"), Some(Scope::Read));
+    assert_eq!(method_scope("ListFeeTreasuries"), Some(Scope::Admin));
+    assert_eq!(method_scope("ExportTrades"), Some(Scope::Admin));
+    assert_eq!(method_scope("SetMaintenanceMode"), Some(Scope::Admin));
+    assert_eq!(method_scope("HealthCheck"), None);
+}
@@ -0,0 +1,42 @@
+use bigdecimal::BigDecimal;
+use std::str::FromStr;
+
+use crate::depth::aggregate_levels;
+
+fn levels(levels: &[(&str, &str)]) -> Vec<(BigDecimal, BigDecimal)> {
+    levels
+        .iter()
+        .map(|(price, amount)| {
+            (
+                BigDecimal::from_str(price).unwrap(),
+                BigDecimal::from_str(amount).unwrap(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_bids_round_down_and_asks_round_up() {
+    let book = levels(&[("10.04", "1"), ("10.06", "2"), ("10.15", "3")]);
+
+    let bids = aggregate_levels(book.clone(), true, 1, 10);
+    assert_eq!(bids, levels(&[("10.1", "3"), ("10.0", "3")]));
+
+    let asks = aggregate_levels(book, false, 1, 10);
+    assert_eq!(asks, levels(&[("10.1", "3"), ("10.2", "3")]));
+}
+
+#[test]
+fn test_only_the_best_buckets_are_kept() {
+    let book = levels(&[("1", "1"), ("2", "1"), ("3", "1"), ("4", "0")]);
+
+    assert_eq!(
+        aggregate_levels(book.clone(), true, 0, 2),
+        levels(&[("3", "1"), ("2", "1")])
+    );
+    // Empty levels are left out
+    assert_eq!(
+        aggregate_levels(book, false, 0, 2),
+        levels(&[("1", "1"), ("2", "1")])
+    );
+}
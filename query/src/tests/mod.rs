@@ -0,0 +1,8 @@
+#[cfg(test)]
+mod adapter_test;
+#[cfg(test)]
+mod auth_test;
+#[cfg(test)]
+mod depth_test;
+#[cfg(test)]
+mod rate_limit_test;
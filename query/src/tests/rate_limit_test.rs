@@ -0,0 +1,12 @@
+use common::rate_limit::RateClass;
+
+use crate::rate_limit::method_class;
+
+#[test]
+fn test_query_methods_count_as_queries() {
+    assert_eq!(method_class("ListOrders"), Some(RateClass::Query));
+    assert_eq!(method_class("GetKlines"), Some(RateClass::Query));
+    assert_eq!(method_class("HealthCheck"), None);
+    assert_eq!(method_class("GetRateLimits"), None);
+    assert_eq!(method_class("SetMaintenanceMode"), None);
+}